@@ -0,0 +1,200 @@
+//! どこで: archive canister との連携 / 何を: 送り先の設定・ackの記録・archive済み範囲の検索 / なぜ: pruneで消す履歴をarchiveへ逃がし、問い合わせをそちらへ案内するため
+
+use evm_db::chain_data::constants::{MAX_ARCHIVED_TX_INDEX, MAX_PRINCIPAL_LEN};
+use evm_db::chain_data::{ArchiveRangeV1, ArchiveStateV1, ArchivedTxKey, CallerKey, TxId};
+use evm_db::stable_state::{with_state, with_state_mut, StableState};
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ArchiveError {
+    NotAllowed(&'static str),
+    InvalidAck(&'static str),
+}
+
+/// archive canister が持っている block 範囲（両端を含む）。
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ArchiveRange {
+    pub start_block: u64,
+    pub end_block: u64,
+    pub canister: Vec<u8>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ArchiveStatus {
+    pub canister: Option<Vec<u8>>,
+    pub acked_through: Option<u64>,
+    pub acked_at: u64,
+    /// 次の ack が始まるべき block
+    pub next_block: u64,
+    pub ranges: Vec<ArchiveRange>,
+}
+
+pub fn archive_status() -> ArchiveStatus {
+    with_state(|state| {
+        let archive = *state.archive_state.get();
+        ArchiveStatus {
+            canister: archive.canister.map(|key| key.principal_bytes().to_vec()),
+            acked_through: archive.acked_through,
+            acked_at: archive.acked_at,
+            next_block: next_ack_start(state, &archive),
+            ranges: state
+                .archive_ranges
+                .iter()
+                .map(|entry| range_of(*entry.key(), entry.value()))
+                .collect(),
+        }
+    })
+}
+
+/// 送り先を切り替えても ack 済みの範囲は元の canister を指したまま残る。
+/// None にすると prune は ack を待たずに進むため、未archiveの履歴は従来どおり消える。
+pub fn set_archive_canister(canister: Option<Vec<u8>>) -> Result<ArchiveStatus, ArchiveError> {
+    let key = match canister {
+        Some(bytes) => {
+            if bytes.is_empty() || bytes.len() > MAX_PRINCIPAL_LEN {
+                return Err(ArchiveError::NotAllowed("archive.canister.invalid"));
+            }
+            Some(CallerKey::from_principal_bytes(&bytes))
+        }
+        None => None,
+    };
+    with_state_mut(|state| {
+        let mut archive = *state.archive_state.get();
+        archive.canister = key;
+        state.archive_state.set(archive);
+    });
+    Ok(archive_status())
+}
+
+/// archive canister が export_blocks で end_block まで受け取ったことを記録する。
+/// 範囲は前回の ack（無ければ prune 済みの直後）から連続していなければならない。
+pub fn ack_archived_blocks(caller: &[u8], end_block: u64) -> Result<ArchiveRange, ArchiveError> {
+    let now = crate::time::now_sec();
+    with_state_mut(|state| {
+        let mut archive = *state.archive_state.get();
+        let Some(canister) = archive.canister else {
+            return Err(ArchiveError::NotAllowed("archive.not_configured"));
+        };
+        if canister.principal_bytes() != caller {
+            return Err(ArchiveError::NotAllowed("archive.caller_not_archive"));
+        }
+        let start_block = next_ack_start(state, &archive);
+        let head = state.head.get().number;
+        if end_block > head {
+            return Err(ArchiveError::InvalidAck("archive.ack.above_head"));
+        }
+        if !verified_core::archive::archive_ack_valid(start_block, end_block, head) {
+            return Err(ArchiveError::InvalidAck("archive.ack.not_advancing"));
+        }
+        let merged_start = match state.archive_ranges.last_key_value() {
+            Some((last_start, last))
+                if last.canister == canister
+                    && last.end_block.checked_add(1) == Some(start_block) =>
+            {
+                last_start
+            }
+            _ => start_block,
+        };
+        state.archive_ranges.insert(
+            merged_start,
+            ArchiveRangeV1 {
+                end_block,
+                canister,
+            },
+        );
+        archive.acked_through = Some(end_block);
+        archive.acked_at = now;
+        state.archive_state.set(archive);
+        Ok(ArchiveRange {
+            start_block,
+            end_block,
+            canister: canister.principal_bytes().to_vec(),
+        })
+    })
+}
+
+/// prune で消える tx が archive 済み block のものなら、その block 番号を残す。
+/// get_receipt が tx_id だけから archive 先を1範囲に絞るため。
+/// 記録は直近 MAX_ARCHIVED_TX_INDEX 件までで、古い tx は archive canister の索引で引く。
+pub(crate) fn record_pruned_txs(state: &mut StableState, number: u64, tx_ids: &[TxId]) {
+    let archived = state
+        .archive_ranges
+        .range(..=number)
+        .next_back()
+        .is_some_and(|entry| entry.value().end_block >= number);
+    if !archived {
+        return;
+    }
+    for tx_id in tx_ids {
+        state.archived_tx_blocks.insert(*tx_id, number);
+        state
+            .archived_tx_order
+            .insert(ArchivedTxKey::new(number, *tx_id), 1);
+    }
+    let trim = verified_core::archive::archived_tx_trim_count(
+        state.archived_tx_order.len(),
+        MAX_ARCHIVED_TX_INDEX,
+    );
+    for _ in 0..trim {
+        let Some((key, _)) = state.archived_tx_order.pop_first() else {
+            break;
+        };
+        state.archived_tx_blocks.remove(&key.tx_id());
+    }
+}
+
+/// prune 時に記録した tx の archive 先。記録のない tx（上限で捨てた古い tx を含む）は None。
+pub fn archived_range_for_tx(tx_id: &TxId) -> Option<ArchiveRange> {
+    let number = with_state(|state| state.archived_tx_blocks.get(tx_id))?;
+    archived_range_for_block(number)
+}
+
+/// number を含む archive 済み範囲。prune 済みかどうかの判定は呼び出し側で行う。
+pub fn archived_range_for_block(number: u64) -> Option<ArchiveRange> {
+    with_state(|state| {
+        let entry = state.archive_ranges.range(..=number).next_back()?;
+        let range = entry.value();
+        (range.end_block >= number).then(|| range_of(*entry.key(), range))
+    })
+}
+
+/// [from, to] と重なる archive 済み範囲を先頭から返す。
+pub fn archived_ranges_overlapping(from: u64, to: u64) -> Vec<ArchiveRange> {
+    with_state(|state| {
+        state
+            .archive_ranges
+            .range(..=to)
+            .filter(|entry| entry.value().end_block >= from)
+            .map(|entry| range_of(*entry.key(), entry.value()))
+            .collect()
+    })
+}
+
+/// prune が消してよい最後の block。archive 設定中は ack 済みの範囲に制限する。
+pub(crate) fn prune_ceiling(state: &StableState, prune_before: u64) -> Option<u64> {
+    let archive = state.archive_state.get();
+    verified_core::archive::archive_prune_ceiling(
+        prune_before,
+        archive.canister.is_some(),
+        archive.acked_through,
+    )
+}
+
+fn next_ack_start(state: &StableState, archive: &ArchiveStateV1) -> u64 {
+    match archive.acked_through {
+        Some(acked) => acked.saturating_add(1),
+        None => state
+            .prune_state
+            .get()
+            .pruned_before()
+            .map(|pruned| pruned.saturating_add(1))
+            .unwrap_or(0),
+    }
+}
+
+fn range_of(start_block: u64, range: ArchiveRangeV1) -> ArchiveRange {
+    ArchiveRange {
+        start_block,
+        end_block: range.end_block,
+        canister: range.canister.principal_bytes().to_vec(),
+    }
+}
@@ -0,0 +1,276 @@
+//! どこで: BlobStoreのcompaction / 何を: 参照表を走査してblobを低いoffsetへ移し、末尾のFree slotを切り詰める / なぜ: prune後に散らばった空きをまとめてarenaを再利用可能にするため
+
+use crate::chain::ChainError;
+use evm_db::blob_ptr::BlobPtr;
+use evm_db::chain_data::{
+    BlobCompactionPhase, BlobCompactionStateV1, BlobRefMap, BlobRelocationJournal, TxId,
+};
+use evm_db::stable_state::{with_state, with_state_mut, StableState};
+use std::ops::Bound;
+
+/// block event から進行中の pass を進めるときの既定予算。
+pub const DEFAULT_BLOB_COMPACTION_OPS_PER_TICK: u32 = 256;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct BlobCompactionStatus {
+    pub phase: BlobCompactionPhase,
+    pub cursor_map: BlobRefMap,
+    pub did_work: bool,
+    /// 実行条件を満たさず何もしなかった理由
+    pub blocked: Option<&'static str>,
+    pub passes: u64,
+    pub relocated_blobs: u64,
+    pub relocated_bytes: u64,
+    pub trimmed_bytes: u64,
+    pub live_bytes: u64,
+    pub arena_end_bytes: u64,
+    pub reclaimable_bytes: u64,
+}
+
+pub fn blob_compaction_status() -> BlobCompactionStatus {
+    with_state(|state| status_of(state, false, None))
+}
+
+pub fn blob_compaction_active() -> bool {
+    with_state(|state| state.blob_compaction_state.get().phase != BlobCompactionPhase::Idle)
+}
+
+/// 1 tick 分だけ compaction を進める。Idle なら新しい pass を始める。
+/// 参照1件の走査・blob1件の移動・末尾slot1件の切り詰めをそれぞれ1opとして数える。
+pub fn blob_compaction_tick(max_ops: u32) -> Result<BlobCompactionStatus, ChainError> {
+    if max_ops == 0 {
+        return Err(ChainError::InvalidLimit);
+    }
+    with_state_mut(|state| {
+        if let Some(reason) = blocked_reason(state) {
+            return Ok(status_of(state, false, Some(compaction_reason(reason))));
+        }
+        let mut compaction = *state.blob_compaction_state.get();
+        let mut ops = 0u32;
+        let mut did_work = false;
+        if let Some(journal) = compaction.journal {
+            recover_relocation(state, journal);
+            compaction.journal = None;
+            state.blob_compaction_state.set(compaction);
+            ops = ops.saturating_add(1);
+            did_work = true;
+        }
+        if compaction.phase == BlobCompactionPhase::Idle {
+            compaction.phase = BlobCompactionPhase::Relocating;
+            compaction.cursor_map = BlobRefMap::Blocks;
+            compaction.cursor_key = None;
+            compaction.passes = compaction.passes.saturating_add(1);
+            did_work = true;
+        }
+        while ops < max_ops {
+            match compaction.phase {
+                BlobCompactionPhase::Idle => break,
+                BlobCompactionPhase::Relocating => {
+                    let Some((key, ptr)) =
+                        next_ref(state, compaction.cursor_map, compaction.cursor_key)
+                    else {
+                        match compaction.cursor_map.next() {
+                            Some(next) => compaction.cursor_map = next,
+                            None => compaction.phase = BlobCompactionPhase::Trimming,
+                        }
+                        compaction.cursor_key = None;
+                        continue;
+                    };
+                    ops = ops.saturating_add(1);
+                    did_work = true;
+                    compaction.cursor_key = Some(key);
+                    // 読めない参照は scrubber の領分なので、ここでは触らずに進む。
+                    let Ok(Some(dst)) = state.blob_store.relocate_lower(&ptr) else {
+                        continue;
+                    };
+                    // WAL: 参照を張り替える前に移動記録を残す。
+                    compaction.journal = Some(BlobRelocationJournal {
+                        map: compaction.cursor_map,
+                        key,
+                        src: ptr,
+                        dst,
+                    });
+                    state.blob_compaction_state.set(compaction);
+                    set_ref(state, compaction.cursor_map, key, dst);
+                    let _ = state.blob_store.release_relocated(&ptr);
+                    compaction.journal = None;
+                    compaction.relocated_blobs = compaction.relocated_blobs.saturating_add(1);
+                    compaction.relocated_bytes = compaction
+                        .relocated_bytes
+                        .saturating_add(u64::from(ptr.class()));
+                    ops = ops.saturating_add(1);
+                }
+                BlobCompactionPhase::Trimming => {
+                    let budget = max_ops.saturating_sub(ops);
+                    let (slots, bytes) = state.blob_store.trim_free_tail(budget);
+                    ops = ops.saturating_add(slots);
+                    compaction.trimmed_bytes = compaction.trimmed_bytes.saturating_add(bytes);
+                    did_work |= slots > 0;
+                    if slots < budget {
+                        compaction.phase = BlobCompactionPhase::Idle;
+                        compaction.cursor_map = BlobRefMap::Blocks;
+                        compaction.cursor_key = None;
+                    }
+                }
+            }
+        }
+        state.blob_compaction_state.set(compaction);
+        Ok(status_of(state, did_work, None))
+    })
+}
+
+fn compaction_reason(reason: MaintenanceBlock) -> &'static str {
+    match reason {
+        MaintenanceBlock::StagedBlockActive => "blob_compaction.staged_block_active",
+        MaintenanceBlock::PruneJournalPending => "blob_compaction.prune_journal_pending",
+        MaintenanceBlock::SnapshotImportActive => "blob_compaction.snapshot_import_active",
+    }
+}
+
+/// blob を触る保守処理（compaction / 再圧縮 / scrub）を止める理由。
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum MaintenanceBlock {
+    StagedBlockActive,
+    PruneJournalPending,
+    SnapshotImportActive,
+}
+
+pub(crate) fn blocked_reason(state: &StableState) -> Option<MaintenanceBlock> {
+    let staged_block_active = state.staged_block_meta.get().active;
+    let prune_journal_pending =
+        state.prune_state.get().journal_block().is_some() || !state.prune_journal.is_empty();
+    let snapshot_import_active = state.state_snapshot_import.get().is_active();
+    if verified_core::blob_compaction::compaction_tick_allowed(
+        staged_block_active,
+        prune_journal_pending,
+        snapshot_import_active,
+    ) {
+        return None;
+    }
+    if staged_block_active {
+        Some(MaintenanceBlock::StagedBlockActive)
+    } else if prune_journal_pending {
+        Some(MaintenanceBlock::PruneJournalPending)
+    } else {
+        Some(MaintenanceBlock::SnapshotImportActive)
+    }
+}
+
+/// 途中で止まった移動を完了させる。参照がどちらを指しているかで進み具合を判定する。
+pub(crate) fn recover_relocation(state: &mut StableState, journal: BlobRelocationJournal) {
+    match get_ref(state, journal.map, journal.key) {
+        Some(current) if current == journal.src => {
+            set_ref(state, journal.map, journal.key, journal.dst);
+            let _ = state.blob_store.release_relocated(&journal.src);
+        }
+        Some(current) if current == journal.dst => {
+            let _ = state.blob_store.release_relocated(&journal.src);
+        }
+        // 参照自体が消えた（pruneされた）なら、どちらのslotも持ち主がいない。
+        _ => {
+            let _ = state.blob_store.release_relocated(&journal.dst);
+            let _ = state.blob_store.release_relocated(&journal.src);
+        }
+    }
+}
+
+pub(crate) fn next_ref(
+    state: &StableState,
+    map: BlobRefMap,
+    after: Option<[u8; 32]>,
+) -> Option<([u8; 32], BlobPtr)> {
+    match map {
+        BlobRefMap::Blocks => {
+            let lower = match after {
+                Some(key) => Bound::Excluded(block_number_of(key)),
+                None => Bound::Unbounded,
+            };
+            state
+                .blocks
+                .range((lower, Bound::Unbounded))
+                .next()
+                .map(|entry| (block_key(*entry.key()), entry.value()))
+        }
+        BlobRefMap::Receipts | BlobRefMap::TxIndex | BlobRefMap::InternalTraces => {
+            let lower = match after {
+                Some(key) => Bound::Excluded(TxId(key)),
+                None => Bound::Unbounded,
+            };
+            let table = match map {
+                BlobRefMap::Receipts => &state.receipts,
+                BlobRefMap::TxIndex => &state.tx_index,
+                _ => &state.internal_traces,
+            };
+            table
+                .range((lower, Bound::Unbounded))
+                .next()
+                .map(|entry| (entry.key().0, entry.value()))
+        }
+    }
+}
+
+fn get_ref(state: &StableState, map: BlobRefMap, key: [u8; 32]) -> Option<BlobPtr> {
+    match map {
+        BlobRefMap::Blocks => state.blocks.get(&block_number_of(key)),
+        BlobRefMap::Receipts => state.receipts.get(&TxId(key)),
+        BlobRefMap::TxIndex => state.tx_index.get(&TxId(key)),
+        BlobRefMap::InternalTraces => state.internal_traces.get(&TxId(key)),
+    }
+}
+
+pub(crate) fn set_ref(state: &mut StableState, map: BlobRefMap, key: [u8; 32], ptr: BlobPtr) {
+    match map {
+        BlobRefMap::Blocks => {
+            state.blocks.insert(block_number_of(key), ptr);
+        }
+        BlobRefMap::Receipts => {
+            state.receipts.insert(TxId(key), ptr);
+        }
+        BlobRefMap::TxIndex => {
+            state.tx_index.insert(TxId(key), ptr);
+        }
+        BlobRefMap::InternalTraces => {
+            state.internal_traces.insert(TxId(key), ptr);
+        }
+    }
+}
+
+fn block_key(number: u64) -> [u8; 32] {
+    let mut out = [0u8; 32];
+    out[0..8].copy_from_slice(&number.to_be_bytes());
+    out
+}
+
+fn block_number_of(key: [u8; 32]) -> u64 {
+    let mut buf = [0u8; 8];
+    buf.copy_from_slice(&key[0..8]);
+    u64::from_be_bytes(buf)
+}
+
+fn status_of(
+    state: &StableState,
+    did_work: bool,
+    blocked: Option<&'static str>,
+) -> BlobCompactionStatus {
+    let compaction: BlobCompactionStateV1 = *state.blob_compaction_state.get();
+    let usage = state.blob_store.usage_stats();
+    BlobCompactionStatus {
+        phase: compaction.phase,
+        cursor_map: compaction.cursor_map,
+        did_work,
+        blocked,
+        passes: compaction.passes,
+        relocated_blobs: compaction.relocated_blobs,
+        relocated_bytes: compaction.relocated_bytes,
+        trimmed_bytes: compaction.trimmed_bytes,
+        live_bytes: usage
+            .used_class_bytes
+            .saturating_add(usage.quarantine_class_bytes),
+        arena_end_bytes: usage.arena_end_bytes,
+        reclaimable_bytes: verified_core::blob_compaction::reclaimable_bytes(
+            usage.arena_end_bytes,
+            usage.used_class_bytes,
+            usage.quarantine_class_bytes,
+        ),
+    }
+}
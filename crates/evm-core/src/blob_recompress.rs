@@ -0,0 +1,142 @@
+//! どこで: BlobStoreの再圧縮migration / 何を: 圧縮導入前に書かれたblock/receipt/traceを辞書付きLZ4で書き直す / なぜ: upgrade後も既存履歴のサイズクラスを下げ、pruneまでの保持期間を伸ばすため
+
+use crate::blob_compaction::{
+    blocked_reason, next_ref, recover_relocation, set_ref, MaintenanceBlock,
+};
+use crate::chain::ChainError;
+use evm_db::blob_codec::{encode_blob, has_envelope};
+use evm_db::chain_data::{
+    BlobRecompressPhase, BlobRecompressStateV1, BlobRefMap, BlobRelocationJournal,
+};
+use evm_db::stable_state::{with_state, with_state_mut, StableState};
+use std::borrow::Cow;
+
+/// block event から migration を進めるときの既定予算。
+pub const DEFAULT_BLOB_RECOMPRESS_OPS_PER_TICK: u32 = 64;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct BlobRecompressStatus {
+    pub phase: BlobRecompressPhase,
+    pub cursor_map: BlobRefMap,
+    pub did_work: bool,
+    /// 実行条件を満たさず何もしなかった理由
+    pub blocked: Option<&'static str>,
+    pub scanned: u64,
+    pub recompressed_blobs: u64,
+    pub saved_bytes: u64,
+}
+
+pub fn blob_recompress_status() -> BlobRecompressStatus {
+    with_state(|state| status_of(state, false, None))
+}
+
+pub fn blob_recompress_active() -> bool {
+    with_state(|state| state.blob_recompress_state.get().phase != BlobRecompressPhase::Done)
+}
+
+/// 1 tick 分だけ migration を進める。参照1件の走査と blob1件の書き直しをそれぞれ1opとして数える。
+/// tx_index は固定長で小さいため対象外。
+pub fn blob_recompress_tick(max_ops: u32) -> Result<BlobRecompressStatus, ChainError> {
+    if max_ops == 0 {
+        return Err(ChainError::InvalidLimit);
+    }
+    with_state_mut(|state| {
+        if let Some(reason) = blocked_reason(state) {
+            return Ok(status_of(state, false, Some(recompress_reason(reason))));
+        }
+        let mut recompress = *state.blob_recompress_state.get();
+        let mut ops = 0u32;
+        let mut did_work = false;
+        if let Some(journal) = recompress.journal {
+            recover_relocation(state, journal);
+            recompress.journal = None;
+            state.blob_recompress_state.set(recompress);
+            ops = ops.saturating_add(1);
+            did_work = true;
+        }
+        if recompress.phase == BlobRecompressPhase::Pending {
+            recompress.phase = BlobRecompressPhase::Running;
+            recompress.cursor_map = BlobRefMap::Blocks;
+            recompress.cursor_key = None;
+            did_work = true;
+        }
+        while ops < max_ops && recompress.phase == BlobRecompressPhase::Running {
+            let Some((key, src)) = next_ref(state, recompress.cursor_map, recompress.cursor_key)
+            else {
+                match next_target(recompress.cursor_map) {
+                    Some(next) => recompress.cursor_map = next,
+                    None => recompress.phase = BlobRecompressPhase::Done,
+                }
+                recompress.cursor_key = None;
+                continue;
+            };
+            ops = ops.saturating_add(1);
+            did_work = true;
+            recompress.cursor_key = Some(key);
+            recompress.scanned = recompress.scanned.saturating_add(1);
+            // 読めない参照は scrubber の領分なので、ここでは触らずに進む。
+            let Ok(raw) = state.blob_store.read(&src) else {
+                continue;
+            };
+            if has_envelope(&raw) {
+                continue;
+            }
+            let Cow::Owned(encoded) = encode_blob(&raw) else {
+                continue;
+            };
+            let Ok(dst) = state.blob_store.store_bytes(&encoded) else {
+                continue;
+            };
+            // WAL: 参照を張り替える前に書き直し記録を残す。
+            recompress.journal = Some(BlobRelocationJournal {
+                map: recompress.cursor_map,
+                key,
+                src,
+                dst,
+            });
+            state.blob_recompress_state.set(recompress);
+            set_ref(state, recompress.cursor_map, key, dst);
+            let _ = state.blob_store.release_relocated(&src);
+            recompress.journal = None;
+            recompress.recompressed_blobs = recompress.recompressed_blobs.saturating_add(1);
+            recompress.saved_bytes = recompress
+                .saved_bytes
+                .saturating_add(u64::from(src.class().saturating_sub(dst.class())));
+            ops = ops.saturating_add(1);
+        }
+        state.blob_recompress_state.set(recompress);
+        Ok(status_of(state, did_work, None))
+    })
+}
+
+fn next_target(map: BlobRefMap) -> Option<BlobRefMap> {
+    match map.next()? {
+        BlobRefMap::TxIndex => BlobRefMap::TxIndex.next(),
+        other => Some(other),
+    }
+}
+
+fn recompress_reason(reason: MaintenanceBlock) -> &'static str {
+    match reason {
+        MaintenanceBlock::StagedBlockActive => "blob_recompress.staged_block_active",
+        MaintenanceBlock::PruneJournalPending => "blob_recompress.prune_journal_pending",
+        MaintenanceBlock::SnapshotImportActive => "blob_recompress.snapshot_import_active",
+    }
+}
+
+fn status_of(
+    state: &StableState,
+    did_work: bool,
+    blocked: Option<&'static str>,
+) -> BlobRecompressStatus {
+    let recompress: BlobRecompressStateV1 = *state.blob_recompress_state.get();
+    BlobRecompressStatus {
+        phase: recompress.phase,
+        cursor_map: recompress.cursor_map,
+        did_work,
+        blocked,
+        scanned: recompress.scanned,
+        recompressed_blobs: recompress.recompressed_blobs,
+        saved_bytes: recompress.saved_bytes,
+    }
+}
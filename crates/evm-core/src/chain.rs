@@ -2,13 +2,17 @@
 
 use crate::base_fee::compute_next_base_fee;
 use crate::bytes::try_address_to_bytes;
+use crate::drop_record::{self, DropContext};
 use crate::hash;
 use crate::kasane_precompiles::{IcpQueryRequest, PrecompileAccess};
+use crate::optimistic_exec::{clear_pre_executed, pre_executed_context, OptimisticBlockExec};
+pub use crate::optimistic_exec::{BlockExecMode, OptimisticExecStats};
 use crate::revm_exec::{
     commit_state_diff_to_db, compute_effective_gas_price, execute_tx_on, execute_tx_on_async,
     BlockExecContext, ExecError, ExecOutcome, ExecPath, OpHaltReason, OpTransactionError,
     StateDiff,
 };
+use crate::staged_block;
 use crate::state_root::TouchedSummary;
 use crate::trie_commit;
 use crate::tx_decode::{decode_tx, encode_ic_synthetic_input, IcSyntheticTxInput};
@@ -18,12 +22,16 @@ use evm_db::chain_data::constants::{
     DROP_CODE_DECODE, DROP_CODE_EXEC, DROP_CODE_EXEC_PRECHECK, DROP_CODE_INSTRUCTION_BUDGET,
     DROP_CODE_INVALID_FEE, DROP_CODE_MISSING, DROP_CODE_REPLACED, DROP_CODE_RESULT_TOO_LARGE,
     MAX_PENDING_GLOBAL, MAX_PENDING_PER_PRINCIPAL, MAX_PENDING_PER_SENDER, MAX_TX_SIZE,
-    READY_CANDIDATE_LIMIT,
+};
+use evm_db::chain_data::ordering::{
+    READY_TIP_BUCKET_CAP, READY_TIP_BUCKET_TIP, READY_TIP_BUCKET_ZERO,
 };
 use evm_db::chain_data::{
-    BlockData, CallerKey, Head, InternalTraceSet, NativeCreditRecord, PendingFeeKey, PruneJournal,
-    PrunePolicy, ReadyKey, ReadySeqKey, ReceiptLike, SenderKey, SenderNonceKey, StoredTx,
+    BlockData, CallerKey, DropRecordStateV1, DropRecordV1, Head, InternalTraceSet,
+    NativeCreditRecord, PendingFeeKey, PruneJournal, PrunePolicy, ReadyFeeBoundaryKey, ReadyKey,
+    ReadySeqKey, ReadyTipKey, ReceiptLike, SenderKey, SenderNonceKey, StagedBlockMetaV1, StoredTx,
     StoredTxBytes, StoredTxError, TxId, TxIndexEntry, TxKind, TxLoc, TxLocKind,
+    MAX_STAGED_BLOCK_ROUNDS,
 };
 use evm_db::memory::{chain_data_memory_ids_for_estimate, memory_size_pages, WASM_PAGE_SIZE_BYTES};
 use evm_db::meta::tx_locs_v3_active;
@@ -46,8 +54,9 @@ use std::borrow::Cow;
 #[cfg(not(target_arch = "wasm32"))]
 use std::cell::Cell;
 use std::cell::RefCell;
-use std::cmp::Reverse;
-use std::collections::{BTreeMap, BTreeSet, BinaryHeap};
+use std::collections::{BTreeMap, BTreeSet};
+use verified_core::block_round::BlockRoundDecision;
+use verified_core::ready_bucket::ReadyFeeBucket;
 
 const OPS_WARN_RATE_LIMIT_SECS: u64 = 60;
 const CALLER_EVM_CACHE_CAPACITY: usize = 4096;
@@ -56,6 +65,11 @@ thread_local! {
     static STORE_FAIL_AT_OP: Cell<u64> = const { Cell::new(0) };
     static STORE_OP_COUNTER: Cell<u64> = const { Cell::new(0) };
 }
+#[cfg(test)]
+thread_local! {
+    static BLOCK_INSTRUCTION_COUNTER_FOR_TEST: Cell<u64> = const { Cell::new(0) };
+    static BLOCK_INSTRUCTION_COUNTER_STEP_FOR_TEST: Cell<u64> = const { Cell::new(0) };
+}
 thread_local! {
     static DECODE_SUPPRESS_UNTIL_BY_PRINCIPAL: RefCell<BTreeMap<Vec<u8>, u64>> = const { RefCell::new(BTreeMap::new()) };
     static CALLER_EVM_BY_PRINCIPAL: RefCell<BTreeMap<Vec<u8>, [u8; 20]>> = const { RefCell::new(BTreeMap::new()) };
@@ -68,12 +82,27 @@ fn current_instruction_counter() -> u64 {
             ic_cdk::api::PerformanceCounterType::InstructionCounter,
         );
     }
-    #[cfg(not(target_arch = "wasm32"))]
+    #[cfg(test)]
+    {
+        BLOCK_INSTRUCTION_COUNTER_FOR_TEST.with(|counter| {
+            let value = counter.get();
+            let step = BLOCK_INSTRUCTION_COUNTER_STEP_FOR_TEST.with(|step| step.get());
+            counter.set(value.saturating_add(step));
+            value
+        })
+    }
+    #[cfg(all(not(target_arch = "wasm32"), not(test)))]
     {
         0
     }
 }
 
+#[cfg(test)]
+fn configure_block_instruction_counter_for_test(start: u64, step: u64) {
+    BLOCK_INSTRUCTION_COUNTER_FOR_TEST.with(|counter| counter.set(start));
+    BLOCK_INSTRUCTION_COUNTER_STEP_FOR_TEST.with(|counter| counter.set(step));
+}
+
 fn format_tx_id_hex(tx_id: Option<TxId>) -> String {
     match tx_id {
         Some(value) => hex::encode(value.0),
@@ -171,6 +200,25 @@ fn derive_caller_evm_cached(caller_principal: &[u8]) -> Result<[u8; 20], ChainEr
     Ok(derived)
 }
 
+/// upgrade checkpoint 用。cache は再導出できるが、upgrade 直後の導出コストを避けるため引き継ぐ。
+pub fn caller_evm_cache_entries() -> Vec<(Vec<u8>, [u8; 20])> {
+    CALLER_EVM_BY_PRINCIPAL.with(|cache| {
+        cache
+            .borrow()
+            .iter()
+            .map(|(principal, address)| (principal.clone(), *address))
+            .collect()
+    })
+}
+
+pub fn restore_caller_evm_cache(entries: Vec<(Vec<u8>, [u8; 20])>) {
+    CALLER_EVM_BY_PRINCIPAL.with(|cache| {
+        let mut cache = cache.borrow_mut();
+        cache.clear();
+        cache.extend(entries.into_iter().take(CALLER_EVM_CACHE_CAPACITY));
+    });
+}
+
 fn remaining_instruction_budget(
     instruction_soft_limit: u64,
     instruction_start: u64,
@@ -204,6 +252,8 @@ pub enum ChainError {
     InvariantViolation(String),
     NoExecutableTx,
     MintOverflow,
+    /// state snapshot の export 中で、head を動かせない。
+    ProductionHeld,
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -272,6 +322,8 @@ pub struct CallObjectResult {
     pub gas_used: u64,
     pub return_data: Vec<u8>,
     pub revert_data: Option<Vec<u8>>,
+    /// status=0 のうち revert ではなく halt（out of gas / invalid opcode 等）で止まった理由
+    pub halt_reason: Option<OpHaltReason>,
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -343,6 +395,26 @@ pub struct ProduceBlockOutcome {
     pub block: BlockData,
     pub gas_used: u64,
     pub dropped: u64,
+    pub optimistic: OptimisticExecStats,
+    /// ブロック封印までに要したラウンド数（単一メッセージで封印した場合は1）
+    pub rounds: u32,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct StagedBlockProgress {
+    pub number: u64,
+    pub rounds: u32,
+    pub tx_count: u32,
+    pub gas_used: u64,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum BlockRoundOutcome {
+    /// 命令上限で止まり、実行済みTxを staged block として次ラウンドへ持ち越した。
+    Staged(StagedBlockProgress),
+    /// 楽観実行モードで候補を事前実行しただけで、ブロックは次ラウンドで組む。
+    PreExecuted(u32),
+    Sealed(Box<ProduceBlockOutcome>),
 }
 
 pub fn set_prune_policy(policy: PrunePolicy) -> Result<(), ChainError> {
@@ -354,6 +426,25 @@ pub fn set_prune_policy(policy: PrunePolicy) -> Result<(), ChainError> {
     Ok(())
 }
 
+pub fn set_block_exec_mode(mode: BlockExecMode) -> Result<(), ChainError> {
+    with_state_mut(|state| {
+        let mut chain_state = *state.chain_state.get();
+        chain_state.optimistic_exec_enabled = mode == BlockExecMode::Optimistic;
+        state.chain_state.set(chain_state);
+    });
+    Ok(())
+}
+
+pub fn get_block_exec_mode() -> BlockExecMode {
+    with_state(|state| {
+        if state.chain_state.get().optimistic_exec_enabled {
+            BlockExecMode::Optimistic
+        } else {
+            BlockExecMode::Sequential
+        }
+    })
+}
+
 pub fn set_pruning_enabled(enabled: bool) -> Result<(), ChainError> {
     with_state_mut(|state| {
         let mut config = *state.prune_config.get();
@@ -404,11 +495,28 @@ pub fn migrate_tx_locs_batch(start_key: Option<TxId>, max_items: u32) -> (Option
     })
 }
 
+/// 件数一致に加え、先頭から sample_limit 件の値が旧 map と一致するかを見る。
+pub fn verify_tx_locs_v3(sample_limit: u32) -> bool {
+    with_state(|state| {
+        if state.tx_locs.len() != state.tx_locs_v3.len() {
+            return false;
+        }
+        state
+            .tx_locs
+            .iter()
+            .take(usize::try_from(sample_limit).unwrap_or(usize::MAX))
+            .all(|entry| state.tx_locs_v3.get(entry.key()) == Some(entry.value()))
+    })
+}
+
 pub fn clear_mempool_on_upgrade() {
     with_state_mut(|state| {
         clear_stable_map(&mut state.ready_queue);
         clear_stable_map(&mut state.ready_by_seq);
         clear_stable_map(&mut state.ready_key_by_tx_id);
+        clear_stable_map(&mut state.ready_tip_index);
+        clear_stable_map(&mut state.ready_tip_key_by_tx_id);
+        clear_stable_map(&mut state.ready_fee_boundaries);
         clear_stable_map(&mut state.pending_by_sender_nonce);
         clear_stable_map(&mut state.pending_min_nonce);
         clear_stable_map(&mut state.pending_meta_by_tx_id);
@@ -416,7 +524,10 @@ pub fn clear_mempool_on_upgrade() {
         clear_stable_map(&mut state.principal_pending_count);
         clear_stable_map(&mut state.pending_fee_index);
         clear_stable_map(&mut state.pending_fee_key_by_tx_id);
+        clear_stable_map(&mut state.pending_tip_index);
+        clear_stable_map(&mut state.pending_tip_key_by_tx_id);
         clear_stable_map(&mut state.sender_expected_nonce);
+        staged_block::clear(state, false);
     });
 }
 
@@ -452,8 +563,7 @@ pub fn rebuild_pending_runtime_indexes() {
                 .principal_pending_count
                 .insert(principal, verified_core::pending::increment_count(count));
         }
-        let base_fee = state.chain_state.get().base_fee;
-        rebuild_pending_fee_index_for_base_fee(state, base_fee);
+        rebuild_pending_fee_indexes(state);
 
         let mut ready_entries = Vec::new();
         for entry in state.ready_key_by_tx_id.iter() {
@@ -550,7 +660,7 @@ pub fn verify_eth_tx_hash_index(sample_limit: u32) -> (bool, u64, u64) {
     })
 }
 
-fn tx_locs_get(state: &StableState, tx_id: &TxId) -> Option<TxLoc> {
+pub(crate) fn tx_locs_get(state: &StableState, tx_id: &TxId) -> Option<TxLoc> {
     let loc = if tx_locs_v3_active() {
         state.tx_locs_v3.get(tx_id)
     } else {
@@ -593,7 +703,7 @@ fn remove_eth_tx_hash_index_for_tx_id(state: &mut evm_db::stable_state::StableSt
         .remove(&TxId(hash::keccak256(&stored.raw)));
 }
 
-fn tx_locs_insert(state: &mut StableState, tx_id: TxId, loc: TxLoc) {
+pub(crate) fn tx_locs_insert(state: &mut StableState, tx_id: TxId, loc: TxLoc) {
     if tx_locs_v3_active() {
         state.tx_locs_v3.insert(tx_id, loc);
     } else {
@@ -878,7 +988,17 @@ pub fn submit_tx(
             base_fee,
         )?;
         if replaced.is_none() {
-            enforce_pending_caps(state, sender_key, &caller_principal, effective_gas_price)?;
+            enforce_pending_caps(
+                state,
+                sender_key,
+                &caller_principal,
+                max_fee_per_gas,
+                if is_dynamic_fee {
+                    max_priority_fee_per_gas
+                } else {
+                    0
+                },
+            )?;
         }
         let pending_key = SenderNonceKey::new(sender_key.0, tx_env.nonce);
         if state.pending_by_sender_nonce.get(&pending_key).is_some() {
@@ -900,7 +1020,17 @@ pub fn submit_tx(
         state.chain_state.set(chain_state);
         state.pending_by_sender_nonce.insert(pending_key, tx_id);
         state.pending_meta_by_tx_id.insert(tx_id, pending_key);
-        track_pending_indexes_on_insert(state, tx_id, &caller_principal, effective_gas_price);
+        track_pending_indexes_on_insert(
+            state,
+            tx_id,
+            &caller_principal,
+            max_fee_per_gas,
+            if is_dynamic_fee {
+                max_priority_fee_per_gas
+            } else {
+                0
+            },
+        );
         promote_if_next_nonce(
             state,
             sender_key,
@@ -989,7 +1119,12 @@ pub fn submit_ic_tx_input(
                 state,
                 sender_key,
                 caller_principal.as_slice(),
-                effective_gas_price,
+                max_fee_per_gas,
+                if is_dynamic_fee {
+                    max_priority_fee_per_gas
+                } else {
+                    0
+                },
             )?;
         }
         let pending_key = SenderNonceKey::new(sender_key.0, nonce);
@@ -1011,7 +1146,17 @@ pub fn submit_ic_tx_input(
         state.chain_state.set(chain_state);
         state.pending_by_sender_nonce.insert(pending_key, tx_id);
         state.pending_meta_by_tx_id.insert(tx_id, pending_key);
-        track_pending_indexes_on_insert(state, tx_id, &caller_principal, effective_gas_price);
+        track_pending_indexes_on_insert(
+            state,
+            tx_id,
+            &caller_principal,
+            max_fee_per_gas,
+            if is_dynamic_fee {
+                max_priority_fee_per_gas
+            } else {
+                0
+            },
+        );
         promote_if_next_nonce(
             state,
             sender_key,
@@ -1113,23 +1258,258 @@ pub fn expected_nonce_for_sender_view(sender: [u8; 20]) -> u64 {
 }
 
 pub fn produce_block(max_txs: usize) -> Result<ProduceBlockOutcome, ChainError> {
-    // どこで: ブロック組成前の候補デコード段 / 何を: 無効Txデコード処理数を制限 / なぜ: 署名不正スパムで命令を使い切らないため
-    const MAX_DECODE_DROPS_PER_BLOCK: usize =
-        evm_db::chain_data::DEFAULT_MAX_DECODE_DROPS_PER_BLOCK;
+    produce_block_with_mode(max_txs, get_block_exec_mode())
+}
+
+/// staged block があれば続きから組み、現ラウンドで必ず封印する。
+pub fn produce_block_with_mode(
+    max_txs: usize,
+    mode: BlockExecMode,
+) -> Result<ProduceBlockOutcome, ChainError> {
+    match build_block_round(max_txs, mode, false)? {
+        BlockRoundOutcome::Sealed(outcome) => Ok(*outcome),
+        BlockRoundOutcome::Staged(_) | BlockRoundOutcome::PreExecuted(_) => Err(
+            ChainError::InvariantViolation("block_round.unexpected_stage".to_string()),
+        ),
+    }
+}
+
+/// 1メッセージ分だけブロック組成を進める。命令上限で止まった場合は封印せずに持ち越す。
+/// 楽観実行モードでは、使える事前実行結果が無ければこのラウンドを事前実行に充てる。
+pub fn produce_block_round(max_txs: usize) -> Result<BlockRoundOutcome, ChainError> {
+    let mode = get_block_exec_mode();
+    if mode == BlockExecMode::Optimistic && needs_pre_execution() {
+        return pre_execute_block(max_txs).map(BlockRoundOutcome::PreExecuted);
+    }
+    build_block_round(max_txs, mode, true)
+}
+
+fn needs_pre_execution() -> bool {
+    let (active, parent_hash) = with_state(|state| {
+        (
+            staged_block::current_meta(state).active,
+            state.head.get().block_hash,
+        )
+    });
+    !active && pre_executed_context(parent_hash, current_evm_state_epoch()).is_none()
+}
+
+/// 次ブロックの候補を現在の head 上で事前実行し、結果を heap に置く。
+/// 封印側のラウンドが命令予算を丸ごと使えるよう、事前実行は別メッセージで行う。
+pub fn pre_execute_block(max_txs: usize) -> Result<u32, ChainError> {
     if !verified_core::block::valid_block_limit(max_txs) {
         return Err(ChainError::InvalidLimit);
     }
-    let head = with_state(|state| *state.head.get());
+    let (parent_hash, exec_ctx) = next_block_exec_context();
+    let state_epoch = current_evm_state_epoch();
+    let update_instruction_soft_limit =
+        with_state(|state| state.chain_state.get().update_instruction_soft_limit);
+    let mut tx_ids = Vec::new();
+    with_state_mut(|state| {
+        sync_ready_tip_index(state, exec_ctx.base_fee);
+        tx_ids = select_ready_candidates(state, exec_ctx.base_fee, max_txs);
+    });
+    if tx_ids.is_empty() {
+        return Err(ChainError::QueueEmpty);
+    }
+    // デコードできないTxは封印側で drop として扱うため、ここでは読み飛ばすだけにする。
+    let candidates = tx_ids
+        .into_iter()
+        .filter_map(|tx_id| decode_candidate_tx_env(tx_id).map(|tx_env| (tx_id, tx_env)));
+    let instruction_start = current_instruction_counter();
+    Ok(crate::optimistic_exec::pre_execute_block(
+        parent_hash,
+        state_epoch,
+        &exec_ctx,
+        candidates,
+        |pre_gas_used| {
+            let instruction_now = current_instruction_counter();
+            if should_stop_block_execution(
+                pre_gas_used,
+                exec_ctx.block_gas_limit,
+                update_instruction_soft_limit,
+                instruction_start,
+                instruction_now,
+            ) {
+                return None;
+            }
+            Some(remaining_instruction_budget(
+                update_instruction_soft_limit,
+                instruction_start,
+                instruction_now,
+            ))
+        },
+    ))
+}
+
+fn decode_candidate_tx_env(tx_id: TxId) -> Option<revm::context::TxEnv> {
+    let stored = StoredTx::try_from(with_state(|state| state.tx_store.get(&tx_id))?).ok()?;
+    let caller = match stored.kind {
+        TxKind::IcSynthetic => stored.caller_evm?,
+        TxKind::EthSigned => [0u8; 20],
+    };
+    decode_tx(stored.kind, Address::from(caller), &stored.raw).ok()
+}
+
+// 事前実行した tick と同じ head・状態であれば、その時に決めた timestamp を引き継ぐ。
+fn next_block_exec_context() -> ([u8; 32], BlockExecContext) {
+    let (head, base_fee, block_gas_limit) = with_state(|state| {
+        let chain_state = state.chain_state.get();
+        (
+            *state.head.get(),
+            chain_state.base_fee,
+            chain_state.block_gas_limit,
+        )
+    });
+    if let Some(exec_ctx) = pre_executed_context(head.block_hash, current_evm_state_epoch())
+        .filter(|ctx| ctx.base_fee == base_fee && ctx.block_gas_limit == block_gas_limit)
+    {
+        return (head.block_hash, exec_ctx);
+    }
     let number = verified_core::block::next_block_number(head.number);
     let timestamp =
         verified_core::block::next_block_timestamp(head.timestamp, crate::time::now_sec());
-    let parent_hash = head.block_hash;
-    let exec_ctx = with_state(|state| BlockExecContext {
-        block_number: number,
-        timestamp,
-        base_fee: state.chain_state.get().base_fee,
-        block_gas_limit: state.chain_state.get().block_gas_limit,
+    (
+        head.block_hash,
+        BlockExecContext {
+            block_number: number,
+            timestamp,
+            base_fee,
+            block_gas_limit,
+        },
+    )
+}
+
+pub fn get_staged_block_meta() -> StagedBlockMetaV1 {
+    with_state(staged_block::current_meta)
+}
+
+pub fn discard_staged_block() {
+    with_state_mut(|state| staged_block::clear(state, false));
+}
+
+pub fn get_drop_record(tx_id: &TxId) -> Option<DropRecordV1> {
+    with_state(|state| drop_record::get(state, tx_id))
+}
+
+pub fn get_drop_record_by_eth_hash(eth_tx_hash: &[u8; 32]) -> Option<DropRecordV1> {
+    with_state(|state| drop_record::get_by_eth_hash(state, eth_tx_hash))
+}
+
+pub fn get_drop_record_state() -> DropRecordStateV1 {
+    with_state(|state| *state.drop_record_state.get())
+}
+
+/// drop記録の保持期間と件数上限を変える。縮めた分は次のdrop以降に少しずつ消える。
+pub fn set_drop_record_retention(retain_secs: u64, max_records: u64) -> Result<(), ChainError> {
+    if max_records == 0 {
+        return Err(ChainError::InvalidLimit);
+    }
+    with_state_mut(|state| {
+        let mut meta = *state.drop_record_state.get();
+        meta.retain_secs = retain_secs;
+        meta.max_records = max_records;
+        state.drop_record_state.set(meta);
     });
+    Ok(())
+}
+
+struct ResumedBlock {
+    meta: StagedBlockMetaV1,
+    items: Vec<staged_block::StagedIncluded>,
+}
+
+// head・状態epoch・各Txのpending状態のどれかが変わっていれば、staged block は破棄する。
+// 破棄しても状態へは何も反映していないため、Txは ready のまま次の組成で拾い直される。
+fn load_resumable_staged_block() -> Option<ResumedBlock> {
+    with_state_mut(|state| {
+        let meta = staged_block::current_meta(state);
+        if !meta.active {
+            return None;
+        }
+        let head = *state.head.get();
+        let resumable = verified_core::block_round::staged_block_resumable(
+            head.number,
+            meta.number,
+            head.block_hash == meta.parent_hash,
+            *state.evm_state_epoch.get() == meta.evm_state_epoch,
+        );
+        let items = if resumable {
+            staged_block::load_items(state)
+        } else {
+            None
+        };
+        match items {
+            Some(items)
+                if items
+                    .iter()
+                    .all(|item| staged_tx_is_current_pending(state, item.tx_id, item.sender)) =>
+            {
+                Some(ResumedBlock { meta, items })
+            }
+            _ => {
+                staged_block::clear(state, false);
+                None
+            }
+        }
+    })
+}
+
+fn staged_tx_is_current_pending(state: &StableState, tx_id: TxId, sender: [u8; 20]) -> bool {
+    let not_dropped = !matches!(
+        tx_locs_get(state, &tx_id),
+        Some(loc) if loc.kind == TxLocKind::Dropped
+    );
+    verified_core::staging::staged_tx_is_current_pending_raw(
+        u64::from(state.ready_key_by_tx_id.contains_key(&tx_id)),
+        u64::from(state.pending_meta_by_tx_id.contains_key(&tx_id)),
+        u64::from(state.pending_current_by_sender.get(&SenderKey::new(sender)) == Some(tx_id)),
+        u64::from(state.tx_store.contains_key(&tx_id)),
+        u64::from(not_dropped),
+    )
+}
+
+fn build_block_round(
+    max_txs: usize,
+    mode: BlockExecMode,
+    allow_stage: bool,
+) -> Result<BlockRoundOutcome, ChainError> {
+    // どこで: ブロック組成前の候補デコード段 / 何を: 無効Txデコード処理数を制限 / なぜ: 署名不正スパムで命令を使い切らないため
+    const MAX_DECODE_DROPS_PER_BLOCK: usize =
+        evm_db::chain_data::DEFAULT_MAX_DECODE_DROPS_PER_BLOCK;
+    if !verified_core::block::valid_block_limit(max_txs) {
+        return Err(ChainError::InvalidLimit);
+    }
+    if with_state(|state| state.chain_state.get().state_snapshot_export_hold) {
+        return Err(ChainError::ProductionHeld);
+    }
+    let resumed = load_resumable_staged_block();
+    let (number, timestamp, parent_hash, exec_ctx, rounds_done, state_epoch) = match &resumed {
+        Some(resumed) => (
+            resumed.meta.number,
+            resumed.meta.timestamp,
+            resumed.meta.parent_hash,
+            BlockExecContext {
+                block_number: resumed.meta.number,
+                timestamp: resumed.meta.timestamp,
+                base_fee: resumed.meta.base_fee,
+                block_gas_limit: resumed.meta.block_gas_limit,
+            },
+            resumed.meta.rounds,
+            resumed.meta.evm_state_epoch,
+        ),
+        None => {
+            let (parent_hash, exec_ctx) = next_block_exec_context();
+            (
+                exec_ctx.block_number,
+                exec_ctx.timestamp,
+                parent_hash,
+                exec_ctx,
+                0,
+                current_evm_state_epoch(),
+            )
+        }
+    };
     let mut included_tx_ids: Vec<TxId> = Vec::new();
     let mut dropped_total = 0u64;
     let mut dropped_by_code = [0u64; evm_db::chain_data::metrics::DROP_CODE_SLOTS];
@@ -1152,13 +1532,48 @@ pub fn produce_block(max_txs: usize) -> Result<ProduceBlockOutcome, ChainError>
     let mut staged_txs: Vec<PreparedTx> = Vec::new();
     let mut decode_drop_count = 0usize;
     let mut decode_drops_by_principal: BTreeMap<Vec<u8>, u16> = BTreeMap::new();
-    with_state(|state| {
-        tx_ids = select_ready_candidates(state, state.chain_state.get().base_fee, max_txs);
-    });
-    if tx_ids.is_empty() {
+    let mut block_gas_used = 0u64;
+    let mut exec_db = CacheDB::new(crate::revm_db::RevmStableDb);
+    // 前ラウンドまでの実行結果を同じ順で exec_db へ積み直す。
+    for item in resumed.map(|resumed| resumed.items).unwrap_or_default() {
+        collect_touched_addresses(
+            &item.state_diff,
+            &mut touched_addrs,
+            &mut touched_slots,
+            &mut delta_digests,
+        );
+        exec_db.commit(item.state_diff.clone());
+        staged_state_diffs.push(item.state_diff);
+        block_gas_used =
+            verified_core::block::add_block_gas_used(block_gas_used, item.outcome.receipt.gas_used);
+        included_tx_ids.push(item.tx_id);
+        staged_included.push(StagedIncludedTx::Success {
+            tx_id: item.tx_id,
+            outcome: item.outcome,
+            sender_bytes: item.sender,
+            sender_nonce: item.nonce,
+        });
+    }
+    let resumed_included = staged_included.len();
+    let remaining_slots = max_txs.saturating_sub(resumed_included);
+    if remaining_slots > 0 {
+        with_state_mut(|state| {
+            sync_ready_tip_index(state, exec_ctx.base_fee);
+            tx_ids = select_ready_candidates(
+                state,
+                exec_ctx.base_fee,
+                remaining_slots.saturating_add(resumed_included),
+            )
+            .into_iter()
+            .filter(|tx_id| !included_tx_ids.contains(tx_id))
+            .take(remaining_slots)
+            .collect();
+        });
+    }
+    if tx_ids.is_empty() && included_tx_ids.is_empty() {
         return Err(ChainError::QueueEmpty);
     }
-    let mut block_gas_used = 0u64;
+    let candidate_count = tx_ids.len();
     let instruction_start = current_instruction_counter();
     for tx_id in tx_ids {
         if should_stop_block_execution(
@@ -1307,6 +1722,7 @@ pub fn produce_block(max_txs: usize) -> Result<ProduceBlockOutcome, ChainError>
         }
     }
 
+    let mut unprocessed_candidates = candidate_count.saturating_sub(prepared.len());
     for item in prepared {
         match item {
             PreparedItem::Drop(drop) => {
@@ -1320,9 +1736,17 @@ pub fn produce_block(max_txs: usize) -> Result<ProduceBlockOutcome, ChainError>
     fn apply_drops_only(
         drops: &[QueuedDrop],
         dropped_by_code: &[u64; evm_db::chain_data::metrics::DROP_CODE_SLOTS],
+        block_attempted: u64,
     ) {
         with_state_mut(|state| {
             for drop in drops.iter() {
+                let ctx = DropContext::capture(
+                    state,
+                    drop.tx_id,
+                    drop.sender_override,
+                    drop.nonce_override,
+                    Some(block_attempted),
+                );
                 advance_sender_after_tx(
                     state,
                     drop.tx_id,
@@ -1330,7 +1754,7 @@ pub fn produce_block(max_txs: usize) -> Result<ProduceBlockOutcome, ChainError>
                     drop.nonce_override,
                     false,
                 );
-                mark_dropped_and_purge_payload(state, drop.tx_id, drop.drop_code);
+                mark_dropped_and_purge_payload(state, drop.tx_id, drop.drop_code, ctx);
             }
             let mut metrics = *state.metrics_state.get();
             for (idx, count) in dropped_by_code.iter().enumerate() {
@@ -1342,13 +1766,25 @@ pub fn produce_block(max_txs: usize) -> Result<ProduceBlockOutcome, ChainError>
         });
     }
 
-    if staged_txs.is_empty() {
-        apply_drops_only(&staged_drops, &dropped_by_code);
+    if staged_txs.is_empty() && included_tx_ids.is_empty() {
+        apply_drops_only(&staged_drops, &dropped_by_code, number);
         return Err(ChainError::NoExecutableTx);
     }
 
-    let mut exec_db = CacheDB::new(crate::revm_db::RevmStableDb);
-    for prepared_tx in staged_txs {
+    let mut optimistic = match mode {
+        BlockExecMode::Sequential => None,
+        BlockExecMode::Optimistic => {
+            OptimisticBlockExec::attach(parent_hash, state_epoch, &exec_ctx)
+        }
+    };
+    if let Some(optimistic) = optimistic.as_mut() {
+        // 事前実行は RevmStableDb 上のため、前ラウンドまでの書き込みも衝突対象に含める。
+        for state_diff in staged_state_diffs.iter() {
+            optimistic.record_commit(state_diff);
+        }
+    }
+    unprocessed_candidates = unprocessed_candidates.saturating_add(staged_txs.len());
+    for prepared_tx in staged_txs.into_iter() {
         let instruction_now = current_instruction_counter();
         if should_stop_block_execution(
             block_gas_used,
@@ -1367,6 +1803,7 @@ pub fn produce_block(max_txs: usize) -> Result<ProduceBlockOutcome, ChainError>
         if remaining_instruction_budget == Some(0) {
             break;
         }
+        unprocessed_candidates = unprocessed_candidates.saturating_sub(1);
         if !verified_core::block::tx_fits_block_gas(
             block_gas_used,
             exec_ctx.block_gas_limit,
@@ -1389,17 +1826,23 @@ pub fn produce_block(max_txs: usize) -> Result<ProduceBlockOutcome, ChainError>
             ChainError::InvariantViolation("tx_index.overflow".to_string()),
         )?;
         let tx_id = prepared_tx.tx_id;
-        let execution = execute_tx_on(
-            &mut exec_db,
-            tx_id,
-            tx_index,
-            prepared_tx.tx_env,
-            &exec_ctx,
-            ExecPath::UserTx,
-            false,
-            remaining_instruction_budget,
-            PrecompileAccess::wrap_side_effects(),
-        );
+        let accepted = optimistic
+            .as_mut()
+            .and_then(|optimistic| optimistic.take_accepted(tx_id, tx_index, &mut exec_db));
+        let execution = match accepted {
+            Some(value) => Ok(value),
+            None => execute_tx_on(
+                &mut exec_db,
+                tx_id,
+                tx_index,
+                prepared_tx.tx_env,
+                &exec_ctx,
+                ExecPath::UserTx,
+                false,
+                remaining_instruction_budget,
+                PrecompileAccess::wrap_side_effects(),
+            ),
+        };
         let outcome = match execution {
             Ok((value, user_diff)) => {
                 collect_touched_addresses(
@@ -1408,11 +1851,24 @@ pub fn produce_block(max_txs: usize) -> Result<ProduceBlockOutcome, ChainError>
                     &mut touched_slots,
                     &mut delta_digests,
                 );
+                if let Some(optimistic) = optimistic.as_mut() {
+                    optimistic.record_commit(&user_diff);
+                }
                 exec_db.commit(user_diff.clone());
                 staged_state_diffs.push(user_diff);
                 value
             }
             Err(err) => {
+                if err == ExecError::InstructionBudgetExceeded
+                    && verified_core::block_round::budget_exceeded_tx_retries(
+                        allow_stage,
+                        staged_included.len().saturating_sub(resumed_included),
+                    )
+                {
+                    // 次ラウンドの予算で再実行するため、未処理の候補として残す。
+                    unprocessed_candidates = unprocessed_candidates.saturating_add(1);
+                    break;
+                }
                 observe_exec_error(&err, timestamp);
                 if err == ExecError::InvalidGasFee {
                     staged_drops.push(QueuedDrop {
@@ -1501,8 +1957,74 @@ pub fn produce_block(max_txs: usize) -> Result<ProduceBlockOutcome, ChainError>
         }
     }
 
+    let round_decision = if allow_stage {
+        verified_core::block_round::round_end_decision(
+            exec_ctx.block_gas_limit > 0 && block_gas_used >= exec_ctx.block_gas_limit,
+            verified_core::block::instruction_limit_exhausted(
+                update_instruction_soft_limit,
+                instruction_start,
+                current_instruction_counter(),
+            ),
+            unprocessed_candidates,
+            rounds_done,
+            MAX_STAGED_BLOCK_ROUNDS,
+        )
+    } else {
+        BlockRoundDecision::Seal
+    };
+    if round_decision == BlockRoundDecision::Stage {
+        let encoded = staged_included[resumed_included..]
+            .iter()
+            .zip(staged_state_diffs[resumed_included..].iter())
+            .map(|(included, state_diff)| {
+                let StagedIncludedTx::Success {
+                    tx_id,
+                    outcome,
+                    sender_bytes,
+                    sender_nonce,
+                } = included;
+                staged_block::encode_included(
+                    *tx_id,
+                    *sender_bytes,
+                    *sender_nonce,
+                    outcome,
+                    state_diff,
+                )
+            })
+            .collect::<Result<Vec<Vec<u8>>, ChainError>>()?;
+        let progress = with_state_mut(|state| {
+            let item_count = staged_block::append_items(state, &encoded).ok()?;
+            let meta = staged_block::current_meta(state);
+            let rounds = rounds_done.saturating_add(1);
+            state.staged_block_meta.set(StagedBlockMetaV1 {
+                active: true,
+                number,
+                timestamp,
+                parent_hash,
+                base_fee: exec_ctx.base_fee,
+                block_gas_limit: exec_ctx.block_gas_limit,
+                evm_state_epoch: state_epoch,
+                rounds,
+                item_count,
+                ..meta
+            });
+            Some(StagedBlockProgress {
+                number,
+                rounds,
+                tx_count: item_count,
+                gas_used: block_gas_used,
+            })
+        });
+        // blob に載らないdiffがある場合は、持ち越さずにこのラウンドで封印する。
+        if let Some(progress) = progress {
+            // drop は組成中のブロック内容に依存しないため、ラウンドごとに確定させる。
+            apply_drops_only(&staged_drops, &dropped_by_code, number);
+            return Ok(BlockRoundOutcome::Staged(progress));
+        }
+    }
+
     if included_tx_ids.is_empty() {
-        apply_drops_only(&staged_drops, &dropped_by_code);
+        apply_drops_only(&staged_drops, &dropped_by_code, number);
         return Err(ChainError::NoExecutableTx);
     }
 
@@ -1564,7 +2086,16 @@ pub fn produce_block(max_txs: usize) -> Result<ProduceBlockOutcome, ChainError>
         }
 
         trie_commit::apply(state, prepared_root);
+        staged_block::clear(state, true);
+        clear_pre_executed();
         for drop in staged_drops.iter() {
+            let ctx = DropContext::capture(
+                state,
+                drop.tx_id,
+                drop.sender_override,
+                drop.nonce_override,
+                Some(number),
+            );
             advance_sender_after_tx(
                 state,
                 drop.tx_id,
@@ -1572,7 +2103,7 @@ pub fn produce_block(max_txs: usize) -> Result<ProduceBlockOutcome, ChainError>
                 drop.nonce_override,
                 false,
             );
-            mark_dropped_and_purge_payload(state, drop.tx_id, drop.drop_code);
+            mark_dropped_and_purge_payload(state, drop.tx_id, drop.drop_code, ctx);
         }
         let mut staged_persisted = Vec::with_capacity(staged_included.len());
         for included in staged_included.iter() {
@@ -1665,7 +2196,6 @@ pub fn produce_block(max_txs: usize) -> Result<ProduceBlockOutcome, ChainError>
             chain_state.block_gas_limit,
         );
         state.chain_state.set(chain_state);
-        rebuild_pending_fee_index_for_base_fee(state, chain_state.base_fee);
         let mut metrics = *state.metrics_state.get();
         for (idx, count) in dropped_by_code.iter().enumerate() {
             if *count > 0 {
@@ -1677,13 +2207,39 @@ pub fn produce_block(max_txs: usize) -> Result<ProduceBlockOutcome, ChainError>
         state.metrics_state.set(metrics);
     });
 
-    Ok(ProduceBlockOutcome {
+    Ok(BlockRoundOutcome::Sealed(Box::new(ProduceBlockOutcome {
         block,
         gas_used: block_gas_used,
         dropped: dropped_total,
+        optimistic: optimistic
+            .map(|optimistic| optimistic.stats())
+            .unwrap_or_default(),
+        rounds: rounds_done.saturating_add(1),
+    })))
+}
+
+/// upgrade checkpoint 用。期限切れの entry は次の判定で掃除されるため、そのまま返す。
+pub fn decode_suppress_entries() -> Vec<(Vec<u8>, u64)> {
+    DECODE_SUPPRESS_UNTIL_BY_PRINCIPAL.with(|cell| {
+        cell.borrow()
+            .iter()
+            .map(|(principal, until)| (principal.clone(), *until))
+            .collect()
     })
 }
 
+pub fn restore_decode_suppress(entries: Vec<(Vec<u8>, u64)>) {
+    DECODE_SUPPRESS_UNTIL_BY_PRINCIPAL.with(|cell| {
+        let mut map = cell.borrow_mut();
+        map.clear();
+        map.extend(
+            entries
+                .into_iter()
+                .take(evm_db::chain_data::DEFAULT_MAX_DECODE_SUPPRESS_PRINCIPALS),
+        );
+    });
+}
+
 fn is_principal_decode_suppressed(principal: &[u8], now_ts: u64) -> bool {
     if principal.is_empty() {
         return false;
@@ -1929,6 +2485,7 @@ pub fn eth_call_object(input: CallObjectInput) -> Result<CallObjectResult, Chain
         gas_used: outcome.receipt.gas_used,
         return_data: outcome.return_data,
         revert_data,
+        halt_reason: outcome.halt_reason,
     })
 }
 
@@ -2049,6 +2606,7 @@ where
         gas_used: outcome.receipt.gas_used,
         return_data: outcome.return_data,
         revert_data,
+        halt_reason: outcome.halt_reason,
     })
 }
 
@@ -2165,7 +2723,7 @@ pub fn get_receipt(tx_id: &TxId) -> Option<ReceiptLike> {
 fn store_block(state: &mut StableState, block: &BlockData) -> evm_db::blob_ptr::BlobPtr {
     before_store_write_for_test("store_block", Some(block.number), None);
     let bytes = block.to_bytes().into_owned();
-    let ptr = state.blob_store.store_bytes(&bytes).unwrap_or_else(|_| {
+    let ptr = state.blob_store.store_encoded(&bytes).unwrap_or_else(|_| {
         trap_store_err("store_block", Some(block.number), None, "blob_store");
     });
     let mut config = *state.prune_config.get();
@@ -2183,7 +2741,7 @@ fn store_receipt(state: &mut StableState, receipt: &ReceiptLike) -> evm_db::blob
         Some(receipt.tx_id),
     );
     let bytes = receipt.to_bytes().into_owned();
-    state.blob_store.store_bytes(&bytes).unwrap_or_else(|_| {
+    state.blob_store.store_encoded(&bytes).unwrap_or_else(|_| {
         trap_store_err(
             "store_receipt",
             Some(receipt.block_number),
@@ -2214,7 +2772,7 @@ fn store_internal_traces(
             .expect("internal trace failed marker must encode")
             .into_owned(),
     };
-    Some(state.blob_store.store_bytes(&bytes).unwrap_or_else(|_| {
+    Some(state.blob_store.store_encoded(&bytes).unwrap_or_else(|_| {
         trap_store_err(
             "store_internal_traces",
             Some(block_number),
@@ -2224,7 +2782,10 @@ fn store_internal_traces(
     }))
 }
 
-fn store_tx_index_entry(state: &mut StableState, entry: TxIndexEntry) -> evm_db::blob_ptr::BlobPtr {
+pub(crate) fn store_tx_index_entry(
+    state: &mut StableState,
+    entry: TxIndexEntry,
+) -> evm_db::blob_ptr::BlobPtr {
     before_store_write_for_test("store_tx_index_entry", Some(entry.block_number), None);
     let bytes = entry.to_bytes().into_owned();
     state.blob_store.store_bytes(&bytes).unwrap_or_else(|_| {
@@ -2237,9 +2798,9 @@ fn store_tx_index_entry(state: &mut StableState, entry: TxIndexEntry) -> evm_db:
     })
 }
 
-fn load_block(state: &StableState, number: u64) -> Option<BlockData> {
+pub(crate) fn load_block(state: &StableState, number: u64) -> Option<BlockData> {
     if let Some(ptr) = state.blocks.get(&number) {
-        let bytes = state.blob_store.read(&ptr).ok()?;
+        let bytes = state.blob_store.read_decoded(&ptr).ok()?;
         let block = BlockData::from_bytes(Cow::Owned(bytes));
         if is_corrupt_block(&block) {
             return None;
@@ -2249,9 +2810,9 @@ fn load_block(state: &StableState, number: u64) -> Option<BlockData> {
     None
 }
 
-fn load_receipt(state: &StableState, tx_id: &TxId) -> Option<ReceiptLike> {
+pub(crate) fn load_receipt(state: &StableState, tx_id: &TxId) -> Option<ReceiptLike> {
     if let Some(ptr) = state.receipts.get(tx_id) {
-        let bytes = state.blob_store.read(&ptr).ok()?;
+        let bytes = state.blob_store.read_decoded(&ptr).ok()?;
         let receipt = ReceiptLike::from_bytes(Cow::Owned(bytes));
         if is_corrupt_receipt(&receipt, tx_id) {
             return None;
@@ -2430,7 +2991,6 @@ fn execute_and_seal_with_caller(
             chain_state.block_gas_limit,
         );
         state.chain_state.set(chain_state);
-        rebuild_pending_fee_index_for_base_fee(state, chain_state.base_fee);
         let mut metrics = *state.metrics_state.get();
         metrics.record_included(1);
         metrics.record_block(number, timestamp, 1, 0);
@@ -2468,8 +3028,11 @@ pub fn prune_blocks(retain: u64, max_ops: u32) -> Result<PruneResult, ChainError
                 pruned_before_block: pruned_before,
             });
         }
-        let prune_before = match verified_core::prune::prune_before_block(head_number, retain) {
+        let prune_before = match verified_core::prune::prune_before_block(head_number, retain)
+            .and_then(|value| crate::archive::prune_ceiling(state, value))
+        {
             Some(value) => value,
+            // archive 設定中で ack がまだ無い場合もここで止まる。
             None => {
                 let pruned_before = state.prune_state.get().pruned_before();
                 return Ok(PruneResult {
@@ -2512,6 +3075,7 @@ pub fn prune_blocks(retain: u64, max_ops: u32) -> Result<PruneResult, ChainError
             state.prune_state.set(prune_state);
 
             let _ = state.blocks.remove(&next);
+            crate::archive::record_pruned_txs(state, next, &block.tx_ids);
             for tx_id in block.tx_ids.iter() {
                 remove_pending_fee_index_by_tx_id(state, *tx_id);
                 decrement_principal_pending_count_for_tx(state, *tx_id);
@@ -2621,6 +3185,7 @@ fn recover_prune_journal(state: &mut evm_db::stable_state::StableState) -> Resul
     if let Some(journal) = state.prune_journal.get(&journal_block) {
         if let Some(block) = load_block(state, journal_block) {
             let _ = state.blocks.remove(&journal_block);
+            crate::archive::record_pruned_txs(state, journal_block, &block.tx_ids);
             for tx_id in block.tx_ids.iter() {
                 remove_pending_fee_index_by_tx_id(state, *tx_id);
                 decrement_principal_pending_count_for_tx(state, *tx_id);
@@ -2868,13 +3433,15 @@ fn enforce_pending_caps(
     state: &mut evm_db::stable_state::StableState,
     sender: SenderKey,
     caller_principal: &[u8],
-    incoming_effective_gas_price: u64,
+    max_fee_per_gas: u128,
+    max_priority_fee_per_gas: u128,
 ) -> Result<(), ChainError> {
-    let lowest_effective_gas_price = state.pending_fee_index.range(..).next().map(|entry| {
-        let mut fee = [0u8; 8];
-        fee.copy_from_slice(&entry.key().0[0..8]);
-        u64::from_be_bytes(fee)
-    });
+    let base_fee = state.chain_state.get().base_fee;
+    let incoming_effective_gas_price =
+        compute_effective_gas_price(max_fee_per_gas, max_priority_fee_per_gas, base_fee)
+            .unwrap_or(0);
+    let lowest_effective_gas_price =
+        lowest_fee_pending(state, base_fee).map(|(effective, _)| effective);
     let decision =
         verified_core::queue::classify_pending_caps(verified_core::queue::PendingCapInput {
             sender_count: count_pending_for_sender(state, sender),
@@ -2894,7 +3461,7 @@ fn enforce_pending_caps(
         }
         verified_core::queue::PendingCapDecision::GlobalFull => Err(ChainError::QueueFull),
         verified_core::queue::PendingCapDecision::EvictLowest => {
-            evict_lowest_fee_pending(state, incoming_effective_gas_price)
+            evict_lowest_fee_pending(state, base_fee, incoming_effective_gas_price)
         }
     }
 }
@@ -2910,25 +3477,58 @@ fn count_pending_for_principal(
     usize::try_from(state.principal_pending_count.get(&key).unwrap_or(0)).unwrap_or(usize::MAX)
 }
 
+// どこで: global cap到達時 / 何を: 現在のbase feeでの最安pending txを返す / なぜ: indexをbase fee非依存に保ち、pool全体の再構築を不要にするため
+// max fee順とpriority順の各先頭だけを比べれば足りる（verified_core::fee::pending_fee_ranks 参照）。
+fn lowest_fee_pending(
+    state: &evm_db::stable_state::StableState,
+    base_fee: u64,
+) -> Option<(u64, TxId)> {
+    let cap_head = state
+        .pending_fee_index
+        .range(..)
+        .next()
+        .map(|entry| (*entry.key(), entry.value()));
+    let tip_head = state
+        .pending_tip_index
+        .range(..)
+        .next()
+        .map(|entry| (*entry.key(), entry.value()));
+    let cap_candidate = cap_head.and_then(|(cap_key, tx_id)| {
+        let tip_key = state.pending_tip_key_by_tx_id.get(&tx_id)?;
+        Some((cap_key.fee_rank(), tip_key.fee_rank(), tx_id))
+    });
+    let tip_candidate = tip_head.and_then(|(tip_key, tx_id)| {
+        let cap_key = state.pending_fee_key_by_tx_id.get(&tx_id)?;
+        Some((cap_key.fee_rank(), tip_key.fee_rank(), tx_id))
+    });
+    cap_candidate
+        .into_iter()
+        .chain(tip_candidate)
+        .map(|(cap_rank, tip_rank, tx_id)| {
+            let effective =
+                compute_effective_gas_price(u128::from(cap_rank), u128::from(tip_rank), base_fee)
+                    .unwrap_or(0);
+            (effective, tx_id)
+        })
+        .min()
+}
+
 fn evict_lowest_fee_pending(
     state: &mut evm_db::stable_state::StableState,
+    base_fee: u64,
     incoming_effective_gas_price: u64,
 ) -> Result<(), ChainError> {
-    let Some(entry) = state.pending_fee_index.range(..).next() else {
+    let Some((lowest_fee, evict_tx_id)) = lowest_fee_pending(state, base_fee) else {
         return Err(ChainError::QueueFull);
     };
-    let key = *entry.key();
-    let mut fee = [0u8; 8];
-    fee.copy_from_slice(&key.0[0..8]);
-    let lowest_fee = u64::from_be_bytes(fee);
-    let evict_tx_id = entry.value();
     if incoming_effective_gas_price <= lowest_fee {
         return Err(ChainError::QueueFull);
     }
 
     // Queue pressure eviction is modeled as a replacement-style drop for telemetry consistency.
+    let ctx = DropContext::capture(state, evict_tx_id, None, None, None);
     advance_sender_after_tx(state, evict_tx_id, None, None, false);
-    mark_dropped_and_purge_payload(state, evict_tx_id, DROP_CODE_REPLACED);
+    mark_dropped_and_purge_payload(state, evict_tx_id, DROP_CODE_REPLACED, ctx);
     let mut metrics = *state.metrics_state.get();
     metrics.record_drop(DROP_CODE_REPLACED, 1);
     state.metrics_state.set(metrics);
@@ -2952,7 +3552,8 @@ fn track_pending_indexes_on_insert(
     state: &mut evm_db::stable_state::StableState,
     tx_id: TxId,
     caller_principal: &[u8],
-    effective_gas_price: u64,
+    max_fee_per_gas: u128,
+    max_priority_fee_per_gas: u128,
 ) {
     let principal_key = CallerKey::from_principal_bytes(caller_principal);
     let current = state
@@ -2962,17 +3563,31 @@ fn track_pending_indexes_on_insert(
     state
         .principal_pending_count
         .insert(principal_key, current.saturating_add(1));
-    let fee_key = PendingFeeKey::new(effective_gas_price, tx_id.0);
-    state.pending_fee_index.insert(fee_key, tx_id);
-    state.pending_fee_key_by_tx_id.insert(tx_id, fee_key);
+    insert_pending_fee_indexes(state, tx_id, max_fee_per_gas, max_priority_fee_per_gas);
 }
 
-fn rebuild_pending_fee_index_for_base_fee(
+// どこで: pending挿入/upgrade再構築 / 何を: max fee順とpriority順のindexへ登録 / なぜ: base fee変動でindexを作り直さないため
+fn insert_pending_fee_indexes(
     state: &mut evm_db::stable_state::StableState,
-    base_fee: u64,
+    tx_id: TxId,
+    max_fee_per_gas: u128,
+    max_priority_fee_per_gas: u128,
 ) {
+    let (cap_rank, tip_rank) =
+        verified_core::fee::pending_fee_ranks(max_fee_per_gas, max_priority_fee_per_gas);
+    let fee_key = PendingFeeKey::new(cap_rank, tx_id.0);
+    state.pending_fee_index.insert(fee_key, tx_id);
+    state.pending_fee_key_by_tx_id.insert(tx_id, fee_key);
+    let tip_key = PendingFeeKey::new(tip_rank, tx_id.0);
+    state.pending_tip_index.insert(tip_key, tx_id);
+    state.pending_tip_key_by_tx_id.insert(tx_id, tip_key);
+}
+
+fn rebuild_pending_fee_indexes(state: &mut evm_db::stable_state::StableState) {
     clear_stable_map(&mut state.pending_fee_index);
     clear_stable_map(&mut state.pending_fee_key_by_tx_id);
+    clear_stable_map(&mut state.pending_tip_index);
+    clear_stable_map(&mut state.pending_tip_key_by_tx_id);
     let mut pending_ids = Vec::new();
     for entry in state.pending_by_sender_nonce.iter() {
         pending_ids.push(entry.value());
@@ -2984,19 +3599,17 @@ fn rebuild_pending_fee_index_for_base_fee(
         let Ok(stored) = StoredTx::try_from(envelope) else {
             continue;
         };
-        let effective = compute_effective_gas_price(
+        let max_priority_fee_per_gas = if stored.is_dynamic_fee {
+            stored.max_priority_fee_per_gas
+        } else {
+            0
+        };
+        insert_pending_fee_indexes(
+            state,
+            tx_id,
             stored.max_fee_per_gas,
-            if stored.is_dynamic_fee {
-                stored.max_priority_fee_per_gas
-            } else {
-                0
-            },
-            base_fee,
-        )
-        .unwrap_or(0);
-        let fee_key = PendingFeeKey::new(effective, tx_id.0);
-        state.pending_fee_index.insert(fee_key, tx_id);
-        state.pending_fee_key_by_tx_id.insert(tx_id, fee_key);
+            max_priority_fee_per_gas,
+        );
     }
 }
 
@@ -3004,6 +3617,9 @@ fn remove_pending_fee_index_by_tx_id(state: &mut evm_db::stable_state::StableSta
     if let Some(key) = state.pending_fee_key_by_tx_id.remove(&tx_id) {
         state.pending_fee_index.remove(&key);
     }
+    if let Some(key) = state.pending_tip_key_by_tx_id.remove(&tx_id) {
+        state.pending_tip_index.remove(&key);
+    }
 }
 
 fn decrement_principal_pending_count_for_tx(
@@ -3047,6 +3663,7 @@ fn insert_ready(
         .ready_by_seq
         .insert(ReadySeqKey::new(seq, tx_id.0), tx_id);
     state.ready_key_by_tx_id.insert(tx_id, key);
+    insert_ready_tip(state, tx_id, key);
     Ok(())
 }
 
@@ -3056,6 +3673,7 @@ fn remove_ready_by_tx_id(state: &mut evm_db::stable_state::StableState, tx_id: T
         state
             .ready_by_seq
             .remove(&ReadySeqKey::new(key.seq(), tx_id.0));
+        remove_ready_tip(state, tx_id, key);
     }
 }
 
@@ -3139,8 +3757,9 @@ fn drop_invalid_fee_pending(
     dropped_total: Option<&mut u64>,
     dropped_by_code: Option<&mut [u64]>,
 ) {
+    let ctx = DropContext::capture(state, tx_id, None, None, None);
     advance_sender_after_tx(state, tx_id, None, None, false);
-    mark_dropped_and_purge_payload(state, tx_id, DROP_CODE_INVALID_FEE);
+    mark_dropped_and_purge_payload(state, tx_id, DROP_CODE_INVALID_FEE, ctx);
     if let (Some(total), Some(by_code)) = (dropped_total, dropped_by_code) {
         track_drop(total, by_code, DROP_CODE_INVALID_FEE);
     } else {
@@ -3156,8 +3775,9 @@ fn drop_invalid_fee_pending_decode(
     dropped_total: Option<&mut u64>,
     dropped_by_code: Option<&mut [u64]>,
 ) {
+    let ctx = DropContext::capture(state, tx_id, None, None, None);
     advance_sender_after_tx(state, tx_id, None, None, false);
-    mark_dropped_and_purge_payload(state, tx_id, DROP_CODE_DECODE);
+    mark_dropped_and_purge_payload(state, tx_id, DROP_CODE_DECODE, ctx);
     if let (Some(total), Some(by_code)) = (dropped_total, dropped_by_code) {
         track_drop(total, by_code, DROP_CODE_DECODE);
     } else {
@@ -3174,6 +3794,7 @@ fn drop_exec_pending_sync(state: &mut evm_db::stable_state::StableState, tx_id:
     if !has_pending && !has_ready {
         return;
     }
+    let ctx = DropContext::capture(state, tx_id, None, None, None);
     remove_ready_by_tx_id(state, tx_id);
     remove_pending_fee_index_by_tx_id(state, tx_id);
     decrement_principal_pending_count_for_tx(state, tx_id);
@@ -3181,7 +3802,7 @@ fn drop_exec_pending_sync(state: &mut evm_db::stable_state::StableState, tx_id:
         state.pending_by_sender_nonce.remove(&pending_key);
         finalize_pending_for_sender(state, pending_key.sender, tx_id, false);
     }
-    mark_dropped_and_purge_payload(state, tx_id, DROP_CODE_EXEC);
+    mark_dropped_and_purge_payload(state, tx_id, DROP_CODE_EXEC, ctx);
     let mut metrics = *state.metrics_state.get();
     metrics.record_drop(DROP_CODE_EXEC, 1);
     state.metrics_state.set(metrics);
@@ -3304,6 +3925,7 @@ fn replace_pending_for_sender(
     sender: SenderKey,
     old_tx_id: TxId,
 ) {
+    let ctx = DropContext::capture(state, old_tx_id, None, None, None);
     remove_ready_by_tx_id(state, old_tx_id);
     remove_pending_fee_index_by_tx_id(state, old_tx_id);
     decrement_principal_pending_count_for_tx(state, old_tx_id);
@@ -3312,7 +3934,7 @@ fn replace_pending_for_sender(
     }
     state.pending_min_nonce.remove(&sender);
     state.pending_current_by_sender.remove(&sender);
-    mark_dropped_and_purge_payload(state, old_tx_id, DROP_CODE_REPLACED);
+    mark_dropped_and_purge_payload(state, old_tx_id, DROP_CODE_REPLACED, ctx);
     let mut metrics = *state.metrics_state.get();
     metrics.record_drop(DROP_CODE_REPLACED, 1);
     state.metrics_state.set(metrics);
@@ -3322,7 +3944,9 @@ fn mark_dropped_and_purge_payload(
     state: &mut evm_db::stable_state::StableState,
     tx_id: TxId,
     drop_code: u16,
+    ctx: DropContext,
 ) {
+    drop_record::record(state, tx_id, drop_code, ctx);
     remove_pending_fee_index_by_tx_id(state, tx_id);
     remove_eth_tx_hash_index_for_tx_id(state, tx_id);
     state.tx_store.remove(&tx_id);
@@ -3451,84 +4075,146 @@ fn select_ready_candidates(
     if max_txs == 0 {
         return Vec::new();
     }
-    let mut keys: Vec<ReadyKey> = Vec::new();
-    for entry in state.ready_queue.range(..).take(READY_CANDIDATE_LIMIT) {
-        keys.push(*entry.key());
-    }
-
-    let mut candidates: Vec<ReadyCandidate> = Vec::new();
-    for key in keys {
-        let tx_id = match state.ready_queue.get(&key) {
-            Some(value) => value,
-            None => continue,
-        };
-        let fields = load_fee_fields_and_seq(state, tx_id);
-        let (max_fee_per_gas, max_priority_fee_per_gas, is_dynamic_fee, seq) = match fields {
-            Ok(Some(value)) => value,
-            Ok(None) | Err(RekeyError::DecodeFailed) => {
-                candidates.push(ReadyCandidate {
-                    tx_id,
-                    effective_gas_price: 0,
-                    seq: key.seq(),
-                });
+    // 各bucketは effective gas price 降順に並んでいるため、先頭同士を比べるk-way mergeで足りる。
+    let mut streams = [
+        ready_bucket_candidates(state, READY_TIP_BUCKET_TIP, base_fee).peekable(),
+        ready_bucket_candidates(state, READY_TIP_BUCKET_CAP, base_fee).peekable(),
+        ready_bucket_candidates(state, READY_TIP_BUCKET_ZERO, base_fee).peekable(),
+    ];
+    let mut selected = Vec::with_capacity(max_txs);
+    while selected.len() < max_txs {
+        let mut best: Option<(usize, ReadyCandidate)> = None;
+        for (idx, stream) in streams.iter_mut().enumerate() {
+            let Some(candidate) = stream.peek() else {
                 continue;
+            };
+            if best.is_none_or(|(_, current)| *candidate > current) {
+                best = Some((idx, *candidate));
             }
+        }
+        let Some((idx, candidate)) = best else {
+            break;
         };
-        let effective_gas_price = compute_effective_gas_price(
-            max_fee_per_gas,
-            if is_dynamic_fee {
-                max_priority_fee_per_gas
-            } else {
-                0
-            },
-            base_fee,
-        )
-        .unwrap_or(0);
-        candidates.push(ReadyCandidate {
-            tx_id,
-            effective_gas_price,
-            seq,
-        });
+        let _ = streams[idx].next();
+        selected.push(candidate.tx_id);
     }
+    selected
+}
 
-    select_top_k_ready_candidates(candidates, max_txs)
+fn ready_bucket_candidates(
+    state: &evm_db::stable_state::StableState,
+    bucket: u8,
+    base_fee: u64,
+) -> impl Iterator<Item = ReadyCandidate> + '_ {
+    let start = ReadyTipKey::bucket_start(bucket);
+    let end = ReadyTipKey::bucket_start(bucket.saturating_add(1));
+    state.ready_tip_index.range(start..end).map(move |entry| {
+        let key = *entry.key();
+        let effective_gas_price = match key.bucket() {
+            READY_TIP_BUCKET_TIP => ReadyFeeBucket::Tip(key.rank()),
+            READY_TIP_BUCKET_CAP => ReadyFeeBucket::Cap(key.rank()),
+            _ => ReadyFeeBucket::Zero,
+        }
+        .effective_gas_price(base_fee);
+        ReadyCandidate {
+            tx_id: entry.value(),
+            effective_gas_price,
+            seq: key.seq(),
+        }
+    })
 }
 
-fn push_top_k_candidate(
-    selected_heap: &mut BinaryHeap<Reverse<ReadyCandidate>>,
-    candidate: ReadyCandidate,
-    max_txs: usize,
-) {
-    if selected_heap.len() < max_txs {
-        selected_heap.push(Reverse(candidate));
-        return;
-    }
+fn ready_tip_key_for(tx_id: TxId, ready_key: ReadyKey, base_fee: u64) -> ReadyTipKey {
+    let bucket = verified_core::ready_bucket::classify(
+        ready_key.max_fee_per_gas(),
+        ready_key.max_priority_fee_per_gas(),
+        base_fee,
+    );
+    let (bucket, rank) = match bucket {
+        ReadyFeeBucket::Tip(priority) => (READY_TIP_BUCKET_TIP, priority),
+        ReadyFeeBucket::Cap(max_fee) => (READY_TIP_BUCKET_CAP, max_fee),
+        ReadyFeeBucket::Zero => (READY_TIP_BUCKET_ZERO, 0),
+    };
+    ReadyTipKey::new(bucket, rank, ready_key.seq(), tx_id.0)
+}
 
-    if let Some(worst_selected) = selected_heap.peek() {
-        if candidate > worst_selected.0 {
-            selected_heap.pop();
-            selected_heap.push(Reverse(candidate));
-        }
+fn insert_ready_tip(state: &mut StableState, tx_id: TxId, ready_key: ReadyKey) {
+    let base_fee = state.ready_index_state.get().ready_tip_base_fee;
+    let tip_key = ready_tip_key_for(tx_id, ready_key, base_fee);
+    state.ready_tip_index.insert(tip_key, tx_id);
+    state.ready_tip_key_by_tx_id.insert(tx_id, tip_key);
+    let boundaries = verified_core::ready_bucket::boundaries(
+        ready_key.max_fee_per_gas(),
+        ready_key.max_priority_fee_per_gas(),
+    );
+    for boundary in boundaries.into_iter().flatten() {
+        state
+            .ready_fee_boundaries
+            .insert(ReadyFeeBoundaryKey::new(boundary, tx_id.0), ready_key);
     }
 }
 
-fn select_top_k_ready_candidates(mut candidates: Vec<ReadyCandidate>, max_txs: usize) -> Vec<TxId> {
-    if max_txs == 0 {
-        return Vec::new();
+fn remove_ready_tip(state: &mut StableState, tx_id: TxId, ready_key: ReadyKey) {
+    if let Some(tip_key) = state.ready_tip_key_by_tx_id.remove(&tx_id) {
+        state.ready_tip_index.remove(&tip_key);
     }
-
-    let mut selected_heap: BinaryHeap<Reverse<ReadyCandidate>> = BinaryHeap::new();
-    for candidate in candidates.drain(..) {
-        push_top_k_candidate(&mut selected_heap, candidate, max_txs);
+    let boundaries = verified_core::ready_bucket::boundaries(
+        ready_key.max_fee_per_gas(),
+        ready_key.max_priority_fee_per_gas(),
+    );
+    for boundary in boundaries.into_iter().flatten() {
+        state
+            .ready_fee_boundaries
+            .remove(&ReadyFeeBoundaryKey::new(boundary, tx_id.0));
     }
+}
 
-    let mut selected: Vec<ReadyCandidate> =
-        selected_heap.into_iter().map(|entry| entry.0).collect();
-    selected.sort_by(|left, right| right.cmp_priority(left));
-    selected
-        .into_iter()
-        .map(|candidate| candidate.tx_id)
-        .collect()
+// どこで: ブロック組成直前 / 何を: ready_tip_indexを現在のbase feeへ追従 / なぜ: 境界をまたいだTxだけ再分類し、pool全体の走査を避けるため
+fn sync_ready_tip_index(state: &mut StableState, base_fee: u64) {
+    if state.ready_tip_key_by_tx_id.len() != state.ready_queue.len() {
+        rebuild_ready_tip_index(state, base_fee);
+        return;
+    }
+    let mut index_state = *state.ready_index_state.get();
+    let indexed_base_fee = index_state.ready_tip_base_fee;
+    if indexed_base_fee == base_fee {
+        return;
+    }
+    let start = ReadyFeeBoundaryKey::new(u128::from(indexed_base_fee.min(base_fee)), [0u8; 32]);
+    let end = ReadyFeeBoundaryKey::new(u128::from(indexed_base_fee.max(base_fee)), [0u8; 32]);
+    let mut affected: BTreeMap<TxId, ReadyKey> = BTreeMap::new();
+    for entry in state.ready_fee_boundaries.range(start..end) {
+        affected.insert(TxId(entry.key().tx_hash()), entry.value());
+    }
+    for (tx_id, ready_key) in affected {
+        let next = ready_tip_key_for(tx_id, ready_key, base_fee);
+        if let Some(prev) = state.ready_tip_key_by_tx_id.get(&tx_id) {
+            if prev == next {
+                continue;
+            }
+            state.ready_tip_index.remove(&prev);
+        }
+        state.ready_tip_index.insert(next, tx_id);
+        state.ready_tip_key_by_tx_id.insert(tx_id, next);
+    }
+    index_state.ready_tip_base_fee = base_fee;
+    state.ready_index_state.set(index_state);
+}
+
+fn rebuild_ready_tip_index(state: &mut StableState, base_fee: u64) {
+    clear_stable_map(&mut state.ready_tip_index);
+    clear_stable_map(&mut state.ready_tip_key_by_tx_id);
+    clear_stable_map(&mut state.ready_fee_boundaries);
+    let mut index_state = *state.ready_index_state.get();
+    index_state.ready_tip_base_fee = base_fee;
+    state.ready_index_state.set(index_state);
+    let mut ready_entries = Vec::new();
+    for entry in state.ready_queue.iter() {
+        ready_entries.push((*entry.key(), entry.value()));
+    }
+    for (ready_key, tx_id) in ready_entries {
+        insert_ready_tip(state, tx_id, ready_key);
+    }
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
@@ -3539,3 +4225,7 @@ enum RekeyError {
 #[cfg(test)]
 #[path = "chain_tests.rs"]
 mod tests;
+
+#[cfg(test)]
+#[path = "chain_block_rounds_tests.rs"]
+mod block_rounds_tests;
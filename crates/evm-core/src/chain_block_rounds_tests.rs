@@ -0,0 +1,219 @@
+//! どこで: Phase1複数ラウンド組成テスト / 何を: staged blockの持ち越し・再開・破棄 / なぜ: 命令上限をまたいでも単一ラウンドと同じブロックになることを固定するため
+
+use super::{self as chain, BlockRoundOutcome, ProduceBlockOutcome, TxIn};
+use crate::hash;
+use crate::tx_decode::IcSyntheticTxInput;
+use evm_db::chain_data::{ReceiptLike, TxId, TxLocKind};
+use evm_db::stable_state::{init_stable_state, with_state, with_state_mut};
+use evm_db::types::keys::{make_account_key, make_code_key};
+use evm_db::types::values::{AccountVal, CodeVal};
+
+// SLOAD(0) + 1 を SSTORE(0) するだけのカウンタ。ラウンド間でstorage diffが引き継がれることを確認する。
+const COUNTER_CODE: [u8; 10] = [0x60, 0x00, 0x54, 0x60, 0x01, 0x01, 0x60, 0x00, 0x55, 0x00];
+const COUNTER: [u8; 20] = [0x77; 20];
+
+type BlockResult = ([u8; 32], Vec<TxId>, Vec<ReceiptLike>);
+
+// 命令カウンタの差し替えは crate 内のテストからしか使えないため、ここで最小限の準備を持つ。
+fn install_contract(address: [u8; 20], code: &[u8]) {
+    let code_hash = hash::keccak256(code);
+    with_state_mut(|state| {
+        state.accounts.insert(
+            make_account_key(address),
+            AccountVal::from_parts(0, [0u8; 32], code_hash),
+        );
+        state
+            .codes
+            .insert(make_code_key(code_hash), CodeVal(code.to_vec()));
+    });
+}
+
+fn build_ic_tx_input(to: [u8; 20]) -> IcSyntheticTxInput {
+    IcSyntheticTxInput {
+        to: Some(to),
+        value: [0u8; 32],
+        gas_limit: 50_000,
+        nonce: 0,
+        max_fee_per_gas: 2_000_000_000,
+        max_priority_fee_per_gas: 1_000_000_000,
+        data: Vec::new(),
+    }
+}
+
+fn assert_block_persist_invariants(block_number: u64, tx_ids: &[TxId]) {
+    for (idx, tx_id) in tx_ids.iter().enumerate() {
+        let expected_index = u32::try_from(idx).expect("test block index fits u32");
+        let loc = with_state(|state| chain::tx_locs_get(state, tx_id)).expect("included tx_loc");
+        assert_eq!(loc.kind, TxLocKind::Included);
+        assert_eq!(loc.block_number, block_number);
+        assert_eq!(loc.tx_index, expected_index);
+        let receipt = chain::get_receipt(tx_id).expect("receipt");
+        assert_eq!(receipt.block_number, block_number);
+        assert_eq!(receipt.tx_index, expected_index);
+        with_state(|state| {
+            assert!(!state.pending_meta_by_tx_id.contains_key(tx_id));
+            assert!(!state.ready_key_by_tx_id.contains_key(tx_id));
+        });
+    }
+}
+
+fn setup_and_submit(targets: &[[u8; 20]]) -> Vec<TxId> {
+    init_stable_state();
+    with_state_mut(|state| {
+        let mut chain_state = *state.chain_state.get();
+        chain_state.base_fee = 1;
+        chain_state.min_gas_price = 1;
+        chain_state.min_priority_fee = 1;
+        state.chain_state.set(chain_state);
+    });
+    install_contract(COUNTER, &COUNTER_CODE);
+    let mut tx_ids = Vec::new();
+    for (idx, to) in targets.iter().enumerate() {
+        let idx_u8 = u8::try_from(idx).expect("test index fits u8");
+        let caller_principal = vec![0x40 + idx_u8];
+        chain::credit_balance(
+            hash::derive_evm_address_from_principal(&caller_principal).expect("must derive"),
+            1_000_000_000_000_000_000,
+        )
+        .expect("fund account");
+        let tx_id = chain::submit_tx_in(TxIn::IcSynthetic {
+            caller_principal,
+            canister_id: vec![0xa0 + idx_u8],
+            tx: build_ic_tx_input(*to),
+        })
+        .expect("submit");
+        tx_ids.push(tx_id);
+    }
+    tx_ids
+}
+
+fn limit_round_instructions(soft_limit: u64) {
+    with_state_mut(|state| {
+        let mut chain_state = *state.chain_state.get();
+        chain_state.update_instruction_soft_limit = soft_limit;
+        state.chain_state.set(chain_state);
+    });
+    // 命令カウンタを読むたびに1進める（候補準備・実行の各チェックで1ずつ消費する）。
+    chain::configure_block_instruction_counter_for_test(0, 1);
+}
+
+fn block_result(outcome: &ProduceBlockOutcome) -> BlockResult {
+    let receipts = outcome
+        .block
+        .tx_ids
+        .iter()
+        .map(|tx_id| chain::get_receipt(tx_id).expect("receipt"))
+        .collect();
+    (
+        outcome.block.state_root,
+        outcome.block.tx_ids.clone(),
+        receipts,
+    )
+}
+
+fn targets() -> Vec<[u8; 20]> {
+    vec![[0x31u8; 20], COUNTER, COUNTER, [0x32u8; 20], COUNTER]
+}
+
+fn single_round_block() -> BlockResult {
+    std::thread::spawn(|| {
+        setup_and_submit(&targets());
+        let outcome = chain::produce_block(8).expect("produce");
+        block_result(&outcome)
+    })
+    .join()
+    .expect("single round thread")
+}
+
+#[test]
+fn staged_rounds_seal_the_same_block_as_single_round() {
+    let expected = single_round_block();
+    let (result, rounds) = std::thread::spawn(|| {
+        let tx_ids = setup_and_submit(&targets());
+        limit_round_instructions(12);
+        let head_before = chain::get_head_number();
+        let mut staged_rounds = 0u32;
+        let outcome = loop {
+            match chain::produce_block_round(8).expect("round") {
+                BlockRoundOutcome::Staged(progress) => {
+                    staged_rounds += 1;
+                    assert_eq!(progress.rounds, staged_rounds);
+                    assert_eq!(progress.number, head_before + 1);
+                    assert!(progress.tx_count > 0);
+                    // 封印前は head もreceiptも動かない。
+                    assert_eq!(chain::get_head_number(), head_before);
+                    assert!(chain::get_receipt(&tx_ids[0]).is_none());
+                    let meta = chain::get_staged_block_meta();
+                    assert!(meta.active);
+                    assert_eq!(meta.item_count, progress.tx_count);
+                    assert!(staged_rounds < 16, "rounds must terminate");
+                }
+                BlockRoundOutcome::Sealed(outcome) => break *outcome,
+                BlockRoundOutcome::PreExecuted(_) => panic!("sequential mode must not pre-execute"),
+            }
+        };
+        assert_eq!(outcome.rounds, staged_rounds + 1);
+        assert_block_persist_invariants(outcome.block.number, &tx_ids);
+        let meta = chain::get_staged_block_meta();
+        assert!(!meta.active);
+        assert_eq!(meta.sealed_total, 1);
+        assert!(with_state(|state| state.staged_block_items.is_empty()));
+        (block_result(&outcome), staged_rounds)
+    })
+    .join()
+    .expect("multi round thread");
+    // 2ラウンド目は前ラウンドのdiffを積み直した上で、さらに持ち越す。
+    assert!(rounds >= 2, "soft limit must force repeated staged rounds");
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn produce_block_seals_pending_staged_block() {
+    let expected = single_round_block();
+    let result = std::thread::spawn(|| {
+        setup_and_submit(&targets());
+        limit_round_instructions(12);
+        assert!(matches!(
+            chain::produce_block_round(8).expect("round"),
+            BlockRoundOutcome::Staged(_)
+        ));
+        limit_round_instructions(0);
+        let outcome = chain::produce_block(8).expect("produce");
+        assert_eq!(outcome.rounds, 2);
+        block_result(&outcome)
+    })
+    .join()
+    .expect("resume thread");
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn staged_block_is_discarded_when_state_changes_between_rounds() {
+    let expected_len = targets().len();
+    std::thread::spawn(move || {
+        let tx_ids = setup_and_submit(&targets());
+        limit_round_instructions(12);
+        assert!(matches!(
+            chain::produce_block_round(8).expect("round"),
+            BlockRoundOutcome::Staged(_)
+        ));
+        // 組成中に外部から状態が変わると、staged diffの前提が崩れる。
+        chain::credit_native_deposit([0x99; 32], [0x55; 20], {
+            let mut amount = [0u8; 32];
+            amount[31] = 7;
+            amount
+        })
+        .expect("credit");
+        limit_round_instructions(0);
+        let outcome = chain::produce_block(8).expect("produce");
+        assert_eq!(outcome.rounds, 1);
+        assert_eq!(outcome.block.tx_ids.len(), expected_len);
+        assert_eq!(outcome.block.tx_ids, tx_ids);
+        let meta = chain::get_staged_block_meta();
+        assert!(!meta.active);
+        assert_eq!(meta.discarded_total, 1);
+        assert_eq!(meta.sealed_total, 0);
+    })
+    .join()
+    .expect("discard thread");
+}
@@ -140,7 +140,7 @@ fn store_internal_traces_stores_failed_marker_on_encode_failure() {
     with_state(|state| {
         let bytes = state
             .blob_store
-            .read(&ptr)
+            .read_decoded(&ptr)
             .expect("failed marker bytes must be readable");
         let decoded = InternalTraceSet::from_bytes(Cow::Owned(bytes));
         assert!(decoded.encode_failed);
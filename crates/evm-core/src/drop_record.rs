@@ -0,0 +1,136 @@
+//! どこで: Tx drop時の照会記録 / 何を: DropRecordの保存・検索・保持期間による削除 / なぜ: receipt照会で消えたTxの理由を返すため
+
+use crate::hash;
+use evm_db::chain_data::{DropRecordV1, SenderNonceKey, StoredTx, TxId, TxKind};
+use evm_db::stable_state::StableState;
+
+/// 1回のdropで削除を試みる古い記録の上限。挿入ごとに少しずつ追いつく。
+const DROP_RECORD_EVICT_PER_INSERT: usize = 4;
+
+/// drop時点でしか分からない情報。pending索引を消す前に `capture` で取る。
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct DropContext {
+    pub(crate) sender: Option<[u8; 20]>,
+    pub(crate) nonce: Option<u64>,
+    pub(crate) block_attempted: Option<u64>,
+}
+
+impl DropContext {
+    pub(crate) fn capture(
+        state: &StableState,
+        tx_id: TxId,
+        sender_override: Option<[u8; 20]>,
+        nonce_override: Option<u64>,
+        block_attempted: Option<u64>,
+    ) -> Self {
+        let pending: Option<SenderNonceKey> = state.pending_meta_by_tx_id.get(&tx_id);
+        Self {
+            sender: pending.map(|key| key.sender.0).or(sender_override),
+            nonce: pending.map(|key| key.nonce).or(nonce_override),
+            block_attempted,
+        }
+    }
+}
+
+/// payloadを消す前に呼ぶ。tx_store から kind・fee・eth hash を読み取って記録する。
+pub(crate) fn record(state: &mut StableState, tx_id: TxId, drop_code: u16, ctx: DropContext) {
+    let stored = state
+        .tx_store
+        .get(&tx_id)
+        .and_then(|envelope| StoredTx::try_from(envelope).ok());
+    let (kind, eth_tx_hash, caller_evm, max_fee_per_gas, max_priority_fee_per_gas) =
+        match stored.as_ref() {
+            Some(stored) => (
+                stored.kind.to_u8(),
+                (stored.kind == TxKind::EthSigned).then(|| hash::keccak256(&stored.raw)),
+                stored.caller_evm,
+                stored.max_fee_per_gas,
+                stored.max_priority_fee_per_gas,
+            ),
+            None => (0, None, None, 0, 0),
+        };
+    let now = crate::time::now_sec();
+    let mut meta = *state.drop_record_state.get();
+    if let Some(previous) = state.drop_records.get(&tx_id) {
+        state.drop_record_seq.remove(&previous.seq);
+    }
+    let seq = meta.next_seq;
+    let record = DropRecordV1 {
+        tx_id,
+        kind,
+        eth_tx_hash,
+        sender: ctx.sender.or(caller_evm),
+        nonce: ctx.nonce,
+        drop_code,
+        block_attempted: ctx.block_attempted,
+        dropped_at: now,
+        max_fee_per_gas,
+        max_priority_fee_per_gas,
+        base_fee: state.chain_state.get().base_fee,
+        seq,
+    };
+    state.drop_records.insert(tx_id, record);
+    state.drop_record_seq.insert(seq, tx_id);
+    if let Some(eth_hash) = eth_tx_hash {
+        state.drop_records_by_eth_hash.insert(TxId(eth_hash), tx_id);
+    }
+    meta.next_seq = seq.saturating_add(1);
+    state.drop_record_state.set(meta);
+    evict_expired(state, now, DROP_RECORD_EVICT_PER_INSERT);
+}
+
+/// 保持件数・保持期間を超えた古い記録を最大 `max_ops` 件消す。消した件数を返す。
+pub(crate) fn evict_expired(state: &mut StableState, now: u64, max_ops: usize) -> usize {
+    let mut meta = *state.drop_record_state.get();
+    let mut removed = 0usize;
+    while removed < max_ops {
+        let Some(entry) = state.drop_record_seq.iter().next() else {
+            break;
+        };
+        let (seq, tx_id) = (*entry.key(), entry.value());
+        let record = state
+            .drop_records
+            .get(&tx_id)
+            .filter(|record| record.seq == seq);
+        // 同じtx_idで再記録された場合、古いseqは索引だけ消す。
+        let oldest_dropped_at = record.map(|record| record.dropped_at).unwrap_or(0);
+        if !verified_core::dropped_ring::drop_record_should_evict(
+            state.drop_record_seq.len(),
+            meta.max_records,
+            oldest_dropped_at,
+            now,
+            meta.retain_secs,
+        ) {
+            break;
+        }
+        state.drop_record_seq.remove(&seq);
+        if let Some(record) = record {
+            state.drop_records.remove(&tx_id);
+            if let Some(eth_hash) = record.eth_tx_hash {
+                let key = TxId(eth_hash);
+                if state.drop_records_by_eth_hash.get(&key) == Some(tx_id) {
+                    state.drop_records_by_eth_hash.remove(&key);
+                }
+            }
+        }
+        meta.pruned_total = meta.pruned_total.saturating_add(1);
+        removed += 1;
+    }
+    meta.oldest_seq = state
+        .drop_record_seq
+        .iter()
+        .next()
+        .map(|entry| *entry.key())
+        .unwrap_or(meta.next_seq);
+    state.drop_record_state.set(meta);
+    removed
+}
+
+pub(crate) fn get(state: &StableState, tx_id: &TxId) -> Option<DropRecordV1> {
+    state.drop_records.get(tx_id)
+}
+
+pub(crate) fn get_by_eth_hash(state: &StableState, eth_tx_hash: &[u8; 32]) -> Option<DropRecordV1> {
+    let tx_id = state.drop_records_by_eth_hash.get(&TxId(*eth_tx_hash))?;
+    state.drop_records.get(&tx_id)
+}
@@ -232,7 +232,7 @@ impl BlockPayloads {
             .ok_or(ExportError::MissingData("block missing"))?;
         let block_bytes = state
             .blob_store
-            .read(&block_ptr)
+            .read_decoded(&block_ptr)
             .map_err(|_| ExportError::MissingData("block bytes missing"))?;
         let block = BlockData::from_bytes(Cow::Borrowed(&block_bytes));
         let _ = checked_segment_len(block_bytes.len(), 0)?;
@@ -311,7 +311,7 @@ fn build_internal_traces_payload(
         };
         let bytes = state
             .blob_store
-            .read(&ptr)
+            .read_decoded(&ptr)
             .map_err(|_| ExportError::MissingData("internal_traces bytes missing"))?;
         let traces = InternalTraceSet::from_bytes(Cow::Owned(bytes));
         let encoded = encode_internal_trace_entries(&traces)?;
@@ -391,7 +391,7 @@ fn build_receipts_payload(
             .ok_or(ExportError::MissingData("receipt missing"))?;
         let bytes = state
             .blob_store
-            .read(&ptr)
+            .read_decoded(&ptr)
             .map_err(|_| ExportError::MissingData("receipt bytes missing"))?;
         let receipt = ReceiptLike::from_bytes(Cow::Owned(bytes));
         if receipt.tx_id != *tx_id || receipt.tx_id.0 == [0u8; 32] {
@@ -2,7 +2,7 @@
 
 use alloy_primitives::keccak256 as alloy_keccak256;
 use alloy_primitives::Keccak256;
-use evm_db::chain_data::TxKind;
+use evm_db::chain_data::{ReceiptLike, TxKind};
 pub use ic_evm_address::derive_evm_address_from_principal;
 
 pub const HASH_LEN: usize = 32;
@@ -76,3 +76,40 @@ pub fn block_hash(
     buf.extend_from_slice(&state_root);
     keccak256(&buf)
 }
+
+/// certified data に載せる receipt の要約。return_data 本体は return_data_hash が代表する。
+pub fn receipt_hash(receipt: &ReceiptLike) -> [u8; HASH_LEN] {
+    let mut buf = Vec::with_capacity(1 + HASH_LEN + 8 + 4 + 1 + 8 + 8 + 16 * 3 + HASH_LEN + 21 + 4);
+    buf.push(0x02);
+    buf.extend_from_slice(&receipt.tx_id.0);
+    buf.extend_from_slice(&receipt.block_number.to_be_bytes());
+    buf.extend_from_slice(&receipt.tx_index.to_be_bytes());
+    buf.push(receipt.status);
+    buf.extend_from_slice(&receipt.gas_used.to_be_bytes());
+    buf.extend_from_slice(&receipt.effective_gas_price.to_be_bytes());
+    buf.extend_from_slice(&receipt.l1_data_fee.to_be_bytes());
+    buf.extend_from_slice(&receipt.operator_fee.to_be_bytes());
+    buf.extend_from_slice(&receipt.total_fee.to_be_bytes());
+    buf.extend_from_slice(&receipt.return_data_hash);
+    match receipt.contract_address {
+        Some(address) => {
+            buf.push(1);
+            buf.extend_from_slice(&address);
+        }
+        None => buf.push(0),
+    }
+    let log_count = u32::try_from(receipt.logs.len()).unwrap_or(u32::MAX);
+    buf.extend_from_slice(&log_count.to_be_bytes());
+    for log in receipt.logs.iter() {
+        buf.extend_from_slice(log.address.as_slice());
+        let topics = log.data.topics();
+        buf.push(u8::try_from(topics.len()).unwrap_or(u8::MAX));
+        for topic in topics.iter() {
+            buf.extend_from_slice(topic.as_slice());
+        }
+        let data_len = u32::try_from(log.data.data.len()).unwrap_or(u32::MAX);
+        buf.extend_from_slice(&data_len.to_be_bytes());
+        buf.extend_from_slice(&log.data.data);
+    }
+    keccak256(&buf)
+}
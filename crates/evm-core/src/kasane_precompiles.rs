@@ -3,7 +3,9 @@
 use crate::hash;
 use evm_db::chain_data::constants::{CHAIN_ID, MAX_LOG_DATA};
 use evm_db::chain_data::receipt::LogEntry;
-use evm_db::stable_state::current_runtime_config;
+use evm_db::stable_state::{current_runtime_config, with_state};
+use evm_db::types::keys::make_storage_key;
+use evm_db::upgrade::PrecompileProfileCheckpoint;
 use revm::{
     context::Cfg,
     context_interface::{
@@ -46,17 +48,30 @@ const _: () = assert!(
 const COMPACT_UNWRAP_FORMAT_VERSION: u8 = 1;
 const COMPACT_NATIVE_WITHDRAW_FORMAT_VERSION: u8 = 1;
 const COMPACT_ICP_PRECOMPILE_FORMAT_VERSION: u8 = 1;
+/// unwrap / native withdraw の版 2 は、版 1 の末尾に ICRC-1 subaccount を 32byte 固定で足す。
+const COMPACT_SUBACCOUNT_FORMAT_VERSION: u8 = 2;
+const COMPACT_SUBACCOUNT_LEN: usize = 32;
+/// NFT unwrap は同じ precompile の版 3 として受け、subaccount 欄を常に持つ固定長にする。
+const COMPACT_NFT_UNWRAP_FORMAT_VERSION: u8 = 3;
 const ICP_QUERY_KIND_QUERY: u8 = 0;
 const ICP_PRECOMPILE_KIND_UPDATE: u8 = 1;
 const COMPACT_PRINCIPAL_FIELD_LEN: usize = 1 + MAX_PRINCIPAL_LEN;
 const COMPACT_UNWRAP_INPUT_LEN: usize = 1 + COMPACT_PRINCIPAL_FIELD_LEN * 2 + 32;
 const COMPACT_NATIVE_WITHDRAW_INPUT_LEN: usize = 1 + COMPACT_PRINCIPAL_FIELD_LEN;
+const COMPACT_NFT_UNWRAP_INPUT_LEN: usize =
+    1 + COMPACT_PRINCIPAL_FIELD_LEN * 2 + 32 + COMPACT_SUBACCOUNT_LEN;
 const ABI_DYNAMIC_FIELDS: usize = 2;
 const NATIVE_WITHDRAW_FIELDS: usize = 2;
 const WRAP_FACTORY_STORAGE_TOKEN_BY_ASSET_KEY_SLOT: u64 = 0;
 const WRAPPED_TOKEN_TOTAL_SUPPLY_SLOT: u64 = 2;
 const WRAPPED_TOKEN_BALANCE_OF_SLOT: u64 = 3;
 const WRAPPED_TOKEN_ALLOWANCE_SLOT: u64 = 4;
+const WRAP_FACTORY_STORAGE_NFT_BY_COLLECTION_KEY_SLOT: u64 = 2;
+const WRAPPED_NFT_TOTAL_SUPPLY_SLOT: u64 = 2;
+const WRAPPED_NFT_OWNER_OF_SLOT: u64 = 3;
+const WRAPPED_NFT_BALANCE_OF_SLOT: u64 = 4;
+const WRAPPED_NFT_GET_APPROVED_SLOT: u64 = 5;
+const WRAPPED_NFT_IS_APPROVED_FOR_ALL_SLOT: u64 = 6;
 const UNWRAP_BURN_GAS_SURCHARGE: u64 = 45_000;
 pub const WEI_PER_E8S: u128 = 10_000_000_000;
 const FIXED_PRECOMPILE_GAS_RATIO_NUMERATOR: u32 = 1;
@@ -99,12 +114,24 @@ pub struct UnwrapIntent {
     pub asset_id: Vec<u8>,
     pub amount: [u8; 32],
     pub recipient: Vec<u8>,
+    /// 全 0 の subaccount は既定口座と同じなので None に正規化する
+    pub recipient_subaccount: Option<[u8; 32]>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct NftUnwrapIntent {
+    pub collection_id: Vec<u8>,
+    /// ICRC-7 の token id（Nat）を 32byte big-endian で保持する
+    pub token_id: [u8; 32],
+    pub recipient: Vec<u8>,
+    pub recipient_subaccount: Option<[u8; 32]>,
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct NativeWithdrawIntent {
     pub amount_e8s: [u8; 32],
     pub recipient: Vec<u8>,
+    pub recipient_subaccount: Option<[u8; 32]>,
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -376,18 +403,29 @@ fn run_wrap_precompile<CTX: ContextTr>(
     }
 
     let input = inputs.input.bytes(context);
-    let parsed = match parse_input(&input) {
-        Ok(v) => v,
-        Err(code) => return precompile_fail(context, gas_limit, code),
+    let (log_data, topic0) = if input.first() == Some(&COMPACT_NFT_UNWRAP_FORMAT_VERSION) {
+        let parsed = match parse_nft_unwrap_input(&input) {
+            Ok(v) => v,
+            Err(code) => return precompile_fail(context, gas_limit, code),
+        };
+        if let Err(code) = burn_wrapped_nft(context, unwrap_owner(inputs), &parsed) {
+            return precompile_fail(context, gas_limit, &code);
+        }
+        (encode_nft_log_data(&parsed), nft_unwrap_event_topic0())
+    } else {
+        let parsed = match parse_input(&input) {
+            Ok(v) => v,
+            Err(code) => return precompile_fail(context, gas_limit, code),
+        };
+        if let Err(code) = burn_wrapped_asset(context, unwrap_owner(inputs), &parsed) {
+            return precompile_fail(context, gas_limit, &code);
+        }
+        (encode_log_data(&parsed), wrap_event_topic0())
     };
-    if let Err(code) = burn_wrapped_asset(context, unwrap_owner(inputs), &parsed) {
-        return precompile_fail(context, gas_limit, &code);
-    }
-    let log_data = encode_log_data(&parsed);
     let log_data_len = log_data.len();
     let log = Log::new_unchecked(
         WRAP_PRECOMPILE_ADDRESS,
-        vec![B256::from(wrap_event_topic0())],
+        vec![B256::from(topic0)],
         log_data.into(),
     );
     context.journal_mut().log(log);
@@ -431,7 +469,7 @@ fn run_native_withdraw_precompile<CTX: ContextTr>(
     }
 
     let input = inputs.input.bytes(context);
-    let recipient = match parse_native_withdraw_input(&input) {
+    let (recipient, recipient_subaccount) = match parse_native_withdraw_input(&input) {
         Ok(v) => v,
         Err(code) => return precompile_fail(context, gas_limit, code),
     };
@@ -445,6 +483,7 @@ fn run_native_withdraw_precompile<CTX: ContextTr>(
     let parsed = NativeWithdrawIntent {
         amount_e8s: amount_e8s.to_be_bytes(),
         recipient,
+        recipient_subaccount,
     };
     let log_data = encode_native_withdraw_log_data(&parsed);
     let log_data_len = log_data.len();
@@ -628,16 +667,23 @@ fn parse_input(input: &[u8]) -> Result<UnwrapIntent, &'static str> {
 }
 
 fn parse_compact_input(input: &[u8]) -> Result<UnwrapIntent, &'static str> {
-    if input.len() != COMPACT_UNWRAP_INPUT_LEN {
-        return Err("wrap.arg.abi_invalid");
-    }
-    if input[0] != COMPACT_UNWRAP_FORMAT_VERSION {
-        return Err("wrap.arg.abi_invalid");
-    }
+    let has_subaccount = compact_input_has_subaccount(
+        input,
+        COMPACT_UNWRAP_FORMAT_VERSION,
+        COMPACT_UNWRAP_INPUT_LEN,
+    )
+    .ok_or("wrap.arg.abi_invalid")?;
     let mut offset = 1usize;
     let asset_id = read_compact_principal(input, &mut offset)?;
     let amount = read_array_32(input, &mut offset).ok_or("wrap.arg.amount_invalid")?;
     let recipient = read_compact_principal(input, &mut offset)?;
+    let recipient_subaccount = if has_subaccount {
+        read_array_32(input, &mut offset)
+            .map(normalize_subaccount)
+            .ok_or("wrap.arg.abi_invalid")?
+    } else {
+        None
+    };
     if offset != input.len() {
         return Err("wrap.arg.abi_invalid");
     }
@@ -645,25 +691,77 @@ fn parse_compact_input(input: &[u8]) -> Result<UnwrapIntent, &'static str> {
         asset_id,
         amount,
         recipient,
+        recipient_subaccount,
     })
 }
 
-fn parse_native_withdraw_input(input: &[u8]) -> Result<Vec<u8>, &'static str> {
-    if input.len() != COMPACT_NATIVE_WITHDRAW_INPUT_LEN {
-        return Err("native_withdraw.arg.abi_invalid");
+fn parse_nft_unwrap_input(input: &[u8]) -> Result<NftUnwrapIntent, &'static str> {
+    if input.first() != Some(&COMPACT_NFT_UNWRAP_FORMAT_VERSION)
+        || input.len() != COMPACT_NFT_UNWRAP_INPUT_LEN
+    {
+        return Err("wrap.arg.abi_invalid");
     }
-    if input[0] != COMPACT_NATIVE_WITHDRAW_FORMAT_VERSION {
-        return Err("native_withdraw.arg.abi_invalid");
+    let mut offset = 1usize;
+    let collection_id = read_compact_principal(input, &mut offset)?;
+    let token_id = read_array_32(input, &mut offset).ok_or("wrap.arg.token_id_invalid")?;
+    let recipient = read_compact_principal(input, &mut offset)?;
+    let recipient_subaccount = read_array_32(input, &mut offset)
+        .map(normalize_subaccount)
+        .ok_or("wrap.arg.abi_invalid")?;
+    if offset != input.len() {
+        return Err("wrap.arg.abi_invalid");
     }
+    Ok(NftUnwrapIntent {
+        collection_id,
+        token_id,
+        recipient,
+        recipient_subaccount,
+    })
+}
+
+fn parse_native_withdraw_input(input: &[u8]) -> Result<(Vec<u8>, Option<[u8; 32]>), &'static str> {
+    let has_subaccount = compact_input_has_subaccount(
+        input,
+        COMPACT_NATIVE_WITHDRAW_FORMAT_VERSION,
+        COMPACT_NATIVE_WITHDRAW_INPUT_LEN,
+    )
+    .ok_or("native_withdraw.arg.abi_invalid")?;
     let mut offset = 1usize;
     let recipient = read_compact_principal(input, &mut offset)?;
     if recipient == [4u8] {
         return Err("native_withdraw.recipient_anonymous");
     }
+    let recipient_subaccount = if has_subaccount {
+        read_array_32(input, &mut offset)
+            .map(normalize_subaccount)
+            .ok_or("native_withdraw.arg.abi_invalid")?
+    } else {
+        None
+    };
     if offset != input.len() {
         return Err("native_withdraw.arg.abi_invalid");
     }
-    Ok(recipient)
+    Ok((recipient, recipient_subaccount))
+}
+
+/// 版 1 は固定長そのまま、版 2 は末尾に subaccount が付いた長さだけを受け入れる。
+fn compact_input_has_subaccount(input: &[u8], v1_version: u8, v1_len: usize) -> Option<bool> {
+    match input.first().copied()? {
+        version if version == v1_version && input.len() == v1_len => Some(false),
+        COMPACT_SUBACCOUNT_FORMAT_VERSION
+            if input.len() == v1_len.checked_add(COMPACT_SUBACCOUNT_LEN)? =>
+        {
+            Some(true)
+        }
+        _ => None,
+    }
+}
+
+fn normalize_subaccount(subaccount: [u8; 32]) -> Option<[u8; 32]> {
+    subaccount
+        .iter()
+        .any(|&byte| byte != 0)
+        .then_some(subaccount)
 }
 
 fn parse_icp_query_input(input: &[u8]) -> Result<IcpQueryRequest, &'static str> {
@@ -796,7 +894,12 @@ fn burn_wrapped_asset<CTX: ContextTr>(
     let factory = current_wrap_factory_address();
     let amount = U256::from_be_bytes(intent.amount);
     let asset_key = compute_asset_key(intent.asset_id.as_slice());
-    let token_address = load_factory_token_address(context, factory, asset_key)?;
+    let token_address = load_factory_token_address(
+        context,
+        factory,
+        asset_key,
+        WRAP_FACTORY_STORAGE_TOKEN_BY_ASSET_KEY_SLOT,
+    )?;
     if token_address == Address::ZERO {
         return Err("unwrap.token_not_deployed".to_string());
     }
@@ -860,6 +963,97 @@ fn burn_wrapped_asset<CTX: ContextTr>(
     Ok(())
 }
 
+/// ERC-721 の burn も precompile 内で完結させる。owner 本人の呼び出しに加え、
+/// fungible の allowance と同じく factory への approve / setApprovalForAll を要求する。
+fn burn_wrapped_nft<CTX: ContextTr>(
+    context: &mut CTX,
+    owner: Address,
+    intent: &NftUnwrapIntent,
+) -> Result<(), String> {
+    let factory = current_wrap_factory_address();
+    let collection_key = compute_collection_key(intent.collection_id.as_slice());
+    let token_address = load_factory_token_address(
+        context,
+        factory,
+        collection_key,
+        WRAP_FACTORY_STORAGE_NFT_BY_COLLECTION_KEY_SLOT,
+    )?;
+    if token_address == Address::ZERO {
+        return Err("unwrap.token_not_deployed".to_string());
+    }
+
+    let owner_slot = mapping_slot(
+        B256::from(intent.token_id),
+        U256::from(WRAPPED_NFT_OWNER_OF_SLOT),
+    );
+    let approved_slot = mapping_slot(
+        B256::from(intent.token_id),
+        U256::from(WRAPPED_NFT_GET_APPROVED_SLOT),
+    );
+    let operator_slot =
+        nested_address_mapping_slot(owner, factory, WRAPPED_NFT_IS_APPROVED_FOR_ALL_SLOT);
+    let balance_slot = address_mapping_slot(owner, WRAPPED_NFT_BALANCE_OF_SLOT);
+    let total_supply_slot = U256::from(WRAPPED_NFT_TOTAL_SUPPLY_SLOT);
+    {
+        let mut token_account = context
+            .journal_mut()
+            .load_account_mut(token_address)
+            .map_err(|err| format!("wrap.burn.account_load_failed:{err:?}"))?;
+        let token = &mut token_account.data;
+        let mut sload = |slot: U256| {
+            token
+                .sload(slot, false)
+                .map(|value| value.data.present_value())
+                .map_err(|err| format!("wrap.burn.storage_read_failed:{err:?}"))
+        };
+        let current_owner = address_from_word(sload(owner_slot)?);
+        if current_owner == Address::ZERO {
+            return Err("erc721.nonexistent_token".to_string());
+        }
+        if current_owner != owner {
+            return Err("erc721.incorrect_owner".to_string());
+        }
+        let approved = address_from_word(sload(approved_slot)?);
+        let operator_approved = !sload(operator_slot)?.is_zero();
+        if approved != factory && !operator_approved {
+            return Err("erc721.insufficient_approval".to_string());
+        }
+        let next_balance = sload(balance_slot)?
+            .checked_sub(U256::from(1u8))
+            .ok_or_else(|| "erc721.insufficient_balance".to_string())?;
+        let next_total_supply = sload(total_supply_slot)?
+            .checked_sub(U256::from(1u8))
+            .ok_or_else(|| "erc721.insufficient_balance".to_string())?;
+        for (slot, value) in [
+            (approved_slot, U256::ZERO),
+            (owner_slot, U256::ZERO),
+            (balance_slot, next_balance),
+            (total_supply_slot, next_total_supply),
+        ] {
+            token
+                .sstore(slot, value, false)
+                .map_err(|err| format!("wrap.burn.storage_write_failed:{err:?}"))?;
+        }
+    }
+    let log = Log::new_unchecked(
+        token_address,
+        vec![
+            B256::from(transfer_event_topic0()),
+            topic_from_address(owner),
+            topic_from_address(Address::ZERO),
+            B256::from(intent.token_id),
+        ],
+        Bytes::new(),
+    );
+    context.journal_mut().log(log);
+    Ok(())
+}
+
+fn address_from_word(word: U256) -> Address {
+    let raw = word.to_be_bytes::<32>();
+    Address::from_slice(&raw[12..])
+}
+
 fn current_wrap_factory_address() -> Address {
     let raw = current_runtime_config()
         .wrap_factory_address()
@@ -867,6 +1061,47 @@ fn current_wrap_factory_address() -> Address {
     Address::new(raw)
 }
 
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct WrappedTokenSupply {
+    pub token: [u8; 20],
+    pub total_supply: [u8; 32],
+}
+
+/// factory が asset に割り当てた wrapped token と、その totalSupply を確定済み state から読む。
+pub fn wrapped_token_supply(asset_id: &[u8]) -> Result<Option<WrappedTokenSupply>, String> {
+    let factory = current_runtime_config()
+        .wrap_factory_address()
+        .map_err(str::to_string)?;
+    let token_slot = mapping_slot(
+        B256::from(compute_asset_key(asset_id)),
+        U256::from(WRAP_FACTORY_STORAGE_TOKEN_BY_ASSET_KEY_SLOT),
+    );
+    let word = read_committed_storage(factory, token_slot.to_be_bytes::<32>());
+    let mut token = [0u8; 20];
+    token.copy_from_slice(&word[12..]);
+    if token == [0u8; 20] {
+        return Ok(None);
+    }
+    let supply = read_committed_storage(
+        token,
+        U256::from(WRAPPED_TOKEN_TOTAL_SUPPLY_SLOT).to_be_bytes::<32>(),
+    );
+    Ok(Some(WrappedTokenSupply {
+        token,
+        total_supply: supply,
+    }))
+}
+
+fn read_committed_storage(address: [u8; 20], slot: [u8; 32]) -> [u8; 32] {
+    with_state(|state| {
+        state
+            .storage
+            .get(&make_storage_key(address, slot))
+            .map(|value| value.0)
+            .unwrap_or([0u8; 32])
+    })
+}
+
 fn compute_asset_key(asset_id: &[u8]) -> [u8; 32] {
     let mut payload = Vec::with_capacity(14 + 32 + asset_id.len());
     payload.extend_from_slice(b"kasane.wrap.v1");
@@ -875,15 +1110,21 @@ fn compute_asset_key(asset_id: &[u8]) -> [u8; 32] {
     hash::keccak256(&payload)
 }
 
+fn compute_collection_key(collection_id: &[u8]) -> [u8; 32] {
+    let mut payload = Vec::with_capacity(18 + 32 + collection_id.len());
+    payload.extend_from_slice(b"kasane.wrap.nft.v1");
+    payload.extend_from_slice(&U256::from(CHAIN_ID).to_be_bytes::<32>());
+    payload.extend_from_slice(collection_id);
+    hash::keccak256(&payload)
+}
+
 fn load_factory_token_address<CTX: ContextTr>(
     context: &mut CTX,
     factory: Address,
-    asset_key: [u8; 32],
+    key: [u8; 32],
+    mapping_base_slot: u64,
 ) -> Result<Address, String> {
-    let slot = mapping_slot(
-        B256::from(asset_key),
-        U256::from(WRAP_FACTORY_STORAGE_TOKEN_BY_ASSET_KEY_SLOT),
-    );
+    let slot = mapping_slot(B256::from(key), U256::from(mapping_base_slot));
     let mut factory_account = context
         .journal_mut()
         .load_account_mut(factory)
@@ -914,10 +1155,14 @@ fn address_mapping_slot(key: Address, slot: u64) -> U256 {
 }
 
 fn allowance_slot(owner: Address, spender: Address) -> U256 {
-    let outer = address_mapping_slot(owner, WRAPPED_TOKEN_ALLOWANCE_SLOT);
-    let mut spender_bytes = [0u8; 32];
-    spender_bytes[12..].copy_from_slice(spender.as_slice());
-    mapping_slot(B256::from(spender_bytes), outer)
+    nested_address_mapping_slot(owner, spender, WRAPPED_TOKEN_ALLOWANCE_SLOT)
+}
+
+fn nested_address_mapping_slot(outer_key: Address, inner_key: Address, slot: u64) -> U256 {
+    let outer = address_mapping_slot(outer_key, slot);
+    let mut inner_bytes = [0u8; 32];
+    inner_bytes[12..].copy_from_slice(inner_key.as_slice());
+    mapping_slot(B256::from(inner_bytes), outer)
 }
 
 fn emit_approval_log<CTX: ContextTr>(
@@ -971,14 +1216,35 @@ fn encode_log_data(intent: &UnwrapIntent) -> Vec<u8> {
     out.extend_from_slice(&intent.amount);
     out.push(intent.recipient.len() as u8);
     out.extend_from_slice(&intent.recipient);
+    if let Some(subaccount) = intent.recipient_subaccount.as_ref() {
+        out.extend_from_slice(subaccount);
+    }
+    out
+}
+
+fn encode_nft_log_data(intent: &NftUnwrapIntent) -> Vec<u8> {
+    let mut out = Vec::with_capacity(
+        2 + intent.collection_id.len() + 32 + intent.recipient.len() + COMPACT_SUBACCOUNT_LEN,
+    );
+    out.push(intent.collection_id.len() as u8);
+    out.extend_from_slice(&intent.collection_id);
+    out.extend_from_slice(&intent.token_id);
+    out.push(intent.recipient.len() as u8);
+    out.extend_from_slice(&intent.recipient);
+    if let Some(subaccount) = intent.recipient_subaccount.as_ref() {
+        out.extend_from_slice(subaccount);
+    }
     out
 }
 
 fn encode_native_withdraw_log_data(intent: &NativeWithdrawIntent) -> Vec<u8> {
-    let mut out = Vec::with_capacity(32 + 1 + intent.recipient.len());
+    let mut out = Vec::with_capacity(32 + 1 + intent.recipient.len() + COMPACT_SUBACCOUNT_LEN);
     out.extend_from_slice(&intent.amount_e8s);
     out.push(intent.recipient.len() as u8);
     out.extend_from_slice(&intent.recipient);
+    if let Some(subaccount) = intent.recipient_subaccount.as_ref() {
+        out.extend_from_slice(subaccount);
+    }
     out
 }
 
@@ -1007,6 +1273,7 @@ pub fn unwrap_intent_from_log(log: &LogEntry) -> Option<UnwrapIntent> {
     let asset_id = read_len_prefixed(data, &mut offset)?;
     let amount = read_array_32(data, &mut offset)?;
     let recipient = read_len_prefixed(data, &mut offset)?;
+    let recipient_subaccount = read_log_subaccount(data, &mut offset)?;
     if offset != data.len() {
         return None;
     }
@@ -1014,6 +1281,32 @@ pub fn unwrap_intent_from_log(log: &LogEntry) -> Option<UnwrapIntent> {
         asset_id,
         amount,
         recipient,
+        recipient_subaccount,
+    })
+}
+
+pub fn nft_unwrap_intent_from_log(log: &LogEntry) -> Option<NftUnwrapIntent> {
+    if log.address.into_array() != WRAP_PRECOMPILE_ADDRESS.into_array() {
+        return None;
+    }
+    let topics = log.topics();
+    if topics.len() != 1 || topics[0].0 != nft_unwrap_event_topic0() {
+        return None;
+    }
+    let data = log.data.data.as_ref();
+    let mut offset = 0usize;
+    let collection_id = read_len_prefixed(data, &mut offset)?;
+    let token_id = read_array_32(data, &mut offset)?;
+    let recipient = read_len_prefixed(data, &mut offset)?;
+    let recipient_subaccount = read_log_subaccount(data, &mut offset)?;
+    if offset != data.len() {
+        return None;
+    }
+    Some(NftUnwrapIntent {
+        collection_id,
+        token_id,
+        recipient,
+        recipient_subaccount,
     })
 }
 
@@ -1029,15 +1322,25 @@ pub fn native_withdraw_intent_from_log(log: &LogEntry) -> Option<NativeWithdrawI
     let mut offset = 0usize;
     let amount_e8s = read_array_32(data, &mut offset)?;
     let recipient = read_len_prefixed(data, &mut offset)?;
+    let recipient_subaccount = read_log_subaccount(data, &mut offset)?;
     if offset != data.len() {
         return None;
     }
     Some(NativeWithdrawIntent {
         amount_e8s,
         recipient,
+        recipient_subaccount,
     })
 }
 
+/// subaccount 無しの log は版 1 と同じ形のまま残るので、末尾 0byte なら None として読む。
+fn read_log_subaccount(data: &[u8], offset: &mut usize) -> Option<Option<[u8; 32]>> {
+    if *offset == data.len() {
+        return Some(None);
+    }
+    read_array_32(data, offset).map(normalize_subaccount)
+}
+
 pub fn icp_update_intent_from_log(log: &LogEntry) -> Option<IcpUpdateIntent> {
     if log.address.into_array() != ICP_UPDATE_INTENT_PRECOMPILE_ADDRESS.into_array() {
         return None;
@@ -1075,6 +1378,10 @@ fn wrap_event_topic0() -> [u8; 32] {
     hash::keccak256(b"KasaneUnwrapRequest(bytes)")
 }
 
+fn nft_unwrap_event_topic0() -> [u8; 32] {
+    hash::keccak256(b"KasaneNftUnwrapRequest(bytes)")
+}
+
 fn native_withdraw_event_topic0() -> [u8; 32] {
     hash::keccak256(b"KasaneNativeWithdrawalRequest(bytes)")
 }
@@ -1248,6 +1555,42 @@ pub fn precompile_profile_snapshot() -> Vec<PrecompileProfileEntry> {
     })
 }
 
+pub fn precompile_profile_checkpoint() -> Vec<PrecompileProfileCheckpoint> {
+    PRECOMPILE_PROFILE_ACC.with(|map| {
+        map.borrow()
+            .iter()
+            .map(|(address, acc)| PrecompileProfileCheckpoint {
+                address: *address,
+                calls: acc.calls,
+                total_instructions: acc.total_instructions,
+                max_instructions: acc.max_instructions,
+                total_extra_gas: acc.total_extra_gas,
+                max_extra_gas: acc.max_extra_gas,
+            })
+            .collect()
+    })
+}
+
+/// upgrade 前の集計を引き継ぐ。計測中の値は上書きする。
+pub fn restore_precompile_profile(entries: Vec<PrecompileProfileCheckpoint>) {
+    PRECOMPILE_PROFILE_ACC.with(|map| {
+        let mut map = map.borrow_mut();
+        map.clear();
+        for entry in entries {
+            map.insert(
+                entry.address,
+                PrecompileProfileAccumulator {
+                    calls: entry.calls,
+                    total_instructions: entry.total_instructions,
+                    max_instructions: entry.max_instructions,
+                    total_extra_gas: entry.total_extra_gas,
+                    max_extra_gas: entry.max_extra_gas,
+                },
+            );
+        }
+    });
+}
+
 pub fn clear_precompile_profile() {
     PRECOMPILE_PROFILE_ACC.with(|map| map.borrow_mut().clear());
 }
@@ -1,12 +1,16 @@
 use super::{
-    allowance_slot, approval_event_topic0, compute_asset_key, compute_extra_gas,
-    encode_icp_update_intent_log_data, estimate_wrap_precompile_gas,
+    allowance_slot, approval_event_topic0, compute_asset_key, compute_collection_key,
+    compute_extra_gas, encode_icp_update_intent_log_data, encode_log_data,
+    encode_native_withdraw_log_data, encode_nft_log_data, estimate_wrap_precompile_gas,
     extra_gas_by_instruction_ratio, extra_gas_for_precompile, icp_update_intent_event_topic0,
     icp_update_intent_from_log, native_value_to_e8s, native_withdraw_event_topic0,
-    native_withdraw_intent_from_log, parse_icp_query_input, parse_icp_update_intent_input,
-    parse_input, resolve_icp_query_reply, topic_from_address, transfer_event_topic0,
+    native_withdraw_intent_from_log, nft_unwrap_event_topic0, nft_unwrap_intent_from_log,
+    parse_icp_query_input, parse_icp_update_intent_input, parse_input, parse_native_withdraw_input,
+    parse_nft_unwrap_input, resolve_icp_query_reply, topic_from_address, transfer_event_topic0,
     unwrap_intent_from_log, unwrap_owner, with_icp_query_reply, wrap_event_topic0, IcpQueryReply,
-    COMPACT_ICP_PRECOMPILE_FORMAT_VERSION, COMPACT_UNWRAP_FORMAT_VERSION,
+    NativeWithdrawIntent, NftUnwrapIntent, UnwrapIntent, COMPACT_ICP_PRECOMPILE_FORMAT_VERSION,
+    COMPACT_NATIVE_WITHDRAW_FORMAT_VERSION, COMPACT_NFT_UNWRAP_FORMAT_VERSION,
+    COMPACT_NFT_UNWRAP_INPUT_LEN, COMPACT_SUBACCOUNT_FORMAT_VERSION, COMPACT_UNWRAP_FORMAT_VERSION,
     ICP_PRECOMPILE_KIND_UPDATE, ICP_QUERY_KIND_QUERY, ICP_UPDATE_INTENT_PRECOMPILE_ADDRESS,
     MAX_ICP_QUERY_ARG_LEN, MAX_ICP_UPDATE_ARG_LEN, MAX_PRINCIPAL_LEN, MAX_QUERY_METHOD_LEN,
     NATIVE_WITHDRAW_PRECOMPILE_ADDRESS, WEI_PER_E8S, WRAP_PRECOMPILE_ADDRESS,
@@ -338,6 +342,34 @@ fn unwrap_intent_log_roundtrip_decodes() {
     assert_eq!(parsed.recipient, recipient);
 }
 
+#[test]
+fn nft_unwrap_intent_log_roundtrip_uses_distinct_topic() {
+    let intent = NftUnwrapIntent {
+        collection_id: vec![4, 5, 6],
+        token_id: U256::from(42u64).to_be_bytes(),
+        recipient: vec![9, 10, 11],
+        recipient_subaccount: Some([0x11; 32]),
+    };
+    let log = log_entry_from_parts(
+        WRAP_PRECOMPILE_ADDRESS.into_array(),
+        vec![nft_unwrap_event_topic0()],
+        encode_nft_log_data(&intent),
+    );
+    assert_eq!(nft_unwrap_intent_from_log(&log), Some(intent.clone()));
+    // fungible の decoder は NFT log を取り込まない（逆も同様）。
+    assert_eq!(unwrap_intent_from_log(&log), None);
+    let fungible_log = log_entry_from_parts(
+        WRAP_PRECOMPILE_ADDRESS.into_array(),
+        vec![wrap_event_topic0()],
+        encode_nft_log_data(&intent),
+    );
+    assert_eq!(nft_unwrap_intent_from_log(&fungible_log), None);
+    assert_eq!(
+        nft_unwrap_event_topic0(),
+        hash::keccak256(b"KasaneNftUnwrapRequest(bytes)")
+    );
+}
+
 #[test]
 fn native_withdraw_intent_log_roundtrip_decodes() {
     let amount = U256::from(123u64).to_be_bytes();
@@ -356,6 +388,50 @@ fn native_withdraw_intent_log_roundtrip_decodes() {
     assert_eq!(parsed.recipient, recipient);
 }
 
+#[test]
+fn subaccount_intent_logs_roundtrip_and_keep_v1_shape_without_subaccount() {
+    let unwrap = UnwrapIntent {
+        asset_id: vec![4, 5, 6],
+        amount: [8u8; 32],
+        recipient: vec![9, 10, 11],
+        recipient_subaccount: Some([0x5a; 32]),
+    };
+    let log = log_entry_from_parts(
+        WRAP_PRECOMPILE_ADDRESS.into_array(),
+        vec![wrap_event_topic0()],
+        encode_log_data(&unwrap),
+    );
+    assert_eq!(unwrap_intent_from_log(&log), Some(unwrap.clone()));
+
+    let legacy = UnwrapIntent {
+        recipient_subaccount: None,
+        ..unwrap
+    };
+    assert_eq!(encode_log_data(&legacy).len(), 1 + 3 + 32 + 1 + 3);
+
+    let native = NativeWithdrawIntent {
+        amount_e8s: U256::from(123u64).to_be_bytes(),
+        recipient: vec![9, 10, 11],
+        recipient_subaccount: Some([0x6b; 32]),
+    };
+    let mut data = encode_native_withdraw_log_data(&native);
+    let log = log_entry_from_parts(
+        NATIVE_WITHDRAW_PRECOMPILE_ADDRESS.into_array(),
+        vec![native_withdraw_event_topic0()],
+        data.clone(),
+    );
+    assert_eq!(native_withdraw_intent_from_log(&log), Some(native));
+
+    // 32byte に満たない末尾は subaccount として読まない。
+    data.pop();
+    let log = log_entry_from_parts(
+        NATIVE_WITHDRAW_PRECOMPILE_ADDRESS.into_array(),
+        vec![native_withdraw_event_topic0()],
+        data,
+    );
+    assert_eq!(native_withdraw_intent_from_log(&log), None);
+}
+
 #[test]
 fn icp_update_intent_log_roundtrip_decodes() {
     let target = vec![1, 2, 3];
@@ -428,6 +504,83 @@ fn compact_decode_valid_input() {
     assert_eq!(parsed.recipient, vec![9, 10, 11]);
 }
 
+#[test]
+fn compact_decode_v2_carries_subaccount_and_normalizes_zero() {
+    let mut encoded = encode_compact(vec![4, 5, 6], [8u8; 32], vec![9, 10, 11]);
+    encoded[0] = COMPACT_SUBACCOUNT_FORMAT_VERSION;
+    encoded.extend_from_slice(&[0x11; 32]);
+    let parsed = parse_input(&encoded).expect("must decode");
+    assert_eq!(parsed.recipient, vec![9, 10, 11]);
+    assert_eq!(parsed.recipient_subaccount, Some([0x11; 32]));
+
+    let zero_len = encoded.len() - 32;
+    encoded[zero_len..].fill(0);
+    let parsed = parse_input(&encoded).expect("must decode");
+    assert_eq!(parsed.recipient_subaccount, None);
+
+    // 版 1 の長さに subaccount を足しただけでは受け入れない。
+    encoded[0] = COMPACT_UNWRAP_FORMAT_VERSION;
+    assert_eq!(parse_input(&encoded), Err("wrap.arg.abi_invalid"));
+}
+
+#[test]
+fn nft_compact_decode_requires_v3_fixed_length() {
+    let mut encoded = encode_compact(vec![4, 5, 6], [8u8; 32], vec![9, 10, 11]);
+    encoded[0] = COMPACT_NFT_UNWRAP_FORMAT_VERSION;
+    encoded.extend_from_slice(&[0x22; 32]);
+    assert_eq!(encoded.len(), COMPACT_NFT_UNWRAP_INPUT_LEN);
+    let parsed = parse_nft_unwrap_input(&encoded).expect("must decode");
+    assert_eq!(parsed.collection_id, vec![4, 5, 6]);
+    assert_eq!(parsed.token_id, [8u8; 32]);
+    assert_eq!(parsed.recipient, vec![9, 10, 11]);
+    assert_eq!(parsed.recipient_subaccount, Some([0x22; 32]));
+
+    let zero_len = encoded.len() - 32;
+    encoded[zero_len..].fill(0);
+    let parsed = parse_nft_unwrap_input(&encoded).expect("must decode");
+    assert_eq!(parsed.recipient_subaccount, None);
+
+    // 同じ長さでも fungible の版番号は NFT として扱わず、fungible parser も版 3 を拒否する。
+    assert_eq!(parse_input(&encoded), Err("wrap.arg.abi_invalid"));
+    encoded[0] = COMPACT_SUBACCOUNT_FORMAT_VERSION;
+    assert_eq!(
+        parse_nft_unwrap_input(&encoded),
+        Err("wrap.arg.abi_invalid")
+    );
+    encoded[0] = COMPACT_NFT_UNWRAP_FORMAT_VERSION;
+    encoded.truncate(zero_len);
+    assert_eq!(
+        parse_nft_unwrap_input(&encoded),
+        Err("wrap.arg.abi_invalid")
+    );
+}
+
+#[test]
+fn native_withdraw_compact_decode_accepts_v1_and_v2() {
+    let mut slot = vec![0u8; 1 + MAX_PRINCIPAL_LEN];
+    slot[0] = 3;
+    slot[1..4].copy_from_slice(&[9, 10, 11]);
+    let mut v1 = vec![COMPACT_NATIVE_WITHDRAW_FORMAT_VERSION];
+    v1.extend_from_slice(&slot);
+    assert_eq!(
+        parse_native_withdraw_input(&v1),
+        Ok((vec![9, 10, 11], None))
+    );
+
+    let mut v2 = vec![COMPACT_SUBACCOUNT_FORMAT_VERSION];
+    v2.extend_from_slice(&slot);
+    v2.extend_from_slice(&[0x22; 32]);
+    assert_eq!(
+        parse_native_withdraw_input(&v2),
+        Ok((vec![9, 10, 11], Some([0x22; 32])))
+    );
+    v2.pop();
+    assert_eq!(
+        parse_native_withdraw_input(&v2),
+        Err("native_withdraw.arg.abi_invalid")
+    );
+}
+
 #[test]
 fn compact_decode_rejects_non_zero_padding() {
     let mut encoded = encode_compact(vec![4, 5, 6], [8u8; 32], vec![9, 10, 11]);
@@ -506,6 +659,25 @@ fn compute_asset_key_matches_factory_domain_format() {
     );
 }
 
+#[test]
+fn compute_collection_key_uses_nft_domain() {
+    let mut chain_bytes = [0u8; 32];
+    chain_bytes[24..].copy_from_slice(&evm_db::chain_data::constants::CHAIN_ID.to_be_bytes());
+    let key = compute_collection_key(&[1, 2, 3]);
+    assert_eq!(
+        key,
+        hash::keccak256(
+            &[
+                b"kasane.wrap.nft.v1".as_slice(),
+                chain_bytes.as_slice(),
+                &[1, 2, 3],
+            ]
+            .concat()
+        )
+    );
+    assert_ne!(key, compute_asset_key(&[1, 2, 3]));
+}
+
 #[test]
 fn allowance_slot_uses_factory_as_spender() {
     let factory = [0x33u8; 20];
@@ -1,18 +1,28 @@
 //! どこで: evm-coreの入口 / 何を: Phase1の実行・ブロック生成の核 / なぜ: canisterから分離するため
 
+pub mod archive;
 pub mod base_fee;
+pub mod blob_compaction;
+pub mod blob_recompress;
 pub(crate) mod bytes;
 pub mod chain;
 pub mod commit;
 pub(crate) mod constants;
 pub mod db_adapter;
+pub(crate) mod drop_record;
 pub mod export;
 pub mod hash;
 pub mod kasane_precompiles;
+pub mod optimistic_exec;
 pub mod revm_db;
 pub mod revm_exec;
+pub mod schema_migration;
+pub mod scrub;
 pub mod selfdestruct;
+pub(crate) mod staged_block;
+pub mod state_proof;
 pub mod state_root;
+pub mod state_snapshot;
 pub(crate) mod time;
 pub(crate) mod trie_commit;
 pub mod tx_decode;
@@ -0,0 +1,268 @@
+//! どこで: produce_block の実行段 / 何を: snapshot事前実行と衝突検出による採否 / なぜ: 重いブロックを逐次実行と同じ結果のまま分割実行できるようにするため
+//!
+//! 事前実行は封印より前の timer tick で行い、結果は heap に置く。封印側のラウンドは
+//! head・状態epoch・ブロック文脈が一致するときだけ結果を使う。
+
+use crate::constants::FEE_RECIPIENT;
+use crate::kasane_precompiles::PrecompileAccess;
+use crate::revm_db::RevmStableDb;
+use crate::revm_exec::{
+    pre_execute_tx_on_snapshot, BlockExecContext, ExecError, ExecOutcome, PreExecution, StateDiff,
+};
+use evm_db::chain_data::TxId;
+use revm::context::TxEnv;
+use revm::database::CacheDB;
+use revm::database_interface::{Database, DatabaseRef};
+use revm::state::AccountInfo;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet};
+use verified_core::optimistic_exec::{
+    fee_recipient_conflicts, optimistic_commit_decision, read_set_conflicts,
+    OptimisticCommitDecision,
+};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BlockExecMode {
+    /// 候補を1件ずつ実行し、StateDiffを逐次commitする。
+    Sequential,
+    /// 前の tick で候補を同一snapshot上で事前実行し、衝突しない結果だけを順にcommitする。
+    Optimistic,
+}
+
+thread_local! {
+    // upgrade で失われても逐次実行へ戻るだけなので、stable には置かない。
+    static PRE_EXECUTED: RefCell<Option<PreExecutedBlock>> = const { RefCell::new(None) };
+}
+
+struct PreExecutedBlock {
+    parent_hash: [u8; 32],
+    evm_state_epoch: u64,
+    exec_ctx: BlockExecContext,
+    results: BTreeMap<TxId, Result<PreExecution, ExecError>>,
+    fee_recipient_snapshot: Option<AccountInfo>,
+}
+
+impl PreExecutedBlock {
+    fn matches(&self, parent_hash: [u8; 32], evm_state_epoch: u64) -> bool {
+        self.parent_hash == parent_hash && self.evm_state_epoch == evm_state_epoch
+    }
+}
+
+/// `next_budget` が None を返した時点で事前実行を打ち切る。残りは封印側で逐次実行する。
+pub(crate) fn pre_execute_block(
+    parent_hash: [u8; 32],
+    evm_state_epoch: u64,
+    exec_ctx: &BlockExecContext,
+    candidates: impl Iterator<Item = (TxId, TxEnv)>,
+    mut next_budget: impl FnMut(u64) -> Option<Option<u64>>,
+) -> u32 {
+    let fee_recipient_snapshot = RevmStableDb.basic_ref(FEE_RECIPIENT).ok().flatten();
+    let mut results = BTreeMap::new();
+    let mut pre_gas_used = 0u64;
+    for (position, (tx_id, tx_env)) in candidates.enumerate() {
+        let Some(budget) = next_budget(pre_gas_used) else {
+            break;
+        };
+        // 先行Txが全て取り込まれた場合の位置を仮置きし、採用時に付け替える。
+        let tx_index = u32::try_from(position).unwrap_or(u32::MAX);
+        let result = pre_execute_tx_on_snapshot(
+            tx_id,
+            tx_index,
+            tx_env,
+            exec_ctx,
+            budget,
+            PrecompileAccess::wrap_side_effects(),
+        );
+        if let Ok(pre) = result.as_ref() {
+            pre_gas_used = verified_core::block::add_block_gas_used(
+                pre_gas_used,
+                pre.outcome.receipt.gas_used,
+            );
+        }
+        results.insert(tx_id, result);
+    }
+    let pre_executed = u32::try_from(results.len()).unwrap_or(u32::MAX);
+    PRE_EXECUTED.with(|cell| {
+        *cell.borrow_mut() = Some(PreExecutedBlock {
+            parent_hash,
+            evm_state_epoch,
+            exec_ctx: exec_ctx.clone(),
+            results,
+            fee_recipient_snapshot,
+        });
+    });
+    pre_executed
+}
+
+/// head と状態が事前実行時のままなら、その時に決めたブロック文脈を返す。
+pub(crate) fn pre_executed_context(
+    parent_hash: [u8; 32],
+    evm_state_epoch: u64,
+) -> Option<BlockExecContext> {
+    PRE_EXECUTED.with(|cell| {
+        cell.borrow()
+            .as_ref()
+            .filter(|block| block.matches(parent_hash, evm_state_epoch))
+            .map(|block| block.exec_ctx.clone())
+    })
+}
+
+pub(crate) fn clear_pre_executed() {
+    PRE_EXECUTED.with(|cell| {
+        cell.borrow_mut().take();
+    });
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct OptimisticExecStats {
+    pub pre_executed: u32,
+    pub accepted: u32,
+    pub reexecuted: u32,
+}
+
+pub(crate) struct OptimisticBlockExec {
+    /// 同一ブロックで既にcommitされた Tx が書いたaddress。
+    written: BTreeSet<[u8; 20]>,
+    fee_recipient_snapshot: Option<AccountInfo>,
+    stats: OptimisticExecStats,
+}
+
+impl OptimisticBlockExec {
+    /// 前の tick の事前実行結果が今のブロックに使えるときだけ返す。使えない結果はここで捨てる。
+    pub(crate) fn attach(
+        parent_hash: [u8; 32],
+        evm_state_epoch: u64,
+        exec_ctx: &BlockExecContext,
+    ) -> Option<Self> {
+        PRE_EXECUTED.with(|cell| {
+            let mut cell = cell.borrow_mut();
+            let usable = cell.as_ref().is_some_and(|block| {
+                block.matches(parent_hash, evm_state_epoch)
+                    && block.exec_ctx.block_number == exec_ctx.block_number
+                    && block.exec_ctx.timestamp == exec_ctx.timestamp
+                    && block.exec_ctx.base_fee == exec_ctx.base_fee
+                    && block.exec_ctx.block_gas_limit == exec_ctx.block_gas_limit
+            });
+            if !usable {
+                cell.take();
+                return None;
+            }
+            let block = cell.as_ref()?;
+            Some(Self {
+                written: BTreeSet::new(),
+                fee_recipient_snapshot: block.fee_recipient_snapshot.clone(),
+                stats: OptimisticExecStats {
+                    pre_executed: u32::try_from(block.results.len()).unwrap_or(u32::MAX),
+                    ..OptimisticExecStats::default()
+                },
+            })
+        })
+    }
+
+    /// 事前実行結果を採用できれば、`exec_db` の現在値へ載せ替えて返す。
+    /// None の場合、呼び出し側は `exec_db` 上で再実行する。
+    pub(crate) fn take_accepted(
+        &mut self,
+        tx_id: TxId,
+        tx_index: u32,
+        exec_db: &mut CacheDB<RevmStableDb>,
+    ) -> Option<(ExecOutcome, StateDiff)> {
+        let result = PRE_EXECUTED.with(|cell| {
+            cell.borrow_mut()
+                .as_mut()
+                .and_then(|block| block.results.remove(&tx_id))
+        })?;
+        let fee_recipient = FEE_RECIPIENT.into_array();
+        let recipient_written = self.written.contains(&fee_recipient);
+        let (decision, pre) = match result {
+            Ok(pre) => {
+                let read_set: Vec<[u8; 20]> = pre
+                    .state_diff
+                    .keys()
+                    .map(|address| address.into_array())
+                    .collect();
+                // precompile は EVM 状態の外も読むため、tick をまたいだ結果は使わない。
+                let conflicts = pre.kasane_precompile_called
+                    || read_set_conflicts(&read_set, &self.written, fee_recipient);
+                let snapshot_balance = self
+                    .fee_recipient_snapshot
+                    .as_ref()
+                    .map(|info| info.balance)
+                    .unwrap_or_default();
+                let balance_decreased = pre
+                    .state_diff
+                    .get(&FEE_RECIPIENT)
+                    .is_some_and(|account| account.info.balance < snapshot_balance);
+                let recipient_conflicts = fee_recipient_conflicts(
+                    recipient_written,
+                    pre.fee_recipient_observed,
+                    self.fee_recipient_snapshot.is_some(),
+                    balance_decreased,
+                );
+                (
+                    optimistic_commit_decision(true, conflicts, recipient_conflicts),
+                    Some(pre),
+                )
+            }
+            Err(_) => (optimistic_commit_decision(false, false, false), None),
+        };
+        let pre = match (decision, pre) {
+            (OptimisticCommitDecision::Accept, Some(pre)) => pre,
+            _ => {
+                self.stats.reexecuted = self.stats.reexecuted.saturating_add(1);
+                return None;
+            }
+        };
+        let PreExecution {
+            mut outcome,
+            mut state_diff,
+            ..
+        } = pre;
+        if recipient_written {
+            self.rebase_fee_recipient(&mut state_diff, exec_db);
+        }
+        outcome.reassign_tx_index(tx_index);
+        self.stats.accepted = self.stats.accepted.saturating_add(1);
+        Some((outcome, state_diff))
+    }
+
+    /// commit済みの StateDiff から write set を更新する。
+    pub(crate) fn record_commit(&mut self, state_diff: &StateDiff) {
+        for (address, account) in state_diff.iter() {
+            if account.is_touched() {
+                self.written.insert(address.into_array());
+            }
+        }
+    }
+
+    pub(crate) fn stats(&self) -> OptimisticExecStats {
+        self.stats
+    }
+
+    // fee recipient は全Txが書くため、snapshotからの増分を現在値へ足し直す。
+    fn rebase_fee_recipient(
+        &self,
+        state_diff: &mut StateDiff,
+        exec_db: &mut CacheDB<RevmStableDb>,
+    ) {
+        let Some(account) = state_diff.get_mut(&FEE_RECIPIENT) else {
+            return;
+        };
+        if !account.is_touched() {
+            return;
+        }
+        let snapshot_balance = self
+            .fee_recipient_snapshot
+            .as_ref()
+            .map(|info| info.balance)
+            .unwrap_or_default();
+        let delta = account.info.balance.saturating_sub(snapshot_balance);
+        let current = exec_db
+            .basic(FEE_RECIPIENT)
+            .ok()
+            .flatten()
+            .unwrap_or_default();
+        account.info = current;
+        account.info.balance = account.info.balance.saturating_add(delta);
+    }
+}
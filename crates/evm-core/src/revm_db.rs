@@ -60,7 +60,7 @@ impl Database for RevmStableDb {
     fn block_hash(&mut self, number: u64) -> Result<B256, Self::Error> {
         let hash = evm_db::stable_state::with_state(|state| {
             if let Some(ptr) = state.blocks.get(&number) {
-                let bytes = state.blob_store.read(&ptr).ok()?;
+                let bytes = state.blob_store.read_decoded(&ptr).ok()?;
                 let block = evm_db::chain_data::BlockData::from_bytes(Cow::Owned(bytes));
                 return Some(block.block_hash);
             }
@@ -112,7 +112,7 @@ impl DatabaseRef for RevmStableDb {
     fn block_hash_ref(&self, number: u64) -> Result<B256, Self::Error> {
         let hash = evm_db::stable_state::with_state(|state| {
             if let Some(ptr) = state.blocks.get(&number) {
-                let bytes = state.blob_store.read(&ptr).ok()?;
+                let bytes = state.blob_store.read_decoded(&ptr).ok()?;
                 let block = evm_db::chain_data::BlockData::from_bytes(Cow::Owned(bytes));
                 return Some(block.block_hash);
             }
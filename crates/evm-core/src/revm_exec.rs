@@ -6,7 +6,9 @@ use crate::constants::FEE_RECIPIENT;
 use crate::hash::keccak256;
 use crate::kasane_precompiles::{
     with_icp_query_detection, with_icp_query_reply, IcpQueryReply, IcpQueryRequest,
-    KasanePrecompileProvider, PrecompileAccess,
+    KasanePrecompileProvider, PrecompileAccess, ICP_QUERY_PRECOMPILE_ADDRESS,
+    ICP_UPDATE_INTENT_PRECOMPILE_ADDRESS, NATIVE_WITHDRAW_PRECOMPILE_ADDRESS,
+    WRAP_PRECOMPILE_ADDRESS,
 };
 use crate::revm_db::RevmStableDb;
 use crate::tx_decode::DecodeError;
@@ -20,12 +22,15 @@ use evm_db::chain_data::{
 };
 use evm_db::stable_state::with_state_mut;
 use evm_db::Storable;
+use revm::bytecode::opcode;
 use revm::context::{Context, TxEnv};
 use revm::context_interface::result::{EVMError, ExecutionResult, HaltReason, InvalidTransaction};
 use revm::context_interface::CreateScheme;
+use revm::database::CacheDB;
 use revm::database_interface::{Database, DatabaseCommit};
 use revm::handler::{ExecuteCommitEvm, MainBuilder, MainContext};
 use revm::inspector::InspectEvm;
+use revm::interpreter::interpreter_types::{Jumps, StackTr};
 use revm::interpreter::{
     CallInputs, CallOutcome, CallScheme, CreateInputs, CreateOutcome, InstructionResult,
     Interpreter, InterpreterTypes,
@@ -95,6 +100,17 @@ pub struct ExecOutcome {
     pub internal_traces: InternalTraceSet,
 }
 
+impl ExecOutcome {
+    /// 事前実行時に仮置きした tx_index を、ブロック内の確定位置へ付け替える。
+    pub(crate) fn reassign_tx_index(&mut self, tx_index: u32) {
+        self.tx_index = tx_index;
+        self.receipt.tx_index = tx_index;
+        for trace in self.internal_traces.items.iter_mut() {
+            trace.tx_index = tx_index;
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct BlockExecContext {
     pub block_number: u64,
@@ -210,6 +226,74 @@ pub(crate) fn execute_tx_on<DB>(
     instruction_soft_limit: Option<u64>,
     precompile_access: PrecompileAccess,
 ) -> Result<(ExecOutcome, StateDiff), ExecError>
+where
+    DB: revm::database_interface::Database<Error = core::convert::Infallible> + DatabaseCommit,
+{
+    execute_tx_on_inner(
+        db,
+        tx_id,
+        tx_index,
+        tx_env,
+        exec_ctx,
+        exec_path,
+        persist_receipt_index,
+        instruction_soft_limit,
+        precompile_access,
+        false,
+    )
+    .map(|(outcome, state_diff, _)| (outcome, state_diff))
+}
+
+pub(crate) struct PreExecution {
+    pub outcome: ExecOutcome,
+    pub state_diff: StateDiff,
+    pub fee_recipient_observed: bool,
+    pub kasane_precompile_called: bool,
+}
+
+/// ブロック開始時点のstable stateを snapshot として Tx を事前実行する。
+/// 結果は捨てられる前提のため、receipt index は書かない。
+pub(crate) fn pre_execute_tx_on_snapshot(
+    tx_id: TxId,
+    tx_index: u32,
+    tx_env: TxEnv,
+    exec_ctx: &BlockExecContext,
+    instruction_soft_limit: Option<u64>,
+    precompile_access: PrecompileAccess,
+) -> Result<PreExecution, ExecError> {
+    let (outcome, state_diff, observer) = execute_tx_on_inner(
+        CacheDB::new(RevmStableDb),
+        tx_id,
+        tx_index,
+        tx_env,
+        exec_ctx,
+        ExecPath::UserTx,
+        false,
+        instruction_soft_limit,
+        precompile_access,
+        true,
+    )?;
+    Ok(PreExecution {
+        outcome,
+        state_diff,
+        fee_recipient_observed: observer.fee_recipient_observed,
+        kasane_precompile_called: observer.kasane_precompile_called,
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+fn execute_tx_on_inner<DB>(
+    db: DB,
+    tx_id: TxId,
+    tx_index: u32,
+    tx_env: TxEnv,
+    exec_ctx: &BlockExecContext,
+    exec_path: ExecPath,
+    persist_receipt_index: bool,
+    instruction_soft_limit: Option<u64>,
+    precompile_access: PrecompileAccess,
+    observe_pre_execution: bool,
+) -> Result<(ExecOutcome, StateDiff, PreExecObserver), ExecError>
 where
     DB: revm::database_interface::Database<Error = core::convert::Infallible> + DatabaseCommit,
 {
@@ -231,7 +315,8 @@ where
             .collect::<BTreeSet<Vec<u8>>>()
     });
     let inspector_limit = instruction_soft_limit.unwrap_or(0);
-    let inspector = InspectorMux::new(inspector_limit, exec_ctx.block_number, tx_index);
+    let mut inspector = InspectorMux::new(inspector_limit, exec_ctx.block_number, tx_index);
+    inspector.pre_exec.enabled = observe_pre_execution;
     let mut evm = Context::mainnet()
         .with_db(db)
         .modify_cfg_chained(|cfg| {
@@ -348,12 +433,13 @@ where
         halt_reason,
         internal_traces: evm.inspector.traces.finish(),
     };
-    Ok((outcome, state_diff))
+    Ok((outcome, state_diff, evm.inspector.pre_exec))
 }
 
 struct InspectorMux {
     budget: InstructionBudgetInspector,
     traces: InternalTraceInspector,
+    pre_exec: PreExecObserver,
 }
 
 impl InspectorMux {
@@ -361,10 +447,59 @@ impl InspectorMux {
         Self {
             budget: InstructionBudgetInspector::new(limit),
             traces: InternalTraceInspector::new(block_number, tx_index),
+            pre_exec: PreExecObserver::default(),
+        }
+    }
+}
+
+/// 事前実行の結果を後の tick で使えるかの判断材料を集める。
+/// fee recipient の残高・コードを読む命令は、加算を差分で載せ替えられないため再実行に回す。
+/// kasane precompile は EVM 状態の外を読むため、呼んだTxも再実行に回す。
+#[derive(Default)]
+pub(crate) struct PreExecObserver {
+    enabled: bool,
+    fee_recipient_observed: bool,
+    kasane_precompile_called: bool,
+}
+
+impl PreExecObserver {
+    fn step<INTR: InterpreterTypes>(&mut self, interp: &Interpreter<INTR>) {
+        if !self.enabled || self.fee_recipient_observed {
+            return;
+        }
+        match interp.bytecode.opcode() {
+            opcode::COINBASE => self.fee_recipient_observed = true,
+            opcode::BALANCE | opcode::EXTCODESIZE | opcode::EXTCODECOPY | opcode::EXTCODEHASH
+                if interp.stack.data().last() == Some(&fee_recipient_word()) =>
+            {
+                self.fee_recipient_observed = true;
+            }
+            _ => {}
+        }
+    }
+
+    fn touch(&mut self, address: Address) {
+        if self.enabled && address == FEE_RECIPIENT {
+            self.fee_recipient_observed = true;
+        }
+    }
+
+    fn call_target(&mut self, address: Address) {
+        if self.enabled
+            && (address == WRAP_PRECOMPILE_ADDRESS
+                || address == NATIVE_WITHDRAW_PRECOMPILE_ADDRESS
+                || address == ICP_QUERY_PRECOMPILE_ADDRESS
+                || address == ICP_UPDATE_INTENT_PRECOMPILE_ADDRESS)
+        {
+            self.kasane_precompile_called = true;
         }
     }
 }
 
+fn fee_recipient_word() -> U256 {
+    U256::from_be_slice(FEE_RECIPIENT.as_slice())
+}
+
 struct InstructionBudgetInspector {
     start: u64,
     limit: u64,
@@ -593,10 +728,15 @@ impl InternalTraceInspector {
 impl<CTX, INTR: InterpreterTypes> revm::Inspector<CTX, INTR> for InspectorMux {
     fn step(&mut self, interp: &mut Interpreter<INTR>, context: &mut CTX) {
         self.budget.step(interp, context);
+        self.pre_exec.step(interp);
     }
 
     fn call(&mut self, context: &mut CTX, inputs: &mut CallInputs) -> Option<CallOutcome> {
         let _ = context;
+        self.pre_exec.touch(inputs.caller);
+        self.pre_exec.touch(inputs.target_address);
+        self.pre_exec.touch(inputs.bytecode_address);
+        self.pre_exec.call_target(inputs.bytecode_address);
         self.traces.start_call(inputs);
         None
     }
@@ -609,6 +749,7 @@ impl<CTX, INTR: InterpreterTypes> revm::Inspector<CTX, INTR> for InspectorMux {
 
     fn create(&mut self, context: &mut CTX, inputs: &mut CreateInputs) -> Option<CreateOutcome> {
         let _ = context;
+        self.pre_exec.touch(inputs.caller());
         self.traces.start_create(inputs);
         None
     }
@@ -625,6 +766,8 @@ impl<CTX, INTR: InterpreterTypes> revm::Inspector<CTX, INTR> for InspectorMux {
     }
 
     fn selfdestruct(&mut self, contract: Address, target: Address, value: U256) {
+        self.pre_exec.touch(contract);
+        self.pre_exec.touch(target);
         self.traces.record_selfdestruct(contract, target, value);
     }
 }
@@ -766,7 +909,7 @@ fn store_receipt_index(tx_id: TxId, block_number: u64, tx_index: u32, receipt: &
         before_store_write_for_test("store_receipt", Some(block_number), Some(tx_id));
         let receipt_ptr = state
             .blob_store
-            .store_bytes(&receipt_bytes)
+            .store_encoded(&receipt_bytes)
             .unwrap_or_else(|_| {
                 trap_store_err(
                     "store_receipt",
@@ -0,0 +1,223 @@
+//! どこで: evm_db stable layout の schema migration / 何を: migration の宣言 registry と tick 駆動 / なぜ: 版ごとの移行を post_upgrade や timer に個別配線せず、再開可能に前進させるため
+
+use crate::chain;
+use evm_db::chain_data::TxId;
+use evm_db::meta::{
+    mark_migration_applied, schema_migration_state, set_needs_migration,
+    set_schema_migration_state, set_tx_locs_v3_active, tx_locs_v3_active, SchemaMigrationPhase,
+    SchemaMigrationState,
+};
+use verified_core::schema_migration::schema_migration_applies;
+
+/// 1 回の Rewrite で処理する最大件数。
+pub const SCHEMA_MIGRATION_BATCH: u32 = 512;
+/// Verify で値まで突き合わせる件数。件数一致は全件で確認する。
+pub const SCHEMA_MIGRATION_VERIFY_SAMPLE: u32 = 256;
+
+pub const SCHEMA_MIGRATION_ERR_UNKNOWN_MIGRATION: u32 = 1;
+pub const SCHEMA_MIGRATION_ERR_TX_LOCS_INACTIVE: u32 = 2;
+pub const SCHEMA_MIGRATION_ERR_TX_LOCS_MISMATCH: u32 = 3;
+pub const SCHEMA_MIGRATION_ERR_ETH_TX_HASH_INDEX: u32 = 4;
+
+/// step 1 回分の結果。last_key は次回の排他開始位置として state に残す。
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct SchemaMigrationBatch {
+    pub last_key: Option<[u8; 32]>,
+    pub processed: u64,
+    pub done: bool,
+}
+
+pub struct SchemaMigration {
+    pub id: &'static str,
+    pub from_version: u32,
+    pub to_version: u32,
+    /// 対象に選ばれた時点で毎回呼ぶ。upgrade で Init からやり直しても安全な処理に限る。
+    pub prepare: fn(),
+    pub step: fn(Option<[u8; 32]>, u32) -> SchemaMigrationBatch,
+    /// 失敗時は SchemaMigrationState.last_error に残す code を返す。
+    pub verify: fn() -> Result<(), u32>,
+    /// verify 成功後に新しい layout を有効にする。
+    pub activate: fn(),
+    /// 運用者向けの手順。driver は自動で巻き戻さない。
+    pub rollback: &'static str,
+}
+
+/// to_version の昇順。Scan は migration_index から先だけを探す。
+pub const SCHEMA_MIGRATIONS: &[SchemaMigration] = &[
+    SchemaMigration {
+        id: "tx_locs_v3",
+        from_version: 2,
+        to_version: 3,
+        prepare: chain::clear_tx_locs_v3,
+        step: tx_locs_v3_step,
+        verify: tx_locs_v3_verify,
+        activate: tx_locs_v3_activate,
+        rollback:
+            "tx_locs は書き換えないため、v3 無効のままの旧 wasm に戻せば tx_locs_v3 は無視される",
+    },
+    SchemaMigration {
+        id: "eth_tx_hash_index",
+        from_version: 4,
+        to_version: 5,
+        prepare: chain::clear_eth_tx_hash_index,
+        step: eth_tx_hash_index_step,
+        verify: eth_tx_hash_index_verify,
+        activate: noop,
+        rollback: "索引は tx_store から再生成できるため、旧 wasm でも再 upgrade で作り直せる",
+    },
+];
+
+/// Rewrite/Verify 中、または失敗した migration。
+pub fn current_schema_migration(state: &SchemaMigrationState) -> Option<&'static SchemaMigration> {
+    match state.phase {
+        SchemaMigrationPhase::Rewrite
+        | SchemaMigrationPhase::Verify
+        | SchemaMigrationPhase::Error => SCHEMA_MIGRATIONS.get(usize::from(state.migration_index)),
+        SchemaMigrationPhase::Init | SchemaMigrationPhase::Scan | SchemaMigrationPhase::Done => {
+            None
+        }
+    }
+}
+
+/// 最大 max_steps だけ状態を進める。Rewrite の batch は 1 tick に 1 回だけ走らせる。
+/// 全 migration が verify を通ったら schema 版を確定して true を返す。
+pub fn schema_migration_tick(max_steps: u32, now_nanos: u64) -> bool {
+    let mut steps = 0u32;
+    while steps < max_steps {
+        let mut state = schema_migration_state();
+        match state.phase {
+            SchemaMigrationPhase::Done => return true,
+            SchemaMigrationPhase::Error => return false,
+            SchemaMigrationPhase::Init => {
+                state.migration_index = 0;
+                state.phase = SchemaMigrationPhase::Scan;
+                reset_cursor(&mut state);
+                set_schema_migration_state(state);
+            }
+            SchemaMigrationPhase::Scan => match next_applicable(&state) {
+                Some(index) => {
+                    (SCHEMA_MIGRATIONS[index].prepare)();
+                    state.migration_index = u8::try_from(index).unwrap_or(u8::MAX);
+                    state.phase = SchemaMigrationPhase::Rewrite;
+                    reset_cursor(&mut state);
+                    set_schema_migration_state(state);
+                }
+                None => {
+                    if !tx_locs_v3_active() {
+                        fail(state, SCHEMA_MIGRATION_ERR_TX_LOCS_INACTIVE);
+                        return false;
+                    }
+                    mark_migration_applied(state.from_version, state.to_version, now_nanos);
+                    set_needs_migration(false);
+                    state.phase = SchemaMigrationPhase::Done;
+                    reset_cursor(&mut state);
+                    set_schema_migration_state(state);
+                    return true;
+                }
+            },
+            SchemaMigrationPhase::Rewrite => {
+                let Some(migration) = SCHEMA_MIGRATIONS.get(usize::from(state.migration_index))
+                else {
+                    fail(state, SCHEMA_MIGRATION_ERR_UNKNOWN_MIGRATION);
+                    return false;
+                };
+                let start_key = state.cursor_key_set.then_some(state.cursor_key);
+                let batch = (migration.step)(start_key, SCHEMA_MIGRATION_BATCH);
+                state.cursor = state.cursor.saturating_add(batch.processed);
+                if let Some(key) = batch.last_key {
+                    state.cursor_key_set = true;
+                    state.cursor_key = key;
+                }
+                if !batch.done {
+                    set_schema_migration_state(state);
+                    return false;
+                }
+                state.phase = SchemaMigrationPhase::Verify;
+                set_schema_migration_state(state);
+            }
+            SchemaMigrationPhase::Verify => {
+                let Some(migration) = SCHEMA_MIGRATIONS.get(usize::from(state.migration_index))
+                else {
+                    fail(state, SCHEMA_MIGRATION_ERR_UNKNOWN_MIGRATION);
+                    return false;
+                };
+                if let Err(code) = (migration.verify)() {
+                    fail(state, code);
+                    return false;
+                }
+                (migration.activate)();
+                state.migration_index = state.migration_index.saturating_add(1);
+                state.phase = SchemaMigrationPhase::Scan;
+                reset_cursor(&mut state);
+                set_schema_migration_state(state);
+            }
+        }
+        steps = steps.saturating_add(1);
+    }
+    false
+}
+
+fn next_applicable(state: &SchemaMigrationState) -> Option<usize> {
+    SCHEMA_MIGRATIONS
+        .iter()
+        .enumerate()
+        .skip(usize::from(state.migration_index))
+        .find(|(_, migration)| {
+            schema_migration_applies(state.from_version, state.to_version, migration.to_version)
+        })
+        .map(|(index, _)| index)
+}
+
+fn reset_cursor(state: &mut SchemaMigrationState) {
+    state.cursor = 0;
+    state.cursor_key_set = false;
+    state.cursor_key = [0u8; 32];
+}
+
+fn fail(mut state: SchemaMigrationState, code: u32) {
+    state.phase = SchemaMigrationPhase::Error;
+    state.last_error = code;
+    set_schema_migration_state(state);
+}
+
+fn batch_of((last_key, processed, done): (Option<TxId>, u64, bool)) -> SchemaMigrationBatch {
+    SchemaMigrationBatch {
+        last_key: last_key.map(|key| key.0),
+        processed,
+        done,
+    }
+}
+
+fn tx_locs_v3_step(start_key: Option<[u8; 32]>, max_items: u32) -> SchemaMigrationBatch {
+    batch_of(chain::migrate_tx_locs_batch(start_key.map(TxId), max_items))
+}
+
+fn tx_locs_v3_verify() -> Result<(), u32> {
+    if chain::verify_tx_locs_v3(SCHEMA_MIGRATION_VERIFY_SAMPLE) {
+        Ok(())
+    } else {
+        Err(SCHEMA_MIGRATION_ERR_TX_LOCS_MISMATCH)
+    }
+}
+
+fn tx_locs_v3_activate() {
+    set_tx_locs_v3_active(true);
+}
+
+fn eth_tx_hash_index_step(start_key: Option<[u8; 32]>, max_items: u32) -> SchemaMigrationBatch {
+    batch_of(chain::rebuild_eth_tx_hash_index_batch(
+        start_key.map(TxId),
+        max_items,
+    ))
+}
+
+fn eth_tx_hash_index_verify() -> Result<(), u32> {
+    let (index_ok, _, _) = chain::verify_eth_tx_hash_index(SCHEMA_MIGRATION_VERIFY_SAMPLE);
+    if index_ok {
+        Ok(())
+    } else {
+        Err(SCHEMA_MIGRATION_ERR_ETH_TX_HASH_INDEX)
+    }
+}
+
+fn noop() {}
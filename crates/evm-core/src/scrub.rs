@@ -0,0 +1,572 @@
+//! どこで: stable構造の整合性scrub / 何を: block・receipt・索引・wrap/unwrap request を有界batchで突き合わせる / なぜ: hot pathでdecodeされない破損や索引の欠けを先に見つけ、安全に補えるものは補うため
+
+use crate::blob_compaction::{blocked_reason, MaintenanceBlock};
+use crate::chain::{load_block, load_receipt, store_tx_index_entry, tx_locs_get, tx_locs_insert};
+use crate::hash;
+use evm_db::chain_data::{
+    ScrubFindingKind, ScrubFindingV1, ScrubPhase, ScrubStateV1, ScrubTarget, StoredTx, TxId,
+    TxIndexEntry, TxKind, TxLoc, TxLocKind, SCRUB_FINDING_LOG_CAP, UNWRAP_DECODE_FAILURE_CODE,
+    WRAP_DECODE_FAILURE_CODE,
+};
+use evm_db::stable_state::{with_state, with_state_mut, StableState};
+use evm_db::Storable;
+use std::borrow::Cow;
+use std::ops::Bound;
+use verified_core::receipt_index::ReceiptIndexObservation;
+use verified_core::scrub::classify_block_tx;
+use verified_core::tx_index::{included_position_matches, IncludedTxPosition};
+
+/// block event から scrub を進めるときの既定予算。
+pub const DEFAULT_SCRUB_OPS_PER_TICK: u32 = 64;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ScrubError {
+    InvalidLimit,
+    /// 走行中の scrub が無い
+    NotActive,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ScrubStatus {
+    pub state: ScrubStateV1,
+    pub did_work: bool,
+    /// 実行条件を満たさず何もしなかった理由
+    pub blocked: Option<&'static str>,
+}
+
+pub fn scrub_status() -> ScrubStatus {
+    with_state(|state| ScrubStatus {
+        state: *state.scrub_state.get(),
+        did_work: false,
+        blocked: None,
+    })
+}
+
+pub fn scrub_active() -> bool {
+    with_state(|state| state.scrub_state.get().phase == ScrubPhase::Running)
+}
+
+/// 走行中でも先頭からやり直す。検出 log は run_id 付きで残す。
+pub fn start_scrub(auto_repair: bool, now_nanos: u64) -> ScrubStatus {
+    with_state_mut(|state| {
+        let current = *state.scrub_state.get();
+        let next = ScrubStateV1 {
+            phase: ScrubPhase::Running,
+            auto_repair,
+            run_id: current.run_id.saturating_add(1),
+            started_at: now_nanos,
+            next_finding_seq: current.next_finding_seq,
+            ..ScrubStateV1::new()
+        };
+        state.scrub_state.set(next);
+        ScrubStatus {
+            state: next,
+            did_work: false,
+            blocked: None,
+        }
+    })
+}
+
+pub fn set_scrub_paused(paused: bool) -> Result<ScrubStatus, ScrubError> {
+    with_state_mut(|state| {
+        let mut scrub = *state.scrub_state.get();
+        scrub.phase = match (scrub.phase, paused) {
+            (ScrubPhase::Running | ScrubPhase::Paused, true) => ScrubPhase::Paused,
+            (ScrubPhase::Running | ScrubPhase::Paused, false) => ScrubPhase::Running,
+            (ScrubPhase::Idle, _) => return Err(ScrubError::NotActive),
+        };
+        state.scrub_state.set(scrub);
+        Ok(ScrubStatus {
+            state: scrub,
+            did_work: false,
+            blocked: None,
+        })
+    })
+}
+
+/// seq 昇順で after_seq より後の検出を返す。
+pub fn scrub_findings(after_seq: Option<u64>, limit: usize) -> Vec<(u64, ScrubFindingV1)> {
+    with_state(|state| {
+        let start = match after_seq {
+            Some(seq) => Bound::Excluded(seq),
+            None => Bound::Unbounded,
+        };
+        state
+            .scrub_findings
+            .range((start, Bound::Unbounded))
+            .take(limit)
+            .map(|entry| (*entry.key(), entry.value()))
+            .collect()
+    })
+}
+
+/// key 1件の検査を1opとして数える。block は載っている tx ごとに1opを足す。
+pub fn scrub_tick(max_ops: u32, now_nanos: u64) -> Result<ScrubStatus, ScrubError> {
+    if max_ops == 0 {
+        return Err(ScrubError::InvalidLimit);
+    }
+    with_state_mut(|state| {
+        let mut scrub = *state.scrub_state.get();
+        if scrub.phase != ScrubPhase::Running {
+            return Ok(ScrubStatus {
+                state: scrub,
+                did_work: false,
+                blocked: None,
+            });
+        }
+        // staged block や prune 途中の中間状態を欠けとして数えない。
+        if let Some(reason) = blocked_reason(state) {
+            return Ok(ScrubStatus {
+                state: scrub,
+                did_work: false,
+                blocked: Some(scrub_reason(reason)),
+            });
+        }
+        let mut ops = 0u32;
+        while ops < max_ops {
+            match scrub_next(state, &mut scrub, now_nanos) {
+                Some(cost) => {
+                    ops = ops.saturating_add(cost);
+                    scrub.scanned = scrub.scanned.saturating_add(1);
+                }
+                None => match scrub.target.next() {
+                    Some(next) => {
+                        scrub.target = next;
+                        scrub.cursor_key = None;
+                    }
+                    None => {
+                        scrub.phase = ScrubPhase::Idle;
+                        scrub.finished_at = now_nanos;
+                        scrub.cursor_key = None;
+                        break;
+                    }
+                },
+            }
+        }
+        state.scrub_state.set(scrub);
+        Ok(ScrubStatus {
+            state: scrub,
+            did_work: true,
+            blocked: None,
+        })
+    })
+}
+
+fn scrub_reason(reason: MaintenanceBlock) -> &'static str {
+    match reason {
+        MaintenanceBlock::StagedBlockActive => "scrub.staged_block_active",
+        MaintenanceBlock::PruneJournalPending => "scrub.prune_journal_pending",
+        MaintenanceBlock::SnapshotImportActive => "scrub.snapshot_import_active",
+    }
+}
+
+/// cursor の次の key を1件検査して cursor を進める。target を読み切ったら None。
+fn scrub_next(state: &mut StableState, scrub: &mut ScrubStateV1, now: u64) -> Option<u32> {
+    match scrub.target {
+        ScrubTarget::Blocks => {
+            let number = *state
+                .blocks
+                .range(after_u64(scrub.cursor_key))
+                .next()?
+                .key();
+            scrub.cursor_key = Some(u64_to_key(number));
+            Some(scrub_block(state, scrub, number, now))
+        }
+        ScrubTarget::Receipts => {
+            let tx_id = *state
+                .receipts
+                .range(after_tx(scrub.cursor_key))
+                .next()?
+                .key();
+            scrub.cursor_key = Some(tx_id.0);
+            scrub_receipt(state, scrub, tx_id, now);
+            Some(1)
+        }
+        ScrubTarget::TxLocs => {
+            let table = if evm_db::meta::tx_locs_v3_active() {
+                &state.tx_locs_v3
+            } else {
+                &state.tx_locs
+            };
+            let tx_id = *table.range(after_tx(scrub.cursor_key)).next()?.key();
+            scrub.cursor_key = Some(tx_id.0);
+            scrub_tx_loc(state, scrub, tx_id, now);
+            Some(1)
+        }
+        ScrubTarget::EthTxHashIndex => {
+            let tx_id = *state
+                .tx_store
+                .range(after_tx(scrub.cursor_key))
+                .next()?
+                .key();
+            scrub.cursor_key = Some(tx_id.0);
+            scrub_eth_tx_hash(state, scrub, tx_id, now);
+            Some(1)
+        }
+        ScrubTarget::WrapRequests => {
+            let request_id = *state
+                .wrap_requests
+                .range(after_tx(scrub.cursor_key))
+                .next()?
+                .key();
+            scrub.cursor_key = Some(request_id.0);
+            let unreadable = state.wrap_requests.get(&request_id).is_some_and(|req| {
+                req.result.error_code.as_deref() == Some(WRAP_DECODE_FAILURE_CODE)
+            });
+            if unreadable {
+                record(
+                    state,
+                    scrub,
+                    ScrubFindingKind::Unreadable,
+                    request_id.0,
+                    false,
+                    now,
+                );
+            }
+            Some(1)
+        }
+        ScrubTarget::WrapQueue => {
+            let seq = *state
+                .wrap_queue
+                .range(after_u64(scrub.cursor_key))
+                .next()?
+                .key();
+            scrub.cursor_key = Some(u64_to_key(seq));
+            let dangling = state
+                .wrap_queue
+                .get(&seq)
+                .is_some_and(|request_id| state.wrap_requests.get(&request_id).is_none());
+            if dangling {
+                // worker は request の無い entry を読み飛ばすだけなので、消しても順序は変わらない。
+                let repaired = scrub.auto_repair && state.wrap_queue.remove(&seq).is_some();
+                record(
+                    state,
+                    scrub,
+                    ScrubFindingKind::DanglingQueueEntry,
+                    u64_to_key(seq),
+                    repaired,
+                    now,
+                );
+            }
+            Some(1)
+        }
+        ScrubTarget::UnwrapRequests => {
+            let request_id = *state
+                .unwrap_requests
+                .range(after_tx(scrub.cursor_key))
+                .next()?
+                .key();
+            scrub.cursor_key = Some(request_id.0);
+            let unreadable = state
+                .unwrap_requests
+                .get(&request_id)
+                .is_some_and(|req| req.error_code.as_deref() == Some(UNWRAP_DECODE_FAILURE_CODE));
+            if unreadable {
+                record(
+                    state,
+                    scrub,
+                    ScrubFindingKind::Unreadable,
+                    request_id.0,
+                    false,
+                    now,
+                );
+            }
+            Some(1)
+        }
+        ScrubTarget::UnwrapQueue => {
+            let seq = *state
+                .unwrap_dispatch_queue
+                .range(after_u64(scrub.cursor_key))
+                .next()?
+                .key();
+            scrub.cursor_key = Some(u64_to_key(seq));
+            let dangling = state
+                .unwrap_dispatch_queue
+                .get(&seq)
+                .is_some_and(|request_id| state.unwrap_requests.get(&request_id).is_none());
+            if dangling {
+                let repaired =
+                    scrub.auto_repair && state.unwrap_dispatch_queue.remove(&seq).is_some();
+                record(
+                    state,
+                    scrub,
+                    ScrubFindingKind::DanglingQueueEntry,
+                    u64_to_key(seq),
+                    repaired,
+                    now,
+                );
+            }
+            Some(1)
+        }
+    }
+}
+
+/// block を正として、載っている tx の tx_index・receipt・included loc を突き合わせる。
+fn scrub_block(state: &mut StableState, scrub: &mut ScrubStateV1, number: u64, now: u64) -> u32 {
+    let Some(block) = load_block(state, number) else {
+        record(
+            state,
+            scrub,
+            ScrubFindingKind::Unreadable,
+            u64_to_key(number),
+            false,
+            now,
+        );
+        return 1;
+    };
+    for (index, tx_id) in block.tx_ids.iter().enumerate() {
+        let position = IncludedTxPosition {
+            block_number: number,
+            tx_index: u32::try_from(index).unwrap_or(u32::MAX),
+        };
+        let entry = read_tx_index(state, tx_id);
+        let loc = tx_locs_get(state, tx_id).filter(|loc| loc.kind == TxLocKind::Included);
+        let receipt = load_receipt(state, tx_id);
+        let observation = ReceiptIndexObservation {
+            tx_index_present: entry.is_some(),
+            receipt_present: receipt.is_some(),
+            included_loc_present: loc.is_some(),
+            index_matches_loc: matches!((entry, loc), (Some(entry), Some(loc))
+                if entry.block_number == loc.block_number && entry.tx_index == loc.tx_index),
+            receipt_matches_loc: matches!((receipt.as_ref(), loc), (Some(receipt), Some(loc))
+                if receipt.block_number == loc.block_number && receipt.tx_index == loc.tx_index),
+            loc_points_to_block_tx: loc.is_some_and(|loc| {
+                included_position_matches(
+                    position,
+                    verified_core::tx_index::TX_LOC_KIND_INCLUDED,
+                    loc.block_number,
+                    loc.tx_index,
+                )
+            }),
+        };
+        let verdict = classify_block_tx(observation);
+        // 欠けは block 上の位置から作り直せる。食い違いはどちらが正しいか決められないので報告だけ。
+        if verdict.missing_tx_index {
+            let repaired = scrub.auto_repair && state.tx_index.get(tx_id).is_none();
+            if repaired {
+                let ptr = store_tx_index_entry(
+                    state,
+                    TxIndexEntry {
+                        block_number: position.block_number,
+                        tx_index: position.tx_index,
+                    },
+                );
+                state.tx_index.insert(*tx_id, ptr);
+            }
+            record(
+                state,
+                scrub,
+                ScrubFindingKind::MissingTxIndex,
+                tx_id.0,
+                repaired,
+                now,
+            );
+        }
+        if verdict.missing_included_loc {
+            if scrub.auto_repair {
+                tx_locs_insert(
+                    state,
+                    *tx_id,
+                    TxLoc::included(position.block_number, position.tx_index),
+                );
+            }
+            record(
+                state,
+                scrub,
+                ScrubFindingKind::MissingTxLoc,
+                tx_id.0,
+                scrub.auto_repair,
+                now,
+            );
+        }
+        if verdict.missing_receipt {
+            record(
+                state,
+                scrub,
+                ScrubFindingKind::MissingReceipt,
+                tx_id.0,
+                false,
+                now,
+            );
+        }
+        if verdict.location_mismatch {
+            record(
+                state,
+                scrub,
+                ScrubFindingKind::LocationMismatch,
+                tx_id.0,
+                false,
+                now,
+            );
+        }
+    }
+    u32::try_from(block.tx_ids.len())
+        .unwrap_or(u32::MAX)
+        .saturating_add(1)
+}
+
+/// block 側から辿れない receipt を探す。
+fn scrub_receipt(state: &mut StableState, scrub: &mut ScrubStateV1, tx_id: TxId, now: u64) {
+    let Some(receipt) = load_receipt(state, &tx_id) else {
+        record(
+            state,
+            scrub,
+            ScrubFindingKind::Unreadable,
+            tx_id.0,
+            false,
+            now,
+        );
+        return;
+    };
+    if !block_has_tx_at(state, receipt.block_number, receipt.tx_index, tx_id) {
+        record(
+            state,
+            scrub,
+            ScrubFindingKind::OrphanReceipt,
+            tx_id.0,
+            false,
+            now,
+        );
+    }
+}
+
+fn scrub_tx_loc(state: &mut StableState, scrub: &mut ScrubStateV1, tx_id: TxId, now: u64) {
+    let Some(loc) = tx_locs_get(state, &tx_id) else {
+        record(
+            state,
+            scrub,
+            ScrubFindingKind::Unreadable,
+            tx_id.0,
+            false,
+            now,
+        );
+        return;
+    };
+    if loc.kind == TxLocKind::Included
+        && !block_has_tx_at(state, loc.block_number, loc.tx_index, tx_id)
+    {
+        record(
+            state,
+            scrub,
+            ScrubFindingKind::DanglingTxLoc,
+            tx_id.0,
+            false,
+            now,
+        );
+    }
+}
+
+fn scrub_eth_tx_hash(state: &mut StableState, scrub: &mut ScrubStateV1, tx_id: TxId, now: u64) {
+    let Some(envelope) = state.tx_store.get(&tx_id) else {
+        return;
+    };
+    let Ok(stored) = StoredTx::try_from(envelope) else {
+        record(
+            state,
+            scrub,
+            ScrubFindingKind::Unreadable,
+            tx_id.0,
+            false,
+            now,
+        );
+        return;
+    };
+    if stored.kind != TxKind::EthSigned {
+        return;
+    }
+    let eth_hash = TxId(hash::keccak256(&stored.raw));
+    if state.eth_tx_hash_index.get(&eth_hash) == Some(tx_id) {
+        return;
+    }
+    // 別の tx を指している場合は上書きせず報告だけにする。
+    let repaired = scrub.auto_repair && state.eth_tx_hash_index.get(&eth_hash).is_none();
+    if repaired {
+        state.eth_tx_hash_index.insert(eth_hash, tx_id);
+    }
+    record(
+        state,
+        scrub,
+        ScrubFindingKind::MissingEthTxHash,
+        tx_id.0,
+        repaired,
+        now,
+    );
+}
+
+fn block_has_tx_at(state: &StableState, number: u64, tx_index: u32, tx_id: TxId) -> bool {
+    load_block(state, number).is_some_and(|block| {
+        usize::try_from(tx_index)
+            .ok()
+            .and_then(|index| block.tx_ids.get(index))
+            == Some(&tx_id)
+    })
+}
+
+/// 長さが合わない entry は decode せずに欠けとして扱う（fail-closed の decode 失敗記録を避ける）。
+fn read_tx_index(state: &StableState, tx_id: &TxId) -> Option<TxIndexEntry> {
+    let ptr = state.tx_index.get(tx_id)?;
+    let bytes = state.blob_store.read(&ptr).ok()?;
+    if bytes.len() != 12 {
+        return None;
+    }
+    Some(TxIndexEntry::from_bytes(Cow::Owned(bytes)))
+}
+
+fn record(
+    state: &mut StableState,
+    scrub: &mut ScrubStateV1,
+    kind: ScrubFindingKind,
+    key: [u8; 32],
+    repaired: bool,
+    now: u64,
+) {
+    let seq = scrub.next_finding_seq;
+    state.scrub_findings.insert(
+        seq,
+        ScrubFindingV1 {
+            run_id: scrub.run_id,
+            target: scrub.target,
+            kind,
+            repaired,
+            key,
+            detected_at: now,
+        },
+    );
+    while state.scrub_findings.len() > SCRUB_FINDING_LOG_CAP {
+        let Some(oldest) = state.scrub_findings.first_key_value().map(|(key, _)| key) else {
+            break;
+        };
+        state.scrub_findings.remove(&oldest);
+    }
+    scrub.next_finding_seq = seq.saturating_add(1);
+    scrub.findings = scrub.findings.saturating_add(1);
+    if repaired {
+        scrub.repaired = scrub.repaired.saturating_add(1);
+    }
+}
+
+fn after_u64(after: Option<[u8; 32]>) -> (Bound<u64>, Bound<u64>) {
+    match after {
+        Some(key) => (Bound::Excluded(key_to_u64(key)), Bound::Unbounded),
+        None => (Bound::Unbounded, Bound::Unbounded),
+    }
+}
+
+fn after_tx(after: Option<[u8; 32]>) -> (Bound<TxId>, Bound<TxId>) {
+    match after {
+        Some(key) => (Bound::Excluded(TxId(key)), Bound::Unbounded),
+        None => (Bound::Unbounded, Bound::Unbounded),
+    }
+}
+
+fn u64_to_key(value: u64) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    key[..8].copy_from_slice(&value.to_be_bytes());
+    key
+}
+
+fn key_to_u64(key: [u8; 32]) -> u64 {
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&key[..8]);
+    u64::from_be_bytes(bytes)
+}
@@ -0,0 +1,283 @@
+//! どこで: produce_block の複数ラウンド組成 / 何を: 実行済みTxとStateDiffの保存・読込・破棄 / なぜ: 命令上限で止まったブロックを次のtimerで続けて封印するため
+
+use crate::chain::ChainError;
+use crate::revm_exec::{ExecOutcome, StateDiff};
+use evm_db::blob_ptr::BlobPtr;
+use evm_db::chain_data::{InternalTraceSet, ReceiptLike, StagedBlockMetaV1, TxId};
+use evm_db::stable_state::StableState;
+use evm_db::Storable;
+use revm::bytecode::Bytecode;
+use revm::primitives::{Address, Bytes, B256, U256};
+use revm::state::{Account, AccountInfo, AccountStatus, EvmStorageSlot};
+use std::borrow::Cow;
+
+const STAGED_ITEM_VERSION: u8 = 1;
+
+/// 前ラウンドまでに取り込み済みのTx。`state_diff` は実行時と同じ順で exec_db に再適用する。
+pub(crate) struct StagedIncluded {
+    pub(crate) tx_id: TxId,
+    pub(crate) sender: [u8; 20],
+    pub(crate) nonce: u64,
+    pub(crate) outcome: ExecOutcome,
+    pub(crate) state_diff: StateDiff,
+}
+
+// halt_reason は封印時に使わないため保存しない。
+// 符号化できない item はブロック組成のエラーとして返し、staged block には積まない。
+pub(crate) fn encode_included(
+    tx_id: TxId,
+    sender: [u8; 20],
+    nonce: u64,
+    outcome: &ExecOutcome,
+    state_diff: &StateDiff,
+) -> Result<Vec<u8>, ChainError> {
+    let mut out = Vec::new();
+    out.push(STAGED_ITEM_VERSION);
+    out.extend_from_slice(&tx_id.0);
+    out.extend_from_slice(&sender);
+    out.extend_from_slice(&nonce.to_be_bytes());
+    put_bytes(&mut out, outcome.final_status.as_bytes())?;
+    put_bytes(&mut out, outcome.receipt.to_bytes().as_ref())?;
+    if outcome.internal_traces.total_count == 0 {
+        put_bytes(&mut out, &[])?;
+    } else {
+        let traces = match outcome.internal_traces.to_bytes_checked() {
+            Ok(bytes) => bytes.into_owned(),
+            Err(_) => InternalTraceSet::failed(outcome.internal_traces.total_count)
+                .to_bytes_checked()
+                .map_err(|_| {
+                    ChainError::InvariantViolation("staged_block.trace_marker_encode".to_string())
+                })?
+                .into_owned(),
+        };
+        put_bytes(&mut out, &traces)?;
+    }
+    let mut accounts: Vec<(&Address, &Account)> = state_diff.iter().collect();
+    accounts.sort_by_key(|(address, _)| **address);
+    put_len(&mut out, accounts.len())?;
+    for (address, account) in accounts {
+        out.extend_from_slice(address.as_slice());
+        out.push(account.status.bits());
+        out.extend_from_slice(&account.info.nonce.to_be_bytes());
+        out.extend_from_slice(&account.info.balance.to_be_bytes::<32>());
+        out.extend_from_slice(account.info.code_hash.as_slice());
+        match account.info.code.as_ref() {
+            Some(code) => {
+                out.push(1);
+                put_bytes(&mut out, code.original_byte_slice())?;
+            }
+            None => out.push(0),
+        }
+        let mut slots: Vec<(&U256, &EvmStorageSlot)> = account
+            .storage
+            .iter()
+            .filter(|(_, slot)| slot.is_changed())
+            .collect();
+        slots.sort_by_key(|(key, _)| **key);
+        put_len(&mut out, slots.len())?;
+        for (key, slot) in slots {
+            out.extend_from_slice(&key.to_be_bytes::<32>());
+            out.extend_from_slice(&slot.original_value.to_be_bytes::<32>());
+            out.extend_from_slice(&slot.present_value.to_be_bytes::<32>());
+        }
+    }
+    Ok(out)
+}
+
+pub(crate) fn decode_included(bytes: &[u8]) -> Option<StagedIncluded> {
+    let mut reader = Reader { data: bytes };
+    if reader.u8()? != STAGED_ITEM_VERSION {
+        return None;
+    }
+    let tx_id = TxId(reader.array::<32>()?);
+    let sender = reader.array::<20>()?;
+    let nonce = reader.u64()?;
+    let final_status = String::from_utf8(reader.bytes()?.to_vec()).ok()?;
+    let receipt = ReceiptLike::from_bytes(Cow::Owned(reader.bytes()?.to_vec()));
+    let traces = reader.bytes()?;
+    let internal_traces = if traces.is_empty() {
+        InternalTraceSet::new(Vec::new())
+    } else {
+        InternalTraceSet::from_bytes(Cow::Owned(traces.to_vec()))
+    };
+    let account_count = reader.u32()?;
+    let mut state_diff = StateDiff::default();
+    for _ in 0..account_count {
+        let address = Address::from(reader.array::<20>()?);
+        let status = AccountStatus::from_bits_retain(reader.u8()?);
+        let account_nonce = reader.u64()?;
+        let balance = U256::from_be_bytes(reader.array::<32>()?);
+        let code_hash = B256::from(reader.array::<32>()?);
+        let code = match reader.u8()? {
+            0 => None,
+            1 => Some(Bytecode::new_raw_checked(Bytes::copy_from_slice(reader.bytes()?)).ok()?),
+            _ => return None,
+        };
+        let mut account = Account::from(AccountInfo {
+            balance,
+            nonce: account_nonce,
+            code_hash,
+            account_id: None,
+            code,
+        });
+        account.status = status;
+        let slot_count = reader.u32()?;
+        for _ in 0..slot_count {
+            let key = U256::from_be_bytes(reader.array::<32>()?);
+            let original = U256::from_be_bytes(reader.array::<32>()?);
+            let present = U256::from_be_bytes(reader.array::<32>()?);
+            account
+                .storage
+                .insert(key, EvmStorageSlot::new_changed(original, present, 0));
+        }
+        state_diff.insert(address, account);
+    }
+    if !reader.data.is_empty() || receipt.tx_id != tx_id {
+        return None;
+    }
+    let outcome = ExecOutcome {
+        tx_id,
+        tx_index: receipt.tx_index,
+        return_data: receipt.return_data.clone(),
+        receipt,
+        final_status,
+        halt_reason: None,
+        internal_traces,
+    };
+    Some(StagedIncluded {
+        tx_id,
+        sender,
+        nonce,
+        outcome,
+        state_diff,
+    })
+}
+
+/// 保存済みの全項目を順に読む。1件でも読めなければ staged block 全体を無効とみなす。
+pub(crate) fn load_items(state: &StableState) -> Option<Vec<StagedIncluded>> {
+    let meta = *state.staged_block_meta.get();
+    let mut items = Vec::with_capacity(usize::try_from(meta.item_count).unwrap_or(0));
+    for key in 0..meta.item_count {
+        let ptr = state.staged_block_items.get(&key)?;
+        let bytes = state.blob_store.read(&ptr).ok()?;
+        items.push(decode_included(&bytes)?);
+    }
+    Some(items)
+}
+
+/// 新しい項目を末尾へ追加する。blob に載らない項目があれば追加分を解放して失敗を返す。
+pub(crate) fn append_items(state: &mut StableState, encoded: &[Vec<u8>]) -> Result<u32, ()> {
+    let start = state.staged_block_meta.get().item_count;
+    let mut stored: Vec<BlobPtr> = Vec::with_capacity(encoded.len());
+    for bytes in encoded {
+        match state.blob_store.store_bytes(bytes) {
+            Ok(ptr) => stored.push(ptr),
+            Err(_) => {
+                for ptr in stored.iter() {
+                    release_blob(state, ptr);
+                }
+                return Err(());
+            }
+        }
+    }
+    let mut next = start;
+    for ptr in stored {
+        state.staged_block_items.insert(next, ptr);
+        next = next.saturating_add(1);
+    }
+    Ok(next)
+}
+
+/// staged block の blob を解放して meta を空に戻す。`sealed` は累計カウンタの振り分けに使う。
+pub(crate) fn clear(state: &mut StableState, sealed: bool) {
+    let meta = *state.staged_block_meta.get();
+    if !meta.active && state.staged_block_items.is_empty() {
+        return;
+    }
+    let ptrs: Vec<BlobPtr> = state
+        .staged_block_items
+        .iter()
+        .map(|entry| entry.value())
+        .collect();
+    for ptr in ptrs.iter() {
+        release_blob(state, ptr);
+    }
+    let keys: Vec<u32> = state
+        .staged_block_items
+        .iter()
+        .map(|entry| *entry.key())
+        .collect();
+    for key in keys {
+        state.staged_block_items.remove(&key);
+    }
+    let mut next = meta.cleared();
+    if meta.active {
+        if sealed {
+            next.sealed_total = next.sealed_total.saturating_add(1);
+        } else {
+            next.discarded_total = next.discarded_total.saturating_add(1);
+        }
+    }
+    state.staged_block_meta.set(next);
+}
+
+pub(crate) fn current_meta(state: &StableState) -> StagedBlockMetaV1 {
+    *state.staged_block_meta.get()
+}
+
+fn release_blob(state: &mut StableState, ptr: &BlobPtr) {
+    if state.blob_store.mark_quarantine(ptr).is_ok() {
+        let _ = state.blob_store.mark_free(ptr);
+    }
+}
+
+fn put_len(out: &mut Vec<u8>, len: usize) -> Result<(), ChainError> {
+    let len = u32::try_from(len)
+        .map_err(|_| ChainError::InvariantViolation("staged_block.item_too_large".to_string()))?;
+    out.extend_from_slice(&len.to_be_bytes());
+    Ok(())
+}
+
+fn put_bytes(out: &mut Vec<u8>, bytes: &[u8]) -> Result<(), ChainError> {
+    put_len(out, bytes.len())?;
+    out.extend_from_slice(bytes);
+    Ok(())
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.data.len() < len {
+            return None;
+        }
+        let (head, tail) = self.data.split_at(len);
+        self.data = tail;
+        Some(head)
+    }
+
+    fn array<const N: usize>(&mut self) -> Option<[u8; N]> {
+        let mut out = [0u8; N];
+        out.copy_from_slice(self.take(N)?);
+        Some(out)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        Some(self.array::<1>()?[0])
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_be_bytes(self.array::<4>()?))
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        Some(u64::from_be_bytes(self.array::<8>()?))
+    }
+
+    fn bytes(&mut self) -> Option<&'a [u8]> {
+        let len = usize::try_from(self.u32()?).ok()?;
+        self.take(len)
+    }
+}
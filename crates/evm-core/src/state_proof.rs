@@ -0,0 +1,147 @@
+//! どこで: state root の読み出し証明 / 何を: node DB から account/storage の Merkle-Patricia proof を組む / なぜ: certified な state root から query の値を検証できるようにするため
+
+use crate::bytes::b256_to_bytes;
+use crate::hash::keccak256;
+use alloy_primitives::{B256, U256};
+use alloy_rlp::Decodable;
+use alloy_trie::nodes::TrieNode;
+use alloy_trie::{Nibbles, TrieAccount, EMPTY_ROOT_HASH, KECCAK_EMPTY};
+use evm_db::chain_data::HashKey;
+use evm_db::stable_state::{with_state, StableState};
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum StateProofError {
+    /// GC 済み、または root が古くて node DB に残っていない。
+    MissingNode([u8; 32]),
+    CorruptNode,
+}
+
+/// eth_getProof と同じく、root から leaf までの RLP node を順に並べる。
+/// 親の RLP に埋め込まれた inline node は別要素にしない。
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AccountProof {
+    pub nonce: u64,
+    pub balance: [u8; 32],
+    pub storage_root: [u8; 32],
+    pub code_hash: [u8; 32],
+    pub proof: Vec<Vec<u8>>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct StorageProof {
+    pub value: [u8; 32],
+    pub proof: Vec<Vec<u8>>,
+}
+
+/// 存在しない account は空 account として、不在の proof を返す。
+pub fn prove_account(
+    state_root: [u8; 32],
+    address: [u8; 20],
+) -> Result<AccountProof, StateProofError> {
+    let (value, proof) = with_state(|state| walk(state, state_root, keccak256(&address)))?;
+    let account = match value {
+        Some(raw) => {
+            let mut slice = raw.as_slice();
+            TrieAccount::decode(&mut slice).map_err(|_| StateProofError::CorruptNode)?
+        }
+        None => TrieAccount {
+            nonce: 0,
+            balance: U256::ZERO,
+            storage_root: EMPTY_ROOT_HASH,
+            code_hash: KECCAK_EMPTY,
+        },
+    };
+    Ok(AccountProof {
+        nonce: account.nonce,
+        balance: account.balance.to_be_bytes(),
+        storage_root: b256_to_bytes(account.storage_root),
+        code_hash: b256_to_bytes(account.code_hash),
+        proof,
+    })
+}
+
+pub fn prove_storage(
+    storage_root: [u8; 32],
+    slot: [u8; 32],
+) -> Result<StorageProof, StateProofError> {
+    let (value, proof) = with_state(|state| walk(state, storage_root, keccak256(&slot)))?;
+    let value = match value {
+        Some(raw) => {
+            let mut slice = raw.as_slice();
+            U256::decode(&mut slice)
+                .map_err(|_| StateProofError::CorruptNode)?
+                .to_be_bytes()
+        }
+        None => [0u8; 32],
+    };
+    Ok(StorageProof { value, proof })
+}
+
+type ProofNodes = Vec<Vec<u8>>;
+
+/// key の leaf 値（無ければ None）と、そこまでに辿った node を返す。
+fn walk(
+    state: &StableState,
+    root: [u8; 32],
+    key_hash: [u8; 32],
+) -> Result<(Option<Vec<u8>>, ProofNodes), StateProofError> {
+    let mut proof = Vec::new();
+    if B256::from(root) == EMPTY_ROOT_HASH {
+        return Ok((None, proof));
+    }
+    let key = Nibbles::unpack(key_hash);
+    let mut depth = 0usize;
+    let mut raw = load_node(state, root)?;
+    proof.push(raw.clone());
+    loop {
+        let mut slice = raw.as_slice();
+        let node = TrieNode::decode(&mut slice).map_err(|_| StateProofError::CorruptNode)?;
+        let child = match node {
+            TrieNode::EmptyRoot => return Ok((None, proof)),
+            TrieNode::Leaf(leaf) => {
+                let found = leaf.key == key.slice(depth..);
+                return Ok((found.then_some(leaf.value), proof));
+            }
+            TrieNode::Extension(ext) => {
+                if key.slice(depth..).common_prefix_length(&ext.key) != ext.key.len() {
+                    return Ok((None, proof));
+                }
+                depth += ext.key.len();
+                ext.child
+            }
+            TrieNode::Branch(branch) => {
+                let Some(nibble) = key.get(depth) else {
+                    return Ok((None, proof));
+                };
+                if !branch.state_mask.is_bit_set(nibble) {
+                    return Ok((None, proof));
+                }
+                let position = (0..nibble)
+                    .filter(|index| branch.state_mask.is_bit_set(*index))
+                    .count();
+                depth += 1;
+                branch
+                    .stack
+                    .get(position)
+                    .cloned()
+                    .ok_or(StateProofError::CorruptNode)?
+            }
+        };
+        raw = match child.as_hash() {
+            Some(hash) => {
+                let next = load_node(state, b256_to_bytes(hash))?;
+                proof.push(next.clone());
+                next
+            }
+            None => child.as_ref().to_vec(),
+        };
+    }
+}
+
+fn load_node(state: &StableState, hash: [u8; 32]) -> Result<Vec<u8>, StateProofError> {
+    state
+        .state_root_node_db
+        .get(&HashKey(hash))
+        .map(|record| record.rlp)
+        .ok_or(StateProofError::MissingNode(hash))
+}
@@ -0,0 +1,533 @@
+//! どこで: 全状態スナップショット / 何を: accounts/storage/codesのcursor export と検証付きimport / なぜ: ブロック全履歴を再実行せずに新しいcanisterを立ち上げるため
+
+use crate::export::ExportError;
+use crate::hash::keccak256;
+use crate::trie_commit;
+use evm_db::chain_data::{Head, MigrationPhase, StateSnapshotImportPhase, StateSnapshotImportV1};
+use evm_db::stable_state::{remove_first_entries, with_state, with_state_mut, StableState};
+use evm_db::types::keys::{
+    AccountKey, CodeKey, StorageKey, ACCOUNT_KEY_LEN, ACCOUNT_KEY_PREFIX, CODE_KEY_PREFIX,
+    STORAGE_KEY_LEN, STORAGE_KEY_PREFIX,
+};
+use evm_db::types::values::{
+    AccountVal, CodeVal, U256Val, ACCOUNT_VAL_LEN, MAX_CODE_SIZE, U256_LEN,
+};
+use evm_db::Storable;
+use std::borrow::Cow;
+use std::ops::Bound;
+
+pub const STATE_SNAPSHOT_SEGMENT_ACCOUNTS: u8 = 0;
+pub const STATE_SNAPSHOT_SEGMENT_STORAGE: u8 = 1;
+pub const STATE_SNAPSHOT_SEGMENT_CODES: u8 = 2;
+const STATE_SNAPSHOT_LAST_SEGMENT: u8 = STATE_SNAPSHOT_SEGMENT_CODES;
+const CODE_KEY_LEN: usize = 33;
+const MAX_STATE_SNAPSHOT_BYTES: u32 = 1_500_000;
+/// entry = key(固定長) + value長(u32 BE) + value
+const ENTRY_LEN_PREFIX: usize = 4;
+
+type RawEntry<'a> = (&'a [u8], &'a [u8]);
+
+/// export開始時点の状態を識別する。全ページが同じmanifestを持つ。
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct StateSnapshotManifest {
+    pub head_number: u64,
+    pub block_hash: [u8; 32],
+    pub timestamp: u64,
+    pub state_root: [u8; 32],
+    pub evm_state_epoch: u64,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct StateSnapshotCursor {
+    pub head_number: u64,
+    pub evm_state_epoch: u64,
+    pub segment: u8,
+    /// 直前のページで最後に返したkey。Noneならsegmentの先頭から読む。
+    pub last_key: Option<Vec<u8>>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct StateSnapshotPage {
+    pub manifest: StateSnapshotManifest,
+    pub segment: u8,
+    pub entry_count: u32,
+    pub entries: Vec<u8>,
+    pub next_cursor: Option<StateSnapshotCursor>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum StateSnapshotImportError {
+    NotAllowed(&'static str),
+    InvalidPage(&'static str),
+    RootMismatch {
+        expected: [u8; 32],
+        computed: [u8; 32],
+    },
+}
+
+/// export の間はブロックを封印させず、head と状態を固定する。
+/// hold 中は produce_block が `ChainError::ProductionHeld` を返す。
+pub fn set_state_snapshot_export_hold(hold: bool) {
+    with_state_mut(|state| {
+        let mut chain_state = *state.chain_state.get();
+        chain_state.state_snapshot_export_hold = hold;
+        state.chain_state.set(chain_state);
+    });
+}
+
+/// export hold 中だけ読める。hold 中でも head と evm_state_epoch が動いた cursor は拒否する。
+pub fn export_state_snapshot(
+    cursor: Option<StateSnapshotCursor>,
+    max_bytes: u32,
+) -> Result<StateSnapshotPage, ExportError> {
+    if max_bytes == 0 {
+        return Err(ExportError::Limit);
+    }
+    let max_bytes = usize::try_from(max_bytes.min(MAX_STATE_SNAPSHOT_BYTES)).unwrap_or(0);
+    with_state(|state| {
+        if !state.chain_state.get().state_snapshot_export_hold {
+            return Err(ExportError::MissingData("state_snapshot.export_not_held"));
+        }
+        let manifest = current_manifest(state)?;
+        let cursor = cursor.unwrap_or(StateSnapshotCursor {
+            head_number: manifest.head_number,
+            evm_state_epoch: manifest.evm_state_epoch,
+            segment: STATE_SNAPSHOT_SEGMENT_ACCOUNTS,
+            last_key: None,
+        });
+        if !verified_core::state_snapshot::snapshot_cursor_current(
+            cursor.head_number,
+            cursor.evm_state_epoch,
+            manifest.head_number,
+            manifest.evm_state_epoch,
+        ) {
+            return Err(ExportError::InvalidCursor("state_snapshot.state_changed"));
+        }
+        if cursor.segment > STATE_SNAPSHOT_LAST_SEGMENT {
+            return Err(ExportError::InvalidCursor("segment out of range"));
+        }
+        if let Some(last_key) = cursor.last_key.as_deref() {
+            if !segment_key_valid(cursor.segment, last_key) {
+                return Err(ExportError::InvalidCursor(
+                    "last_key does not match segment",
+                ));
+            }
+        }
+
+        let mut segment = cursor.segment;
+        let mut last_key = cursor.last_key;
+        loop {
+            let mut entries = Vec::new();
+            let (entry_count, page_last_key, exhausted) = match segment {
+                STATE_SNAPSHOT_SEGMENT_ACCOUNTS => collect_segment(
+                    state
+                        .accounts
+                        .range((
+                            lower_bound(last_key.as_deref(), account_key_from_slice),
+                            Bound::Unbounded,
+                        ))
+                        .map(|entry| raw_entry(entry.key(), &entry.value())),
+                    max_bytes,
+                    &mut entries,
+                )?,
+                STATE_SNAPSHOT_SEGMENT_STORAGE => collect_segment(
+                    state
+                        .storage
+                        .range((
+                            lower_bound(last_key.as_deref(), storage_key_from_slice),
+                            Bound::Unbounded,
+                        ))
+                        .map(|entry| raw_entry(entry.key(), &entry.value())),
+                    max_bytes,
+                    &mut entries,
+                )?,
+                _ => collect_segment(
+                    state
+                        .codes
+                        .range((
+                            lower_bound(last_key.as_deref(), code_key_from_slice),
+                            Bound::Unbounded,
+                        ))
+                        .map(|entry| raw_entry(entry.key(), &entry.value())),
+                    max_bytes,
+                    &mut entries,
+                )?,
+            };
+            let next_segment = if exhausted {
+                (segment < STATE_SNAPSHOT_LAST_SEGMENT).then_some(segment + 1)
+            } else {
+                Some(segment)
+            };
+            let next_cursor = next_segment.map(|next| StateSnapshotCursor {
+                head_number: manifest.head_number,
+                evm_state_epoch: manifest.evm_state_epoch,
+                segment: next,
+                last_key: if exhausted { None } else { page_last_key },
+            });
+            // 空のsegmentだけのページは返さず、次のsegmentへ進む。
+            if entry_count == 0 && exhausted {
+                if let Some(next) = next_segment {
+                    segment = next;
+                    last_key = None;
+                    continue;
+                }
+            }
+            return Ok(StateSnapshotPage {
+                manifest,
+                segment,
+                entry_count,
+                entries,
+                next_cursor,
+            });
+        }
+    })
+}
+
+/// import の開始。genesis のまま空の canister だけが受け付ける。
+/// 既に取り込み中なら、取り込み済みの状態を捨てて新しいmanifestでやり直す。
+/// 既存状態の削除は `clear_state_snapshot_import` で複数回に分けて行う。
+pub fn begin_state_snapshot_import(
+    manifest: StateSnapshotManifest,
+) -> Result<StateSnapshotImportV1, StateSnapshotImportError> {
+    with_state_mut(|state| {
+        let current = *state.state_snapshot_import.get();
+        if !current.is_active()
+            && !verified_core::state_snapshot::snapshot_import_begin_allowed(
+                state.head.get().number,
+                state.pending_by_sender_nonce.len(),
+                state.staged_block_meta.get().active,
+            )
+        {
+            return Err(StateSnapshotImportError::NotAllowed(
+                "state_snapshot.target_not_empty",
+            ));
+        }
+        let import = StateSnapshotImportV1 {
+            phase: StateSnapshotImportPhase::Clearing,
+            head_number: manifest.head_number,
+            block_hash: manifest.block_hash,
+            timestamp: manifest.timestamp,
+            state_root: manifest.state_root,
+            evm_state_epoch: manifest.evm_state_epoch,
+            ..StateSnapshotImportV1::new()
+        };
+        state.state_snapshot_import.set(import);
+        Ok(import)
+    })
+}
+
+/// accounts/storage/codes/sender_expected_nonce を合計 `max_steps` 件まで消す。
+/// 全て空になると Loading へ進むため、phase が変わるまで繰り返し呼ぶ。
+pub fn clear_state_snapshot_import(
+    max_steps: u32,
+) -> Result<StateSnapshotImportV1, StateSnapshotImportError> {
+    with_state_mut(|state| {
+        let mut import = *state.state_snapshot_import.get();
+        if import.phase != StateSnapshotImportPhase::Clearing {
+            return Err(StateSnapshotImportError::NotAllowed(
+                "state_snapshot.not_clearing",
+            ));
+        }
+        let mut budget = u64::from(max_steps);
+        budget -= remove_first_entries(&mut state.accounts, budget);
+        budget -= remove_first_entries(&mut state.storage, budget);
+        budget -= remove_first_entries(&mut state.codes, budget);
+        remove_first_entries(&mut state.sender_expected_nonce, budget);
+        import.accounts = state.accounts.len();
+        import.storage = state.storage.len();
+        import.codes = state.codes.len();
+        if state.accounts.is_empty()
+            && state.storage.is_empty()
+            && state.codes.is_empty()
+            && state.sender_expected_nonce.is_empty()
+        {
+            import.phase = StateSnapshotImportPhase::Loading;
+        }
+        state.state_snapshot_import.set(import);
+        Ok(import)
+    })
+}
+
+/// 1ページ分を検証してから書き込む。途中で不正なentryがあればページ全体を捨てる。
+/// 同じページを再送しても同じkeyを上書きするだけになる。
+pub fn import_state_snapshot_page(
+    page: &StateSnapshotPage,
+) -> Result<StateSnapshotImportV1, StateSnapshotImportError> {
+    with_state_mut(|state| {
+        let mut import = *state.state_snapshot_import.get();
+        if import.phase != StateSnapshotImportPhase::Loading {
+            return Err(StateSnapshotImportError::NotAllowed(
+                "state_snapshot.not_loading",
+            ));
+        }
+        if page.manifest != manifest_of(&import) {
+            return Err(StateSnapshotImportError::InvalidPage(
+                "state_snapshot.manifest_mismatch",
+            ));
+        }
+        let entries = parse_entries(page)?;
+        for (key, value) in entries {
+            match page.segment {
+                STATE_SNAPSHOT_SEGMENT_ACCOUNTS => {
+                    let mut raw = [0u8; ACCOUNT_VAL_LEN];
+                    raw.copy_from_slice(value);
+                    state
+                        .accounts
+                        .insert(account_key_from_slice(key), AccountVal(raw));
+                }
+                STATE_SNAPSHOT_SEGMENT_STORAGE => {
+                    let mut raw = [0u8; U256_LEN];
+                    raw.copy_from_slice(value);
+                    state
+                        .storage
+                        .insert(storage_key_from_slice(key), U256Val(raw));
+                }
+                _ => {
+                    state
+                        .codes
+                        .insert(code_key_from_slice(key), CodeVal(value.to_vec()));
+                }
+            }
+        }
+        import.accounts = state.accounts.len();
+        import.storage = state.storage.len();
+        import.codes = state.codes.len();
+        state.state_snapshot_import.set(import);
+        Ok(import)
+    })
+}
+
+/// 取り込んだ状態から state root を再計算し、manifest と一致したら head を切り替える。
+/// 再計算は `max_steps` ずつ進むため、phase が Idle に戻るまで繰り返し呼ぶ。
+pub fn finish_state_snapshot_import(
+    max_steps: u32,
+) -> Result<StateSnapshotImportV1, StateSnapshotImportError> {
+    with_state_mut(|state| {
+        let mut import = *state.state_snapshot_import.get();
+        match import.phase {
+            StateSnapshotImportPhase::Idle => {
+                return Err(StateSnapshotImportError::NotAllowed(
+                    "state_snapshot.not_active",
+                ))
+            }
+            StateSnapshotImportPhase::Clearing => {
+                return Err(StateSnapshotImportError::NotAllowed(
+                    "state_snapshot.not_loading",
+                ))
+            }
+            StateSnapshotImportPhase::Loading => {
+                let mut meta = *state.state_root_meta.get();
+                meta.initialized = false;
+                state.state_root_meta.set(meta);
+                let mut migration = *state.state_root_migration.get();
+                migration.phase = MigrationPhase::Init;
+                migration.cursor = 0;
+                migration.last_error = 0;
+                state.state_root_migration.set(migration);
+                import.phase = StateSnapshotImportPhase::Verifying;
+                state.state_snapshot_import.set(import);
+            }
+            StateSnapshotImportPhase::Verifying => {}
+        }
+        if !trie_commit::migration_tick(state, max_steps) {
+            return Ok(import);
+        }
+        let computed = state.state_root_meta.get().state_root;
+        if computed != import.state_root {
+            // 欠けたページを再送してからやり直せるよう Loading に戻す。
+            import.phase = StateSnapshotImportPhase::Loading;
+            state.state_snapshot_import.set(import);
+            return Err(StateSnapshotImportError::RootMismatch {
+                expected: import.state_root,
+                computed,
+            });
+        }
+        state.head.set(Head {
+            number: import.head_number,
+            block_hash: import.block_hash,
+            timestamp: import.timestamp,
+        });
+        let mut chain_state = *state.chain_state.get();
+        chain_state.last_block_number = import.head_number;
+        chain_state.last_block_time = import.timestamp;
+        state.chain_state.set(chain_state);
+        if import.head_number > 0 {
+            // 取り込み元のブロック本体は持たないため、head以前は pruned として扱う。
+            let mut prune_state = *state.prune_state.get();
+            prune_state.set_pruned_before(import.head_number);
+            state.prune_state.set(prune_state);
+        }
+        let epoch = state.evm_state_epoch.get().saturating_add(1);
+        state.evm_state_epoch.set(epoch);
+        import.phase = StateSnapshotImportPhase::Idle;
+        state.state_snapshot_import.set(import);
+        Ok(import)
+    })
+}
+
+pub fn state_snapshot_import_status() -> StateSnapshotImportV1 {
+    with_state(|state| *state.state_snapshot_import.get())
+}
+
+pub fn state_snapshot_import_active() -> bool {
+    state_snapshot_import_status().is_active()
+}
+
+fn current_manifest(state: &StableState) -> Result<StateSnapshotManifest, ExportError> {
+    let meta = *state.state_root_meta.get();
+    if !meta.initialized || state.state_root_migration.get().phase != MigrationPhase::Done {
+        return Err(ExportError::MissingData("state_root.not_ready"));
+    }
+    if state.state_snapshot_import.get().is_active() {
+        return Err(ExportError::MissingData(
+            "state_snapshot.import_in_progress",
+        ));
+    }
+    let head = *state.head.get();
+    Ok(StateSnapshotManifest {
+        head_number: head.number,
+        block_hash: head.block_hash,
+        timestamp: head.timestamp,
+        state_root: meta.state_root,
+        evm_state_epoch: *state.evm_state_epoch.get(),
+    })
+}
+
+fn manifest_of(import: &StateSnapshotImportV1) -> StateSnapshotManifest {
+    StateSnapshotManifest {
+        head_number: import.head_number,
+        block_hash: import.block_hash,
+        timestamp: import.timestamp,
+        state_root: import.state_root,
+        evm_state_epoch: import.evm_state_epoch,
+    }
+}
+
+fn lower_bound<K>(last_key: Option<&[u8]>, decode: fn(&[u8]) -> K) -> Bound<K> {
+    match last_key {
+        Some(raw) => Bound::Excluded(decode(raw)),
+        None => Bound::Unbounded,
+    }
+}
+
+fn raw_entry<K: Storable, V: Storable>(key: &K, value: &V) -> (Vec<u8>, Vec<u8>) {
+    (key.to_bytes().into_owned(), value.to_bytes().into_owned())
+}
+
+/// cursor の次のkeyから `max_bytes` に収まるだけentryを詰める。
+/// 戻り値は (entry数, 最後のkey, segmentを読み切ったか)。
+fn collect_segment(
+    entries: impl Iterator<Item = (Vec<u8>, Vec<u8>)>,
+    max_bytes: usize,
+    out: &mut Vec<u8>,
+) -> Result<(u32, Option<Vec<u8>>, bool), ExportError> {
+    let mut count = 0u32;
+    let mut last_key = None;
+    for (key, value) in entries {
+        let entry_len = key.len() + ENTRY_LEN_PREFIX + value.len();
+        if out.len() + entry_len > max_bytes {
+            if count == 0 {
+                return Err(ExportError::Limit);
+            }
+            return Ok((count, last_key, false));
+        }
+        let value_len = u32::try_from(value.len())
+            .map_err(|_| ExportError::MissingData("state_snapshot.value_too_large"))?;
+        out.extend_from_slice(&key);
+        out.extend_from_slice(&value_len.to_be_bytes());
+        out.extend_from_slice(&value);
+        count = count.saturating_add(1);
+        last_key = Some(key);
+    }
+    Ok((count, last_key, true))
+}
+
+fn parse_entries(page: &StateSnapshotPage) -> Result<Vec<RawEntry<'_>>, StateSnapshotImportError> {
+    let key_len = match page.segment {
+        STATE_SNAPSHOT_SEGMENT_ACCOUNTS => ACCOUNT_KEY_LEN,
+        STATE_SNAPSHOT_SEGMENT_STORAGE => STORAGE_KEY_LEN,
+        STATE_SNAPSHOT_SEGMENT_CODES => CODE_KEY_LEN,
+        _ => {
+            return Err(StateSnapshotImportError::InvalidPage(
+                "state_snapshot.segment_out_of_range",
+            ))
+        }
+    };
+    let mut out = Vec::new();
+    let mut rest = page.entries.as_slice();
+    while !rest.is_empty() {
+        if rest.len() < key_len + ENTRY_LEN_PREFIX {
+            return Err(StateSnapshotImportError::InvalidPage(
+                "state_snapshot.entry_truncated",
+            ));
+        }
+        let (key, tail) = rest.split_at(key_len);
+        let (len_bytes, tail) = tail.split_at(ENTRY_LEN_PREFIX);
+        let mut len_raw = [0u8; ENTRY_LEN_PREFIX];
+        len_raw.copy_from_slice(len_bytes);
+        let value_len = usize::try_from(u32::from_be_bytes(len_raw)).unwrap_or(usize::MAX);
+        if tail.len() < value_len {
+            return Err(StateSnapshotImportError::InvalidPage(
+                "state_snapshot.entry_truncated",
+            ));
+        }
+        let (value, tail) = tail.split_at(value_len);
+        validate_entry(page.segment, key, value)?;
+        out.push((key, value));
+        rest = tail;
+    }
+    if u32::try_from(out.len()).ok() != Some(page.entry_count) {
+        return Err(StateSnapshotImportError::InvalidPage(
+            "state_snapshot.entry_count_mismatch",
+        ));
+    }
+    Ok(out)
+}
+
+fn validate_entry(segment: u8, key: &[u8], value: &[u8]) -> Result<(), StateSnapshotImportError> {
+    if !segment_key_valid(segment, key) {
+        return Err(StateSnapshotImportError::InvalidPage(
+            "state_snapshot.key_prefix",
+        ));
+    }
+    let value_ok = match segment {
+        STATE_SNAPSHOT_SEGMENT_ACCOUNTS => value.len() == ACCOUNT_VAL_LEN,
+        STATE_SNAPSHOT_SEGMENT_STORAGE => value.len() == U256_LEN,
+        _ => value.len() <= MAX_CODE_SIZE,
+    };
+    if !value_ok {
+        return Err(StateSnapshotImportError::InvalidPage(
+            "state_snapshot.value_length",
+        ));
+    }
+    // codes は key が内容のhashなので、改ざんされたbytecodeはここで弾ける。
+    if segment == STATE_SNAPSHOT_SEGMENT_CODES && keccak256(value) != key[1..] {
+        return Err(StateSnapshotImportError::InvalidPage(
+            "state_snapshot.code_hash_mismatch",
+        ));
+    }
+    Ok(())
+}
+
+fn segment_key_valid(segment: u8, key: &[u8]) -> bool {
+    let (prefix, len) = match segment {
+        STATE_SNAPSHOT_SEGMENT_ACCOUNTS => (ACCOUNT_KEY_PREFIX, ACCOUNT_KEY_LEN),
+        STATE_SNAPSHOT_SEGMENT_STORAGE => (STORAGE_KEY_PREFIX, STORAGE_KEY_LEN),
+        STATE_SNAPSHOT_SEGMENT_CODES => (CODE_KEY_PREFIX, CODE_KEY_LEN),
+        _ => return false,
+    };
+    key.len() == len && key.first() == Some(&prefix)
+}
+
+// 以下は segment_key_valid で長さを確認済みのkeyにだけ使う。
+fn account_key_from_slice(raw: &[u8]) -> AccountKey {
+    AccountKey::from_bytes(Cow::Borrowed(raw))
+}
+
+fn storage_key_from_slice(raw: &[u8]) -> StorageKey {
+    StorageKey::from_bytes(Cow::Borrowed(raw))
+}
+
+fn code_key_from_slice(raw: &[u8]) -> CodeKey {
+    CodeKey::from_bytes(Cow::Borrowed(raw))
+}
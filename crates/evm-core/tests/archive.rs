@@ -0,0 +1,168 @@
+//! どこで: archive canister 連携のテスト / 何を: ackまでのprune制限と範囲の記録 / なぜ: archiveが受け取っていない履歴を消さず、消した履歴の問い合わせ先を返せることを担保するため
+
+use evm_core::archive::{
+    ack_archived_blocks, archive_status, archived_range_for_block, archived_range_for_tx,
+    archived_ranges_overlapping, set_archive_canister, ArchiveError,
+};
+use evm_core::chain;
+use evm_db::chain_data::constants::MAX_ARCHIVED_TX_INDEX;
+use evm_db::chain_data::{ArchivedTxKey, BlockData, TxId};
+use evm_db::stable_state::{init_stable_state, with_state, with_state_mut};
+use evm_db::Storable;
+
+const ARCHIVE_A: [u8; 10] = [0x0a; 10];
+const ARCHIVE_B: [u8; 10] = [0x0b; 10];
+
+fn seed_blocks(head: u64) {
+    with_state_mut(|state| {
+        for number in 1..=head {
+            let tag = u8::try_from(number).expect("tag");
+            let block = BlockData::new(
+                number,
+                [0u8; 32],
+                [tag; 32],
+                number,
+                1_000_000_000,
+                3_000_000,
+                0,
+                [0u8; 20],
+                vec![TxId([tag; 32])],
+                [tag; 32],
+                [0u8; 32],
+            );
+            let ptr = state
+                .blob_store
+                .store_bytes(&block.to_bytes())
+                .expect("store block");
+            state.blocks.insert(number, ptr);
+        }
+        let mut head_state = *state.head.get();
+        head_state.number = head;
+        state.head.set(head_state);
+    });
+}
+
+#[test]
+fn prune_waits_for_archive_ack_and_records_ranges() {
+    std::thread::spawn(|| {
+        init_stable_state();
+        seed_blocks(10);
+        set_archive_canister(Some(ARCHIVE_A.to_vec())).expect("configure archive");
+
+        // ack が無い間は何も消さない
+        let result = chain::prune_blocks(2, 100).expect("prune");
+        assert!(!result.did_work);
+        assert_eq!(result.pruned_before_block, None);
+        assert!(with_state(|state| state.blocks.get(&1).is_some()));
+
+        assert_eq!(
+            ack_archived_blocks(&ARCHIVE_B, 3),
+            Err(ArchiveError::NotAllowed("archive.caller_not_archive"))
+        );
+        assert_eq!(
+            ack_archived_blocks(&ARCHIVE_A, 11),
+            Err(ArchiveError::InvalidAck("archive.ack.above_head"))
+        );
+        let first = ack_archived_blocks(&ARCHIVE_A, 3).expect("ack");
+        assert_eq!((first.start_block, first.end_block), (0, 3));
+        assert_eq!(
+            ack_archived_blocks(&ARCHIVE_A, 2),
+            Err(ArchiveError::InvalidAck("archive.ack.not_advancing"))
+        );
+
+        // retain=2 なら 8 まで消せるが、ack 済みの 3 で止まる
+        let result = chain::prune_blocks(2, 100).expect("prune");
+        assert!(result.did_work);
+        assert_eq!(result.pruned_before_block, Some(3));
+        with_state(|state| {
+            assert!(state.blocks.get(&3).is_none());
+            assert!(state.blocks.get(&4).is_some());
+        });
+
+        // 同じ canister への連続した ack は1つの範囲にまとまる
+        ack_archived_blocks(&ARCHIVE_A, 5).expect("ack");
+        set_archive_canister(Some(ARCHIVE_B.to_vec())).expect("switch archive");
+        let moved = ack_archived_blocks(&ARCHIVE_B, 6).expect("ack on new archive");
+        assert_eq!((moved.start_block, moved.end_block), (6, 6));
+        let status = archive_status();
+        assert_eq!(status.acked_through, Some(6));
+        assert_eq!(status.next_block, 7);
+        assert_eq!(status.ranges.len(), 2);
+        assert_eq!(
+            (status.ranges[0].start_block, status.ranges[0].end_block),
+            (0, 5)
+        );
+
+        let result = chain::prune_blocks(2, 100).expect("prune");
+        assert_eq!(result.pruned_before_block, Some(6));
+        assert_eq!(
+            archived_range_for_block(2).map(|range| range.canister),
+            Some(ARCHIVE_A.to_vec())
+        );
+        assert_eq!(
+            archived_range_for_block(6).map(|range| range.canister),
+            Some(ARCHIVE_B.to_vec())
+        );
+        assert_eq!(archived_range_for_block(7), None);
+        assert_eq!(archived_ranges_overlapping(4, 9).len(), 2);
+        assert_eq!(archived_ranges_overlapping(6, 9).len(), 1);
+        // prune した tx は tx_id から archive 先を1範囲だけ引ける
+        assert_eq!(
+            archived_range_for_tx(&TxId([2u8; 32])).map(|range| range.canister),
+            Some(ARCHIVE_A.to_vec())
+        );
+        assert_eq!(
+            archived_range_for_tx(&TxId([6u8; 32])).map(|range| range.canister),
+            Some(ARCHIVE_B.to_vec())
+        );
+        assert_eq!(archived_range_for_tx(&TxId([7u8; 32])), None);
+
+        // archive を外すと従来どおり ack を待たずに消える
+        set_archive_canister(None).expect("clear archive");
+        let result = chain::prune_blocks(2, 100).expect("prune");
+        assert_eq!(result.pruned_before_block, Some(8));
+        // archive 外で消えた tx は記録しない
+        assert_eq!(archived_range_for_tx(&TxId([8u8; 32])), None);
+        assert_eq!(
+            ack_archived_blocks(&ARCHIVE_B, 9),
+            Err(ArchiveError::NotAllowed("archive.not_configured"))
+        );
+    })
+    .join()
+    .expect("thread");
+}
+
+#[test]
+fn archived_tx_records_are_capped_oldest_first() {
+    std::thread::spawn(|| {
+        init_stable_state();
+        seed_blocks(4);
+        set_archive_canister(Some(ARCHIVE_A.to_vec())).expect("configure archive");
+        ack_archived_blocks(&ARCHIVE_A, 4).expect("ack");
+        // 上限まで埋まった状態を作る。block 0 の tx が最も古い。
+        with_state_mut(|state| {
+            for idx in 0..MAX_ARCHIVED_TX_INDEX {
+                let mut tx = [0xf0u8; 32];
+                tx[24..].copy_from_slice(&idx.to_be_bytes());
+                state.archived_tx_blocks.insert(TxId(tx), 0);
+                state
+                    .archived_tx_order
+                    .insert(ArchivedTxKey::new(0, TxId(tx)), 1);
+            }
+        });
+
+        let result = chain::prune_blocks(2, 100).expect("prune");
+        assert_eq!(result.pruned_before_block, Some(2));
+        with_state(|state| {
+            assert_eq!(state.archived_tx_blocks.len(), MAX_ARCHIVED_TX_INDEX);
+            assert_eq!(state.archived_tx_order.len(), MAX_ARCHIVED_TX_INDEX);
+        });
+        let mut oldest = [0xf0u8; 32];
+        oldest[24..].copy_from_slice(&0u64.to_be_bytes());
+        assert_eq!(archived_range_for_tx(&TxId(oldest)), None);
+        assert!(archived_range_for_tx(&TxId([1u8; 32])).is_some());
+        assert!(archived_range_for_tx(&TxId([2u8; 32])).is_some());
+    })
+    .join()
+    .expect("thread");
+}
@@ -0,0 +1,104 @@
+//! どこで: BlobStore再圧縮migrationのテスト / 何を: 未圧縮receiptの書き直しと読み出し互換 / なぜ: upgrade後の移行で履歴の内容が変わらずサイズクラスだけ下がることを担保するため
+
+use evm_core::blob_recompress::{
+    blob_recompress_active, blob_recompress_status, blob_recompress_tick,
+};
+use evm_core::chain::{self, ChainError};
+use evm_db::blob_codec::is_compressed;
+use evm_db::chain_data::receipt::log_entry_from_parts;
+use evm_db::chain_data::{BlobRecompressPhase, ReceiptLike, TxId};
+use evm_db::stable_state::{init_stable_state, with_state, with_state_mut};
+use evm_db::Storable;
+
+const TRANSFER_TOPIC: [u8; 32] = [
+    0xdd, 0xf2, 0x52, 0xad, 0x1b, 0xe2, 0xc8, 0x9b, 0x69, 0xc2, 0xb0, 0x68, 0xfc, 0x37, 0x8d, 0xaa,
+    0x95, 0x2b, 0xa7, 0xf1, 0x63, 0xc4, 0xa1, 0x16, 0x28, 0xf5, 0x5a, 0x4d, 0xf5, 0x23, 0xb3, 0xef,
+];
+
+fn word(tail: [u8; 20]) -> [u8; 32] {
+    let mut out = [0u8; 32];
+    out[12..].copy_from_slice(&tail);
+    out
+}
+
+/// Transfer を上限まで出した tx の receipt。
+fn transfer_heavy_receipt(tx_id: TxId) -> ReceiptLike {
+    let logs = (0u32..64)
+        .map(|i| {
+            // amount と ABI で左詰めされた付随値を 8 word 並べる
+            let mut amount = vec![0u8; 32 * 8];
+            for word in amount.chunks_mut(32) {
+                word[28..].copy_from_slice(&i.to_be_bytes());
+            }
+            log_entry_from_parts(
+                [0x33; 20],
+                vec![TRANSFER_TOPIC, word([0x11; 20]), word([0x22; 20])],
+                amount,
+            )
+        })
+        .collect();
+    ReceiptLike {
+        tx_id,
+        block_number: 7,
+        tx_index: 0,
+        status: 1,
+        gas_used: 21_000,
+        effective_gas_price: 1,
+        l1_data_fee: 0,
+        operator_fee: 0,
+        total_fee: 21_000,
+        return_data_hash: [0u8; 32],
+        return_data: Vec::new(),
+        contract_address: None,
+        logs,
+    }
+}
+
+#[test]
+fn recompress_rewrites_legacy_receipt_into_smaller_class() {
+    std::thread::spawn(|| {
+        init_stable_state();
+        let tx_id = TxId([0x77; 32]);
+        let receipt = transfer_heavy_receipt(tx_id);
+        let legacy = with_state_mut(|state| {
+            let bytes = receipt.to_bytes().into_owned();
+            assert!(bytes.len() > 16 * 1024);
+            let ptr = state
+                .blob_store
+                .store_bytes(&bytes)
+                .expect("store legacy receipt");
+            state.receipts.insert(tx_id, ptr);
+            ptr
+        });
+        assert!(blob_recompress_active());
+        assert_eq!(blob_recompress_tick(0), Err(ChainError::InvalidLimit));
+
+        let mut ticks = 0;
+        while blob_recompress_active() {
+            blob_recompress_tick(1).expect("tick");
+            ticks += 1;
+            assert!(ticks < 32, "migration must converge");
+        }
+
+        let status = blob_recompress_status();
+        assert_eq!(status.phase, BlobRecompressPhase::Done);
+        assert_eq!(status.recompressed_blobs, 1);
+        assert!(status.saved_bytes > 0);
+        with_state(|state| {
+            let current = state.receipts.get(&tx_id).expect("receipt ref");
+            assert_ne!(current, legacy);
+            assert!(current.class() < legacy.class());
+            assert!(is_compressed(
+                &state.blob_store.read(&current).expect("stored")
+            ));
+            assert!(state.blob_store.read(&legacy).is_err());
+        });
+        assert_eq!(chain::get_receipt(&tx_id), Some(receipt));
+
+        // 完了後は何もしない
+        let again = blob_recompress_tick(16).expect("tick after done");
+        assert!(!again.did_work);
+    })
+    .join()
+    .expect("thread");
+}
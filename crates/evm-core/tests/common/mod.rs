@@ -138,7 +138,10 @@ pub fn assert_block_persist_invariants(block_number: u64, tx_ids: &[TxId]) {
             assert_eq!(loc.tx_index, expected_index);
 
             let receipt_ptr = state.receipts.get(tx_id).expect("receipt ptr");
-            let receipt_bytes = state.blob_store.read(&receipt_ptr).expect("receipt bytes");
+            let receipt_bytes = state
+                .blob_store
+                .read_decoded(&receipt_ptr)
+                .expect("receipt bytes");
             let receipt = ReceiptLike::from_bytes(Cow::Owned(receipt_bytes));
             assert_eq!(receipt.tx_id, *tx_id);
             assert_eq!(receipt.block_number, block_number);
@@ -190,10 +193,17 @@ pub fn assert_runtime_indexes_match_pending() {
                 .get(&tx_id)
                 .expect("pending fee key");
             assert_eq!(state.pending_fee_index.get(&fee_key), Some(tx_id));
+            let tip_key = state
+                .pending_tip_key_by_tx_id
+                .get(&tx_id)
+                .expect("pending tip key");
+            assert_eq!(state.pending_tip_index.get(&tip_key), Some(tx_id));
         }
 
         assert_eq!(state.pending_fee_key_by_tx_id.len(), pending_count);
         assert_eq!(state.pending_fee_index.len(), pending_count);
+        assert_eq!(state.pending_tip_key_by_tx_id.len(), pending_count);
+        assert_eq!(state.pending_tip_index.len(), pending_count);
         assert_eq!(state.pending_meta_by_tx_id.len(), pending_count);
 
         let actual_principal_counts: BTreeMap<CallerKey, u64> = state
@@ -216,6 +226,16 @@ pub fn assert_runtime_indexes_match_pending() {
         }
         assert_eq!(state.ready_queue.len(), state.ready_key_by_tx_id.len());
         assert_eq!(state.ready_by_seq.len(), state.ready_key_by_tx_id.len());
+        for entry in state.ready_tip_key_by_tx_id.iter() {
+            assert_eq!(
+                state.ready_tip_index.get(&entry.value()),
+                Some(*entry.key())
+            );
+        }
+        assert_eq!(
+            state.ready_tip_index.len(),
+            state.ready_tip_key_by_tx_id.len()
+        );
     });
     let (ok, indexed, expected) = evm_core::chain::verify_eth_tx_hash_index(u32::MAX);
     assert!(
@@ -228,7 +248,9 @@ fn assert_no_pending_or_ready_refs(tx_id: TxId) {
     with_state(|state| {
         assert!(state.pending_meta_by_tx_id.get(&tx_id).is_none());
         assert!(state.pending_fee_key_by_tx_id.get(&tx_id).is_none());
+        assert!(state.pending_tip_key_by_tx_id.get(&tx_id).is_none());
         assert!(state.ready_key_by_tx_id.get(&tx_id).is_none());
+        assert!(state.ready_tip_key_by_tx_id.get(&tx_id).is_none());
         assert!(state
             .pending_by_sender_nonce
             .iter()
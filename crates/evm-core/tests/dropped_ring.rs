@@ -3,7 +3,7 @@
 use evm_core::chain::{self, TxIn};
 use evm_core::hash;
 use evm_core::tx_decode::IcSyntheticTxInput;
-use evm_db::chain_data::constants::DROPPED_RING_CAPACITY;
+use evm_db::chain_data::constants::{DROPPED_RING_CAPACITY, DROP_CODE_REPLACED};
 use evm_db::chain_data::TxLocKind;
 use evm_db::stable_state::{init_stable_state, with_state, with_state_mut};
 
@@ -109,3 +109,49 @@ fn dropped_ring_does_not_remove_included_or_queued() {
     let after = chain::get_tx_loc(&included_tx).expect("included must remain");
     assert_eq!(after.kind, TxLocKind::Included);
 }
+
+#[test]
+fn replaced_tx_keeps_drop_record_within_retention() {
+    init_stable_state();
+    relax_fee_floor_for_tests();
+    let caller_principal = vec![0x51];
+    let canister_id = vec![0x61];
+    let sender = hash::derive_evm_address_from_principal(&caller_principal).expect("must derive");
+    let submit = |max_fee: u128| {
+        chain::submit_tx_in(TxIn::IcSynthetic {
+            caller_principal: caller_principal.clone(),
+            canister_id: canister_id.clone(),
+            tx: build_ic_tx_input_with_fee(max_fee, max_fee, 0),
+        })
+        .expect("submit")
+    };
+
+    let first = submit(2_000_000_000);
+    let mut latest = submit(3_000_000_000);
+    let record = chain::get_drop_record(&first).expect("replaced tx must leave a drop record");
+    assert_eq!(record.drop_code, DROP_CODE_REPLACED);
+    assert_eq!(record.sender, Some(sender));
+    assert_eq!(record.nonce, Some(0));
+    assert_eq!(record.max_fee_per_gas, 2_000_000_000);
+    assert_eq!(record.max_priority_fee_per_gas, 2_000_000_000);
+    assert_eq!(record.base_fee, 1);
+    assert_eq!(record.block_attempted, None);
+    assert_eq!(record.eth_tx_hash, None);
+
+    assert!(chain::set_drop_record_retention(3_600, 0).is_err());
+    chain::set_drop_record_retention(3_600, 2).expect("retention");
+    let mut replaced = vec![first];
+    for i in 0..3u128 {
+        replaced.push(latest);
+        latest = submit(4_000_000_000 + i * 2_000_000_000);
+    }
+    // 件数上限2を超えた古い記録から消える。
+    let state = chain::get_drop_record_state();
+    assert_eq!(state.live_records(), 2);
+    assert_eq!(state.pruned_total, 2);
+    assert!(chain::get_drop_record(&replaced[0]).is_none());
+    assert!(chain::get_drop_record(&replaced[1]).is_none());
+    assert!(chain::get_drop_record(&replaced[2]).is_some());
+    assert!(chain::get_drop_record(&replaced[3]).is_some());
+    assert!(chain::get_drop_record(&latest).is_none());
+}
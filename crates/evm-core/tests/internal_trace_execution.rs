@@ -101,7 +101,7 @@ fn execution_path_keeps_sibling_trace_ids_after_selfdestruct() {
             .expect("internal traces must be stored");
         let bytes = state
             .blob_store
-            .read(&ptr)
+            .read_decoded(&ptr)
             .expect("internal trace bytes must be readable");
         InternalTraceSet::from_bytes(Cow::Owned(bytes))
     });
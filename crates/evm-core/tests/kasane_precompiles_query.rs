@@ -6,8 +6,9 @@ use candid::Encode;
 use evm_core::chain::{self, CallObjectInput, ChainError};
 use evm_core::hash;
 use evm_core::kasane_precompiles::{
-    precompile_allow_key, ICP_QUERY_PRECOMPILE_ADDRESS, ICP_UPDATE_INTENT_PRECOMPILE_ADDRESS,
-    NATIVE_WITHDRAW_PRECOMPILE_ADDRESS, WRAP_PRECOMPILE_ADDRESS,
+    precompile_allow_key, wrapped_token_supply, WrappedTokenSupply, ICP_QUERY_PRECOMPILE_ADDRESS,
+    ICP_UPDATE_INTENT_PRECOMPILE_ADDRESS, NATIVE_WITHDRAW_PRECOMPILE_ADDRESS,
+    WRAP_PRECOMPILE_ADDRESS,
 };
 use evm_core::revm_exec::{configure_instruction_budget_tripped_for_test, ExecError};
 use evm_core::tx_decode::IcSyntheticTxInput;
@@ -288,6 +289,29 @@ fn kasane_precompiles_eth_call_object_succeeds_in_query_path() {
     assert!(out.revert_data.is_none());
 }
 
+#[test]
+fn wrapped_token_supply_reads_factory_mapping_and_total_supply_slot() {
+    init_stable_state();
+    assert_eq!(
+        wrapped_token_supply(&[0x44, 0x55, 0x66]),
+        Err("runtime_config.not_configured".to_string())
+    );
+    set_runtime_config(RuntimeConfigV1::new(
+        candid::Principal::self_authenticating(b"wrap-precompile-query"),
+        TEST_FACTORY_ADDRESS,
+    ));
+    seed_unwrap_burn_state([0x31u8; 20]);
+
+    assert_eq!(
+        wrapped_token_supply(&[0x44, 0x55, 0x66]),
+        Ok(Some(WrappedTokenSupply {
+            token: WRAPPED_TOKEN_ADDRESS,
+            total_supply: U256::from(TEST_AMOUNT).to_be_bytes::<32>(),
+        }))
+    );
+    assert_eq!(wrapped_token_supply(&[0x44, 0x55, 0x67]), Ok(None));
+}
+
 #[test]
 fn kasane_precompiles_eth_estimate_gas_succeeds_in_query_path() {
     init_stable_state();
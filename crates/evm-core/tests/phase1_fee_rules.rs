@@ -10,8 +10,8 @@ use alloy_signer_local::PrivateKeySigner;
 use evm_core::base_fee::compute_next_base_fee;
 use evm_core::chain::{self, ChainError, TxIn};
 use evm_core::hash;
-use evm_db::chain_data::constants::CHAIN_ID;
-use evm_db::chain_data::TxKind;
+use evm_db::chain_data::constants::{CHAIN_ID, MAX_PENDING_GLOBAL};
+use evm_db::chain_data::{SenderNonceKey, TxId, TxKind, TxLocKind};
 use evm_db::stable_state::{init_stable_state, with_state, with_state_mut};
 use evm_db::types::keys::make_account_key;
 use revm::primitives::U256;
@@ -121,6 +121,132 @@ fn base_fee_rekey_reorders_by_effective_fee() {
     assert_eq!(block.tx_ids[1], a_id);
 }
 
+#[test]
+fn base_fee_decrease_rebuckets_ready_candidates() {
+    init_stable_state();
+    with_state_mut(|state| {
+        let mut chain_state = *state.chain_state.get();
+        chain_state.base_fee = 5_000_000_000;
+        chain_state.min_priority_fee = 1_000_000_000;
+        state.chain_state.set(chain_state);
+    });
+
+    let tx_a = common::build_zero_to_ic_tx_input(0, 6_000_000_000, 4_000_000_000);
+    let tx_b = common::build_zero_to_ic_tx_input(0, 10_000_000_000, 2_000_000_000);
+    let tx_c = common::build_zero_to_ic_tx_input(0, 50_000_000_000, 40_000_000_000);
+
+    fund_principal(&[0x37]);
+    fund_principal(&[0x47]);
+    fund_principal(&[0x57]);
+    let a_id = chain::submit_tx_in(TxIn::IcSynthetic {
+        caller_principal: vec![0x37],
+        canister_id: vec![0x03],
+        tx: tx_a,
+    })
+    .expect("submit a");
+    let b_id = chain::submit_tx_in(TxIn::IcSynthetic {
+        caller_principal: vec![0x47],
+        canister_id: vec![0x04],
+        tx: tx_b,
+    })
+    .expect("submit b");
+    let c_id = chain::submit_tx_in(TxIn::IcSynthetic {
+        caller_principal: vec![0x57],
+        canister_id: vec![0x05],
+        tx: tx_c,
+    })
+    .expect("submit c");
+
+    // base_fee=5gwei: c(45) > b(7) > a(6)
+    let first = chain::produce_block(1).expect("produce first");
+    assert_eq!(first.block.tx_ids, vec![c_id]);
+    common::assert_runtime_indexes_match_pending();
+
+    with_state_mut(|state| {
+        let mut chain_state = *state.chain_state.get();
+        chain_state.base_fee = 1_000_000_000;
+        state.chain_state.set(chain_state);
+    });
+
+    // base_fee=1gwei: a(5) > b(3)
+    let second = chain::produce_block(2).expect("produce second");
+    assert_eq!(second.block.tx_ids, vec![a_id, b_id]);
+    common::assert_runtime_indexes_match_pending();
+}
+
+#[test]
+fn pending_fee_eviction_uses_current_base_fee_without_reindex() {
+    init_stable_state();
+    with_state_mut(|state| {
+        let mut chain_state = *state.chain_state.get();
+        chain_state.base_fee = 1_000_000_000;
+        chain_state.min_priority_fee = 1_000_000_000;
+        state.chain_state.set(chain_state);
+    });
+    fund_principal(&[0x38]);
+    fund_principal(&[0x39]);
+    fund_principal(&[0x3a]);
+    // base_fee=1gwei: a=3gwei, b=2gwei なので b が最安。
+    let a_id = chain::submit_tx_in(TxIn::IcSynthetic {
+        caller_principal: vec![0x38],
+        canister_id: vec![0x03],
+        tx: common::build_zero_to_ic_tx_input(0, 3_000_000_000, 3_000_000_000),
+    })
+    .expect("submit a");
+    let b_id = chain::submit_tx_in(TxIn::IcSynthetic {
+        caller_principal: vec![0x39],
+        canister_id: vec![0x03],
+        tx: common::build_zero_to_ic_tx_input(0, 10_000_000_000, 1_000_000_000),
+    })
+    .expect("submit b");
+    let index_keys = || {
+        with_state(|state| {
+            state
+                .pending_fee_index
+                .iter()
+                .map(|entry| *entry.key())
+                .chain(state.pending_tip_index.iter().map(|entry| *entry.key()))
+                .collect::<Vec<_>>()
+        })
+    };
+    let keys_before = index_keys();
+
+    // base_fee=5gwei: a は max_fee < base_fee で 0、b は 6gwei。
+    with_state_mut(|state| {
+        let mut chain_state = *state.chain_state.get();
+        chain_state.base_fee = 5_000_000_000;
+        state.chain_state.set(chain_state);
+        for i in 2..MAX_PENDING_GLOBAL {
+            let mut sender = [0xeeu8; 20];
+            sender[18] = ((i >> 8) & 0xff) as u8;
+            sender[19] = (i & 0xff) as u8;
+            let mut tx_id = [0xeeu8; 32];
+            tx_id[30] = ((i >> 8) & 0xff) as u8;
+            tx_id[31] = (i & 0xff) as u8;
+            state
+                .pending_by_sender_nonce
+                .insert(SenderNonceKey::new(sender, 0), TxId(tx_id));
+        }
+    });
+    // base fee が変わっても index は作り直さない。
+    assert_eq!(index_keys(), keys_before);
+
+    chain::submit_tx_in(TxIn::IcSynthetic {
+        caller_principal: vec![0x3a],
+        canister_id: vec![0x03],
+        tx: common::build_zero_to_ic_tx_input(0, 10_000_000_000, 2_000_000_000),
+    })
+    .expect("higher fee tx should evict");
+    assert_eq!(
+        chain::get_tx_loc(&a_id).expect("a loc").kind,
+        TxLocKind::Dropped
+    );
+    assert_eq!(
+        chain::get_tx_loc(&b_id).expect("b loc").kind,
+        TxLocKind::Queued
+    );
+}
+
 #[test]
 fn equal_fee_uses_seq_order() {
     init_stable_state();
@@ -0,0 +1,186 @@
+//! どこで: Phase1楽観実行テスト / 何を: 事前実行の採用と衝突時の再実行 / なぜ: 逐次実行と同じブロック結果になることを固定するため
+
+use evm_core::chain::{self, BlockExecMode, BlockRoundOutcome, OptimisticExecStats, TxIn};
+use evm_core::hash;
+use evm_db::chain_data::ReceiptLike;
+use evm_db::stable_state::{init_stable_state, with_state_mut};
+
+mod common;
+
+// SLOAD(0) + 1 を SSTORE(0) するだけのカウンタ。
+const COUNTER_CODE: [u8; 10] = [0x60, 0x00, 0x54, 0x60, 0x01, 0x01, 0x60, 0x00, 0x55, 0x00];
+const COUNTER: [u8; 20] = [0x77; 20];
+// COINBASE の残高を SSTORE(0) する。fee recipient の観測を伴うTx。
+const COINBASE_PROBE_CODE: [u8; 6] = [0x41, 0x31, 0x60, 0x00, 0x55, 0x00];
+const COINBASE_PROBE: [u8; 20] = [0x78; 20];
+
+fn relax_fee_floor_for_tests() {
+    with_state_mut(|state| {
+        let mut chain_state = *state.chain_state.get();
+        chain_state.base_fee = 1;
+        chain_state.min_gas_price = 1;
+        chain_state.min_priority_fee = 1;
+        state.chain_state.set(chain_state);
+    });
+}
+
+type MixedBlockResult = ([u8; 32], Vec<ReceiptLike>, OptimisticExecStats);
+
+// stable memory はスレッド単位のため、モードごとに別スレッドで同じブロックを組む。
+fn produce_mixed_block_isolated(mode: BlockExecMode, targets: Vec<[u8; 20]>) -> MixedBlockResult {
+    std::thread::spawn(move || produce_mixed_block(mode, &targets))
+        .join()
+        .expect("mixed block thread")
+}
+
+fn produce_mixed_block(mode: BlockExecMode, targets: &[[u8; 20]]) -> MixedBlockResult {
+    init_stable_state();
+    relax_fee_floor_for_tests();
+    common::install_contract(COUNTER, &COUNTER_CODE);
+    common::install_contract(COINBASE_PROBE, &COINBASE_PROBE_CODE);
+    // snapshot時点で fee recipient が存在しないと増分の載せ替えができないため、先に作っておく。
+    common::fund_account(evm_core::fee_recipient(), 1);
+    let mut tx_ids = Vec::new();
+    for (idx, to) in targets.iter().enumerate() {
+        let idx_u8 = u8::try_from(idx).expect("test index fits u8");
+        let caller_principal = vec![0x50 + idx_u8];
+        common::fund_account(
+            hash::derive_evm_address_from_principal(&caller_principal).expect("must derive"),
+            1_000_000_000_000_000_000,
+        );
+        let tx_id = chain::submit_tx_in(TxIn::IcSynthetic {
+            caller_principal,
+            canister_id: vec![0x90 + idx_u8],
+            tx: common::build_ic_tx_input(*to, 0, 2_000_000_000, 1_000_000_000),
+        })
+        .expect("submit");
+        tx_ids.push(tx_id);
+    }
+    if mode == BlockExecMode::Optimistic {
+        // 事前実行は封印より前の tick で行う。
+        let pre_executed = chain::pre_execute_block(8).expect("pre-execute");
+        assert_eq!(pre_executed, u32::try_from(tx_ids.len()).expect("fits u32"));
+    }
+    let outcome = chain::produce_block_with_mode(8, mode).expect("produce");
+    assert_eq!(outcome.block.tx_ids, tx_ids);
+    common::assert_block_persist_invariants(outcome.block.number, &tx_ids);
+    let receipts = tx_ids
+        .iter()
+        .map(|tx_id| chain::get_receipt(tx_id).expect("receipt"))
+        .collect();
+    (outcome.block.state_root, receipts, outcome.optimistic)
+}
+
+#[test]
+fn optimistic_mode_matches_sequential_and_reexecutes_conflicts() {
+    // 独立送金 / カウンタ / カウンタ（直前と衝突）/ 独立送金 の順で取り込まれる。
+    let targets = vec![[0x21u8; 20], COUNTER, COUNTER, [0x22u8; 20]];
+    let (sequential_root, sequential_receipts, sequential_stats) =
+        produce_mixed_block_isolated(BlockExecMode::Sequential, targets.clone());
+    assert_eq!(sequential_stats, OptimisticExecStats::default());
+
+    let (optimistic_root, optimistic_receipts, optimistic_stats) =
+        produce_mixed_block_isolated(BlockExecMode::Optimistic, targets);
+    assert_eq!(
+        optimistic_stats,
+        OptimisticExecStats {
+            pre_executed: 4,
+            accepted: 3,
+            reexecuted: 1,
+        }
+    );
+    assert_eq!(optimistic_root, sequential_root);
+    assert_eq!(optimistic_receipts, sequential_receipts);
+    // 2件目のカウンタ更新は 1 -> 2 のため、再実行でなければ gas が一致しない。
+    assert!(optimistic_receipts[2].gas_used < optimistic_receipts[1].gas_used);
+}
+
+#[test]
+fn optimistic_mode_reexecutes_fee_recipient_observers() {
+    let targets = vec![[0x21u8; 20], COINBASE_PROBE];
+    let (sequential_root, sequential_receipts, _) =
+        produce_mixed_block_isolated(BlockExecMode::Sequential, targets.clone());
+    let (optimistic_root, optimistic_receipts, optimistic_stats) =
+        produce_mixed_block_isolated(BlockExecMode::Optimistic, targets);
+    assert_eq!(
+        optimistic_stats,
+        OptimisticExecStats {
+            pre_executed: 2,
+            accepted: 1,
+            reexecuted: 1,
+        }
+    );
+    assert_eq!(optimistic_root, sequential_root);
+    assert_eq!(optimistic_receipts, sequential_receipts);
+}
+
+#[test]
+fn produce_block_follows_persisted_exec_mode() {
+    init_stable_state();
+    relax_fee_floor_for_tests();
+    assert_eq!(chain::get_block_exec_mode(), BlockExecMode::Sequential);
+    chain::set_block_exec_mode(BlockExecMode::Optimistic).expect("set mode");
+    assert_eq!(chain::get_block_exec_mode(), BlockExecMode::Optimistic);
+
+    common::fund_account(evm_core::fee_recipient(), 1);
+    let caller_principal = vec![0x61];
+    common::fund_account(
+        hash::derive_evm_address_from_principal(&caller_principal).expect("must derive"),
+        1_000_000_000_000_000_000,
+    );
+    chain::submit_tx_in(TxIn::IcSynthetic {
+        caller_principal,
+        canister_id: vec![0x62],
+        tx: common::build_ic_tx_input([0x23u8; 20], 0, 2_000_000_000, 1_000_000_000),
+    })
+    .expect("submit");
+    assert_eq!(
+        chain::produce_block_round(1).expect("pre-execute round"),
+        BlockRoundOutcome::PreExecuted(1)
+    );
+    let outcome = match chain::produce_block_round(1).expect("seal round") {
+        BlockRoundOutcome::Sealed(outcome) => *outcome,
+        other => panic!("expected sealed block, got {other:?}"),
+    };
+    assert_eq!(outcome.optimistic.pre_executed, 1);
+    assert_eq!(outcome.optimistic.accepted, 1);
+}
+
+#[test]
+fn pre_executed_results_are_discarded_when_head_moves() {
+    init_stable_state();
+    relax_fee_floor_for_tests();
+    common::fund_account(evm_core::fee_recipient(), 1);
+    for idx in 0..2u8 {
+        let caller_principal = vec![0x63 + idx];
+        common::fund_account(
+            hash::derive_evm_address_from_principal(&caller_principal).expect("must derive"),
+            1_000_000_000_000_000_000,
+        );
+        chain::submit_tx_in(TxIn::IcSynthetic {
+            caller_principal,
+            canister_id: vec![0x65 + idx],
+            tx: common::build_ic_tx_input([0x24u8 + idx; 20], 0, 2_000_000_000, 1_000_000_000),
+        })
+        .expect("submit");
+    }
+    assert_eq!(chain::pre_execute_block(8).expect("pre-execute"), 2);
+    // 事前実行の後に別のブロックが封印されると、snapshot は古くなる。
+    chain::produce_block_with_mode(1, BlockExecMode::Sequential).expect("produce");
+
+    chain::set_block_exec_mode(BlockExecMode::Optimistic).expect("set mode");
+    assert_eq!(
+        chain::produce_block_round(8).expect("pre-execute round"),
+        BlockRoundOutcome::PreExecuted(1)
+    );
+    let outcome = chain::produce_block_with_mode(8, BlockExecMode::Optimistic).expect("produce");
+    assert_eq!(outcome.block.tx_ids.len(), 1);
+    assert_eq!(
+        outcome.optimistic,
+        OptimisticExecStats {
+            pre_executed: 1,
+            accepted: 1,
+            reexecuted: 0,
+        }
+    );
+}
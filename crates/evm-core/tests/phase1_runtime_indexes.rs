@@ -90,6 +90,7 @@ fn principal_pending_and_fee_indexes_track_lifecycle() {
         assert_eq!(state.principal_pending_count.get(&key), None);
         assert_eq!(state.pending_fee_key_by_tx_id.len(), 0);
         assert_eq!(state.pending_fee_index.len(), 0);
+        assert_eq!(state.pending_tip_index.len(), 0);
     });
 }
 
@@ -114,6 +115,8 @@ fn rebuild_runtime_indexes_recovers_from_empty_indexes() {
         clear_map(&mut state.principal_pending_count);
         clear_map(&mut state.pending_fee_index);
         clear_map(&mut state.pending_fee_key_by_tx_id);
+        clear_map(&mut state.pending_tip_index);
+        clear_map(&mut state.pending_tip_key_by_tx_id);
         clear_map(&mut state.ready_by_seq);
     });
 
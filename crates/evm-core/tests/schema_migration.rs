@@ -0,0 +1,116 @@
+//! どこで: schema migration registry テスト / 何を: 宣言順序と tick 跨ぎの再開・検証失敗 / なぜ: upgrade 元の版に関わらず移行が確定まで進むことを担保するため
+
+use evm_core::schema_migration::{
+    current_schema_migration, schema_migration_tick, SCHEMA_MIGRATIONS, SCHEMA_MIGRATION_BATCH,
+    SCHEMA_MIGRATION_ERR_TX_LOCS_MISMATCH,
+};
+use evm_db::chain_data::{TxId, TxLoc};
+use evm_db::meta::{
+    current_schema_version, get_meta, schema_migration_state, set_needs_migration_and_schema,
+    set_schema_migration_state, set_tx_locs_v3_active, tx_locs_v3_active, SchemaMigrationPhase,
+    SchemaMigrationState,
+};
+use evm_db::stable_state::{init_stable_state, with_state, with_state_mut};
+
+fn seed_legacy_tx_locs(count: u32) {
+    with_state_mut(|state| {
+        for index in 0..count {
+            let mut key = [0u8; 32];
+            key[..4].copy_from_slice(&index.to_be_bytes());
+            state
+                .tx_locs
+                .insert(TxId(key), TxLoc::queued(u64::from(index)));
+        }
+    });
+}
+
+fn start_migration_from(version: u32) {
+    set_needs_migration_and_schema(version);
+    set_schema_migration_state(SchemaMigrationState {
+        phase: SchemaMigrationPhase::Init,
+        from_version: version,
+        to_version: current_schema_version(),
+        ..SchemaMigrationState::done()
+    });
+}
+
+#[test]
+fn registry_is_ordered_and_inside_current_schema() {
+    let mut last_to = 0u32;
+    for migration in SCHEMA_MIGRATIONS {
+        assert!(
+            migration.from_version < migration.to_version,
+            "{}",
+            migration.id
+        );
+        assert!(migration.to_version > last_to, "{}", migration.id);
+        assert!(migration.to_version <= current_schema_version());
+        assert!(!migration.rollback.is_empty(), "{}", migration.id);
+        last_to = migration.to_version;
+    }
+}
+
+#[test]
+fn driver_resumes_batches_across_ticks_until_schema_is_applied() {
+    init_stable_state();
+    set_tx_locs_v3_active(false);
+    let legacy = SCHEMA_MIGRATION_BATCH + 88;
+    seed_legacy_tx_locs(legacy);
+    start_migration_from(2);
+
+    // Init -> Scan -> 最初の batch で止まり、途中経過が state に残る。
+    assert!(!schema_migration_tick(8, 1));
+    let state = schema_migration_state();
+    assert_eq!(state.phase, SchemaMigrationPhase::Rewrite);
+    assert_eq!(state.cursor, u64::from(SCHEMA_MIGRATION_BATCH));
+    assert!(state.cursor_key_set);
+    assert_eq!(
+        current_schema_migration(&state).map(|migration| migration.id),
+        Some("tx_locs_v3")
+    );
+    assert!(!tx_locs_v3_active());
+
+    // 残り batch -> Verify -> 次の migration の選択までで予算が尽きる。
+    assert!(!schema_migration_tick(3, 2));
+    assert!(tx_locs_v3_active());
+    assert_eq!(
+        current_schema_migration(&schema_migration_state()).map(|migration| migration.id),
+        Some("eth_tx_hash_index")
+    );
+
+    assert!(schema_migration_tick(8, 3));
+    let state = schema_migration_state();
+    assert_eq!(state.phase, SchemaMigrationPhase::Done);
+    assert_eq!(current_schema_migration(&state).map(|m| m.id), None);
+    let meta = get_meta();
+    assert!(!meta.needs_migration);
+    assert_eq!(meta.schema_version, current_schema_version());
+    assert_eq!(meta.last_migration_from, 2);
+    assert_eq!(meta.last_migration_ts, 3);
+    with_state(|state| assert_eq!(state.tx_locs_v3.len(), u64::from(legacy)));
+}
+
+#[test]
+fn failed_verification_latches_error_on_that_migration() {
+    init_stable_state();
+    set_tx_locs_v3_active(false);
+    seed_legacy_tx_locs(4);
+    start_migration_from(2);
+    set_schema_migration_state(SchemaMigrationState {
+        phase: SchemaMigrationPhase::Verify,
+        migration_index: 0,
+        ..schema_migration_state()
+    });
+
+    assert!(!schema_migration_tick(8, 1));
+    let state = schema_migration_state();
+    assert_eq!(state.phase, SchemaMigrationPhase::Error);
+    assert_eq!(state.last_error, SCHEMA_MIGRATION_ERR_TX_LOCS_MISMATCH);
+    assert_eq!(
+        current_schema_migration(&state).map(|migration| migration.id),
+        Some("tx_locs_v3")
+    );
+    assert!(!tx_locs_v3_active());
+    assert!(get_meta().needs_migration);
+    assert!(!schema_migration_tick(8, 2));
+}
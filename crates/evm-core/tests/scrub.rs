@@ -0,0 +1,118 @@
+//! どこで: 整合性scrubのテスト / 何を: 欠けた索引の検出・自動修復と一時停止 / なぜ: hot pathで読まれない不整合も scrub で見つかり、安全な範囲だけ補われることを担保するため
+
+use evm_core::hash;
+use evm_core::scrub::{
+    scrub_findings, scrub_status, scrub_tick, set_scrub_paused, start_scrub, ScrubError,
+};
+use evm_db::chain_data::{ScrubFindingKind, ScrubPhase, ScrubTarget, TxId};
+use evm_db::stable_state::{init_stable_state, with_state, with_state_mut};
+
+mod common;
+
+fn relax_fee_floor_for_tests() {
+    with_state_mut(|state| {
+        let mut chain_state = *state.chain_state.get();
+        chain_state.base_fee = 1;
+        chain_state.min_gas_price = 1;
+        chain_state.min_priority_fee = 1;
+        state.chain_state.set(chain_state);
+    });
+}
+
+fn seed_included_tx() -> TxId {
+    relax_fee_floor_for_tests();
+    let caller_principal = vec![0x42];
+    let caller = hash::derive_evm_address_from_principal(&caller_principal).expect("must derive");
+    common::fund_account(caller, 1_000_000_000_000_000_000);
+    let (tx_id, _) = common::execute_ic_tx_via_produce(
+        caller_principal,
+        vec![0xaa],
+        common::build_ic_tx_input([0x12u8; 20], 0, 2_000_000_000, 1_000_000_000),
+    );
+    tx_id
+}
+
+fn run_to_idle(now: u64) {
+    for _ in 0..64 {
+        let status = scrub_tick(4, now).expect("tick");
+        if status.state.phase == ScrubPhase::Idle {
+            return;
+        }
+    }
+    panic!("scrub must converge");
+}
+
+#[test]
+fn scrub_rebuilds_missing_tx_index_and_drops_dangling_queue_entry() {
+    init_stable_state();
+    let tx_id = seed_included_tx();
+    let block_number = with_state_mut(|state| {
+        let number = state.blocks.last_key_value().expect("block").0;
+        state.tx_index.remove(&tx_id);
+        state.wrap_queue.insert(900, TxId([0x77; 32]));
+        number
+    });
+    assert_eq!(
+        evm_core::chain::get_receipt(&tx_id).map(|r| r.tx_id),
+        Some(tx_id)
+    );
+
+    assert_eq!(scrub_tick(0, 1), Err(ScrubError::InvalidLimit));
+    start_scrub(true, 1);
+    run_to_idle(2);
+
+    let status = scrub_status().state;
+    assert_eq!(status.run_id, 1);
+    assert_eq!(status.finished_at, 2);
+    assert_eq!(status.findings, 2);
+    assert_eq!(status.repaired, 2);
+    let findings = scrub_findings(None, 16);
+    let kinds: Vec<_> = findings
+        .iter()
+        .map(|(_, finding)| (finding.target, finding.kind, finding.repaired))
+        .collect();
+    assert_eq!(
+        kinds,
+        vec![
+            (ScrubTarget::Blocks, ScrubFindingKind::MissingTxIndex, true),
+            (
+                ScrubTarget::WrapQueue,
+                ScrubFindingKind::DanglingQueueEntry,
+                true
+            ),
+        ]
+    );
+    assert_eq!(findings[0].1.key, tx_id.0);
+    common::assert_block_persist_invariants(block_number, &[tx_id]);
+    with_state(|state| assert!(state.wrap_queue.get(&900).is_none()));
+
+    // 直した後の再走査では何も見つからない。
+    start_scrub(true, 3);
+    run_to_idle(4);
+    assert_eq!(scrub_status().state.findings, 0);
+    assert_eq!(scrub_findings(Some(findings[1].0), 16), Vec::new());
+}
+
+#[test]
+fn report_only_scrub_leaves_state_and_pause_stops_progress() {
+    init_stable_state();
+    let tx_id = seed_included_tx();
+    with_state_mut(|state| {
+        state.tx_index.remove(&tx_id);
+    });
+    assert_eq!(set_scrub_paused(true), Err(ScrubError::NotActive));
+
+    start_scrub(false, 1);
+    set_scrub_paused(true).expect("pause");
+    let paused = scrub_tick(4, 2).expect("tick");
+    assert!(!paused.did_work);
+    assert_eq!(paused.state.scanned, 0);
+
+    set_scrub_paused(false).expect("resume");
+    run_to_idle(3);
+    let findings = scrub_findings(None, 16);
+    assert_eq!(findings.len(), 1);
+    assert_eq!(findings[0].1.kind, ScrubFindingKind::MissingTxIndex);
+    assert!(!findings[0].1.repaired);
+    with_state(|state| assert!(state.tx_index.get(&tx_id).is_none()));
+}
@@ -0,0 +1,124 @@
+//! どこで: state proofテスト / 何を: node DB から組んだ proof を state root で検証 / なぜ: certified state read の値を root key だけで確かめられることを担保するため
+
+use alloy_primitives::{keccak256, Bytes, B256, U256};
+use alloy_rlp::Encodable;
+use alloy_trie::proof::verify_proof;
+use alloy_trie::{Nibbles, TrieAccount};
+use evm_core::chain;
+use evm_core::state_proof::{prove_account, prove_storage, StateProofError};
+use evm_db::chain_data::MigrationPhase;
+use evm_db::stable_state::{init_stable_state, with_state, with_state_mut};
+use evm_db::types::keys::{make_account_key, make_storage_key};
+use evm_db::types::values::{AccountVal, U256Val};
+
+fn build_trie_from_state() -> [u8; 32] {
+    with_state_mut(|state| {
+        let mut m = *state.state_root_migration.get();
+        m.phase = MigrationPhase::Init;
+        m.cursor = 0;
+        state.state_root_migration.set(m);
+    });
+    let mut done = false;
+    for _ in 0..64 {
+        done = chain::state_root_migration_tick(1024);
+        if done {
+            break;
+        }
+    }
+    assert!(done);
+    with_state(|state| state.state_root_meta.get().state_root)
+}
+
+fn to_bytes(proof: &[Vec<u8>]) -> Vec<Bytes> {
+    proof.iter().cloned().map(Bytes::from).collect()
+}
+
+#[test]
+fn account_and_storage_proofs_verify_against_state_root() {
+    init_stable_state();
+    let contract = [0x42u8; 20];
+    with_state_mut(|state| {
+        for i in 1..=40u8 {
+            let mut balance = [0u8; 32];
+            balance[31] = i;
+            state.accounts.insert(
+                make_account_key([i; 20]),
+                AccountVal::from_parts(u64::from(i), balance, [0u8; 32]),
+            );
+        }
+        state.accounts.insert(
+            make_account_key(contract),
+            AccountVal::from_parts(1, [0u8; 32], [0u8; 32]),
+        );
+        for slot in 1..=8u8 {
+            state.storage.insert(
+                make_storage_key(contract, [slot; 32]),
+                U256Val::new([slot; 32]),
+            );
+        }
+    });
+    let state_root = B256::from(build_trie_from_state());
+
+    let funded = prove_account(state_root.0, [7u8; 20]).expect("funded proof");
+    assert_eq!(funded.nonce, 7);
+    assert_eq!(funded.balance[31], 7);
+    let mut encoded = Vec::new();
+    TrieAccount {
+        nonce: funded.nonce,
+        balance: U256::from_be_bytes(funded.balance),
+        storage_root: B256::from(funded.storage_root),
+        code_hash: B256::from(funded.code_hash),
+    }
+    .encode(&mut encoded);
+    verify_proof(
+        state_root,
+        Nibbles::unpack(keccak256([7u8; 20])),
+        Some(encoded),
+        to_bytes(&funded.proof).iter(),
+    )
+    .expect("funded account proof verifies");
+
+    let missing = prove_account(state_root.0, [0xeeu8; 20]).expect("absent proof");
+    assert_eq!(missing.nonce, 0);
+    assert_eq!(missing.balance, [0u8; 32]);
+    verify_proof(
+        state_root,
+        Nibbles::unpack(keccak256([0xeeu8; 20])),
+        None,
+        to_bytes(&missing.proof).iter(),
+    )
+    .expect("absent account proof verifies");
+
+    let account = prove_account(state_root.0, contract).expect("contract proof");
+    let storage_root = B256::from(account.storage_root);
+    let slot = prove_storage(account.storage_root, [3u8; 32]).expect("slot proof");
+    assert_eq!(slot.value, [3u8; 32]);
+    let mut encoded = Vec::new();
+    U256::from_be_bytes(slot.value).encode(&mut encoded);
+    verify_proof(
+        storage_root,
+        Nibbles::unpack(keccak256([3u8; 32])),
+        Some(encoded),
+        to_bytes(&slot.proof).iter(),
+    )
+    .expect("slot proof verifies");
+
+    let empty_slot = prove_storage(account.storage_root, [0x99u8; 32]).expect("empty slot");
+    assert_eq!(empty_slot.value, [0u8; 32]);
+    verify_proof(
+        storage_root,
+        Nibbles::unpack(keccak256([0x99u8; 32])),
+        None,
+        to_bytes(&empty_slot.proof).iter(),
+    )
+    .expect("empty slot proof verifies");
+}
+
+#[test]
+fn proof_against_unknown_root_reports_missing_node() {
+    init_stable_state();
+    assert_eq!(
+        prove_account([0x11u8; 32], [1u8; 20]),
+        Err(StateProofError::MissingNode([0x11u8; 32]))
+    );
+}
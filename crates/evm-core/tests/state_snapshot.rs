@@ -0,0 +1,277 @@
+//! どこで: 状態スナップショットのテスト / 何を: export→importの往復とroot検証 / なぜ: 別canisterへ移した状態が元と一致することを担保するため
+
+use evm_core::chain;
+use evm_core::export::ExportError;
+use evm_core::state_snapshot::{
+    begin_state_snapshot_import, clear_state_snapshot_import, export_state_snapshot,
+    finish_state_snapshot_import, import_state_snapshot_page, set_state_snapshot_export_hold,
+    state_snapshot_import_active, StateSnapshotImportError, StateSnapshotManifest,
+    StateSnapshotPage, STATE_SNAPSHOT_SEGMENT_CODES,
+};
+use evm_db::chain_data::{MigrationPhase, StateSnapshotImportPhase};
+use evm_db::stable_state::{init_stable_state, with_state, with_state_mut};
+use evm_db::types::keys::make_storage_key;
+use evm_db::types::values::U256Val;
+
+mod common;
+
+const SOURCE_HEAD: u64 = 7;
+
+fn build_source_state() {
+    init_stable_state();
+    common::install_contract([0x31; 20], &[0x60, 0x00, 0x60, 0x00, 0xf3]);
+    common::fund_account([0x32; 20], 5_000_000_000);
+    for slot in 0..6u8 {
+        with_state_mut(|state| {
+            state.storage.insert(
+                make_storage_key([0x31; 20], [slot; 32]),
+                U256Val::new([slot.wrapping_add(1); 32]),
+            );
+        });
+    }
+    with_state_mut(|state| {
+        let mut head = *state.head.get();
+        head.number = SOURCE_HEAD;
+        head.block_hash = [0x77; 32];
+        head.timestamp = 1_700_000_000;
+        state.head.set(head);
+        let mut meta = *state.state_root_meta.get();
+        meta.initialized = false;
+        state.state_root_meta.set(meta);
+    });
+    while !chain::state_root_migration_tick(128) {}
+}
+
+fn export_all(max_bytes: u32) -> Vec<StateSnapshotPage> {
+    set_state_snapshot_export_hold(true);
+    let mut pages = Vec::new();
+    let mut cursor = None;
+    loop {
+        let page = export_state_snapshot(cursor, max_bytes).expect("export");
+        cursor = page.next_cursor.clone();
+        pages.push(page);
+        if cursor.is_none() {
+            set_state_snapshot_export_hold(false);
+            return pages;
+        }
+    }
+}
+
+fn begin_and_clear(manifest: StateSnapshotManifest) {
+    begin_state_snapshot_import(manifest).expect("begin");
+    assert!(state_snapshot_import_active());
+    while clear_state_snapshot_import(2).expect("clear").phase == StateSnapshotImportPhase::Clearing
+    {
+    }
+}
+
+fn import_all(manifest: StateSnapshotManifest, pages: &[StateSnapshotPage]) -> [u8; 32] {
+    begin_and_clear(manifest);
+    for page in pages {
+        import_state_snapshot_page(page).expect("page");
+    }
+    loop {
+        match finish_state_snapshot_import(128) {
+            Ok(status) if status.phase == StateSnapshotImportPhase::Idle => {
+                return with_state(|state| state.state_root_meta.get().state_root);
+            }
+            Ok(_) => {}
+            Err(StateSnapshotImportError::RootMismatch { computed, .. }) => return computed,
+            Err(err) => panic!("finish failed: {err:?}"),
+        }
+    }
+}
+
+#[test]
+fn state_snapshot_roundtrip_restores_state_root_and_head() {
+    let pages = std::thread::spawn(|| {
+        build_source_state();
+        let pages = export_all(200);
+        assert!(pages.len() > 3, "small max_bytes must split into pages");
+        assert_eq!(
+            pages.iter().map(|page| page.entry_count).sum::<u32>(),
+            1 + 1 + 6 + 1
+        );
+        // head が進んだ canister には import できない。
+        assert_eq!(
+            begin_state_snapshot_import(pages[0].manifest),
+            Err(StateSnapshotImportError::NotAllowed(
+                "state_snapshot.target_not_empty"
+            ))
+        );
+        // 状態が動いたら古いcursorは使えない。
+        let stale = pages[0].next_cursor.clone();
+        common::fund_account([0x33; 20], 1);
+        set_state_snapshot_export_hold(true);
+        assert_eq!(
+            export_state_snapshot(stale, 200),
+            Err(ExportError::InvalidCursor("state_snapshot.state_changed"))
+        );
+        pages
+    })
+    .join()
+    .expect("source thread");
+    let manifest = pages[0].manifest;
+
+    std::thread::spawn(move || {
+        init_stable_state();
+        begin_and_clear(manifest);
+        let code_page = pages
+            .iter()
+            .find(|page| page.segment == STATE_SNAPSHOT_SEGMENT_CODES)
+            .expect("code page");
+        let mut tampered = code_page.clone();
+        let last = tampered.entries.len() - 1;
+        tampered.entries[last] ^= 0xff;
+        assert_eq!(
+            import_state_snapshot_page(&tampered),
+            Err(StateSnapshotImportError::InvalidPage(
+                "state_snapshot.code_hash_mismatch"
+            ))
+        );
+
+        let root = import_all(manifest, &pages);
+        assert_eq!(root, manifest.state_root);
+        assert!(!state_snapshot_import_active());
+        let head = with_state(|state| *state.head.get());
+        assert_eq!(head.number, SOURCE_HEAD);
+        assert_eq!(head.block_hash, manifest.block_hash);
+        assert_eq!(
+            chain::get_prune_status().pruned_before_block,
+            Some(SOURCE_HEAD)
+        );
+        let phase = with_state(|state| state.state_root_migration.get().phase);
+        assert_eq!(phase, MigrationPhase::Done);
+
+        // 取り込んだcanisterから再exportしても同じentryになる。
+        let again = export_all(200);
+        let flatten = |pages: &[StateSnapshotPage]| {
+            pages
+                .iter()
+                .flat_map(|page| page.entries.clone())
+                .collect::<Vec<u8>>()
+        };
+        assert_eq!(flatten(&again), flatten(&pages));
+    })
+    .join()
+    .expect("target thread");
+}
+
+#[test]
+fn state_snapshot_import_rejects_mismatched_root() {
+    let pages = std::thread::spawn(|| {
+        build_source_state();
+        export_all(1_500_000)
+    })
+    .join()
+    .expect("source thread");
+    let mut manifest = pages[0].manifest;
+    manifest.state_root = [0xee; 32];
+
+    std::thread::spawn(move || {
+        init_stable_state();
+        // manifestが違うページは受け付けない。
+        begin_and_clear(manifest);
+        assert_eq!(
+            import_state_snapshot_page(&pages[0]),
+            Err(StateSnapshotImportError::InvalidPage(
+                "state_snapshot.manifest_mismatch"
+            ))
+        );
+        let forged = pages
+            .into_iter()
+            .map(|page| StateSnapshotPage { manifest, ..page })
+            .collect::<Vec<_>>();
+        let computed = import_all(manifest, &forged);
+        assert_ne!(computed, manifest.state_root);
+        // rootが一致しない限りheadは切り替わらず、書き込み停止も解けない。
+        assert!(state_snapshot_import_active());
+        assert_eq!(with_state(|state| state.head.get().number), 0);
+    })
+    .join()
+    .expect("target thread");
+}
+
+#[test]
+fn state_snapshot_export_requires_hold_and_hold_stops_production() {
+    build_source_state();
+    assert_eq!(
+        export_state_snapshot(None, 200),
+        Err(ExportError::MissingData("state_snapshot.export_not_held"))
+    );
+    set_state_snapshot_export_hold(true);
+    let first = export_state_snapshot(None, 200).expect("export");
+    with_state_mut(|state| {
+        let mut chain_state = *state.chain_state.get();
+        chain_state.base_fee = 1;
+        chain_state.min_gas_price = 1;
+        chain_state.min_priority_fee = 1;
+        state.chain_state.set(chain_state);
+    });
+    common::fund_account(
+        evm_core::hash::derive_evm_address_from_principal(&[0x41]).expect("must derive"),
+        1_000_000_000_000_000_000,
+    );
+    chain::submit_tx_in(chain::TxIn::IcSynthetic {
+        caller_principal: vec![0x41],
+        canister_id: vec![0x42],
+        tx: common::build_default_ic_tx_input(0),
+    })
+    .expect("submit");
+    // hold 中は head が動かない。
+    assert_eq!(
+        chain::produce_block(1),
+        Err(chain::ChainError::ProductionHeld)
+    );
+    assert_eq!(with_state(|state| state.head.get().number), SOURCE_HEAD);
+    set_state_snapshot_export_hold(false);
+    chain::produce_block(1).expect("produce after release");
+    set_state_snapshot_export_hold(true);
+    assert_eq!(
+        export_state_snapshot(first.next_cursor, 200),
+        Err(ExportError::InvalidCursor("state_snapshot.state_changed"))
+    );
+}
+
+#[test]
+fn state_snapshot_import_clears_target_across_calls() {
+    let pages = std::thread::spawn(|| {
+        build_source_state();
+        export_all(1_500_000)
+    })
+    .join()
+    .expect("source thread");
+    let manifest = pages[0].manifest;
+
+    std::thread::spawn(move || {
+        init_stable_state();
+        for idx in 0..5u8 {
+            common::fund_account([0x50 + idx; 20], 1);
+        }
+        let accounts_before = with_state(|state| state.accounts.len());
+        begin_state_snapshot_import(manifest).expect("begin");
+        // 消し終わるまではページを受け付けない。
+        assert_eq!(
+            import_state_snapshot_page(&pages[0]),
+            Err(StateSnapshotImportError::NotAllowed(
+                "state_snapshot.not_loading"
+            ))
+        );
+        let status = clear_state_snapshot_import(2).expect("clear");
+        assert_eq!(status.phase, StateSnapshotImportPhase::Clearing);
+        assert_eq!(status.accounts, accounts_before - 2);
+        let mut calls = 1;
+        while clear_state_snapshot_import(2).expect("clear").phase
+            == StateSnapshotImportPhase::Clearing
+        {
+            calls += 1;
+        }
+        assert!(calls >= 2, "clearing must span several calls");
+        assert_eq!(with_state(|state| state.accounts.len()), 0);
+        for page in &pages {
+            import_state_snapshot_page(page).expect("page");
+        }
+    })
+    .join()
+    .expect("target thread");
+}
@@ -0,0 +1,173 @@
+//! どこで: BlobStoreの格納形式 / 何を: プリセット辞書付きLZ4の圧縮envelopeと透過的な展開 / なぜ: logの多いreceiptやtraceのサイズクラスを下げ、同じtarget_bytesでより長く履歴を保つため
+
+use crate::size_class::{smallest_class, CLASS_4M};
+use std::borrow::Cow;
+use std::sync::OnceLock;
+
+/// envelope = magic(8) + codec(1) + 展開後の長さ(u32 BE) + 本体。
+/// magic を持たない bytes は従来どおり未圧縮として扱う。
+const COMPRESSED_BLOB_MAGIC: [u8; 8] = *b"blobz\0\0\x01";
+const HEADER_LEN: usize = COMPRESSED_BLOB_MAGIC.len() + 1 + 4;
+
+/// 本体は未圧縮。raw がたまたま magic で始まるときに、圧縮形式と取り違えないよう包む。
+pub const BLOB_CODEC_STORED: u8 = 0;
+/// LZ4 block + PRESET_DICTIONARY_V1。辞書を変えるときは codec を増やし、古い codec の展開は残す。
+pub const BLOB_CODEC_LZ4_DICT_V1: u8 = 1;
+pub const CURRENT_BLOB_CODEC: u8 = BLOB_CODEC_LZ4_DICT_V1;
+
+/// プリセット辞書。実データから学習したものではなく、固定の magic とよく出る event topic を並べたもの。
+static PRESET_DICTIONARY_V1: OnceLock<Vec<u8>> = OnceLock::new();
+
+/// よく出る event topic（keccak256 of signature）。
+const PRESET_DICTIONARY_V1_TOPICS: [[u8; 32]; 13] = [
+    // Transfer(address,address,uint256)
+    hex32("ddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef"),
+    // Approval(address,address,uint256)
+    hex32("8c5be1e5ebec7d5bd14f71427d1e84f3dd0314c0f7b2291e5b200ac8c7c3b925"),
+    // ApprovalForAll(address,address,bool)
+    hex32("17307eab39ab6107e8899845ad3d59bd9653f200f220920489ca2b5937696c31"),
+    // TransferSingle(address,address,address,uint256,uint256)
+    hex32("c3d58168c5ae7397731d063d5bbf3d657854427343f4c083240f7aacaa2d0f62"),
+    // TransferBatch(address,address,address,uint256[],uint256[])
+    hex32("4a39dc06d4c0dbc64b70af90fd698a233a518aa5d07e595d983b8c0526c8f7fb"),
+    // Deposit(address,uint256)
+    hex32("e1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"),
+    // Withdrawal(address,uint256)
+    hex32("7fcf532c15f0a6db0bd6d0e038bea71d30d808c7d98cb3bf7268a95bf5081b65"),
+    // Sync(uint112,uint112)
+    hex32("1c411e9a96e071241c2f21f7726b17ae89e3cab4c78be50e062b03a9fffbbad1"),
+    // Swap(address,uint256,uint256,uint256,uint256,address)
+    hex32("d78ad95fa46c994b6551d0da85fc275fe613ce37657fb8d5e3d130840159d822"),
+    // Swap(address,address,int256,int256,uint160,uint128,int24)
+    hex32("c42079f94a6350d7e6235f29174924f928cc2ac818eb64fed8004e115fbcca67"),
+    // OwnershipTransferred(address,address)
+    hex32("8be0079c531659141344cd1fd0a4f28419497f9722a3daafe3b4186f6b6457e0"),
+    // Mint(address,uint256,uint256)
+    hex32("4c209b5fc8ad50758f13e2e1088ba56a560dff690a1c6fef26394f4c03821c4f"),
+    // Burn(address,uint256,uint256,address)
+    hex32("dccd412f0b1252819cb1fd330b93224ca42612892bb3f4f789976e6d81936496"),
+];
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BlobCodecError {
+    UnknownCodec(u8),
+    Truncated,
+    LengthMismatch,
+    Corrupt,
+}
+
+/// envelope を持つか（codec は問わない）。再圧縮 migration はこれで書き直し済みを飛ばす。
+pub fn has_envelope(stored: &[u8]) -> bool {
+    stored.len() >= HEADER_LEN && stored.starts_with(&COMPRESSED_BLOB_MAGIC)
+}
+
+pub fn is_compressed(stored: &[u8]) -> bool {
+    has_envelope(stored) && stored[COMPRESSED_BLOB_MAGIC.len()] != BLOB_CODEC_STORED
+}
+
+/// 圧縮でサイズクラスが下がるときだけ圧縮 envelope を返す。下がらなければ raw のまま。
+/// raw が magic で始まるときは、展開側で誤読しないよう STORED envelope で包む。
+pub fn encode_blob(raw: &[u8]) -> Cow<'_, [u8]> {
+    let Ok(raw_len) = u32::try_from(raw.len()) else {
+        return Cow::Borrowed(raw);
+    };
+    let Ok(raw_class) = smallest_class(raw.len()) else {
+        return stored_unless_ambiguous(raw, raw_len);
+    };
+    let compressed =
+        lz4_flex::block::compress_with_dict(raw, preset_dictionary(CURRENT_BLOB_CODEC));
+    let out = envelope(CURRENT_BLOB_CODEC, raw_len, &compressed);
+    match smallest_class(out.len()) {
+        Ok(class) if verified_core::blob_codec::keep_compressed(raw_class, class) => {
+            Cow::Owned(out)
+        }
+        _ => stored_unless_ambiguous(raw, raw_len),
+    }
+}
+
+fn stored_unless_ambiguous(raw: &[u8], raw_len: u32) -> Cow<'_, [u8]> {
+    if raw.starts_with(&COMPRESSED_BLOB_MAGIC) {
+        Cow::Owned(envelope(BLOB_CODEC_STORED, raw_len, raw))
+    } else {
+        Cow::Borrowed(raw)
+    }
+}
+
+fn envelope(codec: u8, raw_len: u32, body: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(HEADER_LEN + body.len());
+    out.extend_from_slice(&COMPRESSED_BLOB_MAGIC);
+    out.push(codec);
+    out.extend_from_slice(&raw_len.to_be_bytes());
+    out.extend_from_slice(body);
+    out
+}
+
+/// 格納されていた bytes を元の encoding へ戻す。未圧縮ならそのまま返す。
+pub fn decode_blob(stored: Vec<u8>) -> Result<Vec<u8>, BlobCodecError> {
+    if !stored.starts_with(&COMPRESSED_BLOB_MAGIC) {
+        return Ok(stored);
+    }
+    if stored.len() < HEADER_LEN {
+        return Err(BlobCodecError::Truncated);
+    }
+    let codec = stored[COMPRESSED_BLOB_MAGIC.len()];
+    if codec != BLOB_CODEC_STORED && codec != BLOB_CODEC_LZ4_DICT_V1 {
+        return Err(BlobCodecError::UnknownCodec(codec));
+    }
+    let mut len_bytes = [0u8; 4];
+    len_bytes.copy_from_slice(&stored[COMPRESSED_BLOB_MAGIC.len() + 1..HEADER_LEN]);
+    let declared_len = u32::from_be_bytes(len_bytes);
+    if declared_len == 0 || declared_len > CLASS_4M {
+        return Err(BlobCodecError::LengthMismatch);
+    }
+    let raw = if codec == BLOB_CODEC_STORED {
+        stored[HEADER_LEN..].to_vec()
+    } else {
+        let capacity = usize::try_from(declared_len).map_err(|_| BlobCodecError::LengthMismatch)?;
+        lz4_flex::block::decompress_with_dict(
+            &stored[HEADER_LEN..],
+            capacity,
+            preset_dictionary(codec),
+        )
+        .map_err(|_| BlobCodecError::Corrupt)?
+    };
+    if !verified_core::blob_codec::decoded_len_valid(declared_len, raw.len(), CLASS_4M) {
+        return Err(BlobCodecError::LengthMismatch);
+    }
+    Ok(raw)
+}
+
+fn preset_dictionary(codec: u8) -> &'static [u8] {
+    debug_assert_eq!(codec, BLOB_CODEC_LZ4_DICT_V1);
+    PRESET_DICTIONARY_V1.get_or_init(|| {
+        let mut out = Vec::with_capacity(16 + 64 + PRESET_DICTIONARY_V1_TOPICS.len() * 32);
+        // receipt v2 / internal trace の先頭magic
+        out.extend_from_slice(b"rcptv2\0\x02");
+        out.extend_from_slice(b"itrace01");
+        // 左詰めaddressやuint256の上位に並ぶゼロ
+        out.extend_from_slice(&[0u8; 64]);
+        for topic in PRESET_DICTIONARY_V1_TOPICS.iter() {
+            out.extend_from_slice(topic);
+        }
+        out
+    })
+}
+
+const fn hex32(hex: &str) -> [u8; 32] {
+    let bytes = hex.as_bytes();
+    let mut out = [0u8; 32];
+    let mut i = 0;
+    while i < 32 {
+        out[i] = (hex_nibble(bytes[i * 2]) << 4) | hex_nibble(bytes[i * 2 + 1]);
+        i += 1;
+    }
+    out
+}
+
+const fn hex_nibble(c: u8) -> u8 {
+    match c {
+        b'0'..=b'9' => c - b'0',
+        b'a'..=b'f' => c - b'a' + 10,
+        _ => panic!("invalid hex"),
+    }
+}
@@ -1,9 +1,10 @@
 //! どこで: stableのBlob格納 / 何を: arena + alloc_table + free_list / なぜ: 再利用可能な基盤を先に固定するため
 
+use crate::blob_codec::{decode_blob, encode_blob, BlobCodecError};
 use crate::blob_ptr::BlobPtr;
 use crate::corrupt_log::record_corrupt;
 use crate::memory::VMem;
-use crate::size_class::{smallest_class, SizeClassError};
+use crate::size_class::{smallest_class, SizeClassError, CLASSES};
 use ic_stable_structures::storable::Bound;
 use ic_stable_structures::{Memory, StableBTreeMap, StableCell, Storable};
 use std::borrow::Cow;
@@ -148,6 +149,7 @@ pub enum BlobError {
     InvalidPointer,
     LengthMismatch,
     DuplicateFree,
+    Codec(BlobCodecError),
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
@@ -241,6 +243,16 @@ impl BlobStore {
         Ok(ptr)
     }
 
+    /// 圧縮でサイズクラスが下がるときだけ envelope で格納する。
+    pub fn store_encoded(&mut self, raw: &[u8]) -> Result<BlobPtr, BlobError> {
+        self.store_bytes(&encode_blob(raw))
+    }
+
+    /// store_encoded / store_bytes のどちらで書かれた blob も元の bytes で返す。
+    pub fn read_decoded(&self, ptr: &BlobPtr) -> Result<Vec<u8>, BlobError> {
+        decode_blob(self.read(ptr)?).map_err(BlobError::Codec)
+    }
+
     pub fn read(&self, ptr: &BlobPtr) -> Result<Vec<u8>, BlobError> {
         let entry = self
             .alloc_table
@@ -346,7 +358,7 @@ impl BlobStore {
     }
 
     // prune回復経路向け:
-    // Used/Quarantine のどちらからでも Free へ遷移させる。
+    // Used/Quarantine のどちらからでも Free へ遷移させ、free_list にも載せる。
     // 同一ポインタの再実行でも安全に前進できるよう冪等性を持たせる。
     pub fn reclaim_for_prune(&mut self, ptr: &BlobPtr) -> Result<(), BlobError> {
         let key = AllocKey::new(ptr.class(), ptr.offset());
@@ -380,6 +392,9 @@ impl BlobStore {
         }
         entry.state = BlobState::Free;
         self.alloc_table.insert(key, entry);
+        if self.free_list_by_class.get(&key).is_none() {
+            self.free_list_by_class.insert(key, ());
+        }
         self.usage_totals.free_class_bytes = self
             .usage_totals
             .free_class_bytes
@@ -387,6 +402,92 @@ impl BlobStore {
         Ok(())
     }
 
+    // compaction向け:
+    // 同じクラスでより低いoffsetのFree slotがあれば中身を複製して新しいポインタを返す。
+    // 旧slotはUsedのまま残し、参照の張り替え後に release_relocated で解放する。
+    pub fn relocate_lower(&mut self, ptr: &BlobPtr) -> Result<Option<BlobPtr>, BlobError> {
+        let src_key = AllocKey::new(ptr.class(), ptr.offset());
+        let src_entry = self
+            .alloc_table
+            .get(&src_key)
+            .ok_or(BlobError::MissingAllocEntry)?;
+        if src_entry.gen != ptr.gen() {
+            return Err(BlobError::InvalidPointer);
+        }
+        if src_entry.state != BlobState::Used {
+            return Err(BlobError::InvalidState);
+        }
+        let offset = match self.lowest_free_below(ptr.class(), ptr.offset()) {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+        if !verified_core::blob_compaction::relocation_moves_lower(
+            ptr.class(),
+            ptr.offset(),
+            ptr.class(),
+            offset,
+        ) {
+            return Ok(None);
+        }
+        let data = self.read(ptr)?;
+        let key = AllocKey::new(ptr.class(), offset);
+        let mut entry = self
+            .alloc_table
+            .get(&key)
+            .ok_or(BlobError::MissingAllocEntry)?;
+        entry.gen = entry.gen.checked_add(1).ok_or(BlobError::Overflow)?;
+        entry.state = BlobState::Used;
+        self.free_list_by_class.remove(&key);
+        self.alloc_table.insert(key, entry);
+        self.usage_totals.free_class_bytes = self
+            .usage_totals
+            .free_class_bytes
+            .saturating_sub(class_u64(ptr.class()));
+        self.usage_totals.used_class_bytes = self
+            .usage_totals
+            .used_class_bytes
+            .saturating_add(class_u64(ptr.class()));
+        let moved = BlobPtr::new(offset, ptr.len(), ptr.class(), entry.gen);
+        self.write(&moved, &data)?;
+        Ok(Some(moved))
+    }
+
+    // relocate_lower の移動元を解放する。再実行しても安全なよう冪等にし、
+    // 次の relocate_lower / allocate から見つかるよう free_list にも載せる。
+    pub fn release_relocated(&mut self, ptr: &BlobPtr) -> Result<(), BlobError> {
+        self.reclaim_for_prune(ptr)
+    }
+
+    // arena末尾に並ぶFree slotを alloc_table から外し、arena_end を下げる。
+    // 外したslotの世代は残らないため、呼び出し側は参照が残っていないことを保証すること。
+    // 戻り値は (外したslot数, 切り詰めたbyte数)。
+    pub fn trim_free_tail(&mut self, max_slots: u32) -> (u32, u64) {
+        let mut slots = 0u32;
+        let mut trimmed = 0u64;
+        while slots < max_slots {
+            let end = *self.arena_end.get();
+            let Some((key, entry)) = self.tail_entry(end) else {
+                break;
+            };
+            if entry.state != BlobState::Free
+                || key.offset().checked_add(class_u64(key.class())) != Some(end)
+            {
+                break;
+            }
+            self.alloc_table.remove(&key);
+            self.free_list_by_class.remove(&key);
+            self.arena_end.set(key.offset());
+            self.usage_totals.free_class_bytes = self
+                .usage_totals
+                .free_class_bytes
+                .saturating_sub(class_u64(key.class()));
+            self.usage_totals.arena_end_bytes = key.offset();
+            slots = slots.saturating_add(1);
+            trimmed = trimmed.saturating_add(class_u64(key.class()));
+        }
+        (slots, trimmed)
+    }
+
     pub fn usage_stats(&self) -> BlobUsageStats {
         let mut stats = self.usage_totals;
         stats.arena_end_bytes = *self.arena_end.get();
@@ -410,6 +511,32 @@ impl BlobStore {
             .map(|entry| entry.key().offset())
     }
 
+    // compaction は1 blobごとに呼ぶため、alloc_table は走査せず free_list の先頭だけを見る。
+    fn lowest_free_below(&self, class: u32, offset: u64) -> Option<u64> {
+        let start = AllocKey::new(class, 0);
+        let end = AllocKey::new(class, offset);
+        self.free_list_by_class
+            .range(start..end)
+            .next()
+            .map(|entry| entry.key().offset())
+    }
+
+    // クラスごとの最後のslotのうち、arena_end 以下で最も後ろにあるもの。
+    fn tail_entry(&self, arena_end: u64) -> Option<(AllocKey, AllocEntry)> {
+        let mut tail: Option<(AllocKey, AllocEntry)> = None;
+        for class in CLASSES.iter() {
+            let start = AllocKey::new(*class, 0);
+            let end = AllocKey::new(*class, arena_end);
+            if let Some(entry) = self.alloc_table.range(start..end).next_back() {
+                let key = *entry.key();
+                if tail.is_none_or(|(current, _)| key.offset() > current.offset()) {
+                    tail = Some((key, entry.value()));
+                }
+            }
+        }
+        tail
+    }
+
     fn current_gen(&self, class: u32, offset: u64) -> Result<u32, BlobError> {
         let entry = self
             .alloc_table
@@ -0,0 +1,235 @@
+//! どこで: archive canister の状態 / 何を: 送り先canister・ack済みblock・archive済み範囲 / なぜ: pruneをarchiveのackまでに制限し、消した履歴の問い合わせ先を返すため
+
+use crate::chain_data::codec::{encode_guarded, mark_decode_failure};
+use crate::chain_data::constants::CALLER_KEY_LEN;
+use crate::chain_data::{CallerKey, TxId};
+use crate::decode::hash_to_array;
+use ic_stable_structures::storable::Bound;
+use ic_stable_structures::Storable;
+use std::borrow::Cow;
+use zerocopy::byteorder::big_endian::{U32, U64};
+use zerocopy::{FromBytes, Immutable, IntoBytes, KnownLayout, Unaligned};
+
+pub const ARCHIVE_STATE_SIZE_U32: u32 = 52;
+pub const ARCHIVE_RANGE_SIZE_U32: u32 = 38;
+pub const ARCHIVED_TX_KEY_LEN: usize = 40;
+pub const ARCHIVED_TX_KEY_LEN_U32: u32 = 40;
+const FLAG_NONE: u8 = 0;
+const FLAG_SOME: u8 = 1;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ArchiveStateV1 {
+    pub schema_version: u32,
+    /// None なら archive を使わず、従来どおり prune で履歴を消す
+    pub canister: Option<CallerKey>,
+    /// archive が受け取ったと ack した最後の block
+    pub acked_through: Option<u64>,
+    pub acked_at: u64,
+}
+
+/// archive_ranges の値。key は範囲の先頭 block。
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ArchiveRangeV1 {
+    pub end_block: u64,
+    pub canister: CallerKey,
+}
+
+/// archived_tx_order の key（block番号BE + tx_id）。block順に並ぶので、上限を超えたら古い tx から捨てられる。
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct ArchivedTxKey(pub [u8; ARCHIVED_TX_KEY_LEN]);
+
+impl ArchivedTxKey {
+    pub fn new(block_number: u64, tx_id: TxId) -> Self {
+        let mut buf = [0u8; ARCHIVED_TX_KEY_LEN];
+        buf[0..8].copy_from_slice(&block_number.to_be_bytes());
+        buf[8..40].copy_from_slice(&tx_id.0);
+        Self(buf)
+    }
+
+    pub fn tx_id(self) -> TxId {
+        let mut tx = [0u8; 32];
+        tx.copy_from_slice(&self.0[8..40]);
+        TxId(tx)
+    }
+}
+
+#[derive(
+    Clone, Copy, Debug, Eq, PartialEq, FromBytes, IntoBytes, KnownLayout, Immutable, Unaligned,
+)]
+#[repr(C)]
+struct ArchiveStateWire {
+    schema_version: U32,
+    canister_flag: u8,
+    canister: [u8; CALLER_KEY_LEN],
+    acked_flag: u8,
+    acked_through: U64,
+    acked_at: U64,
+}
+
+#[derive(
+    Clone, Copy, Debug, Eq, PartialEq, FromBytes, IntoBytes, KnownLayout, Immutable, Unaligned,
+)]
+#[repr(C)]
+struct ArchiveRangeWire {
+    end_block: U64,
+    canister: [u8; CALLER_KEY_LEN],
+}
+
+impl ArchiveStateWire {
+    fn new(value: &ArchiveStateV1) -> Self {
+        Self {
+            schema_version: U32::new(value.schema_version),
+            canister_flag: if value.canister.is_some() {
+                FLAG_SOME
+            } else {
+                FLAG_NONE
+            },
+            canister: value
+                .canister
+                .map(|key| key.0)
+                .unwrap_or([0u8; CALLER_KEY_LEN]),
+            acked_flag: if value.acked_through.is_some() {
+                FLAG_SOME
+            } else {
+                FLAG_NONE
+            },
+            acked_through: U64::new(value.acked_through.unwrap_or(0)),
+            acked_at: U64::new(value.acked_at),
+        }
+    }
+}
+
+impl ArchiveStateV1 {
+    pub fn new() -> Self {
+        Self {
+            schema_version: 1,
+            canister: None,
+            acked_through: None,
+            acked_at: 0,
+        }
+    }
+}
+
+impl Default for ArchiveStateV1 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Storable for ArchiveStateV1 {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        let wire = ArchiveStateWire::new(self);
+        match encode_guarded(
+            b"archive_state",
+            Cow::Owned(wire.as_bytes().to_vec()),
+            ARCHIVE_STATE_SIZE_U32,
+        ) {
+            Ok(value) => value,
+            Err(_) => Cow::Owned(vec![0u8; ARCHIVE_STATE_SIZE_U32 as usize]),
+        }
+    }
+
+    fn into_bytes(self) -> Vec<u8> {
+        ArchiveStateWire::new(&self).as_bytes().to_vec()
+    }
+
+    fn from_bytes(bytes: Cow<'_, [u8]>) -> Self {
+        let wire = match ArchiveStateWire::read_from_bytes(bytes.as_ref()) {
+            Ok(value) => value,
+            Err(_) => {
+                mark_decode_failure(b"archive_state", false);
+                return ArchiveStateV1::new();
+            }
+        };
+        if wire.canister_flag > FLAG_SOME || wire.acked_flag > FLAG_SOME {
+            mark_decode_failure(b"archive_state", false);
+            return ArchiveStateV1::new();
+        }
+        Self {
+            schema_version: wire.schema_version.get(),
+            canister: (wire.canister_flag == FLAG_SOME).then_some(CallerKey(wire.canister)),
+            acked_through: (wire.acked_flag == FLAG_SOME).then_some(wire.acked_through.get()),
+            acked_at: wire.acked_at.get(),
+        }
+    }
+
+    const BOUND: Bound = Bound::Bounded {
+        max_size: ARCHIVE_STATE_SIZE_U32,
+        is_fixed_size: true,
+    };
+}
+
+impl Storable for ArchiveRangeV1 {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        let wire = ArchiveRangeWire {
+            end_block: U64::new(self.end_block),
+            canister: self.canister.0,
+        };
+        match encode_guarded(
+            b"archive_range",
+            Cow::Owned(wire.as_bytes().to_vec()),
+            ARCHIVE_RANGE_SIZE_U32,
+        ) {
+            Ok(value) => value,
+            Err(_) => Cow::Owned(vec![0u8; ARCHIVE_RANGE_SIZE_U32 as usize]),
+        }
+    }
+
+    fn into_bytes(self) -> Vec<u8> {
+        self.to_bytes().into_owned()
+    }
+
+    fn from_bytes(bytes: Cow<'_, [u8]>) -> Self {
+        match ArchiveRangeWire::read_from_bytes(bytes.as_ref()) {
+            Ok(wire) => Self {
+                end_block: wire.end_block.get(),
+                canister: CallerKey(wire.canister),
+            },
+            Err(_) => {
+                mark_decode_failure(b"archive_range", false);
+                Self {
+                    end_block: 0,
+                    canister: CallerKey([0u8; CALLER_KEY_LEN]),
+                }
+            }
+        }
+    }
+
+    const BOUND: Bound = Bound::Bounded {
+        max_size: ARCHIVE_RANGE_SIZE_U32,
+        is_fixed_size: true,
+    };
+}
+
+impl Storable for ArchivedTxKey {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        match encode_guarded(
+            b"archived_tx_key",
+            Cow::Borrowed(&self.0),
+            ARCHIVED_TX_KEY_LEN_U32,
+        ) {
+            Ok(value) => value,
+            Err(_) => Cow::Owned(vec![0u8; ARCHIVED_TX_KEY_LEN]),
+        }
+    }
+
+    fn into_bytes(self) -> Vec<u8> {
+        self.0.to_vec()
+    }
+
+    fn from_bytes(bytes: Cow<'_, [u8]>) -> Self {
+        let data = bytes.as_ref();
+        if !verified_core::stable_codec::fixed_len_matches(data.len(), ARCHIVED_TX_KEY_LEN) {
+            mark_decode_failure(b"archived_tx_key", false);
+            return ArchivedTxKey(hash_to_array(b"archived_tx_key", data));
+        }
+        let mut buf = [0u8; ARCHIVED_TX_KEY_LEN];
+        buf.copy_from_slice(data);
+        Self(buf)
+    }
+
+    const BOUND: Bound = Bound::Bounded {
+        max_size: ARCHIVED_TX_KEY_LEN_U32,
+        is_fixed_size: true,
+    };
+}
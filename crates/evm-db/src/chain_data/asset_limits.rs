@@ -0,0 +1,217 @@
+//! どこで: asset ごとの bridge 上限 / 何を: 停止フラグ、上限値、24 時間窓の wrap/unwrap 量 / なぜ: 許可 asset 一覧と全体 fee だけでは asset 単位の損失を抑えられないため
+
+use crate::chain_data::codec::{encode_guarded, mark_decode_failure};
+use ic_stable_structures::storable::Bound;
+use ic_stable_structures::Storable;
+use std::borrow::Cow;
+use verified_core::bridge_limits::{
+    bridge_window_buckets_to_clear, bridge_window_contains, BRIDGE_WINDOW_BUCKETS,
+};
+use zerocopy::byteorder::big_endian::{U128, U64};
+use zerocopy::{FromBytes, Immutable, IntoBytes, KnownLayout, Unaligned};
+
+pub const ASSET_LIMITS_SIZE_U32: u32 = 833;
+const WINDOW_LEN: usize = BRIDGE_WINDOW_BUCKETS as usize;
+const FLAG_PAUSED: u8 = 1 << 0;
+const FLAG_MAX_SINGLE_WRAP: u8 = 1 << 1;
+const FLAG_WRAP_DAILY_CAP: u8 = 1 << 2;
+const FLAG_UNWRAP_DAILY_CAP: u8 = 1 << 3;
+const FLAG_KNOWN: u8 =
+    FLAG_PAUSED | FLAG_MAX_SINGLE_WRAP | FLAG_WRAP_DAILY_CAP | FLAG_UNWRAP_DAILY_CAP;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BridgeDirection {
+    Wrap,
+    Unwrap,
+}
+
+/// asset_limits の値。key は asset の principal bytes。
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct AssetLimitsV1 {
+    pub paused: bool,
+    pub max_single_wrap: Option<u128>,
+    pub wrap_daily_cap: Option<u128>,
+    pub unwrap_daily_cap: Option<u128>,
+    /// 窓の最新 bucket の時刻（UNIX 時間の hour）
+    pub window_hour: u64,
+    /// hour % 24 番目に、その時間に受け付けた量を持つ。
+    pub wrap_buckets: [u128; WINDOW_LEN],
+    pub unwrap_buckets: [u128; WINDOW_LEN],
+    pub updated_at: u64,
+}
+
+impl AssetLimitsV1 {
+    /// 読めなかった値は停止扱いにし、上限の分からない asset を流さない。
+    pub fn decode_failure() -> Self {
+        Self {
+            paused: true,
+            ..Self::default()
+        }
+    }
+
+    pub fn cap(&self, direction: BridgeDirection) -> Option<u128> {
+        match direction {
+            BridgeDirection::Wrap => self.wrap_daily_cap,
+            BridgeDirection::Unwrap => self.unwrap_daily_cap,
+        }
+    }
+
+    /// 直近 24 時間の合計。呼ぶ前に advance_window で窓を現在へ送っておく。
+    pub fn volume(&self, direction: BridgeDirection) -> u128 {
+        self.buckets(direction)
+            .iter()
+            .fold(0u128, |total, amount| total.saturating_add(*amount))
+    }
+
+    /// 窓から外れた bucket を空にする。時計が戻っても窓は戻さない。
+    pub fn advance_window(&mut self, now_hour: u64) {
+        let cleared = bridge_window_buckets_to_clear(self.window_hour, now_hour);
+        for step in 1..=cleared {
+            let index = bucket_index(self.window_hour.wrapping_add(step));
+            self.wrap_buckets[index] = 0;
+            self.unwrap_buckets[index] = 0;
+        }
+        self.window_hour = self.window_hour.max(now_hour);
+    }
+
+    /// 受け付けた量を窓の最新 bucket に積み、積んだ hour を返す。
+    pub fn record(&mut self, direction: BridgeDirection, now_hour: u64, amount: u128) -> u64 {
+        self.advance_window(now_hour);
+        let hour = self.window_hour;
+        let bucket = &mut self.buckets_mut(direction)[bucket_index(hour)];
+        *bucket = bucket.saturating_add(amount);
+        hour
+    }
+
+    /// record で積んだ量を取り消す。積んだ bucket が窓から出ていれば何もしない。
+    pub fn release(
+        &mut self,
+        direction: BridgeDirection,
+        recorded_hour: u64,
+        now_hour: u64,
+        amount: u128,
+    ) {
+        self.advance_window(now_hour);
+        if !bridge_window_contains(recorded_hour, self.window_hour) {
+            return;
+        }
+        let bucket = &mut self.buckets_mut(direction)[bucket_index(recorded_hour)];
+        *bucket = bucket.saturating_sub(amount);
+    }
+
+    fn buckets(&self, direction: BridgeDirection) -> &[u128; WINDOW_LEN] {
+        match direction {
+            BridgeDirection::Wrap => &self.wrap_buckets,
+            BridgeDirection::Unwrap => &self.unwrap_buckets,
+        }
+    }
+
+    fn buckets_mut(&mut self, direction: BridgeDirection) -> &mut [u128; WINDOW_LEN] {
+        match direction {
+            BridgeDirection::Wrap => &mut self.wrap_buckets,
+            BridgeDirection::Unwrap => &mut self.unwrap_buckets,
+        }
+    }
+}
+
+fn bucket_index(hour: u64) -> usize {
+    (hour % BRIDGE_WINDOW_BUCKETS) as usize
+}
+
+#[derive(
+    Clone, Copy, Debug, Eq, PartialEq, FromBytes, IntoBytes, KnownLayout, Immutable, Unaligned,
+)]
+#[repr(C)]
+struct AssetLimitsWire {
+    flags: u8,
+    max_single_wrap: U128,
+    wrap_daily_cap: U128,
+    unwrap_daily_cap: U128,
+    window_hour: U64,
+    wrap_buckets: [U128; WINDOW_LEN],
+    unwrap_buckets: [U128; WINDOW_LEN],
+    updated_at: U64,
+}
+
+impl AssetLimitsWire {
+    fn new(value: &AssetLimitsV1) -> Self {
+        let mut flags = 0u8;
+        if value.paused {
+            flags |= FLAG_PAUSED;
+        }
+        if value.max_single_wrap.is_some() {
+            flags |= FLAG_MAX_SINGLE_WRAP;
+        }
+        if value.wrap_daily_cap.is_some() {
+            flags |= FLAG_WRAP_DAILY_CAP;
+        }
+        if value.unwrap_daily_cap.is_some() {
+            flags |= FLAG_UNWRAP_DAILY_CAP;
+        }
+        Self {
+            flags,
+            max_single_wrap: U128::new(value.max_single_wrap.unwrap_or_default()),
+            wrap_daily_cap: U128::new(value.wrap_daily_cap.unwrap_or_default()),
+            unwrap_daily_cap: U128::new(value.unwrap_daily_cap.unwrap_or_default()),
+            window_hour: U64::new(value.window_hour),
+            wrap_buckets: value.wrap_buckets.map(U128::new),
+            unwrap_buckets: value.unwrap_buckets.map(U128::new),
+            updated_at: U64::new(value.updated_at),
+        }
+    }
+}
+
+fn optional_cap(flags: u8, flag: u8, value: U128) -> Option<u128> {
+    (flags & flag != 0).then(|| value.get())
+}
+
+impl Storable for AssetLimitsV1 {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        let wire = AssetLimitsWire::new(self);
+        match encode_guarded(
+            b"asset_limits",
+            Cow::Owned(wire.as_bytes().to_vec()),
+            ASSET_LIMITS_SIZE_U32,
+        ) {
+            Ok(value) => value,
+            Err(_) => Cow::Owned(vec![0u8; ASSET_LIMITS_SIZE_U32 as usize]),
+        }
+    }
+
+    fn into_bytes(self) -> Vec<u8> {
+        AssetLimitsWire::new(&self).as_bytes().to_vec()
+    }
+
+    fn from_bytes(bytes: Cow<'_, [u8]>) -> Self {
+        let wire = match AssetLimitsWire::read_from_bytes(bytes.as_ref()) {
+            Ok(value) => value,
+            Err(_) => {
+                mark_decode_failure(b"asset_limits", false);
+                return Self::decode_failure();
+            }
+        };
+        if wire.flags & !FLAG_KNOWN != 0 {
+            mark_decode_failure(b"asset_limits", false);
+            return Self::decode_failure();
+        }
+        Self {
+            paused: wire.flags & FLAG_PAUSED != 0,
+            max_single_wrap: optional_cap(wire.flags, FLAG_MAX_SINGLE_WRAP, wire.max_single_wrap),
+            wrap_daily_cap: optional_cap(wire.flags, FLAG_WRAP_DAILY_CAP, wire.wrap_daily_cap),
+            unwrap_daily_cap: optional_cap(
+                wire.flags,
+                FLAG_UNWRAP_DAILY_CAP,
+                wire.unwrap_daily_cap,
+            ),
+            window_hour: wire.window_hour.get(),
+            wrap_buckets: wire.wrap_buckets.map(|amount| amount.get()),
+            unwrap_buckets: wire.unwrap_buckets.map(|amount| amount.get()),
+            updated_at: wire.updated_at.get(),
+        }
+    }
+
+    const BOUND: Bound = Bound::Bounded {
+        max_size: ASSET_LIMITS_SIZE_U32,
+        is_fixed_size: true,
+    };
+}
@@ -0,0 +1,253 @@
+//! どこで: BlobStoreのcompaction状態 / 何を: 走査cursorと移動中blobのjournal / なぜ: 複数tickに分かれた詰め直しを途中から安全に再開するため
+
+use crate::blob_ptr::BlobPtr;
+use crate::chain_data::codec::{encode_guarded, mark_decode_failure};
+use ic_stable_structures::storable::Bound;
+use ic_stable_structures::Storable;
+use std::borrow::Cow;
+use zerocopy::byteorder::big_endian::{U32, U64};
+use zerocopy::{FromBytes, Immutable, IntoBytes, KnownLayout, Unaligned};
+
+pub const BLOB_COMPACTION_STATE_SIZE_U32: u32 = 145;
+const FLAG_NONE: u8 = 0;
+const FLAG_SOME: u8 = 1;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BlobCompactionPhase {
+    Idle,
+    /// 参照表を順に走査し、低いoffsetへ移せるblobを移している
+    Relocating,
+    /// arena末尾に残ったFree slotを切り詰めている
+    Trimming,
+}
+
+impl BlobCompactionPhase {
+    pub fn to_u8(self) -> u8 {
+        match self {
+            BlobCompactionPhase::Idle => 0,
+            BlobCompactionPhase::Relocating => 1,
+            BlobCompactionPhase::Trimming => 2,
+        }
+    }
+
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(BlobCompactionPhase::Idle),
+            1 => Some(BlobCompactionPhase::Relocating),
+            2 => Some(BlobCompactionPhase::Trimming),
+            _ => None,
+        }
+    }
+}
+
+/// BlobPtr を値に持つ参照表。走査はこの順で進む。
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BlobRefMap {
+    Blocks,
+    Receipts,
+    TxIndex,
+    InternalTraces,
+}
+
+impl BlobRefMap {
+    pub fn to_u8(self) -> u8 {
+        match self {
+            BlobRefMap::Blocks => 0,
+            BlobRefMap::Receipts => 1,
+            BlobRefMap::TxIndex => 2,
+            BlobRefMap::InternalTraces => 3,
+        }
+    }
+
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(BlobRefMap::Blocks),
+            1 => Some(BlobRefMap::Receipts),
+            2 => Some(BlobRefMap::TxIndex),
+            3 => Some(BlobRefMap::InternalTraces),
+            _ => None,
+        }
+    }
+
+    pub fn next(self) -> Option<Self> {
+        match self {
+            BlobRefMap::Blocks => Some(BlobRefMap::Receipts),
+            BlobRefMap::Receipts => Some(BlobRefMap::TxIndex),
+            BlobRefMap::TxIndex => Some(BlobRefMap::InternalTraces),
+            BlobRefMap::InternalTraces => None,
+        }
+    }
+}
+
+/// 参照の張り替え前に書く移動記録。blocksのkeyは先頭8byteにBEで入れる。
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct BlobRelocationJournal {
+    pub map: BlobRefMap,
+    pub key: [u8; 32],
+    pub src: BlobPtr,
+    pub dst: BlobPtr,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct BlobCompactionStateV1 {
+    pub schema_version: u32,
+    pub phase: BlobCompactionPhase,
+    pub cursor_map: BlobRefMap,
+    /// cursor_map 内で最後に処理したkey（None は先頭から）
+    pub cursor_key: Option<[u8; 32]>,
+    pub journal: Option<BlobRelocationJournal>,
+    pub passes: u64,
+    pub relocated_blobs: u64,
+    pub relocated_bytes: u64,
+    pub trimmed_bytes: u64,
+}
+
+#[derive(
+    Clone, Copy, Debug, Eq, PartialEq, FromBytes, IntoBytes, KnownLayout, Immutable, Unaligned,
+)]
+#[repr(C)]
+struct BlobCompactionStateWire {
+    schema_version: U32,
+    phase: u8,
+    cursor_map: u8,
+    cursor_flag: u8,
+    cursor_key: [u8; 32],
+    journal_flag: u8,
+    journal_map: u8,
+    journal_key: [u8; 32],
+    journal_src: [u8; 20],
+    journal_dst: [u8; 20],
+    passes: U64,
+    relocated_blobs: U64,
+    relocated_bytes: U64,
+    trimmed_bytes: U64,
+}
+
+impl BlobCompactionStateWire {
+    fn new(value: &BlobCompactionStateV1) -> Self {
+        let (journal_flag, journal_map, journal_key, journal_src, journal_dst) = match value.journal
+        {
+            Some(journal) => (
+                FLAG_SOME,
+                journal.map.to_u8(),
+                journal.key,
+                ptr_bytes(&journal.src),
+                ptr_bytes(&journal.dst),
+            ),
+            None => (FLAG_NONE, 0, [0u8; 32], [0u8; 20], [0u8; 20]),
+        };
+        Self {
+            schema_version: U32::new(value.schema_version),
+            phase: value.phase.to_u8(),
+            cursor_map: value.cursor_map.to_u8(),
+            cursor_flag: if value.cursor_key.is_some() {
+                FLAG_SOME
+            } else {
+                FLAG_NONE
+            },
+            cursor_key: value.cursor_key.unwrap_or([0u8; 32]),
+            journal_flag,
+            journal_map,
+            journal_key,
+            journal_src,
+            journal_dst,
+            passes: U64::new(value.passes),
+            relocated_blobs: U64::new(value.relocated_blobs),
+            relocated_bytes: U64::new(value.relocated_bytes),
+            trimmed_bytes: U64::new(value.trimmed_bytes),
+        }
+    }
+}
+
+impl BlobCompactionStateV1 {
+    pub fn new() -> Self {
+        Self {
+            schema_version: 1,
+            phase: BlobCompactionPhase::Idle,
+            cursor_map: BlobRefMap::Blocks,
+            cursor_key: None,
+            journal: None,
+            passes: 0,
+            relocated_blobs: 0,
+            relocated_bytes: 0,
+            trimmed_bytes: 0,
+        }
+    }
+}
+
+impl Default for BlobCompactionStateV1 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Storable for BlobCompactionStateV1 {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        let wire = BlobCompactionStateWire::new(self);
+        match encode_guarded(
+            b"blob_compaction_state",
+            Cow::Owned(wire.as_bytes().to_vec()),
+            BLOB_COMPACTION_STATE_SIZE_U32,
+        ) {
+            Ok(value) => value,
+            Err(_) => Cow::Owned(vec![0u8; BLOB_COMPACTION_STATE_SIZE_U32 as usize]),
+        }
+    }
+
+    fn into_bytes(self) -> Vec<u8> {
+        BlobCompactionStateWire::new(&self).as_bytes().to_vec()
+    }
+
+    fn from_bytes(bytes: Cow<'_, [u8]>) -> Self {
+        let wire = match BlobCompactionStateWire::read_from_bytes(bytes.as_ref()) {
+            Ok(value) => value,
+            Err(_) => {
+                mark_decode_failure(b"blob_compaction_state", false);
+                return BlobCompactionStateV1::new();
+            }
+        };
+        let (Some(phase), Some(cursor_map)) = (
+            BlobCompactionPhase::from_u8(wire.phase),
+            BlobRefMap::from_u8(wire.cursor_map),
+        ) else {
+            mark_decode_failure(b"blob_compaction_state", false);
+            return BlobCompactionStateV1::new();
+        };
+        let journal = if wire.journal_flag == FLAG_SOME {
+            let Some(map) = BlobRefMap::from_u8(wire.journal_map) else {
+                mark_decode_failure(b"blob_compaction_state", false);
+                return BlobCompactionStateV1::new();
+            };
+            Some(BlobRelocationJournal {
+                map,
+                key: wire.journal_key,
+                src: BlobPtr::from_bytes(Cow::Borrowed(&wire.journal_src)),
+                dst: BlobPtr::from_bytes(Cow::Borrowed(&wire.journal_dst)),
+            })
+        } else {
+            None
+        };
+        Self {
+            schema_version: wire.schema_version.get(),
+            phase,
+            cursor_map,
+            cursor_key: (wire.cursor_flag == FLAG_SOME).then_some(wire.cursor_key),
+            journal,
+            passes: wire.passes.get(),
+            relocated_blobs: wire.relocated_blobs.get(),
+            relocated_bytes: wire.relocated_bytes.get(),
+            trimmed_bytes: wire.trimmed_bytes.get(),
+        }
+    }
+
+    const BOUND: Bound = Bound::Bounded {
+        max_size: BLOB_COMPACTION_STATE_SIZE_U32,
+        is_fixed_size: true,
+    };
+}
+
+pub(super) fn ptr_bytes(ptr: &BlobPtr) -> [u8; 20] {
+    let mut out = [0u8; 20];
+    out.copy_from_slice(ptr.to_bytes().as_ref());
+    out
+}
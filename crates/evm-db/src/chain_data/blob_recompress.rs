@@ -0,0 +1,198 @@
+//! どこで: BlobStoreの再圧縮状態 / 何を: 既存の未圧縮blobを走査するcursorと張り替え中のjournal / なぜ: 圧縮導入前に書かれた履歴を複数tickに分けて安全に詰め直すため
+
+use crate::blob_ptr::BlobPtr;
+use crate::chain_data::blob_compaction::ptr_bytes;
+use crate::chain_data::codec::{encode_guarded, mark_decode_failure};
+use crate::chain_data::{BlobRefMap, BlobRelocationJournal};
+use ic_stable_structures::storable::Bound;
+use ic_stable_structures::Storable;
+use std::borrow::Cow;
+use zerocopy::byteorder::big_endian::{U32, U64};
+use zerocopy::{FromBytes, Immutable, IntoBytes, KnownLayout, Unaligned};
+
+pub const BLOB_RECOMPRESS_STATE_SIZE_U32: u32 = 137;
+const FLAG_NONE: u8 = 0;
+const FLAG_SOME: u8 = 1;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BlobRecompressPhase {
+    /// upgrade直後。まだ一度も走査していない
+    Pending,
+    Running,
+    /// 全参照表を走査し終えた。以後の書き込みは最初から圧縮される
+    Done,
+}
+
+impl BlobRecompressPhase {
+    pub fn to_u8(self) -> u8 {
+        match self {
+            BlobRecompressPhase::Pending => 0,
+            BlobRecompressPhase::Running => 1,
+            BlobRecompressPhase::Done => 2,
+        }
+    }
+
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(BlobRecompressPhase::Pending),
+            1 => Some(BlobRecompressPhase::Running),
+            2 => Some(BlobRecompressPhase::Done),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct BlobRecompressStateV1 {
+    pub schema_version: u32,
+    pub phase: BlobRecompressPhase,
+    pub cursor_map: BlobRefMap,
+    /// cursor_map 内で最後に処理したkey（None は先頭から）
+    pub cursor_key: Option<[u8; 32]>,
+    /// src は未圧縮blob、dst は圧縮して書き直したblob
+    pub journal: Option<BlobRelocationJournal>,
+    pub scanned: u64,
+    pub recompressed_blobs: u64,
+    /// サイズクラスの差分の合計
+    pub saved_bytes: u64,
+}
+
+#[derive(
+    Clone, Copy, Debug, Eq, PartialEq, FromBytes, IntoBytes, KnownLayout, Immutable, Unaligned,
+)]
+#[repr(C)]
+struct BlobRecompressStateWire {
+    schema_version: U32,
+    phase: u8,
+    cursor_map: u8,
+    cursor_flag: u8,
+    cursor_key: [u8; 32],
+    journal_flag: u8,
+    journal_map: u8,
+    journal_key: [u8; 32],
+    journal_src: [u8; 20],
+    journal_dst: [u8; 20],
+    scanned: U64,
+    recompressed_blobs: U64,
+    saved_bytes: U64,
+}
+
+impl BlobRecompressStateWire {
+    fn new(value: &BlobRecompressStateV1) -> Self {
+        let (journal_flag, journal_map, journal_key, journal_src, journal_dst) = match value.journal
+        {
+            Some(journal) => (
+                FLAG_SOME,
+                journal.map.to_u8(),
+                journal.key,
+                ptr_bytes(&journal.src),
+                ptr_bytes(&journal.dst),
+            ),
+            None => (FLAG_NONE, 0, [0u8; 32], [0u8; 20], [0u8; 20]),
+        };
+        Self {
+            schema_version: U32::new(value.schema_version),
+            phase: value.phase.to_u8(),
+            cursor_map: value.cursor_map.to_u8(),
+            cursor_flag: if value.cursor_key.is_some() {
+                FLAG_SOME
+            } else {
+                FLAG_NONE
+            },
+            cursor_key: value.cursor_key.unwrap_or([0u8; 32]),
+            journal_flag,
+            journal_map,
+            journal_key,
+            journal_src,
+            journal_dst,
+            scanned: U64::new(value.scanned),
+            recompressed_blobs: U64::new(value.recompressed_blobs),
+            saved_bytes: U64::new(value.saved_bytes),
+        }
+    }
+}
+
+impl BlobRecompressStateV1 {
+    pub fn new() -> Self {
+        Self {
+            schema_version: 1,
+            phase: BlobRecompressPhase::Pending,
+            cursor_map: BlobRefMap::Blocks,
+            cursor_key: None,
+            journal: None,
+            scanned: 0,
+            recompressed_blobs: 0,
+            saved_bytes: 0,
+        }
+    }
+}
+
+impl Default for BlobRecompressStateV1 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Storable for BlobRecompressStateV1 {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        let wire = BlobRecompressStateWire::new(self);
+        match encode_guarded(
+            b"blob_recompress_state",
+            Cow::Owned(wire.as_bytes().to_vec()),
+            BLOB_RECOMPRESS_STATE_SIZE_U32,
+        ) {
+            Ok(value) => value,
+            Err(_) => Cow::Owned(vec![0u8; BLOB_RECOMPRESS_STATE_SIZE_U32 as usize]),
+        }
+    }
+
+    fn into_bytes(self) -> Vec<u8> {
+        BlobRecompressStateWire::new(&self).as_bytes().to_vec()
+    }
+
+    fn from_bytes(bytes: Cow<'_, [u8]>) -> Self {
+        let wire = match BlobRecompressStateWire::read_from_bytes(bytes.as_ref()) {
+            Ok(value) => value,
+            Err(_) => {
+                mark_decode_failure(b"blob_recompress_state", false);
+                return BlobRecompressStateV1::new();
+            }
+        };
+        let (Some(phase), Some(cursor_map)) = (
+            BlobRecompressPhase::from_u8(wire.phase),
+            BlobRefMap::from_u8(wire.cursor_map),
+        ) else {
+            mark_decode_failure(b"blob_recompress_state", false);
+            return BlobRecompressStateV1::new();
+        };
+        let journal = if wire.journal_flag == FLAG_SOME {
+            let Some(map) = BlobRefMap::from_u8(wire.journal_map) else {
+                mark_decode_failure(b"blob_recompress_state", false);
+                return BlobRecompressStateV1::new();
+            };
+            Some(BlobRelocationJournal {
+                map,
+                key: wire.journal_key,
+                src: BlobPtr::from_bytes(Cow::Borrowed(&wire.journal_src)),
+                dst: BlobPtr::from_bytes(Cow::Borrowed(&wire.journal_dst)),
+            })
+        } else {
+            None
+        };
+        Self {
+            schema_version: wire.schema_version.get(),
+            phase,
+            cursor_map,
+            cursor_key: (wire.cursor_flag == FLAG_SOME).then_some(wire.cursor_key),
+            journal,
+            scanned: wire.scanned.get(),
+            recompressed_blobs: wire.recompressed_blobs.get(),
+            saved_bytes: wire.saved_bytes.get(),
+        }
+    }
+
+    const BOUND: Bound = Bound::Bounded {
+        max_size: BLOB_RECOMPRESS_STATE_SIZE_U32,
+        is_fixed_size: true,
+    };
+}
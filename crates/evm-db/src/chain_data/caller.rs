@@ -22,6 +22,12 @@ impl CallerKey {
         out[1..1 + bytes.len()].copy_from_slice(bytes);
         Self(out)
     }
+
+    /// 先頭の長さbyteに従って principal の bytes を取り出す。
+    pub fn principal_bytes(&self) -> &[u8] {
+        let len = usize::from(self.0[0]).min(MAX_PRINCIPAL_LEN);
+        &self.0[1..1 + len]
+    }
 }
 
 impl Storable for CallerKey {
@@ -22,6 +22,10 @@ pub struct ChainStateV1 {
     pub auto_production_enabled: bool,
     pub is_producing: bool,
     pub mining_scheduled: bool,
+    /// produce_block を snapshot 事前実行 + 衝突検出で組み立てるか。
+    pub optimistic_exec_enabled: bool,
+    /// state snapshot の export 中は封印を止め、head と状態を固定する。
+    pub state_snapshot_export_hold: bool,
     pub next_queue_seq: u64,
     pub mining_interval_ms: u64,
     pub base_fee: u64,
@@ -104,6 +108,8 @@ impl ChainStateV1 {
             auto_production_enabled: true,
             is_producing: false,
             mining_scheduled: false,
+            optimistic_exec_enabled: false,
+            state_snapshot_export_hold: false,
             next_queue_seq: 0,
             mining_interval_ms: DEFAULT_MINING_INTERVAL_MS,
             base_fee: DEFAULT_BASE_FEE,
@@ -126,6 +132,12 @@ impl ChainStateV1 {
         if self.mining_scheduled {
             out |= 1 << 2;
         }
+        if self.optimistic_exec_enabled {
+            out |= 1 << 3;
+        }
+        if self.state_snapshot_export_hold {
+            out |= 1 << 4;
+        }
         out
     }
 
@@ -133,6 +145,8 @@ impl ChainStateV1 {
         self.auto_production_enabled = (flags & (1 << 0)) != 0;
         self.is_producing = (flags & (1 << 1)) != 0;
         self.mining_scheduled = (flags & (1 << 2)) != 0;
+        self.optimistic_exec_enabled = (flags & (1 << 3)) != 0;
+        self.state_snapshot_export_hold = (flags & (1 << 4)) != 0;
     }
 
     fn decode_failure_default() -> Self {
@@ -180,6 +194,8 @@ impl Storable for ChainStateV1 {
                 auto_production_enabled: false,
                 is_producing: false,
                 mining_scheduled: false,
+                optimistic_exec_enabled: false,
+                state_snapshot_export_hold: false,
                 next_queue_seq: wire.next_queue_seq.get(),
                 mining_interval_ms: wire.mining_interval_ms.get(),
                 base_fee: wire.base_fee.get(),
@@ -211,6 +227,8 @@ impl Storable for ChainStateV1 {
             auto_production_enabled: false,
             is_producing: false,
             mining_scheduled: false,
+            optimistic_exec_enabled: false,
+            state_snapshot_export_hold: false,
             next_queue_seq: wire.next_queue_seq.get(),
             mining_interval_ms: wire.mining_interval_ms.get(),
             base_fee: wire.base_fee.get(),
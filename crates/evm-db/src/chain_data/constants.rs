@@ -17,6 +17,8 @@ pub const MAX_PENDING_GLOBAL: usize = 8_192;
 pub const MAX_PENDING_PER_SENDER: usize = 64;
 pub const MAX_PENDING_PER_PRINCIPAL: usize = 32;
 pub const MAX_NONCE_WINDOW: u64 = 64;
+// prune時に残す archive 済み tx の block 番号の件数上限。古いものは archive canister の索引で引く
+pub const MAX_ARCHIVED_TX_INDEX: u64 = 100_000;
 
 pub const RECEIPT_CONTRACT_ADDR_LEN: usize = 20;
 pub const RECEIPT_CONTRACT_ADDR_LEN_U32: u32 = 20;
@@ -0,0 +1,111 @@
+//! どこで: 入金用subaccountの状態 / 何を: asset と EVM address ごとの sweep 連番と未確定 sweep / なぜ: approve 無しの入金を二重に mint せず、結果の分からない sweep を同じ request で再試行するため
+
+use crate::chain_data::codec::{encode_guarded, mark_decode_failure};
+use ic_stable_structures::storable::Bound;
+use ic_stable_structures::Storable;
+use std::borrow::Cow;
+use zerocopy::byteorder::big_endian::{U128, U64};
+use zerocopy::{FromBytes, Immutable, IntoBytes, KnownLayout, Unaligned};
+
+pub const DEPOSIT_ACCOUNT_SIZE_U32: u32 = 73;
+const FLAG_NONE: u8 = 0;
+const FLAG_SOME: u8 = 1;
+
+/// 台帳の結果が確定していない sweep。再試行でも同じ memo と created_at_time を送り、台帳の重複排除に任せる。
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct DepositSweepV1 {
+    /// sweep が成功したときに作る wrap request の id
+    pub request_id: [u8; 32],
+    pub amount_e8s: u128,
+    pub created_at_time: u64,
+}
+
+/// deposit_accounts の値。key は asset と EVM address から導く入金口座 key。
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct DepositAccountV1 {
+    /// 次の sweep に使う連番。request id の導出に含める。
+    pub next_seq: u64,
+    pub in_flight: Option<DepositSweepV1>,
+    pub updated_at: u64,
+}
+
+#[derive(
+    Clone, Copy, Debug, Eq, PartialEq, FromBytes, IntoBytes, KnownLayout, Immutable, Unaligned,
+)]
+#[repr(C)]
+struct DepositAccountWire {
+    next_seq: U64,
+    in_flight_flag: u8,
+    request_id: [u8; 32],
+    amount_e8s: U128,
+    created_at_time: U64,
+    updated_at: U64,
+}
+
+impl DepositAccountWire {
+    fn new(value: &DepositAccountV1) -> Self {
+        let sweep = value.in_flight.unwrap_or_default();
+        Self {
+            next_seq: U64::new(value.next_seq),
+            in_flight_flag: if value.in_flight.is_some() {
+                FLAG_SOME
+            } else {
+                FLAG_NONE
+            },
+            request_id: sweep.request_id,
+            amount_e8s: U128::new(sweep.amount_e8s),
+            created_at_time: U64::new(sweep.created_at_time),
+            updated_at: U64::new(value.updated_at),
+        }
+    }
+}
+
+impl Storable for DepositAccountV1 {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        let wire = DepositAccountWire::new(self);
+        match encode_guarded(
+            b"deposit_account",
+            Cow::Owned(wire.as_bytes().to_vec()),
+            DEPOSIT_ACCOUNT_SIZE_U32,
+        ) {
+            Ok(value) => value,
+            Err(_) => Cow::Owned(vec![0u8; DEPOSIT_ACCOUNT_SIZE_U32 as usize]),
+        }
+    }
+
+    fn into_bytes(self) -> Vec<u8> {
+        DepositAccountWire::new(&self).as_bytes().to_vec()
+    }
+
+    fn from_bytes(bytes: Cow<'_, [u8]>) -> Self {
+        let wire = match DepositAccountWire::read_from_bytes(bytes.as_ref()) {
+            Ok(value) => value,
+            Err(_) => {
+                mark_decode_failure(b"deposit_account", false);
+                return Self::default();
+            }
+        };
+        let in_flight = match wire.in_flight_flag {
+            FLAG_NONE => None,
+            FLAG_SOME => Some(DepositSweepV1 {
+                request_id: wire.request_id,
+                amount_e8s: wire.amount_e8s.get(),
+                created_at_time: wire.created_at_time.get(),
+            }),
+            _ => {
+                mark_decode_failure(b"deposit_account", false);
+                return Self::default();
+            }
+        };
+        Self {
+            next_seq: wire.next_seq.get(),
+            in_flight,
+            updated_at: wire.updated_at.get(),
+        }
+    }
+
+    const BOUND: Bound = Bound::Bounded {
+        max_size: DEPOSIT_ACCOUNT_SIZE_U32,
+        is_fixed_size: true,
+    };
+}
@@ -0,0 +1,243 @@
+//! どこで: drop済みTxの照会記録 / 何を: drop時点のsender・nonce・fee・理由コード / なぜ: receipt照会で消えたTxの理由をwalletへ返すため
+
+use crate::chain_data::codec::{encode_guarded, mark_decode_failure};
+use crate::chain_data::TxId;
+use ic_stable_structures::storable::Bound;
+use ic_stable_structures::Storable;
+use std::borrow::Cow;
+use zerocopy::byteorder::big_endian::{U16, U32, U64};
+use zerocopy::{FromBytes, FromZeros, Immutable, IntoBytes, KnownLayout, Unaligned};
+
+pub const DROP_RECORD_SIZE_U32: u32 = 160;
+pub const DROP_RECORD_STATE_SIZE_U32: u32 = 44;
+/// drop記録の既定保持期間（秒）。block pruneとは独立に消える。
+pub const DEFAULT_DROP_RECORD_RETAIN_SECS: u64 = 7 * 24 * 60 * 60;
+pub const DEFAULT_DROP_RECORD_MAX_RECORDS: u64 = 100_000;
+
+const FLAG_ETH_HASH: u8 = 0b0001;
+const FLAG_SENDER: u8 = 0b0010;
+const FLAG_NONCE: u8 = 0b0100;
+const FLAG_BLOCK: u8 = 0b1000;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct DropRecordV1 {
+    pub tx_id: TxId,
+    /// TxKind::to_u8
+    pub kind: u8,
+    pub eth_tx_hash: Option<[u8; 32]>,
+    pub sender: Option<[u8; 20]>,
+    pub nonce: Option<u64>,
+    pub drop_code: u16,
+    /// produce_block 中のdropなら組成中のブロック番号
+    pub block_attempted: Option<u64>,
+    pub dropped_at: u64,
+    pub max_fee_per_gas: u128,
+    pub max_priority_fee_per_gas: u128,
+    /// drop時点の base fee
+    pub base_fee: u64,
+    /// DropRecordSeq 上の位置（保持期間による削除で使う）
+    pub seq: u64,
+}
+
+#[derive(
+    Clone, Copy, Debug, Eq, PartialEq, FromBytes, IntoBytes, KnownLayout, Immutable, Unaligned,
+)]
+#[repr(C)]
+struct DropRecordWire {
+    tx_id: [u8; 32],
+    flags: u8,
+    kind: u8,
+    eth_tx_hash: [u8; 32],
+    sender: [u8; 20],
+    nonce: U64,
+    drop_code: U16,
+    block_attempted: U64,
+    dropped_at: U64,
+    max_fee_per_gas: [u8; 16],
+    max_priority_fee_per_gas: [u8; 16],
+    base_fee: U64,
+    seq: U64,
+}
+
+impl DropRecordWire {
+    fn new(record: &DropRecordV1) -> Self {
+        let mut flags = 0u8;
+        if record.eth_tx_hash.is_some() {
+            flags |= FLAG_ETH_HASH;
+        }
+        if record.sender.is_some() {
+            flags |= FLAG_SENDER;
+        }
+        if record.nonce.is_some() {
+            flags |= FLAG_NONCE;
+        }
+        if record.block_attempted.is_some() {
+            flags |= FLAG_BLOCK;
+        }
+        Self {
+            tx_id: record.tx_id.0,
+            flags,
+            kind: record.kind,
+            eth_tx_hash: record.eth_tx_hash.unwrap_or([0u8; 32]),
+            sender: record.sender.unwrap_or([0u8; 20]),
+            nonce: U64::new(record.nonce.unwrap_or(0)),
+            drop_code: U16::new(record.drop_code),
+            block_attempted: U64::new(record.block_attempted.unwrap_or(0)),
+            dropped_at: U64::new(record.dropped_at),
+            max_fee_per_gas: record.max_fee_per_gas.to_be_bytes(),
+            max_priority_fee_per_gas: record.max_priority_fee_per_gas.to_be_bytes(),
+            base_fee: U64::new(record.base_fee),
+            seq: U64::new(record.seq),
+        }
+    }
+}
+
+impl Storable for DropRecordV1 {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        let wire = DropRecordWire::new(self);
+        match encode_guarded(
+            b"drop_record",
+            Cow::Owned(wire.as_bytes().to_vec()),
+            DROP_RECORD_SIZE_U32,
+        ) {
+            Ok(value) => value,
+            Err(_) => Cow::Owned(vec![0u8; DROP_RECORD_SIZE_U32 as usize]),
+        }
+    }
+
+    fn into_bytes(self) -> Vec<u8> {
+        DropRecordWire::new(&self).as_bytes().to_vec()
+    }
+
+    fn from_bytes(bytes: Cow<'_, [u8]>) -> Self {
+        let wire = match DropRecordWire::read_from_bytes(bytes.as_ref()) {
+            Ok(value) => value,
+            Err(_) => {
+                mark_decode_failure(b"drop_record", false);
+                DropRecordWire::new_zeroed()
+            }
+        };
+        let flag = |bit: u8| wire.flags & bit != 0;
+        Self {
+            tx_id: TxId(wire.tx_id),
+            kind: wire.kind,
+            eth_tx_hash: flag(FLAG_ETH_HASH).then_some(wire.eth_tx_hash),
+            sender: flag(FLAG_SENDER).then_some(wire.sender),
+            nonce: flag(FLAG_NONCE).then_some(wire.nonce.get()),
+            drop_code: wire.drop_code.get(),
+            block_attempted: flag(FLAG_BLOCK).then_some(wire.block_attempted.get()),
+            dropped_at: wire.dropped_at.get(),
+            max_fee_per_gas: u128::from_be_bytes(wire.max_fee_per_gas),
+            max_priority_fee_per_gas: u128::from_be_bytes(wire.max_priority_fee_per_gas),
+            base_fee: wire.base_fee.get(),
+            seq: wire.seq.get(),
+        }
+    }
+
+    const BOUND: Bound = Bound::Bounded {
+        max_size: DROP_RECORD_SIZE_U32,
+        is_fixed_size: true,
+    };
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct DropRecordStateV1 {
+    pub schema_version: u32,
+    /// 次に割り当てる seq
+    pub next_seq: u64,
+    /// 未削除の最古 seq
+    pub oldest_seq: u64,
+    pub retain_secs: u64,
+    pub max_records: u64,
+    pub pruned_total: u64,
+}
+
+#[derive(
+    Clone, Copy, Debug, Eq, PartialEq, FromBytes, IntoBytes, KnownLayout, Immutable, Unaligned,
+)]
+#[repr(C)]
+struct DropRecordStateWire {
+    schema_version: U32,
+    next_seq: U64,
+    oldest_seq: U64,
+    retain_secs: U64,
+    max_records: U64,
+    pruned_total: U64,
+}
+
+impl DropRecordStateWire {
+    fn new(state: &DropRecordStateV1) -> Self {
+        Self {
+            schema_version: U32::new(state.schema_version),
+            next_seq: U64::new(state.next_seq),
+            oldest_seq: U64::new(state.oldest_seq),
+            retain_secs: U64::new(state.retain_secs),
+            max_records: U64::new(state.max_records),
+            pruned_total: U64::new(state.pruned_total),
+        }
+    }
+}
+
+impl DropRecordStateV1 {
+    pub fn new() -> Self {
+        Self {
+            schema_version: 1,
+            next_seq: 0,
+            oldest_seq: 0,
+            retain_secs: DEFAULT_DROP_RECORD_RETAIN_SECS,
+            max_records: DEFAULT_DROP_RECORD_MAX_RECORDS,
+            pruned_total: 0,
+        }
+    }
+
+    pub fn live_records(&self) -> u64 {
+        self.next_seq.saturating_sub(self.oldest_seq)
+    }
+}
+
+impl Default for DropRecordStateV1 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Storable for DropRecordStateV1 {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        let wire = DropRecordStateWire::new(self);
+        match encode_guarded(
+            b"drop_record_state",
+            Cow::Owned(wire.as_bytes().to_vec()),
+            DROP_RECORD_STATE_SIZE_U32,
+        ) {
+            Ok(value) => value,
+            Err(_) => Cow::Owned(vec![0u8; DROP_RECORD_STATE_SIZE_U32 as usize]),
+        }
+    }
+
+    fn into_bytes(self) -> Vec<u8> {
+        DropRecordStateWire::new(&self).as_bytes().to_vec()
+    }
+
+    fn from_bytes(bytes: Cow<'_, [u8]>) -> Self {
+        let wire = match DropRecordStateWire::read_from_bytes(bytes.as_ref()) {
+            Ok(value) => value,
+            Err(_) => {
+                mark_decode_failure(b"drop_record_state", false);
+                return DropRecordStateV1::new();
+            }
+        };
+        Self {
+            schema_version: wire.schema_version.get(),
+            next_seq: wire.next_seq.get(),
+            oldest_seq: wire.oldest_seq.get(),
+            retain_secs: wire.retain_secs.get(),
+            max_records: wire.max_records.get(),
+            pruned_total: wire.pruned_total.get(),
+        }
+    }
+
+    const BOUND: Bound = Bound::Bounded {
+        max_size: DROP_RECORD_STATE_SIZE_U32,
+        is_fixed_size: true,
+    };
+}
@@ -1,10 +1,16 @@
 //! どこで: Phase1型の集約 / 何を: Tx/Block/Receiptの公開 / なぜ: 依存の簡略化
 
+pub mod archive;
+pub mod asset_limits;
+pub mod blob_compaction;
+pub mod blob_recompress;
 pub mod block;
 pub mod caller;
 pub mod chain_state;
 pub(crate) mod codec;
 pub mod constants;
+pub mod deposit_account;
+pub mod drop_record;
 pub mod dropped_ring;
 pub mod icp_update_request;
 pub mod internal_trace;
@@ -17,16 +23,32 @@ pub mod ordering;
 pub mod prune_config;
 pub mod prune_state;
 pub mod queue;
+pub mod ready_index;
 pub mod receipt;
 pub mod runtime_config;
 pub mod runtime_defaults;
+pub mod scrub;
+pub mod staged_block;
 pub mod state_root_meta;
 pub mod state_root_ops;
+pub mod state_snapshot;
 pub mod tx;
 pub mod tx_loc;
 pub mod unwrap_request;
 pub mod wrap_request;
 
+pub use archive::{
+    ArchiveRangeV1, ArchiveStateV1, ArchivedTxKey, ARCHIVED_TX_KEY_LEN, ARCHIVED_TX_KEY_LEN_U32,
+    ARCHIVE_RANGE_SIZE_U32, ARCHIVE_STATE_SIZE_U32,
+};
+pub use asset_limits::{AssetLimitsV1, BridgeDirection, ASSET_LIMITS_SIZE_U32};
+pub use blob_compaction::{
+    BlobCompactionPhase, BlobCompactionStateV1, BlobRefMap, BlobRelocationJournal,
+    BLOB_COMPACTION_STATE_SIZE_U32,
+};
+pub use blob_recompress::{
+    BlobRecompressPhase, BlobRecompressStateV1, BLOB_RECOMPRESS_STATE_SIZE_U32,
+};
 pub use block::{BlockData, Head};
 pub use caller::CallerKey;
 pub use chain_state::ChainStateV1;
@@ -35,6 +57,11 @@ pub use constants::{
     CALLER_KEY_LEN, CHAIN_STATE_SIZE_U32, HASH_LEN, MAX_PRINCIPAL_LEN, MAX_TXS_PER_BLOCK,
     MAX_TX_SIZE, RECEIPT_CONTRACT_ADDR_LEN, TX_ID_LEN,
 };
+pub use deposit_account::{DepositAccountV1, DepositSweepV1, DEPOSIT_ACCOUNT_SIZE_U32};
+pub use drop_record::{
+    DropRecordStateV1, DropRecordV1, DEFAULT_DROP_RECORD_MAX_RECORDS,
+    DEFAULT_DROP_RECORD_RETAIN_SECS, DROP_RECORD_SIZE_U32, DROP_RECORD_STATE_SIZE_U32,
+};
 pub use dropped_ring::{DroppedRingStateV1, DROPPED_RING_STATE_SIZE_U32};
 pub use icp_update_request::{
     IcpUpdateDispatchRequest, IcpUpdateRequestStatus, ICP_UPDATE_DECODE_FAILURE_CODE,
@@ -47,10 +74,14 @@ pub use metrics::{MetricsStateV1, MetricsWindowSummary, METRICS_BUCKETS};
 pub use native_credit::{NativeCreditRecord, NATIVE_CREDIT_RECORD_SIZE_U32};
 pub use ops::{OpsConfigV1, OpsMode, OpsStateV1};
 pub use ops_metrics::{OpsMetricsV1, OPS_METRICS_SIZE_U32};
-pub use ordering::{PendingFeeKey, ReadyKey, ReadySeqKey, SenderKey, SenderNonceKey};
+pub use ordering::{
+    PendingFeeKey, ReadyFeeBoundaryKey, ReadyKey, ReadySeqKey, ReadyTipKey, SenderKey,
+    SenderNonceKey,
+};
 pub use prune_config::{PruneConfigV1, PrunePolicy};
 pub use prune_state::{PruneJournal, PruneStateV1};
 pub use queue::QueueMeta;
+pub use ready_index::{ReadyIndexStateV1, READY_INDEX_STATE_SIZE_U32};
 pub use receipt::ReceiptLike;
 pub use runtime_config::{RuntimeConfigV1, RUNTIME_CONFIG_SIZE_U32};
 pub use runtime_defaults::{
@@ -61,18 +92,29 @@ pub use runtime_defaults::{
     DEFAULT_PRUNE_TIMER_INTERVAL_MS, DEFAULT_QUERY_INSTRUCTION_SOFT_LIMIT,
     MIN_PRUNE_MAX_OPS_PER_TICK, MIN_PRUNE_TIMER_INTERVAL_MS,
 };
+pub use scrub::{
+    ScrubFindingKind, ScrubFindingV1, ScrubPhase, ScrubStateV1, ScrubTarget, SCRUB_FINDING_LOG_CAP,
+    SCRUB_FINDING_SIZE_U32, SCRUB_STATE_SIZE_U32,
+};
+pub use staged_block::{StagedBlockMetaV1, MAX_STAGED_BLOCK_ROUNDS, STAGED_BLOCK_META_SIZE_U32};
 pub use state_root_meta::{StateRootMetaV1, STATE_ROOT_META_SIZE_U32};
 pub use state_root_ops::{
     GcStateV1, HashKey, MigrationPhase, MigrationStateV1, MismatchRecordV1, NodeRecord,
     StateRootMetricsV1, STATE_ROOT_GC_STATE_SIZE_U32, STATE_ROOT_METRICS_SIZE_U32,
     STATE_ROOT_MIGRATION_SIZE_U32, STATE_ROOT_MISMATCH_SIZE_U32, STATE_ROOT_NODE_RECORD_MAX_U32,
 };
+pub use state_snapshot::{
+    StateSnapshotImportPhase, StateSnapshotImportV1, STATE_SNAPSHOT_IMPORT_SIZE_U32,
+};
 pub use tx::{
     StoredTx, StoredTxBytes, StoredTxBytesError, StoredTxError, TxId, TxIndexEntry, TxKind,
 };
 pub use tx_loc::{TxLoc, TxLocKind};
-pub use unwrap_request::{UnwrapDispatchRequest, UnwrapRequestStatus, UNWRAP_DECODE_FAILURE_CODE};
+pub use unwrap_request::{
+    UnwrapAssetKind, UnwrapDispatchRequest, UnwrapRequestStatus, UNWRAP_DECODE_FAILURE_CODE,
+};
 pub use wrap_request::{
-    FeePolicyStored, MintSubmitStatus, RequestStatus, WrapEvmConfigStored, WrapPendingSubmission,
-    WrapRequestResult, WrapRequestStage, WrapStoredRequest, WRAP_DECODE_FAILURE_CODE,
+    AssetFeeScheduleStored, FeePolicyStored, FeeTierStored, MintSubmitStatus, RequestStatus,
+    WrapAssetKind, WrapEvmConfigStored, WrapPendingSubmission, WrapRequestResult, WrapRequestStage,
+    WrapStoredRequest, MAX_FEE_TIERS, WRAP_DECODE_FAILURE_CODE,
 };
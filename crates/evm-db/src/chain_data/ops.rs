@@ -113,6 +113,9 @@ pub struct OpsStateV1 {
     pub last_check_ts: u64,
     pub mode: OpsMode,
     pub safe_stop_latched: bool,
+    /// 準備金の不足で立つ。cycle の回復では下ろさず、controller が確認して解除する。
+    pub wrap_paused: bool,
+    pub last_reserves_check_ts: u64,
 }
 
 impl OpsStateV1 {
@@ -122,6 +125,8 @@ impl OpsStateV1 {
             last_check_ts: 0,
             mode: OpsMode::Normal,
             safe_stop_latched: false,
+            wrap_paused: false,
+            last_reserves_check_ts: 0,
         }
     }
 
@@ -131,6 +136,8 @@ impl OpsStateV1 {
             last_check_ts: 0,
             mode: OpsMode::Critical,
             safe_stop_latched: true,
+            wrap_paused: true,
+            last_reserves_check_ts: 0,
         }
     }
 }
@@ -148,6 +155,8 @@ impl Storable for OpsStateV1 {
         out[16..24].copy_from_slice(&self.last_check_ts.to_be_bytes());
         out[24] = self.mode.as_u8();
         out[25] = u8::from(self.safe_stop_latched);
+        out[26] = u8::from(self.wrap_paused);
+        out[27..35].copy_from_slice(&self.last_reserves_check_ts.to_be_bytes());
         match encode_guarded(b"ops_state", Cow::Owned(out.to_vec()), OPS_STATE_SIZE_U32) {
             Ok(value) => value,
             Err(_) => panic!("ops_state: fixed-size encode failed"),
@@ -170,11 +179,16 @@ impl Storable for OpsStateV1 {
         last_check_ts.copy_from_slice(&data[16..24]);
         let mode = OpsMode::from_u8(data[24]);
         let safe_stop_latched = data[25] != 0;
+        let wrap_paused = data[26] != 0;
+        let mut last_reserves_check_ts = [0u8; 8];
+        last_reserves_check_ts.copy_from_slice(&data[27..35]);
         Self {
             last_cycle_balance: u128::from_be_bytes(cycle_balance),
             last_check_ts: u64::from_be_bytes(last_check_ts),
             mode,
             safe_stop_latched,
+            wrap_paused,
+            last_reserves_check_ts: u64::from_be_bytes(last_reserves_check_ts),
         }
     }
 
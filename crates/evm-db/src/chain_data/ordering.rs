@@ -12,6 +12,13 @@ pub const READY_SEQ_KEY_LEN: usize = 40;
 pub const READY_SEQ_KEY_LEN_U32: u32 = 40;
 pub const PENDING_FEE_KEY_LEN: usize = 40;
 pub const PENDING_FEE_KEY_LEN_U32: u32 = 40;
+pub const READY_TIP_KEY_LEN: usize = 57;
+pub const READY_TIP_KEY_LEN_U32: u32 = 57;
+pub const READY_FEE_BOUNDARY_KEY_LEN: usize = 48;
+pub const READY_FEE_BOUNDARY_KEY_LEN_U32: u32 = 48;
+pub const READY_TIP_BUCKET_TIP: u8 = 0;
+pub const READY_TIP_BUCKET_CAP: u8 = 1;
+pub const READY_TIP_BUCKET_ZERO: u8 = 2;
 pub const SENDER_KEY_LEN: usize = 20;
 pub const SENDER_KEY_LEN_U32: u32 = 20;
 pub const SENDER_NONCE_KEY_LEN: usize = 28;
@@ -42,6 +49,24 @@ impl ReadyKey {
         raw.copy_from_slice(&self.0[32..40]);
         u64::from_be_bytes(raw)
     }
+
+    pub fn max_fee_per_gas(self) -> u128 {
+        let mut raw = [0u8; 16];
+        raw.copy_from_slice(&self.0[0..16]);
+        u128::MAX - u128::from_be_bytes(raw)
+    }
+
+    pub fn max_priority_fee_per_gas(self) -> u128 {
+        let mut raw = [0u8; 16];
+        raw.copy_from_slice(&self.0[16..32]);
+        u128::MAX - u128::from_be_bytes(raw)
+    }
+
+    pub fn tx_hash(self) -> [u8; 32] {
+        let mut tx = [0u8; 32];
+        tx.copy_from_slice(&self.0[40..72]);
+        tx
+    }
 }
 
 impl Storable for ReadyKey {
@@ -118,17 +143,153 @@ impl Storable for ReadySeqKey {
     };
 }
 
+/// bucket（tip/cap/zero）→ bucket内rank降順 → seq昇順 → tx_hash の順で並ぶ。
+/// rankはtip bucketならpriority、cap bucketならmax_fee。
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct ReadyTipKey(pub [u8; READY_TIP_KEY_LEN]);
+
+impl ReadyTipKey {
+    pub fn new(bucket: u8, rank: u128, seq: u64, tx_hash: [u8; 32]) -> Self {
+        let rank_inv = u128::MAX.saturating_sub(rank);
+        let mut buf = [0u8; READY_TIP_KEY_LEN];
+        buf[0] = bucket;
+        buf[1..17].copy_from_slice(&rank_inv.to_be_bytes());
+        buf[17..25].copy_from_slice(&seq.to_be_bytes());
+        buf[25..57].copy_from_slice(&tx_hash);
+        Self(buf)
+    }
+
+    pub fn bucket_start(bucket: u8) -> Self {
+        let mut buf = [0u8; READY_TIP_KEY_LEN];
+        buf[0] = bucket;
+        Self(buf)
+    }
+
+    pub fn bucket(self) -> u8 {
+        self.0[0]
+    }
+
+    pub fn rank(self) -> u128 {
+        let mut raw = [0u8; 16];
+        raw.copy_from_slice(&self.0[1..17]);
+        u128::MAX - u128::from_be_bytes(raw)
+    }
+
+    pub fn seq(self) -> u64 {
+        let mut raw = [0u8; 8];
+        raw.copy_from_slice(&self.0[17..25]);
+        u64::from_be_bytes(raw)
+    }
+}
+
+impl Storable for ReadyTipKey {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        match encode_guarded(
+            b"ready_tip_key",
+            Cow::Borrowed(&self.0),
+            READY_TIP_KEY_LEN_U32,
+        ) {
+            Ok(value) => value,
+            Err(_) => Cow::Owned(vec![0u8; READY_TIP_KEY_LEN]),
+        }
+    }
+
+    fn into_bytes(self) -> Vec<u8> {
+        self.0.to_vec()
+    }
+
+    fn from_bytes(bytes: Cow<'_, [u8]>) -> Self {
+        let data = bytes.as_ref();
+        if !verified_core::stable_codec::fixed_len_matches(data.len(), READY_TIP_KEY_LEN) {
+            mark_decode_failure(b"ready_tip_key", false);
+            return ReadyTipKey(hash_to_array(b"ready_tip_key", data));
+        }
+        let mut buf = [0u8; READY_TIP_KEY_LEN];
+        buf.copy_from_slice(data);
+        Self(buf)
+    }
+
+    const BOUND: Bound = Bound::Bounded {
+        max_size: READY_TIP_KEY_LEN_U32,
+        is_fixed_size: true,
+    };
+}
+
+/// base fee がこの境界値をまたぐと、対応Txのbucketが変わり得る。
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct ReadyFeeBoundaryKey(pub [u8; READY_FEE_BOUNDARY_KEY_LEN]);
+
+impl ReadyFeeBoundaryKey {
+    pub fn new(boundary: u128, tx_hash: [u8; 32]) -> Self {
+        let mut buf = [0u8; READY_FEE_BOUNDARY_KEY_LEN];
+        buf[0..16].copy_from_slice(&boundary.to_be_bytes());
+        buf[16..48].copy_from_slice(&tx_hash);
+        Self(buf)
+    }
+
+    pub fn boundary(self) -> u128 {
+        let mut raw = [0u8; 16];
+        raw.copy_from_slice(&self.0[0..16]);
+        u128::from_be_bytes(raw)
+    }
+
+    pub fn tx_hash(self) -> [u8; 32] {
+        let mut tx = [0u8; 32];
+        tx.copy_from_slice(&self.0[16..48]);
+        tx
+    }
+}
+
+impl Storable for ReadyFeeBoundaryKey {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        match encode_guarded(
+            b"ready_fee_boundary_key",
+            Cow::Borrowed(&self.0),
+            READY_FEE_BOUNDARY_KEY_LEN_U32,
+        ) {
+            Ok(value) => value,
+            Err(_) => Cow::Owned(vec![0u8; READY_FEE_BOUNDARY_KEY_LEN]),
+        }
+    }
+
+    fn into_bytes(self) -> Vec<u8> {
+        self.0.to_vec()
+    }
+
+    fn from_bytes(bytes: Cow<'_, [u8]>) -> Self {
+        let data = bytes.as_ref();
+        if !verified_core::stable_codec::fixed_len_matches(data.len(), READY_FEE_BOUNDARY_KEY_LEN) {
+            mark_decode_failure(b"ready_fee_boundary_key", false);
+            return ReadyFeeBoundaryKey(hash_to_array(b"ready_fee_boundary_key", data));
+        }
+        let mut buf = [0u8; READY_FEE_BOUNDARY_KEY_LEN];
+        buf.copy_from_slice(data);
+        Self(buf)
+    }
+
+    const BOUND: Bound = Bound::Bounded {
+        max_size: READY_FEE_BOUNDARY_KEY_LEN_U32,
+        is_fixed_size: true,
+    };
+}
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
 pub struct PendingFeeKey(pub [u8; PENDING_FEE_KEY_LEN]);
 
 impl PendingFeeKey {
-    pub fn new(effective_gas_price: u64, tx_hash: [u8; 32]) -> Self {
+    pub fn new(fee_rank: u64, tx_hash: [u8; 32]) -> Self {
         let mut buf = [0u8; PENDING_FEE_KEY_LEN];
-        buf[0..8].copy_from_slice(&effective_gas_price.to_be_bytes());
+        buf[0..8].copy_from_slice(&fee_rank.to_be_bytes());
         buf[8..40].copy_from_slice(&tx_hash);
         Self(buf)
     }
 
+    pub fn fee_rank(self) -> u64 {
+        let mut fee = [0u8; 8];
+        fee.copy_from_slice(&self.0[0..8]);
+        u64::from_be_bytes(fee)
+    }
+
     pub fn tx_id(self) -> [u8; 32] {
         let mut tx = [0u8; 32];
         tx.copy_from_slice(&self.0[8..40]);
@@ -0,0 +1,88 @@
+//! どこで: ready tip index の同期状態 / 何を: index化済みbase feeを保持 / なぜ: base fee変動時の再分類をblock確定から切り離すため
+
+use crate::chain_data::codec::{encode_guarded, mark_decode_failure};
+use ic_stable_structures::storable::Bound;
+use ic_stable_structures::Storable;
+use std::borrow::Cow;
+use zerocopy::byteorder::big_endian::{U32, U64};
+use zerocopy::{FromBytes, Immutable, IntoBytes, KnownLayout, Unaligned};
+
+pub const READY_INDEX_STATE_SIZE_U32: u32 = 12;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ReadyIndexStateV1 {
+    pub schema_version: u32,
+    /// ready_tip_index のbucketが整合しているbase fee
+    pub ready_tip_base_fee: u64,
+}
+
+#[derive(
+    Clone, Copy, Debug, Eq, PartialEq, FromBytes, IntoBytes, KnownLayout, Immutable, Unaligned,
+)]
+#[repr(C)]
+struct ReadyIndexStateWire {
+    schema_version: U32,
+    ready_tip_base_fee: U64,
+}
+
+impl ReadyIndexStateWire {
+    fn new(state: &ReadyIndexStateV1) -> Self {
+        Self {
+            schema_version: U32::new(state.schema_version),
+            ready_tip_base_fee: U64::new(state.ready_tip_base_fee),
+        }
+    }
+}
+
+impl ReadyIndexStateV1 {
+    pub fn new() -> Self {
+        Self {
+            schema_version: 1,
+            ready_tip_base_fee: 0,
+        }
+    }
+}
+
+impl Default for ReadyIndexStateV1 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Storable for ReadyIndexStateV1 {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        let wire = ReadyIndexStateWire::new(self);
+        match encode_guarded(
+            b"ready_index_state",
+            Cow::Owned(wire.as_bytes().to_vec()),
+            READY_INDEX_STATE_SIZE_U32,
+        ) {
+            Ok(value) => value,
+            Err(_) => Cow::Owned(vec![0u8; READY_INDEX_STATE_SIZE_U32 as usize]),
+        }
+    }
+
+    fn into_bytes(self) -> Vec<u8> {
+        ReadyIndexStateWire::new(&self).as_bytes().to_vec()
+    }
+
+    fn from_bytes(bytes: Cow<'_, [u8]>) -> Self {
+        let data = bytes.as_ref();
+        let wire = match ReadyIndexStateWire::read_from_bytes(data) {
+            Ok(value) => value,
+            Err(_) => {
+                mark_decode_failure(b"ready_index_state", false);
+                return ReadyIndexStateV1::new();
+            }
+        };
+        Self {
+            schema_version: wire.schema_version.get(),
+            ready_tip_base_fee: wire.ready_tip_base_fee.get(),
+        }
+    }
+
+    const BOUND: Bound = Bound::Bounded {
+        max_size: READY_INDEX_STATE_SIZE_U32,
+        is_fixed_size: true,
+    };
+}
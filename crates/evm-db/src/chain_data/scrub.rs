@@ -0,0 +1,372 @@
+//! どこで: stable構造の整合性scrub / 何を: 走査cursorと検出結果のlog / なぜ: hot pathで読まれない値の破損や索引の欠けを、複数tickに分けて見つけて残すため
+
+use crate::chain_data::codec::{encode_guarded, mark_decode_failure};
+use ic_stable_structures::storable::Bound;
+use ic_stable_structures::Storable;
+use std::borrow::Cow;
+use zerocopy::byteorder::big_endian::{U32, U64};
+use zerocopy::{FromBytes, Immutable, IntoBytes, KnownLayout, Unaligned};
+
+pub const SCRUB_STATE_SIZE_U32: u32 = 96;
+pub const SCRUB_FINDING_SIZE_U32: u32 = 51;
+/// 検出logの上限。超えたら古いものから捨てる。
+pub const SCRUB_FINDING_LOG_CAP: u64 = 1_024;
+const FLAG_NONE: u8 = 0;
+const FLAG_SOME: u8 = 1;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ScrubPhase {
+    Idle,
+    Running,
+    Paused,
+}
+
+impl ScrubPhase {
+    pub fn to_u8(self) -> u8 {
+        match self {
+            ScrubPhase::Idle => 0,
+            ScrubPhase::Running => 1,
+            ScrubPhase::Paused => 2,
+        }
+    }
+
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(ScrubPhase::Idle),
+            1 => Some(ScrubPhase::Running),
+            2 => Some(ScrubPhase::Paused),
+            _ => None,
+        }
+    }
+}
+
+/// 走査対象の map。scrub はこの順で進む。
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ScrubTarget {
+    Blocks,
+    Receipts,
+    TxLocs,
+    /// tx_store を走査し、EthSigned tx の hash 索引を確かめる
+    EthTxHashIndex,
+    WrapRequests,
+    WrapQueue,
+    UnwrapRequests,
+    UnwrapQueue,
+}
+
+impl ScrubTarget {
+    pub fn to_u8(self) -> u8 {
+        match self {
+            ScrubTarget::Blocks => 0,
+            ScrubTarget::Receipts => 1,
+            ScrubTarget::TxLocs => 2,
+            ScrubTarget::EthTxHashIndex => 3,
+            ScrubTarget::WrapRequests => 4,
+            ScrubTarget::WrapQueue => 5,
+            ScrubTarget::UnwrapRequests => 6,
+            ScrubTarget::UnwrapQueue => 7,
+        }
+    }
+
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(ScrubTarget::Blocks),
+            1 => Some(ScrubTarget::Receipts),
+            2 => Some(ScrubTarget::TxLocs),
+            3 => Some(ScrubTarget::EthTxHashIndex),
+            4 => Some(ScrubTarget::WrapRequests),
+            5 => Some(ScrubTarget::WrapQueue),
+            6 => Some(ScrubTarget::UnwrapRequests),
+            7 => Some(ScrubTarget::UnwrapQueue),
+            _ => None,
+        }
+    }
+
+    pub fn next(self) -> Option<Self> {
+        match self {
+            ScrubTarget::Blocks => Some(ScrubTarget::Receipts),
+            ScrubTarget::Receipts => Some(ScrubTarget::TxLocs),
+            ScrubTarget::TxLocs => Some(ScrubTarget::EthTxHashIndex),
+            ScrubTarget::EthTxHashIndex => Some(ScrubTarget::WrapRequests),
+            ScrubTarget::WrapRequests => Some(ScrubTarget::WrapQueue),
+            ScrubTarget::WrapQueue => Some(ScrubTarget::UnwrapRequests),
+            ScrubTarget::UnwrapRequests => Some(ScrubTarget::UnwrapQueue),
+            ScrubTarget::UnwrapQueue => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ScrubFindingKind {
+    /// 値や blob が decode できない
+    Unreadable,
+    MissingTxIndex,
+    MissingTxLoc,
+    MissingReceipt,
+    /// 揃っているが block 上の位置と食い違う
+    LocationMismatch,
+    /// block に載っていない receipt
+    OrphanReceipt,
+    /// 存在しない block 位置を指す included loc
+    DanglingTxLoc,
+    MissingEthTxHash,
+    /// request が無い queue entry
+    DanglingQueueEntry,
+}
+
+impl ScrubFindingKind {
+    pub fn to_u8(self) -> u8 {
+        match self {
+            ScrubFindingKind::Unreadable => 0,
+            ScrubFindingKind::MissingTxIndex => 1,
+            ScrubFindingKind::MissingTxLoc => 2,
+            ScrubFindingKind::MissingReceipt => 3,
+            ScrubFindingKind::LocationMismatch => 4,
+            ScrubFindingKind::OrphanReceipt => 5,
+            ScrubFindingKind::DanglingTxLoc => 6,
+            ScrubFindingKind::MissingEthTxHash => 7,
+            ScrubFindingKind::DanglingQueueEntry => 8,
+        }
+    }
+
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(ScrubFindingKind::Unreadable),
+            1 => Some(ScrubFindingKind::MissingTxIndex),
+            2 => Some(ScrubFindingKind::MissingTxLoc),
+            3 => Some(ScrubFindingKind::MissingReceipt),
+            4 => Some(ScrubFindingKind::LocationMismatch),
+            5 => Some(ScrubFindingKind::OrphanReceipt),
+            6 => Some(ScrubFindingKind::DanglingTxLoc),
+            7 => Some(ScrubFindingKind::MissingEthTxHash),
+            8 => Some(ScrubFindingKind::DanglingQueueEntry),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ScrubStateV1 {
+    pub schema_version: u32,
+    pub phase: ScrubPhase,
+    pub target: ScrubTarget,
+    /// target 内で最後に見た key（None は先頭から）。u64 key は先頭8byteにBEで入れる。
+    pub cursor_key: Option<[u8; 32]>,
+    pub auto_repair: bool,
+    pub run_id: u64,
+    pub started_at: u64,
+    pub finished_at: u64,
+    pub scanned: u64,
+    pub findings: u64,
+    pub repaired: u64,
+    pub next_finding_seq: u64,
+}
+
+/// scrub_findings の値。key は next_finding_seq から振る連番。
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ScrubFindingV1 {
+    pub run_id: u64,
+    pub target: ScrubTarget,
+    pub kind: ScrubFindingKind,
+    pub repaired: bool,
+    pub key: [u8; 32],
+    pub detected_at: u64,
+}
+
+#[derive(
+    Clone, Copy, Debug, Eq, PartialEq, FromBytes, IntoBytes, KnownLayout, Immutable, Unaligned,
+)]
+#[repr(C)]
+struct ScrubStateWire {
+    schema_version: U32,
+    phase: u8,
+    target: u8,
+    cursor_flag: u8,
+    cursor_key: [u8; 32],
+    auto_repair: u8,
+    run_id: U64,
+    started_at: U64,
+    finished_at: U64,
+    scanned: U64,
+    findings: U64,
+    repaired: U64,
+    next_finding_seq: U64,
+}
+
+#[derive(
+    Clone, Copy, Debug, Eq, PartialEq, FromBytes, IntoBytes, KnownLayout, Immutable, Unaligned,
+)]
+#[repr(C)]
+struct ScrubFindingWire {
+    run_id: U64,
+    target: u8,
+    kind: u8,
+    repaired: u8,
+    key: [u8; 32],
+    detected_at: U64,
+}
+
+impl ScrubStateWire {
+    fn new(value: &ScrubStateV1) -> Self {
+        Self {
+            schema_version: U32::new(value.schema_version),
+            phase: value.phase.to_u8(),
+            target: value.target.to_u8(),
+            cursor_flag: if value.cursor_key.is_some() {
+                FLAG_SOME
+            } else {
+                FLAG_NONE
+            },
+            cursor_key: value.cursor_key.unwrap_or([0u8; 32]),
+            auto_repair: u8::from(value.auto_repair),
+            run_id: U64::new(value.run_id),
+            started_at: U64::new(value.started_at),
+            finished_at: U64::new(value.finished_at),
+            scanned: U64::new(value.scanned),
+            findings: U64::new(value.findings),
+            repaired: U64::new(value.repaired),
+            next_finding_seq: U64::new(value.next_finding_seq),
+        }
+    }
+}
+
+impl ScrubFindingWire {
+    fn new(value: &ScrubFindingV1) -> Self {
+        Self {
+            run_id: U64::new(value.run_id),
+            target: value.target.to_u8(),
+            kind: value.kind.to_u8(),
+            repaired: u8::from(value.repaired),
+            key: value.key,
+            detected_at: U64::new(value.detected_at),
+        }
+    }
+}
+
+impl ScrubStateV1 {
+    pub fn new() -> Self {
+        Self {
+            schema_version: 1,
+            phase: ScrubPhase::Idle,
+            target: ScrubTarget::Blocks,
+            cursor_key: None,
+            auto_repair: false,
+            run_id: 0,
+            started_at: 0,
+            finished_at: 0,
+            scanned: 0,
+            findings: 0,
+            repaired: 0,
+            next_finding_seq: 0,
+        }
+    }
+}
+
+impl Default for ScrubStateV1 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Storable for ScrubStateV1 {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        let wire = ScrubStateWire::new(self);
+        match encode_guarded(
+            b"scrub_state",
+            Cow::Owned(wire.as_bytes().to_vec()),
+            SCRUB_STATE_SIZE_U32,
+        ) {
+            Ok(value) => value,
+            Err(_) => Cow::Owned(vec![0u8; SCRUB_STATE_SIZE_U32 as usize]),
+        }
+    }
+
+    fn into_bytes(self) -> Vec<u8> {
+        ScrubStateWire::new(&self).as_bytes().to_vec()
+    }
+
+    fn from_bytes(bytes: Cow<'_, [u8]>) -> Self {
+        let wire = match ScrubStateWire::read_from_bytes(bytes.as_ref()) {
+            Ok(value) => value,
+            Err(_) => {
+                mark_decode_failure(b"scrub_state", false);
+                return ScrubStateV1::new();
+            }
+        };
+        let (Some(phase), Some(target)) = (
+            ScrubPhase::from_u8(wire.phase),
+            ScrubTarget::from_u8(wire.target),
+        ) else {
+            mark_decode_failure(b"scrub_state", false);
+            return ScrubStateV1::new();
+        };
+        Self {
+            schema_version: wire.schema_version.get(),
+            phase,
+            target,
+            cursor_key: (wire.cursor_flag == FLAG_SOME).then_some(wire.cursor_key),
+            auto_repair: wire.auto_repair != 0,
+            run_id: wire.run_id.get(),
+            started_at: wire.started_at.get(),
+            finished_at: wire.finished_at.get(),
+            scanned: wire.scanned.get(),
+            findings: wire.findings.get(),
+            repaired: wire.repaired.get(),
+            next_finding_seq: wire.next_finding_seq.get(),
+        }
+    }
+
+    const BOUND: Bound = Bound::Bounded {
+        max_size: SCRUB_STATE_SIZE_U32,
+        is_fixed_size: true,
+    };
+}
+
+impl Storable for ScrubFindingV1 {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        let wire = ScrubFindingWire::new(self);
+        match encode_guarded(
+            b"scrub_finding",
+            Cow::Owned(wire.as_bytes().to_vec()),
+            SCRUB_FINDING_SIZE_U32,
+        ) {
+            Ok(value) => value,
+            Err(_) => Cow::Owned(vec![0u8; SCRUB_FINDING_SIZE_U32 as usize]),
+        }
+    }
+
+    fn into_bytes(self) -> Vec<u8> {
+        ScrubFindingWire::new(&self).as_bytes().to_vec()
+    }
+
+    fn from_bytes(bytes: Cow<'_, [u8]>) -> Self {
+        let decoded = ScrubFindingWire::read_from_bytes(bytes.as_ref())
+            .ok()
+            .and_then(|wire| {
+                Some(Self {
+                    run_id: wire.run_id.get(),
+                    target: ScrubTarget::from_u8(wire.target)?,
+                    kind: ScrubFindingKind::from_u8(wire.kind)?,
+                    repaired: wire.repaired != 0,
+                    key: wire.key,
+                    detected_at: wire.detected_at.get(),
+                })
+            });
+        decoded.unwrap_or_else(|| {
+            mark_decode_failure(b"scrub_finding", false);
+            Self {
+                run_id: 0,
+                target: ScrubTarget::Blocks,
+                kind: ScrubFindingKind::Unreadable,
+                repaired: false,
+                key: [0u8; 32],
+                detected_at: 0,
+            }
+        })
+    }
+
+    const BOUND: Bound = Bound::Bounded {
+        max_size: SCRUB_FINDING_SIZE_U32,
+        is_fixed_size: true,
+    };
+}
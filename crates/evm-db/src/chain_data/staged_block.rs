@@ -0,0 +1,151 @@
+//! どこで: 複数メッセージにまたがるブロック組成 / 何を: 未封印ブロックのヘッダ情報と進捗 / なぜ: 命令上限で止まったブロックを次のtimerで再開するため
+
+use crate::chain_data::codec::{encode_guarded, mark_decode_failure};
+use ic_stable_structures::storable::Bound;
+use ic_stable_structures::Storable;
+use std::borrow::Cow;
+use zerocopy::byteorder::big_endian::{U32, U64};
+use zerocopy::{FromBytes, Immutable, IntoBytes, KnownLayout, Unaligned};
+
+pub const STAGED_BLOCK_META_SIZE_U32: u32 = 101;
+/// staged block を封印せずに持ち越せるラウンド数の上限。
+pub const MAX_STAGED_BLOCK_ROUNDS: u32 = 16;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct StagedBlockMetaV1 {
+    pub schema_version: u32,
+    pub active: bool,
+    pub number: u64,
+    pub timestamp: u64,
+    pub parent_hash: [u8; 32],
+    pub base_fee: u64,
+    pub block_gas_limit: u64,
+    /// staged diff が前提とする evm_state_epoch（変化していれば破棄する）
+    pub evm_state_epoch: u64,
+    /// 封印前に終えたラウンド数
+    pub rounds: u32,
+    /// StagedBlockItems に保存済みの項目数（keyは 0..item_count）
+    pub item_count: u32,
+    pub sealed_total: u64,
+    pub discarded_total: u64,
+}
+
+#[derive(
+    Clone, Copy, Debug, Eq, PartialEq, FromBytes, IntoBytes, KnownLayout, Immutable, Unaligned,
+)]
+#[repr(C)]
+struct StagedBlockMetaWire {
+    schema_version: U32,
+    active: u8,
+    number: U64,
+    timestamp: U64,
+    parent_hash: [u8; 32],
+    base_fee: U64,
+    block_gas_limit: U64,
+    evm_state_epoch: U64,
+    rounds: U32,
+    item_count: U32,
+    sealed_total: U64,
+    discarded_total: U64,
+}
+
+impl StagedBlockMetaWire {
+    fn new(meta: &StagedBlockMetaV1) -> Self {
+        Self {
+            schema_version: U32::new(meta.schema_version),
+            active: u8::from(meta.active),
+            number: U64::new(meta.number),
+            timestamp: U64::new(meta.timestamp),
+            parent_hash: meta.parent_hash,
+            base_fee: U64::new(meta.base_fee),
+            block_gas_limit: U64::new(meta.block_gas_limit),
+            evm_state_epoch: U64::new(meta.evm_state_epoch),
+            rounds: U32::new(meta.rounds),
+            item_count: U32::new(meta.item_count),
+            sealed_total: U64::new(meta.sealed_total),
+            discarded_total: U64::new(meta.discarded_total),
+        }
+    }
+}
+
+impl StagedBlockMetaV1 {
+    pub fn new() -> Self {
+        Self {
+            schema_version: 1,
+            active: false,
+            number: 0,
+            timestamp: 0,
+            parent_hash: [0u8; 32],
+            base_fee: 0,
+            block_gas_limit: 0,
+            evm_state_epoch: 0,
+            rounds: 0,
+            item_count: 0,
+            sealed_total: 0,
+            discarded_total: 0,
+        }
+    }
+
+    /// 累計カウンタを残したまま未封印ブロックを空にする。
+    pub fn cleared(&self) -> Self {
+        Self {
+            sealed_total: self.sealed_total,
+            discarded_total: self.discarded_total,
+            ..Self::new()
+        }
+    }
+}
+
+impl Default for StagedBlockMetaV1 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Storable for StagedBlockMetaV1 {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        let wire = StagedBlockMetaWire::new(self);
+        match encode_guarded(
+            b"staged_block_meta",
+            Cow::Owned(wire.as_bytes().to_vec()),
+            STAGED_BLOCK_META_SIZE_U32,
+        ) {
+            Ok(value) => value,
+            Err(_) => Cow::Owned(vec![0u8; STAGED_BLOCK_META_SIZE_U32 as usize]),
+        }
+    }
+
+    fn into_bytes(self) -> Vec<u8> {
+        StagedBlockMetaWire::new(&self).as_bytes().to_vec()
+    }
+
+    fn from_bytes(bytes: Cow<'_, [u8]>) -> Self {
+        let data = bytes.as_ref();
+        let wire = match StagedBlockMetaWire::read_from_bytes(data) {
+            Ok(value) => value,
+            Err(_) => {
+                mark_decode_failure(b"staged_block_meta", false);
+                return StagedBlockMetaV1::new();
+            }
+        };
+        Self {
+            schema_version: wire.schema_version.get(),
+            active: wire.active != 0,
+            number: wire.number.get(),
+            timestamp: wire.timestamp.get(),
+            parent_hash: wire.parent_hash,
+            base_fee: wire.base_fee.get(),
+            block_gas_limit: wire.block_gas_limit.get(),
+            evm_state_epoch: wire.evm_state_epoch.get(),
+            rounds: wire.rounds.get(),
+            item_count: wire.item_count.get(),
+            sealed_total: wire.sealed_total.get(),
+            discarded_total: wire.discarded_total.get(),
+        }
+    }
+
+    const BOUND: Bound = Bound::Bounded {
+        max_size: STAGED_BLOCK_META_SIZE_U32,
+        is_fixed_size: true,
+    };
+}
@@ -0,0 +1,169 @@
+//! どこで: 状態スナップショットの取り込み / 何を: import中のmanifestと進捗 / なぜ: 複数メッセージに分かれた取り込みを途中から再開するため
+
+use crate::chain_data::codec::{encode_guarded, mark_decode_failure};
+use ic_stable_structures::storable::Bound;
+use ic_stable_structures::Storable;
+use std::borrow::Cow;
+use zerocopy::byteorder::big_endian::{U32, U64};
+use zerocopy::{FromBytes, Immutable, IntoBytes, KnownLayout, Unaligned};
+
+pub const STATE_SNAPSHOT_IMPORT_SIZE_U32: u32 = 117;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum StateSnapshotImportPhase {
+    Idle,
+    /// 取り込み先の既存状態を少しずつ消している
+    Clearing,
+    /// accounts/storage/codes のchunkを受け付けている
+    Loading,
+    /// 取り込み済みの状態からstate rootを再計算している
+    Verifying,
+}
+
+impl StateSnapshotImportPhase {
+    pub fn to_u8(self) -> u8 {
+        match self {
+            StateSnapshotImportPhase::Idle => 0,
+            StateSnapshotImportPhase::Loading => 1,
+            StateSnapshotImportPhase::Verifying => 2,
+            StateSnapshotImportPhase::Clearing => 3,
+        }
+    }
+
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(StateSnapshotImportPhase::Idle),
+            1 => Some(StateSnapshotImportPhase::Loading),
+            2 => Some(StateSnapshotImportPhase::Verifying),
+            3 => Some(StateSnapshotImportPhase::Clearing),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct StateSnapshotImportV1 {
+    pub schema_version: u32,
+    pub phase: StateSnapshotImportPhase,
+    /// 取り込み元のhead（完了時にこのheadへ揃える）
+    pub head_number: u64,
+    pub block_hash: [u8; 32],
+    pub timestamp: u64,
+    /// 取り込み元が公開したstate root。再計算結果と一致しなければ完了させない。
+    pub state_root: [u8; 32],
+    /// 取り込み元でexportを開始した時点のevm_state_epoch
+    pub evm_state_epoch: u64,
+    pub accounts: u64,
+    pub storage: u64,
+    pub codes: u64,
+}
+
+#[derive(
+    Clone, Copy, Debug, Eq, PartialEq, FromBytes, IntoBytes, KnownLayout, Immutable, Unaligned,
+)]
+#[repr(C)]
+struct StateSnapshotImportWire {
+    schema_version: U32,
+    phase: u8,
+    head_number: U64,
+    block_hash: [u8; 32],
+    timestamp: U64,
+    state_root: [u8; 32],
+    evm_state_epoch: U64,
+    accounts: U64,
+    storage: U64,
+    codes: U64,
+}
+
+impl StateSnapshotImportWire {
+    fn new(value: &StateSnapshotImportV1) -> Self {
+        Self {
+            schema_version: U32::new(value.schema_version),
+            phase: value.phase.to_u8(),
+            head_number: U64::new(value.head_number),
+            block_hash: value.block_hash,
+            timestamp: U64::new(value.timestamp),
+            state_root: value.state_root,
+            evm_state_epoch: U64::new(value.evm_state_epoch),
+            accounts: U64::new(value.accounts),
+            storage: U64::new(value.storage),
+            codes: U64::new(value.codes),
+        }
+    }
+}
+
+impl StateSnapshotImportV1 {
+    pub fn new() -> Self {
+        Self {
+            schema_version: 1,
+            phase: StateSnapshotImportPhase::Idle,
+            head_number: 0,
+            block_hash: [0u8; 32],
+            timestamp: 0,
+            state_root: [0u8; 32],
+            evm_state_epoch: 0,
+            accounts: 0,
+            storage: 0,
+            codes: 0,
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.phase != StateSnapshotImportPhase::Idle
+    }
+}
+
+impl Default for StateSnapshotImportV1 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Storable for StateSnapshotImportV1 {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        let wire = StateSnapshotImportWire::new(self);
+        match encode_guarded(
+            b"state_snapshot_import",
+            Cow::Owned(wire.as_bytes().to_vec()),
+            STATE_SNAPSHOT_IMPORT_SIZE_U32,
+        ) {
+            Ok(value) => value,
+            Err(_) => Cow::Owned(vec![0u8; STATE_SNAPSHOT_IMPORT_SIZE_U32 as usize]),
+        }
+    }
+
+    fn into_bytes(self) -> Vec<u8> {
+        StateSnapshotImportWire::new(&self).as_bytes().to_vec()
+    }
+
+    fn from_bytes(bytes: Cow<'_, [u8]>) -> Self {
+        let wire = match StateSnapshotImportWire::read_from_bytes(bytes.as_ref()) {
+            Ok(value) => value,
+            Err(_) => {
+                mark_decode_failure(b"state_snapshot_import", false);
+                return StateSnapshotImportV1::new();
+            }
+        };
+        let Some(phase) = StateSnapshotImportPhase::from_u8(wire.phase) else {
+            mark_decode_failure(b"state_snapshot_import", false);
+            return StateSnapshotImportV1::new();
+        };
+        Self {
+            schema_version: wire.schema_version.get(),
+            phase,
+            head_number: wire.head_number.get(),
+            block_hash: wire.block_hash,
+            timestamp: wire.timestamp.get(),
+            state_root: wire.state_root,
+            evm_state_epoch: wire.evm_state_epoch.get(),
+            accounts: wire.accounts.get(),
+            storage: wire.storage.get(),
+            codes: wire.codes.get(),
+        }
+    }
+
+    const BOUND: Bound = Bound::Bounded {
+        max_size: STATE_SNAPSHOT_IMPORT_SIZE_U32,
+        is_fixed_size: true,
+    };
+}
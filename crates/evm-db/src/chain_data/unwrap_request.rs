@@ -10,6 +10,9 @@ const MAX_ERROR_LEN: usize = 192;
 const MAX_LEDGER_TX_ID_LEN: usize = 128;
 const MAX_ENCODED_LEN: u32 = 1_088;
 const CHECKSUM_LEN: usize = 4;
+/// 1: created_at_time 無し。2: created_at_time 付き。3: recipient の subaccount 付き。
+/// 4: fungible / NFT の区別付き。
+const ENCODING_VERSION: u8 = 4;
 pub const UNWRAP_DECODE_FAILURE_CODE: &str = "stable.decode.unwrap_request";
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
@@ -18,6 +21,8 @@ pub enum UnwrapRequestStatus {
     Dispatching,
     Dispatched,
     DispatchFailed,
+    /// ledger が応答を返さず、送金されたか分からない。ledger 照合で確定させる。
+    DispatchUncertain,
 }
 
 impl UnwrapRequestStatus {
@@ -27,6 +32,7 @@ impl UnwrapRequestStatus {
             Self::Dispatching => 1,
             Self::Dispatched => 2,
             Self::DispatchFailed => 3,
+            Self::DispatchUncertain => 4,
         }
     }
 
@@ -36,6 +42,33 @@ impl UnwrapRequestStatus {
             1 => Some(Self::Dispatching),
             2 => Some(Self::Dispatched),
             3 => Some(Self::DispatchFailed),
+            4 => Some(Self::DispatchUncertain),
+            _ => None,
+        }
+    }
+}
+
+/// 版 3 以前の record は全て fungible。
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum UnwrapAssetKind {
+    #[default]
+    Fungible,
+    /// ICRC-7 collection。`amount` は数量ではなく token id を保持する。
+    Nft,
+}
+
+impl UnwrapAssetKind {
+    fn to_u8(self) -> u8 {
+        match self {
+            Self::Fungible => 0,
+            Self::Nft => 1,
+        }
+    }
+
+    fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(Self::Fungible),
+            1 => Some(Self::Nft),
             _ => None,
         }
     }
@@ -46,11 +79,14 @@ pub struct UnwrapDispatchRequest {
     pub asset_id: Vec<u8>,
     pub amount: [u8; 32],
     pub recipient: Vec<u8>,
+    /// None は ICRC-1 の既定口座
+    pub recipient_subaccount: Option<[u8; 32]>,
     pub status: UnwrapRequestStatus,
     pub ledger_tx_id: Option<Vec<u8>>,
     pub error_code: Option<String>,
     pub updated_at: u64,
     pub transfer_created_at_time: u64,
+    pub kind: UnwrapAssetKind,
 }
 
 impl Storable for UnwrapDispatchRequest {
@@ -85,11 +121,13 @@ impl UnwrapDispatchRequest {
             asset_id: vec![0u8],
             amount: [0u8; 32],
             recipient: vec![0u8],
+            recipient_subaccount: None,
             status: UnwrapRequestStatus::DispatchFailed,
             ledger_tx_id: None,
             error_code: Some(UNWRAP_DECODE_FAILURE_CODE.to_string()),
             updated_at: 0,
             transfer_created_at_time: 0,
+            kind: UnwrapAssetKind::Fungible,
         }
     }
 
@@ -116,7 +154,7 @@ impl UnwrapDispatchRequest {
             return None;
         }
         let mut out = Vec::with_capacity(256);
-        out.push(2u8);
+        out.push(ENCODING_VERSION);
         write_bytes(&mut out, &self.asset_id)?;
         out.extend_from_slice(&self.amount);
         write_bytes(&mut out, &self.recipient)?;
@@ -137,6 +175,14 @@ impl UnwrapDispatchRequest {
         }
         out.extend_from_slice(&self.updated_at.to_be_bytes());
         out.extend_from_slice(&self.transfer_created_at_time.to_be_bytes());
+        match self.recipient_subaccount.as_ref() {
+            Some(value) => {
+                out.push(1u8);
+                out.extend_from_slice(value);
+            }
+            None => out.push(0u8),
+        }
+        out.push(self.kind.to_u8());
         let checksum = crc32_ieee(&out);
         out.extend_from_slice(&checksum.to_be_bytes());
         Some(out)
@@ -146,7 +192,7 @@ impl UnwrapDispatchRequest {
         let mut offset = 0usize;
         let version = *data.get(offset)?;
         offset += 1;
-        if version != 1 && version != 2 {
+        if version == 0 || version > ENCODING_VERSION {
             return None;
         }
         let asset_id = read_bytes(data, &mut offset, MAX_BLOB_LEN)?;
@@ -177,11 +223,29 @@ impl UnwrapDispatchRequest {
             _ => return None,
         };
         let updated_at = read_u64(data, &mut offset)?;
-        let transfer_created_at_time = if version == 2 {
+        let transfer_created_at_time = if version >= 2 {
             read_u64(data, &mut offset)?
         } else {
             0
         };
+        let recipient_subaccount = if version >= 3 {
+            let flag = *data.get(offset)?;
+            offset += 1;
+            match flag {
+                0 => None,
+                1 => Some(read_array_32(data, &mut offset)?),
+                _ => return None,
+            }
+        } else {
+            None
+        };
+        let kind = if version >= 4 {
+            let kind = UnwrapAssetKind::from_u8(*data.get(offset)?)?;
+            offset += 1;
+            kind
+        } else {
+            UnwrapAssetKind::Fungible
+        };
         let remaining = data.len().checked_sub(offset)?;
         if remaining != CHECKSUM_LEN {
             return None;
@@ -197,11 +261,13 @@ impl UnwrapDispatchRequest {
             asset_id,
             amount,
             recipient,
+            recipient_subaccount,
             status,
             ledger_tx_id,
             error_code,
             updated_at,
             transfer_created_at_time,
+            kind,
         })
     }
 }
@@ -257,7 +323,10 @@ fn crc32_ieee(data: &[u8]) -> u32 {
 
 #[cfg(test)]
 mod tests {
-    use super::{UnwrapDispatchRequest, UnwrapRequestStatus, UNWRAP_DECODE_FAILURE_CODE};
+    use super::{
+        crc32_ieee, UnwrapAssetKind, UnwrapDispatchRequest, UnwrapRequestStatus,
+        UNWRAP_DECODE_FAILURE_CODE,
+    };
     use crate::meta::{clear_needs_migration, needs_migration};
     use crate::stable_state::init_stable_state;
     use ic_stable_structures::Storable;
@@ -268,11 +337,13 @@ mod tests {
             asset_id: vec![0x22u8; 10],
             amount: [0x33u8; 32],
             recipient: vec![0x44u8; 10],
+            recipient_subaccount: Some([0x66u8; 32]),
             status: UnwrapRequestStatus::Queued,
             ledger_tx_id: Some(vec![0x55u8; 8]),
             error_code: Some("wrap.sample".to_string()),
             updated_at: 11,
             transfer_created_at_time: 12,
+            kind: UnwrapAssetKind::Fungible,
         }
     }
 
@@ -282,6 +353,49 @@ mod tests {
         let bytes = req.to_bytes().into_owned();
         let decoded = UnwrapDispatchRequest::from_bytes(Cow::Owned(bytes));
         assert_eq!(decoded, req);
+
+        let uncertain = UnwrapDispatchRequest {
+            status: UnwrapRequestStatus::DispatchUncertain,
+            ..sample_request()
+        };
+        let decoded = UnwrapDispatchRequest::from_bytes(uncertain.to_bytes());
+        assert_eq!(decoded, uncertain);
+
+        let nft = UnwrapDispatchRequest {
+            kind: UnwrapAssetKind::Nft,
+            ..sample_request()
+        };
+        let decoded = UnwrapDispatchRequest::from_bytes(nft.to_bytes());
+        assert_eq!(decoded, nft);
+    }
+
+    #[test]
+    fn unwrap_request_decodes_v2_record_without_subaccount() {
+        let req = UnwrapDispatchRequest {
+            recipient_subaccount: None,
+            ..sample_request()
+        };
+        let mut bytes = req.to_bytes().into_owned();
+        // 版 4 の末尾 (subaccount flag + kind + checksum) を外し、版 2 として組み直す。
+        bytes.truncate(bytes.len() - 6);
+        bytes[0] = 2;
+        let checksum = crc32_ieee(&bytes);
+        bytes.extend_from_slice(&checksum.to_be_bytes());
+        let decoded = UnwrapDispatchRequest::from_bytes(Cow::Owned(bytes));
+        assert_eq!(decoded, req);
+    }
+
+    #[test]
+    fn unwrap_request_decodes_v3_record_as_fungible() {
+        let req = sample_request();
+        let mut bytes = req.to_bytes().into_owned();
+        // 版 4 の末尾 (kind + checksum) を外し、版 3 として組み直す。
+        bytes.truncate(bytes.len() - 5);
+        bytes[0] = 3;
+        let checksum = crc32_ieee(&bytes);
+        bytes.extend_from_slice(&checksum.to_be_bytes());
+        let decoded = UnwrapDispatchRequest::from_bytes(Cow::Owned(bytes));
+        assert_eq!(decoded, req);
     }
 
     #[test]
@@ -9,6 +9,8 @@ use std::borrow::Cow;
 pub const PRINCIPAL_MAX_BYTES: usize = 29;
 pub const WRAP_STORED_REQUEST_MAX_BYTES: u32 = 2_048;
 pub const FEE_POLICY_MAX_BYTES: u32 = 128;
+pub const ASSET_FEE_SCHEDULE_MAX_BYTES: u32 = 512;
+pub const MAX_FEE_TIERS: usize = 8;
 pub const WRAP_EVM_CONFIG_MAX_BYTES: u32 = 32;
 pub const WRAP_PENDING_SUBMISSION_MAX_BYTES: u32 = 96;
 pub const WRAP_DECODE_FAILURE_CODE: &str = "stable.decode.wrap_request";
@@ -39,6 +41,14 @@ pub enum MintSubmitStatus {
     Submitted,
 }
 
+/// 既存 record は全て fungible。NFT では `asset_id` が ICRC-7 collection、`amount` が token id。
+#[derive(Clone, Copy, Debug, CandidType, Default, Deserialize, Eq, PartialEq)]
+pub enum WrapAssetKind {
+    #[default]
+    Fungible,
+    Nft,
+}
+
 #[derive(Clone, Copy, Debug, CandidType, Deserialize, Eq, PartialEq)]
 pub enum RequestStatus {
     Queued,
@@ -79,6 +89,9 @@ pub struct WrapRequestResult {
     pub mint_submitted_at_time: u64,
     #[serde(default)]
     pub mint_submit_status: MintSubmitStatus,
+    /// 期限切れ返金で送る量。再送でも同じ転送になるよう最初の試行で固定する。
+    #[serde(default)]
+    pub refund_amount_e8s: Option<u128>,
 }
 
 #[derive(Clone, Debug, CandidType, Deserialize)]
@@ -100,6 +113,14 @@ pub struct WrapStoredRequest {
     pub pull_created_at_time: u64,
     #[serde(default)]
     pub withdraw_created_at_time: u64,
+    /// true なら charged_fee_e8s を wrap する asset で受け取り、mint 量から引く。
+    #[serde(default)]
+    pub fee_in_asset: bool,
+    /// この時刻 (ns) を過ぎても mint できていなければ自動で返金する。0 は期限なし。
+    #[serde(default)]
+    pub deadline_at: u64,
+    #[serde(default)]
+    pub kind: WrapAssetKind,
     pub result: WrapRequestResult,
 }
 
@@ -110,6 +131,25 @@ pub struct FeePolicyStored {
     pub gas_price_buffer_bps: u32,
 }
 
+/// amount が min_amount_e8s 以上の wrap に fee_bps の比例 fee を足す。
+#[derive(Clone, Debug, CandidType, Deserialize, Eq, PartialEq)]
+pub struct FeeTierStored {
+    pub min_amount_e8s: u128,
+    pub fee_bps: u32,
+}
+
+/// asset ごとの wrap fee。無い asset は全体の FeePolicyStored を使う。
+/// fee_in_asset なら fee は wrap する asset で取り、fee_ledger_canister は使わない。
+#[derive(Clone, Debug, CandidType, Deserialize, Eq, PartialEq)]
+pub struct AssetFeeScheduleStored {
+    pub fee_ledger_canister: Vec<u8>,
+    pub cycle_fee_e8s: u64,
+    pub gas_price_buffer_bps: u32,
+    pub fee_in_asset: bool,
+    /// min_amount_e8s の昇順
+    pub tiers: Vec<FeeTierStored>,
+}
+
 #[derive(Clone, Debug, CandidType, Deserialize)]
 pub struct WrapEvmConfigStored {
     pub wrap_factory_address: Vec<u8>,
@@ -176,6 +216,34 @@ impl Storable for FeePolicyStored {
     };
 }
 
+impl Storable for AssetFeeScheduleStored {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        let bytes = candid::encode_one(self).expect("asset_fee_schedule.encode_failed");
+        encode_guarded(
+            b"asset_fee_schedule",
+            Cow::Owned(bytes),
+            ASSET_FEE_SCHEDULE_MAX_BYTES,
+        )
+        .unwrap_or_else(|_| panic!("asset_fee_schedule.encode_guard_failed"))
+    }
+
+    fn into_bytes(self) -> Vec<u8> {
+        self.to_bytes().into_owned()
+    }
+
+    fn from_bytes(bytes: Cow<'_, [u8]>) -> Self {
+        candid::decode_one::<Self>(bytes.as_ref()).unwrap_or_else(|_| {
+            mark_decode_failure(b"asset_fee_schedule", false);
+            Self::decode_failure_placeholder()
+        })
+    }
+
+    const BOUND: Bound = Bound::Bounded {
+        max_size: ASSET_FEE_SCHEDULE_MAX_BYTES,
+        is_fixed_size: false,
+    };
+}
+
 impl Storable for WrapEvmConfigStored {
     fn to_bytes(&self) -> Cow<'_, [u8]> {
         Cow::Owned(candid::encode_one(self).expect("wrap_evm_config.encode_failed"))
@@ -234,6 +302,9 @@ impl WrapStoredRequest {
             fee_created_at_time: 0,
             pull_created_at_time: 0,
             withdraw_created_at_time: 0,
+            fee_in_asset: false,
+            deadline_at: 0,
+            kind: WrapAssetKind::Fungible,
             result: WrapRequestResult {
                 status: RequestStatus::Failed,
                 pull_ledger_tx_id: None,
@@ -252,6 +323,7 @@ impl WrapStoredRequest {
                 mint_nonce: None,
                 mint_submitted_at_time: 0,
                 mint_submit_status: MintSubmitStatus::NotSubmitted,
+                refund_amount_e8s: None,
             },
         }
     }
@@ -267,6 +339,19 @@ impl FeePolicyStored {
     }
 }
 
+impl AssetFeeScheduleStored {
+    /// ledger が空なので quote が wrap_config.unconfigured で止まり、読めない schedule で fee を取らない。
+    fn decode_failure_placeholder() -> Self {
+        Self {
+            fee_ledger_canister: Vec::new(),
+            cycle_fee_e8s: 0,
+            gas_price_buffer_bps: 0,
+            fee_in_asset: false,
+            tiers: Vec::new(),
+        }
+    }
+}
+
 impl WrapEvmConfigStored {
     fn decode_failure_placeholder() -> Self {
         Self {
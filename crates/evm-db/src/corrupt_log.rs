@@ -17,7 +17,34 @@ thread_local! {
     static LAST_RECORDED_TS: Cell<u64> = const { Cell::new(0) };
 }
 
+// ホスト側の検査ツールは CorruptLog へ書かずに、decode失敗のタグだけを集める。
+#[cfg(not(target_arch = "wasm32"))]
+thread_local! {
+    static CAPTURED_TAGS: std::cell::RefCell<Option<Vec<&'static [u8]>>> =
+        const { std::cell::RefCell::new(None) };
+}
+
+/// ホスト側専用: `f` の実行中に記録されたcorruptタグを返す。
+#[cfg(not(target_arch = "wasm32"))]
+pub fn capture_corrupt_tags<R>(f: impl FnOnce() -> R) -> (R, Vec<&'static [u8]>) {
+    let previous = CAPTURED_TAGS.with(|slot| slot.borrow_mut().replace(Vec::new()));
+    let out = f();
+    let tags = CAPTURED_TAGS.with(|slot| {
+        let mut slot = slot.borrow_mut();
+        let tags = slot.take().unwrap_or_default();
+        *slot = previous;
+        tags
+    });
+    (out, tags)
+}
+
 pub fn record_corrupt(tag: &'static [u8]) {
+    #[cfg(not(target_arch = "wasm32"))]
+    CAPTURED_TAGS.with(|slot| {
+        if let Some(tags) = slot.borrow_mut().as_mut() {
+            tags.push(tag);
+        }
+    });
     if !is_replicated_execution() {
         return;
     }
@@ -0,0 +1,567 @@
+//! どこで: ホスト側のstable memory検査 / 何を: ダウンロードしたイメージの領域サイズ・schema・decode整合性の報告 / なぜ: 本番canisterに触れずに破損箇所を特定するため
+
+use crate::blob_ptr::BlobPtr;
+use crate::blob_store::BlobUsageStats;
+use crate::chain_data::{
+    BlockData, Head, InternalTraceSet, ReceiptLike, StoredTx, TxId, TxIndexEntry,
+};
+use crate::corrupt_log::{capture_corrupt_tags, read_corrupt_count, read_last_corrupt_tag};
+use crate::memory::{
+    all_memory_regions, load_memory_image, memory_size_pages, WASM_PAGE_SIZE_BYTES,
+};
+use crate::meta::{current_schema_version, read_meta_unrepaired};
+use crate::stable_state::{init_stable_state, with_state, StableState};
+use ic_stable_structures::Storable;
+use std::borrow::Cow;
+use std::fmt;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+const MEMORY_MANAGER_MAGIC: &[u8; 3] = b"MGR";
+/// 1つのイメージで保持するissueの上限。件数自体は `issue_total` に数える。
+pub const MAX_REPORTED_ISSUES: usize = 1_000;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum InspectError {
+    /// MemoryManager のヘッダが無い（stable memory全体のイメージではない）
+    NotMemoryManagerImage,
+    /// 長さがwasm pageの倍数ではない
+    UnalignedImage { len: u64 },
+    /// MemoryManager がヘッダを読めずに止まった
+    LayoutUnreadable(String),
+}
+
+impl fmt::Display for InspectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InspectError::NotMemoryManagerImage => write!(f, "image has no MemoryManager header"),
+            InspectError::UnalignedImage { len } => {
+                write!(
+                    f,
+                    "image length {len} is not a multiple of the wasm page size"
+                )
+            }
+            InspectError::LayoutUnreadable(message) => {
+                write!(f, "memory layout unreadable: {message}")
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RegionReport {
+    pub id: u8,
+    pub name: &'static str,
+    pub pages: u64,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MetaReport {
+    pub schema_version: u32,
+    pub supported_schema_version: u32,
+    pub needs_migration: bool,
+    /// magic / layout / schema hash の不一致
+    pub header_problems: Vec<&'static str>,
+}
+
+/// 1種類のレコードを走査した結果。bytes はBlobに格納された本体の長さ。
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct SectionReport {
+    pub name: &'static str,
+    pub entries: u64,
+    pub bytes: u64,
+    pub issues: u64,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct InspectIssue {
+    pub section: &'static str,
+    pub key: String,
+    pub detail: String,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct InspectReport {
+    pub image_bytes: u64,
+    pub regions: Vec<RegionReport>,
+    pub meta: MetaReport,
+    pub head: Head,
+    pub last_block_number: u64,
+    pub pruned_before_block: Option<u64>,
+    pub corrupt_count: u64,
+    pub last_corrupt_tag: [u8; 32],
+    pub blob_usage: BlobUsageStats,
+    pub sections: Vec<SectionReport>,
+    pub issues: Vec<InspectIssue>,
+    pub issue_total: u64,
+}
+
+impl InspectReport {
+    pub fn is_clean(&self) -> bool {
+        self.issue_total == 0 && self.meta.header_problems.is_empty()
+    }
+}
+
+/// `image` を現在threadのstable memoryとして読み込み、全領域を検査する。
+/// 呼び出したthreadのstable stateはこのイメージで置き換わる（元のファイルには書かない）。
+pub fn inspect_image(image: Vec<u8>) -> Result<InspectReport, InspectError> {
+    let image_bytes = u64::try_from(image.len()).unwrap_or(u64::MAX);
+    if image.get(..MEMORY_MANAGER_MAGIC.len()) != Some(MEMORY_MANAGER_MAGIC.as_slice()) {
+        return Err(InspectError::NotMemoryManagerImage);
+    }
+    if image_bytes % WASM_PAGE_SIZE_BYTES != 0 {
+        return Err(InspectError::UnalignedImage { len: image_bytes });
+    }
+    catch_unwind(|| {
+        load_memory_image(image);
+        init_stable_state();
+    })
+    .map_err(|panic| InspectError::LayoutUnreadable(panic_message(&panic)))?;
+
+    let regions = all_memory_regions()
+        .iter()
+        .map(|region| RegionReport {
+            id: region.id.as_u8(),
+            name: region.name,
+            pages: memory_size_pages(region.id),
+        })
+        .collect();
+    let (meta, header_problems) = read_meta_unrepaired();
+    let mut report = InspectReport {
+        image_bytes,
+        regions,
+        meta: MetaReport {
+            schema_version: meta.schema_version,
+            supported_schema_version: current_schema_version(),
+            needs_migration: meta.needs_migration,
+            header_problems,
+        },
+        head: Head {
+            number: 0,
+            block_hash: [0u8; 32],
+            timestamp: 0,
+        },
+        last_block_number: 0,
+        pruned_before_block: None,
+        corrupt_count: read_corrupt_count(),
+        last_corrupt_tag: read_last_corrupt_tag(),
+        blob_usage: BlobUsageStats {
+            used_class_bytes: 0,
+            quarantine_class_bytes: 0,
+            free_class_bytes: 0,
+            arena_end_bytes: 0,
+        },
+        sections: Vec::new(),
+        issues: Vec::new(),
+        issue_total: 0,
+    };
+    let mut scan = Scanner {
+        report: &mut report,
+    };
+    scan.section("chain", inspect_chain);
+    scan.section("blocks", inspect_blocks);
+    scan.section("receipts", |state, out| {
+        inspect_blob_map(
+            state,
+            out,
+            state.receipts.iter().map(|e| (*e.key(), e.value())),
+            |raw| {
+                let _ = ReceiptLike::from_bytes(Cow::Owned(raw));
+            },
+        )
+    });
+    scan.section("tx_index", |state, out| {
+        inspect_blob_map(
+            state,
+            out,
+            state.tx_index.iter().map(|e| (*e.key(), e.value())),
+            |raw| {
+                let _ = TxIndexEntry::from_bytes(Cow::Owned(raw));
+            },
+        )
+    });
+    scan.section("internal_traces", |state, out| {
+        inspect_blob_map(
+            state,
+            out,
+            state.internal_traces.iter().map(|e| (*e.key(), e.value())),
+            |raw| {
+                let _ = InternalTraceSet::from_bytes(Cow::Owned(raw));
+            },
+        )
+    });
+    scan.section("tx_store", inspect_tx_store);
+    scan.section("queues", inspect_queues);
+    scan.section("tx_locs", |state, out| {
+        for entry in state.tx_locs.iter() {
+            let key = *entry.key();
+            let (loc, tags) = capture_corrupt_tags(|| entry.value());
+            out.report.entries += 1;
+            if !tags.is_empty() || loc.is_decode_failure_placeholder() {
+                out.issue(tx_key(&key), decode_detail("tx_loc", &tags));
+            }
+        }
+    });
+    scan.section("wrap_requests", |state, out| {
+        for entry in state.wrap_requests.iter() {
+            let key = *entry.key();
+            let (_, tags) = capture_corrupt_tags(|| entry.value());
+            out.report.entries += 1;
+            if !tags.is_empty() {
+                out.issue(tx_key(&key), decode_detail("wrap_request", &tags));
+            }
+        }
+    });
+    scan.section("unwrap_requests", |state, out| {
+        for entry in state.unwrap_requests.iter() {
+            let key = *entry.key();
+            let (_, tags) = capture_corrupt_tags(|| entry.value());
+            out.report.entries += 1;
+            if !tags.is_empty() {
+                out.issue(tx_key(&key), decode_detail("unwrap_request", &tags));
+            }
+        }
+    });
+    scan.section("icp_update_requests", |state, out| {
+        for entry in state.icp_update_requests.iter() {
+            let key = *entry.key();
+            let (_, tags) = capture_corrupt_tags(|| entry.value());
+            out.report.entries += 1;
+            if !tags.is_empty() {
+                out.issue(tx_key(&key), decode_detail("icp_update_request", &tags));
+            }
+        }
+    });
+    Ok(report)
+}
+
+struct Scanner<'a> {
+    report: &'a mut InspectReport,
+}
+
+impl Scanner<'_> {
+    /// 1セクションを走査する。BTree自体が壊れてpanicしても他のセクションは続ける。
+    fn section(&mut self, name: &'static str, f: impl FnOnce(&StableState, &mut SectionScan)) {
+        let mut scan = SectionScan {
+            report: SectionReport {
+                name,
+                ..SectionReport::default()
+            },
+            issues: Vec::new(),
+            head: None,
+            blob_usage: None,
+        };
+        let outcome = catch_unwind(AssertUnwindSafe(|| with_state(|state| f(state, &mut scan))));
+        if let Err(panic) = outcome {
+            scan.issue(
+                String::new(),
+                format!("scan aborted: {}", panic_message(&panic)),
+            );
+        }
+        if let Some((head, last_block_number, pruned_before)) = scan.head {
+            self.report.head = head;
+            self.report.last_block_number = last_block_number;
+            self.report.pruned_before_block = pruned_before;
+        }
+        if let Some(usage) = scan.blob_usage {
+            self.report.blob_usage = usage;
+        }
+        self.report.issue_total = self.report.issue_total.saturating_add(scan.report.issues);
+        let room = MAX_REPORTED_ISSUES.saturating_sub(self.report.issues.len());
+        self.report
+            .issues
+            .extend(scan.issues.into_iter().take(room));
+        self.report.sections.push(scan.report);
+    }
+}
+
+struct SectionScan {
+    report: SectionReport,
+    issues: Vec<InspectIssue>,
+    head: Option<(Head, u64, Option<u64>)>,
+    blob_usage: Option<BlobUsageStats>,
+}
+
+impl SectionScan {
+    fn issue(&mut self, key: String, detail: String) {
+        self.report.issues = self.report.issues.saturating_add(1);
+        if self.issues.len() < MAX_REPORTED_ISSUES {
+            self.issues.push(InspectIssue {
+                section: self.report.name,
+                key,
+                detail,
+            });
+        }
+    }
+
+    fn add_bytes(&mut self, len: usize) {
+        let len = u64::try_from(len).unwrap_or(u64::MAX);
+        self.report.bytes = self.report.bytes.saturating_add(len);
+    }
+}
+
+fn inspect_chain(state: &StableState, out: &mut SectionScan) {
+    let ((head, chain_state, prune_state), tags) = capture_corrupt_tags(|| {
+        (
+            *state.head.get(),
+            *state.chain_state.get(),
+            *state.prune_state.get(),
+        )
+    });
+    if !tags.is_empty() {
+        out.issue(String::new(), decode_detail("chain cells", &tags));
+    }
+    if chain_state.last_block_number != head.number {
+        out.issue(
+            String::new(),
+            format!(
+                "chain_state.last_block_number={} differs from head={}",
+                chain_state.last_block_number, head.number
+            ),
+        );
+    }
+    let (root_meta, tags) = capture_corrupt_tags(|| *state.state_root_meta.get());
+    if !tags.is_empty() {
+        out.issue(String::new(), decode_detail("state_root_meta", &tags));
+    }
+    if !root_meta.initialized {
+        out.issue(
+            String::new(),
+            "state_root_meta is not initialized".to_string(),
+        );
+    }
+    out.report.entries = 1;
+    out.head = Some((
+        head,
+        chain_state.last_block_number,
+        prune_state.pruned_before(),
+    ));
+    out.blob_usage = Some(state.blob_store.usage_stats());
+}
+
+fn inspect_blocks(state: &StableState, out: &mut SectionScan) {
+    let head = state.head.get().number;
+    for entry in state.blocks.iter() {
+        let number = *entry.key();
+        let ptr = entry.value();
+        out.report.entries += 1;
+        let raw = match state.blob_store.read_decoded(&ptr) {
+            Ok(raw) => raw,
+            Err(err) => {
+                out.issue(number.to_string(), blob_detail(&ptr, &err));
+                continue;
+            }
+        };
+        out.add_bytes(raw.len());
+        let (block, tags) = capture_corrupt_tags(|| BlockData::from_bytes(Cow::Owned(raw)));
+        if !tags.is_empty() {
+            out.issue(number.to_string(), decode_detail("block_data", &tags));
+        } else if block.number != number {
+            out.issue(
+                number.to_string(),
+                format!("stored block number {} differs from key", block.number),
+            );
+        }
+        if number > head {
+            out.issue(number.to_string(), format!("block is above head {head}"));
+        }
+    }
+}
+
+/// TxId→BlobPtr のmapを読み、blobとdecodeの両方を確認する。
+fn inspect_blob_map(
+    state: &StableState,
+    out: &mut SectionScan,
+    entries: impl Iterator<Item = (TxId, BlobPtr)>,
+    decode: impl Fn(Vec<u8>),
+) {
+    for (key, ptr) in entries {
+        out.report.entries += 1;
+        let raw = match state.blob_store.read_decoded(&ptr) {
+            Ok(raw) => raw,
+            Err(err) => {
+                out.issue(tx_key(&key), blob_detail(&ptr, &err));
+                continue;
+            }
+        };
+        out.add_bytes(raw.len());
+        let (_, tags) = capture_corrupt_tags(|| decode(raw));
+        if !tags.is_empty() {
+            out.issue(tx_key(&key), decode_detail(out.report.name, &tags));
+        }
+    }
+}
+
+fn inspect_tx_store(state: &StableState, out: &mut SectionScan) {
+    for entry in state.tx_store.iter() {
+        let key = *entry.key();
+        let (stored, tags) = capture_corrupt_tags(|| entry.value());
+        out.report.entries += 1;
+        out.add_bytes(stored.to_bytes().len());
+        if !tags.is_empty() {
+            out.issue(tx_key(&key), decode_detail("tx_store", &tags));
+            continue;
+        }
+        if let Err(err) = StoredTx::try_from(stored) {
+            out.issue(tx_key(&key), format!("stored tx rejected: {err:?}"));
+        }
+    }
+}
+
+fn inspect_queues(state: &StableState, out: &mut SectionScan) {
+    for entry in state.queue.iter() {
+        let tx_id = entry.value();
+        out.report.entries += 1;
+        if !state.tx_store.contains_key(&tx_id) {
+            out.issue(
+                format!("queue seq {}", entry.key()),
+                format!("queued tx {} has no payload", tx_key(&tx_id)),
+            );
+        }
+    }
+    for entry in state.ready_queue.iter() {
+        let tx_id = entry.value();
+        out.report.entries += 1;
+        if !state.tx_store.contains_key(&tx_id) {
+            out.issue(tx_key(&tx_id), "ready tx has no payload".to_string());
+        }
+    }
+    out.report.entries = out
+        .report
+        .entries
+        .saturating_add(state.pending_by_sender_nonce.len());
+    let pairs = [
+        (
+            "ready_queue/ready_key_by_tx_id",
+            state.ready_queue.len(),
+            state.ready_key_by_tx_id.len(),
+        ),
+        (
+            "pending_by_sender_nonce/pending_meta_by_tx_id",
+            state.pending_by_sender_nonce.len(),
+            state.pending_meta_by_tx_id.len(),
+        ),
+        (
+            "pending_fee_index/pending_fee_key_by_tx_id",
+            state.pending_fee_index.len(),
+            state.pending_fee_key_by_tx_id.len(),
+        ),
+        (
+            "pending_tip_index/pending_tip_key_by_tx_id",
+            state.pending_tip_index.len(),
+            state.pending_tip_key_by_tx_id.len(),
+        ),
+    ];
+    for (name, left, right) in pairs {
+        if left != right {
+            out.issue(
+                name.to_string(),
+                format!("index sizes differ: {left} != {right}"),
+            );
+        }
+    }
+}
+
+fn tx_key(tx_id: &TxId) -> String {
+    let mut out = String::with_capacity(2 + tx_id.0.len() * 2);
+    out.push_str("0x");
+    for byte in tx_id.0 {
+        out.push_str(&format!("{byte:02x}"));
+    }
+    out
+}
+
+fn blob_detail(ptr: &BlobPtr, err: &impl fmt::Debug) -> String {
+    format!(
+        "blob unreadable ({err:?}) offset={} len={} class={} gen={}",
+        ptr.offset(),
+        ptr.len(),
+        ptr.class(),
+        ptr.gen()
+    )
+}
+
+fn decode_detail(what: &str, tags: &[&'static [u8]]) -> String {
+    let tags = tags
+        .iter()
+        .map(|tag| String::from_utf8_lossy(tag).into_owned())
+        .collect::<Vec<_>>()
+        .join(",");
+    format!("{what} decode failed [{tags}]")
+}
+
+fn panic_message(panic: &Box<dyn std::any::Any + Send>) -> String {
+    if let Some(message) = panic.downcast_ref::<&str>() {
+        return (*message).to_string();
+    }
+    if let Some(message) = panic.downcast_ref::<String>() {
+        return message.clone();
+    }
+    "panic".to_string()
+}
+
+impl fmt::Display for InspectReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "image: {} bytes ({} pages)",
+            self.image_bytes,
+            self.image_bytes / WASM_PAGE_SIZE_BYTES
+        )?;
+        writeln!(
+            f,
+            "schema: version={} supported={} needs_migration={}",
+            self.meta.schema_version, self.meta.supported_schema_version, self.meta.needs_migration
+        )?;
+        for problem in &self.meta.header_problems {
+            writeln!(f, "  meta header mismatch: {problem}")?;
+        }
+        writeln!(
+            f,
+            "head: number={} timestamp={} last_block_number={} pruned_before={:?}",
+            self.head.number, self.head.timestamp, self.last_block_number, self.pruned_before_block
+        )?;
+        let tag_len = self
+            .last_corrupt_tag
+            .iter()
+            .position(|byte| *byte == 0)
+            .unwrap_or(self.last_corrupt_tag.len());
+        writeln!(
+            f,
+            "corrupt_log: count={} last_tag={}",
+            self.corrupt_count,
+            String::from_utf8_lossy(&self.last_corrupt_tag[..tag_len])
+        )?;
+        writeln!(
+            f,
+            "blob_store: arena_end={} used={} quarantine={} free={}",
+            self.blob_usage.arena_end_bytes,
+            self.blob_usage.used_class_bytes,
+            self.blob_usage.quarantine_class_bytes,
+            self.blob_usage.free_class_bytes
+        )?;
+        writeln!(f, "regions:")?;
+        for region in self.regions.iter().filter(|region| region.pages > 0) {
+            writeln!(
+                f,
+                "  {:>3} {:<32} {:>8} pages {:>12} bytes",
+                region.id,
+                region.name,
+                region.pages,
+                region.pages.saturating_mul(WASM_PAGE_SIZE_BYTES)
+            )?;
+        }
+        writeln!(f, "sections:")?;
+        for section in &self.sections {
+            writeln!(
+                f,
+                "  {:<20} entries={} bytes={} issues={}",
+                section.name, section.entries, section.bytes, section.issues
+            )?;
+        }
+        writeln!(f, "issues: {}", self.issue_total)?;
+        for issue in &self.issues {
+            writeln!(f, "  [{}] {} {}", issue.section, issue.key, issue.detail)?;
+        }
+        Ok(())
+    }
+}
@@ -1,10 +1,13 @@
 //! Phase0の土台（どこで: canister入口 / 何を: 初期化とupgradeフック / なぜ: Stable Memory凍結を守るため）
 
+pub mod blob_codec;
 pub mod blob_ptr;
 pub mod blob_store;
 pub mod chain_data;
 pub mod corrupt_log;
 pub mod decode;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod inspect;
 pub mod memory;
 pub mod meta;
 pub mod overlay;
@@ -85,6 +85,31 @@ pub enum AppMemoryId {
     IcpUpdateDispatchQueue = 71,
     IcpUpdateDispatchMeta = 72,
     IcpUpdatePrecompileAllowlist = 73,
+    ReadyTipIndex = 74,
+    ReadyTipKeyByTxId = 75,
+    ReadyFeeBoundaries = 76,
+    ReadyIndexState = 77,
+    StagedBlockMeta = 78,
+    StagedBlockItems = 79,
+    DropRecords = 80,
+    DropRecordsByEthHash = 81,
+    DropRecordSeq = 82,
+    DropRecordState = 83,
+    StateSnapshotImport = 84,
+    BlobCompactionState = 85,
+    BlobRecompressState = 86,
+    ArchiveState = 87,
+    ArchiveRanges = 88,
+    ScrubState = 89,
+    ScrubFindings = 90,
+    DepositAccounts = 91,
+    AssetLimits = 92,
+    AssetFeeSchedules = 93,
+    WrapAllowedNftCollections = 94,
+    ArchivedTxBlocks = 95,
+    PendingTipIndex = 96,
+    PendingTipKeyByTxId = 97,
+    ArchivedTxOrder = 98,
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
@@ -94,7 +119,7 @@ pub struct MemoryRegionInfo {
     pub include_in_estimate: bool,
 }
 
-const ALL_MEMORY_REGIONS: [MemoryRegionInfo; 74] = [
+const ALL_MEMORY_REGIONS: [MemoryRegionInfo; 99] = [
     MemoryRegionInfo {
         id: AppMemoryId::Upgrades,
         name: "Upgrades",
@@ -465,6 +490,131 @@ const ALL_MEMORY_REGIONS: [MemoryRegionInfo; 74] = [
         name: "IcpUpdatePrecompileAllowlist",
         include_in_estimate: false,
     },
+    MemoryRegionInfo {
+        id: AppMemoryId::ReadyTipIndex,
+        name: "ReadyTipIndex",
+        include_in_estimate: true,
+    },
+    MemoryRegionInfo {
+        id: AppMemoryId::ReadyTipKeyByTxId,
+        name: "ReadyTipKeyByTxId",
+        include_in_estimate: true,
+    },
+    MemoryRegionInfo {
+        id: AppMemoryId::ReadyFeeBoundaries,
+        name: "ReadyFeeBoundaries",
+        include_in_estimate: true,
+    },
+    MemoryRegionInfo {
+        id: AppMemoryId::ReadyIndexState,
+        name: "ReadyIndexState",
+        include_in_estimate: false,
+    },
+    MemoryRegionInfo {
+        id: AppMemoryId::StagedBlockMeta,
+        name: "StagedBlockMeta",
+        include_in_estimate: false,
+    },
+    MemoryRegionInfo {
+        id: AppMemoryId::StagedBlockItems,
+        name: "StagedBlockItems",
+        include_in_estimate: true,
+    },
+    MemoryRegionInfo {
+        id: AppMemoryId::DropRecords,
+        name: "DropRecords",
+        include_in_estimate: true,
+    },
+    MemoryRegionInfo {
+        id: AppMemoryId::DropRecordsByEthHash,
+        name: "DropRecordsByEthHash",
+        include_in_estimate: true,
+    },
+    MemoryRegionInfo {
+        id: AppMemoryId::DropRecordSeq,
+        name: "DropRecordSeq",
+        include_in_estimate: true,
+    },
+    MemoryRegionInfo {
+        id: AppMemoryId::DropRecordState,
+        name: "DropRecordState",
+        include_in_estimate: false,
+    },
+    MemoryRegionInfo {
+        id: AppMemoryId::StateSnapshotImport,
+        name: "StateSnapshotImport",
+        include_in_estimate: false,
+    },
+    MemoryRegionInfo {
+        id: AppMemoryId::BlobCompactionState,
+        name: "BlobCompactionState",
+        include_in_estimate: false,
+    },
+    MemoryRegionInfo {
+        id: AppMemoryId::BlobRecompressState,
+        name: "BlobRecompressState",
+        include_in_estimate: false,
+    },
+    MemoryRegionInfo {
+        id: AppMemoryId::ArchiveState,
+        name: "ArchiveState",
+        include_in_estimate: false,
+    },
+    MemoryRegionInfo {
+        id: AppMemoryId::ArchiveRanges,
+        name: "ArchiveRanges",
+        include_in_estimate: false,
+    },
+    MemoryRegionInfo {
+        id: AppMemoryId::ScrubState,
+        name: "ScrubState",
+        include_in_estimate: false,
+    },
+    MemoryRegionInfo {
+        id: AppMemoryId::ScrubFindings,
+        name: "ScrubFindings",
+        include_in_estimate: false,
+    },
+    MemoryRegionInfo {
+        id: AppMemoryId::DepositAccounts,
+        name: "DepositAccounts",
+        include_in_estimate: false,
+    },
+    MemoryRegionInfo {
+        id: AppMemoryId::AssetLimits,
+        name: "AssetLimits",
+        include_in_estimate: false,
+    },
+    MemoryRegionInfo {
+        id: AppMemoryId::AssetFeeSchedules,
+        name: "AssetFeeSchedules",
+        include_in_estimate: false,
+    },
+    MemoryRegionInfo {
+        id: AppMemoryId::WrapAllowedNftCollections,
+        name: "WrapAllowedNftCollections",
+        include_in_estimate: false,
+    },
+    MemoryRegionInfo {
+        id: AppMemoryId::ArchivedTxBlocks,
+        name: "ArchivedTxBlocks",
+        include_in_estimate: true,
+    },
+    MemoryRegionInfo {
+        id: AppMemoryId::PendingTipIndex,
+        name: "PendingTipIndex",
+        include_in_estimate: true,
+    },
+    MemoryRegionInfo {
+        id: AppMemoryId::PendingTipKeyByTxId,
+        name: "PendingTipKeyByTxId",
+        include_in_estimate: true,
+    },
+    MemoryRegionInfo {
+        id: AppMemoryId::ArchivedTxOrder,
+        name: "ArchivedTxOrder",
+        include_in_estimate: true,
+    },
 ];
 
 impl AppMemoryId {
@@ -544,6 +694,31 @@ impl AppMemoryId {
             AppMemoryId::IcpUpdateDispatchQueue => 71,
             AppMemoryId::IcpUpdateDispatchMeta => 72,
             AppMemoryId::IcpUpdatePrecompileAllowlist => 73,
+            AppMemoryId::ReadyTipIndex => 74,
+            AppMemoryId::ReadyTipKeyByTxId => 75,
+            AppMemoryId::ReadyFeeBoundaries => 76,
+            AppMemoryId::ReadyIndexState => 77,
+            AppMemoryId::StagedBlockMeta => 78,
+            AppMemoryId::StagedBlockItems => 79,
+            AppMemoryId::DropRecords => 80,
+            AppMemoryId::DropRecordsByEthHash => 81,
+            AppMemoryId::DropRecordSeq => 82,
+            AppMemoryId::DropRecordState => 83,
+            AppMemoryId::StateSnapshotImport => 84,
+            AppMemoryId::BlobCompactionState => 85,
+            AppMemoryId::BlobRecompressState => 86,
+            AppMemoryId::ArchiveState => 87,
+            AppMemoryId::ArchiveRanges => 88,
+            AppMemoryId::ScrubState => 89,
+            AppMemoryId::ScrubFindings => 90,
+            AppMemoryId::DepositAccounts => 91,
+            AppMemoryId::AssetLimits => 92,
+            AppMemoryId::AssetFeeSchedules => 93,
+            AppMemoryId::WrapAllowedNftCollections => 94,
+            AppMemoryId::ArchivedTxBlocks => 95,
+            AppMemoryId::PendingTipIndex => 96,
+            AppMemoryId::PendingTipKeyByTxId => 97,
+            AppMemoryId::ArchivedTxOrder => 98,
         }
     }
 
@@ -579,6 +754,27 @@ thread_local! {
         RefCell::new(MemoryManager::init(DefaultMemoryImpl::default()));
 }
 
+// ホスト側ツールが読み込んだイメージ。wasmでは使わない。
+#[cfg(not(target_arch = "wasm32"))]
+thread_local! {
+    static HOST_IMAGE: RefCell<Option<DefaultMemoryImpl>> = const { RefCell::new(None) };
+}
+
+/// ホスト側専用: 現在threadのMemoryManagerを `image` の上に作り直す。
+/// 以降の `init_stable_state` はこのイメージのレイアウトを読む。
+#[cfg(not(target_arch = "wasm32"))]
+pub fn load_memory_image(image: Vec<u8>) {
+    let memory: DefaultMemoryImpl = std::rc::Rc::new(RefCell::new(image));
+    HOST_IMAGE.with(|slot| *slot.borrow_mut() = Some(memory.clone()));
+    MEMORY_MANAGER.with(|m| *m.borrow_mut() = MemoryManager::init(memory));
+}
+
+/// ホスト側専用: `load_memory_image` 以降の書き込みを含むイメージの複製を返す。
+#[cfg(not(target_arch = "wasm32"))]
+pub fn memory_image() -> Option<Vec<u8>> {
+    HOST_IMAGE.with(|slot| slot.borrow().as_ref().map(|memory| memory.borrow().clone()))
+}
+
 pub fn get_memory(id: AppMemoryId) -> VMem {
     MEMORY_MANAGER.with(|m| m.borrow().get(id.as_memory_id()))
 }
@@ -47,6 +47,17 @@ impl SchemaMigrationPhase {
             _ => Self::Error,
         }
     }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Init => "init",
+            Self::Scan => "scan",
+            Self::Rewrite => "rewrite",
+            Self::Verify => "verify",
+            Self::Done => "done",
+            Self::Error => "error",
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
@@ -58,6 +69,8 @@ pub struct SchemaMigrationState {
     pub last_error: u32,
     pub cursor_key_set: bool,
     pub cursor_key: [u8; 32],
+    /// migration registry 上の位置。Scan はここから次に該当する migration を探す。
+    pub migration_index: u8,
 }
 
 #[derive(
@@ -67,7 +80,8 @@ pub struct SchemaMigrationState {
 struct SchemaMigrationWire {
     phase: u8,
     cursor_key_set: u8,
-    _pad0: [u8; 6],
+    migration_index: u8,
+    _pad0: [u8; 5],
     cursor: U64,
     from_version: U32,
     to_version: U32,
@@ -81,7 +95,8 @@ impl SchemaMigrationWire {
         Self {
             phase: state.phase as u8,
             cursor_key_set: u8::from(state.cursor_key_set),
-            _pad0: [0u8; 6],
+            migration_index: state.migration_index,
+            _pad0: [0u8; 5],
             cursor: U64::new(state.cursor),
             from_version: U32::new(state.from_version),
             to_version: U32::new(state.to_version),
@@ -102,6 +117,7 @@ impl SchemaMigrationState {
             last_error: 0,
             cursor_key_set: false,
             cursor_key: [0u8; 32],
+            migration_index: 0,
         }
     }
 }
@@ -138,6 +154,7 @@ impl Storable for SchemaMigrationState {
                 last_error: wire.last_error.get(),
                 cursor_key_set: wire.cursor_key_set != 0,
                 cursor_key: wire.cursor_key,
+                migration_index: wire.migration_index,
             };
         }
         let mut cursor = [0u8; 8];
@@ -163,6 +180,7 @@ impl Storable for SchemaMigrationState {
             last_error: u32::from_be_bytes(last_error),
             cursor_key_set,
             cursor_key,
+            migration_index: 0,
         }
     }
 
@@ -355,6 +373,26 @@ pub fn ensure_meta_initialized() -> Meta {
     meta
 }
 
+/// 修復せずに保存済みのMetaを読み、ヘッダの不一致をタグで返す（オフライン検査用）。
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn read_meta_unrepaired() -> (Meta, Vec<&'static str>) {
+    let meta = *init_meta_cell().get();
+    let mut problems = Vec::new();
+    if meta.magic != META_MAGIC {
+        problems.push("meta_magic");
+    }
+    if meta.layout_version != META_LAYOUT_VERSION {
+        problems.push("meta_layout");
+    }
+    if meta.schema_hash != META_SCHEMA_HASH {
+        problems.push("meta_schema_hash");
+    }
+    if meta.schema_version == 0 {
+        problems.push("meta_schema_version");
+    }
+    (meta, problems)
+}
+
 pub fn get_meta() -> Meta {
     ensure_meta_initialized()
 }
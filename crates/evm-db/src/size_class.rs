@@ -17,7 +17,7 @@ pub const CLASS_1M: u32 = 1024 * 1024;
 pub const CLASS_2M: u32 = 2 * 1024 * 1024;
 pub const CLASS_4M: u32 = 4 * 1024 * 1024;
 
-const CLASSES: [u32; 10] = [
+pub(crate) const CLASSES: [u32; 10] = [
     CLASS_8K, CLASS_16K, CLASS_32K, CLASS_64K, CLASS_128K, CLASS_256K, CLASS_512K, CLASS_1M,
     CLASS_2M, CLASS_4M,
 ];
@@ -4,12 +4,16 @@ use crate::blob_ptr::BlobPtr;
 use crate::blob_store::BlobStore;
 use crate::chain_data::constants::CHAIN_ID;
 use crate::chain_data::{
-    CallerKey, ChainStateV1, DroppedRingStateV1, FeePolicyStored, GcStateV1, HashKey, Head,
+    ArchiveRangeV1, ArchiveStateV1, ArchivedTxKey, AssetFeeScheduleStored, AssetLimitsV1,
+    BlobCompactionStateV1, BlobRecompressStateV1, CallerKey, ChainStateV1, DepositAccountV1,
+    DropRecordStateV1, DropRecordV1, DroppedRingStateV1, FeePolicyStored, GcStateV1, HashKey, Head,
     IcpUpdateDispatchRequest, LogConfigV1, MetricsStateV1, MigrationStateV1, MismatchRecordV1,
     NativeCreditRecord, NodeRecord, OpsConfigV1, OpsMetricsV1, OpsStateV1, PendingFeeKey,
-    PruneConfigV1, PruneJournal, PruneStateV1, QueueMeta, ReadyKey, ReadySeqKey, RuntimeConfigV1,
-    SenderKey, SenderNonceKey, StateRootMetaV1, StateRootMetricsV1, StoredTxBytes, TxId,
-    UnwrapDispatchRequest, WrapEvmConfigStored, WrapPendingSubmission, WrapStoredRequest,
+    PruneConfigV1, PruneJournal, PruneStateV1, QueueMeta, ReadyFeeBoundaryKey, ReadyIndexStateV1,
+    ReadyKey, ReadySeqKey, ReadyTipKey, RuntimeConfigV1, ScrubFindingV1, ScrubStateV1, SenderKey,
+    SenderNonceKey, StagedBlockMetaV1, StateRootMetaV1, StateRootMetricsV1, StateSnapshotImportV1,
+    StoredTxBytes, TxId, UnwrapDispatchRequest, WrapEvmConfigStored, WrapPendingSubmission,
+    WrapStoredRequest,
 };
 use crate::memory::{get_memory, AppMemoryId, VMem};
 use crate::types::keys::{AccountKey, CodeKey, StorageKey};
@@ -39,6 +43,8 @@ pub type PendingCurrentBySender = StableBTreeMap<SenderKey, TxId, VMem>;
 pub type PrincipalPendingCount = StableBTreeMap<CallerKey, u32, VMem>;
 pub type PendingFeeIndex = StableBTreeMap<PendingFeeKey, TxId, VMem>;
 pub type PendingFeeKeyByTxId = StableBTreeMap<TxId, PendingFeeKey, VMem>;
+pub type PendingTipIndex = StableBTreeMap<PendingFeeKey, TxId, VMem>;
+pub type PendingTipKeyByTxId = StableBTreeMap<TxId, PendingFeeKey, VMem>;
 pub type ReadyBySeq = StableBTreeMap<ReadySeqKey, TxId, VMem>;
 pub type EthTxHashIndex = StableBTreeMap<TxId, TxId, VMem>;
 pub type UnwrapRequests = StableBTreeMap<TxId, UnwrapDispatchRequest, VMem>;
@@ -59,6 +65,22 @@ pub type StateRootNodeDb = StableBTreeMap<HashKey, NodeRecord, VMem>;
 pub type StateRootAccountLeafHash = StableBTreeMap<AccountKey, HashKey, VMem>;
 pub type StateRootGcQueue = StableBTreeMap<u64, HashKey, VMem>;
 pub type NativeCreditRecords = StableBTreeMap<TxId, NativeCreditRecord, VMem>;
+pub type ReadyTipIndex = StableBTreeMap<ReadyTipKey, TxId, VMem>;
+pub type ReadyTipKeyByTxId = StableBTreeMap<TxId, ReadyTipKey, VMem>;
+pub type ReadyFeeBoundaries = StableBTreeMap<ReadyFeeBoundaryKey, ReadyKey, VMem>;
+pub type StagedBlockItems = StableBTreeMap<u32, BlobPtr, VMem>;
+pub type DropRecords = StableBTreeMap<TxId, DropRecordV1, VMem>;
+pub type DropRecordsByEthHash = StableBTreeMap<TxId, TxId, VMem>;
+pub type DropRecordSeq = StableBTreeMap<u64, TxId, VMem>;
+pub type ArchiveRanges = StableBTreeMap<u64, ArchiveRangeV1, VMem>;
+pub type ScrubFindings = StableBTreeMap<u64, ScrubFindingV1, VMem>;
+pub type DepositAccounts = StableBTreeMap<TxId, DepositAccountV1, VMem>;
+pub type AssetLimits = StableBTreeMap<Vec<u8>, AssetLimitsV1, VMem>;
+pub type AssetFeeSchedules = StableBTreeMap<Vec<u8>, AssetFeeScheduleStored, VMem>;
+pub type WrapAllowedNftCollections = StableBTreeMap<Vec<u8>, u8, VMem>;
+/// prune 時に archive 済み block へ移った tx の block 番号。
+pub type ArchivedTxBlocks = StableBTreeMap<TxId, u64, VMem>;
+pub type ArchivedTxOrder = StableBTreeMap<ArchivedTxKey, u8, VMem>;
 
 pub struct StableState {
     pub accounts: Accounts,
@@ -96,6 +118,8 @@ pub struct StableState {
     pub principal_pending_count: PrincipalPendingCount,
     pub pending_fee_index: PendingFeeIndex,
     pub pending_fee_key_by_tx_id: PendingFeeKeyByTxId,
+    pub pending_tip_index: PendingTipIndex,
+    pub pending_tip_key_by_tx_id: PendingTipKeyByTxId,
     pub ready_by_seq: ReadyBySeq,
     pub eth_tx_hash_index: EthTxHashIndex,
     pub unwrap_requests: UnwrapRequests,
@@ -128,6 +152,29 @@ pub struct StableState {
     pub state_root_gc_state: StableCell<GcStateV1, VMem>,
     pub native_credit_records: NativeCreditRecords,
     pub evm_state_epoch: StableCell<u64, VMem>,
+    pub ready_tip_index: ReadyTipIndex,
+    pub ready_tip_key_by_tx_id: ReadyTipKeyByTxId,
+    pub ready_fee_boundaries: ReadyFeeBoundaries,
+    pub ready_index_state: StableCell<ReadyIndexStateV1, VMem>,
+    pub staged_block_meta: StableCell<StagedBlockMetaV1, VMem>,
+    pub staged_block_items: StagedBlockItems,
+    pub drop_records: DropRecords,
+    pub drop_records_by_eth_hash: DropRecordsByEthHash,
+    pub drop_record_seq: DropRecordSeq,
+    pub drop_record_state: StableCell<DropRecordStateV1, VMem>,
+    pub state_snapshot_import: StableCell<StateSnapshotImportV1, VMem>,
+    pub blob_compaction_state: StableCell<BlobCompactionStateV1, VMem>,
+    pub blob_recompress_state: StableCell<BlobRecompressStateV1, VMem>,
+    pub archive_state: StableCell<ArchiveStateV1, VMem>,
+    pub archive_ranges: ArchiveRanges,
+    pub scrub_state: StableCell<ScrubStateV1, VMem>,
+    pub scrub_findings: ScrubFindings,
+    pub deposit_accounts: DepositAccounts,
+    pub asset_limits: AssetLimits,
+    pub asset_fee_schedules: AssetFeeSchedules,
+    pub wrap_allowed_nft_collections: WrapAllowedNftCollections,
+    pub archived_tx_blocks: ArchivedTxBlocks,
+    pub archived_tx_order: ArchivedTxOrder,
 }
 
 thread_local! {
@@ -144,6 +191,20 @@ pub fn clear_map<K: Copy + Ord + Storable, V: Storable>(map: &mut StableBTreeMap
     }
 }
 
+/// どこで: stable_state共通ユーティリティ
+/// 何を: StableBTreeMapの先頭から最大 `max` 件を削除し、削除件数を返す
+/// なぜ: 大きなmapを1メッセージで消さず、呼び出しをまたいで少しずつ空にするため
+pub fn remove_first_entries<K: Ord + Storable + Clone, V: Storable>(
+    map: &mut StableBTreeMap<K, V, VMem>,
+    max: u64,
+) -> u64 {
+    let mut removed = 0u64;
+    while removed < max && map.pop_first().is_some() {
+        removed += 1;
+    }
+    removed
+}
+
 pub fn init_stable_state() {
     let accounts = StableBTreeMap::init(get_memory(AppMemoryId::Accounts));
     let storage = StableBTreeMap::init(get_memory(AppMemoryId::Storage));
@@ -199,6 +260,9 @@ pub fn init_stable_state() {
     let pending_fee_index = StableBTreeMap::init(get_memory(AppMemoryId::PendingFeeIndex));
     let pending_fee_key_by_tx_id =
         StableBTreeMap::init(get_memory(AppMemoryId::PendingFeeKeyByTxId));
+    let pending_tip_index = StableBTreeMap::init(get_memory(AppMemoryId::PendingTipIndex));
+    let pending_tip_key_by_tx_id =
+        StableBTreeMap::init(get_memory(AppMemoryId::PendingTipKeyByTxId));
     let ready_by_seq = StableBTreeMap::init(get_memory(AppMemoryId::ReadyBySeq));
     let eth_tx_hash_index = StableBTreeMap::init(get_memory(AppMemoryId::EthTxHashIndex));
     let unwrap_requests = StableBTreeMap::init(get_memory(AppMemoryId::UnwrapRequests));
@@ -274,6 +338,50 @@ pub fn init_stable_state() {
         StableCell::init(get_memory(AppMemoryId::StateRootGcState), GcStateV1::new());
     let native_credit_records = StableBTreeMap::init(get_memory(AppMemoryId::NativeCreditRecords));
     let evm_state_epoch = StableCell::init(get_memory(AppMemoryId::EvmStateEpoch), 0u64);
+    let ready_tip_index = StableBTreeMap::init(get_memory(AppMemoryId::ReadyTipIndex));
+    let ready_tip_key_by_tx_id = StableBTreeMap::init(get_memory(AppMemoryId::ReadyTipKeyByTxId));
+    let ready_fee_boundaries = StableBTreeMap::init(get_memory(AppMemoryId::ReadyFeeBoundaries));
+    let ready_index_state = StableCell::init(
+        get_memory(AppMemoryId::ReadyIndexState),
+        ReadyIndexStateV1::new(),
+    );
+    let staged_block_meta = StableCell::init(
+        get_memory(AppMemoryId::StagedBlockMeta),
+        StagedBlockMetaV1::new(),
+    );
+    let staged_block_items = StableBTreeMap::init(get_memory(AppMemoryId::StagedBlockItems));
+    let drop_records = StableBTreeMap::init(get_memory(AppMemoryId::DropRecords));
+    let drop_records_by_eth_hash =
+        StableBTreeMap::init(get_memory(AppMemoryId::DropRecordsByEthHash));
+    let drop_record_seq = StableBTreeMap::init(get_memory(AppMemoryId::DropRecordSeq));
+    let drop_record_state = StableCell::init(
+        get_memory(AppMemoryId::DropRecordState),
+        DropRecordStateV1::new(),
+    );
+    let state_snapshot_import = StableCell::init(
+        get_memory(AppMemoryId::StateSnapshotImport),
+        StateSnapshotImportV1::new(),
+    );
+    let blob_compaction_state = StableCell::init(
+        get_memory(AppMemoryId::BlobCompactionState),
+        BlobCompactionStateV1::new(),
+    );
+    let blob_recompress_state = StableCell::init(
+        get_memory(AppMemoryId::BlobRecompressState),
+        BlobRecompressStateV1::new(),
+    );
+    let archive_state =
+        StableCell::init(get_memory(AppMemoryId::ArchiveState), ArchiveStateV1::new());
+    let archive_ranges = StableBTreeMap::init(get_memory(AppMemoryId::ArchiveRanges));
+    let scrub_state = StableCell::init(get_memory(AppMemoryId::ScrubState), ScrubStateV1::new());
+    let scrub_findings = StableBTreeMap::init(get_memory(AppMemoryId::ScrubFindings));
+    let deposit_accounts = StableBTreeMap::init(get_memory(AppMemoryId::DepositAccounts));
+    let asset_limits = StableBTreeMap::init(get_memory(AppMemoryId::AssetLimits));
+    let asset_fee_schedules = StableBTreeMap::init(get_memory(AppMemoryId::AssetFeeSchedules));
+    let wrap_allowed_nft_collections =
+        StableBTreeMap::init(get_memory(AppMemoryId::WrapAllowedNftCollections));
+    let archived_tx_blocks = StableBTreeMap::init(get_memory(AppMemoryId::ArchivedTxBlocks));
+    let archived_tx_order = StableBTreeMap::init(get_memory(AppMemoryId::ArchivedTxOrder));
     STABLE_STATE.with(|s| {
         *s.borrow_mut() = Some(StableState {
             accounts,
@@ -311,6 +419,8 @@ pub fn init_stable_state() {
             principal_pending_count,
             pending_fee_index,
             pending_fee_key_by_tx_id,
+            pending_tip_index,
+            pending_tip_key_by_tx_id,
             ready_by_seq,
             eth_tx_hash_index,
             unwrap_requests,
@@ -343,6 +453,29 @@ pub fn init_stable_state() {
             state_root_gc_state,
             native_credit_records,
             evm_state_epoch,
+            ready_tip_index,
+            ready_tip_key_by_tx_id,
+            ready_fee_boundaries,
+            ready_index_state,
+            staged_block_meta,
+            staged_block_items,
+            drop_records,
+            drop_records_by_eth_hash,
+            drop_record_seq,
+            drop_record_state,
+            state_snapshot_import,
+            blob_compaction_state,
+            blob_recompress_state,
+            archive_state,
+            archive_ranges,
+            scrub_state,
+            scrub_findings,
+            deposit_accounts,
+            asset_limits,
+            asset_fee_schedules,
+            wrap_allowed_nft_collections,
+            archived_tx_blocks,
+            archived_tx_order,
         });
     });
 }
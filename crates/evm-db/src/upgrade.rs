@@ -1,48 +1,260 @@
-//! どこで: UPGRADES領域 / 何を: pre/post upgradeの最小退避 / なぜ: Phase0の基本運用
+//! どこで: UPGRADES領域 / 何を: 版付き upgrade checkpoint の退避と検証 / なぜ: heap 側の状態を upgrade 越しに引き継ぎ、互換の無い stable layout への downgrade を拒否するため
 
-use crate::memory::{get_memory, AppMemoryId, VMem};
+use crate::chain_data::constants::MAX_PRINCIPAL_LEN;
+use crate::memory::{get_memory, AppMemoryId, VMem, WASM_PAGE_SIZE_BYTES};
 use ic_stable_structures::reader::Reader;
 use ic_stable_structures::writer::Writer;
 use ic_stable_structures::Memory;
-use tracing::warn;
+use verified_core::schema_migration::upgrade_checkpoint_accepted;
 
-const UPGRADE_STATE_VERSION: u32 = 1;
+/// 1: version のみ。2: schema 版と volatile state を続けて書く。
+pub const UPGRADE_STATE_VERSION: u32 = 2;
+const LEGACY_UPGRADE_STATE_VERSION: u32 = 1;
+const HEADER_LEN: usize = 12;
+const PRECOMPILE_PROFILE_ENTRY_LEN: usize = 20 + 8 + 16 + 8 + 16 + 8;
 
-pub fn pre_upgrade() {
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct PrecompileProfileCheckpoint {
+    pub address: [u8; 20],
+    pub calls: u64,
+    pub total_instructions: u128,
+    pub max_instructions: u64,
+    pub total_extra_gas: u128,
+    pub max_extra_gas: u64,
+}
+
+/// pre_upgrade 時点で heap にしか無い状態。timer 実体は引き継げないため、予約済みだった worker を記録して再登録に使う。
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct UpgradeCheckpoint {
+    /// 退避時点の stable layout の schema 版
+    pub schema_version: u32,
+    pub caller_evm: Vec<(Vec<u8>, [u8; 20])>,
+    pub decode_suppress_until: Vec<(Vec<u8>, u64)>,
+    pub precompile_profile: Vec<PrecompileProfileCheckpoint>,
+    pub unwrap_dispatch_scheduled: bool,
+    pub icp_update_dispatch_scheduled: bool,
+    pub wrap_worker_scheduled: bool,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum UpgradeError {
+    /// 前の wasm が版を書いていない
+    MissingVersion,
+    UnknownVersion(u32),
+    /// 前の wasm の stable layout がこの wasm の対応版より新しい
+    SchemaDowngrade {
+        stored: u32,
+        supported: u32,
+    },
+    CorruptCheckpoint,
+}
+
+impl core::fmt::Display for UpgradeError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            UpgradeError::MissingVersion => write!(f, "upgrade.state_version.missing"),
+            UpgradeError::UnknownVersion(version) => {
+                write!(f, "upgrade.state_version.unknown: {version}")
+            }
+            UpgradeError::SchemaDowngrade { stored, supported } => write!(
+                f,
+                "upgrade.schema.downgrade: stored {stored}, supported {supported}"
+            ),
+            UpgradeError::CorruptCheckpoint => write!(f, "upgrade.checkpoint.corrupt"),
+        }
+    }
+}
+
+pub fn pre_upgrade(checkpoint: &UpgradeCheckpoint) {
+    let payload = encode_payload(checkpoint);
+    let payload_len = u32::try_from(payload.len())
+        .unwrap_or_else(|_| panic!("upgrade: checkpoint payload too large"));
+    let mut bytes = Vec::with_capacity(HEADER_LEN + payload.len());
+    bytes.extend_from_slice(&UPGRADE_STATE_VERSION.to_le_bytes());
+    bytes.extend_from_slice(&checkpoint.schema_version.to_le_bytes());
+    bytes.extend_from_slice(&payload_len.to_le_bytes());
+    bytes.extend_from_slice(&payload);
     let mut memory: VMem = get_memory(AppMemoryId::Upgrades);
     let mut writer = Writer::new(&mut memory, 0);
-    let version_bytes = UPGRADE_STATE_VERSION.to_le_bytes();
-    if writer.write(&version_bytes).is_err() {
-        panic!("upgrade: failed to persist state version");
+    if writer.write(&bytes).is_err() {
+        panic!("upgrade: failed to persist upgrade checkpoint");
     }
 }
 
-pub fn post_upgrade() {
+/// 版 1 の wasm からの upgrade は引き継ぐ状態が無いので None。
+/// 読めない版や schema の downgrade は Err にし、呼び出し側で upgrade ごと失敗させる。
+pub fn post_upgrade(supported_schema: u32) -> Result<Option<UpgradeCheckpoint>, UpgradeError> {
     let memory: VMem = get_memory(AppMemoryId::Upgrades);
-    if let Some(version) = read_version(&memory) {
-        if version != UPGRADE_STATE_VERSION {
-            warn!(
-                "upgrade: version mismatch detected (found {}, expected {})",
-                version, UPGRADE_STATE_VERSION
-            );
-        }
-    } else {
-        warn!("upgrade: no persisted state version");
+    let version = read_u32(&memory, 0).ok_or(UpgradeError::MissingVersion)?;
+    if version == LEGACY_UPGRADE_STATE_VERSION {
+        return Ok(None);
+    }
+    if version != UPGRADE_STATE_VERSION {
+        return Err(UpgradeError::UnknownVersion(version));
+    }
+    let stored_schema = read_u32(&memory, 4).ok_or(UpgradeError::CorruptCheckpoint)?;
+    if !upgrade_checkpoint_accepted(
+        version,
+        stored_schema,
+        UPGRADE_STATE_VERSION,
+        supported_schema,
+    ) {
+        return Err(UpgradeError::SchemaDowngrade {
+            stored: stored_schema,
+            supported: supported_schema,
+        });
+    }
+    let payload_len = read_u32(&memory, 8).ok_or(UpgradeError::CorruptCheckpoint)?;
+    let capacity = memory
+        .size()
+        .saturating_mul(WASM_PAGE_SIZE_BYTES)
+        .saturating_sub(HEADER_LEN as u64);
+    if u64::from(payload_len) > capacity {
+        return Err(UpgradeError::CorruptCheckpoint);
     }
+    let payload_len = usize::try_from(payload_len).map_err(|_| UpgradeError::CorruptCheckpoint)?;
+    let mut payload = vec![0u8; payload_len];
+    if read_exact(&memory, HEADER_LEN as u64, &mut payload).is_none() {
+        return Err(UpgradeError::CorruptCheckpoint);
+    }
+    let mut checkpoint = decode_payload(&payload).ok_or(UpgradeError::CorruptCheckpoint)?;
+    checkpoint.schema_version = stored_schema;
+    Ok(Some(checkpoint))
 }
 
-fn read_version(memory: &VMem) -> Option<u32> {
-    if memory.size() == 0 {
-        return None;
+fn encode_payload(checkpoint: &UpgradeCheckpoint) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.push(u8::from(checkpoint.unwrap_dispatch_scheduled));
+    out.push(u8::from(checkpoint.icp_update_dispatch_scheduled));
+    out.push(u8::from(checkpoint.wrap_worker_scheduled));
+    let caller_evm: Vec<_> = checkpoint
+        .caller_evm
+        .iter()
+        .filter(|(principal, _)| principal.len() <= MAX_PRINCIPAL_LEN)
+        .collect();
+    push_len(&mut out, caller_evm.len());
+    for (principal, address) in caller_evm {
+        push_principal(&mut out, principal);
+        out.extend_from_slice(address);
     }
-    let mut reader = Reader::new(memory, 0);
-    let mut buf = [0u8; 4];
-    let read = match reader.read(&mut buf) {
-        Ok(value) => value,
-        Err(_) => return None,
+    let suppress: Vec<_> = checkpoint
+        .decode_suppress_until
+        .iter()
+        .filter(|(principal, _)| principal.len() <= MAX_PRINCIPAL_LEN)
+        .collect();
+    push_len(&mut out, suppress.len());
+    for (principal, until) in suppress {
+        push_principal(&mut out, principal);
+        out.extend_from_slice(&until.to_le_bytes());
+    }
+    push_len(&mut out, checkpoint.precompile_profile.len());
+    for entry in checkpoint.precompile_profile.iter() {
+        out.extend_from_slice(&entry.address);
+        out.extend_from_slice(&entry.calls.to_le_bytes());
+        out.extend_from_slice(&entry.total_instructions.to_le_bytes());
+        out.extend_from_slice(&entry.max_instructions.to_le_bytes());
+        out.extend_from_slice(&entry.total_extra_gas.to_le_bytes());
+        out.extend_from_slice(&entry.max_extra_gas.to_le_bytes());
+    }
+    out
+}
+
+fn decode_payload(bytes: &[u8]) -> Option<UpgradeCheckpoint> {
+    let mut cursor = PayloadCursor { bytes, offset: 0 };
+    let mut checkpoint = UpgradeCheckpoint {
+        unwrap_dispatch_scheduled: cursor.take(1)?[0] != 0,
+        icp_update_dispatch_scheduled: cursor.take(1)?[0] != 0,
+        wrap_worker_scheduled: cursor.take(1)?[0] != 0,
+        ..UpgradeCheckpoint::default()
     };
-    if read != 4 {
+    for _ in 0..cursor.u32()? {
+        let principal = cursor.principal()?;
+        let address = cursor.array::<20>()?;
+        checkpoint.caller_evm.push((principal, address));
+    }
+    for _ in 0..cursor.u32()? {
+        let principal = cursor.principal()?;
+        let until = u64::from_le_bytes(cursor.array::<8>()?);
+        checkpoint.decode_suppress_until.push((principal, until));
+    }
+    let profile_len = usize::try_from(cursor.u32()?).ok()?;
+    if profile_len.checked_mul(PRECOMPILE_PROFILE_ENTRY_LEN)? > cursor.remaining() {
+        return None;
+    }
+    for _ in 0..profile_len {
+        checkpoint
+            .precompile_profile
+            .push(PrecompileProfileCheckpoint {
+                address: cursor.array::<20>()?,
+                calls: u64::from_le_bytes(cursor.array::<8>()?),
+                total_instructions: u128::from_le_bytes(cursor.array::<16>()?),
+                max_instructions: u64::from_le_bytes(cursor.array::<8>()?),
+                total_extra_gas: u128::from_le_bytes(cursor.array::<16>()?),
+                max_extra_gas: u64::from_le_bytes(cursor.array::<8>()?),
+            });
+    }
+    if cursor.remaining() != 0 {
         return None;
     }
+    Some(checkpoint)
+}
+
+fn push_len(out: &mut Vec<u8>, len: usize) {
+    let len = u32::try_from(len).unwrap_or(u32::MAX);
+    out.extend_from_slice(&len.to_le_bytes());
+}
+
+fn push_principal(out: &mut Vec<u8>, principal: &[u8]) {
+    // MAX_PRINCIPAL_LEN 以下に絞ってから呼ぶ。
+    out.push(u8::try_from(principal.len()).unwrap_or(u8::MAX));
+    out.extend_from_slice(principal);
+}
+
+struct PayloadCursor<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> PayloadCursor<'a> {
+    fn remaining(&self) -> usize {
+        self.bytes.len().saturating_sub(self.offset)
+    }
+
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        let end = self.offset.checked_add(len)?;
+        let out = self.bytes.get(self.offset..end)?;
+        self.offset = end;
+        Some(out)
+    }
+
+    fn array<const N: usize>(&mut self) -> Option<[u8; N]> {
+        self.take(N)?.try_into().ok()
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.array::<4>()?))
+    }
+
+    fn principal(&mut self) -> Option<Vec<u8>> {
+        let len = usize::from(self.take(1)?[0]);
+        if len > MAX_PRINCIPAL_LEN {
+            return None;
+        }
+        Some(self.take(len)?.to_vec())
+    }
+}
+
+fn read_u32(memory: &VMem, offset: u64) -> Option<u32> {
+    let mut buf = [0u8; 4];
+    read_exact(memory, offset, &mut buf)?;
     Some(u32::from_le_bytes(buf))
 }
+
+fn read_exact(memory: &VMem, offset: u64, buf: &mut [u8]) -> Option<()> {
+    if memory.size() == 0 {
+        return None;
+    }
+    let mut reader = Reader::new(memory, offset);
+    let read = reader.read(buf).ok()?;
+    (read == buf.len()).then_some(())
+}
@@ -1,6 +1,7 @@
 //! どこで: evm-db のユニットテスト / 何を: BlobStoreの往復と再利用 / なぜ: Step0の保存基盤を固定するため
 
-use evm_db::blob_store::{AllocKey, BlobStore};
+use evm_db::blob_codec::{decode_blob, encode_blob, has_envelope, is_compressed, BlobCodecError};
+use evm_db::blob_store::{AllocKey, BlobError, BlobStore};
 use ic_stable_structures::memory_manager::{MemoryId, MemoryManager, VirtualMemory};
 use ic_stable_structures::{DefaultMemoryImpl, StableBTreeMap, StableCell, Storable};
 use std::borrow::Cow;
@@ -146,3 +147,142 @@ fn usage_stats_updates_when_reusing_free_slot() {
         "free bytes should decrease when free slot is reused"
     );
 }
+
+#[test]
+fn relocate_lower_moves_into_free_slot_and_trim_shrinks_arena_end() {
+    let mut store = new_blob_store();
+    let low = store.store_bytes(&[1u8; 64]).expect("store low");
+    let mid = store.store_bytes(&[2u8; 64]).expect("store mid");
+    let high = store.store_bytes(&[3u8; 64]).expect("store high");
+    store.reclaim_for_prune(&low).expect("reclaim low");
+    // 先頭以外に空きが無いblobは動かない。
+    let unmoved = store
+        .relocate_lower(&low)
+        .expect_err("freed blob cannot move");
+    assert_eq!(unmoved, evm_db::blob_store::BlobError::InvalidState);
+
+    let moved = store
+        .relocate_lower(&high)
+        .expect("relocate")
+        .expect("lower slot exists");
+    assert_eq!(moved.offset(), low.offset());
+    assert!(moved.gen() > low.gen());
+    assert_eq!(store.read(&moved).expect("read moved"), vec![3u8; 64]);
+    // 張り替え前は旧ポインタも読める。解放後は読めない。
+    assert_eq!(store.read(&high).expect("read old"), vec![3u8; 64]);
+    store.release_relocated(&high).expect("release");
+    store
+        .release_relocated(&high)
+        .expect("release is idempotent");
+    assert!(store.read(&high).is_err());
+    assert_eq!(store.relocate_lower(&mid).expect("no lower slot"), None);
+
+    let before = store.usage_stats();
+    let (slots, trimmed) = store.trim_free_tail(8);
+    assert_eq!((slots, trimmed), (1, u64::from(high.class())));
+    let after = store.usage_stats();
+    assert_eq!(after.arena_end_bytes, high.offset());
+    assert_eq!(
+        after.free_class_bytes,
+        before.free_class_bytes - u64::from(high.class())
+    );
+    // 末尾がUsedなら何もしない。
+    assert_eq!(store.trim_free_tail(8), (0, 0));
+    let next = store.store_bytes(&[4u8; 64]).expect("store after trim");
+    assert_eq!(next.offset(), high.offset());
+}
+
+fn transfer_heavy_payload() -> Vec<u8> {
+    // Transfer topic + 左詰めaddress + 小さなuint256 を並べた、log の多い receipt に近い形
+    let topic: [u8; 32] = [
+        0xdd, 0xf2, 0x52, 0xad, 0x1b, 0xe2, 0xc8, 0x9b, 0x69, 0xc2, 0xb0, 0x68, 0xfc, 0x37, 0x8d,
+        0xaa, 0x95, 0x2b, 0xa7, 0xf1, 0x63, 0xc4, 0xa1, 0x16, 0x28, 0xf5, 0x5a, 0x4d, 0xf5, 0x23,
+        0xb3, 0xef,
+    ];
+    let mut out = b"rcptv2\0\x02".to_vec();
+    for i in 0u32..200 {
+        out.extend_from_slice(&topic);
+        out.extend_from_slice(&[0u8; 12]);
+        out.extend_from_slice(&[0x11; 20]);
+        out.extend_from_slice(&[0u8; 28]);
+        out.extend_from_slice(&i.to_be_bytes());
+    }
+    out
+}
+
+#[test]
+fn reclaimed_slot_leaves_free_list_once_reused() {
+    let mut store = new_blob_store();
+    let low = store.store_bytes(&[1u8; 64]).expect("store low");
+    let high = store.store_bytes(&[2u8; 64]).expect("store high");
+    store.reclaim_for_prune(&low).expect("reclaim low");
+    let reused = store.store_bytes(&[3u8; 64]).expect("reuse low");
+    assert_eq!(reused.offset(), low.offset());
+    // 再利用したslotは free_list から外れているため、移動先にならない。
+    assert_eq!(store.relocate_lower(&high).expect("relocate"), None);
+}
+
+#[test]
+fn store_encoded_drops_size_class_and_reads_back_raw() {
+    let mut store = new_blob_store();
+    let raw = transfer_heavy_payload();
+    assert!(raw.len() > 16 * 1024);
+    let ptr = store.store_encoded(&raw).expect("store encoded");
+    assert_eq!(ptr.class(), 8 * 1024);
+    assert!(is_compressed(&store.read(&ptr).expect("read stored")));
+    assert_eq!(store.read_decoded(&ptr).expect("read decoded"), raw);
+
+    // 圧縮してもクラスが変わらない小さな blob は raw のまま
+    let small = vec![7u8; 300];
+    assert!(matches!(encode_blob(&small), Cow::Borrowed(_)));
+    let small_ptr = store.store_encoded(&small).expect("store small");
+    assert_eq!(store.read(&small_ptr).expect("read small"), small);
+
+    // 圧縮導入前に store_bytes で書いた blob もそのまま読める
+    let legacy = store.store_bytes(&raw).expect("store legacy");
+    assert_eq!(store.read_decoded(&legacy).expect("read legacy"), raw);
+}
+
+#[test]
+fn raw_blob_starting_with_magic_is_stored_with_explicit_codec() {
+    let mut raw = b"blobz\0\0\x01".to_vec();
+    raw.push(1);
+    raw.extend_from_slice(&[0xa5u8; 300]);
+    let encoded = encode_blob(&raw).into_owned();
+    assert!(has_envelope(&encoded));
+    assert!(!is_compressed(&encoded));
+    assert_eq!(decode_blob(encoded), Ok(raw.clone()));
+
+    let mut store = new_blob_store();
+    let ptr = store.store_encoded(&raw).expect("store ambiguous raw");
+    assert_eq!(store.read_decoded(&ptr).expect("read decoded"), raw);
+}
+
+#[test]
+fn decode_blob_rejects_corrupt_envelope() {
+    let raw = transfer_heavy_payload();
+    let encoded = encode_blob(&raw).into_owned();
+    assert_eq!(decode_blob(encoded.clone()), Ok(raw));
+
+    let mut unknown = encoded.clone();
+    unknown[8] = 0x7f;
+    assert_eq!(
+        decode_blob(unknown),
+        Err(BlobCodecError::UnknownCodec(0x7f))
+    );
+
+    let mut wrong_len = encoded.clone();
+    wrong_len[12] ^= 0x01;
+    assert!(decode_blob(wrong_len).is_err());
+
+    assert_eq!(
+        decode_blob(encoded[..10].to_vec()),
+        Err(BlobCodecError::Truncated)
+    );
+
+    let mut store = new_blob_store();
+    let mut truncated = encoded.clone();
+    truncated.truncate(encoded.len() - 16);
+    let ptr = store.store_bytes(&truncated).expect("store truncated");
+    assert!(matches!(store.read_decoded(&ptr), Err(BlobError::Codec(_))));
+}
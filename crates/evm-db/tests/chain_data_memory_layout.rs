@@ -31,6 +31,31 @@ fn chain_data_memory_ids_are_fixed() {
     assert_eq!(AppMemoryId::IcpUpdateDispatchQueue.as_u8(), 71);
     assert_eq!(AppMemoryId::IcpUpdateDispatchMeta.as_u8(), 72);
     assert_eq!(AppMemoryId::IcpUpdatePrecompileAllowlist.as_u8(), 73);
+    assert_eq!(AppMemoryId::ReadyTipIndex.as_u8(), 74);
+    assert_eq!(AppMemoryId::ReadyTipKeyByTxId.as_u8(), 75);
+    assert_eq!(AppMemoryId::ReadyFeeBoundaries.as_u8(), 76);
+    assert_eq!(AppMemoryId::ReadyIndexState.as_u8(), 77);
+    assert_eq!(AppMemoryId::StagedBlockMeta.as_u8(), 78);
+    assert_eq!(AppMemoryId::StagedBlockItems.as_u8(), 79);
+    assert_eq!(AppMemoryId::DropRecords.as_u8(), 80);
+    assert_eq!(AppMemoryId::DropRecordsByEthHash.as_u8(), 81);
+    assert_eq!(AppMemoryId::DropRecordSeq.as_u8(), 82);
+    assert_eq!(AppMemoryId::DropRecordState.as_u8(), 83);
+    assert_eq!(AppMemoryId::StateSnapshotImport.as_u8(), 84);
+    assert_eq!(AppMemoryId::BlobCompactionState.as_u8(), 85);
+    assert_eq!(AppMemoryId::BlobRecompressState.as_u8(), 86);
+    assert_eq!(AppMemoryId::ArchiveState.as_u8(), 87);
+    assert_eq!(AppMemoryId::ArchiveRanges.as_u8(), 88);
+    assert_eq!(AppMemoryId::ScrubState.as_u8(), 89);
+    assert_eq!(AppMemoryId::ScrubFindings.as_u8(), 90);
+    assert_eq!(AppMemoryId::DepositAccounts.as_u8(), 91);
+    assert_eq!(AppMemoryId::AssetLimits.as_u8(), 92);
+    assert_eq!(AppMemoryId::AssetFeeSchedules.as_u8(), 93);
+    assert_eq!(AppMemoryId::WrapAllowedNftCollections.as_u8(), 94);
+    assert_eq!(AppMemoryId::ArchivedTxBlocks.as_u8(), 95);
+    assert_eq!(AppMemoryId::PendingTipIndex.as_u8(), 96);
+    assert_eq!(AppMemoryId::PendingTipKeyByTxId.as_u8(), 97);
+    assert_eq!(AppMemoryId::ArchivedTxOrder.as_u8(), 98);
 }
 
 #[test]
@@ -5,12 +5,19 @@ use evm_db::chain_data::constants::{
     MAX_LOGS_PER_TX, MAX_RETURN_DATA, MAX_TXS_PER_BLOCK, MAX_TXS_PER_BLOCK_U32,
 };
 use evm_db::chain_data::receipt::LogEntry;
-use evm_db::chain_data::wrap_request::WRAP_STORED_REQUEST_MAX_BYTES;
+use evm_db::chain_data::wrap_request::{
+    ASSET_FEE_SCHEDULE_MAX_BYTES, MAX_FEE_TIERS, WRAP_STORED_REQUEST_MAX_BYTES,
+};
 use evm_db::chain_data::{
-    BlockData, CallerKey, ChainStateV1, FeePolicyStored, Head, InternalTrace,
-    InternalTraceActionKind, InternalTraceSet, MintSubmitStatus, OpsMetricsV1, PruneJournal,
-    QueueMeta, ReceiptLike, RequestStatus, RuntimeConfigV1, StoredTx, StoredTxBytes, TxId,
-    TxIndexEntry, TxKind, TxLoc, UnwrapDispatchRequest, UnwrapRequestStatus, WrapEvmConfigStored,
+    ArchiveRangeV1, ArchiveStateV1, AssetFeeScheduleStored, AssetLimitsV1, BlobCompactionPhase,
+    BlobCompactionStateV1, BlobRecompressPhase, BlobRecompressStateV1, BlobRefMap,
+    BlobRelocationJournal, BlockData, BridgeDirection, CallerKey, ChainStateV1, DepositAccountV1,
+    DepositSweepV1, DropRecordStateV1, DropRecordV1, FeePolicyStored, FeeTierStored, Head,
+    InternalTrace, InternalTraceActionKind, InternalTraceSet, MintSubmitStatus, OpsMetricsV1,
+    PruneJournal, QueueMeta, ReceiptLike, RequestStatus, RuntimeConfigV1, ScrubFindingKind,
+    ScrubFindingV1, ScrubPhase, ScrubStateV1, ScrubTarget, StagedBlockMetaV1,
+    StateSnapshotImportPhase, StateSnapshotImportV1, StoredTx, StoredTxBytes, TxId, TxIndexEntry,
+    TxKind, TxLoc, UnwrapDispatchRequest, UnwrapRequestStatus, WrapAssetKind, WrapEvmConfigStored,
     WrapPendingSubmission, WrapRequestResult, WrapRequestStage, WrapStoredRequest,
     MAX_INTERNAL_TRACES_PER_TX_U32, UNWRAP_DECODE_FAILURE_CODE, WRAP_DECODE_FAILURE_CODE,
 };
@@ -492,6 +499,312 @@ fn queue_meta_roundtrip() {
     assert_eq!(meta, decoded);
 }
 
+#[test]
+fn drop_record_roundtrip_keeps_optional_fields() {
+    let full = DropRecordV1 {
+        tx_id: TxId([0x21; 32]),
+        kind: TxKind::EthSigned.to_u8(),
+        eth_tx_hash: Some([0x22; 32]),
+        sender: Some([0x23; 20]),
+        nonce: Some(9),
+        drop_code: 6,
+        block_attempted: Some(12),
+        dropped_at: 1_700_000_000,
+        max_fee_per_gas: u128::MAX - 1,
+        max_priority_fee_per_gas: 3,
+        base_fee: 250_000_000,
+        seq: 4,
+    };
+    let bytes = full.to_bytes();
+    assert_eq!(bytes.len(), 160);
+    assert_eq!(DropRecordV1::from_bytes(bytes), full);
+    let sparse = DropRecordV1 {
+        eth_tx_hash: None,
+        sender: None,
+        nonce: None,
+        block_attempted: None,
+        ..full
+    };
+    assert_eq!(DropRecordV1::from_bytes(sparse.to_bytes()), sparse);
+
+    let mut state = DropRecordStateV1::new();
+    state.next_seq = 10;
+    state.oldest_seq = 4;
+    state.pruned_total = 4;
+    assert_eq!(state.live_records(), 6);
+    assert_eq!(DropRecordStateV1::from_bytes(state.to_bytes()), state);
+}
+
+#[test]
+fn state_snapshot_import_roundtrip_and_rejects_unknown_phase() {
+    let import = StateSnapshotImportV1 {
+        phase: StateSnapshotImportPhase::Verifying,
+        head_number: 42,
+        block_hash: [0x31; 32],
+        timestamp: 1_700_000_000,
+        state_root: [0x32; 32],
+        evm_state_epoch: 7,
+        accounts: 3,
+        storage: 5,
+        codes: 1,
+        ..StateSnapshotImportV1::new()
+    };
+    let bytes = import.to_bytes();
+    assert_eq!(bytes.len(), 117);
+    assert!(import.is_active());
+    assert_eq!(StateSnapshotImportV1::from_bytes(bytes), import);
+
+    let mut raw = import.into_bytes();
+    raw[4] = 9;
+    let decoded = StateSnapshotImportV1::from_bytes(Cow::Owned(raw));
+    assert_eq!(decoded, StateSnapshotImportV1::new());
+    assert!(!decoded.is_active());
+}
+
+#[test]
+fn blob_compaction_state_roundtrip_keeps_cursor_and_journal() {
+    let state = BlobCompactionStateV1 {
+        phase: BlobCompactionPhase::Relocating,
+        cursor_map: BlobRefMap::TxIndex,
+        cursor_key: Some([0x41; 32]),
+        journal: Some(BlobRelocationJournal {
+            map: BlobRefMap::Receipts,
+            key: [0x42; 32],
+            src: BlobPtr::new(81_920, 300, 8_192, 1),
+            dst: BlobPtr::new(8_192, 300, 8_192, 3),
+        }),
+        passes: 2,
+        relocated_blobs: 5,
+        relocated_bytes: 40_960,
+        trimmed_bytes: 16_384,
+        ..BlobCompactionStateV1::new()
+    };
+    let bytes = state.to_bytes();
+    assert_eq!(bytes.len(), 145);
+    assert_eq!(BlobCompactionStateV1::from_bytes(bytes), state);
+
+    let idle = BlobCompactionStateV1::new();
+    assert_eq!(BlobCompactionStateV1::from_bytes(idle.to_bytes()), idle);
+
+    let mut raw = state.into_bytes();
+    raw[5] = 9;
+    assert_eq!(
+        BlobCompactionStateV1::from_bytes(Cow::Owned(raw)),
+        BlobCompactionStateV1::new()
+    );
+}
+
+#[test]
+fn blob_recompress_state_roundtrip_keeps_cursor_and_counters() {
+    let state = BlobRecompressStateV1 {
+        phase: BlobRecompressPhase::Running,
+        cursor_map: BlobRefMap::InternalTraces,
+        cursor_key: Some([0x51; 32]),
+        journal: Some(BlobRelocationJournal {
+            map: BlobRefMap::Blocks,
+            key: [0x52; 32],
+            src: BlobPtr::new(16_384, 9_000, 16_384, 2),
+            dst: BlobPtr::new(0, 700, 8_192, 4),
+        }),
+        scanned: 12,
+        recompressed_blobs: 3,
+        saved_bytes: 24_576,
+        ..BlobRecompressStateV1::new()
+    };
+    let bytes = state.to_bytes();
+    assert_eq!(bytes.len(), 137);
+    assert_eq!(BlobRecompressStateV1::from_bytes(bytes), state);
+
+    let pending = BlobRecompressStateV1::new();
+    assert_eq!(pending.phase, BlobRecompressPhase::Pending);
+    assert_eq!(
+        BlobRecompressStateV1::from_bytes(pending.to_bytes()),
+        pending
+    );
+
+    let mut raw = state.into_bytes();
+    raw[4] = 7;
+    assert_eq!(
+        BlobRecompressStateV1::from_bytes(Cow::Owned(raw)),
+        BlobRecompressStateV1::new()
+    );
+}
+
+#[test]
+fn archive_state_and_range_roundtrip() {
+    let canister = CallerKey::from_principal_bytes(&[0x0a; 10]);
+    let state = ArchiveStateV1 {
+        canister: Some(canister),
+        acked_through: Some(1_234),
+        acked_at: 99,
+        ..ArchiveStateV1::new()
+    };
+    let bytes = state.to_bytes();
+    assert_eq!(bytes.len(), 52);
+    assert_eq!(ArchiveStateV1::from_bytes(bytes), state);
+    assert_eq!(canister.principal_bytes(), &[0x0a; 10]);
+
+    let unset = ArchiveStateV1::new();
+    assert_eq!(ArchiveStateV1::from_bytes(unset.to_bytes()), unset);
+    let mut raw = state.into_bytes();
+    raw[4] = 3;
+    assert_eq!(
+        ArchiveStateV1::from_bytes(Cow::Owned(raw)),
+        ArchiveStateV1::new()
+    );
+
+    let range = ArchiveRangeV1 {
+        end_block: 500,
+        canister,
+    };
+    let bytes = range.to_bytes();
+    assert_eq!(bytes.len(), 38);
+    assert_eq!(ArchiveRangeV1::from_bytes(bytes), range);
+}
+
+#[test]
+fn scrub_state_and_finding_roundtrip() {
+    let state = ScrubStateV1 {
+        phase: ScrubPhase::Paused,
+        target: ScrubTarget::WrapQueue,
+        cursor_key: Some([0x5a; 32]),
+        auto_repair: true,
+        run_id: 3,
+        started_at: 10,
+        scanned: 77,
+        findings: 2,
+        repaired: 1,
+        next_finding_seq: 40,
+        ..ScrubStateV1::new()
+    };
+    let bytes = state.to_bytes();
+    assert_eq!(bytes.len(), 96);
+    assert_eq!(ScrubStateV1::from_bytes(bytes), state);
+    assert_eq!(
+        ScrubStateV1::from_bytes(ScrubStateV1::new().to_bytes()),
+        ScrubStateV1::new()
+    );
+    let mut raw = state.into_bytes();
+    raw[5] = 0xff;
+    assert_eq!(
+        ScrubStateV1::from_bytes(Cow::Owned(raw)),
+        ScrubStateV1::new()
+    );
+
+    let finding = ScrubFindingV1 {
+        run_id: 3,
+        target: ScrubTarget::Blocks,
+        kind: ScrubFindingKind::MissingTxIndex,
+        repaired: true,
+        key: [0x11; 32],
+        detected_at: 12,
+    };
+    let bytes = finding.to_bytes();
+    assert_eq!(bytes.len(), 51);
+    assert_eq!(ScrubFindingV1::from_bytes(bytes), finding);
+}
+
+#[test]
+fn deposit_account_roundtrip_with_and_without_in_flight_sweep() {
+    let account = DepositAccountV1 {
+        next_seq: 4,
+        in_flight: Some(DepositSweepV1 {
+            request_id: [0x3c; 32],
+            amount_e8s: u128::from(u64::MAX) + 7,
+            created_at_time: 11,
+        }),
+        updated_at: 12,
+    };
+    let bytes = account.to_bytes();
+    assert_eq!(bytes.len(), 73);
+    assert_eq!(DepositAccountV1::from_bytes(bytes), account);
+    let idle = DepositAccountV1 {
+        in_flight: None,
+        ..account
+    };
+    assert_eq!(DepositAccountV1::from_bytes(idle.to_bytes()), idle);
+    let mut raw = account.into_bytes();
+    raw[8] = 0xff;
+    assert_eq!(
+        DepositAccountV1::from_bytes(Cow::Owned(raw)),
+        DepositAccountV1::default()
+    );
+}
+
+#[test]
+fn asset_limits_roundtrip_and_fail_closed_on_unknown_flags() {
+    let mut limits = AssetLimitsV1 {
+        paused: false,
+        max_single_wrap: Some(500),
+        wrap_daily_cap: None,
+        unwrap_daily_cap: Some(u128::from(u64::MAX) + 3),
+        window_hour: 0,
+        wrap_buckets: [0; 24],
+        unwrap_buckets: [0; 24],
+        updated_at: 21,
+    };
+    limits.record(BridgeDirection::Wrap, 100, 40);
+    limits.record(BridgeDirection::Unwrap, 100, 7);
+    let bytes = limits.to_bytes();
+    assert_eq!(bytes.len(), 833);
+    assert_eq!(AssetLimitsV1::from_bytes(bytes), limits);
+    let mut raw = limits.into_bytes();
+    raw[0] = 0x80;
+    let decoded = AssetLimitsV1::from_bytes(Cow::Owned(raw));
+    assert!(decoded.paused);
+    assert_eq!(decoded.max_single_wrap, None);
+}
+
+#[test]
+fn asset_limits_window_expires_buckets_after_a_day() {
+    let mut limits = AssetLimitsV1::default();
+    let first = limits.record(BridgeDirection::Wrap, 1_000, 30);
+    limits.record(BridgeDirection::Wrap, 1_010, 20);
+    limits.advance_window(1_023);
+    assert_eq!(limits.volume(BridgeDirection::Wrap), 50);
+    assert_eq!(limits.volume(BridgeDirection::Unwrap), 0);
+
+    limits.advance_window(1_024);
+    assert_eq!(limits.volume(BridgeDirection::Wrap), 20);
+    limits.release(BridgeDirection::Wrap, first, 1_024, 30);
+    assert_eq!(limits.volume(BridgeDirection::Wrap), 20);
+
+    // 時計が戻っても記録済みの量は残り、最新 bucket に積む。
+    let recorded = limits.record(BridgeDirection::Wrap, 1_020, 5);
+    assert_eq!(recorded, 1_024);
+    limits.release(BridgeDirection::Wrap, recorded, 1_024, 5);
+    assert_eq!(limits.volume(BridgeDirection::Wrap), 20);
+
+    limits.advance_window(5_000);
+    assert_eq!(limits.volume(BridgeDirection::Wrap), 0);
+}
+
+#[test]
+fn staged_block_meta_roundtrip_and_clear_keeps_counters() {
+    let meta = StagedBlockMetaV1 {
+        active: true,
+        number: 9,
+        timestamp: 1_700_000_000,
+        parent_hash: [0x11; 32],
+        base_fee: 250_000_000,
+        block_gas_limit: 30_000_000,
+        evm_state_epoch: 42,
+        rounds: 3,
+        item_count: 5,
+        sealed_total: 7,
+        discarded_total: 1,
+        ..StagedBlockMetaV1::new()
+    };
+    let bytes = meta.to_bytes();
+    assert_eq!(bytes.len(), 101);
+    assert_eq!(StagedBlockMetaV1::from_bytes(bytes), meta);
+    let cleared = meta.cleared();
+    assert!(!cleared.active);
+    assert_eq!(cleared.item_count, 0);
+    assert_eq!(cleared.sealed_total, 7);
+    assert_eq!(cleared.discarded_total, 1);
+}
+
 #[test]
 fn tx_index_roundtrip() {
     let entry = TxIndexEntry {
@@ -529,6 +842,8 @@ fn chain_state_roundtrip() {
     state.auto_production_enabled = true;
     state.is_producing = true;
     state.mining_scheduled = false;
+    state.optimistic_exec_enabled = true;
+    state.state_snapshot_export_hold = true;
     state.next_queue_seq = 12;
     state.mining_interval_ms = 7_000;
     state.base_fee = 1;
@@ -738,6 +1053,9 @@ fn wrap_stored_request_roundtrip() {
         fee_created_at_time: 11,
         pull_created_at_time: 12,
         withdraw_created_at_time: 13,
+        fee_in_asset: false,
+        deadline_at: 19,
+        kind: WrapAssetKind::Nft,
         result: WrapRequestResult {
             status: RequestStatus::Failed,
             pull_ledger_tx_id: Some(vec![8]),
@@ -756,6 +1074,7 @@ fn wrap_stored_request_roundtrip() {
             mint_nonce: Some(15),
             mint_submitted_at_time: 16,
             mint_submit_status: MintSubmitStatus::Submitted,
+            refund_amount_e8s: Some(20),
         },
     };
 
@@ -768,6 +1087,9 @@ fn wrap_stored_request_roundtrip() {
     assert_eq!(decoded.result.status, RequestStatus::Failed);
     assert_eq!(decoded.result.stage, WrapRequestStage::Failed);
     assert_eq!(decoded.result.mint_nonce, Some(15));
+    assert_eq!(decoded.deadline_at, 19);
+    assert_eq!(decoded.kind, WrapAssetKind::Nft);
+    assert_eq!(decoded.result.refund_amount_e8s, Some(20));
     assert!(decoded.result.withdrawn);
     assert!(decoded.result.mint_failed_recoverable);
 }
@@ -804,6 +1126,9 @@ fn wrap_stored_request_worst_case_fits_stable_bound() {
         fee_created_at_time: u64::MAX,
         pull_created_at_time: u64::MAX,
         withdraw_created_at_time: u64::MAX,
+        fee_in_asset: true,
+        deadline_at: u64::MAX,
+        kind: WrapAssetKind::Nft,
         result: WrapRequestResult {
             status: RequestStatus::Failed,
             pull_ledger_tx_id: Some(vec![0x55; 128]),
@@ -822,6 +1147,7 @@ fn wrap_stored_request_worst_case_fits_stable_bound() {
             mint_nonce: Some(u64::MAX),
             mint_submitted_at_time: u64::MAX,
             mint_submit_status: MintSubmitStatus::Submitted,
+            refund_amount_e8s: Some(u128::MAX),
         },
     };
 
@@ -864,6 +1190,29 @@ fn wrap_config_stored_roundtrip() {
     assert_eq!(decoded_pending.request_id, pending.request_id);
 }
 
+#[test]
+fn asset_fee_schedule_roundtrip_fits_max_tiers_and_fails_closed() {
+    let schedule = AssetFeeScheduleStored {
+        fee_ledger_canister: vec![0xee; 29],
+        cycle_fee_e8s: u64::MAX,
+        gas_price_buffer_bps: 12_000,
+        fee_in_asset: true,
+        tiers: (0..MAX_FEE_TIERS)
+            .map(|i| FeeTierStored {
+                min_amount_e8s: u128::MAX - i as u128,
+                fee_bps: u32::MAX,
+            })
+            .collect(),
+    };
+    let bytes = schedule.to_bytes();
+    assert!(bytes.len() <= ASSET_FEE_SCHEDULE_MAX_BYTES as usize);
+    assert_eq!(AssetFeeScheduleStored::from_bytes(bytes), schedule);
+
+    let broken = AssetFeeScheduleStored::from_bytes(Cow::Owned(vec![0xff]));
+    assert!(broken.fee_ledger_canister.is_empty());
+    assert!(!broken.fee_in_asset);
+}
+
 #[test]
 fn wrap_config_decode_failures_do_not_panic() {
     init_stable_state();
@@ -53,6 +53,8 @@ fn ops_state_roundtrip() {
         last_check_ts: 8,
         mode: OpsMode::Critical,
         safe_stop_latched: true,
+        wrap_paused: true,
+        last_reserves_check_ts: 9,
     };
     let decoded = OpsStateV1::from_bytes(Cow::Owned(state.to_bytes().into_owned()));
     assert_eq!(decoded, state);
@@ -76,6 +78,7 @@ fn ops_state_invalid_len_sets_needs_migration() {
     let decoded = OpsStateV1::from_bytes(Cow::Owned(vec![0u8; 1]));
     assert_eq!(decoded.mode, OpsMode::Critical);
     assert!(decoded.safe_stop_latched);
+    assert!(decoded.wrap_paused);
     assert!(needs_migration());
 }
 
@@ -117,6 +120,7 @@ fn schema_migration_state_roundtrip_with_cursor_key() {
     state.last_error = 9;
     state.cursor_key_set = true;
     state.cursor_key = [0xabu8; 32];
+    state.migration_index = 1;
     let decoded = SchemaMigrationState::from_bytes(Cow::Owned(state.to_bytes().into_owned()));
     assert_eq!(decoded, state);
 }
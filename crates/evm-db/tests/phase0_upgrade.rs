@@ -1,17 +1,100 @@
-//! どこで: Phase0テスト / 何を: UPGRADES領域のversion書き込み / なぜ: upgrade耐性の確認
+//! どこで: Phase0テスト / 何を: UPGRADES領域の版付き checkpoint / なぜ: upgrade 越しの引き継ぎと downgrade 拒否の確認
 
 use evm_db::memory::{get_memory, AppMemoryId};
-use evm_db::upgrade::pre_upgrade;
+use evm_db::upgrade::{
+    post_upgrade, pre_upgrade, PrecompileProfileCheckpoint, UpgradeCheckpoint, UpgradeError,
+    UPGRADE_STATE_VERSION,
+};
 use ic_stable_structures::reader::Reader;
+use ic_stable_structures::writer::Writer;
+
+fn sample_checkpoint() -> UpgradeCheckpoint {
+    UpgradeCheckpoint {
+        schema_version: 5,
+        caller_evm: vec![(vec![0x01, 0x02], [0x11; 20]), (vec![0x03; 29], [0x22; 20])],
+        decode_suppress_until: vec![(vec![0x04], 1_700_000_000)],
+        precompile_profile: vec![PrecompileProfileCheckpoint {
+            address: [0x33; 20],
+            calls: 3,
+            total_instructions: u128::from(u64::MAX) + 7,
+            max_instructions: 9,
+            total_extra_gas: 12,
+            max_extra_gas: 5,
+        }],
+        unwrap_dispatch_scheduled: true,
+        icp_update_dispatch_scheduled: false,
+        wrap_worker_scheduled: true,
+    }
+}
+
+fn write_raw(bytes: &[u8]) {
+    let mut memory = get_memory(AppMemoryId::Upgrades);
+    let mut writer = Writer::new(&mut memory, 0);
+    writer.write(bytes).expect("write");
+}
 
 #[test]
 fn upgrade_writes_version() {
-    pre_upgrade();
+    pre_upgrade(&UpgradeCheckpoint::default());
     let memory = get_memory(AppMemoryId::Upgrades);
     let mut reader = Reader::new(&memory, 0);
     let mut buf = [0u8; 4];
     let read = reader.read(&mut buf).expect("read version");
     assert_eq!(read, 4);
     let version = u32::from_le_bytes(buf);
-    assert_eq!(version, 1);
+    assert_eq!(version, UPGRADE_STATE_VERSION);
+}
+
+#[test]
+fn checkpoint_roundtrips_volatile_state() {
+    let checkpoint = sample_checkpoint();
+    pre_upgrade(&checkpoint);
+    assert_eq!(post_upgrade(5), Ok(Some(checkpoint.clone())));
+    // 新しい schema への upgrade も受け入れる。
+    assert_eq!(post_upgrade(6), Ok(Some(checkpoint)));
+}
+
+#[test]
+fn post_upgrade_refuses_schema_downgrade_and_unknown_versions() {
+    pre_upgrade(&sample_checkpoint());
+    assert_eq!(
+        post_upgrade(4),
+        Err(UpgradeError::SchemaDowngrade {
+            stored: 5,
+            supported: 4
+        })
+    );
+
+    write_raw(&(UPGRADE_STATE_VERSION + 1).to_le_bytes());
+    assert_eq!(
+        post_upgrade(5),
+        Err(UpgradeError::UnknownVersion(UPGRADE_STATE_VERSION + 1))
+    );
+    write_raw(&0u32.to_le_bytes());
+    assert_eq!(post_upgrade(5), Err(UpgradeError::UnknownVersion(0)));
+
+    // 版 1 は version だけなので、引き継ぐものは無いが upgrade は通す。
+    write_raw(&1u32.to_le_bytes());
+    assert_eq!(post_upgrade(5), Ok(None));
+}
+
+#[test]
+fn post_upgrade_rejects_missing_version_and_truncated_payload() {
+    assert_eq!(post_upgrade(5), Err(UpgradeError::MissingVersion));
+
+    let mut header = Vec::new();
+    header.extend_from_slice(&UPGRADE_STATE_VERSION.to_le_bytes());
+    header.extend_from_slice(&5u32.to_le_bytes());
+    header.extend_from_slice(&u32::MAX.to_le_bytes());
+    write_raw(&header);
+    assert_eq!(post_upgrade(5), Err(UpgradeError::CorruptCheckpoint));
+
+    // 長さは足りても中身が途中で切れていれば拒否する。
+    let mut header = Vec::new();
+    header.extend_from_slice(&UPGRADE_STATE_VERSION.to_le_bytes());
+    header.extend_from_slice(&5u32.to_le_bytes());
+    header.extend_from_slice(&2u32.to_le_bytes());
+    header.extend_from_slice(&[1, 0]);
+    write_raw(&header);
+    assert_eq!(post_upgrade(5), Err(UpgradeError::CorruptCheckpoint));
 }
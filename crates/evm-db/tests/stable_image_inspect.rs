@@ -0,0 +1,120 @@
+//! どこで: オフライン検査テスト / 何を: stable memoryイメージの読み込みと破損報告 / なぜ: 本番イメージを調べるCLIが壊れた領域を見落とさないため
+
+use evm_db::blob_ptr::BlobPtr;
+use evm_db::chain_data::{BlockData, TxId};
+use evm_db::inspect::{inspect_image, InspectError};
+use evm_db::memory::{load_memory_image, memory_image, AppMemoryId, WASM_PAGE_SIZE_BYTES};
+use evm_db::meta::{current_schema_version, ensure_meta_initialized, mark_migration_applied};
+use evm_db::stable_state::{init_stable_state, with_state_mut};
+use evm_db::Storable;
+
+fn block(number: u64) -> BlockData {
+    BlockData::new(
+        number,
+        [0x01; 32],
+        [0x02; 32],
+        1_700_000_000 + number,
+        1_000,
+        30_000_000,
+        0,
+        [0x03; 20],
+        Vec::new(),
+        [0x04; 32],
+        [0x05; 32],
+    )
+}
+
+/// 正常なブロック1件と、壊れたブロック・宙に浮いたreceipt・payloadの無いqueue項目を持つイメージ。
+fn build_image() -> Vec<u8> {
+    load_memory_image(Vec::new());
+    init_stable_state();
+    ensure_meta_initialized();
+    mark_migration_applied(1, current_schema_version(), 0);
+    with_state_mut(|state| {
+        let good = block(1).to_bytes().into_owned();
+        let ptr = state.blob_store.store_bytes(&good).expect("store block");
+        state.blocks.insert(1, ptr);
+        let truncated = state
+            .blob_store
+            .store_bytes(&good[..16])
+            .expect("store truncated");
+        state.blocks.insert(2, truncated);
+        state
+            .receipts
+            .insert(TxId([0x11; 32]), BlobPtr::new(0, 8, 64, 99));
+        state.queue.insert(0, TxId([0x22; 32]));
+        let mut head = *state.head.get();
+        head.number = 2;
+        state.head.set(head);
+        let mut chain_state = *state.chain_state.get();
+        chain_state.last_block_number = 2;
+        state.chain_state.set(chain_state);
+        let mut root_meta = *state.state_root_meta.get();
+        root_meta.initialized = true;
+        state.state_root_meta.set(root_meta);
+    });
+    memory_image().expect("host image")
+}
+
+#[test]
+fn inspect_reports_layout_schema_and_broken_records() {
+    let image = std::thread::spawn(build_image).join().expect("build image");
+    let report = std::thread::spawn(move || inspect_image(image))
+        .join()
+        .expect("inspect thread")
+        .expect("inspect");
+
+    assert_eq!(report.meta.schema_version, current_schema_version());
+    assert!(report.meta.header_problems.is_empty());
+    assert_eq!(report.head.number, 2);
+    let blocks_region = report
+        .regions
+        .iter()
+        .find(|region| region.id == AppMemoryId::Blocks.as_u8())
+        .expect("blocks region");
+    assert!(blocks_region.pages > 0);
+
+    let section = |name: &str| {
+        report
+            .sections
+            .iter()
+            .find(|section| section.name == name)
+            .cloned()
+            .expect("section")
+    };
+    let blocks = section("blocks");
+    assert_eq!((blocks.entries, blocks.issues), (2, 1));
+    assert_eq!(section("receipts").issues, 1);
+    assert_eq!(section("queues").issues, 1);
+    assert_eq!(section("chain").issues, 0);
+    assert_eq!(report.issue_total, 3);
+    assert!(!report.is_clean());
+
+    let block_issue = report
+        .issues
+        .iter()
+        .find(|issue| issue.section == "blocks")
+        .expect("block issue");
+    assert_eq!(block_issue.key, "2");
+    assert!(block_issue.detail.contains("block_data"));
+    let rendered = report.to_string();
+    assert!(rendered.contains("issues: 3"));
+    assert!(rendered.contains("Blocks"));
+}
+
+#[test]
+fn inspect_rejects_images_without_memory_manager_layout() {
+    let page = usize::try_from(WASM_PAGE_SIZE_BYTES).expect("page size");
+    assert_eq!(
+        inspect_image(vec![0u8; page]),
+        Err(InspectError::NotMemoryManagerImage)
+    );
+    let mut unaligned = b"MGR".to_vec();
+    unaligned.resize(page + 1, 0);
+    assert_eq!(
+        inspect_image(unaligned),
+        Err(InspectError::UnalignedImage {
+            len: WASM_PAGE_SIZE_BYTES + 1
+        })
+    );
+}
@@ -3,7 +3,7 @@
 //! なぜ: 壊れレコード1件で read 経路が trap しない可用性を担保するため
 
 use evm_db::chain_data::{
-    TxId, UnwrapDispatchRequest, UnwrapRequestStatus, UNWRAP_DECODE_FAILURE_CODE,
+    TxId, UnwrapAssetKind, UnwrapDispatchRequest, UnwrapRequestStatus, UNWRAP_DECODE_FAILURE_CODE,
 };
 use evm_db::memory::WASM_PAGE_SIZE_BYTES;
 use ic_stable_structures::memory_manager::{MemoryId, MemoryManager, VirtualMemory};
@@ -17,11 +17,13 @@ fn sample_request() -> UnwrapDispatchRequest {
         asset_id: vec![0xB2u8; 12],
         amount: [0xC3u8; 32],
         recipient: vec![0xD4u8; 20],
+        recipient_subaccount: None,
         status: UnwrapRequestStatus::Queued,
         ledger_tx_id: Some(vec![0xE5u8; 16]),
         error_code: Some("wrap.integration.sample".to_string()),
         updated_at: 123_456_799,
         transfer_created_at_time: 123_456_800,
+        kind: UnwrapAssetKind::Fungible,
     }
 }
 
@@ -0,0 +1,382 @@
+//! どこで: E2E 用の偽 ICRC ledger
+//! 何を: ICRC-1/2/3 の最小限の口座・送金・block 取得と、送金応答を読めなくする故障注入
+//! なぜ: 結果の分からない unwrap dispatch を PocketIC で再現し、reconcile_unwrap_request の判定と二重送金の有無を確かめるため
+
+use candid::{CandidType, Deserialize, Func, Nat, Principal};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::marker::PhantomData;
+
+const TRANSFER_FEE: u128 = 10;
+const DECIMALS: u8 = 8;
+/// icrc1_transfer の応答型と合わない reply。呼び出し側では decode に失敗する。
+const GARBLED_REPLY: &str = "fake-ledger.garbled";
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct Account {
+    pub owner: Principal,
+    pub subaccount: Option<Vec<u8>>,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct InitArgs {
+    pub initial_balances: Vec<(Account, Nat)>,
+}
+
+/// 次の icrc1_transfer 1回だけに効く故障。
+#[derive(Clone, Copy, Debug, CandidType, Deserialize, Eq, PartialEq)]
+pub enum TransferFault {
+    /// 送金を block に載せたうえで、読めない reply を返す。
+    CommitThenGarble,
+    /// 送金せずに、読めない reply を返す。
+    GarbleWithoutCommit,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct TransferArg {
+    pub from_subaccount: Option<Vec<u8>>,
+    pub to: Account,
+    pub amount: Nat,
+    pub fee: Option<Nat>,
+    pub memo: Option<Vec<u8>>,
+    pub created_at_time: Option<u64>,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub enum TransferError {
+    BadFee { expected_fee: Nat },
+    InsufficientFunds { balance: Nat },
+    Duplicate { duplicate_of: Nat },
+    GenericError { error_code: Nat, message: String },
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct TransferFromArgs {
+    pub spender_subaccount: Option<Vec<u8>>,
+    pub from: Account,
+    pub to: Account,
+    pub amount: Nat,
+    pub fee: Option<Nat>,
+    pub memo: Option<Vec<u8>>,
+    pub created_at_time: Option<u64>,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub enum TransferFromError {
+    BadFee { expected_fee: Nat },
+    InsufficientFunds { balance: Nat },
+    Duplicate { duplicate_of: Nat },
+    GenericError { error_code: Nat, message: String },
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct ApproveArgs {
+    pub from_subaccount: Option<Vec<u8>>,
+    pub spender: Account,
+    pub amount: Nat,
+    pub expected_allowance: Option<Nat>,
+    pub expires_at: Option<u64>,
+    pub fee: Option<Nat>,
+    pub memo: Option<Vec<u8>>,
+    pub created_at_time: Option<u64>,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub enum ApproveError {
+    GenericError { error_code: Nat, message: String },
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub enum MetadataValue {
+    Nat(Nat),
+    Int(candid::Int),
+    Text(String),
+    Blob(Vec<u8>),
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct GetBlocksArgs {
+    pub start: Nat,
+    pub length: Nat,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub enum Value {
+    Blob(Vec<u8>),
+    Text(String),
+    Nat(Nat),
+    Int(candid::Int),
+    Array(Vec<Value>),
+    Map(Vec<(String, Value)>),
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct BlockWithId {
+    pub id: Nat,
+    pub block: Value,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct ArchivedBlocks {
+    pub args: Vec<GetBlocksArgs>,
+    pub callback: Func,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct GetBlocksResult {
+    pub log_length: Nat,
+    pub blocks: Vec<BlockWithId>,
+    pub archived_blocks: Vec<ArchivedBlocks>,
+}
+
+type AccountKey = (Principal, [u8; 32]);
+
+#[derive(Clone, Debug)]
+struct Block {
+    timestamp: u64,
+    from: AccountKey,
+    to: AccountKey,
+    amount: u128,
+    memo: Option<Vec<u8>>,
+    created_at_time: Option<u64>,
+}
+
+#[derive(Default)]
+struct Ledger {
+    balances: BTreeMap<AccountKey, u128>,
+    blocks: Vec<Block>,
+    fault: Option<TransferFault>,
+}
+
+thread_local! {
+    static LEDGER: RefCell<Ledger> = RefCell::new(Ledger::default());
+}
+
+fn account_key(owner: Principal, subaccount: Option<&[u8]>) -> AccountKey {
+    let mut out = [0u8; 32];
+    if let Some(subaccount) = subaccount {
+        let len = subaccount.len().min(32);
+        out[..len].copy_from_slice(&subaccount[..len]);
+    }
+    (owner, out)
+}
+
+fn nat_to_u128(value: &Nat) -> u128 {
+    u128::try_from(value.0.clone()).unwrap_or(u128::MAX)
+}
+
+#[derive(Debug)]
+enum MoveError {
+    BadFee,
+    InsufficientFunds(u128),
+    Duplicate(u64),
+}
+
+impl Ledger {
+    /// ICRC-1 の重複排除と同じく、memo・created_at_time・送金元・送金先が揃った送金は1度しか載せない。
+    fn move_funds(&mut self, block: Block, fee: Option<&Nat>) -> Result<u64, MoveError> {
+        if fee.is_some_and(|fee| nat_to_u128(fee) != TRANSFER_FEE) {
+            return Err(MoveError::BadFee);
+        }
+        if block.created_at_time.is_some() {
+            if let Some(index) = self.blocks.iter().position(|existing| {
+                existing.memo == block.memo
+                    && existing.created_at_time == block.created_at_time
+                    && existing.from == block.from
+                    && existing.to == block.to
+                    && existing.amount == block.amount
+            }) {
+                return Err(MoveError::Duplicate(index as u64));
+            }
+        }
+        let balance = self.balances.get(&block.from).copied().unwrap_or(0);
+        let debit = block.amount.saturating_add(TRANSFER_FEE);
+        if balance < debit {
+            return Err(MoveError::InsufficientFunds(balance));
+        }
+        self.balances.insert(block.from, balance - debit);
+        let credited = self.balances.entry(block.to).or_insert(0);
+        *credited = credited.saturating_add(block.amount);
+        self.blocks.push(block);
+        Ok((self.blocks.len() - 1) as u64)
+    }
+}
+
+fn transfer_result(result: Result<u64, MoveError>) -> Result<Nat, TransferError> {
+    match result {
+        Ok(index) => Ok(Nat::from(index)),
+        Err(MoveError::BadFee) => Err(TransferError::BadFee {
+            expected_fee: Nat::from(TRANSFER_FEE),
+        }),
+        Err(MoveError::InsufficientFunds(balance)) => Err(TransferError::InsufficientFunds {
+            balance: Nat::from(balance),
+        }),
+        Err(MoveError::Duplicate(index)) => Err(TransferError::Duplicate {
+            duplicate_of: Nat::from(index),
+        }),
+    }
+}
+
+#[ic_cdk::init]
+fn init(args: InitArgs) {
+    LEDGER.with(|ledger| {
+        let mut ledger = ledger.borrow_mut();
+        for (account, amount) in args.initial_balances {
+            ledger.balances.insert(
+                account_key(account.owner, account.subaccount.as_deref()),
+                nat_to_u128(&amount),
+            );
+        }
+    });
+}
+
+#[ic_cdk::update]
+fn set_transfer_fault(fault: Option<TransferFault>) {
+    LEDGER.with(|ledger| ledger.borrow_mut().fault = fault);
+}
+
+#[ic_cdk::update(manual_reply = true)]
+fn icrc1_transfer(arg: TransferArg) -> PhantomData<Result<Nat, TransferError>> {
+    let fault = LEDGER.with(|ledger| ledger.borrow_mut().fault.take());
+    if fault == Some(TransferFault::GarbleWithoutCommit) {
+        ic_cdk::api::msg_reply(candid::encode_one(GARBLED_REPLY).expect("encode garbled reply"));
+        return PhantomData;
+    }
+    let block = Block {
+        timestamp: ic_cdk::api::time(),
+        from: account_key(ic_cdk::api::msg_caller(), arg.from_subaccount.as_deref()),
+        to: account_key(arg.to.owner, arg.to.subaccount.as_deref()),
+        amount: nat_to_u128(&arg.amount),
+        memo: arg.memo,
+        created_at_time: arg.created_at_time,
+    };
+    let result = LEDGER.with(|ledger| ledger.borrow_mut().move_funds(block, arg.fee.as_ref()));
+    let reply = if fault == Some(TransferFault::CommitThenGarble) {
+        candid::encode_one(GARBLED_REPLY)
+    } else {
+        candid::encode_one(transfer_result(result))
+    };
+    ic_cdk::api::msg_reply(reply.expect("encode transfer reply"));
+    PhantomData
+}
+
+#[ic_cdk::update]
+fn icrc2_transfer_from(args: TransferFromArgs) -> Result<Nat, TransferFromError> {
+    let block = Block {
+        timestamp: ic_cdk::api::time(),
+        from: account_key(args.from.owner, args.from.subaccount.as_deref()),
+        to: account_key(args.to.owner, args.to.subaccount.as_deref()),
+        amount: nat_to_u128(&args.amount),
+        memo: args.memo,
+        created_at_time: args.created_at_time,
+    };
+    // 承認額は見ない。E2E で確かめたいのは unwrap 側の送金だけなので、引き取りは常に通す。
+    match LEDGER.with(|ledger| ledger.borrow_mut().move_funds(block, args.fee.as_ref())) {
+        Ok(index) => Ok(Nat::from(index)),
+        Err(MoveError::BadFee) => Err(TransferFromError::BadFee {
+            expected_fee: Nat::from(TRANSFER_FEE),
+        }),
+        Err(MoveError::InsufficientFunds(balance)) => Err(TransferFromError::InsufficientFunds {
+            balance: Nat::from(balance),
+        }),
+        Err(MoveError::Duplicate(index)) => Err(TransferFromError::Duplicate {
+            duplicate_of: Nat::from(index),
+        }),
+    }
+}
+
+#[ic_cdk::update]
+fn icrc2_approve(_args: ApproveArgs) -> Result<Nat, ApproveError> {
+    Ok(Nat::from(0u8))
+}
+
+#[ic_cdk::query]
+fn icrc1_balance_of(account: Account) -> Nat {
+    let key = account_key(account.owner, account.subaccount.as_deref());
+    Nat::from(LEDGER.with(|ledger| ledger.borrow().balances.get(&key).copied().unwrap_or(0)))
+}
+
+#[ic_cdk::query]
+fn icrc1_fee() -> Nat {
+    Nat::from(TRANSFER_FEE)
+}
+
+#[ic_cdk::query]
+fn icrc1_decimals() -> u8 {
+    DECIMALS
+}
+
+#[ic_cdk::query]
+fn icrc1_metadata() -> Vec<(String, MetadataValue)> {
+    vec![
+        (
+            "icrc1:decimals".to_string(),
+            MetadataValue::Nat(Nat::from(DECIMALS)),
+        ),
+        (
+            "icrc1:name".to_string(),
+            MetadataValue::Text("Fake Ledger".to_string()),
+        ),
+        (
+            "icrc1:symbol".to_string(),
+            MetadataValue::Text("FAKE".to_string()),
+        ),
+        (
+            "icrc1:fee".to_string(),
+            MetadataValue::Nat(Nat::from(TRANSFER_FEE)),
+        ),
+    ]
+}
+
+#[ic_cdk::query]
+fn icrc3_get_blocks(args: Vec<GetBlocksArgs>) -> GetBlocksResult {
+    LEDGER.with(|ledger| {
+        let ledger = ledger.borrow();
+        let log_length = ledger.blocks.len() as u64;
+        let mut blocks = Vec::new();
+        for arg in args {
+            let start = u64::try_from(arg.start.0).unwrap_or(u64::MAX);
+            let length = u64::try_from(arg.length.0).unwrap_or(u64::MAX);
+            let end = start.saturating_add(length).min(log_length);
+            for index in start..end {
+                blocks.push(BlockWithId {
+                    id: Nat::from(index),
+                    block: block_value(&ledger.blocks[index as usize]),
+                });
+            }
+        }
+        GetBlocksResult {
+            log_length: Nat::from(log_length),
+            blocks,
+            archived_blocks: Vec::new(),
+        }
+    })
+}
+
+fn account_value(key: &AccountKey) -> Value {
+    let mut parts = vec![Value::Blob(key.0.as_slice().to_vec())];
+    if key.1 != [0u8; 32] {
+        parts.push(Value::Blob(key.1.to_vec()));
+    }
+    Value::Array(parts)
+}
+
+/// ICRC-3 の generic block。tx の ts は created_at_time、外側の ts は ledger が載せた時刻。
+fn block_value(block: &Block) -> Value {
+    let mut tx = vec![
+        ("op".to_string(), Value::Text("xfer".to_string())),
+        ("amt".to_string(), Value::Nat(Nat::from(block.amount))),
+        ("from".to_string(), account_value(&block.from)),
+        ("to".to_string(), account_value(&block.to)),
+    ];
+    if let Some(memo) = &block.memo {
+        tx.push(("memo".to_string(), Value::Blob(memo.clone())));
+    }
+    if let Some(created_at_time) = block.created_at_time {
+        tx.push(("ts".to_string(), Value::Nat(Nat::from(created_at_time))));
+    }
+    Value::Map(vec![
+        ("ts".to_string(), Value::Nat(Nat::from(block.timestamp))),
+        ("tx".to_string(), Value::Map(tx)),
+    ])
+}
@@ -2,7 +2,7 @@
 //! 何を: 統合版 gateway の wrap / unwrap 経路を運用に近い形で固定
 //! なぜ: wrap統合後の ledger pull、mint tx、unwrap dispatch の実挙動を回帰から守るため
 
-use candid::{CandidType, Decode, Deserialize, Encode, Nat, Principal};
+use candid::{CandidType, Decode, Deserialize, Encode, Int, Nat, Principal};
 use evm_core::hash;
 use evm_core::kasane_precompiles::{NATIVE_WITHDRAW_PRECOMPILE_ADDRESS, WRAP_PRECOMPILE_ADDRESS};
 use pocket_ic::PocketIc;
@@ -37,6 +37,8 @@ struct WrapConfigArgs {
 }
 
 const WRAP_AMOUNT_E8S: u128 = 1_000_000_000_000u128;
+// 試験用 ledger と fake-ledger の送金手数料。unwrap の受取額はこの分だけ減る。
+const LEDGER_FEE_E8S: u128 = 10;
 const TEST_ASSET_DECIMALS: u8 = 8;
 const TEST_LEDGER_BALANCE: u128 = 10_000_000_000_000u128;
 const TEST_GENESIS_BALANCE_WEI: u128 = 10_000_000_000_000_000_000_000_000u128;
@@ -138,12 +140,13 @@ enum ApiError {
     Internal(ApiErrorDetail),
 }
 
-#[derive(Clone, Debug, CandidType, Deserialize, Eq, PartialEq)]
+#[derive(Clone, Copy, Debug, CandidType, Deserialize, Eq, PartialEq)]
 enum RequestDispatchStatusView {
     Queued,
     Dispatching,
     Dispatched,
     DispatchFailed,
+    DispatchUncertain,
 }
 
 #[derive(Clone, Copy, Debug, CandidType, Deserialize, Eq, PartialEq)]
@@ -202,6 +205,56 @@ struct GetUnwrapRequirementsOk {
     readiness: UnwrapReadiness,
 }
 
+#[derive(Clone, Debug, CandidType, Deserialize, Eq, PartialEq)]
+struct FakeLedgerInitArgs {
+    initial_balances: Vec<(LedgerAccount, Nat)>,
+}
+
+#[derive(Clone, Copy, Debug, CandidType, Deserialize, Eq, PartialEq)]
+enum FakeTransferFault {
+    CommitThenGarble,
+    GarbleWithoutCommit,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize, Eq, PartialEq)]
+struct ReconcileUnwrapRequestArgs {
+    request_id: Vec<u8>,
+    scan_before: Option<u64>,
+    max_blocks: u32,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize, Eq, PartialEq)]
+enum UnwrapReconcileVerdictView {
+    Dispatched { block_index: u64 },
+    SafeToRetry,
+    Incomplete { scan_before: u64 },
+    OwnedByRecipient,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize, Eq, PartialEq)]
+struct ReconcileUnwrapRequestOk {
+    verdict: UnwrapReconcileVerdictView,
+    request: RequestOverview,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+struct AssetReservesView {
+    asset_id: Principal,
+    discrepancy: Option<Int>,
+    error: Option<String>,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+struct ReservesReportView {
+    wrap_paused: bool,
+    assets: Vec<AssetReservesView>,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize, Eq, PartialEq)]
+struct RetryRequestArgs {
+    request_id: Vec<u8>,
+}
+
 #[derive(Clone, Debug, CandidType, Deserialize, Eq, PartialEq)]
 struct DispatchUnwrapRequestArgs {
     request_id: Vec<u8>,
@@ -401,6 +454,15 @@ fn mock_ledger_wasm_path() -> PathBuf {
     path
 }
 
+fn fake_ledger_wasm_path() -> PathBuf {
+    std::env::var_os("FAKE_LEDGER_WASM")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| {
+            PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+                .join("fake-ledger/target/wasm32-unknown-unknown/release/fake_ledger.wasm")
+        })
+}
+
 fn read_wasm(path: PathBuf) -> Vec<u8> {
     if !path.exists() {
         panic!("wasm not found: build release wasm first: {path:?}");
@@ -487,6 +549,136 @@ fn install_integrated_pair_with_native(pic: &PocketIc) -> (Principal, Principal,
     (gateway_id, fee_ledger_id, native_ledger_id)
 }
 
+/// fee / native / wrap 対象の ledger をすべて偽 ledger 1つで賄う。
+fn install_gateway_with_fake_ledger(pic: &PocketIc) -> (Principal, Principal) {
+    let gateway_id = pic.create_canister();
+    let ledger_id = pic.create_canister();
+    for canister_id in [gateway_id, ledger_id] {
+        pic.add_cycles(canister_id, 5_000_000_000_000u128);
+    }
+    let caller = test_caller();
+    let caller_evm = hash::derive_evm_address_from_principal(caller.as_slice())
+        .expect("derive caller evm address");
+    let ledger_init = FakeLedgerInitArgs {
+        initial_balances: vec![(
+            LedgerAccount {
+                owner: gateway_id,
+                subaccount: None,
+            },
+            Nat::from(TEST_LEDGER_BALANCE),
+        )],
+    };
+    let gateway_init = Some(GatewayInitArgs {
+        genesis_balances: vec![GenesisBalanceView {
+            address: caller_evm.to_vec(),
+            amount: TEST_GENESIS_BALANCE_WEI,
+        }],
+        wrap_canister_id: gateway_id,
+        wrap_factory_address: predict_create_address(caller_evm, 0).to_vec(),
+        wrap_config: Some(WrapConfigArgs {
+            fee_ledger_canister: ledger_id,
+            native_ledger_canister: ledger_id,
+            cycle_fee_e8s: 1_000_000,
+            gas_price_buffer_bps: 12_000,
+            allowed_assets: vec![ledger_id],
+        }),
+        query_instruction_soft_limit: None,
+        update_instruction_soft_limit: None,
+    });
+    pic.install_canister(
+        ledger_id,
+        read_wasm(fake_ledger_wasm_path()),
+        Encode!(&ledger_init).expect("encode fake ledger init"),
+        None,
+    );
+    pic.install_canister(
+        gateway_id,
+        read_wasm(gateway_wasm_path()),
+        Encode!(&gateway_init).expect("encode gateway init"),
+        None,
+    );
+    pic.set_controllers(gateway_id, Some(Principal::anonymous()), vec![caller])
+        .unwrap_or_else(|err| panic!("set gateway controllers failed: {err}"));
+    settle(pic, 6);
+    (gateway_id, ledger_id)
+}
+
+fn set_fake_ledger_fault(pic: &PocketIc, ledger_id: Principal, fault: Option<FakeTransferFault>) {
+    pic.update_call(
+        ledger_id,
+        test_caller(),
+        "set_transfer_fault",
+        Encode!(&fault).expect("encode transfer fault"),
+    )
+    .unwrap_or_else(|err| panic!("set_transfer_fault call failed: {err}"));
+}
+
+fn dispatch_unwrap_directly(
+    pic: &PocketIc,
+    gateway_id: Principal,
+    asset_id: Principal,
+    request_id: &[u8],
+    recipient: Principal,
+) {
+    let out = pic
+        .update_call(
+            gateway_id,
+            gateway_id,
+            "dispatch_unwrap_request",
+            Encode!(&DispatchUnwrapRequestArgs {
+                request_id: request_id.to_vec(),
+                asset_id,
+                amount_e8s: Nat::from(WRAP_AMOUNT_E8S),
+                recipient,
+            })
+            .expect("encode dispatch unwrap"),
+        )
+        .unwrap();
+    let result: Result<DispatchUnwrapRequestOk, ApiError> = Decode!(
+        &out,
+        Result<DispatchUnwrapRequestOk, ApiError>
+    )
+    .expect("decode dispatch unwrap");
+    result.expect("dispatch should accept queue insertion");
+}
+
+fn reconcile_unwrap(
+    pic: &PocketIc,
+    gateway_id: Principal,
+    request_id: &[u8],
+) -> ReconcileUnwrapRequestOk {
+    let out = pic
+        .update_call(
+            gateway_id,
+            test_caller(),
+            "reconcile_unwrap_request",
+            Encode!(&ReconcileUnwrapRequestArgs {
+                request_id: request_id.to_vec(),
+                scan_before: None,
+                max_blocks: 100,
+            })
+            .expect("encode reconcile"),
+        )
+        .unwrap_or_else(|err| panic!("reconcile_unwrap_request call failed: {err}"));
+    let result: Result<ReconcileUnwrapRequestOk, String> =
+        Decode!(&out, Result<ReconcileUnwrapRequestOk, String>).expect("decode reconcile");
+    result.unwrap_or_else(|err| panic!("reconcile_unwrap_request rejected: {err}"))
+}
+
+fn check_reserves(pic: &PocketIc, gateway_id: Principal) -> ReservesReportView {
+    let out = pic
+        .update_call(
+            gateway_id,
+            test_caller(),
+            "check_reserves",
+            Encode!().expect("encode check_reserves"),
+        )
+        .unwrap_or_else(|err| panic!("check_reserves call failed: {err}"));
+    let result: Result<ReservesReportView, String> =
+        Decode!(&out, Result<ReservesReportView, String>).expect("decode check_reserves");
+    result.unwrap_or_else(|err| panic!("check_reserves rejected: {err}"))
+}
+
 fn set_allowed_assets(pic: &PocketIc, wrap_id: Principal, assets: Vec<Principal>) {
     let out = pic
         .update_call(
@@ -513,7 +705,7 @@ fn build_ledger_init(
             subaccount: None,
         },
         fee_collector_account: None,
-        transfer_fee: Nat::from(10u64),
+        transfer_fee: Nat::from(LEDGER_FEE_E8S),
         decimals: Some(TEST_ASSET_DECIMALS),
         max_memo_length: None,
         token_symbol: "LICP".to_string(),
@@ -911,6 +1103,25 @@ fn wait_for_unwrap_status(
     panic!("unwrap request did not reach expected status; last={last:?}");
 }
 
+fn wait_for_unwrap_dispatch_status(
+    pic: &PocketIc,
+    wrap_id: Principal,
+    request_id: &[u8],
+    expected: RequestDispatchStatusView,
+) -> RequestOverview {
+    let mut last = None;
+    for _ in 0..20 {
+        settle(pic, 1);
+        if let Some(overview) = wrap_get_request(pic, wrap_id, request_id) {
+            last = Some(overview.clone());
+            if overview.dispatch_status == Some(expected) {
+                return overview;
+            }
+        }
+    }
+    panic!("unwrap dispatch did not reach expected status; last={last:?}");
+}
+
 fn wrap_get_unwrap_requirements(
     pic: &PocketIc,
     wrap_id: Principal,
@@ -1291,12 +1502,30 @@ fn integrated_gateway_wrap_and_unwrap_completes_with_single_canister() {
     assert!(unwrap_overview.ledger_tx_id.is_some());
     assert_eq!(
         ledger_balance_of(&pic, fee_ledger_id, recipient) - recipient_before,
-        WRAP_AMOUNT_E8S
+        WRAP_AMOUNT_E8S - LEDGER_FEE_E8S
     );
     assert_eq!(
         wrapped_token_balance_of(&pic, gateway_id, token, caller_evm),
         0
     );
+
+    // 払い出しの ledger 手数料は受取額から引かれ、預かりは burn した量だけ減る。
+    let reserves = check_reserves(&pic, gateway_id);
+    assert!(
+        !reserves.wrap_paused,
+        "unwrap must not trip the reserves pause"
+    );
+    let asset = reserves
+        .assets
+        .iter()
+        .find(|asset| asset.asset_id == fee_ledger_id)
+        .expect("wrapped asset must be reported");
+    assert_eq!(asset.error, None);
+    let discrepancy = asset.discrepancy.clone().expect("discrepancy");
+    assert!(
+        discrepancy >= Int::from(0),
+        "unexpected reserves shortfall: {discrepancy}"
+    );
 }
 
 #[test]
@@ -1638,3 +1867,73 @@ fn direct_unwrap_dispatch_rejects_non_wrap_caller() {
         other => panic!("unexpected dispatch result: {other:?}"),
     }
 }
+
+#[test]
+fn uncertain_unwrap_dispatch_is_reconciled_against_fake_ledger() {
+    let pic = PocketIc::new();
+    let (gateway_id, ledger_id) = install_gateway_with_fake_ledger(&pic);
+    let recipient = Principal::self_authenticating(b"reconcile-recipient");
+
+    // 送金は ledger に載ったが、応答が読めずに結果不明になる
+    set_fake_ledger_fault(&pic, ledger_id, Some(FakeTransferFault::CommitThenGarble));
+    let committed = vec![0xc1u8; 32];
+    dispatch_unwrap_directly(&pic, gateway_id, ledger_id, &committed, recipient);
+    let uncertain = wait_for_unwrap_dispatch_status(
+        &pic,
+        gateway_id,
+        &committed,
+        RequestDispatchStatusView::DispatchUncertain,
+    );
+    assert_eq!(uncertain.ledger_tx_id, None);
+    assert_eq!(
+        ledger_balance_of(&pic, ledger_id, recipient),
+        WRAP_AMOUNT_E8S - LEDGER_FEE_E8S
+    );
+    let reconciled = reconcile_unwrap(&pic, gateway_id, &committed);
+    assert!(matches!(
+        reconciled.verdict,
+        UnwrapReconcileVerdictView::Dispatched { .. }
+    ));
+    assert_eq!(reconciled.request.status, WrapRequestStatus::Succeeded);
+    assert!(reconciled.request.ledger_tx_id.is_some());
+
+    // 送金されないまま応答が読めなかった request は、照合後に再送して1回だけ届く
+    set_fake_ledger_fault(
+        &pic,
+        ledger_id,
+        Some(FakeTransferFault::GarbleWithoutCommit),
+    );
+    let dropped = vec![0xc2u8; 32];
+    dispatch_unwrap_directly(&pic, gateway_id, ledger_id, &dropped, recipient);
+    wait_for_unwrap_dispatch_status(
+        &pic,
+        gateway_id,
+        &dropped,
+        RequestDispatchStatusView::DispatchUncertain,
+    );
+    let reconciled = reconcile_unwrap(&pic, gateway_id, &dropped);
+    assert_eq!(reconciled.verdict, UnwrapReconcileVerdictView::SafeToRetry);
+    assert_eq!(
+        reconciled.request.dispatch_status,
+        Some(RequestDispatchStatusView::DispatchFailed)
+    );
+    let out = pic
+        .update_call(
+            gateway_id,
+            test_caller(),
+            "retry_request",
+            Encode!(&RetryRequestArgs {
+                request_id: dropped.clone(),
+            })
+            .expect("encode retry"),
+        )
+        .unwrap();
+    let retried: Result<RequestOverview, ApiError> =
+        Decode!(&out, Result<RequestOverview, ApiError>).expect("decode retry");
+    retried.expect("retry after reconcile should be accepted");
+    wait_for_unwrap_status(&pic, gateway_id, &dropped, WrapRequestStatus::Succeeded);
+    assert_eq!(
+        ledger_balance_of(&pic, ledger_id, recipient),
+        (WRAP_AMOUNT_E8S - LEDGER_FEE_E8S) * 2
+    );
+}
@@ -0,0 +1,286 @@
+//! どこで: archive canister の取り込み / 何を: export_blocks の chunk 列を block ごとの4 segment へ組み立てる / なぜ: 1回の応答に収まらない block も、応答をまたいで欠けなく保存するため
+
+use ic_evm_rpc_types::{ExportChunkView, ExportCursorView};
+
+pub const SEGMENT_COUNT: usize = 4;
+const LAST_SEGMENT: u8 = 3;
+
+/// 全 segment を受け取り終えた block。segment は export_blocks と同じ並び
+/// （0: block / 1: receipts / 2: tx_index / 3: internal_traces）。
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AssembledBlock {
+    pub block_number: u64,
+    pub segments: [Vec<u8>; SEGMENT_COUNT],
+}
+
+/// 組み立て途中の block と、次に要求する cursor。
+/// export_blocks は中身が空の segment を chunk にしないため、飛ばされた segment は空とみなす。
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BlockAssembler {
+    block_number: u64,
+    segment: u8,
+    offset: u32,
+    // 現在の segment の全長。まだ chunk を受け取っていなければ None。
+    segment_len: Option<u32>,
+    segments: [Vec<u8>; SEGMENT_COUNT],
+}
+
+impl BlockAssembler {
+    pub fn new(block_number: u64) -> Self {
+        Self {
+            block_number,
+            segment: 0,
+            offset: 0,
+            segment_len: None,
+            segments: Default::default(),
+        }
+    }
+
+    pub fn block_number(&self) -> u64 {
+        self.block_number
+    }
+
+    pub fn cursor(&self) -> ExportCursorView {
+        ExportCursorView {
+            block_number: self.block_number,
+            segment: self.segment,
+            byte_offset: self.offset,
+        }
+    }
+
+    /// export_blocks の1応答を順に当てはめ、完成した block を返す。
+    /// 途中の不整合は Err にし、呼び出し側は現在の block を最初から取り直す。
+    pub fn apply(
+        &mut self,
+        chunks: &[ExportChunkView],
+        next_cursor: Option<&ExportCursorView>,
+    ) -> Result<Vec<AssembledBlock>, &'static str> {
+        let mut done = Vec::new();
+        for chunk in chunks {
+            if chunk.segment == 0 && chunk.start == 0 && self.has_started() {
+                done.push(self.finish_block()?);
+            }
+            if chunk.segment > LAST_SEGMENT {
+                return Err("archive.export.segment_out_of_range");
+            }
+            if chunk.segment < self.segment {
+                return Err("archive.export.segment_backwards");
+            }
+            if chunk.segment > self.segment {
+                self.advance_segment(chunk.segment)?;
+            }
+            if chunk.start != self.offset {
+                return Err("archive.export.chunk_gap");
+            }
+            let end = u64::from(chunk.start) + chunk.bytes.len() as u64;
+            if end > u64::from(chunk.payload_len) {
+                return Err("archive.export.chunk_overflow");
+            }
+            self.segments[usize::from(chunk.segment)].extend_from_slice(&chunk.bytes);
+            self.offset = u32::try_from(end).map_err(|_| "archive.export.chunk_overflow")?;
+            self.segment_len = Some(chunk.payload_len);
+        }
+        let Some(next) = next_cursor else {
+            return Ok(done);
+        };
+        if next.block_number > self.block_number {
+            if next.block_number != self.block_number.saturating_add(1)
+                || next.segment != 0
+                || next.byte_offset != 0
+            {
+                return Err("archive.export.cursor_skipped");
+            }
+            done.push(self.finish_block()?);
+            return Ok(done);
+        }
+        if next.block_number < self.block_number || next.segment < self.segment {
+            return Err("archive.export.cursor_backwards");
+        }
+        if next.segment > self.segment {
+            // 区切りちょうどで止まると、cursor は次の segment の先頭を指す。
+            self.advance_segment(next.segment)?;
+        }
+        if next.byte_offset != self.offset {
+            return Err("archive.export.cursor_mismatch");
+        }
+        // 最後の segment を読み切った位置で止まると cursor は同じ block を指したままになり、
+        // 次の export は先頭が空 chunk だけになって Limit を返しうるため、ここで閉じる。
+        if self.segment == LAST_SEGMENT && self.segment_len == Some(self.offset) {
+            done.push(self.finish_block()?);
+        }
+        Ok(done)
+    }
+
+    fn has_started(&self) -> bool {
+        self.segment > 0 || self.offset > 0
+    }
+
+    fn segment_complete(&self) -> bool {
+        self.offset == self.segment_len.unwrap_or(0)
+    }
+
+    fn advance_segment(&mut self, segment: u8) -> Result<(), &'static str> {
+        if !self.segment_complete() {
+            return Err("archive.export.segment_incomplete");
+        }
+        self.segment = segment;
+        self.offset = 0;
+        self.segment_len = None;
+        Ok(())
+    }
+
+    fn finish_block(&mut self) -> Result<AssembledBlock, &'static str> {
+        if !self.segment_complete() {
+            return Err("archive.export.segment_incomplete");
+        }
+        if self.segments[0].is_empty() {
+            return Err("archive.export.block_missing");
+        }
+        let block = AssembledBlock {
+            block_number: self.block_number,
+            segments: std::mem::take(&mut self.segments),
+        };
+        *self = Self::new(self.block_number.saturating_add(1));
+        Ok(block)
+    }
+}
+
+/// receipts segment の1件（tx_id と ReceiptLike の bytes）。
+pub type ReceiptEntry<'a> = ([u8; 32], &'a [u8]);
+
+/// receipts segment（tx_id 32byte / 長さ u32 BE / ReceiptLike）を tx ごとに切り出す。
+pub fn receipt_entries(receipts: &[u8]) -> Result<Vec<ReceiptEntry<'_>>, &'static str> {
+    let mut out = Vec::new();
+    let mut rest = receipts;
+    while !rest.is_empty() {
+        let (tx_id, tail) = rest
+            .split_first_chunk::<32>()
+            .ok_or("archive.receipts.truncated")?;
+        let (len, tail) = tail
+            .split_first_chunk::<4>()
+            .ok_or("archive.receipts.truncated")?;
+        let len =
+            usize::try_from(u32::from_be_bytes(*len)).map_err(|_| "archive.receipts.truncated")?;
+        if tail.len() < len {
+            return Err("archive.receipts.truncated");
+        }
+        let (receipt, tail) = tail.split_at(len);
+        out.push((*tx_id, receipt));
+        rest = tail;
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{receipt_entries, BlockAssembler};
+    use ic_evm_rpc_types::{ExportChunkView, ExportCursorView};
+
+    fn chunk(segment: u8, start: u32, bytes: &[u8], payload_len: u32) -> ExportChunkView {
+        ExportChunkView {
+            segment,
+            start,
+            bytes: bytes.to_vec(),
+            payload_len,
+        }
+    }
+
+    fn cursor(block_number: u64, segment: u8, byte_offset: u32) -> ExportCursorView {
+        ExportCursorView {
+            block_number,
+            segment,
+            byte_offset,
+        }
+    }
+
+    #[test]
+    fn assembles_blocks_across_responses_and_skipped_segments() {
+        let mut assembler = BlockAssembler::new(7);
+        // block 7: segment 0 を2応答に分け、segment 1 で切れる
+        let done = assembler
+            .apply(&[chunk(0, 0, b"blo", 5)], Some(&cursor(7, 0, 3)))
+            .expect("first");
+        assert!(done.is_empty());
+        let done = assembler
+            .apply(
+                &[chunk(0, 3, b"ck", 5), chunk(1, 0, b"rc", 2)],
+                Some(&cursor(7, 2, 0)),
+            )
+            .expect("second");
+        assert!(done.is_empty());
+        assert_eq!(assembler.cursor().segment, 2);
+        // segment 2/3 は空。続けて block 8 は segment 0 だけ。
+        let done = assembler
+            .apply(
+                &[chunk(3, 0, b"tr", 2), chunk(0, 0, b"b8", 2)],
+                Some(&cursor(9, 0, 0)),
+            )
+            .expect("third");
+        assert_eq!(done.len(), 2);
+        assert_eq!(done[0].block_number, 7);
+        assert_eq!(done[0].segments[0], b"block".to_vec());
+        assert_eq!(done[0].segments[1], b"rc".to_vec());
+        assert!(done[0].segments[2].is_empty());
+        assert_eq!(done[0].segments[3], b"tr".to_vec());
+        assert_eq!(done[1].block_number, 8);
+        assert_eq!(done[1].segments[0], b"b8".to_vec());
+        assert_eq!(assembler.block_number(), 9);
+    }
+
+    #[test]
+    fn closes_block_when_cursor_stops_at_end_of_last_segment() {
+        let mut assembler = BlockAssembler::new(4);
+        let done = assembler
+            .apply(
+                &[chunk(0, 0, b"b4", 2), chunk(3, 0, b"t", 1)],
+                Some(&cursor(4, 3, 1)),
+            )
+            .expect("apply");
+        assert_eq!(done.len(), 1);
+        assert_eq!(done[0].segments[3], b"t".to_vec());
+        assert_eq!(assembler.cursor().block_number, 5);
+        // 末尾の block が segment 2 の終わりで切れた場合は、segment 3 の長さが分かるまで閉じない
+        let mut pending = BlockAssembler::new(4);
+        let done = pending
+            .apply(&[chunk(0, 0, b"b4", 2)], Some(&cursor(4, 3, 0)))
+            .expect("apply");
+        assert!(done.is_empty());
+        assert_eq!(pending.cursor().segment, 3);
+    }
+
+    #[test]
+    fn rejects_gaps_and_incomplete_segments() {
+        let mut gap = BlockAssembler::new(1);
+        assert_eq!(
+            gap.apply(&[chunk(0, 2, b"xx", 4)], None),
+            Err("archive.export.chunk_gap")
+        );
+        let mut short = BlockAssembler::new(1);
+        assert_eq!(
+            short.apply(&[chunk(0, 0, b"xx", 4), chunk(1, 0, b"r", 1)], None),
+            Err("archive.export.segment_incomplete")
+        );
+        let mut skipped = BlockAssembler::new(1);
+        assert_eq!(
+            skipped.apply(&[chunk(0, 0, b"xx", 2)], Some(&cursor(3, 0, 0))),
+            Err("archive.export.cursor_skipped")
+        );
+    }
+
+    #[test]
+    fn splits_receipt_entries() {
+        let mut receipts = vec![0x11; 32];
+        receipts.extend_from_slice(&2u32.to_be_bytes());
+        receipts.extend_from_slice(b"r1");
+        receipts.extend_from_slice(&[0x22; 32]);
+        receipts.extend_from_slice(&0u32.to_be_bytes());
+        let entries = receipt_entries(&receipts).expect("entries");
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0], ([0x11; 32], &b"r1"[..]));
+        assert_eq!(entries[1].0, [0x22; 32]);
+        assert_eq!(
+            receipt_entries(&receipts[..40]),
+            Err("archive.receipts.truncated")
+        );
+    }
+}
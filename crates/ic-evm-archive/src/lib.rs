@@ -0,0 +1,260 @@
+//! どこで: archive canister 本体 / 何を: evm canister の export_blocks を取り込み、block と receipt を配信して ack を返す / なぜ: 本体が ack 済みの履歴だけを prune できるようにするため
+
+mod assemble;
+mod store;
+
+use assemble::{AssembledBlock, BlockAssembler};
+use candid::{CandidType, Principal};
+use ic_cdk::api::{canister_self, is_controller, msg_caller};
+use ic_evm_rpc_types::{ArchiveStatusView, ExportErrorView, ExportResponseView};
+use serde::Deserialize;
+use std::cell::RefCell;
+use std::time::Duration;
+
+const SYNC_INTERVAL_SECS: u64 = 60;
+// export_blocks の上限（MAX_EXPORT_BYTES）に合わせる。
+const EXPORT_MAX_BYTES: u32 = 1_500_000;
+// 1回の同期で呼ぶ export_blocks の上限。残りは次の周期で続ける。
+const MAX_EXPORT_CALLS_PER_SYNC: u32 = 8;
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct ArchiveInitArgs {
+    pub source_canister: Principal,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct ArchiveInfoView {
+    pub source_canister: Principal,
+    pub first_block: Option<u64>,
+    pub last_block: Option<u64>,
+    pub tx_count: u64,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct ArchivedBlockView {
+    pub block_number: u64,
+    pub block: Vec<u8>,
+    pub receipts: Vec<u8>,
+    pub tx_index: Vec<u8>,
+    pub internal_traces: Vec<u8>,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct ArchivedReceiptView {
+    pub block_number: u64,
+    pub receipt: Vec<u8>,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct SyncResultView {
+    pub stored_blocks: u32,
+    pub acked_through: Option<u64>,
+}
+
+thread_local! {
+    // heap のみ。upgrade 後は source の next_block から block 単位で取り直す。
+    static ASSEMBLER: RefCell<Option<BlockAssembler>> = const { RefCell::new(None) };
+    static SYNC_RUNNING: RefCell<bool> = const { RefCell::new(false) };
+}
+
+#[ic_cdk::init]
+fn init(args: ArchiveInitArgs) {
+    store::init_config(args.source_canister);
+    schedule_sync();
+}
+
+#[ic_cdk::post_upgrade]
+fn post_upgrade() {
+    schedule_sync();
+}
+
+fn schedule_sync() {
+    ic_cdk_timers::set_timer_interval_serial(Duration::from_secs(SYNC_INTERVAL_SECS), async || {
+        if let Err(err) = run_sync().await {
+            ic_cdk::println!("archive.sync_failed:{err}");
+        }
+    });
+}
+
+// 定期同期を待たずに1回分を進める（controller のみ）。
+#[ic_cdk::update]
+async fn sync() -> Result<SyncResultView, String> {
+    if !is_controller(&msg_caller()) {
+        return Err("auth.controller_required".to_string());
+    }
+    run_sync().await
+}
+
+#[ic_cdk::query]
+fn get_archive_info() -> ArchiveInfoView {
+    let config = store::config();
+    ArchiveInfoView {
+        source_canister: config.source(),
+        first_block: config.range.map(|(first, _)| first),
+        last_block: config.last_block(),
+        tx_count: config.tx_count,
+    }
+}
+
+#[ic_cdk::query]
+fn get_block(block_number: u64) -> Option<ArchivedBlockView> {
+    let [block, receipts, tx_index, internal_traces] = store::get_block(block_number)?;
+    Some(ArchivedBlockView {
+        block_number,
+        block,
+        receipts,
+        tx_index,
+        internal_traces,
+    })
+}
+
+#[ic_cdk::query]
+fn get_receipt(tx_id: Vec<u8>) -> Option<ArchivedReceiptView> {
+    let tx_id: [u8; 32] = tx_id.try_into().ok()?;
+    let (block_number, receipt) = store::get_receipt(tx_id)?;
+    Some(ArchivedReceiptView {
+        block_number,
+        receipt,
+    })
+}
+
+struct SyncGuard;
+
+impl SyncGuard {
+    fn acquire() -> Option<Self> {
+        SYNC_RUNNING.with(|running| {
+            let mut running = running.borrow_mut();
+            if *running {
+                return None;
+            }
+            *running = true;
+            Some(SyncGuard)
+        })
+    }
+}
+
+impl Drop for SyncGuard {
+    fn drop(&mut self) {
+        SYNC_RUNNING.with(|running| *running.borrow_mut() = false);
+    }
+}
+
+/// source の next_block から取り込み、保存済みの最後の block まで ack する。
+/// ack より先に保存するため、ack が失敗しても次回は保存済みの分をそのまま ack し直せる。
+async fn run_sync() -> Result<SyncResultView, String> {
+    let Some(_guard) = SyncGuard::acquire() else {
+        return Err("archive.sync_in_progress".to_string());
+    };
+    let source = store::config().source();
+    let status = fetch_archive_status(source).await?;
+    if status.canister_id != Some(canister_self()) {
+        return Err("archive.not_configured_at_source".to_string());
+    }
+    // 前回 ack に失敗した分は保存済みなので、その続きから取り込む。
+    let start_block = store::config()
+        .last_block()
+        .map_or(status.next_block, |last| {
+            status.next_block.max(last.saturating_add(1))
+        });
+    let mut assembler = ASSEMBLER
+        .with(|slot| slot.borrow_mut().take())
+        .filter(|assembler| assembler.block_number() == start_block)
+        .unwrap_or_else(|| BlockAssembler::new(start_block));
+    let mut stored_blocks = 0u32;
+    for _ in 0..MAX_EXPORT_CALLS_PER_SYNC {
+        let response = fetch_export(source, &assembler).await?;
+        let no_chunks = response.chunks.is_empty();
+        // 不整合なら組み立て途中の block は捨て、次回その先頭から取り直す。
+        let blocks = assembler
+            .apply(&response.chunks, response.next_cursor.as_ref())
+            .map_err(str::to_string)?;
+        stored_blocks = stored_blocks.saturating_add(store_blocks(&blocks)?);
+        if no_chunks || response.next_cursor.is_none() {
+            break;
+        }
+    }
+    ASSEMBLER.with(|slot| *slot.borrow_mut() = Some(assembler));
+    let acked_through = match store::config().last_block() {
+        Some(last) if last >= status.next_block => {
+            ack(source, last).await?;
+            Some(last)
+        }
+        _ => status.acked_through,
+    };
+    Ok(SyncResultView {
+        stored_blocks,
+        acked_through,
+    })
+}
+
+fn store_blocks(blocks: &[AssembledBlock]) -> Result<u32, String> {
+    for block in blocks {
+        store::put_block(block).map_err(str::to_string)?;
+    }
+    Ok(u32::try_from(blocks.len()).unwrap_or(u32::MAX))
+}
+
+async fn fetch_archive_status(source: Principal) -> Result<ArchiveStatusView, String> {
+    let call_result = ic_cdk::call::Call::unbounded_wait(source, "get_archive_status").await;
+    match call_result {
+        Ok(resp) => match resp.candid_tuple::<(ArchiveStatusView,)>() {
+            Ok((status,)) => Ok(status),
+            Err(err) => Err(format!("archive.status_decode_failed:{err}")),
+        },
+        Err(err) => Err(format!("archive.status_call_failed:{err}")),
+    }
+}
+
+async fn fetch_export(
+    source: Principal,
+    assembler: &BlockAssembler,
+) -> Result<ExportResponseView, String> {
+    let call_result = ic_cdk::call::Call::unbounded_wait(source, "export_blocks")
+        .with_args(&(Some(assembler.cursor()), EXPORT_MAX_BYTES))
+        .await;
+    match call_result {
+        Ok(resp) => match resp.candid_tuple::<(Result<ExportResponseView, ExportErrorView>,)>() {
+            Ok((Ok(response),)) => Ok(response),
+            Ok((Err(err),)) => Err(export_error_to_string(err)),
+            Err(err) => Err(format!("archive.export_decode_failed:{err}")),
+        },
+        Err(err) => Err(format!("archive.export_call_failed:{err}")),
+    }
+}
+
+fn export_error_to_string(err: ExportErrorView) -> String {
+    match err {
+        ExportErrorView::InvalidCursor { message } => {
+            format!("archive.export.invalid_cursor:{message}")
+        }
+        ExportErrorView::Pruned {
+            pruned_before_block,
+        } => format!("archive.export.pruned:{pruned_before_block}"),
+        ExportErrorView::MissingData { message } => {
+            format!("archive.export.missing_data:{message}")
+        }
+        ExportErrorView::Limit => "archive.export.limit".to_string(),
+    }
+}
+
+async fn ack(source: Principal, end_block: u64) -> Result<(), String> {
+    let call_result = ic_cdk::call::Call::unbounded_wait(source, "ack_archived_blocks")
+        .with_arg(end_block)
+        .await;
+    match call_result {
+        Ok(resp) => match resp.candid_tuple::<(Result<(), String>,)>() {
+            Ok((Ok(()),)) => Ok(()),
+            Ok((Err(err),)) => Err(format!("archive.ack_rejected:{err}")),
+            Err(err) => Err(format!("archive.ack_decode_failed:{err}")),
+        },
+        Err(err) => Err(format!("archive.ack_call_failed:{err}")),
+    }
+}
+
+ic_cdk::export_candid!();
+
+// NOTE: build-time only; keep out of production surface area.
+#[cfg(feature = "did-gen")]
+pub fn export_did() -> String {
+    __export_service()
+}
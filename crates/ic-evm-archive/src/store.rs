@@ -0,0 +1,195 @@
+//! どこで: archive canister の stable memory / 何を: 設定・block segment・tx索引を保持する / なぜ: upgrade をまたいで受け取った履歴を失わないため
+
+use crate::assemble::{receipt_entries, AssembledBlock, SEGMENT_COUNT};
+use candid::Principal;
+use ic_stable_structures::memory_manager::{MemoryId, MemoryManager, VirtualMemory};
+use ic_stable_structures::storable::Bound;
+use ic_stable_structures::{DefaultMemoryImpl, StableBTreeMap, StableCell, Storable};
+use std::borrow::Cow;
+use std::cell::RefCell;
+
+type Memory = VirtualMemory<DefaultMemoryImpl>;
+
+const CONFIG_MEMORY_ID: MemoryId = MemoryId::new(0);
+const SEGMENTS_MEMORY_ID: MemoryId = MemoryId::new(1);
+const TX_BLOCKS_MEMORY_ID: MemoryId = MemoryId::new(2);
+
+const MAX_PRINCIPAL_LEN: usize = 29;
+const ARCHIVE_CONFIG_SIZE: usize = 1 + MAX_PRINCIPAL_LEN + 1 + 8 + 8 + 8;
+
+/// 取り込み元と保存済み範囲。blocks は source の next_block から順に積むため、
+/// first..=last の間でも source 側で先に prune された番号は欠けうる。
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ArchiveConfig {
+    source_len: u8,
+    source: [u8; MAX_PRINCIPAL_LEN],
+    pub range: Option<(u64, u64)>,
+    pub tx_count: u64,
+}
+
+impl ArchiveConfig {
+    pub fn new(source: Principal) -> Self {
+        let bytes = source.as_slice();
+        let mut buf = [0u8; MAX_PRINCIPAL_LEN];
+        buf[..bytes.len()].copy_from_slice(bytes);
+        Self {
+            source_len: bytes.len() as u8,
+            source: buf,
+            range: None,
+            tx_count: 0,
+        }
+    }
+
+    pub fn source(&self) -> Principal {
+        Principal::from_slice(&self.source[..usize::from(self.source_len)])
+    }
+
+    pub fn last_block(&self) -> Option<u64> {
+        self.range.map(|(_, last)| last)
+    }
+}
+
+impl Default for ArchiveConfig {
+    fn default() -> Self {
+        Self::new(Principal::anonymous())
+    }
+}
+
+impl Storable for ArchiveConfig {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(self.into_bytes())
+    }
+
+    fn into_bytes(self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(ARCHIVE_CONFIG_SIZE);
+        out.push(self.source_len);
+        out.extend_from_slice(&self.source);
+        let (has_range, first, last) = match self.range {
+            Some((first, last)) => (1u8, first, last),
+            None => (0u8, 0, 0),
+        };
+        out.push(has_range);
+        out.extend_from_slice(&first.to_be_bytes());
+        out.extend_from_slice(&last.to_be_bytes());
+        out.extend_from_slice(&self.tx_count.to_be_bytes());
+        out
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        let data = bytes.as_ref();
+        if data.len() != ARCHIVE_CONFIG_SIZE {
+            ic_cdk::trap("archive_config: invalid length");
+        }
+        let source_len = data[0];
+        if usize::from(source_len) > MAX_PRINCIPAL_LEN {
+            ic_cdk::trap("archive_config: invalid principal length");
+        }
+        let mut source = [0u8; MAX_PRINCIPAL_LEN];
+        source.copy_from_slice(&data[1..1 + MAX_PRINCIPAL_LEN]);
+        let mut offset = 1 + MAX_PRINCIPAL_LEN;
+        let has_range = data[offset];
+        offset += 1;
+        let first = read_u64(data, offset);
+        let last = read_u64(data, offset + 8);
+        let tx_count = read_u64(data, offset + 16);
+        Self {
+            source_len,
+            source,
+            range: (has_range == 1).then_some((first, last)),
+            tx_count,
+        }
+    }
+
+    const BOUND: Bound = Bound::Bounded {
+        max_size: ARCHIVE_CONFIG_SIZE as u32,
+        is_fixed_size: true,
+    };
+}
+
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    let mut buf = [0u8; 8];
+    buf.copy_from_slice(&data[offset..offset + 8]);
+    u64::from_be_bytes(buf)
+}
+
+struct ArchiveStore {
+    config: StableCell<ArchiveConfig, Memory>,
+    // (block_number, segment) -> segment の中身。空の segment は保存しない。
+    segments: StableBTreeMap<(u64, u8), Vec<u8>, Memory>,
+    tx_blocks: StableBTreeMap<[u8; 32], u64, Memory>,
+}
+
+thread_local! {
+    static STORE: RefCell<ArchiveStore> = RefCell::new({
+        let manager = MemoryManager::init(DefaultMemoryImpl::default());
+        ArchiveStore {
+            config: StableCell::init(manager.get(CONFIG_MEMORY_ID), ArchiveConfig::default()),
+            segments: StableBTreeMap::init(manager.get(SEGMENTS_MEMORY_ID)),
+            tx_blocks: StableBTreeMap::init(manager.get(TX_BLOCKS_MEMORY_ID)),
+        }
+    });
+}
+
+pub fn init_config(source: Principal) {
+    STORE.with(|store| {
+        store.borrow_mut().config.set(ArchiveConfig::new(source));
+    });
+}
+
+pub fn config() -> ArchiveConfig {
+    STORE.with(|store| *store.borrow().config.get())
+}
+
+/// 完成した block を保存する。receipts の枠が壊れていれば何も書かずに Err を返す。
+pub fn put_block(block: &AssembledBlock) -> Result<(), &'static str> {
+    let entries = receipt_entries(&block.segments[1])?;
+    STORE.with(|store| {
+        let mut store = store.borrow_mut();
+        for (segment, bytes) in block.segments.iter().enumerate() {
+            if !bytes.is_empty() {
+                store
+                    .segments
+                    .insert((block.block_number, segment as u8), bytes.clone());
+            }
+        }
+        let mut added = 0u64;
+        for (tx_id, _) in &entries {
+            if store.tx_blocks.insert(*tx_id, block.block_number).is_none() {
+                added = added.saturating_add(1);
+            }
+        }
+        let mut config = *store.config.get();
+        config.range = Some(match config.range {
+            Some((first, last)) => (first.min(block.block_number), last.max(block.block_number)),
+            None => (block.block_number, block.block_number),
+        });
+        config.tx_count = config.tx_count.saturating_add(added);
+        store.config.set(config);
+    });
+    Ok(())
+}
+
+/// segment 0 が無ければ未保存の block とみなす。
+pub fn get_block(block_number: u64) -> Option<[Vec<u8>; SEGMENT_COUNT]> {
+    STORE.with(|store| {
+        let store = store.borrow();
+        store.segments.get(&(block_number, 0))?;
+        let mut out: [Vec<u8>; SEGMENT_COUNT] = Default::default();
+        for (segment, slot) in out.iter_mut().enumerate() {
+            if let Some(bytes) = store.segments.get(&(block_number, segment as u8)) {
+                *slot = bytes;
+            }
+        }
+        Some(out)
+    })
+}
+
+pub fn get_receipt(tx_id: [u8; 32]) -> Option<(u64, Vec<u8>)> {
+    let block_number = STORE.with(|store| store.borrow().tx_blocks.get(&tx_id))?;
+    let receipts = STORE.with(|store| store.borrow().segments.get(&(block_number, 1)))?;
+    let entries = receipt_entries(&receipts).ok()?;
+    entries
+        .into_iter()
+        .find(|(id, _)| *id == tx_id)
+        .map(|(_, receipt)| (block_number, receipt.to_vec()))
+}
@@ -0,0 +1,35 @@
+//! どこで: オフライン調査 / 何を: ダウンロードしたstable memoryイメージの検査結果を出力 / なぜ: 本番canisterに触れずに破損箇所とschema状態を確認するため
+//!
+//! 使い方: `cargo run -p ic-evm-gateway --features inspect-cli --bin inspect_stable_memory -- <image>`
+//! 終了コード: 0=問題なし, 1=整合性の問題あり, 2=イメージを読めない
+
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    let mut args = std::env::args().skip(1);
+    let (Some(path), None) = (args.next(), args.next()) else {
+        eprintln!("usage: inspect_stable_memory <stable-memory-image>");
+        return ExitCode::from(2);
+    };
+    let image = match std::fs::read(&path) {
+        Ok(bytes) => bytes,
+        Err(err) => {
+            eprintln!("failed to read {path}: {err}");
+            return ExitCode::from(2);
+        }
+    };
+    match evm_db::inspect::inspect_image(image) {
+        Ok(report) => {
+            print!("{report}");
+            if report.is_clean() {
+                ExitCode::SUCCESS
+            } else {
+                ExitCode::from(1)
+            }
+        }
+        Err(err) => {
+            eprintln!("{path}: {err}");
+            ExitCode::from(2)
+        }
+    }
+}
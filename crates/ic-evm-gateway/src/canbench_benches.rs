@@ -17,6 +17,8 @@ use std::sync::OnceLock;
 static NONCE_SEQ: AtomicU64 = AtomicU64::new(0);
 const UNSUPPORTED_TYPED_4844_PREFIX: [u8; 1] = [0x03];
 const ETH_CALL_FROM: [u8; 20] = [0x77u8; 20];
+const SMALL_READY_POOL_SIZE: u64 = 256;
+const LARGE_READY_POOL_SIZE: u64 = 1_024;
 const BENCH_LEGACY_RAW_TX: [u8; 104] = [
     248, 102, 128, 132, 119, 53, 148, 0, 130, 82, 8, 148, 17, 17, 17, 17, 17, 17, 17, 17, 17, 17,
     17, 17, 17, 17, 17, 17, 17, 17, 17, 17, 128, 128, 131, 10, 214, 118, 160, 231, 214, 114, 181,
@@ -72,6 +74,17 @@ fn produce_block_path() -> BenchResult {
     })
 }
 
+// 2 つの pool サイズを並べ、ready候補の選定コストが pool サイズに比例しないことを確認する。
+#[bench(raw)]
+fn produce_block_small_ready_pool_path() -> BenchResult {
+    produce_block_with_ready_pool(SMALL_READY_POOL_SIZE)
+}
+
+#[bench(raw)]
+fn produce_block_large_ready_pool_path() -> BenchResult {
+    produce_block_with_ready_pool(LARGE_READY_POOL_SIZE)
+}
+
 #[bench(raw)]
 fn state_root_migration_tick_path() -> BenchResult {
     bench_fn(|| {
@@ -106,6 +119,32 @@ fn submit_synthetic_tx() -> Result<evm_db::chain_data::TxId, chain::ChainError>
     })
 }
 
+fn produce_block_with_ready_pool(size: u64) -> BenchResult {
+    fill_ready_pool(size);
+    with_state_mut(|state| {
+        let mut chain_state = *state.chain_state.get();
+        chain_state.base_fee = chain_state.base_fee.saturating_add(1);
+        state.chain_state.set(chain_state);
+    });
+    bench_fn(|| {
+        let _ = chain::produce_block(1);
+    })
+}
+
+fn fill_ready_pool(size: u64) {
+    let canister = Principal::self_authenticating(b"canbench-canister");
+    for idx in 0..size {
+        let caller = Principal::self_authenticating(format!("canbench-pool-{idx}").as_bytes());
+        let mut tx = build_ic_tx_input(0);
+        tx.max_priority_fee_per_gas = 1_000_000_000 + u128::from(idx % 97);
+        let _ = chain::submit_tx_in(chain::TxIn::IcSynthetic {
+            caller_principal: caller.as_slice().to_vec(),
+            canister_id: canister.as_slice().to_vec(),
+            tx,
+        });
+    }
+}
+
 fn warm_submit_caller_cache() {
     let caller = Principal::self_authenticating(b"canbench-caller");
     let canister = Principal::self_authenticating(b"canbench-canister");
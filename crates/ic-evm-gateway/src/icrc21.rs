@@ -3,7 +3,7 @@
 //! なぜ: submit_ic_tx を人間可読に承認させるため
 
 use candid::{CandidType, Deserialize, Nat};
-use evm_core::kasane_precompiles::WRAP_PRECOMPILE_ADDRESS;
+use evm_core::kasane_precompiles::{NATIVE_WITHDRAW_PRECOMPILE_ADDRESS, WRAP_PRECOMPILE_ADDRESS};
 use evm_core::tx_decode::IcSyntheticTxInput;
 
 use crate::{parse_submit_ic_tx_args, SubmitIcTxArgsDto};
@@ -11,6 +11,10 @@ use crate::{parse_submit_ic_tx_args, SubmitIcTxArgsDto};
 const ICRC_10_URL: &str = "https://github.com/dfinity/ICRC/blob/main/ICRCs/ICRC-10/ICRC-10.md";
 const ICRC_21_URL: &str = "https://github.com/dfinity/wg-identity-authentication/blob/main/topics/ICRC-21/icrc_21_consent_msg.md";
 const ERC20_APPROVE_SELECTOR: [u8; 4] = [0x09, 0x5e, 0xa7, 0xb3];
+const COMPACT_PRINCIPAL_FIELD_LEN: usize = 1 + 29;
+const COMPACT_UNWRAP_V1_LEN: usize = 1 + COMPACT_PRINCIPAL_FIELD_LEN * 2 + 32;
+const COMPACT_NATIVE_WITHDRAW_V1_LEN: usize = 1 + COMPACT_PRINCIPAL_FIELD_LEN;
+const COMPACT_SUBACCOUNT_LEN: usize = 32;
 
 #[derive(Clone, Debug, CandidType, Deserialize, Eq, PartialEq)]
 pub struct StandardRecord {
@@ -122,6 +126,9 @@ fn describe_submit_ic_tx(tx: &IcSyntheticTxInput) -> Result<String, Icrc21Error>
     if tx.to == Some(WRAP_PRECOMPILE_ADDRESS.into_array()) {
         return describe_precompile_unwrap(tx);
     }
+    if tx.to == Some(NATIVE_WITHDRAW_PRECOMPILE_ADDRESS.into_array()) {
+        return describe_precompile_native_withdraw(tx);
+    }
     if is_erc20_approve(tx) {
         return describe_erc20_approve(tx);
     }
@@ -155,6 +162,7 @@ fn describe_precompile_unwrap(tx: &IcSyntheticTxInput) -> Result<String, Icrc21E
         - asset principal: `{}`\n\
         - amount_e8s: `{}`\n\
         - recipient principal: `{}`\n\
+        - recipient subaccount: `{}`\n\
         - nonce: `{}`\n\
         - gas limit: `{}`\n\
         - max fee per gas: `{}`\n\
@@ -162,6 +170,31 @@ fn describe_precompile_unwrap(tx: &IcSyntheticTxInput) -> Result<String, Icrc21E
         intent.asset_principal,
         intent.amount_e8s,
         intent.recipient_principal,
+        intent.recipient_subaccount,
+        tx.nonce,
+        tx.gas_limit,
+        tx.max_fee_per_gas,
+        tx.max_priority_fee_per_gas,
+    ))
+}
+
+fn describe_precompile_native_withdraw(tx: &IcSyntheticTxInput) -> Result<String, Icrc21Error> {
+    let recipient = decode_native_withdraw_payload(&tx.data)
+        .ok_or_else(|| unsupported("icrc21.native_withdraw_payload_invalid"))?;
+    Ok(format!(
+        "# Approve Kasane native withdrawal\n\n\
+        - method: `submit_ic_tx`\n\
+        - target: `Kasane native withdraw precompile`\n\
+        - value: `{}`\n\
+        - recipient principal: `{}`\n\
+        - recipient subaccount: `{}`\n\
+        - nonce: `{}`\n\
+        - gas limit: `{}`\n\
+        - max fee per gas: `{}`\n\
+        - max priority fee per gas: `{}`",
+        u256_to_decimal(&tx.value),
+        recipient.principal,
+        recipient.subaccount,
         tx.nonce,
         tx.gas_limit,
         tx.max_fee_per_gas,
@@ -197,20 +230,21 @@ struct UnwrapConsentView {
     asset_principal: String,
     amount_e8s: String,
     recipient_principal: String,
+    recipient_subaccount: String,
+}
+
+struct RecipientConsentView {
+    principal: String,
+    subaccount: String,
 }
 
 fn decode_unwrap_payload(data: &[u8]) -> Option<UnwrapConsentView> {
-    const MAX_PRINCIPAL_LEN: usize = 29;
-    if data.len() != 1 + (1 + MAX_PRINCIPAL_LEN) * 2 + 32 {
-        return None;
-    }
-    if data[0] != 1 {
-        return None;
-    }
+    let has_subaccount = compact_payload_has_subaccount(data, COMPACT_UNWRAP_V1_LEN)?;
     let mut offset = 1usize;
     let asset = read_principal_field(data, &mut offset)?;
     let amount = read_array_32(data, &mut offset)?;
     let recipient = read_principal_field(data, &mut offset)?;
+    let subaccount = read_optional_subaccount(data, &mut offset, has_subaccount)?;
     if offset != data.len() {
         return None;
     }
@@ -218,9 +252,56 @@ fn decode_unwrap_payload(data: &[u8]) -> Option<UnwrapConsentView> {
         asset_principal: candid::Principal::from_slice(&asset).to_text(),
         amount_e8s: u256_to_decimal(&amount),
         recipient_principal: candid::Principal::from_slice(&recipient).to_text(),
+        recipient_subaccount: format_subaccount(subaccount),
+    })
+}
+
+fn decode_native_withdraw_payload(data: &[u8]) -> Option<RecipientConsentView> {
+    let has_subaccount = compact_payload_has_subaccount(data, COMPACT_NATIVE_WITHDRAW_V1_LEN)?;
+    let mut offset = 1usize;
+    let recipient = read_principal_field(data, &mut offset)?;
+    let subaccount = read_optional_subaccount(data, &mut offset, has_subaccount)?;
+    if offset != data.len() {
+        return None;
+    }
+    Some(RecipientConsentView {
+        principal: candid::Principal::from_slice(&recipient).to_text(),
+        subaccount: format_subaccount(subaccount),
     })
 }
 
+/// 版 1 は subaccount 無しの固定長、版 2 は末尾に 32byte の subaccount が付く。
+fn compact_payload_has_subaccount(data: &[u8], v1_len: usize) -> Option<bool> {
+    match (data.first().copied()?, data.len()) {
+        (1, len) if len == v1_len => Some(false),
+        (2, len) if len == v1_len + COMPACT_SUBACCOUNT_LEN => Some(true),
+        _ => None,
+    }
+}
+
+fn read_optional_subaccount(
+    data: &[u8],
+    offset: &mut usize,
+    has_subaccount: bool,
+) -> Option<Option<[u8; 32]>> {
+    if !has_subaccount {
+        return Some(None);
+    }
+    let subaccount = read_array_32(data, offset)?;
+    Some(
+        subaccount
+            .iter()
+            .any(|&byte| byte != 0)
+            .then_some(subaccount),
+    )
+}
+
+fn format_subaccount(subaccount: Option<[u8; 32]>) -> String {
+    subaccount
+        .map(|value| format!("0x{}", bytes_to_hex(&value)))
+        .unwrap_or_else(|| "default".to_string())
+}
+
 struct Erc20ApproveView {
     spender: [u8; 20],
     amount: String,
@@ -0,0 +1,239 @@
+//! どこで: gateway certification surface
+//! 何を: ICRC-3 形式の certified tip、state root、直近 block/receipt の hash tree を保持
+//! なぜ: query 応答を update call なしで IC root key から検証できるようにするため
+
+use candid::{CandidType, Deserialize};
+use evm_core::chain;
+use evm_db::chain_data::TxId;
+use evm_db::stable_state::with_state;
+use ic_certification::{fork, labeled, leaf, pruned, AsHashTree, HashTree, RbTree};
+use serde::Serialize;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use verified_core::certified_log::{certified_log_needs_rebuild, certified_window_start};
+
+/// heap に持つ証明窓。1 block が最大 MAX_TXS_PER_BLOCK 件の receipt を抱えるため小さく保つ。
+pub(crate) const CERTIFIED_BLOCK_WINDOW: u64 = 32;
+
+const LABEL_BLOCKS: &[u8] = b"blocks";
+const LABEL_LAST_BLOCK_HASH: &[u8] = b"last_block_hash";
+const LABEL_LAST_BLOCK_INDEX: &[u8] = b"last_block_index";
+const LABEL_LAST_BLOCK_STATE_ROOT: &[u8] = b"last_block_state_root";
+const LABEL_RECEIPTS: &[u8] = b"receipts";
+
+#[derive(Clone, Debug, CandidType, Deserialize, Eq, PartialEq)]
+pub struct Icrc3DataCertificate {
+    pub certificate: Vec<u8>,
+    pub hash_tree: Vec<u8>,
+}
+
+thread_local! {
+    // heap のみ。upgrade 後は post_upgrade の refresh で stable の block から組み直す。
+    static CERTIFIED_LOG: RefCell<CertifiedLog> = RefCell::new(CertifiedLog::new());
+}
+
+struct CertifiedLog {
+    tip: Option<(u64, [u8; 32])>,
+    state_root: [u8; 32],
+    window: VecDeque<(u64, Vec<TxId>)>,
+    blocks: RbTree<Vec<u8>, Vec<u8>>,
+    receipts: RbTree<Vec<u8>, Vec<u8>>,
+}
+
+impl CertifiedLog {
+    fn new() -> Self {
+        Self {
+            tip: None,
+            state_root: [0u8; 32],
+            window: VecDeque::new(),
+            blocks: RbTree::new(),
+            receipts: RbTree::new(),
+        }
+    }
+
+    fn append_block(&mut self, number: u64) {
+        let Some(block) = chain::get_block(number) else {
+            return;
+        };
+        self.blocks
+            .insert(number.to_be_bytes().to_vec(), block.block_hash.to_vec());
+        for tx_id in block.tx_ids.iter() {
+            if let Some(receipt) = chain::get_receipt(tx_id) {
+                self.receipts.insert(
+                    tx_id.0.to_vec(),
+                    evm_core::hash::receipt_hash(&receipt).to_vec(),
+                );
+            }
+        }
+        self.window.push_back((number, block.tx_ids));
+    }
+
+    fn evict_before(&mut self, start: u64) {
+        while let Some((number, _)) = self.window.front() {
+            if *number >= start {
+                break;
+            }
+            let Some((number, tx_ids)) = self.window.pop_front() else {
+                break;
+            };
+            self.blocks.delete(&number.to_be_bytes());
+            for tx_id in tx_ids.iter() {
+                self.receipts.delete(&tx_id.0);
+            }
+        }
+    }
+
+    /// 開示しない subtree は pruned にするので、どの witness も root digest は同じになる。
+    fn tree(&self, blocks: Option<HashTree>, receipts: Option<HashTree>) -> HashTree {
+        let (number, hash) = self.tip.unwrap_or((0, [0u8; 32]));
+        fork(
+            fork(
+                labeled(
+                    LABEL_BLOCKS,
+                    blocks.unwrap_or_else(|| pruned(self.blocks.root_hash())),
+                ),
+                labeled(LABEL_LAST_BLOCK_HASH, leaf(hash.to_vec())),
+            ),
+            fork(
+                labeled(LABEL_LAST_BLOCK_INDEX, leaf(leb128_u64(number))),
+                fork(
+                    labeled(LABEL_LAST_BLOCK_STATE_ROOT, leaf(self.state_root.to_vec())),
+                    labeled(
+                        LABEL_RECEIPTS,
+                        receipts.unwrap_or_else(|| pruned(self.receipts.root_hash())),
+                    ),
+                ),
+            ),
+        )
+    }
+}
+
+/// head が動いた update の最後に呼ぶ。certified data は query からは書き換えられない。
+pub(crate) fn refresh_certified_tip() {
+    let (head, state_root) =
+        with_state(|state| (*state.head.get(), state.state_root_meta.get().state_root));
+    CERTIFIED_LOG.with(|log| {
+        let mut log = log.borrow_mut();
+        let cached = log.tip;
+        let mut rebuild = certified_log_needs_rebuild(
+            cached.map(|(number, _)| number),
+            head.number,
+            CERTIFIED_BLOCK_WINDOW,
+        );
+        // snapshot import で head が差し替わった場合など、前回の tip に繋がらなければ組み直す。
+        if let Some((number, hash)) = cached {
+            if !rebuild && number == head.number {
+                rebuild = hash != head.block_hash;
+            } else if !rebuild {
+                rebuild = chain::get_block(number + 1).map(|block| block.parent_hash) != Some(hash);
+            }
+        }
+        let start = if rebuild {
+            *log = CertifiedLog::new();
+            certified_window_start(head.number, CERTIFIED_BLOCK_WINDOW)
+        } else {
+            cached.map_or(0, |(number, _)| number.saturating_add(1))
+        };
+        for number in start..=head.number {
+            log.append_block(number);
+        }
+        log.evict_before(certified_window_start(head.number, CERTIFIED_BLOCK_WINDOW));
+        log.tip = Some((head.number, head.block_hash));
+        log.state_root = state_root;
+    });
+    set_certified_data(&certified_root());
+}
+
+pub(crate) fn tip_certificate() -> Option<Icrc3DataCertificate> {
+    let hash_tree = tip_hash_tree()?;
+    Some(Icrc3DataCertificate {
+        certificate: data_certificate()?,
+        hash_tree,
+    })
+}
+
+/// tip と state root だけを開示した木。blocks/receipts の subtree は digest だけ残す。
+pub(crate) fn tip_hash_tree() -> Option<Vec<u8>> {
+    CERTIFIED_LOG.with(|log| {
+        let log = log.borrow();
+        log.tip.map(|_| encode_hash_tree(&log.tree(None, None)))
+    })
+}
+
+/// certified data に載っている head の番号と state root。state proof はこの root から組む。
+pub(crate) fn certified_state_root() -> Option<(u64, [u8; 32])> {
+    CERTIFIED_LOG.with(|log| {
+        let log = log.borrow();
+        log.tip.map(|(number, _)| (number, log.state_root))
+    })
+}
+
+/// 窓の外の block では不在証明になる。呼び出し側は窓内の block から parent_hash を辿る。
+pub(crate) fn block_witness(number: u64) -> Vec<u8> {
+    CERTIFIED_LOG.with(|log| {
+        let log = log.borrow();
+        let blocks = log.blocks.witness(&number.to_be_bytes());
+        encode_hash_tree(&log.tree(Some(blocks), None))
+    })
+}
+
+pub(crate) fn receipt_witness(tx_id: &[u8]) -> Vec<u8> {
+    CERTIFIED_LOG.with(|log| {
+        let log = log.borrow();
+        let receipts = log.receipts.witness(tx_id);
+        encode_hash_tree(&log.tree(None, Some(receipts)))
+    })
+}
+
+/// certificate は query call の中でだけ得られる。update/host test では空を返す。
+pub(crate) fn data_certificate() -> Option<Vec<u8>> {
+    #[cfg(target_arch = "wasm32")]
+    {
+        ic_cdk::api::data_certificate()
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        None
+    }
+}
+
+fn set_certified_data(root: &[u8; 32]) {
+    #[cfg(target_arch = "wasm32")]
+    {
+        ic_cdk::api::certified_data_set(root);
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let _ = root;
+    }
+}
+
+pub(crate) fn certified_root() -> [u8; 32] {
+    CERTIFIED_LOG.with(|log| log.borrow().tree(None, None).digest())
+}
+
+fn encode_hash_tree(tree: &HashTree) -> Vec<u8> {
+    // IC の hash tree と同じく self-describe tag 付きの CBOR で返す。
+    let mut out = Vec::new();
+    let mut serializer = serde_cbor::Serializer::new(&mut out);
+    let encoded = serializer
+        .self_describe()
+        .and_then(|_| tree.serialize(&mut serializer));
+    if encoded.is_err() {
+        ic_cdk::trap("certification: hash tree encode failed");
+    }
+    out
+}
+
+fn leb128_u64(mut value: u64) -> Vec<u8> {
+    let mut out = Vec::with_capacity(10);
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return out;
+        }
+        out.push(byte | 0x80);
+    }
+}
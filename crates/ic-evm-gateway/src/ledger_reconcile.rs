@@ -0,0 +1,325 @@
+//! どこで: gateway の ledger 照合
+//! 何を: ICRC-3 `icrc3_get_blocks` を tip から遡り、dispatch memo を持つ block を探す。NFT は ICRC-7 の現在の所有者を見る
+//! なぜ: 呼び出し結果が分からない unwrap dispatch を、二重送金の危険なしに確定させるため
+
+use candid::{CandidType, Deserialize, Func, Nat, Principal};
+use std::future::Future;
+use verified_core::unwrap_dispatch::ledger_block_predates_transfer_raw;
+
+/// ICRC-1 ledger の既定 permitted_drift (2 分) より広く取り、時刻のずれで取りこぼさない。
+pub(crate) const LEDGER_PERMITTED_DRIFT_NANOS: u64 = 10 * 60 * 1_000_000_000;
+/// ICRC-1 ledger の既定 transaction_window。この間は同じ memo と created_at_time が重複排除される。
+pub(crate) const LEDGER_TX_WINDOW_NANOS: u64 = 24 * 60 * 60 * 1_000_000_000;
+const LEDGER_BLOCKS_PAGE: u64 = 500;
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+struct GetBlocksRequest {
+    start: Nat,
+    length: Nat,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize, Eq, PartialEq)]
+pub(crate) enum Icrc3Value {
+    Blob(Vec<u8>),
+    Text(String),
+    Nat(Nat),
+    Int(candid::Int),
+    Array(Vec<Icrc3Value>),
+    Map(Vec<(String, Icrc3Value)>),
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+struct BlockWithId {
+    id: Nat,
+    block: Icrc3Value,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+struct ArchivedBlocks {
+    args: Vec<GetBlocksRequest>,
+    callback: Func,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+struct GetBlocksResult {
+    log_length: Nat,
+    blocks: Vec<BlockWithId>,
+    archived_blocks: Vec<ArchivedBlocks>,
+}
+
+/// 照合に使う block の要約。ICRC-3 の generic block から転記する。
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub(crate) struct LedgerBlock {
+    pub(crate) index: u64,
+    pub(crate) timestamp: u64,
+    pub(crate) memo: Option<Vec<u8>>,
+    pub(crate) created_at_time: Option<u64>,
+    pub(crate) from_owner: Option<Vec<u8>>,
+    pub(crate) to_owner: Option<Vec<u8>>,
+    pub(crate) to_subaccount: Option<[u8; 32]>,
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub(crate) struct LedgerBlockPage {
+    pub(crate) log_length: u64,
+    pub(crate) blocks: Vec<LedgerBlock>,
+}
+
+/// 探す送金。memo は誰でも付けられるので、送金元がこの canister であることも照合する。
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct ExpectedTransfer {
+    pub(crate) memo: Vec<u8>,
+    pub(crate) created_at_time: u64,
+    pub(crate) from_owner: Vec<u8>,
+    pub(crate) to_owner: Vec<u8>,
+    pub(crate) to_subaccount: Option<[u8; 32]>,
+}
+
+impl ExpectedTransfer {
+    fn matches(&self, block: &LedgerBlock) -> bool {
+        block.memo.as_deref() == Some(self.memo.as_slice())
+            && block.created_at_time == Some(self.created_at_time)
+            && block.from_owner.as_deref() == Some(self.from_owner.as_slice())
+            && block.to_owner.as_deref() == Some(self.to_owner.as_slice())
+            && block.to_subaccount == self.to_subaccount
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum MemoScanVerdict {
+    /// 送金は ledger に載っている。
+    Found { block_index: u64 },
+    /// created_at_time より前の block まで遡っても無かった。
+    NotFound,
+    /// 走査上限に達した。scan_before から続きを走査できる。
+    Incomplete { scan_before: u64 },
+    /// NFT は受取人が所有している。所有者の照会では collection の tx index は分からない。
+    OwnedByRecipient,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize, Eq, PartialEq)]
+pub(crate) struct Icrc7Account {
+    pub(crate) owner: Principal,
+    pub(crate) subaccount: Option<Vec<u8>>,
+}
+
+/// NFT は1つの所有者しか持たないので、今の所有者だけで送達の有無が決まる。
+/// canister が持ったままなら未送達で、別の口座が持っていれば判断せずに Err を返す。
+pub(crate) fn nft_owner_verdict(
+    owner: Option<&Icrc7Account>,
+    canister: Principal,
+    recipient: Principal,
+    recipient_subaccount: Option<[u8; 32]>,
+) -> Result<MemoScanVerdict, String> {
+    let Some(owner) = owner else {
+        return Err("reconcile.nft_not_found".to_string());
+    };
+    let subaccount = match owner.subaccount.as_deref() {
+        None => None,
+        Some(bytes) => {
+            let bytes: [u8; 32] = bytes
+                .try_into()
+                .map_err(|_| "reconcile.nft_owner_invalid".to_string())?;
+            (bytes != [0u8; 32]).then_some(bytes)
+        }
+    };
+    if owner.owner == recipient && subaccount == recipient_subaccount {
+        return Ok(MemoScanVerdict::OwnedByRecipient);
+    }
+    if owner.owner == canister && subaccount.is_none() {
+        return Ok(MemoScanVerdict::NotFound);
+    }
+    Err("reconcile.nft_owner_mismatch".to_string())
+}
+
+pub(crate) async fn fetch_nft_owner(
+    collection: Principal,
+    token_id: Nat,
+) -> Result<Option<Icrc7Account>, String> {
+    let call_result = ic_cdk::call::Call::unbounded_wait(collection, "icrc7_owner_of")
+        .with_arg(vec![token_id])
+        .await;
+    match call_result {
+        Ok(resp) => match resp.candid_tuple::<(Vec<Option<Icrc7Account>>,)>() {
+            Ok((owners,)) => Ok(owners.into_iter().next().flatten()),
+            Err(err) => Err(format!("ledger.owner_decode_failed:{err}")),
+        },
+        Err(err) => Err(format!("ledger.owner_call_failed:{err}")),
+    }
+}
+
+/// `scan_before` (未指定なら log_length) 未満の block を新しい順に `max_blocks` 件まで調べる。
+/// ledger の block 時刻は単調なので、送金より古い block に達した時点で不在が確定する。
+pub(crate) async fn scan_ledger_for_transfer<F, Fut>(
+    mut fetch: F,
+    expected: &ExpectedTransfer,
+    scan_before: Option<u64>,
+    max_blocks: u64,
+) -> Result<MemoScanVerdict, String>
+where
+    F: FnMut(u64, u64) -> Fut,
+    Fut: Future<Output = Result<LedgerBlockPage, String>>,
+{
+    let mut end = match scan_before {
+        Some(end) => end,
+        None => fetch(0, 0).await?.log_length,
+    };
+    let mut scanned = 0u64;
+    while end > 0 {
+        if scanned >= max_blocks {
+            return Ok(MemoScanVerdict::Incomplete { scan_before: end });
+        }
+        let length = LEDGER_BLOCKS_PAGE.min(end).min(max_blocks - scanned);
+        let start = end - length;
+        let mut blocks = fetch(start, length).await?.blocks;
+        blocks.retain(|block| block.index >= start && block.index < end);
+        blocks.sort_by_key(|block| std::cmp::Reverse(block.index));
+        blocks.dedup_by_key(|block| block.index);
+        if u64::try_from(blocks.len()).ok() != Some(length) {
+            return Err("reconcile.ledger_page_incomplete".to_string());
+        }
+        for block in blocks.iter() {
+            if expected.matches(block) {
+                return Ok(MemoScanVerdict::Found {
+                    block_index: block.index,
+                });
+            }
+            if ledger_block_predates_transfer_raw(
+                block.timestamp,
+                expected.created_at_time,
+                LEDGER_PERMITTED_DRIFT_NANOS,
+            ) {
+                return Ok(MemoScanVerdict::NotFound);
+            }
+        }
+        scanned += length;
+        end = start;
+    }
+    Ok(MemoScanVerdict::NotFound)
+}
+
+/// ledger 本体と archive callback の両方から [start, start + length) を集める。
+pub(crate) async fn fetch_ledger_blocks(
+    ledger: Principal,
+    start: u64,
+    length: u64,
+) -> Result<LedgerBlockPage, String> {
+    let args = vec![GetBlocksRequest {
+        start: Nat::from(start),
+        length: Nat::from(length),
+    }];
+    let result = call_get_blocks(ledger, "icrc3_get_blocks", args).await?;
+    let log_length = nat_to_u64(&result.log_length)?;
+    let mut blocks = decode_blocks(result.blocks)?;
+    for archived in result.archived_blocks {
+        let archived_result = call_get_blocks(
+            archived.callback.principal,
+            &archived.callback.method,
+            archived.args,
+        )
+        .await?;
+        blocks.extend(decode_blocks(archived_result.blocks)?);
+    }
+    Ok(LedgerBlockPage { log_length, blocks })
+}
+
+async fn call_get_blocks(
+    canister: Principal,
+    method: &str,
+    args: Vec<GetBlocksRequest>,
+) -> Result<GetBlocksResult, String> {
+    let call_result = ic_cdk::call::Call::unbounded_wait(canister, method)
+        .with_arg(args)
+        .await;
+    match call_result {
+        Ok(resp) => match resp.candid_tuple::<(GetBlocksResult,)>() {
+            Ok((result,)) => Ok(result),
+            Err(err) => Err(format!("ledger.blocks_decode_failed:{err}")),
+        },
+        Err(err) => Err(format!("ledger.blocks_call_failed:{err}")),
+    }
+}
+
+fn decode_blocks(blocks: Vec<BlockWithId>) -> Result<Vec<LedgerBlock>, String> {
+    blocks
+        .into_iter()
+        .map(|block| ledger_block_from_value(nat_to_u64(&block.id)?, &block.block))
+        .collect()
+}
+
+/// ICRC-3 の generic block (`ts` と `tx` の map) から照合に要る欄だけを読む。
+pub(crate) fn ledger_block_from_value(
+    index: u64,
+    value: &Icrc3Value,
+) -> Result<LedgerBlock, String> {
+    let timestamp = map_field(value, "ts")
+        .and_then(value_u64)
+        .ok_or_else(|| "ledger.block_timestamp_missing".to_string())?;
+    let mut block = LedgerBlock {
+        index,
+        timestamp,
+        ..LedgerBlock::default()
+    };
+    let Some(tx) = map_field(value, "tx") else {
+        return Ok(block);
+    };
+    block.memo = map_field(tx, "memo")
+        .and_then(value_blob)
+        .map(<[u8]>::to_vec);
+    block.created_at_time = map_field(tx, "ts").and_then(value_u64);
+    block.from_owner = map_field(tx, "from")
+        .and_then(account_owner)
+        .map(<[u8]>::to_vec);
+    if let Some(to) = map_field(tx, "to") {
+        block.to_owner = account_owner(to).map(<[u8]>::to_vec);
+        block.to_subaccount = account_subaccount(to);
+    }
+    Ok(block)
+}
+
+fn map_field<'a>(value: &'a Icrc3Value, key: &str) -> Option<&'a Icrc3Value> {
+    let Icrc3Value::Map(entries) = value else {
+        return None;
+    };
+    entries
+        .iter()
+        .find(|(name, _)| name == key)
+        .map(|(_, value)| value)
+}
+
+fn value_u64(value: &Icrc3Value) -> Option<u64> {
+    match value {
+        Icrc3Value::Nat(nat) => nat_to_u64(nat).ok(),
+        _ => None,
+    }
+}
+
+fn value_blob(value: &Icrc3Value) -> Option<&[u8]> {
+    match value {
+        Icrc3Value::Blob(bytes) => Some(bytes.as_slice()),
+        _ => None,
+    }
+}
+
+fn account_owner(value: &Icrc3Value) -> Option<&[u8]> {
+    match value {
+        Icrc3Value::Array(parts) => parts.first().and_then(value_blob),
+        _ => None,
+    }
+}
+
+/// 省略と全 0 の subaccount はどちらも既定口座として扱う。
+fn account_subaccount(value: &Icrc3Value) -> Option<[u8; 32]> {
+    let Icrc3Value::Array(parts) = value else {
+        return None;
+    };
+    let subaccount: [u8; 32] = parts.get(1).and_then(value_blob)?.try_into().ok()?;
+    if subaccount == [0u8; 32] {
+        return None;
+    }
+    Some(subaccount)
+}
+
+fn nat_to_u64(value: &Nat) -> Result<u64, String> {
+    u64::try_from(&value.0).map_err(|_| "ledger.nat_out_of_range".to_string())
+}
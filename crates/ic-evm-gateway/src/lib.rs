@@ -1,11 +1,14 @@
 //! どこで: canister入口 / 何を: Phase1のAPI公開 / なぜ: submit中心の安全な運用導線を提供するため
 
-use candid::{CandidType, Nat, Principal};
+use candid::{CandidType, Int, Nat, Principal};
 use evm_core::chain;
 use evm_core::hash;
 use evm_core::kasane_precompiles::{
-    icp_update_intent_from_log, native_withdraw_intent_from_log, precompile_allow_key,
-    unwrap_intent_from_log,
+    icp_update_intent_from_log, native_withdraw_intent_from_log, nft_unwrap_intent_from_log,
+    precompile_allow_key, unwrap_intent_from_log,
+};
+use evm_core::state_snapshot::{
+    StateSnapshotCursor, StateSnapshotImportError, StateSnapshotManifest, StateSnapshotPage,
 };
 use evm_core::tx_decode::decode_tx_view;
 use evm_db::chain_data::constants::CHAIN_ID;
@@ -14,34 +17,46 @@ use evm_db::chain_data::runtime_defaults::{DEFAULT_BLOCK_GAS_LIMIT, DEFAULT_MIN_
 use evm_db::chain_data::DEFAULT_MINING_INTERVAL_MS;
 use evm_db::chain_data::MIN_PRUNE_MAX_OPS_PER_TICK;
 use evm_db::chain_data::{
-    BlockData, FeePolicyStored, IcpUpdateDispatchRequest, IcpUpdateRequestStatus, MigrationPhase,
-    MintSubmitStatus, OpsMode, ReceiptLike, RequestStatus as StoredRequestStatus, RuntimeConfigV1,
-    TxId, TxKind, TxLoc, TxLocKind, UnwrapDispatchRequest, UnwrapRequestStatus,
+    AssetFeeScheduleStored, AssetLimitsV1, BlobCompactionPhase, BlobRecompressPhase, BlockData,
+    BridgeDirection, DepositSweepV1, FeePolicyStored, FeeTierStored, IcpUpdateDispatchRequest,
+    IcpUpdateRequestStatus, MigrationPhase, MintSubmitStatus, OpsMode, ReceiptLike,
+    RequestStatus as StoredRequestStatus, RuntimeConfigV1, ScrubFindingKind, ScrubPhase,
+    ScrubTarget, StateSnapshotImportPhase, StateSnapshotImportV1, TxId, TxKind, TxLoc, TxLocKind,
+    UnwrapAssetKind, UnwrapDispatchRequest, UnwrapRequestStatus, WrapAssetKind,
     WrapEvmConfigStored, WrapPendingSubmission, WrapRequestStage, ICP_UPDATE_DECODE_FAILURE_CODE,
-    LOG_CONFIG_FILTER_MAX, UNWRAP_DECODE_FAILURE_CODE,
+    LOG_CONFIG_FILTER_MAX, MAX_FEE_TIERS, UNWRAP_DECODE_FAILURE_CODE,
 };
 use evm_db::memory::{all_memory_regions, memory_size_pages, WASM_PAGE_SIZE_BYTES};
 use evm_db::meta::{
-    current_schema_version, ensure_meta_initialized, get_meta, mark_migration_applied,
-    schema_migration_state, set_needs_migration, set_schema_migration_state, set_tx_locs_v3_active,
-    SchemaMigrationPhase, SchemaMigrationState,
+    current_schema_version, ensure_meta_initialized, get_meta, schema_migration_state,
+    set_needs_migration, set_schema_migration_state, set_tx_locs_v3_active, SchemaMigrationPhase,
+    SchemaMigrationState,
 };
 use evm_db::stable_state::{
     current_runtime_config, init_stable_state, set_runtime_config, with_state, with_state_mut,
+    StableState,
 };
-use evm_db::upgrade;
+use evm_db::upgrade::{self, UpgradeCheckpoint};
 use ic_cdk::api::{
     accept_message, canister_cycle_balance, is_controller, msg_caller, msg_method_name,
 };
 use ic_cdk::call::{Call, CallFailed, RejectCode};
-use num_bigint::BigUint;
+use num_bigint::{BigInt, BigUint};
 use serde::Deserialize;
 use std::collections::BTreeSet;
 use std::sync::atomic::{AtomicU64, Ordering};
 use tiny_keccak::{Hasher, Keccak};
 use tracing::{error, info, warn};
+use verified_core::bridge_limits::bridge_limit_allows;
+use verified_core::deposit::{deposit_sweep_amount, deposit_sweep_stays_in_flight};
+use verified_core::reserves::{reserve_liabilities, reserve_shortfall};
+use verified_core::wrap_quote::{
+    wrap_amount_fee_e8s, wrap_charged_fee_e8s_raw, wrap_mint_amount_raw, FEE_TIER_MAX_BPS,
+};
 
 mod icrc21;
+mod icrc3;
+mod ledger_reconcile;
 
 #[cfg(not(target_arch = "wasm32"))]
 use std::io::{self, Write};
@@ -68,7 +83,10 @@ fn always_fail_getrandom(_buf: &mut [u8]) -> Result<(), getrandom::Error> {
 const PRUNE_EVENT_BLOCK_INTERVAL: u64 = 84;
 const CYCLE_OBSERVER_FAST_INTERVAL_SECS: u64 = 60;
 const CYCLE_OBSERVER_SLOW_INTERVAL_SECS: u64 = 3_600;
+const RESERVES_CHECK_INTERVAL_SECS: u64 = 3_600;
 const WRAP_DISPATCH_DELAY_MS: u64 = 75;
+const UNWRAP_DISPATCH_BATCH_MAX: u64 = 16;
+const UNWRAP_DISPATCH_LEDGER_IN_FLIGHT_MAX: u64 = 4;
 const UNWRAP_QUARANTINE_ERROR: &str = "quarantine.decode.unwrap_request";
 const NATIVE_WITHDRAW_ASSET_MARKER: &[u8] = b"kasane.native.icp";
 const MAX_CYCLE_FEE_E8S: u64 = 1_000_000_000_000;
@@ -80,6 +98,20 @@ const STALE_OPERATION_NANOS: u64 = 10 * 60 * 1_000_000_000;
 const ICP_UPDATE_REPLY_OMITTED_TOO_LARGE: &str = "ic_update.reply_omitted_too_large";
 const ICP_UPDATE_DISPATCH_TIMEOUT_SECONDS: u32 = 30;
 const MAX_ICP_UPDATE_REQUESTS: usize = 10_000;
+const MAX_SCRUB_FINDINGS_LIMIT: u32 = 256;
+const MAX_RECONCILE_SCAN_BLOCKS: u32 = 5_000;
+const DEPOSIT_WRAP_GAS_LIMIT: u64 = 3_000_000;
+const WRAP_DEADLINE_DEFAULT_SECS: u64 = 24 * 60 * 60;
+const WRAP_DEADLINE_MIN_SECS: u64 = 60 * 60;
+const WRAP_DEADLINE_MAX_SECS: u64 = 7 * 24 * 60 * 60;
+const WRAP_EXPIRY_CHECK_INTERVAL_SECS: u64 = 300;
+const MAX_WRAP_EXPIRY_REFUNDS_PER_TICK: usize = 16;
+const WRAP_REFUND_BELOW_FEE_CODE: &str = "wrap.refund_below_fee";
+const WRAPPED_TOKEN_NAME_MAX_BYTES: usize = 64;
+const WRAPPED_TOKEN_SYMBOL_MAX_BYTES: usize = 32;
+const WRAPPED_TOKEN_LOGO_MAX_BYTES: usize = 2_048;
+const WRAP_METADATA_SYNC_BASE_GAS: u64 = 100_000;
+const WRAP_METADATA_SYNC_GAS_PER_WORD: u64 = 25_000;
 
 static UNWRAP_DISPATCH_SCHEDULED: std::sync::atomic::AtomicBool =
     std::sync::atomic::AtomicBool::new(false);
@@ -120,6 +152,33 @@ pub struct FeePolicyView {
     pub gas_price_buffer_bps: u32,
 }
 
+#[derive(Clone, Debug, CandidType, Deserialize, Eq, PartialEq)]
+pub struct FeeTierView {
+    pub min_amount_e8s: Nat,
+    pub fee_bps: u32,
+}
+
+/// fee_in_asset なら fee は wrap する asset で取り、fee_ledger_canister は None にする。
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct SetAssetFeeScheduleArgs {
+    pub asset_id: Principal,
+    pub fee_ledger_canister: Option<Principal>,
+    pub cycle_fee_e8s: u64,
+    pub gas_price_buffer_bps: u32,
+    pub fee_in_asset: bool,
+    pub tiers: Vec<FeeTierView>,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize, Eq, PartialEq)]
+pub struct AssetFeeScheduleView {
+    pub asset_id: Principal,
+    pub fee_ledger_canister: Option<Principal>,
+    pub cycle_fee_e8s: u64,
+    pub gas_price_buffer_bps: u32,
+    pub fee_in_asset: bool,
+    pub tiers: Vec<FeeTierView>,
+}
+
 #[derive(Clone, Debug, CandidType, Deserialize)]
 pub struct SetFeePolicyArgs {
     pub fee_ledger_canister: Principal,
@@ -199,6 +258,9 @@ pub struct QuoteWrapRequestOk {
     pub charged_gas_price_wei: Nat,
     pub cycle_fee_e8s: u64,
     pub fee_ledger_canister: Principal,
+    pub fee_in_asset: bool,
+    pub tier_fee_bps: u32,
+    pub mint_amount_e8s: Nat,
 }
 
 #[derive(Clone, Debug, CandidType, Deserialize)]
@@ -211,6 +273,8 @@ pub struct SubmitWrapRequestArgs {
     pub max_fee_e8s: Nat,
     pub quoted_gas_price_wei: Nat,
     pub fee_ledger_canister: Principal,
+    /// mint されないまま過ぎたら自動で返金するまでの秒数。None は既定の 24 時間。
+    pub deadline_secs: Option<u64>,
 }
 
 #[derive(Clone, Debug, CandidType, Deserialize)]
@@ -221,6 +285,37 @@ pub struct SubmitWrapRequestOk {
     pub fee_ledger_tx_id: Vec<u8>,
 }
 
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct QuoteNftWrapRequestArgs {
+    pub collection_id: Principal,
+    pub token_id: Nat,
+    pub evm_recipient: Vec<u8>,
+    pub gas_limit: u64,
+}
+
+/// NFT wrap は量に比例する fee を持たず、全体の fee policy の gas と cycle 分だけを払う。
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct QuoteNftWrapRequestOk {
+    pub charged_fee_e8s: Nat,
+    pub charged_gas_price_wei: Nat,
+    pub cycle_fee_e8s: u64,
+    pub fee_ledger_canister: Principal,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct SubmitNftWrapRequestArgs {
+    pub collection_id: Principal,
+    pub token_id: Nat,
+    pub evm_recipient: Vec<u8>,
+    pub evm_nonce: u64,
+    pub gas_limit: u64,
+    pub max_fee_e8s: Nat,
+    pub quoted_gas_price_wei: Nat,
+    pub fee_ledger_canister: Principal,
+    /// mint されないまま過ぎたら自動で返金するまでの秒数。None は既定の 24 時間。
+    pub deadline_secs: Option<u64>,
+}
+
 #[derive(Clone, Debug, CandidType, Deserialize)]
 pub struct GetUnwrapRequirementsArgs {
     pub asset_id: Principal,
@@ -257,6 +352,8 @@ pub struct QuoteNativeDepositOk {
     pub charged_fee_e8s: Nat,
     pub native_ledger_canister: Principal,
     pub fee_ledger_canister: Principal,
+    pub fee_in_asset: bool,
+    pub credit_amount_e8s: Nat,
 }
 
 #[derive(Clone, Debug, CandidType, Deserialize)]
@@ -302,6 +399,7 @@ pub struct DispatchUnwrapRequestArgs {
     pub asset_id: Principal,
     pub amount_e8s: Nat,
     pub recipient: Principal,
+    pub recipient_subaccount: Option<Vec<u8>>,
 }
 
 #[derive(Clone, Debug, CandidType, Deserialize)]
@@ -309,6 +407,7 @@ pub struct DispatchNativeWithdrawalRequestArgs {
     pub request_id: Vec<u8>,
     pub amount_e8s: Nat,
     pub recipient: Principal,
+    pub recipient_subaccount: Option<Vec<u8>>,
 }
 
 #[derive(Clone, Debug, CandidType, Deserialize)]
@@ -326,6 +425,88 @@ pub struct RecoverFailedWrapArgs {
     pub request_id: Vec<u8>,
 }
 
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct ReconcileUnwrapRequestArgs {
+    pub request_id: Vec<u8>,
+    pub scan_before: Option<u64>,
+    pub max_blocks: u32,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize, Eq, PartialEq)]
+pub enum UnwrapReconcileVerdictView {
+    Dispatched { block_index: u64 },
+    SafeToRetry,
+    Incomplete { scan_before: u64 },
+    OwnedByRecipient,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct ReconcileUnwrapRequestOk {
+    pub verdict: UnwrapReconcileVerdictView,
+    pub request: RequestOverview,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct NotifyDepositArgs {
+    pub asset_id: Principal,
+    pub evm_recipient: Vec<u8>,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct NotifyDepositOk {
+    pub request_id: Vec<u8>,
+    pub amount_e8s: Nat,
+    pub sweep_ledger_tx_id: Vec<u8>,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct DepositAccountView {
+    pub owner: Principal,
+    pub subaccount: Vec<u8>,
+}
+
+/// discrepancy は ledger_balance から supply と処理中 request の合計を引いた値。読めなかった欄があれば None で error に理由を入れる。
+#[derive(Clone, Debug, CandidType, Deserialize, Eq, PartialEq)]
+pub struct AssetReservesView {
+    pub asset_id: Principal,
+    pub wrapped_token_address: Option<Vec<u8>>,
+    pub ledger_balance: Option<Nat>,
+    pub wrapped_supply: Option<Nat>,
+    pub in_flight_wrap: Nat,
+    pub in_flight_unwrap: Nat,
+    pub discrepancy: Option<Int>,
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct ReservesReportView {
+    pub checked_at: u64,
+    pub wrap_paused: bool,
+    pub assets: Vec<AssetReservesView>,
+}
+
+/// 上限が None の欄は無制限。既存の 24 時間窓の使用量は設定し直しても保つ。
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct SetAssetLimitsArgs {
+    pub asset_id: Principal,
+    pub paused: bool,
+    pub max_single_wrap: Option<Nat>,
+    pub wrap_daily_cap: Option<Nat>,
+    pub unwrap_daily_cap: Option<Nat>,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize, Eq, PartialEq)]
+pub struct AssetLimitsView {
+    pub asset_id: Principal,
+    pub paused: bool,
+    pub max_single_wrap: Option<Nat>,
+    pub wrap_daily_cap: Option<Nat>,
+    pub unwrap_daily_cap: Option<Nat>,
+    pub wrap_volume_24h: Nat,
+    pub unwrap_volume_24h: Nat,
+    pub updated_at: u64,
+}
+
 #[derive(Clone, Debug, CandidType, Deserialize)]
 struct Icrc1Account {
     owner: Principal,
@@ -387,12 +568,46 @@ enum Icrc2TransferFromError {
     GenericError { error_code: Nat, message: String },
 }
 
+#[derive(Clone, Debug, CandidType, Deserialize)]
+struct Icrc7TransferArg {
+    from_subaccount: Option<Vec<u8>>,
+    to: Icrc1Account,
+    token_id: Nat,
+    memo: Option<Vec<u8>>,
+    created_at_time: Option<u64>,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+struct Icrc37TransferFromArg {
+    spender_subaccount: Option<Vec<u8>>,
+    from: Icrc1Account,
+    to: Icrc1Account,
+    token_id: Nat,
+    memo: Option<Vec<u8>>,
+    created_at_time: Option<u64>,
+}
+
+/// ICRC-7 の transfer と ICRC-37 の transfer_from は同じ error 型を返す。
+#[derive(Clone, Debug, CandidType, Deserialize)]
+enum Icrc7TransferError {
+    NonExistingTokenId,
+    InvalidRecipient,
+    Unauthorized,
+    TooOld,
+    CreatedInFuture { ledger_time: u64 },
+    Duplicate { duplicate_of: Nat },
+    GenericError { error_code: Nat, message: String },
+    GenericBatchError { error_code: Nat, message: String },
+}
+
 #[derive(Clone, Copy, Debug, CandidType, Deserialize, Eq, PartialEq)]
 pub enum RequestKind {
     Wrap,
     NativeDeposit,
     Unwrap,
     NativeWithdrawal,
+    NftWrap,
+    NftUnwrap,
 }
 
 #[derive(Clone, Copy, Debug, CandidType, Deserialize, Eq, PartialEq)]
@@ -446,6 +661,7 @@ pub enum RequestStageView {
     Dispatching,
     Dispatched,
     DispatchFailed,
+    DispatchUncertain,
 }
 
 #[derive(Clone, Debug, CandidType, Deserialize)]
@@ -468,6 +684,7 @@ pub struct RequestOverview {
     pub dispatch_error: Option<String>,
     pub charged_fee_e8s: Option<Nat>,
     pub charged_gas_price_wei: Option<Nat>,
+    pub deadline_at: Option<u64>,
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
@@ -559,6 +776,74 @@ fn validate_set_fee_policy(args: &FeePolicyArgs) -> Result<(), String> {
     Ok(())
 }
 
+fn validate_asset_fee_schedule(
+    args: &SetAssetFeeScheduleArgs,
+) -> Result<Vec<FeeTierStored>, String> {
+    validate_non_anonymous_principal(&args.asset_id, "arg.asset_id_anonymous")?;
+    match (args.fee_in_asset, args.fee_ledger_canister) {
+        (false, Some(fee_ledger)) => {
+            validate_non_anonymous_principal(&fee_ledger, "arg.fee_ledger_anonymous")?
+        }
+        (false, None) => return Err("arg.fee_ledger_required".to_string()),
+        (true, Some(_)) => return Err("arg.fee_ledger_with_fee_in_asset".to_string()),
+        (true, None) => {}
+    }
+    if args.cycle_fee_e8s > MAX_CYCLE_FEE_E8S {
+        return Err("arg.cycle_fee_e8s_out_of_range".to_string());
+    }
+    if !(10_000..=50_000).contains(&args.gas_price_buffer_bps) {
+        return Err("arg.gas_price_buffer_bps_out_of_range".to_string());
+    }
+    if args.tiers.len() > MAX_FEE_TIERS {
+        return Err("arg.fee_tiers_too_many".to_string());
+    }
+    let mut tiers: Vec<FeeTierStored> = Vec::with_capacity(args.tiers.len());
+    for tier in &args.tiers {
+        let min_amount_e8s = nat_to_u128(&tier.min_amount_e8s)
+            .ok_or_else(|| "arg.fee_tier_min_out_of_range".to_string())?;
+        if tier.fee_bps > FEE_TIER_MAX_BPS {
+            return Err("arg.fee_tier_bps_out_of_range".to_string());
+        }
+        if tiers
+            .last()
+            .is_some_and(|prev| prev.min_amount_e8s >= min_amount_e8s)
+        {
+            return Err("arg.fee_tiers_unordered".to_string());
+        }
+        tiers.push(FeeTierStored {
+            min_amount_e8s,
+            fee_bps: tier.fee_bps,
+        });
+    }
+    Ok(tiers)
+}
+
+fn asset_fee_schedule_view(
+    asset_id: Principal,
+    stored: AssetFeeScheduleStored,
+) -> Result<AssetFeeScheduleView, String> {
+    let fee_ledger_canister = if stored.fee_in_asset {
+        None
+    } else {
+        Some(principal_from_stored_bytes(&stored.fee_ledger_canister)?)
+    };
+    Ok(AssetFeeScheduleView {
+        asset_id,
+        fee_ledger_canister,
+        cycle_fee_e8s: stored.cycle_fee_e8s,
+        gas_price_buffer_bps: stored.gas_price_buffer_bps,
+        fee_in_asset: stored.fee_in_asset,
+        tiers: stored
+            .tiers
+            .into_iter()
+            .map(|tier| FeeTierView {
+                min_amount_e8s: Nat::from(tier.min_amount_e8s),
+                fee_bps: tier.fee_bps,
+            })
+            .collect(),
+    })
+}
+
 fn validate_allowed_assets(assets: &[Principal]) -> Result<(), String> {
     if assets.is_empty() {
         return Err("arg.allowed_assets_empty".to_string());
@@ -719,9 +1004,12 @@ fn init_inner(args: Option<InitArgs>, require_args: bool) {
             break;
         }
     }
+    icrc3::refresh_certified_tip();
     observe_cycles();
     schedule_mining();
     schedule_cycle_observer();
+    schedule_reserves_check();
+    schedule_wrap_expiry_check();
 }
 
 fn current_wrap_canister_id() -> Principal {
@@ -785,6 +1073,161 @@ fn ensure_asset_allowed(asset: Principal) -> Result<(), String> {
     })
 }
 
+fn ensure_nft_collection_allowed(collection: Principal) -> Result<(), String> {
+    with_state(|state| {
+        if state
+            .wrap_allowed_nft_collections
+            .get(&collection.as_slice().to_vec())
+            .is_some()
+        {
+            Ok(())
+        } else {
+            Err("collection.not_allowed".to_string())
+        }
+    })
+}
+
+const BRIDGE_LIMIT_HOUR_NANOS: u64 = 3_600_000_000_000;
+
+fn bridge_limit_hour(now: u64) -> u64 {
+    now / BRIDGE_LIMIT_HOUR_NANOS
+}
+
+/// u128 を越える量はどの上限にも収まらないものとして数える。
+fn bridge_limit_amount(amount: &[u8]) -> u128 {
+    nat_to_u128(&Nat(BigUint::from_bytes_be(amount))).unwrap_or(u128::MAX)
+}
+
+/// NFT は amount に token id を持つので、量ではなく 1 枚として数える。
+fn wrap_limit_amount(kind: WrapAssetKind, amount: &[u8]) -> u128 {
+    match kind {
+        WrapAssetKind::Fungible => bridge_limit_amount(amount),
+        WrapAssetKind::Nft => 1,
+    }
+}
+
+fn unwrap_limit_amount(req: &UnwrapDispatchRequest) -> u128 {
+    match req.kind {
+        UnwrapAssetKind::Fungible => bridge_limit_amount(&req.amount),
+        UnwrapAssetKind::Nft => 1,
+    }
+}
+
+/// limits は判定する時刻まで窓を送ってから渡す。
+fn bridge_limit_error(
+    limits: &AssetLimitsV1,
+    direction: BridgeDirection,
+    amount: u128,
+) -> Option<&'static str> {
+    if limits.paused {
+        return Some("limits.asset_paused");
+    }
+    let volume = limits.volume(direction);
+    match direction {
+        BridgeDirection::Wrap => {
+            if !bridge_limit_allows(0, amount, limits.max_single_wrap) {
+                return Some("limits.wrap_amount_exceeds_max");
+            }
+            (!bridge_limit_allows(volume, amount, limits.wrap_daily_cap))
+                .then_some("limits.wrap_daily_cap_exceeded")
+        }
+        BridgeDirection::Unwrap => (!bridge_limit_allows(volume, amount, limits.unwrap_daily_cap))
+            .then_some("limits.unwrap_daily_cap_exceeded"),
+    }
+}
+
+/// 上限が設定されていない asset は数えない。
+fn check_bridge_limit(
+    asset_id: &[u8],
+    direction: BridgeDirection,
+    amount: u128,
+    now: u64,
+) -> Result<(), String> {
+    with_state(|state| {
+        let Some(mut limits) = state.asset_limits.get(&asset_id.to_vec()) else {
+            return Ok(());
+        };
+        limits.advance_window(bridge_limit_hour(now));
+        match bridge_limit_error(&limits, direction, amount) {
+            Some(code) => Err(code.to_string()),
+            None => Ok(()),
+        }
+    })
+}
+
+/// 判定と加算を同じ state 更新で行い、並行する request が同じ残り枠を使わないようにする。
+fn charge_bridge_limit(
+    state: &mut StableState,
+    asset_id: &[u8],
+    direction: BridgeDirection,
+    amount: u128,
+    now: u64,
+) -> Result<(), String> {
+    let key = asset_id.to_vec();
+    let Some(mut limits) = state.asset_limits.get(&key) else {
+        return Ok(());
+    };
+    let hour = bridge_limit_hour(now);
+    limits.advance_window(hour);
+    if let Some(code) = bridge_limit_error(&limits, direction, amount) {
+        return Err(code.to_string());
+    }
+    limits.record(direction, hour, amount);
+    state.asset_limits.insert(key, limits);
+    Ok(())
+}
+
+/// 既に台帳で動いた量は上限に関わらず積む。
+fn record_bridge_volume(
+    state: &mut StableState,
+    asset_id: &[u8],
+    direction: BridgeDirection,
+    amount: u128,
+    now: u64,
+) {
+    let key = asset_id.to_vec();
+    let Some(mut limits) = state.asset_limits.get(&key) else {
+        return;
+    };
+    limits.record(direction, bridge_limit_hour(now), amount);
+    state.asset_limits.insert(key, limits);
+}
+
+fn release_bridge_limit(
+    asset_id: &[u8],
+    direction: BridgeDirection,
+    charged_at: u64,
+    amount: u128,
+) {
+    with_state_mut(|state| {
+        let key = asset_id.to_vec();
+        let Some(mut limits) = state.asset_limits.get(&key) else {
+            return;
+        };
+        limits.release(
+            direction,
+            bridge_limit_hour(charged_at),
+            bridge_limit_hour(current_time_nanos()),
+            amount,
+        );
+        state.asset_limits.insert(key, limits);
+    });
+}
+
+fn asset_limits_view(asset_id: Principal, mut limits: AssetLimitsV1, now: u64) -> AssetLimitsView {
+    limits.advance_window(bridge_limit_hour(now));
+    AssetLimitsView {
+        asset_id,
+        paused: limits.paused,
+        max_single_wrap: limits.max_single_wrap.map(Nat::from),
+        wrap_daily_cap: limits.wrap_daily_cap.map(Nat::from),
+        unwrap_daily_cap: limits.unwrap_daily_cap.map(Nat::from),
+        wrap_volume_24h: Nat::from(limits.volume(BridgeDirection::Wrap)),
+        unwrap_volume_24h: Nat::from(limits.volume(BridgeDirection::Unwrap)),
+        updated_at: limits.updated_at,
+    }
+}
+
 fn principal_from_stored_bytes(bytes: &[u8]) -> Result<Principal, String> {
     if bytes.is_empty() {
         return Err("wrap_config.unconfigured".to_string());
@@ -909,27 +1352,113 @@ fn sanitize_wrap_request(
     Ok(req)
 }
 
-fn quote_wrap_request_inner(gas_limit: u64) -> Result<QuoteWrapRequestOk, ApiError> {
-    validate_wrap_gas_limit(gas_limit).map_err(|err| api_invalid_argument(&err, &err))?;
-    let fee_policy = current_fee_policy().map_err(|err| api_internal(&err, &err))?;
+/// asset に schedule が無ければ全体の fee policy を段階 fee 無しで使う。
+struct WrapFeeSchedule {
+    /// fee_in_asset なら asset 自身
+    fee_ledger_canister: Principal,
+    cycle_fee_e8s: u64,
+    gas_price_buffer_bps: u32,
+    fee_in_asset: bool,
+    tiers: Vec<FeeTierStored>,
+}
+
+fn wrap_fee_schedule(asset: Principal) -> Result<WrapFeeSchedule, String> {
+    let Some(stored) =
+        with_state(|state| state.asset_fee_schedules.get(&asset.as_slice().to_vec()))
+    else {
+        let policy = current_fee_policy()?;
+        return Ok(WrapFeeSchedule {
+            fee_ledger_canister: policy.fee_ledger_canister,
+            cycle_fee_e8s: policy.cycle_fee_e8s,
+            gas_price_buffer_bps: policy.gas_price_buffer_bps,
+            fee_in_asset: false,
+            tiers: Vec::new(),
+        });
+    };
+    let fee_ledger_canister = if stored.fee_in_asset {
+        asset
+    } else {
+        principal_from_stored_bytes(&stored.fee_ledger_canister)?
+    };
+    Ok(WrapFeeSchedule {
+        fee_ledger_canister,
+        cycle_fee_e8s: stored.cycle_fee_e8s,
+        gas_price_buffer_bps: stored.gas_price_buffer_bps,
+        fee_in_asset: stored.fee_in_asset,
+        tiers: stored.tiers,
+    })
+}
+
+/// tiers は min_amount_e8s の昇順。amount が届いた最も高い段の bps を使う。
+fn wrap_fee_tier_bps(tiers: &[FeeTierStored], amount_e8s: u128) -> u32 {
+    tiers
+        .iter()
+        .rev()
+        .find(|tier| amount_e8s >= tier.min_amount_e8s)
+        .map_or(0, |tier| tier.fee_bps)
+}
+
+fn wrap_charged_gas_price_wei(gas_price_buffer_bps: u32) -> Result<u128, ApiError> {
     let base_gas_price = wrap_quote_gas_price()?;
-    let charged_gas_price_wei = base_gas_price
-        .saturating_mul(u128::from(fee_policy.gas_price_buffer_bps))
+    Ok(base_gas_price
+        .saturating_mul(u128::from(gas_price_buffer_bps))
         .saturating_add(GAS_PRICE_DENOMINATOR_BPS - 1)
-        / GAS_PRICE_DENOMINATOR_BPS;
+        / GAS_PRICE_DENOMINATOR_BPS)
+}
+
+fn quote_wrap_request_inner(
+    asset: Principal,
+    amount_e8s: u128,
+    gas_limit: u64,
+) -> Result<QuoteWrapRequestOk, ApiError> {
+    validate_wrap_gas_limit(gas_limit).map_err(|err| api_invalid_argument(&err, &err))?;
+    let schedule = wrap_fee_schedule(asset).map_err(|err| api_internal(&err, &err))?;
+    let charged_gas_price_wei = wrap_charged_gas_price_wei(schedule.gas_price_buffer_bps)?;
     let gas_fee_e8s = charged_gas_price_wei
         .saturating_mul(u128::from(gas_limit))
         .saturating_add(WEI_PER_E8S - 1)
         / WEI_PER_E8S;
-    let charged_fee_e8s = gas_fee_e8s.saturating_add(u128::from(fee_policy.cycle_fee_e8s));
+    let tier_fee_bps = wrap_fee_tier_bps(&schedule.tiers, amount_e8s);
+    let fee_in_asset = u64::from(schedule.fee_in_asset);
+    let charged_fee_e8s = wrap_amount_fee_e8s(amount_e8s, tier_fee_bps)
+        .and_then(|amount_fee_e8s| {
+            wrap_charged_fee_e8s_raw(
+                fee_in_asset,
+                gas_fee_e8s,
+                u128::from(schedule.cycle_fee_e8s),
+                amount_fee_e8s,
+            )
+        })
+        .ok_or_else(|| api_internal("fee.quote_out_of_range", "fee.quote_out_of_range"))?;
+    let mint_amount_e8s = wrap_mint_amount_raw(fee_in_asset, amount_e8s, charged_fee_e8s)
+        .ok_or_else(|| api_rejected("fee.exceeds_amount", "fee.exceeds_amount"))?;
     Ok(QuoteWrapRequestOk {
         charged_fee_e8s: Nat::from(charged_fee_e8s),
         charged_gas_price_wei: Nat::from(charged_gas_price_wei),
-        cycle_fee_e8s: fee_policy.cycle_fee_e8s,
-        fee_ledger_canister: fee_policy.fee_ledger_canister,
+        cycle_fee_e8s: schedule.cycle_fee_e8s,
+        fee_ledger_canister: schedule.fee_ledger_canister,
+        fee_in_asset: schedule.fee_in_asset,
+        tier_fee_bps,
+        mint_amount_e8s: Nat::from(mint_amount_e8s),
     })
 }
 
+/// asset で fee を払った wrap は、引き取った量から fee を引いた分だけ mint する。
+fn wrap_mint_amount(req: &evm_db::chain_data::WrapStoredRequest) -> Result<Vec<u8>, String> {
+    if !req.fee_in_asset {
+        return Ok(req.amount.clone());
+    }
+    let amount = nat_to_u128(&Nat(BigUint::from_bytes_be(&req.amount)))
+        .ok_or_else(|| "arg.amount_out_of_range".to_string())?;
+    let charged_fee_e8s = req
+        .result
+        .charged_fee_e8s
+        .ok_or_else(|| "fee.charged_missing".to_string())?;
+    let mint_amount = wrap_mint_amount_raw(1, amount, charged_fee_e8s)
+        .ok_or_else(|| "fee.exceeds_amount".to_string())?;
+    Ok(u256_from_u128(mint_amount).to_vec())
+}
+
 #[allow(dead_code)]
 fn derive_wrap_request_id(
     from_owner: &[u8],
@@ -952,6 +1481,28 @@ fn derive_wrap_request_id(
     out
 }
 
+/// fungible の request id と衝突しないよう domain を分ける。
+fn derive_nft_wrap_request_id(
+    from_owner: &[u8],
+    collection_id: &[u8],
+    token_id: &[u8],
+    evm_recipient: &[u8],
+    evm_nonce: u64,
+    gas_limit: u64,
+) -> [u8; 32] {
+    let mut keccak = Keccak::v256();
+    keccak.update(b"kasane.wrap.nft.request.v1");
+    hash_len_prefixed(&mut keccak, from_owner);
+    hash_len_prefixed(&mut keccak, collection_id);
+    hash_len_prefixed(&mut keccak, token_id);
+    hash_len_prefixed(&mut keccak, evm_recipient);
+    keccak.update(&evm_nonce.to_be_bytes());
+    keccak.update(&gas_limit.to_be_bytes());
+    let mut out = [0u8; 32];
+    keccak.finalize(&mut out);
+    out
+}
+
 #[allow(dead_code)]
 fn derive_native_deposit_request_id(from_owner: &[u8], deposit_id: &[u8]) -> [u8; 32] {
     let mut keccak = Keccak::v256();
@@ -997,16 +1548,13 @@ fn quote_wrap_request(args: QuoteWrapRequestArgs) -> Result<QuoteWrapRequestOk,
     ensure_asset_allowed(args.asset_id).map_err(|err| api_rejected(&err, &err))?;
     validate_evm_address(&args.evm_recipient, "arg.evm_recipient_invalid")
         .map_err(|err| api_invalid_argument(&err, &err))?;
-    if nat_to_u128(&args.amount_e8s)
-        .filter(|amount| *amount > 0)
-        .is_none()
-    {
+    let Some(amount_e8s) = nat_to_u128(&args.amount_e8s).filter(|amount| *amount > 0) else {
         return Err(api_invalid_argument(
             "arg.amount_invalid",
             "arg.amount_invalid",
         ));
-    }
-    quote_wrap_request_inner(args.gas_limit)
+    };
+    quote_wrap_request_inner(args.asset_id, amount_e8s, args.gas_limit)
 }
 
 #[ic_cdk::update]
@@ -1017,39 +1565,164 @@ async fn submit_wrap_request(args: SubmitWrapRequestArgs) -> Result<SubmitWrapRe
     if let Some(reason) = reject_write_reason() {
         return Err(api_rejected(&reason, &reason));
     }
+    if let Some(reason) = reject_wrap_reason() {
+        return Err(api_rejected(&reason, &reason));
+    }
     let caller = msg_caller();
     validate_non_anonymous_principal(&caller, "auth.caller_anonymous")
         .map_err(|err| api_invalid_argument(&err, &err))?;
     let normalized = normalize_submit_wrap_request(args, caller)?;
     ensure_asset_allowed(Principal::from_slice(&normalized.asset_id))
         .map_err(|err| api_rejected(&err, &err))?;
-    let quote = quote_wrap_request_inner(normalized.gas_limit)?;
-    validate_wrap_quote_within_approval(&normalized, &quote)
-        .map_err(|err| api_rejected(&err, &err))?;
-    let charged_fee_e8s = nat_to_u128(&quote.charged_fee_e8s)
-        .ok_or_else(|| api_internal("fee.quote_out_of_range", "fee.quote_out_of_range"))?;
-    let charged_gas_price_wei = nat_to_u128(&quote.charged_gas_price_wei)
-        .ok_or_else(|| api_internal("fee.quote_out_of_range", "fee.quote_out_of_range"))?;
-    let request_id = normalized.request_id;
-    if let Some(existing) = existing_wrap_request_response(&normalized, caller) {
-        return existing;
+    let amount_e8s =
+        nat_to_u128(&Nat(BigUint::from_bytes_be(&normalized.amount))).ok_or_else(|| {
+            api_invalid_argument("arg.amount_out_of_range", "arg.amount_out_of_range")
+        })?;
+    let quote = quote_wrap_request_inner(
+        Principal::from_slice(&normalized.asset_id),
+        amount_e8s,
+        normalized.gas_limit,
+    )?;
+    let fee = WrapSubmitFee {
+        fee_ledger_canister: quote.fee_ledger_canister,
+        charged_fee_e8s: nat_to_u128(&quote.charged_fee_e8s)
+            .ok_or_else(|| api_internal("fee.quote_out_of_range", "fee.quote_out_of_range"))?,
+        charged_gas_price_wei: nat_to_u128(&quote.charged_gas_price_wei)
+            .ok_or_else(|| api_internal("fee.quote_out_of_range", "fee.quote_out_of_range"))?,
+        fee_in_asset: quote.fee_in_asset,
+    };
+    submit_normalized_wrap_request(normalized, caller, fee).await
+}
+
+#[ic_cdk::query]
+fn quote_nft_wrap_request(
+    args: QuoteNftWrapRequestArgs,
+) -> Result<QuoteNftWrapRequestOk, ApiError> {
+    validate_non_anonymous_principal(&args.collection_id, "arg.collection_id_anonymous")
+        .map_err(|err| api_invalid_argument(&err, &err))?;
+    ensure_nft_collection_allowed(args.collection_id).map_err(|err| api_rejected(&err, &err))?;
+    validate_evm_address(&args.evm_recipient, "arg.evm_recipient_invalid")
+        .map_err(|err| api_invalid_argument(&err, &err))?;
+    if nat_to_fixed_be::<32>(&args.token_id).is_none() {
+        return Err(api_invalid_argument(
+            "arg.token_id_out_of_range",
+            "arg.token_id_out_of_range",
+        ));
     }
-    reserve_wrap_pending_submission(request_id, caller).map_err(|err| api_rejected(&err, &err))?;
-    let req =
-        ensure_wrap_request_before_fee(normalized, caller, charged_fee_e8s, charged_gas_price_wei)
-            .map_err(|err| {
-                clear_wrap_pending_submission(request_id);
-                api_rejected(&err, &err)
-            })?;
-    let fee_ledger_tx_id = attempt_icrc2_transfer_from(
+    quote_nft_wrap_request_inner(args.gas_limit)
+}
+
+fn quote_nft_wrap_request_inner(gas_limit: u64) -> Result<QuoteNftWrapRequestOk, ApiError> {
+    validate_wrap_gas_limit(gas_limit).map_err(|err| api_invalid_argument(&err, &err))?;
+    let policy = current_fee_policy().map_err(|err| api_internal(&err, &err))?;
+    let charged_gas_price_wei = wrap_charged_gas_price_wei(policy.gas_price_buffer_bps)?;
+    let gas_fee_e8s = charged_gas_price_wei
+        .saturating_mul(u128::from(gas_limit))
+        .saturating_add(WEI_PER_E8S - 1)
+        / WEI_PER_E8S;
+    let charged_fee_e8s =
+        wrap_charged_fee_e8s_raw(0, gas_fee_e8s, u128::from(policy.cycle_fee_e8s), 0)
+            .ok_or_else(|| api_internal("fee.quote_out_of_range", "fee.quote_out_of_range"))?;
+    Ok(QuoteNftWrapRequestOk {
+        charged_fee_e8s: Nat::from(charged_fee_e8s),
+        charged_gas_price_wei: Nat::from(charged_gas_price_wei),
+        cycle_fee_e8s: policy.cycle_fee_e8s,
+        fee_ledger_canister: policy.fee_ledger_canister,
+    })
+}
+
+#[ic_cdk::update]
+async fn submit_nft_wrap_request(
+    args: SubmitNftWrapRequestArgs,
+) -> Result<SubmitWrapRequestOk, ApiError> {
+    if let Some(reason) = reject_anonymous_update() {
+        return Err(api_rejected(&reason, &reason));
+    }
+    if let Some(reason) = reject_write_reason() {
+        return Err(api_rejected(&reason, &reason));
+    }
+    if let Some(reason) = reject_wrap_reason() {
+        return Err(api_rejected(&reason, &reason));
+    }
+    let caller = msg_caller();
+    validate_non_anonymous_principal(&caller, "auth.caller_anonymous")
+        .map_err(|err| api_invalid_argument(&err, &err))?;
+    let normalized = normalize_submit_nft_wrap_request(args, caller)?;
+    ensure_nft_collection_allowed(Principal::from_slice(&normalized.asset_id))
+        .map_err(|err| api_rejected(&err, &err))?;
+    let quote = quote_nft_wrap_request_inner(normalized.gas_limit)?;
+    let fee = WrapSubmitFee {
+        fee_ledger_canister: quote.fee_ledger_canister,
+        charged_fee_e8s: nat_to_u128(&quote.charged_fee_e8s)
+            .ok_or_else(|| api_internal("fee.quote_out_of_range", "fee.quote_out_of_range"))?,
+        charged_gas_price_wei: nat_to_u128(&quote.charged_gas_price_wei)
+            .ok_or_else(|| api_internal("fee.quote_out_of_range", "fee.quote_out_of_range"))?,
+        fee_in_asset: false,
+    };
+    submit_normalized_wrap_request(normalized, caller, fee).await
+}
+
+/// submit 時点の見積もり。fungible と NFT の wrap で受付以降の流れを共有する。
+struct WrapSubmitFee {
+    fee_ledger_canister: Principal,
+    charged_fee_e8s: u128,
+    charged_gas_price_wei: u128,
+    fee_in_asset: bool,
+}
+
+async fn submit_normalized_wrap_request(
+    normalized: NormalizedSubmitWrapRequest,
+    caller: Principal,
+    fee: WrapSubmitFee,
+) -> Result<SubmitWrapRequestOk, ApiError> {
+    validate_wrap_quote_within_approval(&normalized, &fee)
+        .map_err(|err| api_rejected(&err, &err))?;
+    let request_id = normalized.request_id;
+    if let Some(existing) = existing_wrap_request_response(&normalized, caller) {
+        return existing;
+    }
+    reserve_wrap_pending_submission(request_id, caller).map_err(|err| api_rejected(&err, &err))?;
+    let charged_at = current_time_nanos();
+    let req = ensure_wrap_request_before_fee(
+        normalized,
+        caller,
+        fee.charged_fee_e8s,
+        fee.charged_gas_price_wei,
+        fee.fee_in_asset,
+        charged_at,
+    )
+    .map_err(|err| {
+        clear_wrap_pending_submission(request_id);
+        api_rejected(&err, &err)
+    })?;
+    // asset で払う fee は mint 量から差し引くので、別の fee 転送は行わない。
+    if req.fee_in_asset {
+        enqueue_wrap_request_once(request_id);
+        clear_wrap_pending_submission(request_id);
+        #[cfg(target_arch = "wasm32")]
+        schedule_wrap_worker();
+        return Ok(SubmitWrapRequestOk {
+            request_id: request_id.0.to_vec(),
+            charged_fee_e8s: Nat::from(fee.charged_fee_e8s),
+            charged_gas_price_wei: Nat::from(fee.charged_gas_price_wei),
+            fee_ledger_tx_id: Vec::new(),
+        });
+    }
+    let fee_ledger_tx_id = attempt_icrc2_transfer_from(
         caller,
-        quote.fee_ledger_canister,
-        quote.charged_fee_e8s.clone(),
+        fee.fee_ledger_canister,
+        Nat::from(fee.charged_fee_e8s),
         request_memo(request_id, TransferMemoKind::Fee),
         req.fee_created_at_time,
     )
     .await
     .map_err(|err| {
+        release_bridge_limit(
+            &req.asset_id,
+            BridgeDirection::Wrap,
+            charged_at,
+            wrap_limit_amount(req.kind, &req.amount),
+        );
         record_wrap_request_failure(request_id, map_fee_collection_error(&err), false);
         clear_wrap_pending_submission(request_id);
         let code = map_fee_collection_error(&err);
@@ -1065,14 +1738,15 @@ async fn submit_wrap_request(args: SubmitWrapRequestArgs) -> Result<SubmitWrapRe
     schedule_wrap_worker();
     Ok(SubmitWrapRequestOk {
         request_id: request_id.0.to_vec(),
-        charged_fee_e8s: quote.charged_fee_e8s,
-        charged_gas_price_wei: quote.charged_gas_price_wei,
+        charged_fee_e8s: Nat::from(fee.charged_fee_e8s),
+        charged_gas_price_wei: Nat::from(fee.charged_gas_price_wei),
         fee_ledger_tx_id,
     })
 }
 
 struct NormalizedSubmitWrapRequest {
     request_id: TxId,
+    kind: WrapAssetKind,
     asset_id: Vec<u8>,
     amount: Vec<u8>,
     evm_recipient: Vec<u8>,
@@ -1080,6 +1754,7 @@ struct NormalizedSubmitWrapRequest {
     max_fee_e8s: u128,
     quoted_gas_price_wei: u128,
     fee_ledger_canister: Principal,
+    deadline_secs: u64,
 }
 
 fn existing_wrap_request_response(
@@ -1089,6 +1764,7 @@ fn existing_wrap_request_response(
     with_state(|state| {
         let existing = state.wrap_requests.get(&args.request_id)?;
         if existing.caller.as_slice() != caller.as_slice()
+            || existing.kind != args.kind
             || existing.asset_id.as_slice() != args.asset_id.as_slice()
             || existing.amount.as_slice() != args.amount.as_slice()
             || existing.evm_recipient.as_slice() != args.evm_recipient.as_slice()
@@ -1102,7 +1778,11 @@ fn existing_wrap_request_response(
                 "request.idempotency_mismatch",
             )));
         }
-        let fee_ledger_tx_id = existing.result.fee_ledger_tx_id.clone()?;
+        let fee_ledger_tx_id = if existing.fee_in_asset {
+            Vec::new()
+        } else {
+            existing.result.fee_ledger_tx_id.clone()?
+        };
         let Some(charged_fee_e8s) = existing.result.charged_fee_e8s else {
             return Some(Err(api_rejected(
                 "request.idempotency_incomplete",
@@ -1176,6 +1856,13 @@ fn normalize_submit_wrap_request(
             "arg.quoted_gas_price_out_of_range",
         )
     })?;
+    let deadline_secs = args.deadline_secs.unwrap_or(WRAP_DEADLINE_DEFAULT_SECS);
+    if !(WRAP_DEADLINE_MIN_SECS..=WRAP_DEADLINE_MAX_SECS).contains(&deadline_secs) {
+        return Err(api_invalid_argument(
+            "arg.deadline_out_of_range",
+            "arg.deadline_out_of_range",
+        ));
+    }
     let request_id = TxId(derive_wrap_request_id(
         caller.as_slice(),
         args.asset_id.as_slice(),
@@ -1186,6 +1873,7 @@ fn normalize_submit_wrap_request(
     ));
     Ok(NormalizedSubmitWrapRequest {
         request_id,
+        kind: WrapAssetKind::Fungible,
         asset_id: args.asset_id.as_slice().to_vec(),
         amount: amount.to_vec(),
         evm_recipient: args.evm_recipient,
@@ -1193,24 +1881,71 @@ fn normalize_submit_wrap_request(
         max_fee_e8s,
         quoted_gas_price_wei,
         fee_ledger_canister: args.fee_ledger_canister,
+        deadline_secs,
+    })
+}
+
+/// token id は 0 も有効なので、fungible と違い量の非 0 検査はしない。
+fn normalize_submit_nft_wrap_request(
+    args: SubmitNftWrapRequestArgs,
+    caller: Principal,
+) -> Result<NormalizedSubmitWrapRequest, ApiError> {
+    validate_non_anonymous_principal(&args.collection_id, "arg.collection_id_anonymous")
+        .map_err(|err| api_invalid_argument(&err, &err))?;
+    validate_evm_address(&args.evm_recipient, "arg.evm_recipient_invalid")
+        .map_err(|err| api_invalid_argument(&err, &err))?;
+    let token_id = nat_to_fixed_be::<32>(&args.token_id).ok_or_else(|| {
+        api_invalid_argument("arg.token_id_out_of_range", "arg.token_id_out_of_range")
+    })?;
+    let max_fee_e8s = nat_to_u128(&args.max_fee_e8s).ok_or_else(|| {
+        api_invalid_argument("arg.max_fee_out_of_range", "arg.max_fee_out_of_range")
+    })?;
+    let quoted_gas_price_wei = nat_to_u128(&args.quoted_gas_price_wei).ok_or_else(|| {
+        api_invalid_argument(
+            "arg.quoted_gas_price_out_of_range",
+            "arg.quoted_gas_price_out_of_range",
+        )
+    })?;
+    let deadline_secs = args.deadline_secs.unwrap_or(WRAP_DEADLINE_DEFAULT_SECS);
+    if !(WRAP_DEADLINE_MIN_SECS..=WRAP_DEADLINE_MAX_SECS).contains(&deadline_secs) {
+        return Err(api_invalid_argument(
+            "arg.deadline_out_of_range",
+            "arg.deadline_out_of_range",
+        ));
+    }
+    let request_id = TxId(derive_nft_wrap_request_id(
+        caller.as_slice(),
+        args.collection_id.as_slice(),
+        &token_id,
+        args.evm_recipient.as_slice(),
+        args.evm_nonce,
+        args.gas_limit,
+    ));
+    Ok(NormalizedSubmitWrapRequest {
+        request_id,
+        kind: WrapAssetKind::Nft,
+        asset_id: args.collection_id.as_slice().to_vec(),
+        amount: token_id.to_vec(),
+        evm_recipient: args.evm_recipient,
+        gas_limit: args.gas_limit,
+        max_fee_e8s,
+        quoted_gas_price_wei,
+        fee_ledger_canister: args.fee_ledger_canister,
+        deadline_secs,
     })
 }
 
 fn validate_wrap_quote_within_approval(
     args: &NormalizedSubmitWrapRequest,
-    quote: &QuoteWrapRequestOk,
+    fee: &WrapSubmitFee,
 ) -> Result<(), String> {
-    if quote.fee_ledger_canister != args.fee_ledger_canister {
+    if fee.fee_ledger_canister != args.fee_ledger_canister {
         return Err("fee.ledger_changed".to_string());
     }
-    let charged_fee_e8s =
-        nat_to_u128(&quote.charged_fee_e8s).ok_or_else(|| "fee.quote_out_of_range".to_string())?;
-    let charged_gas_price_wei = nat_to_u128(&quote.charged_gas_price_wei)
-        .ok_or_else(|| "fee.quote_out_of_range".to_string())?;
-    if charged_fee_e8s > args.max_fee_e8s {
+    if fee.charged_fee_e8s > args.max_fee_e8s {
         return Err("fee.quote_exceeded".to_string());
     }
-    if charged_gas_price_wei > args.quoted_gas_price_wei {
+    if fee.charged_gas_price_wei > args.quoted_gas_price_wei {
         return Err("fee.gas_price_exceeded".to_string());
     }
     Ok(())
@@ -1221,13 +1956,31 @@ fn ensure_wrap_request_before_fee(
     caller: Principal,
     charged_fee_e8s: u128,
     charged_gas_price_wei: u128,
+    fee_in_asset: bool,
+    now: u64,
 ) -> Result<evm_db::chain_data::WrapStoredRequest, String> {
     let request_id = args.request_id;
     with_state_mut(|state| {
+        // fee 未徴収の既存 request は前回の試行で枠を返しているので、再試行でも枠を取り直す。
         if let Some(existing) = state.wrap_requests.get(&request_id) {
+            if existing.result.fee_ledger_tx_id.is_none() && !existing.fee_in_asset {
+                charge_bridge_limit(
+                    state,
+                    &existing.asset_id,
+                    BridgeDirection::Wrap,
+                    wrap_limit_amount(existing.kind, &existing.amount),
+                    now,
+                )?;
+            }
             return Ok(existing);
         }
-        let now = current_time_nanos();
+        charge_bridge_limit(
+            state,
+            &args.asset_id,
+            BridgeDirection::Wrap,
+            wrap_limit_amount(args.kind, &args.amount),
+            now,
+        )?;
         let req = sanitize_wrap_request(evm_db::chain_data::WrapStoredRequest {
             caller: caller.as_slice().to_vec(),
             asset_id: args.asset_id,
@@ -1240,6 +1993,9 @@ fn ensure_wrap_request_before_fee(
             fee_created_at_time: now,
             pull_created_at_time: now,
             withdraw_created_at_time: 0,
+            fee_in_asset,
+            deadline_at: wrap_deadline_at(now, args.deadline_secs),
+            kind: args.kind,
             result: evm_db::chain_data::WrapRequestResult {
                 status: StoredRequestStatus::Queued,
                 pull_ledger_tx_id: None,
@@ -1253,11 +2009,16 @@ fn ensure_wrap_request_before_fee(
                 fee_ledger_tx_id: None,
                 charged_fee_e8s: Some(charged_fee_e8s),
                 charged_gas_price_wei: Some(charged_gas_price_wei),
-                stage: WrapRequestStage::FeePending,
+                stage: if fee_in_asset {
+                    WrapRequestStage::FeeCollected
+                } else {
+                    WrapRequestStage::FeePending
+                },
                 updated_at: now,
                 mint_nonce: None,
                 mint_submitted_at_time: 0,
                 mint_submit_status: MintSubmitStatus::NotSubmitted,
+                refund_amount_e8s: None,
             },
         })?;
         state.wrap_requests.insert(request_id, req.clone());
@@ -1295,6 +2056,502 @@ fn enqueue_wrap_request_once(request_id: TxId) {
     });
 }
 
+fn deposit_subaccount(evm_recipient: &[u8]) -> [u8; 32] {
+    let mut keccak = Keccak::v256();
+    keccak.update(b"kasane.deposit.subaccount.v1");
+    hash_len_prefixed(&mut keccak, evm_recipient);
+    let mut out = [0u8; 32];
+    keccak.finalize(&mut out);
+    out
+}
+
+fn deposit_account_key(asset_id: &[u8], evm_recipient: &[u8]) -> TxId {
+    let mut keccak = Keccak::v256();
+    keccak.update(b"kasane.deposit.account.v1");
+    hash_len_prefixed(&mut keccak, asset_id);
+    hash_len_prefixed(&mut keccak, evm_recipient);
+    let mut out = [0u8; 32];
+    keccak.finalize(&mut out);
+    TxId(out)
+}
+
+fn derive_deposit_request_id(account_key: TxId, seq: u64) -> TxId {
+    let mut keccak = Keccak::v256();
+    keccak.update(b"kasane.deposit.request.v1");
+    keccak.update(&account_key.0);
+    keccak.update(&seq.to_be_bytes());
+    let mut out = [0u8; 32];
+    keccak.finalize(&mut out);
+    TxId(out)
+}
+
+#[ic_cdk::query]
+fn get_deposit_account(evm_recipient: Vec<u8>) -> Result<DepositAccountView, ApiError> {
+    validate_evm_address(&evm_recipient, "arg.evm_recipient_invalid")
+        .map_err(|err| api_invalid_argument(&err, &err))?;
+    Ok(DepositAccountView {
+        owner: current_wrap_canister_id(),
+        subaccount: deposit_subaccount(&evm_recipient).to_vec(),
+    })
+}
+
+#[ic_cdk::update]
+async fn notify_deposit(args: NotifyDepositArgs) -> Result<NotifyDepositOk, ApiError> {
+    if let Some(reason) = reject_anonymous_update() {
+        return Err(api_rejected(&reason, &reason));
+    }
+    if let Some(reason) = reject_write_reason() {
+        return Err(api_rejected(&reason, &reason));
+    }
+    if let Some(reason) = reject_wrap_reason() {
+        return Err(api_rejected(&reason, &reason));
+    }
+    validate_non_anonymous_principal(&args.asset_id, "arg.asset_id_anonymous")
+        .map_err(|err| api_invalid_argument(&err, &err))?;
+    validate_evm_address(&args.evm_recipient, "arg.evm_recipient_invalid")
+        .map_err(|err| api_invalid_argument(&err, &err))?;
+    ensure_asset_allowed(args.asset_id).map_err(|err| api_rejected(&err, &err))?;
+    validate_wrap_gas_limit(DEPOSIT_WRAP_GAS_LIMIT)
+        .map_err(|err| api_invalid_argument(&err, &err))?;
+    let schedule = wrap_fee_schedule(args.asset_id).map_err(|err| api_internal(&err, &err))?;
+    let charged_gas_price_wei = wrap_charged_gas_price_wei(schedule.gas_price_buffer_bps)?;
+    let owner = current_wrap_canister_id();
+    let account_key = deposit_account_key(args.asset_id.as_slice(), &args.evm_recipient);
+    let subaccount = deposit_subaccount(&args.evm_recipient);
+    let sweep = match deposit_in_flight_sweep(account_key) {
+        Some(sweep) => sweep,
+        None => {
+            let balance = fetch_icrc1_balance(args.asset_id, owner, Some(subaccount))
+                .await
+                .map_err(|err| api_rejected(&err, &err))?;
+            let fee = fetch_icrc1_fee(args.asset_id)
+                .await
+                .map_err(|err| api_rejected(&err, &err))?;
+            let amount_e8s = deposit_sweep_amount(balance, fee).ok_or_else(|| {
+                api_rejected("deposit.balance_below_fee", "deposit.balance_below_fee")
+            })?;
+            deposit_wrap_fee_e8s(&schedule, amount_e8s)?;
+            check_bridge_limit(
+                args.asset_id.as_slice(),
+                BridgeDirection::Wrap,
+                amount_e8s,
+                current_time_nanos(),
+            )
+            .map_err(|err| api_rejected(&err, &err))?;
+            begin_deposit_sweep(account_key, amount_e8s)
+        }
+    };
+    let charged_fee_e8s = deposit_wrap_fee_e8s(&schedule, sweep.amount_e8s)?;
+    let request_id = TxId(sweep.request_id);
+    let transfer = attempt_icrc1_transfer(
+        args.asset_id,
+        Some(subaccount),
+        owner,
+        None,
+        Nat::from(sweep.amount_e8s),
+        request_memo(request_id, TransferMemoKind::Pull),
+        sweep.created_at_time,
+    )
+    .await;
+    let sweep_ledger_tx_id = finish_deposit_sweep(
+        account_key,
+        sweep,
+        DepositWrapTarget {
+            owner,
+            asset_id: args.asset_id,
+            evm_recipient: args.evm_recipient,
+            charged_gas_price_wei,
+            charged_fee_e8s,
+        },
+        transfer,
+    )
+    .map_err(|err| api_rejected(&err, &err))?;
+    #[cfg(target_arch = "wasm32")]
+    schedule_wrap_worker();
+    Ok(NotifyDepositOk {
+        request_id: request_id.0.to_vec(),
+        amount_e8s: Nat::from(sweep.amount_e8s),
+        sweep_ledger_tx_id,
+    })
+}
+
+struct DepositWrapTarget {
+    owner: Principal,
+    asset_id: Principal,
+    evm_recipient: Vec<u8>,
+    charged_gas_price_wei: u128,
+    charged_fee_e8s: u128,
+}
+
+/// deposit wrap は別 ledger から fee を引き取れないので、schedule の fee を常に入金した asset から差し引く。
+/// fee_in_asset と同じく gas 分は含めない。
+fn deposit_wrap_fee_e8s(schedule: &WrapFeeSchedule, amount_e8s: u128) -> Result<u128, ApiError> {
+    let charged_fee_e8s =
+        wrap_amount_fee_e8s(amount_e8s, wrap_fee_tier_bps(&schedule.tiers, amount_e8s))
+            .and_then(|amount_fee_e8s| {
+                wrap_charged_fee_e8s_raw(1, 0, u128::from(schedule.cycle_fee_e8s), amount_fee_e8s)
+            })
+            .ok_or_else(|| api_internal("fee.quote_out_of_range", "fee.quote_out_of_range"))?;
+    wrap_mint_amount_raw(1, amount_e8s, charged_fee_e8s)
+        .ok_or_else(|| api_rejected("fee.exceeds_amount", "fee.exceeds_amount"))?;
+    Ok(charged_fee_e8s)
+}
+
+fn deposit_in_flight_sweep(account_key: TxId) -> Option<DepositSweepV1> {
+    with_state(|state| {
+        state
+            .deposit_accounts
+            .get(&account_key)
+            .and_then(|account| account.in_flight)
+    })
+}
+
+/// sweep の request id と created_at_time を先に固定して残す。
+/// 残高照会の await 中に別の notify が先に始めていれば、その sweep をそのまま使う。
+fn begin_deposit_sweep(account_key: TxId, amount_e8s: u128) -> DepositSweepV1 {
+    with_state_mut(|state| {
+        let mut account = state.deposit_accounts.get(&account_key).unwrap_or_default();
+        if let Some(sweep) = account.in_flight {
+            return sweep;
+        }
+        // 口座状態が読めず連番が巻き戻っても、既存の wrap request id は再利用しない。
+        let mut seq = account.next_seq;
+        let mut request_id = derive_deposit_request_id(account_key, seq);
+        while state.wrap_requests.get(&request_id).is_some() {
+            seq = seq.saturating_add(1);
+            request_id = derive_deposit_request_id(account_key, seq);
+        }
+        let now = current_time_nanos();
+        let sweep = DepositSweepV1 {
+            request_id: request_id.0,
+            amount_e8s,
+            created_at_time: now,
+        };
+        account.next_seq = seq.saturating_add(1);
+        account.in_flight = Some(sweep);
+        account.updated_at = now;
+        state.deposit_accounts.insert(account_key, account);
+        sweep
+    })
+}
+
+/// 成功した sweep は pull 済みの wrap request として worker に渡す。
+/// 台帳が拒否した sweep は手放し、呼び出し自体の失敗は同じ memo で再試行できるよう残す。
+fn finish_deposit_sweep(
+    account_key: TxId,
+    sweep: DepositSweepV1,
+    target: DepositWrapTarget,
+    transfer: Result<Vec<u8>, String>,
+) -> Result<Vec<u8>, String> {
+    let definite_failure = transfer
+        .as_ref()
+        .err()
+        .is_some_and(|code| code.starts_with("ledger.transfer_failed:"));
+    let request_id = TxId(sweep.request_id);
+    let out = match transfer {
+        Ok(sweep_ledger_tx_id) => {
+            insert_deposit_wrap_request(request_id, sweep, target, sweep_ledger_tx_id.clone()).map(
+                |()| {
+                    enqueue_wrap_request_once(request_id);
+                    sweep_ledger_tx_id
+                },
+            )
+        }
+        Err(code) => Err(code),
+    };
+    if !deposit_sweep_stays_in_flight(out.is_ok(), definite_failure) {
+        with_state_mut(|state| {
+            let Some(mut account) = state.deposit_accounts.get(&account_key) else {
+                return;
+            };
+            if account.in_flight == Some(sweep) {
+                account.in_flight = None;
+                account.updated_at = current_time_nanos();
+                state.deposit_accounts.insert(account_key, account);
+            }
+        });
+    }
+    out
+}
+
+/// sweep 済みの入金を pull 済み wrap request として残す。fee は入金した asset から差し引く。
+fn insert_deposit_wrap_request(
+    request_id: TxId,
+    sweep: DepositSweepV1,
+    target: DepositWrapTarget,
+    sweep_ledger_tx_id: Vec<u8>,
+) -> Result<(), String> {
+    let sweep_ledger_tx_id = validated_ledger_tx_id(sweep_ledger_tx_id)?;
+    let now = current_time_nanos();
+    let req = sanitize_wrap_request(evm_db::chain_data::WrapStoredRequest {
+        caller: target.owner.as_slice().to_vec(),
+        asset_id: target.asset_id.as_slice().to_vec(),
+        amount: u256_from_u128(sweep.amount_e8s).to_vec(),
+        evm_recipient: target.evm_recipient,
+        gas_limit: DEPOSIT_WRAP_GAS_LIMIT,
+        fee_ledger_canister: target.asset_id.as_slice().to_vec(),
+        max_fee_e8s: target.charged_fee_e8s,
+        quoted_gas_price_wei: target.charged_gas_price_wei,
+        fee_created_at_time: 0,
+        pull_created_at_time: sweep.created_at_time,
+        withdraw_created_at_time: 0,
+        fee_in_asset: true,
+        deadline_at: wrap_deadline_at(now, WRAP_DEADLINE_DEFAULT_SECS),
+        kind: WrapAssetKind::Fungible,
+        result: evm_db::chain_data::WrapRequestResult {
+            status: StoredRequestStatus::Queued,
+            pull_ledger_tx_id: Some(sweep_ledger_tx_id),
+            mint_tx_id: None,
+            error_code: None,
+            withdrawn: false,
+            withdraw_ledger_tx_id: None,
+            withdraw_error_code: None,
+            withdraw_in_progress: false,
+            mint_failed_recoverable: false,
+            fee_ledger_tx_id: None,
+            charged_fee_e8s: Some(target.charged_fee_e8s),
+            charged_gas_price_wei: Some(target.charged_gas_price_wei),
+            stage: WrapRequestStage::Pulled,
+            updated_at: now,
+            mint_nonce: None,
+            mint_submitted_at_time: 0,
+            mint_submit_status: MintSubmitStatus::NotSubmitted,
+            refund_amount_e8s: None,
+        },
+    })?;
+    with_state_mut(|state| {
+        if state.wrap_requests.get(&request_id).is_none() {
+            // 入金は sweep 済みなので、上限の判定は sweep 前に済ませ、ここでは量だけ積む。
+            record_bridge_volume(
+                state,
+                &req.asset_id,
+                BridgeDirection::Wrap,
+                sweep.amount_e8s,
+                now,
+            );
+            state.wrap_requests.insert(request_id, req);
+        }
+    });
+    Ok(())
+}
+
+/// 入金 request の返金先は、利用者が再度 notify できるよう元の入金用 subaccount に戻す。
+fn wrap_refund_account(
+    req: &evm_db::chain_data::WrapStoredRequest,
+) -> Result<(Principal, Option<[u8; 32]>), String> {
+    let caller = principal_from_stored_bytes(&req.caller)?;
+    if caller == current_wrap_canister_id() {
+        return Ok((caller, Some(deposit_subaccount(&req.evm_recipient))));
+    }
+    Ok((caller, None))
+}
+
+async fn fetch_icrc1_balance(
+    ledger: Principal,
+    owner: Principal,
+    subaccount: Option<[u8; 32]>,
+) -> Result<u128, String> {
+    let account = Icrc1Account {
+        owner,
+        subaccount: subaccount.map(|subaccount| subaccount.to_vec()),
+    };
+    let call_result = ic_cdk::call::Call::unbounded_wait(ledger, "icrc1_balance_of")
+        .with_arg(account)
+        .await;
+    match call_result {
+        Ok(resp) => match resp.candid_tuple::<(Nat,)>() {
+            Ok((balance,)) => {
+                nat_to_u128(&balance).ok_or_else(|| "ledger.balance_out_of_range".to_string())
+            }
+            Err(err) => Err(format!("ledger.balance_decode_failed:{err}")),
+        },
+        Err(err) => Err(format!("ledger.balance_call_failed:{err}")),
+    }
+}
+
+thread_local! {
+    // heap のみ。upgrade 後は次の定期確認まで空になる。
+    static LAST_RESERVES_REPORT: std::cell::RefCell<Option<ReservesReportView>> =
+        const { std::cell::RefCell::new(None) };
+}
+
+// 直近の定期確認 (または controller の確認) の結果を返す。ここからは ledger を呼ばず、wrap も止めない。
+#[ic_cdk::query]
+fn get_reserves() -> Result<ReservesReportView, ApiError> {
+    let mut report = LAST_RESERVES_REPORT
+        .with(|report| report.borrow().clone())
+        .ok_or_else(|| api_rejected("reserves.not_checked", "reserves.not_checked"))?;
+    report.wrap_paused = with_state(|state| state.ops_state.get().wrap_paused);
+    Ok(report)
+}
+
+// 許可済み asset ごとに、canister の台帳残高が wrapped token の発行量と処理中 request を裏付けているかをその場で確かめる。
+#[ic_cdk::update]
+async fn check_reserves() -> Result<ReservesReportView, String> {
+    if let Some(reason) = reject_anonymous_update() {
+        return Err(reason);
+    }
+    require_control_plane_write()?;
+    run_reserves_check().await.map_err(api_error_code)
+}
+
+// 不足で止めた wrap は、原因を確認した controller だけが再開できる。
+#[ic_cdk::update]
+fn set_wrap_paused(paused: bool) -> Result<OpsStatusView, String> {
+    if let Some(reason) = reject_anonymous_update() {
+        return Err(reason);
+    }
+    require_control_plane_write()?;
+    with_state_mut(|state| {
+        let mut ops = *state.ops_state.get();
+        ops.wrap_paused = paused;
+        state.ops_state.set(ops);
+    });
+    Ok(get_ops_status())
+}
+
+fn reject_wrap_reason() -> Option<String> {
+    with_state(|state| ic_evm_ops::reject_wrap_reason(*state.ops_state.get()))
+}
+
+async fn run_reserves_check() -> Result<ReservesReportView, ApiError> {
+    let assets = get_allowed_assets().map_err(|err| api_internal(&err, &err))?;
+    let canister = ic_cdk::api::canister_self();
+    let mut views = Vec::with_capacity(assets.len());
+    let mut shortfall = false;
+    for asset in assets {
+        let ledger_balance = fetch_icrc1_balance(asset, canister, None).await;
+        // 残高の応答と同じ message で読み、待ちの間に進んだ mint や払い出しとずれないようにする。
+        let supply = evm_core::kasane_precompiles::wrapped_token_supply(asset.as_slice());
+        let (in_flight_wrap, in_flight_unwrap) = reserve_in_flight_totals(asset.as_slice());
+        let (view, asset_shortfall) = asset_reserves_view(
+            asset,
+            ledger_balance,
+            supply,
+            in_flight_wrap,
+            in_flight_unwrap,
+        );
+        if asset_shortfall {
+            warn!(event = "reserves_shortfall", asset = %asset, discrepancy = ?view.discrepancy);
+        }
+        shortfall |= asset_shortfall;
+        views.push(view);
+    }
+    let now = current_time_nanos();
+    let ops = with_state_mut(|state| {
+        let ops = ic_evm_ops::observe_reserves(shortfall, now, *state.ops_state.get());
+        state.ops_state.set(ops);
+        ops
+    });
+    let report = ReservesReportView {
+        checked_at: now,
+        wrap_paused: ops.wrap_paused,
+        assets: views,
+    };
+    LAST_RESERVES_REPORT.with(|last| *last.borrow_mut() = Some(report.clone()));
+    Ok(report)
+}
+
+/// 台帳残高か supply が読めない asset は判定できないので、不足扱いにはせず error だけ返す。
+fn asset_reserves_view(
+    asset: Principal,
+    ledger_balance: Result<u128, String>,
+    supply: Result<Option<evm_core::kasane_precompiles::WrappedTokenSupply>, String>,
+    in_flight_wrap: u128,
+    in_flight_unwrap: u128,
+) -> (AssetReservesView, bool) {
+    let mut view = AssetReservesView {
+        asset_id: asset,
+        wrapped_token_address: None,
+        ledger_balance: None,
+        wrapped_supply: None,
+        in_flight_wrap: Nat::from(in_flight_wrap),
+        in_flight_unwrap: Nat::from(in_flight_unwrap),
+        discrepancy: None,
+        error: None,
+    };
+    let supply = match supply {
+        Ok(Some(supply)) => {
+            view.wrapped_token_address = Some(supply.token.to_vec());
+            Nat(BigUint::from_bytes_be(&supply.total_supply))
+        }
+        Ok(None) => Nat::from(0u8),
+        Err(err) => {
+            view.error = Some(format!("reserves.supply_unavailable:{err}"));
+            return (view, false);
+        }
+    };
+    view.wrapped_supply = Some(supply.clone());
+    let balance = match ledger_balance {
+        Ok(balance) => balance,
+        Err(err) => {
+            view.error = Some(err);
+            return (view, false);
+        }
+    };
+    view.ledger_balance = Some(Nat::from(balance));
+    let liabilities = nat_to_u128(&supply)
+        .and_then(|supply| reserve_liabilities(supply, in_flight_wrap, in_flight_unwrap));
+    let owed =
+        BigInt::from(supply.0) + BigInt::from(in_flight_wrap) + BigInt::from(in_flight_unwrap);
+    view.discrepancy = Some(Int(BigInt::from(balance) - owed));
+    (view, reserve_shortfall(balance, liabilities))
+}
+
+/// 台帳には入ったがまだ mint していない wrap と、EVM で burn 済みでまだ払い出していない unwrap を足す。
+fn reserve_in_flight_totals(asset_id: &[u8]) -> (u128, u128) {
+    let mut total = (0u128, 0u128);
+    with_state(|state| {
+        for entry in state.wrap_requests.iter() {
+            let req = entry.value();
+            if req.asset_id != asset_id || !wrap_request_holds_reserves(&req) {
+                continue;
+            }
+            let amount =
+                nat_to_u128(&Nat(BigUint::from_bytes_be(&req.amount))).unwrap_or(u128::MAX);
+            total.0 = total.0.saturating_add(amount);
+        }
+        for entry in state.unwrap_requests.iter() {
+            let req = entry.value();
+            if req.asset_id != asset_id
+                || is_native_withdraw_dispatch_request(&req)
+                || req.kind == UnwrapAssetKind::Nft
+                || req.status == UnwrapRequestStatus::Dispatched
+            {
+                continue;
+            }
+            let amount =
+                nat_to_u128(&Nat(BigUint::from_bytes_be(&req.amount))).unwrap_or(u128::MAX);
+            total.1 = total.1.saturating_add(amount);
+        }
+    });
+    total
+}
+
+/// native deposit は wrapped token を出さず、NFT は量を持たないので数えない。返金済みと mint 済みは預かりから外れている。
+fn wrap_request_holds_reserves(req: &evm_db::chain_data::WrapStoredRequest) -> bool {
+    req.gas_limit != 0
+        && req.kind == WrapAssetKind::Fungible
+        && req.result.pull_ledger_tx_id.is_some()
+        && !req.result.withdrawn
+        && !matches!(
+            req.result.stage,
+            WrapRequestStage::Succeeded | WrapRequestStage::Refunded
+        )
+}
+
+fn schedule_reserves_check() {
+    ic_cdk_timers::set_timer(
+        std::time::Duration::from_secs(RESERVES_CHECK_INTERVAL_SECS),
+        async move {
+            if let Err(err) = run_reserves_check().await {
+                error!(error = ?err, "reserves check failed");
+            }
+            schedule_reserves_check();
+        },
+    );
+}
+
 fn record_wrap_request_failure(request_id: TxId, code: String, mint_failed_recoverable: bool) {
     with_state_mut(|state| {
         let Some(mut req) = state.wrap_requests.get(&request_id) else {
@@ -1347,7 +2604,34 @@ fn expected_wrap_factory_address() -> Result<Vec<u8>, String> {
 
 fn encode_factory_mint_for_asset_call_data(
     asset_id: &[u8],
-    token_decimals: u8,
+    token_decimals: u8,
+    recipient: &[u8],
+    amount: &[u8],
+) -> Result<Vec<u8>, String> {
+    principal_from_stored_bytes(asset_id)?;
+    validate_evm_address(recipient, "arg.evm_recipient_invalid")?;
+    if amount.len() != 32 {
+        return Err("arg.amount_invalid".to_string());
+    }
+    let mut data = Vec::with_capacity(4 + 32 * 5 + 64);
+    data.extend_from_slice(&factory_mint_for_asset_selector());
+    data.extend_from_slice(&u256_from_u64(128));
+    data.extend_from_slice(&u256_from_u64(u64::from(token_decimals)));
+    data.extend_from_slice(&[0u8; 12]);
+    data.extend_from_slice(recipient);
+    data.extend_from_slice(amount);
+    data.extend_from_slice(&u256_from_u64(asset_id.len() as u64));
+    data.extend_from_slice(asset_id);
+    let padded = (32 - (asset_id.len() % 32)) % 32;
+    if padded != 0 {
+        data.extend(vec![0u8; padded]);
+    }
+    Ok(data)
+}
+
+fn encode_factory_mint_for_asset_with_metadata_call_data(
+    asset_id: &[u8],
+    metadata: &WrappedTokenMetadata,
     recipient: &[u8],
     amount: &[u8],
 ) -> Result<Vec<u8>, String> {
@@ -1356,16 +2640,82 @@ fn encode_factory_mint_for_asset_call_data(
     if amount.len() != 32 {
         return Err("arg.amount_invalid".to_string());
     }
-    let mut data = Vec::with_capacity(4 + 32 * 5 + 64);
-    data.extend_from_slice(&factory_mint_for_asset_selector());
-    data.extend_from_slice(&u256_from_u64(128));
-    data.extend_from_slice(&u256_from_u64(u64::from(token_decimals)));
+    let asset = abi_dynamic_bytes(asset_id);
+    let tuple = encode_token_metadata_tuple(metadata);
+    let mut data = Vec::with_capacity(4 + 32 * 5 + asset.len() + tuple.len());
+    data.extend_from_slice(&factory_mint_for_asset_with_metadata_selector());
+    data.extend_from_slice(&u256_from_u64(160));
+    data.extend_from_slice(&u256_from_u64(u64::from(metadata.decimals)));
     data.extend_from_slice(&[0u8; 12]);
     data.extend_from_slice(recipient);
     data.extend_from_slice(amount);
-    data.extend_from_slice(&u256_from_u64(asset_id.len() as u64));
-    data.extend_from_slice(asset_id);
-    let padded = (32 - (asset_id.len() % 32)) % 32;
+    data.extend_from_slice(&u256_from_u64(160 + asset.len() as u64));
+    data.extend_from_slice(&asset);
+    data.extend_from_slice(&tuple);
+    Ok(data)
+}
+
+fn encode_factory_set_asset_metadata_call_data(
+    asset_id: &[u8],
+    metadata: &WrappedTokenMetadata,
+) -> Result<Vec<u8>, String> {
+    principal_from_stored_bytes(asset_id)?;
+    let asset = abi_dynamic_bytes(asset_id);
+    let tuple = encode_token_metadata_tuple(metadata);
+    let mut data = Vec::with_capacity(4 + 32 * 2 + asset.len() + tuple.len());
+    data.extend_from_slice(&factory_set_asset_metadata_selector());
+    data.extend_from_slice(&u256_from_u64(64));
+    data.extend_from_slice(&u256_from_u64(64 + asset.len() as u64));
+    data.extend_from_slice(&asset);
+    data.extend_from_slice(&tuple);
+    Ok(data)
+}
+
+/// `(string name, string symbol, string logo, uint256 ledgerFee)` の tuple。offset は tuple の先頭から数える。
+fn encode_token_metadata_tuple(metadata: &WrappedTokenMetadata) -> Vec<u8> {
+    let name = abi_dynamic_bytes(metadata.name.as_bytes());
+    let symbol = abi_dynamic_bytes(metadata.symbol.as_bytes());
+    let logo = abi_dynamic_bytes(metadata.logo.as_bytes());
+    let mut out = Vec::with_capacity(32 * 4 + name.len() + symbol.len() + logo.len());
+    out.extend_from_slice(&u256_from_u64(128));
+    out.extend_from_slice(&u256_from_u64(128 + name.len() as u64));
+    out.extend_from_slice(&u256_from_u64((128 + name.len() + symbol.len()) as u64));
+    out.extend_from_slice(&u256_from_u128(metadata.ledger_fee));
+    out.extend_from_slice(&name);
+    out.extend_from_slice(&symbol);
+    out.extend_from_slice(&logo);
+    out
+}
+
+/// ABI の動的 bytes / string。長さ word の後に 32 byte 境界まで 0 を詰める。
+fn abi_dynamic_bytes(bytes: &[u8]) -> Vec<u8> {
+    let padded_len = bytes.len().div_ceil(32) * 32;
+    let mut out = Vec::with_capacity(32 + padded_len);
+    out.extend_from_slice(&u256_from_u64(bytes.len() as u64));
+    out.extend_from_slice(bytes);
+    out.resize(32 + padded_len, 0);
+    out
+}
+
+fn encode_factory_mint_nft_call_data(
+    collection_id: &[u8],
+    recipient: &[u8],
+    token_id: &[u8],
+) -> Result<Vec<u8>, String> {
+    principal_from_stored_bytes(collection_id)?;
+    validate_evm_address(recipient, "arg.evm_recipient_invalid")?;
+    if token_id.len() != 32 {
+        return Err("arg.token_id_invalid".to_string());
+    }
+    let mut data = Vec::with_capacity(4 + 32 * 4 + 32);
+    data.extend_from_slice(&factory_mint_nft_selector());
+    data.extend_from_slice(&u256_from_u64(96));
+    data.extend_from_slice(&[0u8; 12]);
+    data.extend_from_slice(recipient);
+    data.extend_from_slice(token_id);
+    data.extend_from_slice(&u256_from_u64(collection_id.len() as u64));
+    data.extend_from_slice(collection_id);
+    let padded = (32 - (collection_id.len() % 32)) % 32;
     if padded != 0 {
         data.extend(vec![0u8; padded]);
     }
@@ -1406,6 +2756,20 @@ fn factory_mint_for_asset_selector() -> [u8; 4] {
     selector(b"mintForAsset(bytes,uint8,address,uint256)")
 }
 
+fn factory_mint_for_asset_with_metadata_selector() -> [u8; 4] {
+    selector(
+        b"mintForAssetWithMetadata(bytes,uint8,address,uint256,(string,string,string,uint256))",
+    )
+}
+
+fn factory_set_asset_metadata_selector() -> [u8; 4] {
+    selector(b"setAssetMetadata(bytes,(string,string,string,uint256))")
+}
+
+fn factory_mint_nft_selector() -> [u8; 4] {
+    selector(b"mintNftForCollection(bytes,address,uint256)")
+}
+
 fn encode_balance_of_call_data(owner: &[u8]) -> Vec<u8> {
     let mut out = Vec::with_capacity(4 + 32);
     out.extend_from_slice(&selector(b"balanceOf(address)"));
@@ -1466,22 +2830,55 @@ fn native_withdraw_receive_amount(amount_e8s: u128, ledger_fee_e8s: u128) -> Res
 fn quote_native_deposit(args: QuoteNativeDepositArgs) -> Result<QuoteNativeDepositOk, ApiError> {
     validate_evm_address(&args.evm_recipient, "arg.evm_recipient_invalid")
         .map_err(|err| api_invalid_argument(&err, &err))?;
-    if nat_to_u128(&args.amount_e8s)
-        .filter(|amount| *amount > 0)
-        .is_none()
-    {
+    let Some(amount_e8s) = nat_to_u128(&args.amount_e8s).filter(|amount| *amount > 0) else {
         return Err(api_invalid_argument(
             "arg.amount_invalid",
             "arg.amount_invalid",
         ));
-    }
-    let fee_policy = current_fee_policy().map_err(|err| api_internal(&err, &err))?;
-    let native_ledger_canister =
-        current_native_ledger_canister().map_err(|err| api_internal(&err, &err))?;
+    };
+    let quote = native_deposit_quote(amount_e8s)?;
     Ok(QuoteNativeDepositOk {
-        charged_fee_e8s: Nat::from(fee_policy.cycle_fee_e8s),
-        native_ledger_canister,
-        fee_ledger_canister: fee_policy.fee_ledger_canister,
+        charged_fee_e8s: Nat::from(quote.charged_fee_e8s),
+        native_ledger_canister: quote.native_ledger,
+        fee_ledger_canister: quote.fee_ledger_canister,
+        fee_in_asset: quote.fee_in_asset,
+        credit_amount_e8s: Nat::from(quote.credit_amount_e8s),
+    })
+}
+
+#[derive(Debug)]
+struct NativeDepositQuote {
+    native_ledger: Principal,
+    fee_ledger_canister: Principal,
+    charged_fee_e8s: u128,
+    fee_in_asset: bool,
+    credit_amount_e8s: u128,
+}
+
+/// native deposit は gas を払わないので、schedule の cycle fee と段階 fee だけを取る。
+fn native_deposit_quote(amount_e8s: u128) -> Result<NativeDepositQuote, ApiError> {
+    let native_ledger = current_native_ledger_canister().map_err(|err| api_internal(&err, &err))?;
+    let schedule = wrap_fee_schedule(native_ledger).map_err(|err| api_internal(&err, &err))?;
+    let fee_in_asset = u64::from(schedule.fee_in_asset);
+    let charged_fee_e8s =
+        wrap_amount_fee_e8s(amount_e8s, wrap_fee_tier_bps(&schedule.tiers, amount_e8s))
+            .and_then(|amount_fee_e8s| {
+                wrap_charged_fee_e8s_raw(
+                    fee_in_asset,
+                    0,
+                    u128::from(schedule.cycle_fee_e8s),
+                    amount_fee_e8s,
+                )
+            })
+            .ok_or_else(|| api_internal("fee.quote_out_of_range", "fee.quote_out_of_range"))?;
+    let credit_amount_e8s = wrap_mint_amount_raw(fee_in_asset, amount_e8s, charged_fee_e8s)
+        .ok_or_else(|| api_rejected("fee.exceeds_amount", "fee.exceeds_amount"))?;
+    Ok(NativeDepositQuote {
+        native_ledger,
+        fee_ledger_canister: schedule.fee_ledger_canister,
+        charged_fee_e8s,
+        fee_in_asset: schedule.fee_in_asset,
+        credit_amount_e8s,
     })
 }
 
@@ -1596,12 +2993,15 @@ async fn submit_native_deposit(
     if amount.iter().all(|&byte| byte == 0) {
         return Err(api_invalid_argument("arg.amount_zero", "arg.amount_zero"));
     }
-    let amount_wei = native_deposit_amount_wei_bytes(&args.amount_e8s)?;
+    native_deposit_amount_wei_bytes(&args.amount_e8s)?;
     let max_fee_e8s = nat_to_u128(&args.max_fee_e8s).ok_or_else(|| {
         api_invalid_argument("arg.max_fee_out_of_range", "arg.max_fee_out_of_range")
     })?;
-    let (fee_policy, native_ledger) =
-        prepare_native_deposit_funding(args.fee_ledger_canister, max_fee_e8s)?;
+    let amount_e8s = nat_to_u128(&args.amount_e8s).ok_or_else(|| {
+        api_invalid_argument("arg.amount_out_of_range", "arg.amount_out_of_range")
+    })?;
+    let quote = prepare_native_deposit_funding(args.fee_ledger_canister, max_fee_e8s, amount_e8s)?;
+    let native_ledger = quote.native_ledger;
 
     let request_id = TxId(derive_native_deposit_request_id(
         caller.as_slice(),
@@ -1619,20 +3019,25 @@ async fn submit_native_deposit(
         native_ledger,
         amount: amount.to_vec(),
         evm_recipient: args.evm_recipient.clone(),
-        fee_ledger_canister: fee_policy.fee_ledger_canister,
+        fee_ledger_canister: quote.fee_ledger_canister,
         max_fee_e8s,
-        charged_fee_e8s: fee_policy.cycle_fee_e8s,
+        charged_fee_e8s: quote.charged_fee_e8s,
+        fee_in_asset: quote.fee_in_asset,
     })
     .map_err(|err| {
         clear_wrap_pending_submission(request_id);
         api_rejected(&err, &err)
     })?;
 
-    if req.result.fee_ledger_tx_id.is_none() {
+    let charged_fee_e8s = req
+        .result
+        .charged_fee_e8s
+        .ok_or_else(|| api_internal("fee.charged_missing", "fee.charged_missing"))?;
+    if req.result.fee_ledger_tx_id.is_none() && !req.fee_in_asset {
         let fee_ledger_tx_id = attempt_icrc2_transfer_from(
             caller,
-            fee_policy.fee_ledger_canister,
-            Nat::from(fee_policy.cycle_fee_e8s),
+            quote.fee_ledger_canister,
+            Nat::from(charged_fee_e8s),
             request_memo(request_id, TransferMemoKind::Fee),
             req.fee_created_at_time,
         )
@@ -1678,19 +3083,29 @@ async fn submit_native_deposit(
     }
 
     if req.result.mint_tx_id.is_none() || req.result.status != StoredRequestStatus::Succeeded {
+        let credit_amount = wrap_mint_amount(&req).map_err(|err| {
+            clear_wrap_pending_submission(request_id);
+            api_internal(&err, &err)
+        })?;
+        let amount_wei =
+            native_deposit_amount_wei_bytes(&Nat(BigUint::from_bytes_be(&credit_amount)))?;
         finalize_native_deposit_credit(request_id, &args.evm_recipient, amount_wei)?;
     }
     clear_wrap_pending_submission(request_id);
-    let fee_ledger_tx_id = with_state(|state| {
-        state
-            .wrap_requests
-            .get(&request_id)
-            .and_then(|req| req.result.fee_ledger_tx_id)
-    })
-    .ok_or_else(|| api_internal("request.missing_fee_tx", "request.missing_fee_tx"))?;
+    let fee_ledger_tx_id = if req.fee_in_asset {
+        Vec::new()
+    } else {
+        with_state(|state| {
+            state
+                .wrap_requests
+                .get(&request_id)
+                .and_then(|req| req.result.fee_ledger_tx_id)
+        })
+        .ok_or_else(|| api_internal("request.missing_fee_tx", "request.missing_fee_tx"))?
+    };
     Ok(SubmitNativeDepositOk {
         request_id: request_id.0.to_vec(),
-        charged_fee_e8s: Nat::from(fee_policy.cycle_fee_e8s),
+        charged_fee_e8s: Nat::from(charged_fee_e8s),
         fee_ledger_tx_id,
     })
 }
@@ -1715,7 +3130,11 @@ fn existing_native_deposit_response(
         if existing.result.status != StoredRequestStatus::Succeeded {
             return None;
         }
-        let fee_ledger_tx_id = existing.result.fee_ledger_tx_id.clone()?;
+        let fee_ledger_tx_id = if existing.fee_in_asset {
+            Vec::new()
+        } else {
+            existing.result.fee_ledger_tx_id.clone()?
+        };
         let charged_fee_e8s = existing.result.charged_fee_e8s?;
         Some(Ok(SubmitNativeDepositOk {
             request_id: request_id.0.to_vec(),
@@ -1728,16 +3147,16 @@ fn existing_native_deposit_response(
 fn prepare_native_deposit_funding(
     fee_ledger_canister: Principal,
     max_fee_e8s: u128,
-) -> Result<(FeePolicyView, Principal), ApiError> {
-    let fee_policy = current_fee_policy().map_err(|err| api_internal(&err, &err))?;
-    let native_ledger = current_native_ledger_canister().map_err(|err| api_internal(&err, &err))?;
-    if fee_policy.fee_ledger_canister != fee_ledger_canister {
+    amount_e8s: u128,
+) -> Result<NativeDepositQuote, ApiError> {
+    let quote = native_deposit_quote(amount_e8s)?;
+    if quote.fee_ledger_canister != fee_ledger_canister {
         return Err(api_rejected("fee.ledger_changed", "fee.ledger_changed"));
     }
-    if u128::from(fee_policy.cycle_fee_e8s) > max_fee_e8s {
+    if quote.charged_fee_e8s > max_fee_e8s {
         return Err(api_rejected("fee.quote_exceeded", "fee.quote_exceeded"));
     }
-    Ok((fee_policy, native_ledger))
+    Ok(quote)
 }
 
 struct NativeDepositRequestDraft {
@@ -1748,7 +3167,8 @@ struct NativeDepositRequestDraft {
     evm_recipient: Vec<u8>,
     fee_ledger_canister: Principal,
     max_fee_e8s: u128,
-    charged_fee_e8s: u64,
+    charged_fee_e8s: u128,
+    fee_in_asset: bool,
 }
 
 fn ensure_native_deposit_request_before_fee(
@@ -1771,6 +3191,9 @@ fn ensure_native_deposit_request_before_fee(
             fee_created_at_time: now,
             pull_created_at_time: now,
             withdraw_created_at_time: 0,
+            fee_in_asset: draft.fee_in_asset,
+            deadline_at: 0,
+            kind: WrapAssetKind::Fungible,
             result: evm_db::chain_data::WrapRequestResult {
                 status: StoredRequestStatus::Running,
                 pull_ledger_tx_id: None,
@@ -1782,13 +3205,18 @@ fn ensure_native_deposit_request_before_fee(
                 withdraw_in_progress: false,
                 mint_failed_recoverable: false,
                 fee_ledger_tx_id: None,
-                charged_fee_e8s: Some(u128::from(draft.charged_fee_e8s)),
+                charged_fee_e8s: Some(draft.charged_fee_e8s),
                 charged_gas_price_wei: Some(0),
-                stage: WrapRequestStage::FeePending,
+                stage: if draft.fee_in_asset {
+                    WrapRequestStage::FeeCollected
+                } else {
+                    WrapRequestStage::FeePending
+                },
                 updated_at: now,
                 mint_nonce: None,
                 mint_submitted_at_time: 0,
                 mint_submit_status: MintSubmitStatus::NotSubmitted,
+                refund_amount_e8s: None,
             },
         })?;
         state.wrap_requests.insert(draft.request_id, req.clone());
@@ -1889,6 +3317,8 @@ fn dispatch_unwrap_request(
         .map_err(|err| api_invalid_argument(&err, &err))?;
     validate_non_anonymous_principal(&args.recipient, "arg.recipient_anonymous")
         .map_err(|err| api_invalid_argument(&err, &err))?;
+    let recipient_subaccount = parse_recipient_subaccount(args.recipient_subaccount.as_deref())
+        .map_err(|err| api_invalid_argument(&err, &err))?;
     let amount = nat_to_fixed_be::<32>(&args.amount_e8s).ok_or_else(|| {
         api_invalid_argument("arg.amount_out_of_range", "arg.amount_out_of_range")
     })?;
@@ -1900,6 +3330,7 @@ fn dispatch_unwrap_request(
         args.asset_id.as_slice().to_vec(),
         amount,
         args.recipient.as_slice().to_vec(),
+        recipient_subaccount,
     )
     .map_err(|err| api_rejected(&err, &err))?;
     Ok(DispatchUnwrapRequestOk {
@@ -1920,6 +3351,8 @@ async fn dispatch_native_withdrawal_request(
         .ok_or_else(|| api_invalid_argument("arg.request_id_invalid", "arg.request_id_invalid"))?;
     validate_non_anonymous_principal(&args.recipient, "arg.recipient_anonymous")
         .map_err(|err| api_invalid_argument(&err, &err))?;
+    let recipient_subaccount = parse_recipient_subaccount(args.recipient_subaccount.as_deref())
+        .map_err(|err| api_invalid_argument(&err, &err))?;
     let amount_e8s = nat_to_u128(&args.amount_e8s).ok_or_else(|| {
         api_invalid_argument("arg.amount_out_of_range", "arg.amount_out_of_range")
     })?;
@@ -1937,6 +3370,7 @@ async fn dispatch_native_withdrawal_request(
         NATIVE_WITHDRAW_ASSET_MARKER.to_vec(),
         amount,
         args.recipient.as_slice().to_vec(),
+        recipient_subaccount,
     )
     .map_err(|err| api_rejected(&err, &err))?;
     Ok(DispatchUnwrapRequestOk {
@@ -1944,17 +3378,33 @@ async fn dispatch_native_withdrawal_request(
     })
 }
 
+/// ICRC-1 の subaccount は 32byte 固定。全 0 は既定口座と同じなので None に寄せ、冪等比較の揺れを無くす。
+fn parse_recipient_subaccount(subaccount: Option<&[u8]>) -> Result<Option<[u8; 32]>, String> {
+    let Some(bytes) = subaccount else {
+        return Ok(None);
+    };
+    let subaccount: [u8; 32] = bytes
+        .try_into()
+        .map_err(|_| "arg.recipient_subaccount_invalid".to_string())?;
+    Ok(subaccount
+        .iter()
+        .any(|&byte| byte != 0)
+        .then_some(subaccount))
+}
+
 fn insert_unwrap_dispatch_request(
     request_id: TxId,
     asset_id: Vec<u8>,
     amount: [u8; 32],
     recipient: Vec<u8>,
+    recipient_subaccount: Option<[u8; 32]>,
 ) -> Result<(), String> {
     let out = with_state_mut(|state| {
         if let Some(existing) = state.unwrap_requests.get(&request_id) {
             if existing.asset_id != asset_id
                 || existing.amount != amount
                 || existing.recipient != recipient
+                || existing.recipient_subaccount != recipient_subaccount
             {
                 return Err("request.idempotency_mismatch".to_string());
             }
@@ -1966,11 +3416,13 @@ fn insert_unwrap_dispatch_request(
                 asset_id,
                 amount,
                 recipient,
+                recipient_subaccount,
                 status: UnwrapRequestStatus::Queued,
                 ledger_tx_id: None,
                 error_code: None,
                 updated_at: current_time_nanos(),
                 transfer_created_at_time: 0,
+                kind: UnwrapAssetKind::Fungible,
             },
         );
         let mut meta = *state.unwrap_dispatch_meta.get();
@@ -2028,16 +3480,18 @@ async fn attempt_icrc2_transfer_from(
 
 async fn attempt_icrc1_transfer(
     ledger: Principal,
+    from_subaccount: Option<[u8; 32]>,
     recipient: Principal,
+    recipient_subaccount: Option<[u8; 32]>,
     amount: Nat,
     memo: Vec<u8>,
     created_at_time: u64,
 ) -> Result<Vec<u8>, String> {
     let arg = Icrc1TransferArg {
-        from_subaccount: None,
+        from_subaccount: from_subaccount.map(|subaccount| subaccount.to_vec()),
         to: Icrc1Account {
             owner: recipient,
-            subaccount: None,
+            subaccount: recipient_subaccount.map(|subaccount| subaccount.to_vec()),
         },
         amount,
         fee: None,
@@ -2059,10 +3513,117 @@ async fn attempt_icrc1_transfer(
             )),
             Err(err) => Err(format!("ledger.decode_failed:{err}")),
         },
+        Err(err) if is_uncertain_call_error(&err) => Err(format!("ledger.call_uncertain:{err}")),
+        Err(err) => Err(format!("ledger.call_failed:{err}")),
+    }
+}
+
+/// 利用者が ICRC-37 で承認した token を canister の既定口座へ引き取る。
+async fn attempt_icrc37_transfer_from(
+    caller: Principal,
+    collection: Principal,
+    token_id: Nat,
+    memo: Vec<u8>,
+    created_at_time: u64,
+) -> Result<Vec<u8>, String> {
+    let arg = Icrc37TransferFromArg {
+        spender_subaccount: None,
+        from: Icrc1Account {
+            owner: caller,
+            subaccount: None,
+        },
+        to: Icrc1Account {
+            owner: ic_cdk::api::canister_self(),
+            subaccount: None,
+        },
+        token_id,
+        memo: Some(memo),
+        created_at_time: Some(created_at_time),
+    };
+    let call_result = ic_cdk::call::Call::unbounded_wait(collection, "icrc37_transfer_from")
+        .with_arg(vec![arg])
+        .await;
+    match call_result {
+        Ok(resp) => match resp.candid_tuple::<(Vec<Option<Result<Nat, Icrc7TransferError>>>,)>() {
+            Ok((results,)) => icrc7_single_transfer_result(results, "ledger.transfer_from_failed"),
+            Err(err) => Err(format!("ledger.decode_failed:{err}")),
+        },
+        Err(err) => Err(format!("ledger.call_failed:{err}")),
+    }
+}
+
+async fn attempt_icrc7_transfer(
+    collection: Principal,
+    recipient: Principal,
+    recipient_subaccount: Option<[u8; 32]>,
+    token_id: Nat,
+    memo: Vec<u8>,
+    created_at_time: u64,
+) -> Result<Vec<u8>, String> {
+    let arg = Icrc7TransferArg {
+        from_subaccount: None,
+        to: Icrc1Account {
+            owner: recipient,
+            subaccount: recipient_subaccount.map(|subaccount| subaccount.to_vec()),
+        },
+        token_id,
+        memo: Some(memo),
+        created_at_time: Some(created_at_time),
+    };
+    let call_result = ic_cdk::call::Call::unbounded_wait(collection, "icrc7_transfer")
+        .with_arg(vec![arg])
+        .await;
+    match call_result {
+        Ok(resp) => match resp.candid_tuple::<(Vec<Option<Result<Nat, Icrc7TransferError>>>,)>() {
+            Ok((results,)) => icrc7_single_transfer_result(results, "ledger.transfer_failed"),
+            Err(err) => Err(format!("ledger.decode_failed:{err}")),
+        },
+        Err(err) if is_uncertain_call_error(&err) => Err(format!("ledger.call_uncertain:{err}")),
         Err(err) => Err(format!("ledger.call_failed:{err}")),
     }
 }
 
+/// 1 件だけ送った batch の結果を取り出す。null は collection が処理しなかったことを表す。
+fn icrc7_single_transfer_result(
+    results: Vec<Option<Result<Nat, Icrc7TransferError>>>,
+    failure_prefix: &str,
+) -> Result<Vec<u8>, String> {
+    match results.into_iter().next() {
+        Some(Some(Ok(tx_id))) => Ok(nat_to_be_bytes(&tx_id)),
+        Some(Some(Err(Icrc7TransferError::Duplicate { duplicate_of }))) => {
+            Ok(nat_to_be_bytes(&duplicate_of))
+        }
+        Some(Some(Err(err))) => Err(format!(
+            "{failure_prefix}:{}",
+            icrc7_transfer_error_to_code(&err)
+        )),
+        Some(None) | None => Err(format!("{failure_prefix}:not_processed")),
+    }
+}
+
+fn icrc7_transfer_error_to_code(error: &Icrc7TransferError) -> String {
+    match error {
+        Icrc7TransferError::NonExistingTokenId => "non_existing_token_id".to_string(),
+        Icrc7TransferError::InvalidRecipient => "invalid_recipient".to_string(),
+        Icrc7TransferError::Unauthorized => "unauthorized".to_string(),
+        Icrc7TransferError::TooOld => "too_old".to_string(),
+        Icrc7TransferError::CreatedInFuture { ledger_time } => {
+            format!("created_in_future:{ledger_time}")
+        }
+        Icrc7TransferError::Duplicate { duplicate_of } => {
+            format!("duplicate:{}", duplicate_of.0)
+        }
+        Icrc7TransferError::GenericError {
+            error_code,
+            message,
+        } => format!("generic_error:{}:{message}", error_code.0),
+        Icrc7TransferError::GenericBatchError {
+            error_code,
+            message,
+        } => format!("generic_batch_error:{}:{message}", error_code.0),
+    }
+}
+
 async fn fetch_icrc1_fee(ledger: Principal) -> Result<u128, String> {
     let call_result = ic_cdk::call::Call::unbounded_wait(ledger, "icrc1_fee").await;
     match call_result {
@@ -2104,6 +3665,41 @@ fn fetch_wrapped_token_address(
     Ok(Some(address))
 }
 
+fn wrapped_token_address(asset_id: &[u8], factory: &[u8]) -> Result<Option<Vec<u8>>, String> {
+    let wrap_evm = hash::derive_evm_address_from_principal(ic_cdk::api::canister_self().as_slice())
+        .map_err(|_| "wrap.evm_address_derivation_failed".to_string())?;
+    fetch_wrapped_token_address(asset_id, &wrap_evm, factory).map_err(api_error_code)
+}
+
+fn wrapped_token_deployed(asset_id: &[u8], factory: &[u8]) -> Result<bool, String> {
+    Ok(wrapped_token_address(asset_id, factory)?.is_some())
+}
+
+/// factory は deploy 時の decimals と一致しない mint を拒むので、deploy 済みの token 自身から読む。
+#[cfg_attr(not(target_arch = "wasm32"), allow(dead_code))]
+fn fetch_erc20_decimals(token: &[u8]) -> Result<u8, String> {
+    let result = rpc_eth_call_object(RpcCallObjectView {
+        to: Some(token.to_vec()),
+        from: None,
+        gas: Some(500_000),
+        gas_price: None,
+        nonce: None,
+        max_fee_per_gas: None,
+        max_priority_fee_per_gas: None,
+        chain_id: None,
+        tx_type: None,
+        access_list: None,
+        value: Some(zero_eth_value_word()),
+        data: Some(selector(b"decimals()").to_vec()),
+    })
+    .map_err(|err| api_error_code(rpc_error_to_api_error(err)))?;
+    let word = decode_u256_be(result.return_data.as_slice()).map_err(api_error_code)?;
+    if word[..31].iter().any(|byte| *byte != 0) {
+        return Err("wrap.token_decimals_invalid".to_string());
+    }
+    Ok(word[31])
+}
+
 fn fetch_erc20_balance(token: &[u8], owner: &[u8]) -> Result<Nat, ApiError> {
     let result = rpc_eth_call_object(RpcCallObjectView {
         to: Some(token.to_vec()),
@@ -2146,13 +3742,22 @@ fn fetch_erc20_allowance(token: &[u8], owner: &[u8], spender: &[u8]) -> Result<N
     )?)))
 }
 
-#[cfg_attr(not(target_arch = "wasm32"), allow(dead_code))]
-async fn fetch_asset_decimals(asset_id: &[u8]) -> Result<u8, String> {
+/// icrc1_metadata から wrapped token に写す値。
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+struct WrappedTokenMetadata {
+    decimals: u8,
+    name: String,
+    symbol: String,
+    logo: String,
+    ledger_fee: u128,
+}
+
+async fn fetch_wrapped_token_metadata(asset_id: &[u8]) -> Result<WrappedTokenMetadata, String> {
     let ledger = principal_from_stored_bytes(asset_id)?;
     let call_result = ic_cdk::call::Call::unbounded_wait(ledger, "icrc1_metadata").await;
     match call_result {
         Ok(resp) => match resp.candid_tuple::<(Vec<(String, Icrc1MetadataValue)>,)>() {
-            Ok((metadata,)) => decode_asset_decimals(metadata.as_slice()),
+            Ok((metadata,)) => decode_wrapped_token_metadata(metadata.as_slice()),
             Err(err) => Err(format!("wrap.asset_metadata_failed:decode_failed:{err}")),
         },
         Err(err) => Err(format!("wrap.asset_metadata_failed:call_failed:{err}")),
@@ -2175,6 +3780,48 @@ fn decode_asset_decimals(metadata: &[(String, Icrc1MetadataValue)]) -> Result<u8
     Err("wrap.asset_metadata_failed:decimals_missing".to_string())
 }
 
+/// decimals 以外は無くてもよい。空の文字列は token 側で現在値を残す扱いになる。
+/// 名前と記号は上限で切り、上限を越える logo は mint の gas を膨らませないよう送らない。
+fn decode_wrapped_token_metadata(
+    metadata: &[(String, Icrc1MetadataValue)],
+) -> Result<WrappedTokenMetadata, String> {
+    let mut out = WrappedTokenMetadata {
+        decimals: decode_asset_decimals(metadata)?,
+        ..WrappedTokenMetadata::default()
+    };
+    for (key, value) in metadata {
+        match (key.as_str(), value) {
+            ("icrc1:name", Icrc1MetadataValue::Text(name)) => {
+                out.name = clamp_utf8_bytes(name, WRAPPED_TOKEN_NAME_MAX_BYTES);
+            }
+            ("icrc1:symbol", Icrc1MetadataValue::Text(symbol)) => {
+                out.symbol = clamp_utf8_bytes(symbol, WRAPPED_TOKEN_SYMBOL_MAX_BYTES);
+            }
+            ("icrc1:logo", Icrc1MetadataValue::Text(logo))
+                if logo.len() <= WRAPPED_TOKEN_LOGO_MAX_BYTES =>
+            {
+                out.logo = logo.clone();
+            }
+            ("icrc1:fee", Icrc1MetadataValue::Nat(fee)) => {
+                out.ledger_fee =
+                    nat_to_u128(fee).ok_or_else(|| "wrap.asset_fee_invalid".to_string())?;
+            }
+            _ => {}
+        }
+    }
+    Ok(out)
+}
+
+/// 初回 mint の deploy では logo を書かない。最大 2KB の storage 書き込みを wrap した利用者の gas に
+/// 載せないためで、logo は `sync_wrapped_token_metadata` が canister 負担で後から入れる。
+#[cfg_attr(not(target_arch = "wasm32"), allow(dead_code))]
+fn deploy_token_metadata(metadata: WrappedTokenMetadata) -> WrappedTokenMetadata {
+    WrappedTokenMetadata {
+        logo: String::new(),
+        ..metadata
+    }
+}
+
 fn map_fee_collection_error(err: &str) -> String {
     format!("fee.{err}")
 }
@@ -2246,6 +3893,42 @@ fn get_allowed_assets() -> Result<Vec<Principal>, String> {
     })
 }
 
+#[ic_cdk::query]
+fn get_allowed_nft_collections() -> Result<Vec<Principal>, String> {
+    with_state(|state| {
+        let mut out = Vec::new();
+        for entry in state.wrap_allowed_nft_collections.iter() {
+            out.push(principal_from_stored_bytes(entry.key())?);
+        }
+        Ok(out)
+    })
+}
+
+#[ic_cdk::query]
+fn get_asset_limits() -> Result<Vec<AssetLimitsView>, String> {
+    let now = current_time_nanos();
+    with_state(|state| {
+        let mut out = Vec::new();
+        for entry in state.asset_limits.iter() {
+            let asset_id = principal_from_stored_bytes(entry.key())?;
+            out.push(asset_limits_view(asset_id, entry.value(), now));
+        }
+        Ok(out)
+    })
+}
+
+#[ic_cdk::query]
+fn get_asset_fee_schedules() -> Result<Vec<AssetFeeScheduleView>, String> {
+    with_state(|state| {
+        let mut out = Vec::new();
+        for entry in state.asset_fee_schedules.iter() {
+            let asset_id = principal_from_stored_bytes(entry.key())?;
+            out.push(asset_fee_schedule_view(asset_id, entry.value())?);
+        }
+        Ok(out)
+    })
+}
+
 #[ic_cdk::query]
 fn get_query_precompile_allowlist() -> Vec<PrecompileAllowedView> {
     with_state(|state| {
@@ -2291,6 +3974,8 @@ fn get_request(request_id: Vec<u8>) -> Option<RequestOverview> {
             return Some(RequestOverview {
                 kind: if req.gas_limit == 0 {
                     RequestKind::NativeDeposit
+                } else if req.kind == WrapAssetKind::Nft {
+                    RequestKind::NftWrap
                 } else {
                     RequestKind::Wrap
                 },
@@ -2314,6 +3999,7 @@ fn get_request(request_id: Vec<u8>) -> Option<RequestOverview> {
                 dispatch_error: None,
                 charged_fee_e8s: result.charged_fee_e8s.map(Nat::from),
                 charged_gas_price_wei: result.charged_gas_price_wei.map(Nat::from),
+                deadline_at: (req.deadline_at != 0).then_some(req.deadline_at),
             });
         }
         state.unwrap_requests.get(&request_id).map(|req| {
@@ -2321,6 +4007,8 @@ fn get_request(request_id: Vec<u8>) -> Option<RequestOverview> {
             RequestOverview {
                 kind: if is_native_withdraw_dispatch_request(&req) {
                     RequestKind::NativeWithdrawal
+                } else if req.kind == UnwrapAssetKind::Nft {
+                    RequestKind::NftUnwrap
                 } else {
                     RequestKind::Unwrap
                 },
@@ -2332,7 +4020,7 @@ fn get_request(request_id: Vec<u8>) -> Option<RequestOverview> {
                 pull_ledger_tx_id: None,
                 mint_tx_id: None,
                 withdraw_ledger_tx_id: None,
-                recoverable: req.status == UnwrapRequestStatus::DispatchFailed,
+                recoverable: unwrap_request_retryable(&req, current_time_nanos()),
                 withdrawn: false,
                 withdraw_in_progress: req.status == UnwrapRequestStatus::Dispatching,
                 withdraw_error: req.error_code.as_deref().map(request_error_view),
@@ -2341,6 +4029,7 @@ fn get_request(request_id: Vec<u8>) -> Option<RequestOverview> {
                 dispatch_error: req.error_code.clone(),
                 charged_fee_e8s: None,
                 charged_gas_price_wei: None,
+                deadline_at: None,
             }
         })
     })
@@ -2479,19 +4168,62 @@ async fn recover_failed_wrap(args: RecoverFailedWrapArgs) -> Result<RequestOverv
     });
     let req = req_result.map_err(|err| api_rejected(&err, &err))?;
 
-    let caller =
-        principal_from_stored_bytes(&req.caller).map_err(|err| api_internal(&err, &err))?;
+    let (refund_owner, refund_subaccount) =
+        wrap_refund_account(&req).map_err(|err| api_internal(&err, &err))?;
     let asset =
         principal_from_stored_bytes(&req.asset_id).map_err(|err| api_internal(&err, &err))?;
-    let transfer = attempt_icrc1_transfer(
-        asset,
-        caller,
-        Nat(BigUint::from_bytes_be(&req.amount)),
-        request_memo(request_id, TransferMemoKind::Withdraw),
-        req.withdraw_created_at_time,
-    )
-    .await;
+    let transfer =
+        attempt_wrap_refund_transfer(request_id, &req, asset, refund_owner, refund_subaccount)
+            .await;
+
+    settle_wrap_refund(request_id, transfer);
+    get_request(request_id.0.to_vec())
+        .ok_or_else(|| api_internal("request.not_found", "request.not_found"))
+}
 
+/// 期限切れ返金で量が決まっていれば同じ転送を送り、ledger の重複排除に任せる。
+/// NFT は引き取った token をそのまま返す。
+async fn attempt_wrap_refund_transfer(
+    request_id: TxId,
+    req: &evm_db::chain_data::WrapStoredRequest,
+    asset: Principal,
+    refund_owner: Principal,
+    refund_subaccount: Option<[u8; 32]>,
+) -> Result<Vec<u8>, String> {
+    let memo = request_memo(request_id, TransferMemoKind::Withdraw);
+    match req.kind {
+        WrapAssetKind::Fungible => {
+            let amount = req
+                .result
+                .refund_amount_e8s
+                .map(Nat::from)
+                .unwrap_or_else(|| Nat(BigUint::from_bytes_be(&req.amount)));
+            attempt_icrc1_transfer(
+                asset,
+                None,
+                refund_owner,
+                refund_subaccount,
+                amount,
+                memo,
+                req.withdraw_created_at_time,
+            )
+            .await
+        }
+        WrapAssetKind::Nft => {
+            attempt_icrc7_transfer(
+                asset,
+                refund_owner,
+                refund_subaccount,
+                Nat(BigUint::from_bytes_be(&req.amount)),
+                memo,
+                req.withdraw_created_at_time,
+            )
+            .await
+        }
+    }
+}
+
+fn settle_wrap_refund(request_id: TxId, transfer: Result<Vec<u8>, String>) {
     with_state_mut(|state| {
         let Some(mut req) = state.wrap_requests.get(&request_id) else {
             return;
@@ -2519,12 +4251,188 @@ async fn recover_failed_wrap(args: RecoverFailedWrapArgs) -> Result<RequestOverv
                 req.result.withdraw_error_code = Some(clamp_error_code(err));
             }
         }
-        if let Ok(req) = sanitize_wrap_request(req) {
+        if let Ok(req) = sanitize_wrap_request(req) {
+            state.wrap_requests.insert(request_id, req);
+        }
+    });
+}
+
+fn wrap_deadline_at(now: u64, deadline_secs: u64) -> u64 {
+    now.saturating_add(deadline_secs.saturating_mul(1_000_000_000))
+}
+
+/// 期限を過ぎ、mint が後から着地し得ない wrap だけを返金対象にする。
+fn wrap_expiry_refund_allowed(req: &evm_db::chain_data::WrapStoredRequest, now: u64) -> bool {
+    if req.result.withdraw_error_code.as_deref() == Some(WRAP_REFUND_BELOW_FEE_CODE) {
+        return false;
+    }
+    let mint_tx_dropped = req
+        .result
+        .mint_tx_id
+        .clone()
+        .and_then(tx_id_from_bytes)
+        .and_then(|tx_id| chain::get_tx_loc(&tx_id))
+        .is_some_and(|loc| loc.kind == TxLocKind::Dropped);
+    let cannot_land = verified_core::wrap_request::wrap_mint_cannot_land_raw(
+        wrap_request_status_code(req.result.status),
+        wrap_request_stage_code(req.result.stage),
+        u64::from(req.result.mint_failed_recoverable),
+        u64::from(req.result.mint_tx_id.is_some()),
+        u64::from(req.result.mint_submit_status != MintSubmitStatus::NotSubmitted),
+        u64::from(mint_tx_dropped),
+    );
+    verified_core::wrap_request::wrap_expiry_refund_allowed_raw(
+        now,
+        req.deadline_at,
+        u64::from(req.gas_limit != 0),
+        u64::from(req.result.pull_ledger_tx_id.is_some()),
+        u64::from(req.result.withdraw_in_progress),
+        u64::from(req.result.withdrawn),
+        u64::from(req.result.withdraw_ledger_tx_id.is_some()),
+        u64::from(cannot_land),
+    )
+}
+
+fn wrap_request_status_code(status: StoredRequestStatus) -> u64 {
+    match status {
+        StoredRequestStatus::Queued => verified_core::wrap_request::WRAP_REQUEST_STATUS_QUEUED,
+        StoredRequestStatus::Running => verified_core::wrap_request::WRAP_REQUEST_STATUS_RUNNING,
+        StoredRequestStatus::Succeeded => {
+            verified_core::wrap_request::WRAP_REQUEST_STATUS_SUCCEEDED
+        }
+        StoredRequestStatus::Failed => verified_core::wrap_request::WRAP_REQUEST_STATUS_FAILED,
+    }
+}
+
+fn wrap_request_stage_code(stage: WrapRequestStage) -> u64 {
+    use verified_core::wrap_request as stage_code;
+    match stage {
+        WrapRequestStage::FeePending => stage_code::WRAP_STAGE_FEE_PENDING,
+        WrapRequestStage::FeeCollected => stage_code::WRAP_STAGE_FEE_COLLECTED,
+        WrapRequestStage::PullPending => stage_code::WRAP_STAGE_PULL_PENDING,
+        WrapRequestStage::Pulled => stage_code::WRAP_STAGE_PULLED,
+        WrapRequestStage::MintSubmitting => stage_code::WRAP_STAGE_MINT_SUBMITTING,
+        WrapRequestStage::MintSubmitted => stage_code::WRAP_STAGE_MINT_SUBMITTED,
+        WrapRequestStage::Succeeded => stage_code::WRAP_STAGE_SUCCEEDED,
+        WrapRequestStage::Failed => stage_code::WRAP_STAGE_FAILED,
+        WrapRequestStage::Refunding => stage_code::WRAP_STAGE_REFUNDING,
+        WrapRequestStage::Refunded => stage_code::WRAP_STAGE_REFUNDED,
+    }
+}
+
+fn expired_wrap_refund_candidates(now: u64) -> Vec<TxId> {
+    with_state(|state| {
+        state
+            .wrap_requests
+            .iter()
+            .filter(|entry| wrap_expiry_refund_allowed(&entry.value(), now))
+            .map(|entry| *entry.key())
+            .take(MAX_WRAP_EXPIRY_REFUNDS_PER_TICK)
+            .collect()
+    })
+}
+
+/// 返金量を固定して Refunding に入れる。状態が変わっていれば None を返して何もしない。
+/// 以後 worker と mint receipt の確定はこの request に触れない。NFT は token を返すだけなので量を持たない。
+fn begin_wrap_expiry_refund(
+    request_id: TxId,
+    ledger_fee_e8s: u128,
+    now: u64,
+) -> Result<Option<evm_db::chain_data::WrapStoredRequest>, String> {
+    with_state_mut(|state| {
+        let Some(mut req) = state.wrap_requests.get(&request_id) else {
+            return Ok(None);
+        };
+        if !wrap_expiry_refund_allowed(&req, now) {
+            return Ok(None);
+        }
+        if req.kind == WrapAssetKind::Nft {
+            return Ok(Some(mark_wrap_expiry_refunding(
+                state, request_id, req, now,
+            )?));
+        }
+        let pulled = nat_to_u128(&Nat(BigUint::from_bytes_be(&req.amount)))
+            .ok_or_else(|| "arg.amount_out_of_range".to_string())?;
+        let refund_amount = req.result.refund_amount_e8s.or_else(|| {
+            verified_core::wrap_request::wrap_expiry_refund_amount_raw(
+                u64::from(req.fee_in_asset),
+                pulled,
+                req.result.charged_fee_e8s.unwrap_or(0),
+                ledger_fee_e8s,
+            )
+        });
+        let Some(refund_amount) = refund_amount else {
+            req.result.withdraw_error_code = Some(WRAP_REFUND_BELOW_FEE_CODE.to_string());
+            req.result.updated_at = now;
+            let req = sanitize_wrap_request(req)?;
             state.wrap_requests.insert(request_id, req);
+            return Ok(None);
+        };
+        req.result.refund_amount_e8s = Some(refund_amount);
+        Ok(Some(mark_wrap_expiry_refunding(
+            state, request_id, req, now,
+        )?))
+    })
+}
+
+fn mark_wrap_expiry_refunding(
+    state: &mut StableState,
+    request_id: TxId,
+    mut req: evm_db::chain_data::WrapStoredRequest,
+    now: u64,
+) -> Result<evm_db::chain_data::WrapStoredRequest, String> {
+    if req.withdraw_created_at_time == 0 {
+        req.withdraw_created_at_time = now;
+    }
+    req.result.status = StoredRequestStatus::Failed;
+    req.result.error_code = Some("wrap.expired".to_string());
+    req.result.mint_failed_recoverable = true;
+    req.result.withdraw_in_progress = true;
+    req.result.stage = WrapRequestStage::Refunding;
+    req.result.updated_at = now;
+    let req = sanitize_wrap_request(req)?;
+    state.wrap_requests.insert(request_id, req.clone());
+    Ok(req)
+}
+
+async fn refund_expired_wrap(request_id: TxId) -> Result<(), String> {
+    let Some(req) = with_state(|state| state.wrap_requests.get(&request_id)) else {
+        return Ok(());
+    };
+    let asset = principal_from_stored_bytes(&req.asset_id)?;
+    let ledger_fee_e8s = match (req.kind, req.result.refund_amount_e8s) {
+        (WrapAssetKind::Nft, _) | (WrapAssetKind::Fungible, Some(_)) => 0,
+        (WrapAssetKind::Fungible, None) => fetch_icrc1_fee(asset).await?,
+    };
+    let Some(req) = begin_wrap_expiry_refund(request_id, ledger_fee_e8s, current_time_nanos())?
+    else {
+        return Ok(());
+    };
+    let (refund_owner, refund_subaccount) = wrap_refund_account(&req)?;
+    let transfer =
+        attempt_wrap_refund_transfer(request_id, &req, asset, refund_owner, refund_subaccount)
+            .await;
+    settle_wrap_refund(request_id, transfer);
+    Ok(())
+}
+
+async fn run_wrap_expiry_check() {
+    settle_submitted_wrap_mint_receipts(current_time_nanos());
+    for request_id in expired_wrap_refund_candidates(current_time_nanos()) {
+        if let Err(err) = refund_expired_wrap(request_id).await {
+            error!(error = ?err, "wrap expiry refund failed");
         }
-    });
-    get_request(request_id.0.to_vec())
-        .ok_or_else(|| api_internal("request.not_found", "request.not_found"))
+    }
+}
+
+fn schedule_wrap_expiry_check() {
+    ic_cdk_timers::set_timer(
+        std::time::Duration::from_secs(WRAP_EXPIRY_CHECK_INTERVAL_SECS),
+        async move {
+            run_wrap_expiry_check().await;
+            schedule_wrap_expiry_check();
+        },
+    );
 }
 
 fn retry_unwrap_dispatch(request_id: Vec<u8>) -> Result<RequestOverview, ApiError> {
@@ -2534,9 +4442,18 @@ fn retry_unwrap_dispatch(request_id: Vec<u8>) -> Result<RequestOverview, ApiErro
         let Some(mut req) = state.unwrap_requests.get(&request_id) else {
             return Err("request.not_found".to_string());
         };
-        if req.status != UnwrapRequestStatus::DispatchFailed {
+        if !unwrap_request_retryable(&req, current_time_nanos()) {
+            if req.status == UnwrapRequestStatus::DispatchUncertain {
+                return Err("request.reconcile_required".to_string());
+            }
             return Err("request.retry_invalid_state".to_string());
         }
+        if let Some(mut limits) = state.asset_limits.get(&req.asset_id) {
+            limits.advance_window(bridge_limit_hour(current_time_nanos()));
+            if let Some(code) = unwrap_dispatch_limit_error(&limits, &req) {
+                return Err(code.to_string());
+            }
+        }
         req.status = UnwrapRequestStatus::Queued;
         req.error_code = None;
         req.updated_at = current_time_nanos();
@@ -2554,6 +4471,159 @@ fn retry_unwrap_dispatch(request_id: Vec<u8>) -> Result<RequestOverview, ApiErro
         .ok_or_else(|| api_internal("request.not_found", "request.not_found"))
 }
 
+/// 結果の分からない dispatch は、ledger が同じ memo を重複排除する間だけ再送を許す。
+fn unwrap_request_retryable(req: &UnwrapDispatchRequest, now: u64) -> bool {
+    match req.status {
+        UnwrapRequestStatus::DispatchFailed => true,
+        UnwrapRequestStatus::DispatchUncertain => {
+            verified_core::unwrap_dispatch::unwrap_uncertain_retry_safe_raw(
+                now,
+                req.transfer_created_at_time,
+                ledger_reconcile::LEDGER_TX_WINDOW_NANOS,
+            )
+        }
+        UnwrapRequestStatus::Queued
+        | UnwrapRequestStatus::Dispatching
+        | UnwrapRequestStatus::Dispatched => false,
+    }
+}
+
+// ledger の ICRC-3 block を遡り、結果の分からない unwrap dispatch が送金済みかを確定させる。
+// NFT は ICRC-7 の所有者照会で確かめる。
+#[ic_cdk::update]
+async fn reconcile_unwrap_request(
+    args: ReconcileUnwrapRequestArgs,
+) -> Result<ReconcileUnwrapRequestOk, String> {
+    if let Some(reason) = reject_anonymous_update() {
+        return Err(reason);
+    }
+    require_control_plane_write()?;
+    let request_id =
+        tx_id_from_bytes(args.request_id).ok_or_else(|| "arg.request_id_invalid".to_string())?;
+    if args.max_blocks == 0 {
+        return Err("arg.max_blocks_zero".to_string());
+    }
+    let max_blocks = u64::from(args.max_blocks.min(MAX_RECONCILE_SCAN_BLOCKS));
+    let req = with_state(|state| state.unwrap_requests.get(&request_id))
+        .ok_or_else(|| "request.not_found".to_string())?;
+    if req.status != UnwrapRequestStatus::DispatchUncertain || req.transfer_created_at_time == 0 {
+        return Err("reconcile.invalid_state".to_string());
+    }
+    let ledger = unwrap_dispatch_ledger(&req)?;
+    let recipient = principal_from_stored_bytes(&req.recipient)?;
+    let verdict = if req.kind == UnwrapAssetKind::Nft {
+        // ICRC-3 の走査は ICRC-1 の transfer block だけを読むので、NFT は今の所有者を見る。
+        let owner =
+            ledger_reconcile::fetch_nft_owner(ledger, Nat(BigUint::from_bytes_be(&req.amount)))
+                .await?;
+        ledger_reconcile::nft_owner_verdict(
+            owner.as_ref(),
+            ic_cdk::api::canister_self(),
+            recipient,
+            req.recipient_subaccount,
+        )?
+    } else {
+        scan_unwrap_transfer(
+            request_id,
+            &req,
+            ledger,
+            recipient,
+            args.scan_before,
+            max_blocks,
+        )
+        .await?
+    };
+    apply_unwrap_reconcile_verdict(request_id, req.transfer_created_at_time, verdict)?;
+    let request =
+        get_request(request_id.0.to_vec()).ok_or_else(|| "request.not_found".to_string())?;
+    Ok(ReconcileUnwrapRequestOk {
+        verdict: unwrap_reconcile_verdict_to_view(verdict),
+        request,
+    })
+}
+
+async fn scan_unwrap_transfer(
+    request_id: TxId,
+    req: &UnwrapDispatchRequest,
+    ledger: Principal,
+    recipient: Principal,
+    scan_before: Option<u64>,
+    max_blocks: u64,
+) -> Result<ledger_reconcile::MemoScanVerdict, String> {
+    let expected = ledger_reconcile::ExpectedTransfer {
+        memo: request_memo(request_id, TransferMemoKind::Unwrap),
+        created_at_time: req.transfer_created_at_time,
+        from_owner: ic_cdk::api::canister_self().as_slice().to_vec(),
+        to_owner: recipient.as_slice().to_vec(),
+        to_subaccount: req.recipient_subaccount,
+    };
+    ledger_reconcile::scan_ledger_for_transfer(
+        |start, length| ledger_reconcile::fetch_ledger_blocks(ledger, start, length),
+        &expected,
+        scan_before,
+        max_blocks,
+    )
+    .await
+}
+
+/// 走査の await 中に retry などで request が動いていたら、古い走査の結論は当てはめない。
+fn apply_unwrap_reconcile_verdict(
+    request_id: TxId,
+    transfer_created_at_time: u64,
+    verdict: ledger_reconcile::MemoScanVerdict,
+) -> Result<(), String> {
+    with_state_mut(|state| {
+        let Some(mut req) = state.unwrap_requests.get(&request_id) else {
+            return Err("request.not_found".to_string());
+        };
+        if req.status != UnwrapRequestStatus::DispatchUncertain
+            || req.transfer_created_at_time != transfer_created_at_time
+        {
+            return Err("reconcile.state_changed".to_string());
+        }
+        match verdict {
+            ledger_reconcile::MemoScanVerdict::Found { block_index } => {
+                req.status = UnwrapRequestStatus::Dispatched;
+                req.ledger_tx_id = Some(nat_to_be_bytes(&Nat::from(block_index)));
+                req.error_code = None;
+            }
+            ledger_reconcile::MemoScanVerdict::OwnedByRecipient => {
+                req.status = UnwrapRequestStatus::Dispatched;
+                req.ledger_tx_id = None;
+                req.error_code = None;
+            }
+            ledger_reconcile::MemoScanVerdict::NotFound => {
+                // 送金が無いと確定したので、次の retry は新しい created_at_time で送ってよい。
+                req.status = UnwrapRequestStatus::DispatchFailed;
+                req.ledger_tx_id = None;
+                req.error_code = Some("reconcile.not_dispatched".to_string());
+                req.transfer_created_at_time = 0;
+            }
+            ledger_reconcile::MemoScanVerdict::Incomplete { .. } => return Ok(()),
+        }
+        req.updated_at = current_time_nanos();
+        state.unwrap_requests.insert(request_id, req);
+        Ok(())
+    })
+}
+
+fn unwrap_reconcile_verdict_to_view(
+    verdict: ledger_reconcile::MemoScanVerdict,
+) -> UnwrapReconcileVerdictView {
+    match verdict {
+        ledger_reconcile::MemoScanVerdict::Found { block_index } => {
+            UnwrapReconcileVerdictView::Dispatched { block_index }
+        }
+        ledger_reconcile::MemoScanVerdict::NotFound => UnwrapReconcileVerdictView::SafeToRetry,
+        ledger_reconcile::MemoScanVerdict::Incomplete { scan_before } => {
+            UnwrapReconcileVerdictView::Incomplete { scan_before }
+        }
+        ledger_reconcile::MemoScanVerdict::OwnedByRecipient => {
+            UnwrapReconcileVerdictView::OwnedByRecipient
+        }
+    }
+}
+
 fn request_error_view(code: &str) -> RequestErrorView {
     RequestErrorView {
         code: code.to_string(),
@@ -2582,6 +4652,7 @@ fn unwrap_request_stage_to_view(status: UnwrapRequestStatus) -> RequestStageView
         UnwrapRequestStatus::Dispatching => RequestStageView::Dispatching,
         UnwrapRequestStatus::Dispatched => RequestStageView::Dispatched,
         UnwrapRequestStatus::DispatchFailed => RequestStageView::DispatchFailed,
+        UnwrapRequestStatus::DispatchUncertain => RequestStageView::DispatchUncertain,
     }
 }
 
@@ -2602,6 +4673,10 @@ fn unwrap_request_status_to_request_status(
             RequestStatus::Failed,
             RequestDispatchStatusView::DispatchFailed,
         ),
+        UnwrapRequestStatus::DispatchUncertain => (
+            RequestStatus::Failed,
+            RequestDispatchStatusView::DispatchUncertain,
+        ),
     }
 }
 
@@ -2643,6 +4718,166 @@ fn set_allowed_assets(assets: Vec<Principal>) -> Result<(), String> {
     Ok(())
 }
 
+// 空にすると NFT の新規 wrap を止められる。処理中の request と unwrap には影響しない。
+#[ic_cdk::update]
+fn set_allowed_nft_collections(collections: Vec<Principal>) -> Result<(), String> {
+    if let Some(reason) = reject_anonymous_update() {
+        return Err(reason);
+    }
+    require_control_plane_write()?;
+    for collection in &collections {
+        validate_non_anonymous_principal(collection, "arg.allowed_collection_anonymous")?;
+    }
+    with_state_mut(|state| {
+        while let Some(entry) = state.wrap_allowed_nft_collections.range(..).next() {
+            let key = entry.key().clone();
+            state.wrap_allowed_nft_collections.remove(&key);
+        }
+        for collection in collections {
+            state
+                .wrap_allowed_nft_collections
+                .insert(collection.as_slice().to_vec(), 1);
+        }
+    });
+    Ok(())
+}
+
+// deploy 済みの wrapped token へ icrc1_metadata の名前・記号・logo・ledger fee を写し直す。
+// 返り値は factory 呼び出しの tx_id。反映は block 取り込み後になる。
+#[ic_cdk::update]
+async fn sync_wrapped_token_metadata(asset_id: Principal) -> Result<Vec<u8>, String> {
+    if let Some(reason) = reject_anonymous_update() {
+        return Err(reason);
+    }
+    require_control_plane_write()?;
+    validate_non_anonymous_principal(&asset_id, "arg.asset_id_anonymous")?;
+    let factory = expected_wrap_factory_address()?;
+    if !wrapped_token_deployed(asset_id.as_slice(), &factory)? {
+        return Err("asset.token_not_deployed".to_string());
+    }
+    let metadata = fetch_wrapped_token_metadata(asset_id.as_slice()).await?;
+    let data = encode_factory_set_asset_metadata_call_data(asset_id.as_slice(), &metadata)?;
+    let gas_limit = wrap_metadata_sync_gas_limit(&metadata);
+    validate_wrap_gas_limit(gas_limit)?;
+    submit_factory_system_tx(&factory, "wrap_metadata_sync", data, gas_limit)
+}
+
+fn wrap_metadata_sync_gas_limit(metadata: &WrappedTokenMetadata) -> u64 {
+    let words = [&metadata.name, &metadata.symbol, &metadata.logo]
+        .iter()
+        .map(|value| value.len().div_ceil(32) as u64 + 1)
+        .sum::<u64>();
+    WRAP_METADATA_SYNC_BASE_GAS
+        .saturating_add(WRAP_METADATA_SYNC_GAS_PER_WORD.saturating_mul(words))
+}
+
+// wrap request に紐づかない factory 呼び出し。gas は canister 負担で、mint と nonce を取り合ったときは
+// submit が失敗するので呼び直してもらう。
+fn submit_factory_system_tx(
+    factory: &[u8],
+    code: &'static str,
+    data: Vec<u8>,
+    gas_limit: u64,
+) -> Result<Vec<u8>, String> {
+    let policy = current_fee_policy()?;
+    let max_fee_per_gas =
+        wrap_charged_gas_price_wei(policy.gas_price_buffer_bps).map_err(api_error_code)?;
+    let suggested_priority_fee_wei =
+        ic_evm_rpc::rpc_eth_max_priority_fee_per_gas().map_err(|err| {
+            let code = err
+                .error_prefix
+                .unwrap_or_else(|| format!("rpc.error.{}", err.code));
+            format!("fee.priority_failed:{code}:{}", err.message)
+        })?;
+    let wrap_evm = hash::derive_evm_address_from_principal(ic_cdk::api::canister_self().as_slice())
+        .map_err(|_| "wrap.evm_address_derivation_failed".to_string())?;
+    let mut to = [0u8; 20];
+    to.copy_from_slice(factory);
+    let tx = evm_core::tx_decode::IcSyntheticTxInput {
+        to: Some(to),
+        value: [0u8; 32],
+        gas_limit,
+        max_fee_per_gas,
+        max_priority_fee_per_gas: suggested_priority_fee_wei.min(max_fee_per_gas),
+        nonce: chain::expected_nonce_for_sender_view(wrap_evm),
+        data,
+    };
+    submit_ic_tx_internal(ic_cdk::api::canister_self().as_slice().to_vec(), code, tx)
+        .map_err(|err| format!("evm_gateway.submit_failed:{}", submit_error_to_code(err)))
+}
+
+// asset ごとの停止と上限を置き換える。24 時間窓の使用量は引き継ぐ。
+#[ic_cdk::update]
+fn set_asset_limits(args: SetAssetLimitsArgs) -> Result<AssetLimitsView, String> {
+    if let Some(reason) = reject_anonymous_update() {
+        return Err(reason);
+    }
+    require_control_plane_write()?;
+    validate_non_anonymous_principal(&args.asset_id, "arg.asset_id_anonymous")?;
+    let parse_limit = |value: Option<Nat>| match value {
+        Some(value) => nat_to_u128(&value)
+            .map(Some)
+            .ok_or_else(|| "arg.limit_out_of_range".to_string()),
+        None => Ok(None),
+    };
+    let max_single_wrap = parse_limit(args.max_single_wrap)?;
+    let wrap_daily_cap = parse_limit(args.wrap_daily_cap)?;
+    let unwrap_daily_cap = parse_limit(args.unwrap_daily_cap)?;
+    let now = current_time_nanos();
+    let key = args.asset_id.as_slice().to_vec();
+    let limits = with_state_mut(|state| {
+        let mut limits = state.asset_limits.get(&key).unwrap_or_default();
+        limits.paused = args.paused;
+        limits.max_single_wrap = max_single_wrap;
+        limits.wrap_daily_cap = wrap_daily_cap;
+        limits.unwrap_daily_cap = unwrap_daily_cap;
+        limits.updated_at = now;
+        state.asset_limits.insert(key, limits);
+        limits
+    });
+    Ok(asset_limits_view(args.asset_id, limits, now))
+}
+
+// asset の fee schedule を置き換える。受付済みの request は受付時の fee のまま進む。
+#[ic_cdk::update]
+fn set_asset_fee_schedule(args: SetAssetFeeScheduleArgs) -> Result<(), String> {
+    if let Some(reason) = reject_anonymous_update() {
+        return Err(reason);
+    }
+    require_control_plane_write()?;
+    let tiers = validate_asset_fee_schedule(&args)?;
+    let stored = AssetFeeScheduleStored {
+        fee_ledger_canister: args
+            .fee_ledger_canister
+            .map(|ledger| ledger.as_slice().to_vec())
+            .unwrap_or_default(),
+        cycle_fee_e8s: args.cycle_fee_e8s,
+        gas_price_buffer_bps: args.gas_price_buffer_bps,
+        fee_in_asset: args.fee_in_asset,
+        tiers,
+    };
+    with_state_mut(|state| {
+        state
+            .asset_fee_schedules
+            .insert(args.asset_id.as_slice().to_vec(), stored);
+    });
+    Ok(())
+}
+
+#[ic_cdk::update]
+fn clear_asset_fee_schedule(asset_id: Principal) -> Result<(), String> {
+    if let Some(reason) = reject_anonymous_update() {
+        return Err(reason);
+    }
+    require_control_plane_write()?;
+    with_state_mut(|state| {
+        state
+            .asset_fee_schedules
+            .remove(&asset_id.as_slice().to_vec());
+    });
+    Ok(())
+}
+
 #[ic_cdk::update]
 fn add_query_precompile_allowed_method(args: PrecompileAllowArgs) -> Result<(), String> {
     if let Some(reason) = reject_anonymous_update() {
@@ -2701,7 +4936,10 @@ fn remove_update_precompile_allowed_method(args: PrecompileAllowArgs) -> Result<
 
 #[ic_cdk::post_upgrade]
 fn post_upgrade(args: Option<InitArgs>) {
-    upgrade::post_upgrade();
+    // 読めない checkpoint や新しすぎる stable layout では、状態に触れる前に upgrade ごと失敗させる。
+    let checkpoint = upgrade::post_upgrade(current_schema_version())
+        .unwrap_or_else(|err| ic_cdk::trap(format!("UpgradeRefused: {err}")))
+        .unwrap_or_default();
     init_stable_state();
     let _ = ensure_meta_initialized();
     init_tracing();
@@ -2725,16 +4963,21 @@ fn post_upgrade(args: Option<InitArgs>) {
             dropped_from_dispatch_queue, "post_upgrade quarantined decode-failed unwrap requests"
         );
     }
+    // certified data は upgrade で消えるため、stable の head から組み直して張り直す。
+    icrc3::refresh_certified_tip();
     observe_cycles();
     let data_plane_enabled = reject_write_reason().is_none();
     reset_mining_schedule_after_upgrade();
-    restore_unwrap_dispatch_after_upgrade(data_plane_enabled);
-    restore_icp_update_dispatch_after_upgrade(data_plane_enabled);
-    restore_wrap_worker_after_upgrade(data_plane_enabled);
+    let wakeups = restore_volatile_state_after_upgrade(checkpoint);
+    restore_unwrap_dispatch_after_upgrade(data_plane_enabled, wakeups.unwrap_dispatch);
+    restore_icp_update_dispatch_after_upgrade(data_plane_enabled, wakeups.icp_update_dispatch);
+    restore_wrap_worker_after_upgrade(data_plane_enabled, wakeups.wrap_worker);
     if data_plane_enabled {
         schedule_mining();
     }
     schedule_cycle_observer();
+    schedule_reserves_check();
+    schedule_wrap_expiry_check();
 }
 
 fn reset_mining_schedule_after_upgrade() {
@@ -2746,23 +4989,57 @@ fn reset_mining_schedule_after_upgrade() {
     });
 }
 
-fn restore_unwrap_dispatch_after_upgrade(schedule_workers: bool) {
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+struct WorkerWakeups {
+    unwrap_dispatch: bool,
+    icp_update_dispatch: bool,
+    wrap_worker: bool,
+}
+
+fn upgrade_checkpoint() -> UpgradeCheckpoint {
+    UpgradeCheckpoint {
+        schema_version: get_meta().schema_version,
+        caller_evm: chain::caller_evm_cache_entries(),
+        decode_suppress_until: chain::decode_suppress_entries(),
+        precompile_profile: evm_core::kasane_precompiles::precompile_profile_checkpoint(),
+        unwrap_dispatch_scheduled: UNWRAP_DISPATCH_SCHEDULED.load(Ordering::SeqCst),
+        icp_update_dispatch_scheduled: ICP_UPDATE_DISPATCH_SCHEDULED.load(Ordering::SeqCst),
+        wrap_worker_scheduled: WRAP_WORKER_SCHEDULED.load(Ordering::SeqCst),
+    }
+}
+
+// heap 側の cache と集計を戻す。timer 実体は戻せないため、予約済みだった worker は呼び出し側で起こし直す。
+fn restore_volatile_state_after_upgrade(checkpoint: UpgradeCheckpoint) -> WorkerWakeups {
+    chain::restore_caller_evm_cache(checkpoint.caller_evm);
+    chain::restore_decode_suppress(checkpoint.decode_suppress_until);
+    evm_core::kasane_precompiles::restore_precompile_profile(checkpoint.precompile_profile);
+    WorkerWakeups {
+        unwrap_dispatch: checkpoint.unwrap_dispatch_scheduled,
+        icp_update_dispatch: checkpoint.icp_update_dispatch_scheduled,
+        wrap_worker: checkpoint.wrap_worker_scheduled,
+    }
+}
+
+fn restore_unwrap_dispatch_after_upgrade(schedule_workers: bool, was_scheduled: bool) {
     // upgrade後は timer 実体が失われるため、永続化済みの unwrap queue を再接続する。
-    if recover_unwrap_dispatch_state_after_upgrade(current_time_nanos()) && schedule_workers {
+    let pending = recover_unwrap_dispatch_state_after_upgrade(current_time_nanos());
+    if (pending || was_scheduled) && schedule_workers {
         schedule_unwrap_dispatch();
     }
 }
 
-fn restore_icp_update_dispatch_after_upgrade(schedule_workers: bool) {
+fn restore_icp_update_dispatch_after_upgrade(schedule_workers: bool, was_scheduled: bool) {
     // update intentもpost-commit副作用なので、upgrade後は永続queueを再接続する。
-    if recover_icp_update_dispatch_state_after_upgrade(current_time_nanos()) && schedule_workers {
+    let pending = recover_icp_update_dispatch_state_after_upgrade(current_time_nanos());
+    if (pending || was_scheduled) && schedule_workers {
         schedule_icp_update_dispatch();
     }
 }
 
-fn restore_wrap_worker_after_upgrade(schedule_workers: bool) {
+fn restore_wrap_worker_after_upgrade(schedule_workers: bool, was_scheduled: bool) {
     // upgrade後は timer 実体が失われるため、永続化済みの wrap queue を再接続する。
-    if recover_wrap_worker_state_after_upgrade() && schedule_workers {
+    let pending = recover_wrap_worker_state_after_upgrade();
+    if (pending || was_scheduled) && schedule_workers {
         schedule_wrap_worker();
     }
 }
@@ -2986,7 +5263,9 @@ fn recover_unwrap_dispatch_state_after_upgrade(now: u64) -> bool {
                     req.updated_at = now;
                     candidates.push((request_id, req));
                 }
-                UnwrapRequestStatus::Dispatched | UnwrapRequestStatus::DispatchFailed => {}
+                UnwrapRequestStatus::Dispatched
+                | UnwrapRequestStatus::DispatchFailed
+                | UnwrapRequestStatus::DispatchUncertain => {}
             }
         }
 
@@ -3064,7 +5343,7 @@ fn recover_icp_update_dispatch_state_after_upgrade(now: u64) -> bool {
 
 #[ic_cdk::pre_upgrade]
 fn pre_upgrade() {
-    upgrade::pre_upgrade();
+    upgrade::pre_upgrade(&upgrade_checkpoint());
 }
 
 #[ic_cdk::inspect_message]
@@ -3084,6 +5363,8 @@ fn inspect_message() {
 
 const INSPECT_TX_PAYLOAD_LIMIT: usize = MAX_TX_SIZE.saturating_mul(2);
 const INSPECT_MANAGE_PAYLOAD_LIMIT: usize = MAX_TX_SIZE.saturating_mul(8);
+// state snapshot の1ページ（最大1.5MB）を1メッセージで受け取れる上限。
+const INSPECT_SNAPSHOT_PAYLOAD_LIMIT: usize = MAX_TX_SIZE.saturating_mul(16);
 
 #[derive(Clone, Copy)]
 struct InspectMethodPolicy {
@@ -3093,95 +5374,175 @@ struct InspectMethodPolicy {
 
 const INSPECT_METHOD_POLICIES: &[InspectMethodPolicy] = &[
     InspectMethodPolicy {
-        method: "add_query_precompile_allowed_method",
+        method: "add_query_precompile_allowed_method",
+        payload_limit: INSPECT_MANAGE_PAYLOAD_LIMIT,
+    },
+    InspectMethodPolicy {
+        method: "add_update_precompile_allowed_method",
+        payload_limit: INSPECT_MANAGE_PAYLOAD_LIMIT,
+    },
+    InspectMethodPolicy {
+        method: "icrc21_canister_call_consent_message",
+        payload_limit: INSPECT_MANAGE_PAYLOAD_LIMIT,
+    },
+    InspectMethodPolicy {
+        method: "submit_ic_tx",
+        payload_limit: INSPECT_TX_PAYLOAD_LIMIT,
+    },
+    InspectMethodPolicy {
+        method: "rpc_eth_send_raw_transaction",
+        payload_limit: INSPECT_TX_PAYLOAD_LIMIT,
+    },
+    InspectMethodPolicy {
+        method: "credit_native_deposit",
+        payload_limit: INSPECT_MANAGE_PAYLOAD_LIMIT,
+    },
+    InspectMethodPolicy {
+        method: "dispatch_native_withdrawal_request",
+        payload_limit: INSPECT_MANAGE_PAYLOAD_LIMIT,
+    },
+    InspectMethodPolicy {
+        method: "dispatch_unwrap_request",
+        payload_limit: INSPECT_MANAGE_PAYLOAD_LIMIT,
+    },
+    InspectMethodPolicy {
+        method: "remove_query_precompile_allowed_method",
+        payload_limit: INSPECT_MANAGE_PAYLOAD_LIMIT,
+    },
+    InspectMethodPolicy {
+        method: "remove_update_precompile_allowed_method",
+        payload_limit: INSPECT_MANAGE_PAYLOAD_LIMIT,
+    },
+    InspectMethodPolicy {
+        method: "recover_failed_wrap",
+        payload_limit: INSPECT_MANAGE_PAYLOAD_LIMIT,
+    },
+    InspectMethodPolicy {
+        method: "reconcile_unwrap_request",
+        payload_limit: INSPECT_MANAGE_PAYLOAD_LIMIT,
+    },
+    InspectMethodPolicy {
+        method: "repair_stale_wrap_operations",
+        payload_limit: INSPECT_MANAGE_PAYLOAD_LIMIT,
+    },
+    InspectMethodPolicy {
+        method: "retry_native_deposit",
+        payload_limit: INSPECT_MANAGE_PAYLOAD_LIMIT,
+    },
+    InspectMethodPolicy {
+        method: "retry_native_withdrawal",
+        payload_limit: INSPECT_MANAGE_PAYLOAD_LIMIT,
+    },
+    InspectMethodPolicy {
+        method: "retry_request",
         payload_limit: INSPECT_MANAGE_PAYLOAD_LIMIT,
     },
     InspectMethodPolicy {
-        method: "add_update_precompile_allowed_method",
+        method: "set_allowed_assets",
         payload_limit: INSPECT_MANAGE_PAYLOAD_LIMIT,
     },
     InspectMethodPolicy {
-        method: "icrc21_canister_call_consent_message",
+        method: "set_allowed_nft_collections",
         payload_limit: INSPECT_MANAGE_PAYLOAD_LIMIT,
     },
     InspectMethodPolicy {
-        method: "submit_ic_tx",
-        payload_limit: INSPECT_TX_PAYLOAD_LIMIT,
+        method: "set_asset_fee_schedule",
+        payload_limit: INSPECT_MANAGE_PAYLOAD_LIMIT,
     },
     InspectMethodPolicy {
-        method: "rpc_eth_send_raw_transaction",
-        payload_limit: INSPECT_TX_PAYLOAD_LIMIT,
+        method: "clear_asset_fee_schedule",
+        payload_limit: INSPECT_MANAGE_PAYLOAD_LIMIT,
     },
     InspectMethodPolicy {
-        method: "credit_native_deposit",
+        method: "set_asset_limits",
         payload_limit: INSPECT_MANAGE_PAYLOAD_LIMIT,
     },
     InspectMethodPolicy {
-        method: "dispatch_native_withdrawal_request",
+        method: "set_fee_policy",
         payload_limit: INSPECT_MANAGE_PAYLOAD_LIMIT,
     },
     InspectMethodPolicy {
-        method: "dispatch_unwrap_request",
+        method: "sync_wrapped_token_metadata",
         payload_limit: INSPECT_MANAGE_PAYLOAD_LIMIT,
     },
     InspectMethodPolicy {
-        method: "remove_query_precompile_allowed_method",
+        method: "submit_native_deposit",
         payload_limit: INSPECT_MANAGE_PAYLOAD_LIMIT,
     },
     InspectMethodPolicy {
-        method: "remove_update_precompile_allowed_method",
+        method: "submit_wrap_request",
         payload_limit: INSPECT_MANAGE_PAYLOAD_LIMIT,
     },
     InspectMethodPolicy {
-        method: "recover_failed_wrap",
+        method: "submit_nft_wrap_request",
         payload_limit: INSPECT_MANAGE_PAYLOAD_LIMIT,
     },
     InspectMethodPolicy {
-        method: "repair_stale_wrap_operations",
+        method: "notify_deposit",
         payload_limit: INSPECT_MANAGE_PAYLOAD_LIMIT,
     },
     InspectMethodPolicy {
-        method: "retry_native_deposit",
+        method: "set_prune_policy",
         payload_limit: INSPECT_MANAGE_PAYLOAD_LIMIT,
     },
     InspectMethodPolicy {
-        method: "retry_native_withdrawal",
+        method: "set_pruning_enabled",
         payload_limit: INSPECT_MANAGE_PAYLOAD_LIMIT,
     },
     InspectMethodPolicy {
-        method: "retry_request",
+        method: "set_optimistic_block_exec",
         payload_limit: INSPECT_MANAGE_PAYLOAD_LIMIT,
     },
     InspectMethodPolicy {
-        method: "set_allowed_assets",
+        method: "set_drop_record_retention",
         payload_limit: INSPECT_MANAGE_PAYLOAD_LIMIT,
     },
     InspectMethodPolicy {
-        method: "set_fee_policy",
+        method: "set_state_snapshot_export_hold",
         payload_limit: INSPECT_MANAGE_PAYLOAD_LIMIT,
     },
     InspectMethodPolicy {
-        method: "submit_native_deposit",
+        method: "import_state_snapshot",
+        payload_limit: INSPECT_SNAPSHOT_PAYLOAD_LIMIT,
+    },
+    InspectMethodPolicy {
+        method: "set_log_filter",
         payload_limit: INSPECT_MANAGE_PAYLOAD_LIMIT,
     },
     InspectMethodPolicy {
-        method: "submit_wrap_request",
+        method: "prune_blocks",
         payload_limit: INSPECT_MANAGE_PAYLOAD_LIMIT,
     },
     InspectMethodPolicy {
-        method: "set_prune_policy",
+        method: "compact_blob_store",
         payload_limit: INSPECT_MANAGE_PAYLOAD_LIMIT,
     },
     InspectMethodPolicy {
-        method: "set_pruning_enabled",
+        method: "start_scrub",
         payload_limit: INSPECT_MANAGE_PAYLOAD_LIMIT,
     },
     InspectMethodPolicy {
-        method: "set_log_filter",
+        method: "run_scrub",
         payload_limit: INSPECT_MANAGE_PAYLOAD_LIMIT,
     },
     InspectMethodPolicy {
-        method: "prune_blocks",
+        method: "set_scrub_paused",
+        payload_limit: INSPECT_MANAGE_PAYLOAD_LIMIT,
+    },
+    InspectMethodPolicy {
+        method: "set_wrap_paused",
+        payload_limit: INSPECT_MANAGE_PAYLOAD_LIMIT,
+    },
+    InspectMethodPolicy {
+        method: "check_reserves",
+        payload_limit: INSPECT_MANAGE_PAYLOAD_LIMIT,
+    },
+    InspectMethodPolicy {
+        method: "set_archive_canister",
+        payload_limit: INSPECT_MANAGE_PAYLOAD_LIMIT,
+    },
+    InspectMethodPolicy {
+        method: "ack_archived_blocks",
         payload_limit: INSPECT_MANAGE_PAYLOAD_LIMIT,
     },
     #[cfg(feature = "precompile-profile-admin")]
@@ -3502,6 +5863,11 @@ fn get_block(number: u64) -> Result<BlockView, LookupError> {
     let pruned_before = with_state(|state| state.prune_state.get().pruned_before());
     if let Some(pruned) = pruned_before {
         if number <= pruned {
+            if let Some(range) = evm_core::archive::archived_range_for_block(number) {
+                return Err(LookupError::Archived {
+                    ranges: vec![ic_evm_rpc::archive_range_to_view(range)],
+                });
+            }
             return Err(LookupError::Pruned {
                 pruned_before_block: pruned,
             });
@@ -3531,16 +5897,56 @@ fn get_receipt(tx_id: Vec<u8>) -> Result<ReceiptView, LookupError> {
         if loc.kind == TxLocKind::Included {
             if let Some(pruned) = pruned_before {
                 if loc.block_number <= pruned {
+                    if let Some(range) =
+                        evm_core::archive::archived_range_for_block(loc.block_number)
+                    {
+                        return Err(LookupError::Archived {
+                            ranges: vec![ic_evm_rpc::archive_range_to_view(range)],
+                        });
+                    }
                     return Err(LookupError::Pruned {
                         pruned_before_block: pruned,
                     });
                 }
             }
         }
+        return Err(LookupError::NotFound);
+    }
+    // prune で tx_loc は消えている。archive 済み block の tx だけは prune 時に block 番号を残してある。
+    if let Some(range) = evm_core::archive::archived_range_for_tx(&TxId(buf)) {
+        return Err(LookupError::Archived {
+            ranges: vec![ic_evm_rpc::archive_range_to_view(range)],
+        });
     }
     Err(LookupError::NotFound)
 }
 
+#[ic_cdk::query]
+fn icrc3_get_tip_certificate() -> Option<icrc3::Icrc3DataCertificate> {
+    icrc3::tip_certificate()
+}
+
+#[ic_cdk::query]
+fn get_certified_block(number: u64) -> Result<CertifiedBlockView, LookupError> {
+    let block = get_block(number)?;
+    Ok(CertifiedBlockView {
+        block,
+        certificate: icrc3::data_certificate().unwrap_or_default(),
+        witness: icrc3::block_witness(number),
+    })
+}
+
+#[ic_cdk::query]
+fn get_certified_receipt(tx_id: Vec<u8>) -> Result<CertifiedReceiptView, LookupError> {
+    let receipt = get_receipt(tx_id)?;
+    let witness = icrc3::receipt_witness(&receipt.tx_id);
+    Ok(CertifiedReceiptView {
+        receipt,
+        certificate: icrc3::data_certificate().unwrap_or_default(),
+        witness,
+    })
+}
+
 #[ic_cdk::query]
 fn export_blocks(
     cursor: Option<ExportCursorView>,
@@ -3589,6 +5995,163 @@ fn export_error_to_view(err: evm_core::export::ExportError) -> ExportErrorView {
     }
 }
 
+#[ic_cdk::query]
+fn export_state_snapshot(
+    cursor: Option<StateSnapshotCursorView>,
+    max_bytes: u32,
+) -> Result<StateSnapshotPageView, ExportErrorView> {
+    let core_cursor = cursor.map(|value| StateSnapshotCursor {
+        head_number: value.head_number,
+        evm_state_epoch: value.evm_state_epoch,
+        segment: value.segment,
+        last_key: value.last_key,
+    });
+    let page = evm_core::state_snapshot::export_state_snapshot(core_cursor, max_bytes)
+        .map_err(export_error_to_view)?;
+    Ok(state_snapshot_page_to_view(page))
+}
+
+// export を始める前に hold し、最後のページを読んだら解除する。hold 中はブロックを封印しない。
+#[ic_cdk::update]
+fn set_state_snapshot_export_hold(hold: bool) -> Result<(), String> {
+    if let Some(reason) = reject_anonymous_update() {
+        return Err(reason);
+    }
+    require_control_plane_write()?;
+    evm_core::state_snapshot::set_state_snapshot_export_hold(hold);
+    if !hold {
+        schedule_mining();
+    }
+    Ok(())
+}
+
+#[ic_cdk::update]
+fn import_state_snapshot(
+    op: StateSnapshotImportOpView,
+) -> Result<StateSnapshotImportStatusView, String> {
+    if let Some(reason) = reject_anonymous_update() {
+        return Err(reason);
+    }
+    require_control_plane_write()?;
+    let status = match op {
+        StateSnapshotImportOpView::Begin(manifest) => {
+            evm_core::state_snapshot::begin_state_snapshot_import(
+                state_snapshot_manifest_from_view(&manifest)?,
+            )
+        }
+        StateSnapshotImportOpView::Clear { max_steps } => {
+            if max_steps == 0 {
+                return Err("input.state_snapshot.max_steps.non_positive".to_string());
+            }
+            evm_core::state_snapshot::clear_state_snapshot_import(max_steps)
+        }
+        StateSnapshotImportOpView::Chunk(page) => {
+            let page = StateSnapshotPage {
+                manifest: state_snapshot_manifest_from_view(&page.manifest)?,
+                segment: page.segment,
+                entry_count: page.entry_count,
+                entries: page.entries,
+                next_cursor: None,
+            };
+            evm_core::state_snapshot::import_state_snapshot_page(&page)
+        }
+        StateSnapshotImportOpView::Finish { max_steps } => {
+            if max_steps == 0 {
+                return Err("input.state_snapshot.max_steps.non_positive".to_string());
+            }
+            evm_core::state_snapshot::finish_state_snapshot_import(max_steps)
+        }
+    }
+    .map_err(state_snapshot_import_error_to_string)?;
+    // Finish で head が差し替わったら certified tip も追従させる。
+    icrc3::refresh_certified_tip();
+    Ok(state_snapshot_import_status_to_view(status))
+}
+
+#[ic_cdk::query]
+fn get_state_snapshot_import_status() -> StateSnapshotImportStatusView {
+    state_snapshot_import_status_to_view(evm_core::state_snapshot::state_snapshot_import_status())
+}
+
+fn state_snapshot_manifest_from_view(
+    view: &StateSnapshotManifestView,
+) -> Result<StateSnapshotManifest, String> {
+    let block_hash: [u8; 32] = view
+        .block_hash
+        .as_slice()
+        .try_into()
+        .map_err(|_| "input.state_snapshot.block_hash.len".to_string())?;
+    let state_root: [u8; 32] = view
+        .state_root
+        .as_slice()
+        .try_into()
+        .map_err(|_| "input.state_snapshot.state_root.len".to_string())?;
+    Ok(StateSnapshotManifest {
+        head_number: view.head_number,
+        block_hash,
+        timestamp: view.timestamp,
+        state_root,
+        evm_state_epoch: view.evm_state_epoch,
+    })
+}
+
+fn state_snapshot_manifest_to_view(manifest: StateSnapshotManifest) -> StateSnapshotManifestView {
+    StateSnapshotManifestView {
+        head_number: manifest.head_number,
+        block_hash: manifest.block_hash.to_vec(),
+        timestamp: manifest.timestamp,
+        state_root: manifest.state_root.to_vec(),
+        evm_state_epoch: manifest.evm_state_epoch,
+    }
+}
+
+fn state_snapshot_page_to_view(page: StateSnapshotPage) -> StateSnapshotPageView {
+    StateSnapshotPageView {
+        manifest: state_snapshot_manifest_to_view(page.manifest),
+        segment: page.segment,
+        entry_count: page.entry_count,
+        entries: page.entries,
+        next_cursor: page.next_cursor.map(|value| StateSnapshotCursorView {
+            head_number: value.head_number,
+            evm_state_epoch: value.evm_state_epoch,
+            segment: value.segment,
+            last_key: value.last_key,
+        }),
+    }
+}
+
+fn state_snapshot_import_status_to_view(
+    status: StateSnapshotImportV1,
+) -> StateSnapshotImportStatusView {
+    StateSnapshotImportStatusView {
+        phase: match status.phase {
+            StateSnapshotImportPhase::Idle => StateSnapshotImportPhaseView::Idle,
+            StateSnapshotImportPhase::Clearing => StateSnapshotImportPhaseView::Clearing,
+            StateSnapshotImportPhase::Loading => StateSnapshotImportPhaseView::Loading,
+            StateSnapshotImportPhase::Verifying => StateSnapshotImportPhaseView::Verifying,
+        },
+        manifest: state_snapshot_manifest_to_view(StateSnapshotManifest {
+            head_number: status.head_number,
+            block_hash: status.block_hash,
+            timestamp: status.timestamp,
+            state_root: status.state_root,
+            evm_state_epoch: status.evm_state_epoch,
+        }),
+        accounts: status.accounts,
+        storage: status.storage,
+        codes: status.codes,
+    }
+}
+
+fn state_snapshot_import_error_to_string(err: StateSnapshotImportError) -> String {
+    match err {
+        StateSnapshotImportError::NotAllowed(code)
+        | StateSnapshotImportError::InvalidPage(code) => code.to_string(),
+        // 再計算したrootは state_root_meta に残るため、ここではコードだけ返す。
+        StateSnapshotImportError::RootMismatch { .. } => "state_snapshot.root_mismatch".to_string(),
+    }
+}
+
 #[ic_cdk::query]
 fn rpc_eth_chain_id() -> u64 {
     CHAIN_ID
@@ -3635,6 +6198,32 @@ fn set_pruning_enabled(enabled: bool) -> Result<(), String> {
     Ok(())
 }
 
+#[ic_cdk::update]
+fn set_optimistic_block_exec(enabled: bool) -> Result<(), String> {
+    if let Some(reason) = reject_anonymous_update() {
+        return Err(reason);
+    }
+    require_control_plane_write()?;
+    let mode = if enabled {
+        chain::BlockExecMode::Optimistic
+    } else {
+        chain::BlockExecMode::Sequential
+    };
+    chain::set_block_exec_mode(mode).map_err(|_| "set_optimistic_block_exec failed".to_string())?;
+    Ok(())
+}
+
+#[ic_cdk::update]
+fn set_drop_record_retention(retain_secs: u64, max_records: u64) -> Result<(), String> {
+    if let Some(reason) = reject_anonymous_update() {
+        return Err(reason);
+    }
+    require_control_plane_write()?;
+    chain::set_drop_record_retention(retain_secs, max_records)
+        .map_err(|_| "input.drop_record.max_records.non_positive".to_string())?;
+    Ok(())
+}
+
 #[ic_cdk::query]
 fn get_prune_status() -> PruneStatusView {
     let status = chain::get_prune_status();
@@ -3695,6 +6284,47 @@ fn rpc_eth_get_storage_at(
     ic_evm_rpc::rpc_eth_get_storage_at(address, slot, tag)
 }
 
+#[ic_cdk::query]
+fn rpc_eth_get_balance_certified(address: Vec<u8>) -> Result<CertifiedAccountView, RpcErrorView> {
+    certified_account_view(address)
+}
+
+#[ic_cdk::query]
+fn rpc_eth_get_code_certified(address: Vec<u8>) -> Result<CertifiedCodeView, RpcErrorView> {
+    let state = certified_account_view(address)?;
+    let code = ic_evm_rpc::rpc_eth_code_for_proof(&state.account)?;
+    Ok(CertifiedCodeView { state, code })
+}
+
+#[ic_cdk::query]
+fn rpc_eth_get_storage_at_certified(
+    address: Vec<u8>,
+    slot: Vec<u8>,
+) -> Result<CertifiedStorageView, RpcErrorView> {
+    let state = certified_account_view(address)?;
+    let storage = ic_evm_rpc::rpc_eth_storage_proof(&state.account, slot)?;
+    Ok(CertifiedStorageView { state, storage })
+}
+
+// 値は現在の accounts ではなく、certified data に載せた head の state root から読む。
+fn certified_account_view(address: Vec<u8>) -> Result<CertifiedAccountView, RpcErrorView> {
+    let Some((block_number, state_root)) = icrc3::certified_state_root() else {
+        return Err(RpcErrorView {
+            code: 2001,
+            message: "certified state root is not available yet".to_string(),
+            error_prefix: Some("state_proof.uncertified".to_string()),
+        });
+    };
+    let account = ic_evm_rpc::rpc_eth_account_proof(address, state_root)?;
+    Ok(CertifiedAccountView {
+        block_number,
+        state_root: state_root.to_vec(),
+        certificate: icrc3::data_certificate().unwrap_or_default(),
+        witness: icrc3::tip_hash_tree().unwrap_or_default(),
+        account,
+    })
+}
+
 #[ic_cdk::query]
 fn rpc_eth_call_object(call: RpcCallObjectView) -> Result<RpcCallResultView, RpcErrorView> {
     ic_evm_rpc::rpc_eth_call_object(call)
@@ -3884,9 +6514,12 @@ fn get_ops_status() -> OpsStatusView {
             last_check_ts: ops.last_check_ts,
             mode: ic_evm_ops::mode_to_view(ops.mode),
             safe_stop_latched: ops.safe_stop_latched,
+            wrap_paused: ops.wrap_paused,
+            last_reserves_check_ts: ops.last_reserves_check_ts,
             // write-block条件と同じ判定を返し、運用上の見え方を一致させる。
             needs_migration: migration_pending(),
             schema_version: meta.schema_version,
+            schema_migration: schema_migration_status_view(),
             log_filter_override: state.log_config.get().filter().map(str::to_string),
             log_truncated_count: LOG_TRUNCATED_COUNT.load(Ordering::Relaxed),
             critical_corrupt: critical_corrupt_state(),
@@ -3902,6 +6535,22 @@ fn get_ops_status() -> OpsStatusView {
     })
 }
 
+fn schema_migration_status_view() -> SchemaMigrationStatusView {
+    let state = schema_migration_state();
+    let migration = evm_core::schema_migration::current_schema_migration(&state);
+    SchemaMigrationStatusView {
+        phase: state.phase.label().to_string(),
+        from_version: state.from_version,
+        to_version: state.to_version,
+        migration_id: migration.map(|migration| migration.id.to_string()),
+        migration_from_version: migration.map(|migration| migration.from_version),
+        migration_to_version: migration.map(|migration| migration.to_version),
+        processed: state.cursor,
+        last_error: state.last_error,
+        rollback: migration.map(|migration| migration.rollback.to_string()),
+    }
+}
+
 #[ic_cdk::update]
 fn set_log_filter(filter: Option<String>) -> Result<(), String> {
     if let Some(reason) = reject_anonymous_update() {
@@ -3955,6 +6604,7 @@ fn health() -> HealthView {
             auto_production_enabled: chain_state.auto_production_enabled,
             is_producing: chain_state.is_producing,
             mining_scheduled: chain_state.mining_scheduled,
+            optimistic_exec_enabled: chain_state.optimistic_exec_enabled,
             block_gas_limit: chain_state.block_gas_limit,
             query_instruction_soft_limit: chain_state.query_instruction_soft_limit,
             update_instruction_soft_limit: chain_state.update_instruction_soft_limit,
@@ -4100,35 +6750,249 @@ fn get_precompile_profile() -> Vec<PrecompileProfileView> {
         .collect()
 }
 
-#[cfg(feature = "precompile-profile-admin")]
-#[ic_cdk::update]
-fn clear_precompile_profile() -> Result<(), String> {
-    if let Some(reason) = reject_anonymous_update() {
-        return Err(reason);
+#[cfg(feature = "precompile-profile-admin")]
+#[ic_cdk::update]
+fn clear_precompile_profile() -> Result<(), String> {
+    if let Some(reason) = reject_anonymous_update() {
+        return Err(reason);
+    }
+    require_control_plane_write()?;
+    evm_core::kasane_precompiles::clear_precompile_profile();
+    Ok(())
+}
+
+#[ic_cdk::update]
+fn prune_blocks(retain: u64, max_ops: u32) -> Result<PruneResultView, ProduceBlockError> {
+    if let Some(reason) = reject_anonymous_update() {
+        return Err(ProduceBlockError::Internal(reason));
+    }
+    if let Err(reason) = require_control_plane_write() {
+        return Err(ProduceBlockError::Internal(reason));
+    }
+    match chain::prune_blocks(retain, max_ops) {
+        Ok(result) => Ok(PruneResultView {
+            did_work: result.did_work,
+            remaining: result.remaining,
+            pruned_before_block: result.pruned_before_block,
+        }),
+        Err(chain::ChainError::InvalidLimit) => Err(ProduceBlockError::InvalidArgument(
+            "retain/max_ops must be > 0".to_string(),
+        )),
+        Err(_err) => Err(ProduceBlockError::Internal("internal error".to_string())),
+    }
+}
+
+#[ic_cdk::update]
+fn compact_blob_store(max_ops: u32) -> Result<BlobCompactionStatusView, String> {
+    if let Some(reason) = reject_anonymous_update() {
+        return Err(reason);
+    }
+    require_control_plane_write()?;
+    match evm_core::blob_compaction::blob_compaction_tick(max_ops) {
+        Ok(status) => Ok(blob_compaction_status_to_view(status)),
+        Err(chain::ChainError::InvalidLimit) => {
+            Err("input.blob_compaction.max_ops.non_positive".to_string())
+        }
+        Err(_err) => Err("internal error".to_string()),
+    }
+}
+
+#[ic_cdk::update]
+fn set_archive_canister(canister_id: Option<Principal>) -> Result<ArchiveStatusView, String> {
+    if let Some(reason) = reject_anonymous_update() {
+        return Err(reason);
+    }
+    require_control_plane_write()?;
+    evm_core::archive::set_archive_canister(canister_id.map(|id| id.as_slice().to_vec()))
+        .map(archive_status_to_view)
+        .map_err(archive_error_to_string)
+}
+
+// archive canister だけが呼べる。export_blocks で受け取り終えた最後の block を通知する。
+#[ic_cdk::update]
+fn ack_archived_blocks(end_block: u64) -> Result<(), String> {
+    evm_core::archive::ack_archived_blocks(ic_cdk::api::msg_caller().as_slice(), end_block)
+        .map(|_| ())
+        .map_err(archive_error_to_string)
+}
+
+#[ic_cdk::query]
+fn get_archive_status() -> ArchiveStatusView {
+    archive_status_to_view(evm_core::archive::archive_status())
+}
+
+fn archive_status_to_view(status: evm_core::archive::ArchiveStatus) -> ArchiveStatusView {
+    ArchiveStatusView {
+        canister_id: status.canister.map(|bytes| Principal::from_slice(&bytes)),
+        acked_through: status.acked_through,
+        acked_at: status.acked_at,
+        next_block: status.next_block,
+        ranges: status
+            .ranges
+            .into_iter()
+            .map(ic_evm_rpc::archive_range_to_view)
+            .collect(),
+    }
+}
+
+fn archive_error_to_string(err: evm_core::archive::ArchiveError) -> String {
+    match err {
+        evm_core::archive::ArchiveError::NotAllowed(code)
+        | evm_core::archive::ArchiveError::InvalidAck(code) => code.to_string(),
+    }
+}
+
+#[ic_cdk::query]
+fn get_blob_compaction_status() -> BlobCompactionStatusView {
+    blob_compaction_status_to_view(evm_core::blob_compaction::blob_compaction_status())
+}
+
+fn blob_compaction_status_to_view(
+    status: evm_core::blob_compaction::BlobCompactionStatus,
+) -> BlobCompactionStatusView {
+    BlobCompactionStatusView {
+        phase: match status.phase {
+            BlobCompactionPhase::Idle => BlobCompactionPhaseView::Idle,
+            BlobCompactionPhase::Relocating => BlobCompactionPhaseView::Relocating,
+            BlobCompactionPhase::Trimming => BlobCompactionPhaseView::Trimming,
+        },
+        did_work: status.did_work,
+        blocked: status.blocked.map(str::to_string),
+        passes: status.passes,
+        relocated_blobs: status.relocated_blobs,
+        relocated_bytes: status.relocated_bytes,
+        trimmed_bytes: status.trimmed_bytes,
+        live_bytes: status.live_bytes,
+        arena_end_bytes: status.arena_end_bytes,
+        reclaimable_bytes: status.reclaimable_bytes,
+    }
+}
+
+// 走行中の scrub があっても先頭からやり直す。
+#[ic_cdk::update]
+fn start_scrub(auto_repair: bool) -> Result<ScrubStatusView, String> {
+    if let Some(reason) = reject_anonymous_update() {
+        return Err(reason);
+    }
+    require_control_plane_write()?;
+    Ok(scrub_status_to_view(evm_core::scrub::start_scrub(
+        auto_repair,
+        current_time_nanos(),
+    )))
+}
+
+#[ic_cdk::update]
+fn run_scrub(max_ops: u32) -> Result<ScrubStatusView, String> {
+    if let Some(reason) = reject_anonymous_update() {
+        return Err(reason);
+    }
+    require_control_plane_write()?;
+    evm_core::scrub::scrub_tick(max_ops, current_time_nanos())
+        .map(scrub_status_to_view)
+        .map_err(scrub_error_to_string)
+}
+
+#[ic_cdk::update]
+fn set_scrub_paused(paused: bool) -> Result<ScrubStatusView, String> {
+    if let Some(reason) = reject_anonymous_update() {
+        return Err(reason);
+    }
+    require_control_plane_write()?;
+    evm_core::scrub::set_scrub_paused(paused)
+        .map(scrub_status_to_view)
+        .map_err(scrub_error_to_string)
+}
+
+#[ic_cdk::query]
+fn get_scrub_status() -> ScrubStatusView {
+    scrub_status_to_view(evm_core::scrub::scrub_status())
+}
+
+#[ic_cdk::query]
+fn get_scrub_findings(after_seq: Option<u64>, limit: u32) -> Vec<ScrubFindingView> {
+    let limit = usize::try_from(limit.min(MAX_SCRUB_FINDINGS_LIMIT)).unwrap_or(0);
+    evm_core::scrub::scrub_findings(after_seq, limit)
+        .into_iter()
+        .map(|(seq, finding)| ScrubFindingView {
+            seq,
+            run_id: finding.run_id,
+            target: scrub_target_label(finding.target).to_string(),
+            kind: scrub_finding_kind_label(finding.kind).to_string(),
+            key: finding.key.to_vec(),
+            repaired: finding.repaired,
+            detected_at: finding.detected_at,
+        })
+        .collect()
+}
+
+fn scrub_status_to_view(status: evm_core::scrub::ScrubStatus) -> ScrubStatusView {
+    let state = status.state;
+    ScrubStatusView {
+        phase: match state.phase {
+            ScrubPhase::Idle => ScrubPhaseView::Idle,
+            ScrubPhase::Running => ScrubPhaseView::Running,
+            ScrubPhase::Paused => ScrubPhaseView::Paused,
+        },
+        target: scrub_target_label(state.target).to_string(),
+        auto_repair: state.auto_repair,
+        did_work: status.did_work,
+        blocked: status.blocked.map(str::to_string),
+        run_id: state.run_id,
+        started_at: state.started_at,
+        finished_at: state.finished_at,
+        scanned: state.scanned,
+        findings: state.findings,
+        repaired: state.repaired,
     }
-    require_control_plane_write()?;
-    evm_core::kasane_precompiles::clear_precompile_profile();
-    Ok(())
 }
 
-#[ic_cdk::update]
-fn prune_blocks(retain: u64, max_ops: u32) -> Result<PruneResultView, ProduceBlockError> {
-    if let Some(reason) = reject_anonymous_update() {
-        return Err(ProduceBlockError::Internal(reason));
+fn scrub_error_to_string(err: evm_core::scrub::ScrubError) -> String {
+    match err {
+        evm_core::scrub::ScrubError::InvalidLimit => "input.scrub.max_ops.non_positive".to_string(),
+        evm_core::scrub::ScrubError::NotActive => "scrub.not_active".to_string(),
     }
-    if let Err(reason) = require_control_plane_write() {
-        return Err(ProduceBlockError::Internal(reason));
+}
+
+fn scrub_target_label(target: ScrubTarget) -> &'static str {
+    match target {
+        ScrubTarget::Blocks => "blocks",
+        ScrubTarget::Receipts => "receipts",
+        ScrubTarget::TxLocs => "tx_locs",
+        ScrubTarget::EthTxHashIndex => "eth_tx_hash_index",
+        ScrubTarget::WrapRequests => "wrap_requests",
+        ScrubTarget::WrapQueue => "wrap_queue",
+        ScrubTarget::UnwrapRequests => "unwrap_requests",
+        ScrubTarget::UnwrapQueue => "unwrap_queue",
     }
-    match chain::prune_blocks(retain, max_ops) {
-        Ok(result) => Ok(PruneResultView {
-            did_work: result.did_work,
-            remaining: result.remaining,
-            pruned_before_block: result.pruned_before_block,
-        }),
-        Err(chain::ChainError::InvalidLimit) => Err(ProduceBlockError::InvalidArgument(
-            "retain/max_ops must be > 0".to_string(),
-        )),
-        Err(_err) => Err(ProduceBlockError::Internal("internal error".to_string())),
+}
+
+fn scrub_finding_kind_label(kind: ScrubFindingKind) -> &'static str {
+    match kind {
+        ScrubFindingKind::Unreadable => "unreadable",
+        ScrubFindingKind::MissingTxIndex => "missing_tx_index",
+        ScrubFindingKind::MissingTxLoc => "missing_tx_loc",
+        ScrubFindingKind::MissingReceipt => "missing_receipt",
+        ScrubFindingKind::LocationMismatch => "location_mismatch",
+        ScrubFindingKind::OrphanReceipt => "orphan_receipt",
+        ScrubFindingKind::DanglingTxLoc => "dangling_tx_loc",
+        ScrubFindingKind::MissingEthTxHash => "missing_eth_tx_hash",
+        ScrubFindingKind::DanglingQueueEntry => "dangling_queue_entry",
+    }
+}
+
+#[ic_cdk::query]
+fn get_blob_recompress_status() -> BlobRecompressStatusView {
+    let status = evm_core::blob_recompress::blob_recompress_status();
+    BlobRecompressStatusView {
+        phase: match status.phase {
+            BlobRecompressPhase::Pending => BlobRecompressPhaseView::Pending,
+            BlobRecompressPhase::Running => BlobRecompressPhaseView::Running,
+            BlobRecompressPhase::Done => BlobRecompressPhaseView::Done,
+        },
+        blocked: status.blocked.map(str::to_string),
+        scanned: status.scanned,
+        recompressed_blobs: status.recompressed_blobs,
+        saved_bytes: status.saved_bytes,
     }
 }
 
@@ -4352,14 +7216,27 @@ fn migration_pending() -> bool {
     if meta.needs_migration || meta.schema_version < current_schema_version() {
         return true;
     }
+    // snapshot import 中は取り込み途中の状態に書き込ませない。
     with_state(|state| {
         !state.state_root_meta.get().initialized
             || state.state_root_migration.get().phase != MigrationPhase::Done
+            || state.state_snapshot_import.get().is_active()
     })
 }
 
 fn drive_migrations_tick(schema_max_steps: u32, state_root_max_steps: u32) {
-    let _ = schema_migration_tick(schema_max_steps);
+    let before = schema_migration_state().phase;
+    let _ =
+        evm_core::schema_migration::schema_migration_tick(schema_max_steps, current_time_nanos());
+    let after = schema_migration_state();
+    if before != SchemaMigrationPhase::Error && after.phase == SchemaMigrationPhase::Error {
+        warn!(
+            migration = evm_core::schema_migration::current_schema_migration(&after)
+                .map_or("finalize", |migration| migration.id),
+            last_error = after.last_error,
+            "schema migration verification failed"
+        );
+    }
     let _ = chain::state_root_migration_tick(state_root_max_steps);
 }
 
@@ -4371,88 +7248,6 @@ fn critical_corrupt_state() -> bool {
     matches!(schema_migration_state().phase, SchemaMigrationPhase::Error)
 }
 
-fn schema_migration_tick(max_steps: u32) -> bool {
-    let mut steps = 0u32;
-    while steps < max_steps {
-        let mut state = schema_migration_state();
-        match state.phase {
-            SchemaMigrationPhase::Done => return true,
-            SchemaMigrationPhase::Error => return false,
-            SchemaMigrationPhase::Init => {
-                if state.from_version < 5 {
-                    chain::clear_eth_tx_hash_index();
-                    state.cursor_key_set = false;
-                    state.cursor_key = [0u8; 32];
-                }
-                state.phase = SchemaMigrationPhase::Scan;
-                state.cursor = 0;
-                set_schema_migration_state(state);
-            }
-            SchemaMigrationPhase::Scan => {
-                state.phase = SchemaMigrationPhase::Rewrite;
-                state.cursor = 0;
-                set_schema_migration_state(state);
-            }
-            SchemaMigrationPhase::Rewrite => {
-                if state.from_version < 5 {
-                    let start_key = if state.cursor_key_set {
-                        Some(TxId(state.cursor_key))
-                    } else {
-                        None
-                    };
-                    let (last_key, rebuilt, done) =
-                        chain::rebuild_eth_tx_hash_index_batch(start_key, 512);
-                    state.cursor = state.cursor.saturating_add(rebuilt);
-                    if let Some(key) = last_key {
-                        state.cursor_key_set = true;
-                        state.cursor_key = key.0;
-                    }
-                    set_schema_migration_state(state);
-                    if !done {
-                        return false;
-                    }
-                    state.cursor_key_set = false;
-                    state.cursor_key = [0u8; 32];
-                    set_schema_migration_state(state);
-                }
-                state.phase = SchemaMigrationPhase::Verify;
-                state.cursor = 0;
-                set_schema_migration_state(state);
-            }
-            SchemaMigrationPhase::Verify => {
-                if !evm_db::meta::tx_locs_v3_active() {
-                    state.phase = SchemaMigrationPhase::Error;
-                    state.last_error = 2;
-                    set_schema_migration_state(state);
-                    return false;
-                }
-                if state.from_version < 5 {
-                    let (index_ok, indexed, expected) = chain::verify_eth_tx_hash_index(256);
-                    if !index_ok {
-                        warn!(
-                            indexed_eth_hashes = indexed,
-                            expected_eth_signed = expected,
-                            "eth_tx_hash index verification failed"
-                        );
-                        state.phase = SchemaMigrationPhase::Error;
-                        state.last_error = 4;
-                        set_schema_migration_state(state);
-                        return false;
-                    }
-                }
-                mark_migration_applied(state.from_version, state.to_version, current_time_nanos());
-                set_needs_migration(false);
-                state.phase = SchemaMigrationPhase::Done;
-                state.cursor = 0;
-                set_schema_migration_state(state);
-                return true;
-            }
-        }
-        steps = steps.saturating_add(1);
-    }
-    false
-}
-
 fn observe_cycles() -> OpsMode {
     let balance = canister_cycle_balance();
     let now = current_time_nanos();
@@ -4660,15 +7455,14 @@ fn apply_post_upgrade_migrations() {
     let meta = get_meta();
     let current = current_schema_version();
     if meta.schema_version > current {
-        warn!(
-            schema_version = meta.schema_version,
-            supported_schema = current,
-            "upgrade schema is newer than supported"
-        );
-        let mut next = meta;
-        next.needs_migration = true;
-        evm_db::meta::set_meta(next);
-        return;
+        // checkpoint 版 1 の wasm からでも、新しい layout を古い wasm で読み進めない。
+        ic_cdk::trap(format!(
+            "UpgradeRefused: {}",
+            upgrade::UpgradeError::SchemaDowngrade {
+                stored: meta.schema_version,
+                supported: current,
+            }
+        ));
     }
 
     let from = meta.schema_version;
@@ -4692,6 +7486,7 @@ fn apply_post_upgrade_migrations() {
             last_error: 0,
             cursor_key_set: false,
             cursor_key: [0u8; 32],
+            migration_index: 0,
         });
         evm_db::stable_state::with_state_mut(|state| {
             let mut migration = *state.state_root_migration.get();
@@ -4825,6 +7620,31 @@ fn maybe_prune_on_block_event(block_number: u64) {
         PRUNE_ERROR_COUNT.fetch_add(1, Ordering::Relaxed);
         error!(error = ?err, block_number, "prune_tick failed on block event");
     }
+    // compaction は controller が始めた pass だけを block event で少しずつ進める。
+    if evm_core::blob_compaction::blob_compaction_active() {
+        if let Err(err) = evm_core::blob_compaction::blob_compaction_tick(
+            evm_core::blob_compaction::DEFAULT_BLOB_COMPACTION_OPS_PER_TICK,
+        ) {
+            error!(error = ?err, block_number, "blob_compaction_tick failed on block event");
+        }
+    }
+    // 圧縮導入前の blob の書き直しは、完了するまで block event ごとに少しずつ進める。
+    if evm_core::blob_recompress::blob_recompress_active() {
+        if let Err(err) = evm_core::blob_recompress::blob_recompress_tick(
+            evm_core::blob_recompress::DEFAULT_BLOB_RECOMPRESS_OPS_PER_TICK,
+        ) {
+            error!(error = ?err, block_number, "blob_recompress_tick failed on block event");
+        }
+    }
+    // scrub も controller が始めた走査だけを進める。
+    if evm_core::scrub::scrub_active() {
+        if let Err(err) = evm_core::scrub::scrub_tick(
+            evm_core::scrub::DEFAULT_SCRUB_OPS_PER_TICK,
+            current_time_nanos(),
+        ) {
+            error!(error = ?err, block_number, "scrub_tick failed on block event");
+        }
+    }
 }
 
 fn mining_tick() {
@@ -4862,7 +7682,7 @@ fn mining_tick_with_timer(timer_scheduler: fn(u64), reject_provider: fn() -> Opt
     });
 
     if should_produce {
-        let result = chain::produce_block(evm_db::chain_data::MAX_TXS_PER_BLOCK);
+        let result = chain::produce_block_round(evm_db::chain_data::MAX_TXS_PER_BLOCK);
 
         evm_db::stable_state::with_state_mut(|state| {
             let mut chain_state = *state.chain_state.get();
@@ -4870,7 +7690,12 @@ fn mining_tick_with_timer(timer_scheduler: fn(u64), reject_provider: fn() -> Opt
             state.chain_state.set(chain_state);
         });
         match result {
-            Ok(outcome) => {
+            // 封印前のブロックは次のtimerで続きを組む（readyが残るため下で再スケジュールされる）。
+            Ok(chain::BlockRoundOutcome::Staged(_)) => {}
+            // 事前実行だけ済ませたラウンド。封印は次のtimerで行う。
+            Ok(chain::BlockRoundOutcome::PreExecuted(_)) => {}
+            Ok(chain::BlockRoundOutcome::Sealed(outcome)) => {
+                icrc3::refresh_certified_tip();
                 record_unwrap_requests_from_block(&outcome.block.tx_ids);
                 record_icp_update_requests_from_block(&outcome.block.tx_ids);
                 settle_submitted_wrap_mint_receipts(current_time_nanos());
@@ -4878,7 +7703,9 @@ fn mining_tick_with_timer(timer_scheduler: fn(u64), reject_provider: fn() -> Opt
                 schedule_icp_update_dispatch();
                 maybe_prune_on_block_event(outcome.block.number);
             }
-            Err(chain::ChainError::NoExecutableTx) | Err(chain::ChainError::QueueEmpty) => {}
+            Err(chain::ChainError::NoExecutableTx)
+            | Err(chain::ChainError::QueueEmpty)
+            | Err(chain::ChainError::ProductionHeld) => {}
             Err(err) => {
                 MINING_ERROR_COUNT.fetch_add(1, Ordering::Relaxed);
                 error!(error = ?err, "mining_tick produce_block failed");
@@ -4911,36 +7738,7 @@ fn record_unwrap_requests_from_block(tx_ids: &[TxId]) {
             continue;
         };
         for (log_index, log) in receipt.logs.iter().enumerate() {
-            if let Some(intent) = native_withdraw_intent_from_log(log) {
-                let Some(request_id) = derive_log_request_id(tx_id, log_index) else {
-                    continue;
-                };
-                with_state_mut(|state| {
-                    if state.unwrap_requests.get(&request_id).is_some() {
-                        return;
-                    }
-                    let now = current_time_nanos();
-                    state.unwrap_requests.insert(
-                        request_id,
-                        UnwrapDispatchRequest {
-                            asset_id: NATIVE_WITHDRAW_ASSET_MARKER.to_vec(),
-                            amount: intent.amount_e8s,
-                            recipient: intent.recipient,
-                            status: UnwrapRequestStatus::Queued,
-                            ledger_tx_id: None,
-                            error_code: None,
-                            updated_at: now,
-                            transfer_created_at_time: 0,
-                        },
-                    );
-                    let mut meta = *state.unwrap_dispatch_meta.get();
-                    let seq = meta.push();
-                    state.unwrap_dispatch_meta.set(meta);
-                    state.unwrap_dispatch_queue.insert(seq, request_id);
-                });
-                continue;
-            }
-            let Some(intent) = unwrap_intent_from_log(log) else {
+            let Some(req) = unwrap_dispatch_request_from_log(log, current_time_nanos()) else {
                 continue;
             };
             let Some(request_id) = derive_log_request_id(tx_id, log_index) else {
@@ -4950,20 +7748,7 @@ fn record_unwrap_requests_from_block(tx_ids: &[TxId]) {
                 if state.unwrap_requests.get(&request_id).is_some() {
                     return;
                 }
-                let now = current_time_nanos();
-                state.unwrap_requests.insert(
-                    request_id,
-                    UnwrapDispatchRequest {
-                        asset_id: intent.asset_id.clone(),
-                        amount: intent.amount,
-                        recipient: intent.recipient.clone(),
-                        status: UnwrapRequestStatus::Queued,
-                        ledger_tx_id: None,
-                        error_code: None,
-                        updated_at: now,
-                        transfer_created_at_time: 0,
-                    },
-                );
+                state.unwrap_requests.insert(request_id, req);
                 let mut meta = *state.unwrap_dispatch_meta.get();
                 let seq = meta.push();
                 state.unwrap_dispatch_meta.set(meta);
@@ -4973,6 +7758,52 @@ fn record_unwrap_requests_from_block(tx_ids: &[TxId]) {
     }
 }
 
+/// native withdraw / NFT unwrap / fungible unwrap の log を払い出し request にする。NFT は amount に token id を持つ。
+fn unwrap_dispatch_request_from_log(
+    log: &evm_db::chain_data::receipt::LogEntry,
+    now: u64,
+) -> Option<UnwrapDispatchRequest> {
+    let (asset_id, amount, recipient, recipient_subaccount, kind) =
+        if let Some(intent) = native_withdraw_intent_from_log(log) {
+            (
+                NATIVE_WITHDRAW_ASSET_MARKER.to_vec(),
+                intent.amount_e8s,
+                intent.recipient,
+                intent.recipient_subaccount,
+                UnwrapAssetKind::Fungible,
+            )
+        } else if let Some(intent) = nft_unwrap_intent_from_log(log) {
+            (
+                intent.collection_id,
+                intent.token_id,
+                intent.recipient,
+                intent.recipient_subaccount,
+                UnwrapAssetKind::Nft,
+            )
+        } else {
+            let intent = unwrap_intent_from_log(log)?;
+            (
+                intent.asset_id,
+                intent.amount,
+                intent.recipient,
+                intent.recipient_subaccount,
+                UnwrapAssetKind::Fungible,
+            )
+        };
+    Some(UnwrapDispatchRequest {
+        asset_id,
+        amount,
+        recipient,
+        recipient_subaccount,
+        status: UnwrapRequestStatus::Queued,
+        ledger_tx_id: None,
+        error_code: None,
+        updated_at: now,
+        transfer_created_at_time: 0,
+        kind,
+    })
+}
+
 fn record_icp_update_requests_from_block(tx_ids: &[TxId]) {
     for tx_id in tx_ids {
         let Some(receipt) = chain::get_receipt(tx_id) else {
@@ -5185,6 +8016,18 @@ fn pop_next_dispatch_request(now: u64) -> Result<Option<(TxId, UnwrapDispatchReq
                 request_id.0
             ));
         }
+        // burn は precompile で済んでいるので、上限に掛かった request は捨てずに止めて retry を待つ。
+        if let Err(code) = charge_unwrap_dispatch_limit(state, &req, now) {
+            req.updated_at = now;
+            req.status = UnwrapRequestStatus::DispatchFailed;
+            req.ledger_tx_id = None;
+            req.error_code = Some(code.clone());
+            state.unwrap_requests.insert(request_id, req);
+            return Err(format!(
+                "wrap.dispatch.limited:request_id={:?}:reason={code}",
+                request_id.0
+            ));
+        }
         req.status = UnwrapRequestStatus::Dispatching;
         req.updated_at = now;
         if req.transfer_created_at_time == 0 {
@@ -5196,6 +8039,37 @@ fn pop_next_dispatch_request(now: u64) -> Result<Option<(TxId, UnwrapDispatchReq
     out
 }
 
+/// 新しい台帳送金になる dispatch だけを枠に数える。同じ created_at_time の再送は台帳が重複排除するので停止だけを見る。
+fn unwrap_dispatch_limit_error(
+    limits: &AssetLimitsV1,
+    req: &UnwrapDispatchRequest,
+) -> Option<&'static str> {
+    if req.transfer_created_at_time != 0 {
+        return limits.paused.then_some("limits.asset_paused");
+    }
+    bridge_limit_error(limits, BridgeDirection::Unwrap, unwrap_limit_amount(req))
+}
+
+fn charge_unwrap_dispatch_limit(
+    state: &mut StableState,
+    req: &UnwrapDispatchRequest,
+    now: u64,
+) -> Result<(), String> {
+    let Some(mut limits) = state.asset_limits.get(&req.asset_id) else {
+        return Ok(());
+    };
+    let hour = bridge_limit_hour(now);
+    limits.advance_window(hour);
+    if let Some(code) = unwrap_dispatch_limit_error(&limits, req) {
+        return Err(code.to_string());
+    }
+    if req.transfer_created_at_time == 0 {
+        limits.record(BridgeDirection::Unwrap, hour, unwrap_limit_amount(req));
+        state.asset_limits.insert(req.asset_id.clone(), limits);
+    }
+    Ok(())
+}
+
 fn pop_next_icp_update_request(
     now: u64,
 ) -> Result<Option<(TxId, IcpUpdateDispatchRequest)>, String> {
@@ -5299,38 +8173,159 @@ fn quarantine_decode_failed_unwrap_requests(now: u64) -> (u64, u64) {
     out
 }
 
+thread_local! {
+    // ledger ごとの送金中の unwrap 数。tick をまたいで数え、finalize で減らす。
+    // heap のみで、upgrade 時には待っている呼び出しが無いので 0 から始めてよい。
+    static UNWRAP_DISPATCH_LEDGER_IN_FLIGHT: std::cell::RefCell<std::collections::BTreeMap<Vec<u8>, u64>> =
+        const { std::cell::RefCell::new(std::collections::BTreeMap::new()) };
+}
+
+fn unwrap_dispatch_ledger_in_flight(ledger_key: &[u8]) -> u64 {
+    UNWRAP_DISPATCH_LEDGER_IN_FLIGHT
+        .with(|in_flight| in_flight.borrow().get(ledger_key).copied().unwrap_or(0))
+}
+
+fn unwrap_dispatch_in_flight_total() -> u64 {
+    UNWRAP_DISPATCH_LEDGER_IN_FLIGHT.with(|in_flight| in_flight.borrow().values().sum())
+}
+
+fn raise_unwrap_dispatch_in_flight(ledger_key: Vec<u8>) {
+    UNWRAP_DISPATCH_LEDGER_IN_FLIGHT.with(|in_flight| {
+        *in_flight.borrow_mut().entry(ledger_key).or_insert(0) += 1;
+    });
+}
+
+fn lower_unwrap_dispatch_in_flight(ledger_key: &[u8]) {
+    UNWRAP_DISPATCH_LEDGER_IN_FLIGHT.with(|in_flight| {
+        let mut in_flight = in_flight.borrow_mut();
+        let Some(count) = in_flight.get_mut(ledger_key) else {
+            return;
+        };
+        *count = count.saturating_sub(1);
+        if *count == 0 {
+            in_flight.remove(ledger_key);
+        }
+    });
+}
+
 async fn unwrap_dispatch_tick() {
     UNWRAP_DISPATCH_SCHEDULED.store(false, Ordering::SeqCst);
-    loop {
-        let next = pop_next_dispatch_request(current_time_nanos());
-        let Some((request_id, req)) = (match next {
-            Ok(v) => v,
+    let batch = pop_next_dispatch_batch(current_time_nanos());
+    // 上限で残した request は、送金中の tick が終わったときの呼び直しで送る。
+    if batch.is_empty() && unwrap_dispatch_in_flight_total() > 0 {
+        return;
+    }
+    // 各 request は自分の呼び出しが返った時点で確定させ、遅い ledger を待たずに状態を残す。
+    futures::future::join_all(batch.into_iter().map(|(request_id, req)| async move {
+        let applied = dispatch_unwrap_request_internal(request_id, req).await;
+        finalize_unwrap_dispatch_attempt(request_id, current_time_nanos(), applied);
+    }))
+    .await;
+
+    if with_state(|state| !state.unwrap_dispatch_queue.is_empty()) {
+        schedule_unwrap_dispatch();
+    }
+}
+
+/// queue の先頭から最大 `UNWRAP_DISPATCH_BATCH_MAX` 件を見て、送金中の数が上限に達していない ledger の
+/// request だけを取り出す。上限の ledger の request は見た順のまま queue の先頭へ戻すので、
+/// ledger ごとの順序は崩れず、他の ledger の request はその後ろで待たない。
+fn pop_next_dispatch_batch(now: u64) -> Vec<(TxId, UnwrapDispatchRequest)> {
+    let mut batch = Vec::new();
+    let mut deferred = Vec::new();
+    let mut scanned = 0u64;
+    while scanned < UNWRAP_DISPATCH_BATCH_MAX {
+        let Some(ledger_key) = peek_next_dispatch_ledger_key() else {
+            break;
+        };
+        scanned += 1;
+        if !verified_core::unwrap_dispatch::unwrap_batch_admit_raw(
+            batch.len() as u64,
+            UNWRAP_DISPATCH_BATCH_MAX,
+            unwrap_dispatch_ledger_in_flight(&ledger_key),
+            UNWRAP_DISPATCH_LEDGER_IN_FLIGHT_MAX,
+        ) {
+            match defer_dispatch_queue_head() {
+                Some(request_id) => deferred.push(request_id),
+                None => break,
+            }
+            continue;
+        }
+        match pop_next_dispatch_request(now) {
+            Ok(Some((request_id, req))) => {
+                raise_unwrap_dispatch_in_flight(ledger_key);
+                batch.push((request_id, req));
+            }
+            Ok(None) => break,
             Err(err) => {
                 if err.starts_with("wrap.dispatch.quarantined:") {
                     warn!(error = err, "unwrap_dispatch_tick quarantined request");
+                } else if err.starts_with("wrap.dispatch.limited:") {
+                    warn!(
+                        error = err,
+                        "unwrap_dispatch_tick held request over asset limits"
+                    );
                 } else {
                     error!(
                         error = err,
                         "unwrap_dispatch_tick skipped corrupted queue entry"
                     );
                 }
-                continue;
             }
-        }) else {
-            break;
-        };
+        }
+    }
+    requeue_deferred_dispatch_at_head(&deferred);
+    batch
+}
 
-        finalize_unwrap_dispatch_attempt(
-            request_id,
-            current_time_nanos(),
-            dispatch_unwrap_request_internal(request_id, req).await,
-        );
+/// 上限で送らない先頭を状態を変えずに外す。同じ tick の中で `requeue_deferred_dispatch_at_head` が戻す。
+fn defer_dispatch_queue_head() -> Option<TxId> {
+    with_state_mut(|state| {
+        let mut meta = *state.unwrap_dispatch_meta.get();
+        let seq = meta.pop()?;
+        state.unwrap_dispatch_meta.set(meta);
+        state.unwrap_dispatch_queue.remove(&seq)
+    })
+}
 
-        if with_state(|state| !state.unwrap_dispatch_queue.is_empty()) {
-            schedule_unwrap_dispatch();
-        }
-        break;
+/// 外した request は直前まで先頭側の seq にあったので、その seq を詰め直して head を戻す。
+fn requeue_deferred_dispatch_at_head(deferred: &[TxId]) {
+    if deferred.is_empty() {
+        return;
     }
+    with_state_mut(|state| {
+        let mut meta = *state.unwrap_dispatch_meta.get();
+        meta.head = meta.head.saturating_sub(deferred.len() as u64);
+        state.unwrap_dispatch_meta.set(meta);
+        for (offset, request_id) in deferred.iter().enumerate() {
+            state
+                .unwrap_dispatch_queue
+                .insert(meta.head + offset as u64, *request_id);
+        }
+    });
+}
+
+// 壊れた先頭は空の key にして、pop 側の隔離処理へそのまま流す。
+fn peek_next_dispatch_ledger_key() -> Option<Vec<u8>> {
+    with_state(|state| {
+        let meta = *state.unwrap_dispatch_meta.get();
+        if meta.is_empty() {
+            return None;
+        }
+        let key = state
+            .unwrap_dispatch_queue
+            .get(&meta.head)
+            .and_then(|request_id| state.unwrap_requests.get(&request_id))
+            .map(|req| unwrap_dispatch_ledger_key(&req))
+            .unwrap_or_default();
+        Some(key)
+    })
+}
+
+fn unwrap_dispatch_ledger_key(req: &UnwrapDispatchRequest) -> Vec<u8> {
+    unwrap_dispatch_ledger(req)
+        .map(|ledger| ledger.as_slice().to_vec())
+        .unwrap_or_else(|_| req.asset_id.clone())
 }
 
 async fn icp_update_dispatch_tick() {
@@ -5462,6 +8457,16 @@ async fn wrap_worker_tick() {
     while let Some(request_id) = dequeue_wrap_request() {
         let req = with_state_mut(|state| {
             let mut req = state.wrap_requests.get(&request_id)?;
+            // 期限切れ返金に入った request は mint しない。
+            if req.result.withdraw_in_progress
+                || req.result.withdrawn
+                || matches!(
+                    req.result.stage,
+                    WrapRequestStage::Refunding | WrapRequestStage::Refunded
+                )
+            {
+                return None;
+            }
             req.result.status = StoredRequestStatus::Running;
             req.result.updated_at = current_time_nanos();
             state.wrap_requests.insert(request_id, req.clone());
@@ -5509,14 +8514,30 @@ async fn execute_wrap_request(
                 WrapRequestStage::PullPending,
                 StoredRequestStatus::Running,
             );
-            let pull = attempt_icrc2_transfer_from(
-                caller,
-                asset,
-                Nat(BigUint::from_bytes_be(&req.amount)),
-                request_memo(request_id, TransferMemoKind::Pull),
-                req.pull_created_at_time,
-            )
-            .await;
+            let amount = Nat(BigUint::from_bytes_be(&req.amount));
+            let memo = request_memo(request_id, TransferMemoKind::Pull);
+            let pull = match req.kind {
+                WrapAssetKind::Fungible => {
+                    attempt_icrc2_transfer_from(
+                        caller,
+                        asset,
+                        amount,
+                        memo,
+                        req.pull_created_at_time,
+                    )
+                    .await
+                }
+                WrapAssetKind::Nft => {
+                    attempt_icrc37_transfer_from(
+                        caller,
+                        asset,
+                        amount,
+                        memo,
+                        req.pull_created_at_time,
+                    )
+                    .await
+                }
+            };
             match pull {
                 Ok(tx_id) => {
                     if let Err(code) = record_wrap_pull_success(request_id, tx_id.clone()) {
@@ -5599,13 +8620,32 @@ async fn submit_mint_tx_internal(
         return Ok(tx_id);
     }
     let factory = expected_wrap_factory_address()?;
-    let token_decimals = fetch_asset_decimals(&req.asset_id).await?;
-    let data = encode_factory_mint_for_asset_call_data(
-        &req.asset_id,
-        token_decimals,
-        &req.evm_recipient,
-        &req.amount,
-    )?;
+    let data = match req.kind {
+        WrapAssetKind::Fungible => {
+            let mint_amount = wrap_mint_amount(req)?;
+            // deploy 済みなら decimals は token から読み、ledger の metadata は取りに行かない。
+            // 同じ asset の mint が並んでも、factory は deploy した呼び出しでだけ metadata を使う。
+            if let Some(token) = wrapped_token_address(&req.asset_id, &factory)? {
+                encode_factory_mint_for_asset_call_data(
+                    &req.asset_id,
+                    fetch_erc20_decimals(&token)?,
+                    &req.evm_recipient,
+                    &mint_amount,
+                )?
+            } else {
+                let metadata = fetch_wrapped_token_metadata(&req.asset_id).await?;
+                encode_factory_mint_for_asset_with_metadata_call_data(
+                    &req.asset_id,
+                    &deploy_token_metadata(metadata),
+                    &req.evm_recipient,
+                    &mint_amount,
+                )?
+            }
+        }
+        WrapAssetKind::Nft => {
+            encode_factory_mint_nft_call_data(&req.asset_id, &req.evm_recipient, &req.amount)?
+        }
+    };
     let wrap_evm = hash::derive_evm_address_from_principal(ic_cdk::api::canister_self().as_slice())
         .map_err(|_| "wrap.evm_address_derivation_failed".to_string())?;
     let nonce = req
@@ -5786,31 +8826,31 @@ fn native_withdraw_gross_transfer_amount(amount: &Nat, fee: u128) -> Result<Nat,
     gross_transfer_receive_amount(amount, fee, "native_withdraw")
 }
 
+/// 送金手数料は預かりから引かれるので受取額から差し引き、預かりの減少を burn した量に揃える。
+fn unwrap_gross_transfer_amount(amount: &Nat, fee: u128) -> Result<Nat, String> {
+    gross_transfer_receive_amount(amount, fee, "unwrap")
+}
+
+fn unwrap_dispatch_ledger(req: &UnwrapDispatchRequest) -> Result<Principal, String> {
+    if is_native_withdraw_dispatch_request(req) {
+        current_native_ledger_canister()
+    } else {
+        principal_from_stored_bytes(&req.asset_id)
+    }
+}
+
 async fn dispatch_unwrap_request_internal(
     request_id: TxId,
     req: UnwrapDispatchRequest,
 ) -> AppliedUnwrapDispatchOutcome {
-    let ledger = if is_native_withdraw_dispatch_request(&req) {
-        match current_native_ledger_canister() {
-            Ok(ledger) => ledger,
-            Err(code) => {
-                return AppliedUnwrapDispatchOutcome {
-                    status: UnwrapRequestStatus::DispatchFailed,
-                    ledger_tx_id: None,
-                    error_code: Some(code),
-                };
-            }
-        }
-    } else {
-        match principal_from_stored_bytes(&req.asset_id) {
-            Ok(ledger) => ledger,
-            Err(code) => {
-                return AppliedUnwrapDispatchOutcome {
-                    status: UnwrapRequestStatus::DispatchFailed,
-                    ledger_tx_id: None,
-                    error_code: Some(code),
-                };
-            }
+    let ledger = match unwrap_dispatch_ledger(&req) {
+        Ok(ledger) => ledger,
+        Err(code) => {
+            return AppliedUnwrapDispatchOutcome {
+                status: UnwrapRequestStatus::DispatchFailed,
+                ledger_tx_id: None,
+                error_code: Some(code),
+            };
         }
     };
     let recipient = match principal_from_stored_bytes(&req.recipient) {
@@ -5824,7 +8864,7 @@ async fn dispatch_unwrap_request_internal(
         }
     };
     let gross_amount = Nat(BigUint::from_bytes_be(&req.amount));
-    let amount = if is_native_withdraw_dispatch_request(&req) {
+    let amount = if req.kind == UnwrapAssetKind::Fungible {
         let fee = match fetch_icrc1_fee(ledger).await {
             Ok(fee) => fee,
             Err(code) => {
@@ -5835,7 +8875,12 @@ async fn dispatch_unwrap_request_internal(
                 };
             }
         };
-        match native_withdraw_gross_transfer_amount(&gross_amount, fee) {
+        let amount = if is_native_withdraw_dispatch_request(&req) {
+            native_withdraw_gross_transfer_amount(&gross_amount, fee)
+        } else {
+            unwrap_gross_transfer_amount(&gross_amount, fee)
+        };
+        match amount {
             Ok(amount) => amount,
             Err(code) => {
                 return AppliedUnwrapDispatchOutcome {
@@ -5848,28 +8893,55 @@ async fn dispatch_unwrap_request_internal(
     } else {
         gross_amount
     };
-    match attempt_icrc1_transfer(
-        ledger,
-        recipient,
-        amount,
-        request_memo(request_id, TransferMemoKind::Unwrap),
-        req.transfer_created_at_time,
-    )
-    .await
-    {
+    let memo = request_memo(request_id, TransferMemoKind::Unwrap);
+    let transfer = match req.kind {
+        UnwrapAssetKind::Fungible => {
+            attempt_icrc1_transfer(
+                ledger,
+                None,
+                recipient,
+                req.recipient_subaccount,
+                amount,
+                memo,
+                req.transfer_created_at_time,
+            )
+            .await
+        }
+        UnwrapAssetKind::Nft => {
+            attempt_icrc7_transfer(
+                ledger,
+                recipient,
+                req.recipient_subaccount,
+                amount,
+                memo,
+                req.transfer_created_at_time,
+            )
+            .await
+        }
+    };
+    match transfer {
         Ok(ledger_tx_id) => AppliedUnwrapDispatchOutcome {
             status: UnwrapRequestStatus::Dispatched,
             ledger_tx_id: Some(ledger_tx_id),
             error_code: None,
         },
         Err(code) => AppliedUnwrapDispatchOutcome {
-            status: UnwrapRequestStatus::DispatchFailed,
+            status: if unwrap_transfer_outcome_uncertain(&code) {
+                UnwrapRequestStatus::DispatchUncertain
+            } else {
+                UnwrapRequestStatus::DispatchFailed
+            },
             ledger_tx_id: None,
             error_code: Some(code),
         },
     }
 }
 
+/// ledger の応答が読めなかった送金は実行済みかもしれない。ledger が拒否を返したものだけを確定失敗にする。
+fn unwrap_transfer_outcome_uncertain(code: &str) -> bool {
+    code.starts_with("ledger.call_uncertain:") || code.starts_with("ledger.decode_failed:")
+}
+
 async fn dispatch_icp_update_request_internal(
     req: IcpUpdateDispatchRequest,
 ) -> AppliedIcpUpdateDispatchOutcome {
@@ -5889,7 +8961,7 @@ async fn dispatch_icp_update_request_internal(
     match response {
         Ok(response) => icp_update_success_outcome(response.into_bytes()),
         Err(err) => {
-            let uncertain = is_icp_update_uncertain_call_error(&err);
+            let uncertain = is_uncertain_call_error(&err);
             let detail = format!("{err}");
             let status = if uncertain {
                 IcpUpdateRequestStatus::DispatchUncertain
@@ -5924,7 +8996,7 @@ fn icp_update_envelope(req: &IcpUpdateDispatchRequest) -> IcpUpdateEnvelopeV1 {
     }
 }
 
-fn is_icp_update_uncertain_call_error(error: &CallFailed) -> bool {
+fn is_uncertain_call_error(error: &CallFailed) -> bool {
     // bounded_wait timeout is surfaced as SysUnknown. CallPerformFailed means the
     // request was not enqueued, so it remains a deterministic dispatch failure.
     matches!(
@@ -5954,10 +9026,9 @@ fn finalize_unwrap_dispatch_attempt(
     now: u64,
     applied: AppliedUnwrapDispatchOutcome,
 ) {
-    with_state_mut(|state| {
-        let Some(mut req) = state.unwrap_requests.get(&request_id) else {
-            return;
-        };
+    let ledger_key = with_state_mut(|state| {
+        let mut req = state.unwrap_requests.get(&request_id)?;
+        let ledger_key = unwrap_dispatch_ledger_key(&req);
         req.updated_at = now;
         req.ledger_tx_id = applied
             .ledger_tx_id
@@ -5965,7 +9036,11 @@ fn finalize_unwrap_dispatch_attempt(
         req.status = applied.status;
         req.error_code = applied.error_code.map(clamp_error_code);
         state.unwrap_requests.insert(request_id, req);
+        Some(ledger_key)
     });
+    if let Some(ledger_key) = ledger_key {
+        lower_unwrap_dispatch_in_flight(&ledger_key);
+    }
 }
 
 fn finalize_icp_update_dispatch_attempt(
@@ -6013,6 +9088,7 @@ fn unwrap_request_ids_for_tx(tx_id: &TxId) -> Vec<TxId> {
         .filter_map(|(log_index, log)| {
             if unwrap_intent_from_log(log).is_none()
                 && native_withdraw_intent_from_log(log).is_none()
+                && nft_unwrap_intent_from_log(log).is_none()
             {
                 return None;
             }
@@ -6027,6 +9103,7 @@ fn request_dispatch_status_to_view(status: UnwrapRequestStatus) -> RequestDispat
         UnwrapRequestStatus::Dispatching => RequestDispatchStatusView::Dispatching,
         UnwrapRequestStatus::Dispatched => RequestDispatchStatusView::Dispatched,
         UnwrapRequestStatus::DispatchFailed => RequestDispatchStatusView::DispatchFailed,
+        UnwrapRequestStatus::DispatchUncertain => RequestDispatchStatusView::DispatchUncertain,
     }
 }
 
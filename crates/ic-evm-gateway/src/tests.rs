@@ -7,9 +7,9 @@ use super::{
     tx_id_from_bytes, validate_prune_policy_input, validate_query_precompile_allow_args,
     validate_update_precompile_allow_args, ApiError, EthLogFilterView, ExecuteTxError,
     GenesisBalanceView, GetLogsErrorView, InitArgs, PrecompileAllowArgs, PrunePolicyView,
-    QuoteNativeDepositArgs, QuoteWrapRequestArgs, SubmitIcTxArgsDto, WrapConfigArgs,
-    DEFAULT_BLOCK_GAS_LIMIT, DEFAULT_MIN_FEE_FLOOR, INSPECT_METHOD_POLICIES, MINING_ERROR_COUNT,
-    PRUNE_ERROR_COUNT,
+    QuoteNativeDepositArgs, QuoteWrapRequestArgs, ScrubPhaseView, SubmitIcTxArgsDto,
+    WrapConfigArgs, DEFAULT_BLOCK_GAS_LIMIT, DEFAULT_MIN_FEE_FLOOR, INSPECT_METHOD_POLICIES,
+    MINING_ERROR_COUNT, PRUNE_ERROR_COUNT,
 };
 use candid::{encode_one, Nat, Principal};
 use evm_core::chain;
@@ -26,8 +26,9 @@ use evm_db::chain_data::receipt::log_entry_from_parts;
 use evm_db::chain_data::{
     BlockData, IcpUpdateDispatchRequest, IcpUpdateRequestStatus, MigrationPhase, MintSubmitStatus,
     OpsMode, ReceiptLike, RequestStatus, RuntimeConfigV1, StoredTxBytes, TxId, TxIndexEntry,
-    TxKind, TxLoc, TxLocKind, UnwrapDispatchRequest, UnwrapRequestStatus, WrapPendingSubmission,
-    WrapRequestResult, WrapRequestStage, WrapStoredRequest, WRAP_DECODE_FAILURE_CODE,
+    TxKind, TxLoc, TxLocKind, UnwrapAssetKind, UnwrapDispatchRequest, UnwrapRequestStatus,
+    WrapAssetKind, WrapPendingSubmission, WrapRequestResult, WrapRequestStage, WrapStoredRequest,
+    WRAP_DECODE_FAILURE_CODE,
 };
 use evm_db::memory::{get_memory, AppMemoryId, WASM_PAGE_SIZE_BYTES};
 use evm_db::meta::{
@@ -39,6 +40,7 @@ use evm_db::types::keys::{make_account_key, make_storage_key};
 use evm_db::types::values::{AccountVal, U256Val};
 use evm_db::{Memory, Storable};
 use ic_cdk::call::{CallFailed, CallPerformFailed, CallRejected, RejectCode};
+use ic_certification::LookupResult;
 use std::borrow::Cow;
 use std::collections::BTreeSet;
 use std::future::Future;
@@ -300,6 +302,48 @@ fn icrc21_submit_ic_tx_consent_decodes_precompile_unwrap() {
     assert!(markdown.contains(&asset.to_text()));
     assert!(markdown.contains(&recipient.to_text()));
     assert!(markdown.contains("amount_e8s: `42`"));
+    assert!(markdown.contains("recipient subaccount: `default`"));
+}
+
+#[test]
+fn icrc21_submit_ic_tx_consent_shows_native_withdraw_subaccount() {
+    let recipient = Principal::self_authenticating(b"withdraw-recipient");
+    let mut data = vec![2, recipient.as_slice().len() as u8];
+    data.extend_from_slice(recipient.as_slice());
+    data.resize(1 + 1 + 29, 0);
+    data.extend_from_slice(&[0xab; 32]);
+    let response = run_ready_future(super::icrc21::consent_message(
+        super::icrc21::Icrc21ConsentMessageRequest {
+            method: "submit_ic_tx".to_string(),
+            arg: encode_one(SubmitIcTxArgsDto {
+                to: Some(NATIVE_WITHDRAW_PRECOMPILE_ADDRESS.to_vec()),
+                value: Nat::from(10_000_000_000u64),
+                max_priority_fee_per_gas: Nat::from(2u8),
+                data,
+                from: None,
+                max_fee_per_gas: Nat::from(3u8),
+                nonce: 9,
+                gas_limit: 210_000,
+            })
+            .expect("encode submit_ic_tx"),
+            user_preferences: super::icrc21::Icrc21ConsentMessageSpec {
+                metadata: super::icrc21::Icrc21ConsentMessageMetadata {
+                    utc_offset_minutes: None,
+                    language: "en".to_string(),
+                },
+                device_spec: None,
+            },
+        },
+    ))
+    .expect("consent ok");
+    let super::icrc21::Icrc21ConsentMessage::GenericDisplayMessage(markdown) =
+        response.consent_message
+    else {
+        panic!("expected generic display");
+    };
+    assert!(markdown.contains("Approve Kasane native withdrawal"));
+    assert!(markdown.contains(&recipient.to_text()));
+    assert!(markdown.contains(&format!("recipient subaccount: `0x{}`", "ab".repeat(32))));
 }
 
 #[test]
@@ -558,6 +602,7 @@ fn receipt_to_eth_view(receipt: ReceiptLike) -> super::EthReceiptView {
                 log_index: u32::try_from(index).unwrap_or(u32::MAX),
             })
             .collect(),
+        revert_reason: None,
     }
 }
 
@@ -604,11 +649,13 @@ fn sample_unwrap_request(
         asset_id: vec![0x55u8; 10],
         amount: [0x66u8; 32],
         recipient: vec![0x77u8; 10],
+        recipient_subaccount: None,
         status,
         ledger_tx_id: None,
         error_code: error_code.map(str::to_string),
         updated_at,
         transfer_created_at_time: 0,
+        kind: UnwrapAssetKind::Fungible,
     }
 }
 
@@ -625,6 +672,9 @@ fn sample_wrap_request(status: RequestStatus) -> WrapStoredRequest {
         fee_created_at_time: 1,
         pull_created_at_time: 2,
         withdraw_created_at_time: 0,
+        fee_in_asset: false,
+        deadline_at: 0,
+        kind: WrapAssetKind::Fungible,
         result: WrapRequestResult {
             status,
             pull_ledger_tx_id: None,
@@ -643,6 +693,7 @@ fn sample_wrap_request(status: RequestStatus) -> WrapStoredRequest {
             mint_nonce: None,
             mint_submitted_at_time: 0,
             mint_submit_status: MintSubmitStatus::NotSubmitted,
+            refund_amount_e8s: None,
         },
     }
 }
@@ -1050,6 +1101,7 @@ fn migration_pending_does_not_advance_schema_migration_state() {
         last_error: 0,
         cursor_key_set: false,
         cursor_key: [0u8; 32],
+        migration_index: 0,
     });
 
     let before = schema_migration_state();
@@ -1239,6 +1291,145 @@ fn pending_wrap_submission_replaces_decode_failure_placeholder() {
     assert_eq!(stored.request_id, request_id.0);
 }
 
+#[test]
+fn deposit_sweep_resumes_in_flight_request_and_skips_used_ids() {
+    init_stable_state();
+    let account_key = super::deposit_account_key(b"deposit-asset", &[0x42; 20]);
+    let taken = super::derive_deposit_request_id(account_key, 0);
+    with_state_mut(|state| {
+        state
+            .wrap_requests
+            .insert(taken, sample_wrap_request(RequestStatus::Succeeded));
+    });
+
+    let first = super::begin_deposit_sweep(account_key, 500);
+    let resumed = super::begin_deposit_sweep(account_key, 900);
+
+    assert_eq!(
+        first.request_id,
+        super::derive_deposit_request_id(account_key, 1).0
+    );
+    assert_eq!(resumed, first);
+    let account = with_state(|state| state.deposit_accounts.get(&account_key)).expect("account");
+    assert_eq!(account.next_seq, 2);
+    assert_eq!(account.in_flight, Some(first));
+}
+
+#[test]
+fn deposit_sweep_keeps_uncertain_transfer_and_releases_definite_failure() {
+    init_stable_state();
+    let wrap = Principal::self_authenticating(b"wrap-deposit");
+    install_runtime_wrap_canister_id(wrap);
+    let asset = Principal::self_authenticating(b"deposit-asset");
+    let account_key = super::deposit_account_key(asset.as_slice(), &[0x42; 20]);
+    let target = || super::DepositWrapTarget {
+        owner: wrap,
+        asset_id: asset,
+        evm_recipient: vec![0x42; 20],
+        charged_gas_price_wei: 8,
+        charged_fee_e8s: 20,
+    };
+    let sweep = super::begin_deposit_sweep(account_key, 500);
+
+    let err = super::finish_deposit_sweep(
+        account_key,
+        sweep,
+        target(),
+        Err("ledger.call_failed:timeout".to_string()),
+    )
+    .expect_err("uncertain");
+    assert_eq!(err, "ledger.call_failed:timeout");
+    assert_eq!(
+        super::deposit_in_flight_sweep(account_key),
+        Some(sweep),
+        "uncertain sweep must be retried with the same request"
+    );
+    assert!(with_state(|state| state.wrap_requests.get(&TxId(sweep.request_id))).is_none());
+
+    super::finish_deposit_sweep(
+        account_key,
+        sweep,
+        target(),
+        Err("ledger.transfer_failed:insufficient_funds".to_string()),
+    )
+    .expect_err("definite");
+    assert_eq!(super::deposit_in_flight_sweep(account_key), None);
+    let next = super::begin_deposit_sweep(account_key, 700);
+    assert_ne!(next.request_id, sweep.request_id);
+}
+
+#[test]
+fn deposit_sweep_success_queues_pulled_wrap_once() {
+    init_stable_state();
+    let wrap = Principal::self_authenticating(b"wrap-deposit");
+    install_runtime_wrap_canister_id(wrap);
+    let asset = Principal::self_authenticating(b"deposit-asset");
+    let recipient = vec![0x42; 20];
+    let account_key = super::deposit_account_key(asset.as_slice(), &recipient);
+    let sweep = super::begin_deposit_sweep(account_key, 500);
+    let target = || super::DepositWrapTarget {
+        owner: wrap,
+        asset_id: asset,
+        evm_recipient: recipient.clone(),
+        charged_gas_price_wei: 8,
+        charged_fee_e8s: 20,
+    };
+
+    let tx_id = super::finish_deposit_sweep(account_key, sweep, target(), Ok(vec![9]))
+        .expect("sweep recorded");
+    assert_eq!(tx_id, vec![9]);
+    super::finish_deposit_sweep(account_key, sweep, target(), Ok(vec![9])).expect("replayed sweep");
+
+    let request_id = TxId(sweep.request_id);
+    let req = with_state(|state| state.wrap_requests.get(&request_id)).expect("wrap request");
+    assert_eq!(req.caller, wrap.as_slice());
+    assert_eq!(req.amount, super::u256_from_u128(500).to_vec());
+    assert_eq!(req.result.pull_ledger_tx_id, Some(vec![9]));
+    assert_eq!(req.result.stage, WrapRequestStage::Pulled);
+    assert_eq!(req.result.charged_fee_e8s, Some(20));
+    assert!(req.fee_in_asset);
+    assert_eq!(
+        super::wrap_mint_amount(&req).expect("mint amount"),
+        super::u256_from_u128(480).to_vec()
+    );
+    assert_eq!(req.pull_created_at_time, sweep.created_at_time);
+    with_state(|state| {
+        assert_eq!(state.wrap_queue.len(), 1);
+        assert_eq!(state.wrap_queue.get(&0), Some(request_id));
+    });
+    assert_eq!(super::deposit_in_flight_sweep(account_key), None);
+    assert_eq!(
+        super::wrap_refund_account(&req).expect("refund account"),
+        (wrap, Some(super::deposit_subaccount(&recipient)))
+    );
+}
+
+#[test]
+fn deposit_wrap_fee_applies_schedule_in_asset_without_gas() {
+    let schedule = super::WrapFeeSchedule {
+        fee_ledger_canister: Principal::self_authenticating(b"fee-ledger"),
+        cycle_fee_e8s: 10,
+        gas_price_buffer_bps: 12_000,
+        fee_in_asset: false,
+        tiers: vec![evm_db::chain_data::FeeTierStored {
+            min_amount_e8s: 1_000,
+            fee_bps: 100,
+        }],
+    };
+    assert_eq!(
+        super::deposit_wrap_fee_e8s(&schedule, 500).expect("no tier"),
+        10
+    );
+    assert_eq!(
+        super::deposit_wrap_fee_e8s(&schedule, 2_000).expect("tier"),
+        30
+    );
+    assert!(matches!(
+        super::deposit_wrap_fee_e8s(&schedule, 10),
+        Err(super::ApiError::Rejected(detail)) if detail.code == "fee.exceeds_amount"
+    ));
+}
+
 #[test]
 fn recover_wrap_worker_after_upgrade_requeues_active_requests_without_duplicates() {
     init_stable_state();
@@ -1551,7 +1742,10 @@ fn assert_included_receipt_index_location_links(tx_id: TxId, block_number: u64,
         assert_eq!(index.tx_index, tx_index);
 
         let receipt_ptr = state.receipts.get(&tx_id).expect("receipt ptr");
-        let receipt_bytes = state.blob_store.read(&receipt_ptr).expect("receipt bytes");
+        let receipt_bytes = state
+            .blob_store
+            .read_decoded(&receipt_ptr)
+            .expect("receipt bytes");
         let receipt = ReceiptLike::from_bytes(Cow::Owned(receipt_bytes));
         assert_eq!(receipt.tx_id, tx_id);
         assert_eq!(receipt.block_number, block_number);
@@ -1951,6 +2145,101 @@ fn get_ops_status_reports_error_counters() {
     assert!(view.prune_error_count >= before_prune.saturating_add(3));
 }
 
+#[test]
+fn get_ops_status_reports_schema_migration_progress() {
+    init_stable_state();
+    set_schema_migration_state(SchemaMigrationState {
+        phase: SchemaMigrationPhase::Rewrite,
+        cursor: 42,
+        from_version: 4,
+        migration_index: 1,
+        ..SchemaMigrationState::done()
+    });
+    let view = super::get_ops_status().schema_migration;
+    assert_eq!(view.phase, "rewrite");
+    assert_eq!(view.from_version, 4);
+    assert_eq!(view.to_version, current_schema_version());
+    assert_eq!(view.migration_id.as_deref(), Some("eth_tx_hash_index"));
+    assert_eq!(view.migration_to_version, Some(5));
+    assert_eq!(view.processed, 42);
+    assert!(view.rollback.is_some());
+
+    set_schema_migration_state(SchemaMigrationState::done());
+    let view = super::get_ops_status().schema_migration;
+    assert_eq!(view.phase, "done");
+    assert_eq!(view.migration_id, None);
+}
+
+#[test]
+fn scrub_queries_report_findings_with_labels() {
+    init_stable_state();
+    with_state_mut(|state| {
+        state.wrap_queue.insert(5, TxId([0x33; 32]));
+    });
+    evm_core::scrub::start_scrub(false, 7);
+    let status = super::get_scrub_status();
+    assert_eq!(status.phase, ScrubPhaseView::Running);
+    assert_eq!(status.target, "blocks");
+    assert_eq!(
+        super::scrub_error_to_string(evm_core::scrub::scrub_tick(0, 8).expect_err("zero budget")),
+        "input.scrub.max_ops.non_positive"
+    );
+    evm_core::scrub::scrub_tick(64, 8).expect("tick");
+
+    let status = super::get_scrub_status();
+    assert_eq!(status.phase, ScrubPhaseView::Idle);
+    assert_eq!(status.findings, 1);
+    assert_eq!(status.repaired, 0);
+    let findings = super::get_scrub_findings(None, u32::MAX);
+    assert_eq!(findings.len(), 1);
+    assert_eq!(findings[0].target, "wrap_queue");
+    assert_eq!(findings[0].kind, "dangling_queue_entry");
+    assert!(!findings[0].repaired);
+    assert!(super::get_scrub_findings(Some(findings[0].seq), 10).is_empty());
+    assert_eq!(
+        super::scrub_error_to_string(evm_core::scrub::set_scrub_paused(true).expect_err("idle")),
+        "scrub.not_active"
+    );
+}
+
+#[test]
+fn upgrade_checkpoint_carries_volatile_state_through_stable_memory() {
+    init_stable_state();
+    chain::restore_caller_evm_cache(vec![(vec![0x01, 0x02], [0x11; 20])]);
+    chain::restore_decode_suppress(vec![(vec![0x03], 1_700_000_000)]);
+    let mut checkpoint = super::upgrade_checkpoint();
+    assert_eq!(
+        checkpoint.schema_version,
+        evm_db::meta::get_meta().schema_version
+    );
+    checkpoint.wrap_worker_scheduled = true;
+    evm_db::upgrade::pre_upgrade(&checkpoint);
+
+    // 新しい wasm の heap は空から始まる。
+    chain::restore_caller_evm_cache(Vec::new());
+    chain::restore_decode_suppress(Vec::new());
+    let restored = evm_db::upgrade::post_upgrade(current_schema_version())
+        .expect("accepted")
+        .expect("checkpoint");
+    let wakeups = super::restore_volatile_state_after_upgrade(restored);
+    assert!(wakeups.wrap_worker);
+    assert_eq!(
+        chain::caller_evm_cache_entries(),
+        vec![(vec![0x01, 0x02], [0x11; 20])]
+    );
+    assert_eq!(
+        chain::decode_suppress_entries(),
+        vec![(vec![0x03], 1_700_000_000)]
+    );
+    assert_eq!(
+        evm_db::upgrade::post_upgrade(checkpoint.schema_version.saturating_sub(1)),
+        Err(evm_db::upgrade::UpgradeError::SchemaDowngrade {
+            stored: checkpoint.schema_version,
+            supported: checkpoint.schema_version.saturating_sub(1),
+        })
+    );
+}
+
 #[test]
 fn health_and_ops_status_expose_block_gas_limit() {
     init_stable_state();
@@ -2220,6 +2509,105 @@ fn quote_wrap_request_allowed_asset_uses_floor_when_fee_sample_missing() {
     assert_eq!(out.fee_ledger_canister, fee_ledger);
 }
 
+#[test]
+fn quote_wrap_request_applies_asset_tier_and_fee_in_asset() {
+    init_stable_state();
+    let fee_ledger = Principal::self_authenticating(b"fee-ledger");
+    let allowed_asset = Principal::self_authenticating(b"asset-ledger");
+    super::apply_wrap_config_from_init_args(&InitArgs {
+        genesis_balances: vec![GenesisBalanceView {
+            address: vec![0x33u8; 20],
+            amount: 1,
+        }],
+        wrap_canister_id: Principal::self_authenticating(b"wrap-integrated"),
+        wrap_factory_address: vec![0x44u8; 20],
+        wrap_config: Some(WrapConfigArgs {
+            fee_ledger_canister: fee_ledger,
+            native_ledger_canister: Principal::self_authenticating(b"native-ledger"),
+            cycle_fee_e8s: 1_000_000,
+            gas_price_buffer_bps: 12_000,
+            allowed_assets: vec![allowed_asset],
+        }),
+        query_instruction_soft_limit: None,
+        update_instruction_soft_limit: None,
+    });
+    let schedule = |fee_in_asset: bool| super::SetAssetFeeScheduleArgs {
+        asset_id: allowed_asset,
+        fee_ledger_canister: (!fee_in_asset).then_some(fee_ledger),
+        cycle_fee_e8s: 2_000,
+        gas_price_buffer_bps: 10_000,
+        fee_in_asset,
+        tiers: vec![
+            super::FeeTierView {
+                min_amount_e8s: Nat::from(0u8),
+                fee_bps: 10,
+            },
+            super::FeeTierView {
+                min_amount_e8s: Nat::from(50_000_000u64),
+                fee_bps: 5,
+            },
+        ],
+    };
+    let quote = |amount: u128| {
+        super::quote_wrap_request(QuoteWrapRequestArgs {
+            asset_id: allowed_asset,
+            amount_e8s: Nat::from(amount),
+            evm_recipient: vec![0x55; 20],
+            gas_limit: 21_000,
+        })
+    };
+    let install = |args: super::SetAssetFeeScheduleArgs| {
+        let tiers = super::validate_asset_fee_schedule(&args).expect("valid schedule");
+        with_state_mut(|state| {
+            state.asset_fee_schedules.insert(
+                allowed_asset.as_slice().to_vec(),
+                evm_db::chain_data::AssetFeeScheduleStored {
+                    fee_ledger_canister: args
+                        .fee_ledger_canister
+                        .map(|ledger| ledger.as_slice().to_vec())
+                        .unwrap_or_default(),
+                    cycle_fee_e8s: args.cycle_fee_e8s,
+                    gas_price_buffer_bps: args.gas_price_buffer_bps,
+                    fee_in_asset: args.fee_in_asset,
+                    tiers,
+                },
+            );
+        });
+    };
+
+    install(schedule(false));
+    let out = quote(100_000_000).expect("tiered quote");
+    assert_eq!(out.tier_fee_bps, 5);
+    assert_eq!(out.charged_gas_price_wei, Nat::from(150_000_000_000u128));
+    assert_eq!(out.charged_fee_e8s, Nat::from(315_000u128 + 2_000 + 50_000));
+    assert_eq!(out.mint_amount_e8s, Nat::from(100_000_000u128));
+    assert_eq!(quote(1_000).expect("low tier").tier_fee_bps, 10);
+
+    install(schedule(true));
+    let out = quote(100_000_000).expect("in-asset quote");
+    assert!(out.fee_in_asset);
+    assert_eq!(out.fee_ledger_canister, allowed_asset);
+    assert_eq!(out.charged_fee_e8s, Nat::from(52_000u128));
+    assert_eq!(out.mint_amount_e8s, Nat::from(99_948_000u128));
+    match quote(2_000) {
+        Err(ApiError::Rejected(detail)) => assert_eq!(detail.code, "fee.exceeds_amount"),
+        other => panic!("unexpected quote result: {other:?}"),
+    }
+
+    let mut unordered = schedule(false);
+    unordered.tiers.reverse();
+    assert_eq!(
+        super::validate_asset_fee_schedule(&unordered).map(|_| ()),
+        Err("arg.fee_tiers_unordered".to_string())
+    );
+    let mut ledger_missing = schedule(false);
+    ledger_missing.fee_ledger_canister = None;
+    assert_eq!(
+        super::validate_asset_fee_schedule(&ledger_missing).map(|_| ()),
+        Err("arg.fee_ledger_required".to_string())
+    );
+}
+
 #[test]
 fn quote_native_deposit_uses_integrated_fee_policy() {
     init_stable_state();
@@ -2283,7 +2671,7 @@ fn native_deposit_funding_validation_stops_before_pending_when_native_unconfigur
             });
     });
 
-    let out = super::prepare_native_deposit_funding(fee_ledger, 1);
+    let out = super::prepare_native_deposit_funding(fee_ledger, 1, 10);
 
     match out {
         Err(ApiError::Internal(detail)) => assert_eq!(detail.code, "wrap_config.unconfigured"),
@@ -2346,6 +2734,137 @@ fn factory_mint_for_asset_call_data_matches_abi_layout() {
     assert!(data[164 + asset_len..].iter().all(|byte| *byte == 0));
 }
 
+#[test]
+fn decode_wrapped_token_metadata_clamps_text_and_drops_oversized_logo() {
+    use super::Icrc1MetadataValue;
+    let metadata = vec![
+        (
+            "icrc1:decimals".to_string(),
+            Icrc1MetadataValue::Nat(Nat::from(6u8)),
+        ),
+        (
+            "icrc1:name".to_string(),
+            Icrc1MetadataValue::Text("あ".repeat(30)),
+        ),
+        (
+            "icrc1:symbol".to_string(),
+            Icrc1MetadataValue::Text("ckUSDC".to_string()),
+        ),
+        (
+            "icrc1:logo".to_string(),
+            Icrc1MetadataValue::Text("x".repeat(super::WRAPPED_TOKEN_LOGO_MAX_BYTES + 1)),
+        ),
+        (
+            "icrc1:fee".to_string(),
+            Icrc1MetadataValue::Nat(Nat::from(10_000u32)),
+        ),
+    ];
+
+    let decoded = super::decode_wrapped_token_metadata(&metadata).expect("decode");
+    assert_eq!(decoded.decimals, 6);
+    // 3 byte の文字を途中で切らず、上限以下に収める。
+    assert_eq!(decoded.name, "あ".repeat(21));
+    assert_eq!(decoded.symbol, "ckUSDC");
+    assert_eq!(decoded.logo, "");
+    assert_eq!(decoded.ledger_fee, 10_000);
+
+    assert_eq!(
+        super::decode_wrapped_token_metadata(&metadata[1..]),
+        Err("wrap.asset_metadata_failed:decimals_missing".to_string())
+    );
+}
+
+#[test]
+fn factory_metadata_call_data_matches_abi_layout() {
+    let asset = Principal::self_authenticating(b"asset-ledger");
+    let asset_len = asset.as_slice().len();
+    let asset_tail_len = 32 + asset_len.div_ceil(32) * 32;
+    let metadata = super::WrappedTokenMetadata {
+        decimals: 8,
+        name: "Wrapped Token With A Long Display Name".to_string(),
+        symbol: "WTK".to_string(),
+        logo: String::new(),
+        ledger_fee: 10_000,
+    };
+
+    let data = super::encode_factory_set_asset_metadata_call_data(asset.as_slice(), &metadata)
+        .expect("set metadata call data");
+    assert_eq!(
+        data[..4],
+        super::selector(b"setAssetMetadata(bytes,(string,string,string,uint256))")
+    );
+    assert_eq!(data[4..36], super::u256_from_u64(64));
+    assert_eq!(
+        data[36..68],
+        super::u256_from_u64(64 + asset_tail_len as u64)
+    );
+    assert_eq!(data[68..100], super::u256_from_u64(asset_len as u64));
+    let tuple = &data[4 + 64 + asset_tail_len..];
+    assert_eq!(tuple[0..32], super::u256_from_u64(128));
+    // name は 38 byte なので 2 word に詰め、symbol はその後ろに続く。
+    assert_eq!(tuple[32..64], super::u256_from_u64(128 + 32 + 64));
+    assert_eq!(tuple[64..96], super::u256_from_u64(128 + 32 + 64 + 64));
+    assert_eq!(tuple[96..128], super::u256_from_u128(10_000));
+    assert_eq!(tuple[128..160], super::u256_from_u64(38));
+    assert_eq!(&tuple[160..198], metadata.name.as_bytes());
+    assert_eq!(tuple[224..256], super::u256_from_u64(3));
+    assert_eq!(&tuple[256..259], b"WTK");
+    assert_eq!(tuple[288..320], super::u256_from_u64(0));
+    assert_eq!(tuple.len(), 320);
+
+    let recipient = vec![0x55; 20];
+    let amount = vec![0xabu8; 32];
+    let data = super::encode_factory_mint_for_asset_with_metadata_call_data(
+        asset.as_slice(),
+        &metadata,
+        recipient.as_slice(),
+        amount.as_slice(),
+    )
+    .expect("mint call data");
+    assert_eq!(
+        data[..4],
+        super::selector(
+            b"mintForAssetWithMetadata(bytes,uint8,address,uint256,(string,string,string,uint256))"
+        )
+    );
+    assert_eq!(data[4..36], super::u256_from_u64(160));
+    assert_eq!(data[36..68], super::u256_from_u64(8));
+    assert_eq!(data[80..100], *recipient.as_slice());
+    assert_eq!(data[100..132], *amount.as_slice());
+    assert_eq!(
+        data[132..164],
+        super::u256_from_u64(160 + asset_tail_len as u64)
+    );
+    assert_eq!(data[164..196], super::u256_from_u64(asset_len as u64));
+    assert_eq!(&data[164 + asset_tail_len..], tuple);
+}
+
+#[test]
+fn deploy_token_metadata_leaves_logo_for_sync() {
+    let metadata = super::WrappedTokenMetadata {
+        decimals: 8,
+        name: "Wrapped Token".to_string(),
+        symbol: "WTK".to_string(),
+        logo: "x".repeat(super::WRAPPED_TOKEN_LOGO_MAX_BYTES),
+        ledger_fee: 10_000,
+    };
+
+    let deployed = super::deploy_token_metadata(metadata.clone());
+
+    assert_eq!(deployed.logo, "");
+    assert_eq!(
+        deployed,
+        super::WrappedTokenMetadata {
+            logo: String::new(),
+            ..metadata.clone()
+        }
+    );
+    assert!(
+        super::wrap_metadata_sync_gas_limit(&metadata)
+            > super::wrap_metadata_sync_gas_limit(&deployed)
+    );
+}
+
 #[test]
 fn get_request_returns_wrap_overview_from_integrated_state() {
     init_stable_state();
@@ -2365,6 +2884,9 @@ fn get_request_returns_wrap_overview_from_integrated_state() {
                 fee_created_at_time: 1,
                 pull_created_at_time: 2,
                 withdraw_created_at_time: 0,
+                fee_in_asset: false,
+                deadline_at: 0,
+                kind: WrapAssetKind::Fungible,
                 result: WrapRequestResult {
                     status: RequestStatus::Succeeded,
                     pull_ledger_tx_id: Some(vec![4]),
@@ -2383,6 +2905,7 @@ fn get_request_returns_wrap_overview_from_integrated_state() {
                     mint_nonce: None,
                     mint_submitted_at_time: 0,
                     mint_submit_status: MintSubmitStatus::NotSubmitted,
+                    refund_amount_e8s: None,
                 },
             },
         );
@@ -2418,6 +2941,9 @@ fn existing_native_deposit_response_is_idempotent_for_same_request() {
                 fee_created_at_time: 1,
                 pull_created_at_time: 2,
                 withdraw_created_at_time: 0,
+                fee_in_asset: false,
+                deadline_at: 0,
+                kind: WrapAssetKind::Fungible,
                 result: WrapRequestResult {
                     status: RequestStatus::Succeeded,
                     pull_ledger_tx_id: Some(vec![4]),
@@ -2436,6 +2962,7 @@ fn existing_native_deposit_response_is_idempotent_for_same_request() {
                     mint_nonce: None,
                     mint_submitted_at_time: 0,
                     mint_submit_status: MintSubmitStatus::NotSubmitted,
+                    refund_amount_e8s: None,
                 },
             },
         );
@@ -2469,6 +2996,9 @@ fn existing_native_deposit_response_rejects_idempotency_mismatch() {
                 fee_created_at_time: 1,
                 pull_created_at_time: 2,
                 withdraw_created_at_time: 0,
+                fee_in_asset: false,
+                deadline_at: 0,
+                kind: WrapAssetKind::Fungible,
                 result: WrapRequestResult {
                     status: RequestStatus::Succeeded,
                     pull_ledger_tx_id: Some(vec![4]),
@@ -2487,6 +3017,7 @@ fn existing_native_deposit_response_rejects_idempotency_mismatch() {
                     mint_nonce: None,
                     mint_submitted_at_time: 0,
                     mint_submit_status: MintSubmitStatus::NotSubmitted,
+                    refund_amount_e8s: None,
                 },
             },
         );
@@ -2509,6 +3040,7 @@ fn ensure_wrap_request_before_fee_saves_quote_then_enqueues_after_fee() {
     let request_id = TxId([0xacu8; 32]);
     let args = super::NormalizedSubmitWrapRequest {
         request_id,
+        kind: WrapAssetKind::Fungible,
         asset_id: vec![1],
         amount: vec![0x11; 32],
         evm_recipient: vec![0x55; 20],
@@ -2516,9 +3048,11 @@ fn ensure_wrap_request_before_fee_saves_quote_then_enqueues_after_fee() {
         max_fee_e8s: 100,
         quoted_gas_price_wei: 200,
         fee_ledger_canister: Principal::self_authenticating(b"fee-ledger"),
+        deadline_secs: super::WRAP_DEADLINE_DEFAULT_SECS,
     };
 
-    super::ensure_wrap_request_before_fee(args, caller, 7, 8).expect("insert");
+    super::ensure_wrap_request_before_fee(args, caller, 7, 8, false, super::current_time_nanos())
+        .expect("insert");
 
     with_state(|state| {
         let req = state.wrap_requests.get(&request_id).expect("request");
@@ -2554,6 +3088,7 @@ fn existing_wrap_request_response_is_idempotent_for_same_request() {
     let request_id = TxId([0xadu8; 32]);
     let args = super::NormalizedSubmitWrapRequest {
         request_id,
+        kind: WrapAssetKind::Fungible,
         asset_id: vec![1],
         amount: vec![0x11; 32],
         evm_recipient: vec![0x55; 20],
@@ -2561,11 +3096,14 @@ fn existing_wrap_request_response_is_idempotent_for_same_request() {
         max_fee_e8s: 100,
         quoted_gas_price_wei: 200,
         fee_ledger_canister: fee_ledger,
+        deadline_secs: super::WRAP_DEADLINE_DEFAULT_SECS,
     };
-    super::ensure_wrap_request_before_fee(args, caller, 7, 8).expect("insert");
+    super::ensure_wrap_request_before_fee(args, caller, 7, 8, false, super::current_time_nanos())
+        .expect("insert");
     super::record_wrap_fee_collected(request_id, vec![9]).expect("fee");
     let retry_args = super::NormalizedSubmitWrapRequest {
         request_id,
+        kind: WrapAssetKind::Fungible,
         asset_id: vec![1],
         amount: vec![0x11; 32],
         evm_recipient: vec![0x55; 20],
@@ -2573,6 +3111,7 @@ fn existing_wrap_request_response_is_idempotent_for_same_request() {
         max_fee_e8s: 100,
         quoted_gas_price_wei: 200,
         fee_ledger_canister: fee_ledger,
+        deadline_secs: super::WRAP_DEADLINE_DEFAULT_SECS,
     };
 
     let out = super::existing_wrap_request_response(&retry_args, caller)
@@ -2593,6 +3132,7 @@ fn existing_wrap_request_response_rejects_idempotency_mismatch() {
     let request_id = TxId([0xaeu8; 32]);
     let args = super::NormalizedSubmitWrapRequest {
         request_id,
+        kind: WrapAssetKind::Fungible,
         asset_id: vec![1],
         amount: vec![0x11; 32],
         evm_recipient: vec![0x55; 20],
@@ -2600,11 +3140,14 @@ fn existing_wrap_request_response_rejects_idempotency_mismatch() {
         max_fee_e8s: 100,
         quoted_gas_price_wei: 200,
         fee_ledger_canister: fee_ledger,
+        deadline_secs: super::WRAP_DEADLINE_DEFAULT_SECS,
     };
-    super::ensure_wrap_request_before_fee(args, caller, 7, 8).expect("insert");
+    super::ensure_wrap_request_before_fee(args, caller, 7, 8, false, super::current_time_nanos())
+        .expect("insert");
     super::record_wrap_fee_collected(request_id, vec![9]).expect("fee");
     let retry_args = super::NormalizedSubmitWrapRequest {
         request_id,
+        kind: WrapAssetKind::Fungible,
         asset_id: vec![1],
         amount: vec![0x22; 32],
         evm_recipient: vec![0x55; 20],
@@ -2612,6 +3155,7 @@ fn existing_wrap_request_response_rejects_idempotency_mismatch() {
         max_fee_e8s: 100,
         quoted_gas_price_wei: 200,
         fee_ledger_canister: fee_ledger,
+        deadline_secs: super::WRAP_DEADLINE_DEFAULT_SECS,
     };
 
     let out = super::existing_wrap_request_response(&retry_args, caller).expect("existing");
@@ -2648,11 +3192,13 @@ fn get_request_returns_unwrap_dispatch_overview_from_integrated_state() {
                 asset_id: vec![1],
                 amount: [0; 32],
                 recipient: vec![2],
+                recipient_subaccount: None,
                 status: UnwrapRequestStatus::DispatchFailed,
                 ledger_tx_id: None,
                 error_code: Some("dispatch.failed".to_string()),
                 updated_at: 1,
                 transfer_created_at_time: 0,
+                kind: UnwrapAssetKind::Fungible,
             },
         );
     });
@@ -2672,7 +3218,7 @@ fn insert_unwrap_dispatch_request_enqueues_integrated_request() {
     init_stable_state();
     let request_id = TxId([0xee; 32]);
 
-    super::insert_unwrap_dispatch_request(request_id, vec![1, 2], [0x11; 32], vec![3, 4])
+    super::insert_unwrap_dispatch_request(request_id, vec![1, 2], [0x11; 32], vec![3, 4], None)
         .expect("insert");
 
     with_state(|state| {
@@ -2689,12 +3235,27 @@ fn insert_unwrap_dispatch_request_enqueues_integrated_request() {
 fn insert_unwrap_dispatch_request_rejects_idempotency_mismatch() {
     init_stable_state();
     let request_id = TxId([0xef; 32]);
-    super::insert_unwrap_dispatch_request(request_id, vec![1], [0x11; 32], vec![3])
+    super::insert_unwrap_dispatch_request(request_id, vec![1], [0x11; 32], vec![3], None)
         .expect("insert");
 
-    let err = super::insert_unwrap_dispatch_request(request_id, vec![2], [0x11; 32], vec![3])
+    let err = super::insert_unwrap_dispatch_request(request_id, vec![2], [0x11; 32], vec![3], None)
         .expect_err("mismatch");
     assert_eq!(err, "request.idempotency_mismatch");
+
+    // 宛先 subaccount の違いも別 request として扱う。全 0 は既定口座と同じ。
+    let zero = super::parse_recipient_subaccount(Some(&[0u8; 32])).expect("zero");
+    assert_eq!(zero, None);
+    super::insert_unwrap_dispatch_request(request_id, vec![1], [0x11; 32], vec![3], zero)
+        .expect("same request");
+    let subaccount = super::parse_recipient_subaccount(Some(&[7u8; 32])).expect("subaccount");
+    let err =
+        super::insert_unwrap_dispatch_request(request_id, vec![1], [0x11; 32], vec![3], subaccount)
+            .expect_err("mismatch");
+    assert_eq!(err, "request.idempotency_mismatch");
+    assert_eq!(
+        super::parse_recipient_subaccount(Some(&[7u8; 31])),
+        Err("arg.recipient_subaccount_invalid".to_string())
+    );
 }
 
 #[test]
@@ -2708,11 +3269,13 @@ fn retry_unwrap_dispatch_requeues_failed_request() {
                 asset_id: vec![1],
                 amount: [0x11; 32],
                 recipient: vec![2],
+                recipient_subaccount: None,
                 status: UnwrapRequestStatus::DispatchFailed,
                 ledger_tx_id: None,
                 error_code: Some("dispatch.failed".to_string()),
                 updated_at: 1,
                 transfer_created_at_time: 0,
+                kind: UnwrapAssetKind::Fungible,
             },
         );
     });
@@ -2741,11 +3304,13 @@ fn internal_unwrap_dispatch_fails_invalid_integrated_ledger_without_call() {
         recipient: Principal::self_authenticating(b"recipient")
             .as_slice()
             .to_vec(),
+        recipient_subaccount: None,
         status: UnwrapRequestStatus::Dispatching,
         ledger_tx_id: None,
         error_code: None,
         updated_at: 1,
         transfer_created_at_time: 1,
+        kind: UnwrapAssetKind::Fungible,
     };
 
     let out = run_ready_future(super::dispatch_unwrap_request_internal(request_id, req));
@@ -2940,45 +3505,242 @@ fn get_block_returns_pruned_for_pruned_boundary() {
 }
 
 #[test]
-fn get_block_returns_ok_when_prune_boundary_is_absent() {
+fn pruned_lookups_redirect_to_archive_canister() {
     init_stable_state();
+    let archive = Principal::from_slice(&[0x0a; 10]);
+    evm_core::archive::set_archive_canister(Some(archive.as_slice().to_vec()))
+        .expect("configure archive");
     with_state_mut(|state| {
-        let block = BlockData::new(
-            0,
-            [0x20; 32],
-            [0x21; 32],
-            123,
-            1,
-            DEFAULT_BLOCK_GAS_LIMIT,
-            0,
-            [0x22; 20],
-            Vec::new(),
-            [0x23; 32],
-            [0x24; 32],
-        );
-        let ptr = state
-            .blob_store
-            .store_bytes(block.to_bytes().as_ref())
-            .expect("store unpruned block");
-        state.blocks.insert(0, ptr);
+        let mut head = *state.head.get();
+        head.number = 9;
+        state.head.set(head);
+    });
+    evm_core::archive::ack_archived_blocks(archive.as_slice(), 8).expect("ack");
+    with_state_mut(|state| {
+        let mut prune_state = *state.prune_state.get();
+        prune_state.set_pruned_before(8);
+        state.prune_state.set(prune_state);
     });
 
-    let out = super::get_block(0).expect("block without prune boundary");
-    assert_eq!(out.number, 0);
+    let expected = vec![super::ArchivedRangeView {
+        canister_id: archive,
+        start_block: 0,
+        end_block: 8,
+    }];
+    match super::get_block(5) {
+        Err(super::LookupError::Archived { ranges }) => assert_eq!(ranges, expected),
+        other => panic!("unexpected lookup result: {other:?}"),
+    }
+    // prune 時に archive 先を記録した tx だけ Archived になる
+    with_state_mut(|state| {
+        state.archived_tx_blocks.insert(TxId([0x66; 32]), 5);
+    });
+    match super::get_receipt(vec![0x66; 32]) {
+        Err(super::LookupError::Archived { ranges }) => assert_eq!(ranges, expected),
+        other => panic!("unexpected lookup result: {other:?}"),
+    }
+    assert!(matches!(
+        super::get_receipt(vec![0x77; 32]),
+        Err(super::LookupError::NotFound)
+    ));
+    assert!(matches!(
+        super::get_block(9),
+        Err(super::LookupError::NotFound)
+    ));
 }
 
 #[test]
-fn get_block_returns_ok_for_retained_block() {
+fn certified_block_and_receipt_witnesses_match_certified_root() {
     init_stable_state();
-    with_state_mut(|state| {
-        let block = BlockData::new(
-            9,
-            [0x10; 32],
-            [0x11; 32],
-            123,
-            1,
-            DEFAULT_BLOCK_GAS_LIMIT,
-            0,
+    set_migration_not_pending_for_test();
+
+    let caller = Principal::self_authenticating(b"gateway-certified-caller");
+    let canister = Principal::self_authenticating(b"gateway-certified-canister");
+    let (max_fee_per_gas, max_priority_fee_per_gas) = evm_db::stable_state::with_state(|state| {
+        let chain_state = *state.chain_state.get();
+        let min_priority = u128::from(chain_state.min_priority_fee);
+        let required_max_fee = u128::from(chain_state.base_fee)
+            .saturating_add(min_priority)
+            .max(u128::from(chain_state.min_gas_price));
+        (required_max_fee, min_priority)
+    });
+    let caller_evm =
+        hash::derive_evm_address_from_principal(caller.as_slice()).expect("caller evm");
+    chain::credit_balance(caller_evm, 1_000_000_000_000_000_000).expect("fund caller");
+    let tx = build_ic_synthetic_tx_input_for_test(0, max_fee_per_gas, max_priority_fee_per_gas);
+    let tx_id = super::submit_ic_tx_internal_with_canister_and_scheduler(
+        caller.as_slice().to_vec(),
+        canister.as_slice().to_vec(),
+        "submit_ic_tx",
+        tx,
+        no_schedule_for_test,
+    )
+    .expect("submit_ic_tx adapter");
+    let outcome = chain::produce_block(1).expect("produce block");
+    super::icrc3::refresh_certified_tip();
+    let root = super::icrc3::certified_root();
+
+    let decode = |bytes: &[u8]| -> ic_certification::HashTree {
+        serde_cbor::from_slice(bytes).expect("decode witness")
+    };
+    let tip = decode(&super::icrc3::tip_hash_tree().expect("tip hash tree"));
+    assert_eq!(tip.digest(), root);
+    assert_eq!(
+        tip.lookup_path([b"last_block_hash".as_slice()]),
+        LookupResult::Found(outcome.block.block_hash.as_slice())
+    );
+
+    let certified_block = super::get_certified_block(outcome.block.number).expect("block");
+    assert_eq!(
+        certified_block.block.block_hash,
+        outcome.block.block_hash.to_vec()
+    );
+    let witness = decode(&certified_block.witness);
+    assert_eq!(witness.digest(), root);
+    let block_key = outcome.block.number.to_be_bytes();
+    assert_eq!(
+        witness.lookup_path([b"blocks".as_slice(), block_key.as_slice()]),
+        LookupResult::Found(outcome.block.block_hash.as_slice())
+    );
+
+    let certified_receipt = super::get_certified_receipt(tx_id.clone()).expect("receipt");
+    let witness = decode(&certified_receipt.witness);
+    assert_eq!(witness.digest(), root);
+    let receipt = chain::get_receipt(&outcome.block.tx_ids[0]).expect("stored receipt");
+    let receipt_hash = hash::receipt_hash(&receipt);
+    assert_eq!(
+        witness.lookup_path([b"receipts".as_slice(), tx_id.as_slice()]),
+        LookupResult::Found(receipt_hash.as_slice())
+    );
+
+    // 窓を外れた block は不在証明になり、tip だけが前進する。
+    with_state_mut(|state| {
+        let mut head = *state.head.get();
+        head.number = outcome.block.number + super::icrc3::CERTIFIED_BLOCK_WINDOW + 8;
+        head.block_hash = [0x5a; 32];
+        state.head.set(head);
+    });
+    super::icrc3::refresh_certified_tip();
+    let certified_block = super::get_certified_block(outcome.block.number).expect("block");
+    let witness = decode(&certified_block.witness);
+    assert_eq!(witness.digest(), super::icrc3::certified_root());
+    assert_eq!(
+        witness.lookup_path([b"blocks".as_slice(), block_key.as_slice()]),
+        LookupResult::Absent
+    );
+    assert_eq!(
+        witness.lookup_path([b"last_block_hash".as_slice()]),
+        LookupResult::Found([0x5a; 32].as_slice())
+    );
+}
+
+#[test]
+fn certified_state_reads_prove_values_under_certified_state_root() {
+    init_stable_state();
+    set_migration_not_pending_for_test();
+
+    // helper は state root を 0 のまま初期化済みにするので、空 trie の root から始める。
+    with_state_mut(|state| {
+        let mut meta = *state.state_root_meta.get();
+        meta.state_root = hash::keccak256(&[0x80]);
+        state.state_root_meta.set(meta);
+    });
+
+    let caller = Principal::self_authenticating(b"gateway-certified-state-caller");
+    let canister = Principal::self_authenticating(b"gateway-certified-state-canister");
+    let (max_fee_per_gas, max_priority_fee_per_gas) = evm_db::stable_state::with_state(|state| {
+        let chain_state = *state.chain_state.get();
+        let min_priority = u128::from(chain_state.min_priority_fee);
+        let required_max_fee = u128::from(chain_state.base_fee)
+            .saturating_add(min_priority)
+            .max(u128::from(chain_state.min_gas_price));
+        (required_max_fee, min_priority)
+    });
+    let caller_evm =
+        hash::derive_evm_address_from_principal(caller.as_slice()).expect("caller evm");
+    chain::credit_balance(caller_evm, 1_000_000_000_000_000_000).expect("fund caller");
+    let tx = build_ic_synthetic_tx_input_for_test(0, max_fee_per_gas, max_priority_fee_per_gas);
+    super::submit_ic_tx_internal_with_canister_and_scheduler(
+        caller.as_slice().to_vec(),
+        canister.as_slice().to_vec(),
+        "submit_ic_tx",
+        tx,
+        no_schedule_for_test,
+    )
+    .expect("submit_ic_tx adapter");
+    let outcome = chain::produce_block(1).expect("produce block");
+    super::icrc3::refresh_certified_tip();
+
+    let certified =
+        super::rpc_eth_get_balance_certified(caller_evm.to_vec()).expect("certified balance");
+    assert_eq!(certified.block_number, outcome.block.number);
+    assert_eq!(certified.state_root, outcome.block.state_root.to_vec());
+    assert_eq!(certified.account.nonce, 1);
+    assert_eq!(
+        certified.account.balance,
+        super::rpc_eth_get_balance(caller_evm.to_vec(), super::RpcBlockTagView::Latest)
+            .expect("balance")
+    );
+    let witness: ic_certification::HashTree =
+        serde_cbor::from_slice(&certified.witness).expect("decode witness");
+    assert_eq!(witness.digest(), super::icrc3::certified_root());
+    assert_eq!(
+        witness.lookup_path([b"last_block_state_root".as_slice()]),
+        LookupResult::Found(outcome.block.state_root.as_slice())
+    );
+    assert!(!certified.account.account_proof.is_empty());
+
+    let code = super::rpc_eth_get_code_certified(caller_evm.to_vec()).expect("certified code");
+    assert!(code.code.is_empty());
+    let storage = super::rpc_eth_get_storage_at_certified(caller_evm.to_vec(), vec![0x01; 32])
+        .expect("certified storage");
+    assert_eq!(storage.storage.value, vec![0u8; 32]);
+    assert!(matches!(
+        super::rpc_eth_get_storage_at_certified(caller_evm.to_vec(), vec![0x01; 31]),
+        Err(err) if err.error_prefix.as_deref() == Some("invalid.slot")
+    ));
+}
+
+#[test]
+fn get_block_returns_ok_when_prune_boundary_is_absent() {
+    init_stable_state();
+    with_state_mut(|state| {
+        let block = BlockData::new(
+            0,
+            [0x20; 32],
+            [0x21; 32],
+            123,
+            1,
+            DEFAULT_BLOCK_GAS_LIMIT,
+            0,
+            [0x22; 20],
+            Vec::new(),
+            [0x23; 32],
+            [0x24; 32],
+        );
+        let ptr = state
+            .blob_store
+            .store_bytes(block.to_bytes().as_ref())
+            .expect("store unpruned block");
+        state.blocks.insert(0, ptr);
+    });
+
+    let out = super::get_block(0).expect("block without prune boundary");
+    assert_eq!(out.number, 0);
+}
+
+#[test]
+fn get_block_returns_ok_for_retained_block() {
+    init_stable_state();
+    with_state_mut(|state| {
+        let block = BlockData::new(
+            9,
+            [0x10; 32],
+            [0x11; 32],
+            123,
+            1,
+            DEFAULT_BLOCK_GAS_LIMIT,
+            0,
             [0x12; 20],
             Vec::new(),
             [0x13; 32],
@@ -3758,11 +4520,22 @@ fn record_unwrap_requests_from_block_stores_native_withdraw_logs_as_gross() {
             return_data_hash: [0u8; 32],
             return_data: Vec::new(),
             contract_address: None,
-            logs: vec![log_entry_from_parts(
-                NATIVE_WITHDRAW_PRECOMPILE_ADDRESS.into_array(),
-                vec![hash::keccak256(b"KasaneNativeWithdrawalRequest(bytes)")],
-                native_withdraw_log_data(amount, &[0x09, 0x0a]),
-            )],
+            logs: vec![
+                log_entry_from_parts(
+                    NATIVE_WITHDRAW_PRECOMPILE_ADDRESS.into_array(),
+                    vec![hash::keccak256(b"KasaneNativeWithdrawalRequest(bytes)")],
+                    native_withdraw_log_data(amount, &[0x09, 0x0a]),
+                ),
+                log_entry_from_parts(
+                    NATIVE_WITHDRAW_PRECOMPILE_ADDRESS.into_array(),
+                    vec![hash::keccak256(b"KasaneNativeWithdrawalRequest(bytes)")],
+                    [
+                        native_withdraw_log_data(amount, &[0x09, 0x0a]),
+                        vec![0x5c; 32],
+                    ]
+                    .concat(),
+                ),
+            ],
         };
         let ptr = state
             .blob_store
@@ -3778,7 +4551,12 @@ fn record_unwrap_requests_from_block_stores_native_withdraw_logs_as_gross() {
         let req = state.unwrap_requests.get(&request_id).expect("request");
         assert_eq!(req.asset_id, super::NATIVE_WITHDRAW_ASSET_MARKER);
         assert_eq!(req.amount, amount);
-        assert_eq!(state.unwrap_dispatch_queue.len(), 1);
+        assert_eq!(req.recipient_subaccount, None);
+        let with_subaccount = super::derive_log_request_id(&tx_id, 1)
+            .and_then(|id| state.unwrap_requests.get(&id))
+            .expect("subaccount request");
+        assert_eq!(with_subaccount.recipient_subaccount, Some([0x5c; 32]));
+        assert_eq!(state.unwrap_dispatch_queue.len(), 2);
     });
 }
 
@@ -4083,25 +4861,19 @@ fn icp_update_envelope_contains_sender_trace() {
 
 #[test]
 fn icp_update_uncertain_error_classifier_marks_bounded_wait_timeout_unknown_only() {
-    assert!(super::is_icp_update_uncertain_call_error(
-        &CallFailed::CallRejected(CallRejected::with_rejection(
-            RejectCode::SysUnknown as u32,
-            "timeout".to_string(),
-        ))
-    ));
-    assert!(!super::is_icp_update_uncertain_call_error(
-        &CallFailed::CallRejected(CallRejected::with_rejection(
+    assert!(super::is_uncertain_call_error(&CallFailed::CallRejected(
+        CallRejected::with_rejection(RejectCode::SysUnknown as u32, "timeout".to_string(),)
+    )));
+    assert!(!super::is_uncertain_call_error(&CallFailed::CallRejected(
+        CallRejected::with_rejection(
             RejectCode::SysTransient as u32,
             "temporary routing failure".to_string(),
-        ))
-    ));
-    assert!(!super::is_icp_update_uncertain_call_error(
-        &CallFailed::CallRejected(CallRejected::with_rejection(
-            RejectCode::CanisterReject as u32,
-            "bad input".to_string(),
-        ))
-    ));
-    assert!(!super::is_icp_update_uncertain_call_error(
+        )
+    )));
+    assert!(!super::is_uncertain_call_error(&CallFailed::CallRejected(
+        CallRejected::with_rejection(RejectCode::CanisterReject as u32, "bad input".to_string(),)
+    )));
+    assert!(!super::is_uncertain_call_error(
         &CallFailed::CallPerformFailed(CallPerformFailed)
     ));
 }
@@ -4366,11 +5138,13 @@ fn raw_stable_corruption_does_not_trap_query_or_dispatch_for_unwrap_request() {
         asset_id: vec![0xB2u8; 11],
         amount: [0xC3u8; 32],
         recipient: vec![0xD4u8; 19],
+        recipient_subaccount: None,
         status: UnwrapRequestStatus::Queued,
         ledger_tx_id: Some(vec![0xE5u8; 17]),
         error_code: Some("wrap.integration.gateway.raw-corrupt.7f3e2c1b".to_string()),
         updated_at: 987_654_333,
         transfer_created_at_time: 987_654_334,
+        kind: UnwrapAssetKind::Fungible,
     };
     let encoded = request.to_bytes().into_owned();
     with_state_mut(|state| {
@@ -4633,6 +5407,68 @@ fn pop_next_dispatch_request_marks_dispatching_and_dequeues() {
     );
 }
 
+#[test]
+fn pop_next_dispatch_batch_caps_each_ledger_and_keeps_per_ledger_order() {
+    init_stable_state();
+    let ledger_a = vec![0x55u8; 10];
+    let ledger_b = vec![0x56u8; 10];
+    let ledgers = [
+        &ledger_a, &ledger_a, &ledger_a, &ledger_a, &ledger_a, &ledger_b, &ledger_a, &ledger_b,
+    ];
+    with_state_mut(|state| {
+        for (index, ledger) in ledgers.iter().enumerate() {
+            let request_id = TxId([0x40u8 + index as u8; 32]);
+            let mut req = sample_unwrap_request(UnwrapRequestStatus::Queued, None, 1);
+            req.asset_id = (*ledger).clone();
+            let mut meta = *state.unwrap_dispatch_meta.get();
+            let seq = meta.push();
+            state.unwrap_dispatch_meta.set(meta);
+            state.unwrap_dispatch_queue.insert(seq, request_id);
+            state.unwrap_requests.insert(request_id, req);
+        }
+    });
+
+    // A は同時送金上限 4 件で止め、その後ろの B は先に送る。残した A は queue の先頭に順に戻る。
+    let ids = |batch: &[(TxId, UnwrapDispatchRequest)]| {
+        batch.iter().map(|(id, _)| id.0[0]).collect::<Vec<_>>()
+    };
+    let first = super::pop_next_dispatch_batch(123);
+    assert_eq!(ids(&first), vec![0x40, 0x41, 0x42, 0x43, 0x45, 0x47]);
+    assert!(first
+        .iter()
+        .all(|(_, req)| req.status == UnwrapRequestStatus::Dispatching));
+    let queued = with_state(|state| {
+        let meta = *state.unwrap_dispatch_meta.get();
+        (meta.head..meta.tail)
+            .map(|seq| state.unwrap_dispatch_queue.get(&seq).map(|id| id.0[0]))
+            .collect::<Vec<_>>()
+    });
+    assert_eq!(queued, vec![Some(0x44), Some(0x46)]);
+
+    // 前の tick の送金が返る前に次の tick が来ても、A の上限は tick をまたいで効く。
+    assert!(super::pop_next_dispatch_batch(124).is_empty());
+    assert_eq!(
+        with_state(|state| state.unwrap_requests.get(&TxId([0x44u8; 32]))).map(|req| req.status),
+        Some(UnwrapRequestStatus::Queued)
+    );
+
+    for (request_id, _) in first.iter().take(2) {
+        super::finalize_unwrap_dispatch_attempt(
+            *request_id,
+            125,
+            super::AppliedUnwrapDispatchOutcome {
+                status: UnwrapRequestStatus::Dispatched,
+                ledger_tx_id: Some(vec![1]),
+                error_code: None,
+            },
+        );
+    }
+    let second = super::pop_next_dispatch_batch(126);
+    assert_eq!(ids(&second), vec![0x44, 0x46]);
+    assert!(super::pop_next_dispatch_batch(127).is_empty());
+    assert!(with_state(|state| state.unwrap_dispatch_queue.is_empty()));
+}
+
 #[test]
 fn pop_next_dispatch_request_quarantines_decode_failed_entry() {
     init_stable_state();
@@ -4731,11 +5567,13 @@ fn quarantine_decode_failed_unwrap_requests_marks_dead_letter_and_dequeues() {
                 asset_id: vec![2u8; 5],
                 amount: [3u8; 32],
                 recipient: vec![4u8; 5],
+                recipient_subaccount: None,
                 status: UnwrapRequestStatus::Queued,
                 ledger_tx_id: None,
                 error_code: None,
                 updated_at: 1,
                 transfer_created_at_time: 0,
+                kind: UnwrapAssetKind::Fungible,
             },
         );
     });
@@ -4799,6 +5637,9 @@ fn did_contains_dispatch_result_contract_shape() {
     assert!(did.contains("recover_failed_wrap : (RecoverFailedWrapArgs) -> (Result_"));
     assert!(did.contains("set_fee_policy : (FeePolicyView) -> (Result);"));
     assert!(did.contains("set_allowed_assets : (vec principal) -> (Result);"));
+    assert!(did.contains("set_allowed_nft_collections : (vec principal) -> (Result);"));
+    assert!(did.contains("submit_nft_wrap_request : (SubmitNftWrapRequestArgs) -> (Result_"));
+    assert!(did.contains("sync_wrapped_token_metadata : (principal) -> (Result_"));
     assert!(!did.contains("get_request_dispatch_result"));
     assert!(did.contains("get_unwrap_request_ids_by_tx_id"));
     assert!(did.contains("get_unwrap_request_ids_by_eth_tx_hash"));
@@ -4814,7 +5655,1050 @@ fn did_contains_dispatch_result_contract_shape() {
     assert!(did.contains("add_update_precompile_allowed_method : (PrecompileAllowArgs) -> (Result"));
     assert!(did.contains("remove_update_precompile_allowed_method : (PrecompileAllowArgs) -> ("));
     assert!(did.contains(
-        "rpc_eth_call_object_at : (RpcCallObjectView, RpcBlockTagView) -> (\n      Result_19,\n    ) composite_query"
+        "rpc_eth_call_object_at : (RpcCallObjectView, RpcBlockTagView) -> (\n      Result_32,\n    ) composite_query"
     ));
     assert!(!did.contains("set_wrap_canister_id : (principal) -> (Result_15);"));
 }
+
+fn reconcile_expected_transfer() -> crate::ledger_reconcile::ExpectedTransfer {
+    crate::ledger_reconcile::ExpectedTransfer {
+        memo: vec![0xab; 32],
+        created_at_time: 1_000_000_000_000,
+        from_owner: vec![0x01; 10],
+        to_owner: vec![0x77; 10],
+        to_subaccount: None,
+    }
+}
+
+fn reconcile_block(index: u64, timestamp: u64) -> crate::ledger_reconcile::LedgerBlock {
+    crate::ledger_reconcile::LedgerBlock {
+        index,
+        timestamp,
+        memo: Some(vec![index as u8; 4]),
+        created_at_time: Some(timestamp),
+        from_owner: Some(vec![0x09; 10]),
+        to_owner: Some(vec![0x08; 10]),
+        to_subaccount: None,
+    }
+}
+
+fn fake_ledger_fetch<'a>(
+    blocks: &'a [crate::ledger_reconcile::LedgerBlock],
+    calls: &'a std::cell::RefCell<Vec<(u64, u64)>>,
+) -> impl FnMut(
+    u64,
+    u64,
+) -> std::future::Ready<Result<crate::ledger_reconcile::LedgerBlockPage, String>>
+       + 'a {
+    move |start, length| {
+        calls.borrow_mut().push((start, length));
+        let page = crate::ledger_reconcile::LedgerBlockPage {
+            log_length: blocks.len() as u64,
+            blocks: blocks
+                .iter()
+                .filter(|block| block.index >= start && block.index < start + length)
+                .cloned()
+                .collect(),
+        };
+        std::future::ready(Ok(page))
+    }
+}
+
+#[test]
+fn ledger_reconcile_finds_own_transfer_and_ignores_reused_memo() {
+    let expected = reconcile_expected_transfer();
+    let created = expected.created_at_time;
+    let mut blocks: Vec<_> = (0..20).map(|i| reconcile_block(i, created + i)).collect();
+    blocks[12].memo = Some(expected.memo.clone());
+    blocks[12].created_at_time = Some(created);
+    blocks[12].from_owner = Some(expected.from_owner.clone());
+    blocks[12].to_owner = Some(expected.to_owner.clone());
+    // 同じ memo と時刻を他人が付けた送金は一致しない。
+    blocks[15].memo = Some(expected.memo.clone());
+    blocks[15].created_at_time = Some(created);
+    blocks[15].to_owner = Some(expected.to_owner.clone());
+    let calls = std::cell::RefCell::new(Vec::new());
+
+    let verdict = run_ready_future(crate::ledger_reconcile::scan_ledger_for_transfer(
+        fake_ledger_fetch(&blocks, &calls),
+        &expected,
+        None,
+        100,
+    ))
+    .expect("scan");
+
+    assert_eq!(
+        verdict,
+        crate::ledger_reconcile::MemoScanVerdict::Found { block_index: 12 }
+    );
+    assert_eq!(calls.borrow().as_slice(), &[(0, 0), (0, 20)]);
+}
+
+#[test]
+fn ledger_reconcile_stops_at_blocks_older_than_transfer_drift() {
+    let expected = reconcile_expected_transfer();
+    let created = expected.created_at_time;
+    let drift = crate::ledger_reconcile::LEDGER_PERMITTED_DRIFT_NANOS;
+    let old = created - drift - 1;
+    let mut blocks: Vec<_> = (0..1_200).map(|i| reconcile_block(i, old)).collect();
+    for block in blocks.iter_mut().skip(1_195) {
+        block.timestamp = created;
+    }
+    let calls = std::cell::RefCell::new(Vec::new());
+
+    let verdict = run_ready_future(crate::ledger_reconcile::scan_ledger_for_transfer(
+        fake_ledger_fetch(&blocks, &calls),
+        &expected,
+        None,
+        5_000,
+    ))
+    .expect("scan");
+
+    assert_eq!(verdict, crate::ledger_reconcile::MemoScanVerdict::NotFound);
+    assert_eq!(calls.borrow().as_slice(), &[(0, 0), (700, 500)]);
+}
+
+#[test]
+fn ledger_reconcile_reports_cursor_when_scan_budget_runs_out() {
+    let expected = reconcile_expected_transfer();
+    let created = expected.created_at_time;
+    let mut blocks: Vec<_> = (0..50).map(|i| reconcile_block(i, created)).collect();
+    blocks[3].memo = Some(expected.memo.clone());
+    blocks[3].created_at_time = Some(created);
+    blocks[3].from_owner = Some(expected.from_owner.clone());
+    blocks[3].to_owner = Some(expected.to_owner.clone());
+    let calls = std::cell::RefCell::new(Vec::new());
+
+    let first = run_ready_future(crate::ledger_reconcile::scan_ledger_for_transfer(
+        fake_ledger_fetch(&blocks, &calls),
+        &expected,
+        None,
+        30,
+    ))
+    .expect("first scan");
+    assert_eq!(
+        first,
+        crate::ledger_reconcile::MemoScanVerdict::Incomplete { scan_before: 20 }
+    );
+
+    let second = run_ready_future(crate::ledger_reconcile::scan_ledger_for_transfer(
+        fake_ledger_fetch(&blocks, &calls),
+        &expected,
+        Some(20),
+        30,
+    ))
+    .expect("second scan");
+    assert_eq!(
+        second,
+        crate::ledger_reconcile::MemoScanVerdict::Found { block_index: 3 }
+    );
+    assert_eq!(calls.borrow().as_slice(), &[(0, 0), (20, 30), (0, 20)]);
+}
+
+#[test]
+fn ledger_reconcile_rejects_short_pages_and_call_failures() {
+    let expected = reconcile_expected_transfer();
+    let blocks: Vec<_> = (0..10)
+        .map(|i| reconcile_block(i, expected.created_at_time))
+        .collect();
+    let short = run_ready_future(crate::ledger_reconcile::scan_ledger_for_transfer(
+        |_start, _length| {
+            std::future::ready(Ok(crate::ledger_reconcile::LedgerBlockPage {
+                log_length: 10,
+                blocks: blocks[5..].to_vec(),
+            }))
+        },
+        &expected,
+        None,
+        100,
+    ));
+    assert_eq!(short, Err("reconcile.ledger_page_incomplete".to_string()));
+
+    let failed = run_ready_future(crate::ledger_reconcile::scan_ledger_for_transfer(
+        |_start, _length| {
+            std::future::ready(Err::<crate::ledger_reconcile::LedgerBlockPage, _>(
+                "ledger.blocks_call_failed:timeout".to_string(),
+            ))
+        },
+        &expected,
+        Some(10),
+        100,
+    ));
+    assert_eq!(failed, Err("ledger.blocks_call_failed:timeout".to_string()));
+}
+
+#[test]
+fn ledger_reconcile_parses_icrc3_transfer_block() {
+    use crate::ledger_reconcile::Icrc3Value;
+    let owner = vec![0x77; 10];
+    let block = Icrc3Value::Map(vec![
+        ("ts".to_string(), Icrc3Value::Nat(Nat::from(55u64))),
+        (
+            "tx".to_string(),
+            Icrc3Value::Map(vec![
+                ("memo".to_string(), Icrc3Value::Blob(vec![1, 2, 3])),
+                ("ts".to_string(), Icrc3Value::Nat(Nat::from(50u64))),
+                (
+                    "from".to_string(),
+                    Icrc3Value::Array(vec![Icrc3Value::Blob(vec![0x01; 10])]),
+                ),
+                (
+                    "to".to_string(),
+                    Icrc3Value::Array(vec![
+                        Icrc3Value::Blob(owner.clone()),
+                        Icrc3Value::Blob(vec![0u8; 32]),
+                    ]),
+                ),
+            ]),
+        ),
+    ]);
+
+    let parsed = crate::ledger_reconcile::ledger_block_from_value(7, &block).expect("block");
+
+    assert_eq!(
+        parsed,
+        crate::ledger_reconcile::LedgerBlock {
+            index: 7,
+            timestamp: 55,
+            memo: Some(vec![1, 2, 3]),
+            created_at_time: Some(50),
+            from_owner: Some(vec![0x01; 10]),
+            to_owner: Some(owner),
+            to_subaccount: None,
+        }
+    );
+    assert_eq!(
+        crate::ledger_reconcile::ledger_block_from_value(7, &Icrc3Value::Map(Vec::new())),
+        Err("ledger.block_timestamp_missing".to_string())
+    );
+}
+
+#[test]
+fn unwrap_dispatch_classifies_unanswered_transfer_as_uncertain() {
+    assert!(super::unwrap_transfer_outcome_uncertain(
+        "ledger.call_uncertain:timeout"
+    ));
+    assert!(super::unwrap_transfer_outcome_uncertain(
+        "ledger.decode_failed:bad reply"
+    ));
+    assert!(!super::unwrap_transfer_outcome_uncertain(
+        "ledger.call_failed:destination invalid"
+    ));
+    assert!(!super::unwrap_transfer_outcome_uncertain(
+        "ledger.transfer_failed:insufficient_funds"
+    ));
+}
+
+#[test]
+fn retry_unwrap_dispatch_allows_uncertain_only_inside_dedup_window() {
+    init_stable_state();
+    let now = super::current_time_nanos();
+    let fresh = TxId([0xf4; 32]);
+    let stale = TxId([0xf5; 32]);
+    with_state_mut(|state| {
+        let mut req = sample_unwrap_request(
+            UnwrapRequestStatus::DispatchUncertain,
+            Some("ledger.call_uncertain:timeout"),
+            1,
+        );
+        req.transfer_created_at_time = now;
+        state.unwrap_requests.insert(fresh, req.clone());
+        req.transfer_created_at_time = now - crate::ledger_reconcile::LEDGER_TX_WINDOW_NANOS - 1;
+        state.unwrap_requests.insert(stale, req);
+    });
+
+    let overview = super::retry_unwrap_dispatch(fresh.0.to_vec()).expect("retry");
+    assert_eq!(overview.status, RequestStatus::Queued);
+    let req = with_state(|state| state.unwrap_requests.get(&fresh)).expect("request");
+    assert_eq!(req.transfer_created_at_time, now);
+
+    match super::retry_unwrap_dispatch(stale.0.to_vec()) {
+        Err(super::ApiError::Rejected(detail)) => {
+            assert_eq!(detail.code, "request.reconcile_required");
+        }
+        other => panic!("unexpected retry result: {other:?}"),
+    }
+    let view = super::get_request(stale.0.to_vec()).expect("view");
+    assert_eq!(view.status, RequestStatus::Failed);
+    assert_eq!(
+        view.dispatch_status,
+        Some(super::RequestDispatchStatusView::DispatchUncertain)
+    );
+}
+
+#[test]
+fn apply_unwrap_reconcile_verdict_settles_uncertain_request() {
+    init_stable_state();
+    let dispatched = TxId([0xf6; 32]);
+    let absent = TxId([0xf7; 32]);
+    with_state_mut(|state| {
+        let mut req = sample_unwrap_request(
+            UnwrapRequestStatus::DispatchUncertain,
+            Some("ledger.call_uncertain:timeout"),
+            1,
+        );
+        req.transfer_created_at_time = 77;
+        state.unwrap_requests.insert(dispatched, req.clone());
+        state.unwrap_requests.insert(absent, req);
+    });
+
+    assert_eq!(
+        super::apply_unwrap_reconcile_verdict(
+            dispatched,
+            76,
+            crate::ledger_reconcile::MemoScanVerdict::NotFound,
+        ),
+        Err("reconcile.state_changed".to_string())
+    );
+    super::apply_unwrap_reconcile_verdict(
+        dispatched,
+        77,
+        crate::ledger_reconcile::MemoScanVerdict::Found { block_index: 300 },
+    )
+    .expect("found");
+    super::apply_unwrap_reconcile_verdict(
+        absent,
+        77,
+        crate::ledger_reconcile::MemoScanVerdict::NotFound,
+    )
+    .expect("absent");
+
+    let req = with_state(|state| state.unwrap_requests.get(&dispatched)).expect("request");
+    assert_eq!(req.status, UnwrapRequestStatus::Dispatched);
+    assert_eq!(req.ledger_tx_id, Some(vec![0x01, 0x2c]));
+    assert_eq!(req.error_code, None);
+    let req = with_state(|state| state.unwrap_requests.get(&absent)).expect("request");
+    assert_eq!(req.status, UnwrapRequestStatus::DispatchFailed);
+    assert_eq!(req.error_code.as_deref(), Some("reconcile.not_dispatched"));
+    assert_eq!(req.transfer_created_at_time, 0);
+    assert_eq!(
+        super::apply_unwrap_reconcile_verdict(
+            absent,
+            77,
+            crate::ledger_reconcile::MemoScanVerdict::Found { block_index: 1 },
+        ),
+        Err("reconcile.state_changed".to_string())
+    );
+}
+
+#[test]
+fn nft_unwrap_reconcile_settles_by_current_owner() {
+    use crate::ledger_reconcile::{nft_owner_verdict, Icrc7Account, MemoScanVerdict};
+
+    init_stable_state();
+    let canister = Principal::self_authenticating(b"nft-reconcile-canister");
+    let recipient = Principal::self_authenticating(b"nft-reconcile-recipient");
+    let subaccount = Some([0x05; 32]);
+    let account = |owner: Principal, subaccount: Option<[u8; 32]>| Icrc7Account {
+        owner,
+        subaccount: subaccount.map(|bytes| bytes.to_vec()),
+    };
+
+    assert_eq!(
+        nft_owner_verdict(
+            Some(&account(recipient, subaccount)),
+            canister,
+            recipient,
+            subaccount
+        ),
+        Ok(MemoScanVerdict::OwnedByRecipient)
+    );
+    assert_eq!(
+        nft_owner_verdict(
+            Some(&account(canister, Some([0u8; 32]))),
+            canister,
+            recipient,
+            subaccount
+        ),
+        Ok(MemoScanVerdict::NotFound)
+    );
+    assert_eq!(
+        nft_owner_verdict(
+            Some(&account(recipient, None)),
+            canister,
+            recipient,
+            subaccount
+        ),
+        Err("reconcile.nft_owner_mismatch".to_string())
+    );
+    assert_eq!(
+        nft_owner_verdict(None, canister, recipient, subaccount),
+        Err("reconcile.nft_not_found".to_string())
+    );
+
+    let delivered = TxId([0xf8; 32]);
+    let still_held = TxId([0xf9; 32]);
+    with_state_mut(|state| {
+        let mut req = sample_unwrap_request(
+            UnwrapRequestStatus::DispatchUncertain,
+            Some("ledger.call_uncertain:timeout"),
+            1,
+        );
+        req.kind = UnwrapAssetKind::Nft;
+        req.transfer_created_at_time = 77;
+        state.unwrap_requests.insert(delivered, req.clone());
+        state.unwrap_requests.insert(still_held, req);
+    });
+    super::apply_unwrap_reconcile_verdict(delivered, 77, MemoScanVerdict::OwnedByRecipient)
+        .expect("delivered");
+    super::apply_unwrap_reconcile_verdict(still_held, 77, MemoScanVerdict::NotFound)
+        .expect("still held");
+
+    let req = with_state(|state| state.unwrap_requests.get(&delivered)).expect("request");
+    assert_eq!(req.status, UnwrapRequestStatus::Dispatched);
+    assert_eq!(req.ledger_tx_id, None);
+    assert_eq!(req.error_code, None);
+    let req = with_state(|state| state.unwrap_requests.get(&still_held)).expect("request");
+    assert_eq!(req.status, UnwrapRequestStatus::DispatchFailed);
+    assert!(super::unwrap_request_retryable(
+        &req,
+        super::current_time_nanos()
+    ));
+    let overview = super::retry_unwrap_dispatch(still_held.0.to_vec()).expect("retry");
+    assert_eq!(overview.status, RequestStatus::Queued);
+}
+
+fn reserve_supply(total_supply: [u8; 32]) -> evm_core::kasane_precompiles::WrappedTokenSupply {
+    evm_core::kasane_precompiles::WrappedTokenSupply {
+        token: [0x42; 20],
+        total_supply,
+    }
+}
+
+#[test]
+fn asset_reserves_view_reports_signed_discrepancy_and_shortfall() {
+    let asset = Principal::self_authenticating(b"reserve-asset");
+    let mut supply = [0u8; 32];
+    supply[31] = 100;
+
+    let (view, shortfall) =
+        super::asset_reserves_view(asset, Ok(130), Ok(Some(reserve_supply(supply))), 20, 7);
+    assert!(!shortfall);
+    assert_eq!(view.wrapped_token_address, Some(vec![0x42; 20]));
+    assert_eq!(view.ledger_balance, Some(Nat::from(130u8)));
+    assert_eq!(view.wrapped_supply, Some(Nat::from(100u8)));
+    assert_eq!(view.discrepancy, Some(candid::Int::from(3)));
+
+    let (view, shortfall) =
+        super::asset_reserves_view(asset, Ok(126), Ok(Some(reserve_supply(supply))), 20, 7);
+    assert!(shortfall);
+    assert_eq!(view.discrepancy, Some(candid::Int::from(-1)));
+
+    let (view, shortfall) = super::asset_reserves_view(asset, Ok(0), Ok(None), 0, 5);
+    assert!(shortfall);
+    assert_eq!(view.wrapped_token_address, None);
+    assert_eq!(view.discrepancy, Some(candid::Int::from(-5)));
+
+    let (view, shortfall) = super::asset_reserves_view(
+        asset,
+        Err("ledger.balance_call_failed:timeout".to_string()),
+        Ok(Some(reserve_supply(supply))),
+        0,
+        0,
+    );
+    assert!(!shortfall);
+    assert_eq!(view.ledger_balance, None);
+    assert_eq!(view.discrepancy, None);
+    assert_eq!(
+        view.error.as_deref(),
+        Some("ledger.balance_call_failed:timeout")
+    );
+
+    let (_, shortfall) = super::asset_reserves_view(
+        asset,
+        Ok(u128::MAX),
+        Ok(Some(reserve_supply([0xff; 32]))),
+        0,
+        0,
+    );
+    assert!(shortfall, "supply beyond u128 cannot be backed");
+}
+
+#[test]
+fn reserve_in_flight_totals_count_only_custodied_requests() {
+    init_stable_state();
+    let asset = vec![2];
+    let with_stage = |stage: WrapRequestStage, pulled: bool| {
+        let mut req = sample_wrap_request(RequestStatus::Queued);
+        req.amount = super::u256_from_u128(10).to_vec();
+        req.result.stage = stage;
+        req.result.pull_ledger_tx_id = pulled.then(|| vec![1]);
+        req
+    };
+    with_state_mut(|state| {
+        state
+            .wrap_requests
+            .insert(TxId([0xa1; 32]), with_stage(WrapRequestStage::Pulled, true));
+        state.wrap_requests.insert(
+            TxId([0xa2; 32]),
+            with_stage(WrapRequestStage::MintSubmitted, true),
+        );
+        state.wrap_requests.insert(
+            TxId([0xa3; 32]),
+            with_stage(WrapRequestStage::Succeeded, true),
+        );
+        state.wrap_requests.insert(
+            TxId([0xa4; 32]),
+            with_stage(WrapRequestStage::FeeCollected, false),
+        );
+        let mut refunded = with_stage(WrapRequestStage::Failed, true);
+        refunded.result.withdrawn = true;
+        state.wrap_requests.insert(TxId([0xa5; 32]), refunded);
+        let mut native = with_stage(WrapRequestStage::Pulled, true);
+        native.gas_limit = 0;
+        state.wrap_requests.insert(TxId([0xa6; 32]), native);
+
+        let mut unwrap = sample_unwrap_request(UnwrapRequestStatus::Queued, None, 1);
+        unwrap.asset_id = asset.clone();
+        unwrap.amount = super::u256_from_u128(3);
+        state
+            .unwrap_requests
+            .insert(TxId([0xb1; 32]), unwrap.clone());
+        unwrap.status = UnwrapRequestStatus::DispatchUncertain;
+        state
+            .unwrap_requests
+            .insert(TxId([0xb2; 32]), unwrap.clone());
+        unwrap.status = UnwrapRequestStatus::Dispatched;
+        state
+            .unwrap_requests
+            .insert(TxId([0xb3; 32]), unwrap.clone());
+        unwrap.status = UnwrapRequestStatus::Queued;
+        unwrap.asset_id = super::NATIVE_WITHDRAW_ASSET_MARKER.to_vec();
+        state.unwrap_requests.insert(TxId([0xb4; 32]), unwrap);
+    });
+
+    assert_eq!(super::reserve_in_flight_totals(&asset), (20, 6));
+    assert_eq!(
+        super::reserve_in_flight_totals(super::NATIVE_WITHDRAW_ASSET_MARKER),
+        (0, 0)
+    );
+}
+
+#[test]
+fn wrap_then_unwrap_keeps_reserves_balanced_after_ledger_fee() {
+    init_stable_state();
+    let asset = Principal::self_authenticating(b"reserve-asset");
+    let ledger_fee = 10u128;
+    let supply = |amount: u128| Ok(Some(reserve_supply(super::u256_from_u128(amount))));
+    let mut wrap = sample_wrap_request(RequestStatus::Queued);
+    wrap.asset_id = asset.as_slice().to_vec();
+    wrap.amount = super::u256_from_u128(1_000).to_vec();
+    wrap.result.stage = WrapRequestStage::Pulled;
+    wrap.result.pull_ledger_tx_id = Some(vec![1]);
+    with_state_mut(|state| {
+        state.wrap_requests.insert(TxId([0xc1; 32]), wrap.clone());
+    });
+    // 引き取り済みで未 mint の wrap は預かりに入っている。
+    let mut custody = 1_000u128;
+    let (wrap_in, unwrap_in) = super::reserve_in_flight_totals(asset.as_slice());
+    let (_, shortfall) =
+        super::asset_reserves_view(asset, Ok(custody), supply(0), wrap_in, unwrap_in);
+    assert!(!shortfall);
+
+    wrap.result.stage = WrapRequestStage::Succeeded;
+    let mut unwrap = sample_unwrap_request(UnwrapRequestStatus::Queued, None, 1);
+    unwrap.asset_id = asset.as_slice().to_vec();
+    unwrap.amount = super::u256_from_u128(400);
+    with_state_mut(|state| {
+        state.wrap_requests.insert(TxId([0xc1; 32]), wrap);
+        state
+            .unwrap_requests
+            .insert(TxId([0xc2; 32]), unwrap.clone());
+    });
+    let (wrap_in, unwrap_in) = super::reserve_in_flight_totals(asset.as_slice());
+    let (view, shortfall) =
+        super::asset_reserves_view(asset, Ok(custody), supply(600), wrap_in, unwrap_in);
+    assert!(!shortfall);
+    assert_eq!(view.discrepancy, Some(candid::Int::from(0)));
+
+    // ledger は送金額に加えて手数料も預かりから引く。
+    let sent = super::unwrap_gross_transfer_amount(&Nat::from(400u128), ledger_fee).expect("sent");
+    assert_eq!(sent, Nat::from(390u128));
+    custody -= super::nat_to_u128(&sent).expect("u128") + ledger_fee;
+    unwrap.status = UnwrapRequestStatus::Dispatched;
+    with_state_mut(|state| {
+        state.unwrap_requests.insert(TxId([0xc2; 32]), unwrap);
+    });
+    let (wrap_in, unwrap_in) = super::reserve_in_flight_totals(asset.as_slice());
+    let (view, shortfall) =
+        super::asset_reserves_view(asset, Ok(custody), supply(600), wrap_in, unwrap_in);
+    assert!(!shortfall);
+    assert_eq!(view.discrepancy, Some(candid::Int::from(0)));
+    assert_eq!(
+        super::unwrap_gross_transfer_amount(&Nat::from(10u128), ledger_fee).expect_err("fee"),
+        "unwrap.amount_not_above_fee"
+    );
+}
+
+#[test]
+fn wrap_pause_rejects_new_wraps_until_cleared() {
+    init_stable_state();
+    assert_eq!(super::reject_wrap_reason(), None);
+    with_state_mut(|state| {
+        let ops = ic_evm_ops::observe_reserves(true, 9, *state.ops_state.get());
+        state.ops_state.set(ops);
+    });
+
+    assert_eq!(
+        super::reject_wrap_reason(),
+        Some("ops.wrap.paused".to_string())
+    );
+    let status = super::get_ops_status();
+    assert!(status.wrap_paused);
+    assert_eq!(status.last_reserves_check_ts, 9);
+}
+
+fn install_asset_limits(asset_id: &[u8], limits: evm_db::chain_data::AssetLimitsV1) {
+    with_state_mut(|state| {
+        state.asset_limits.insert(asset_id.to_vec(), limits);
+    });
+}
+
+fn limited_wrap_args(request_id: TxId, amount: u128) -> super::NormalizedSubmitWrapRequest {
+    super::NormalizedSubmitWrapRequest {
+        request_id,
+        kind: WrapAssetKind::Fungible,
+        asset_id: vec![1],
+        amount: super::u256_from_u128(amount).to_vec(),
+        evm_recipient: vec![0x55; 20],
+        gas_limit: 21_000,
+        max_fee_e8s: 100,
+        quoted_gas_price_wei: 200,
+        fee_ledger_canister: Principal::self_authenticating(b"fee-ledger"),
+        deadline_secs: super::WRAP_DEADLINE_DEFAULT_SECS,
+    }
+}
+
+#[test]
+fn fee_in_asset_wrap_skips_fee_stage_and_mints_net_amount() {
+    init_stable_state();
+    let caller = Principal::self_authenticating(b"wrap-caller");
+    let request_id = TxId([0xd9; 32]);
+
+    let req = super::ensure_wrap_request_before_fee(
+        limited_wrap_args(request_id, 1_000),
+        caller,
+        30,
+        8,
+        true,
+        super::current_time_nanos(),
+    )
+    .expect("insert");
+
+    assert!(req.fee_in_asset);
+    assert_eq!(req.result.stage, WrapRequestStage::FeeCollected);
+    assert_eq!(req.result.fee_ledger_tx_id, None);
+    assert_eq!(
+        super::wrap_mint_amount(&req),
+        Ok(super::u256_from_u128(970).to_vec())
+    );
+    let response =
+        super::existing_wrap_request_response(&limited_wrap_args(request_id, 1_000), caller)
+            .expect("existing")
+            .expect("idempotent response");
+    assert!(response.fee_ledger_tx_id.is_empty());
+    assert_eq!(response.charged_fee_e8s, Nat::from(30u8));
+}
+
+#[test]
+fn wrap_limits_reject_over_max_and_daily_cap_until_released() {
+    init_stable_state();
+    let caller = Principal::self_authenticating(b"wrap-caller");
+    let now = super::current_time_nanos();
+    install_asset_limits(
+        &[1],
+        evm_db::chain_data::AssetLimitsV1 {
+            max_single_wrap: Some(60),
+            wrap_daily_cap: Some(100),
+            ..Default::default()
+        },
+    );
+
+    assert_eq!(
+        super::ensure_wrap_request_before_fee(
+            limited_wrap_args(TxId([0xd1; 32]), 61),
+            caller,
+            7,
+            8,
+            false,
+            now,
+        )
+        .map(|_| ()),
+        Err("limits.wrap_amount_exceeds_max".to_string())
+    );
+    super::ensure_wrap_request_before_fee(
+        limited_wrap_args(TxId([0xd2; 32]), 60),
+        caller,
+        7,
+        8,
+        false,
+        now,
+    )
+    .expect("first wrap");
+    assert_eq!(
+        super::ensure_wrap_request_before_fee(
+            limited_wrap_args(TxId([0xd3; 32]), 41),
+            caller,
+            7,
+            8,
+            false,
+            now,
+        )
+        .map(|_| ()),
+        Err("limits.wrap_daily_cap_exceeded".to_string())
+    );
+    assert!(with_state(|state| state.wrap_requests.get(&TxId([0xd3; 32]))).is_none());
+
+    super::release_bridge_limit(&[1], super::BridgeDirection::Wrap, now, 60);
+    super::ensure_wrap_request_before_fee(
+        limited_wrap_args(TxId([0xd3; 32]), 41),
+        caller,
+        7,
+        8,
+        false,
+        now,
+    )
+    .expect("wrap after release");
+
+    let views = super::get_asset_limits().expect("limits");
+    assert_eq!(views.len(), 1);
+    assert_eq!(views[0].wrap_volume_24h, Nat::from(41u8));
+    assert_eq!(views[0].wrap_daily_cap, Some(Nat::from(100u8)));
+    assert_eq!(views[0].unwrap_volume_24h, Nat::from(0u8));
+}
+
+#[test]
+fn unwrap_dispatch_holds_requests_over_asset_limits_and_retry_rechecks() {
+    init_stable_state();
+    let asset = vec![0x55; 10];
+    let now = super::current_time_nanos();
+    install_asset_limits(
+        &asset,
+        evm_db::chain_data::AssetLimitsV1 {
+            unwrap_daily_cap: Some(150),
+            ..Default::default()
+        },
+    );
+    let queue = |request_id: TxId| {
+        with_state_mut(|state| {
+            let mut req = sample_unwrap_request(UnwrapRequestStatus::Queued, None, 1);
+            req.amount = super::u256_from_u128(100);
+            state.unwrap_requests.insert(request_id, req);
+            let mut meta = *state.unwrap_dispatch_meta.get();
+            let seq = meta.push();
+            state.unwrap_dispatch_meta.set(meta);
+            state.unwrap_dispatch_queue.insert(seq, request_id);
+        });
+    };
+    queue(TxId([0xe1; 32]));
+    queue(TxId([0xe2; 32]));
+
+    assert!(pop_next_dispatch_request(now).expect("pop").is_some());
+    let err = pop_next_dispatch_request(now).expect_err("held over cap");
+    assert!(err.starts_with("wrap.dispatch.limited:"));
+    let held = with_state(|state| state.unwrap_requests.get(&TxId([0xe2; 32]))).expect("held");
+    assert_eq!(held.status, UnwrapRequestStatus::DispatchFailed);
+    assert_eq!(
+        held.error_code.as_deref(),
+        Some("limits.unwrap_daily_cap_exceeded")
+    );
+    assert_eq!(held.transfer_created_at_time, 0);
+
+    match super::retry_unwrap_dispatch(vec![0xe2; 32]) {
+        Err(super::ApiError::Rejected(detail)) => {
+            assert_eq!(detail.code, "limits.unwrap_daily_cap_exceeded");
+        }
+        other => panic!("unexpected retry result: {other:?}"),
+    }
+
+    with_state_mut(|state| {
+        let mut limits = state.asset_limits.get(&asset).expect("limits");
+        limits.unwrap_daily_cap = None;
+        limits.paused = true;
+        state.asset_limits.insert(asset.clone(), limits);
+    });
+    match super::retry_unwrap_dispatch(vec![0xe2; 32]) {
+        Err(super::ApiError::Rejected(detail)) => {
+            assert_eq!(detail.code, "limits.asset_paused");
+        }
+        other => panic!("unexpected retry result: {other:?}"),
+    }
+}
+
+#[test]
+fn nft_wrap_requires_allowed_collection_and_counts_one_token_per_request() {
+    init_stable_state();
+    let caller = Principal::self_authenticating(b"wrap-caller");
+    let collection = Principal::self_authenticating(b"nft-collection");
+    let fee_ledger = Principal::self_authenticating(b"fee-ledger");
+    let quote = |collection_id: Principal| {
+        super::quote_nft_wrap_request(super::QuoteNftWrapRequestArgs {
+            collection_id,
+            token_id: Nat::from(7u8),
+            evm_recipient: vec![0x55; 20],
+            gas_limit: 21_000,
+        })
+    };
+    match quote(collection) {
+        Err(ApiError::Rejected(detail)) => assert_eq!(detail.code, "collection.not_allowed"),
+        other => panic!("unexpected quote result: {other:?}"),
+    }
+    with_state_mut(|state| {
+        state
+            .wrap_allowed_nft_collections
+            .insert(collection.as_slice().to_vec(), 1);
+        state
+            .wrap_fee_policy
+            .set(evm_db::chain_data::FeePolicyStored {
+                fee_ledger_canister: fee_ledger.as_slice().to_vec(),
+                cycle_fee_e8s: 1_000,
+                gas_price_buffer_bps: 10_000,
+            });
+    });
+    assert_eq!(
+        super::get_allowed_nft_collections().expect("collections"),
+        vec![collection]
+    );
+    let quoted = quote(collection).expect("quote");
+    assert_eq!(quoted.fee_ledger_canister, fee_ledger);
+    assert_eq!(quoted.cycle_fee_e8s, 1_000);
+
+    install_asset_limits(
+        &[1],
+        evm_db::chain_data::AssetLimitsV1 {
+            max_single_wrap: Some(1),
+            wrap_daily_cap: Some(2),
+            ..Default::default()
+        },
+    );
+    let nft_args = |request_id: TxId| super::NormalizedSubmitWrapRequest {
+        kind: WrapAssetKind::Nft,
+        ..limited_wrap_args(request_id, u128::MAX)
+    };
+    let now = super::current_time_nanos();
+    let req =
+        super::ensure_wrap_request_before_fee(nft_args(TxId([0xc1; 32])), caller, 7, 8, false, now)
+            .expect("first nft");
+    assert_eq!(req.kind, WrapAssetKind::Nft);
+    assert!(!super::wrap_request_holds_reserves(&req));
+    super::ensure_wrap_request_before_fee(nft_args(TxId([0xc2; 32])), caller, 7, 8, false, now)
+        .expect("second nft");
+    assert_eq!(
+        super::ensure_wrap_request_before_fee(
+            nft_args(TxId([0xc3; 32])),
+            caller,
+            7,
+            8,
+            false,
+            now,
+        )
+        .map(|_| ()),
+        Err("limits.wrap_daily_cap_exceeded".to_string())
+    );
+    let overview = super::get_request(vec![0xc1; 32]).expect("overview");
+    assert_eq!(overview.kind, super::RequestKind::NftWrap);
+
+    match super::existing_wrap_request_response(&limited_wrap_args(TxId([0xc1; 32]), 1), caller) {
+        Some(Err(ApiError::Rejected(detail))) => {
+            assert_eq!(detail.code, "request.idempotency_mismatch");
+        }
+        other => panic!("unexpected existing result: {other:?}"),
+    }
+}
+
+#[test]
+fn encode_factory_mint_nft_call_data_places_collection_bytes_last() {
+    let collection = Principal::self_authenticating(b"nft-collection");
+    let recipient = [0x55u8; 20];
+    let token_id = super::u256_from_u128(42);
+    let data =
+        super::encode_factory_mint_nft_call_data(collection.as_slice(), &recipient, &token_id)
+            .expect("encode");
+
+    assert_eq!(
+        data[..4],
+        super::selector(b"mintNftForCollection(bytes,address,uint256)")
+    );
+    assert_eq!(data[4..36], super::u256_from_u64(96));
+    assert_eq!(data[48..68], recipient);
+    assert_eq!(data[68..100], token_id);
+    assert_eq!(
+        data[100..132],
+        super::u256_from_u64(collection.as_slice().len() as u64)
+    );
+    assert_eq!(
+        data[132..132 + collection.as_slice().len()],
+        *collection.as_slice()
+    );
+    assert_eq!(data.len(), 132 + 32);
+    assert_eq!(
+        super::encode_factory_mint_nft_call_data(collection.as_slice(), &recipient, &[1u8; 8]),
+        Err("arg.token_id_invalid".to_string())
+    );
+}
+
+#[test]
+fn record_unwrap_requests_from_block_stores_nft_logs_and_limits_count_tokens() {
+    init_stable_state();
+    let tx_id = TxId([0x26u8; 32]);
+    let collection = vec![0x66u8; 10];
+    let token_id = [0xffu8; 32];
+    with_state_mut(|state| {
+        let receipt = ReceiptLike {
+            tx_id,
+            block_number: 10,
+            tx_index: 0,
+            status: 1,
+            gas_used: 1,
+            effective_gas_price: 1,
+            l1_data_fee: 0,
+            operator_fee: 0,
+            total_fee: 0,
+            return_data_hash: [0u8; 32],
+            return_data: Vec::new(),
+            contract_address: None,
+            logs: vec![log_entry_from_parts(
+                WRAP_PRECOMPILE_ADDRESS.into_array(),
+                vec![hash::keccak256(b"KasaneNftUnwrapRequest(bytes)")],
+                unwrap_log_data(&collection, token_id, &[0x09, 0x0a]),
+            )],
+        };
+        let ptr = state
+            .blob_store
+            .store_bytes(receipt.to_bytes().as_ref())
+            .expect("store receipt");
+        state.receipts.insert(tx_id, ptr);
+    });
+    install_asset_limits(
+        &collection,
+        evm_db::chain_data::AssetLimitsV1 {
+            unwrap_daily_cap: Some(1),
+            ..Default::default()
+        },
+    );
+
+    super::record_unwrap_requests_from_block(&[tx_id]);
+
+    let request_id = super::derive_log_request_id(&tx_id, 0).expect("request id");
+    assert_eq!(super::unwrap_request_ids_for_tx(&tx_id), vec![request_id]);
+    let req = with_state(|state| state.unwrap_requests.get(&request_id)).expect("request");
+    assert_eq!(req.kind, UnwrapAssetKind::Nft);
+    assert_eq!(req.asset_id, collection);
+    assert_eq!(req.amount, token_id);
+    assert_eq!(
+        super::get_request(request_id.0.to_vec())
+            .expect("overview")
+            .kind,
+        super::RequestKind::NftUnwrap
+    );
+    assert_eq!(super::reserve_in_flight_totals(&collection), (0, 0));
+    assert!(pop_next_dispatch_request(super::current_time_nanos())
+        .expect("pop")
+        .is_some());
+}
+
+#[test]
+fn wrap_expiry_refund_selects_only_expired_wraps_whose_mint_cannot_land() {
+    init_stable_state();
+    let expiring = |status: RequestStatus, stage: WrapRequestStage, deadline_at: u64| {
+        let mut req = sample_wrap_request(status);
+        req.amount = super::u256_from_u128(1_000).to_vec();
+        req.deadline_at = deadline_at;
+        req.result.stage = stage;
+        req.result.pull_ledger_tx_id = Some(vec![4]);
+        req
+    };
+    let mut failed = expiring(RequestStatus::Failed, WrapRequestStage::Failed, 50);
+    failed.result.mint_failed_recoverable = true;
+    let mut in_flight = expiring(RequestStatus::Running, WrapRequestStage::MintSubmitted, 50);
+    in_flight.result.mint_tx_id = Some(vec![0x77; 32]);
+    in_flight.result.mint_submit_status = MintSubmitStatus::Submitted;
+    let mut refunded = failed.clone();
+    refunded.result.withdrawn = true;
+    with_state_mut(|state| {
+        let requests = [
+            (
+                0xe1,
+                expiring(RequestStatus::Queued, WrapRequestStage::Pulled, 50),
+            ),
+            (0xe2, failed),
+            (0xe3, in_flight),
+            (
+                0xe4,
+                expiring(RequestStatus::Queued, WrapRequestStage::Pulled, 101),
+            ),
+            (
+                0xe5,
+                expiring(RequestStatus::Queued, WrapRequestStage::Pulled, 0),
+            ),
+            (0xe6, refunded),
+        ];
+        for (id, req) in requests {
+            state.wrap_requests.insert(TxId([id; 32]), req);
+        }
+    });
+
+    assert_eq!(
+        super::expired_wrap_refund_candidates(100),
+        vec![TxId([0xe1; 32]), TxId([0xe2; 32])]
+    );
+}
+
+#[test]
+fn wrap_expiry_refund_keeps_fees_and_moves_through_refunding() {
+    init_stable_state();
+    let request_id = TxId([0xe7; 32]);
+    let below_fee_id = TxId([0xe8; 32]);
+    let mut req = sample_wrap_request(RequestStatus::Queued);
+    req.amount = super::u256_from_u128(1_000).to_vec();
+    req.fee_in_asset = true;
+    req.deadline_at = 50;
+    req.result.charged_fee_e8s = Some(30);
+    req.result.stage = WrapRequestStage::Pulled;
+    req.result.pull_ledger_tx_id = Some(vec![4]);
+    let mut below_fee = req.clone();
+    below_fee.amount = super::u256_from_u128(35).to_vec();
+    with_state_mut(|state| {
+        state.wrap_requests.insert(request_id, req);
+        state.wrap_requests.insert(below_fee_id, below_fee);
+    });
+
+    assert!(super::begin_wrap_expiry_refund(below_fee_id, 10, 100)
+        .expect("begin")
+        .is_none());
+    let started = super::begin_wrap_expiry_refund(request_id, 10, 100)
+        .expect("begin")
+        .expect("refund started");
+
+    assert_eq!(started.result.refund_amount_e8s, Some(960));
+    assert_eq!(started.result.stage, WrapRequestStage::Refunding);
+    assert_eq!(started.result.status, RequestStatus::Failed);
+    assert!(started.result.withdraw_in_progress);
+    assert_eq!(started.withdraw_created_at_time, 100);
+    assert_eq!(
+        super::expired_wrap_refund_candidates(200),
+        Vec::<TxId>::new()
+    );
+
+    super::settle_wrap_refund(request_id, Err("ledger.transfer_failed".to_string()));
+    assert_eq!(super::expired_wrap_refund_candidates(200), vec![request_id]);
+    let retried = super::begin_wrap_expiry_refund(request_id, 99, 200)
+        .expect("begin")
+        .expect("refund restarted");
+    assert_eq!(retried.result.refund_amount_e8s, Some(960));
+    assert_eq!(retried.withdraw_created_at_time, 100);
+
+    super::settle_wrap_refund(request_id, Ok(vec![9]));
+    with_state(|state| {
+        let req = state.wrap_requests.get(&request_id).expect("request");
+        assert_eq!(req.result.stage, WrapRequestStage::Refunded);
+        assert!(req.result.withdrawn);
+        assert_eq!(req.result.withdraw_ledger_tx_id, Some(vec![9]));
+        let below_fee = state.wrap_requests.get(&below_fee_id).expect("request");
+        assert_eq!(
+            below_fee.result.withdraw_error_code.as_deref(),
+            Some(super::WRAP_REFUND_BELOW_FEE_CODE)
+        );
+        assert_eq!(below_fee.result.stage, WrapRequestStage::Pulled);
+    });
+}
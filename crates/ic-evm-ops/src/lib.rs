@@ -45,6 +45,23 @@ pub fn reject_write_reason(needs_migration: bool, mode: OpsMode) -> Option<Strin
     None
 }
 
+/// 新しい wrap だけを止める。unwrap と返金は準備金を減らす側ではないので通す。
+pub fn reject_wrap_reason(ops: OpsStateV1) -> Option<String> {
+    if ops.wrap_paused {
+        return Some("ops.wrap.paused".to_string());
+    }
+    None
+}
+
+/// 不足を見たら pause を立てるが、足りていても自動では下ろさない。
+pub fn observe_reserves(shortfall: bool, now: u64, mut ops: OpsStateV1) -> OpsStateV1 {
+    if shortfall {
+        ops.wrap_paused = true;
+    }
+    ops.last_reserves_check_ts = now;
+    ops
+}
+
 pub fn reject_write_reason_with_mode_provider<F>(
     needs_migration: bool,
     mode_provider: F,
@@ -87,8 +104,8 @@ pub fn decode_failure_label_view(raw: [u8; 32]) -> Option<String> {
 #[cfg(test)]
 mod tests {
     use super::{
-        decode_failure_label_view, observe_cycles, reject_write_reason,
-        reject_write_reason_with_mode_provider,
+        decode_failure_label_view, observe_cycles, observe_reserves, reject_wrap_reason,
+        reject_write_reason, reject_write_reason_with_mode_provider,
     };
     use evm_db::chain_data::{OpsConfigV1, OpsMode, OpsStateV1};
 
@@ -104,6 +121,23 @@ mod tests {
         assert!(state.safe_stop_latched);
     }
 
+    #[test]
+    fn reserves_shortfall_latches_wrap_pause_until_cleared() {
+        let paused = observe_reserves(true, 5, OpsStateV1::new());
+        assert!(paused.wrap_paused);
+        assert_eq!(paused.last_reserves_check_ts, 5);
+        assert_eq!(
+            reject_wrap_reason(paused),
+            Some("ops.wrap.paused".to_string())
+        );
+
+        let still_paused = observe_reserves(false, 6, paused);
+        assert!(still_paused.wrap_paused);
+        let cycles = observe_cycles(u128::MAX, 7, OpsConfigV1::new(), still_paused);
+        assert!(cycles.wrap_paused);
+        assert_eq!(reject_wrap_reason(OpsStateV1::new()), None);
+    }
+
     #[test]
     fn reject_reason_priority() {
         assert_eq!(
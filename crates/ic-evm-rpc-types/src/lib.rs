@@ -1,6 +1,6 @@
 //! どこで: canister内の共有DTO層 / 何を: Candid公開型を集約 / なぜ: wrapper分割時もAPI互換を保つため
 
-use candid::{CandidType, Nat};
+use candid::{CandidType, Nat, Principal};
 use serde::Deserialize;
 
 #[derive(Clone, Debug, CandidType, Deserialize)]
@@ -17,7 +17,29 @@ pub struct ExecResultDto {
 pub enum LookupError {
     NotFound,
     Pending,
-    Pruned { pruned_before_block: u64 },
+    Pruned {
+        pruned_before_block: u64,
+    },
+    /// prune 済みの履歴は archive canister に問い合わせる
+    Archived {
+        ranges: Vec<ArchivedRangeView>,
+    },
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize, Eq, PartialEq)]
+pub struct ArchivedRangeView {
+    pub canister_id: Principal,
+    pub start_block: u64,
+    pub end_block: u64,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct ArchiveStatusView {
+    pub canister_id: Option<Principal>,
+    pub acked_through: Option<u64>,
+    pub acked_at: u64,
+    pub next_block: u64,
+    pub ranges: Vec<ArchivedRangeView>,
 }
 
 #[derive(Clone, Debug, CandidType, Deserialize)]
@@ -52,6 +74,20 @@ pub enum OpsModeView {
     Critical,
 }
 
+/// migration_id/rollback は Rewrite・Verify 中か、その migration で失敗したときだけ入る。
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct SchemaMigrationStatusView {
+    pub phase: String,
+    pub from_version: u32,
+    pub to_version: u32,
+    pub migration_id: Option<String>,
+    pub migration_from_version: Option<u32>,
+    pub migration_to_version: Option<u32>,
+    pub processed: u64,
+    pub last_error: u32,
+    pub rollback: Option<String>,
+}
+
 #[derive(Clone, Debug, CandidType, Deserialize)]
 pub struct OpsStatusView {
     pub config: OpsConfigView,
@@ -59,8 +95,11 @@ pub struct OpsStatusView {
     pub last_check_ts: u64,
     pub mode: OpsModeView,
     pub safe_stop_latched: bool,
+    pub wrap_paused: bool,
+    pub last_reserves_check_ts: u64,
     pub needs_migration: bool,
     pub schema_version: u32,
+    pub schema_migration: SchemaMigrationStatusView,
     pub log_filter_override: Option<String>,
     pub log_truncated_count: u64,
     pub critical_corrupt: bool,
@@ -148,6 +187,61 @@ pub struct LogView {
     pub data: Vec<u8>,
 }
 
+/// certificate は IC の data certificate、witness は certified data の root へ繋がる CBOR の hash tree。
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct CertifiedBlockView {
+    pub block: BlockView,
+    pub certificate: Vec<u8>,
+    pub witness: Vec<u8>,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct CertifiedReceiptView {
+    pub receipt: ReceiptView,
+    pub certificate: Vec<u8>,
+    pub witness: Vec<u8>,
+}
+
+/// account_proof / proof は state root から leaf へ並ぶ RLP node（eth_getProof と同じ形）。
+#[derive(Clone, Debug, CandidType, Deserialize, Eq, PartialEq)]
+pub struct AccountProofView {
+    pub address: Vec<u8>,
+    pub nonce: u64,
+    pub balance: Vec<u8>,
+    pub storage_root: Vec<u8>,
+    pub code_hash: Vec<u8>,
+    pub account_proof: Vec<Vec<u8>>,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize, Eq, PartialEq)]
+pub struct StorageProofView {
+    pub slot: Vec<u8>,
+    pub value: Vec<u8>,
+    pub proof: Vec<Vec<u8>>,
+}
+
+/// witness は certified data の last_block_state_root を開示する。値は block_number 時点のもの。
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct CertifiedAccountView {
+    pub block_number: u64,
+    pub state_root: Vec<u8>,
+    pub certificate: Vec<u8>,
+    pub witness: Vec<u8>,
+    pub account: AccountProofView,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct CertifiedStorageView {
+    pub state: CertifiedAccountView,
+    pub storage: StorageProofView,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct CertifiedCodeView {
+    pub state: CertifiedAccountView,
+    pub code: Vec<u8>,
+}
+
 #[derive(Clone, Debug, CandidType, Deserialize)]
 pub struct QueueItemView {
     pub seq: u64,
@@ -170,6 +264,7 @@ pub struct HealthView {
     pub auto_production_enabled: bool,
     pub is_producing: bool,
     pub mining_scheduled: bool,
+    pub optimistic_exec_enabled: bool,
     pub block_gas_limit: u64,
     pub query_instruction_soft_limit: u64,
     pub update_instruction_soft_limit: u64,
@@ -227,6 +322,76 @@ pub struct PruneResultView {
     pub pruned_before_block: Option<u64>,
 }
 
+#[derive(Clone, Copy, Debug, CandidType, Deserialize, Eq, PartialEq)]
+pub enum BlobCompactionPhaseView {
+    Idle,
+    Relocating,
+    Trimming,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct BlobCompactionStatusView {
+    pub phase: BlobCompactionPhaseView,
+    pub did_work: bool,
+    pub blocked: Option<String>,
+    pub passes: u64,
+    pub relocated_blobs: u64,
+    pub relocated_bytes: u64,
+    pub trimmed_bytes: u64,
+    pub live_bytes: u64,
+    pub arena_end_bytes: u64,
+    pub reclaimable_bytes: u64,
+}
+
+#[derive(Clone, Copy, Debug, CandidType, Deserialize, Eq, PartialEq)]
+pub enum BlobRecompressPhaseView {
+    Pending,
+    Running,
+    Done,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct BlobRecompressStatusView {
+    pub phase: BlobRecompressPhaseView,
+    pub blocked: Option<String>,
+    pub scanned: u64,
+    pub recompressed_blobs: u64,
+    pub saved_bytes: u64,
+}
+
+#[derive(Clone, Copy, Debug, CandidType, Deserialize, Eq, PartialEq)]
+pub enum ScrubPhaseView {
+    Idle,
+    Running,
+    Paused,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct ScrubStatusView {
+    pub phase: ScrubPhaseView,
+    pub target: String,
+    pub auto_repair: bool,
+    pub did_work: bool,
+    pub blocked: Option<String>,
+    pub run_id: u64,
+    pub started_at: u64,
+    pub finished_at: u64,
+    pub scanned: u64,
+    pub findings: u64,
+    pub repaired: u64,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct ScrubFindingView {
+    pub seq: u64,
+    pub run_id: u64,
+    pub target: String,
+    pub kind: String,
+    pub key: Vec<u8>,
+    pub repaired: bool,
+    pub detected_at: u64,
+}
+
 #[derive(Clone, Debug, CandidType, Deserialize)]
 pub enum EthTxListView {
     Hashes(Vec<Vec<u8>>),
@@ -298,6 +463,7 @@ pub struct EthReceiptView {
     pub contract_address: Option<Vec<u8>>,
     pub tx_type: Option<u8>,
     pub logs: Vec<EthReceiptLogView>,
+    pub revert_reason: Option<RevertReasonView>,
 }
 
 #[derive(Clone, Debug, CandidType, Deserialize)]
@@ -342,6 +508,8 @@ pub struct EthLogsCursorView {
 pub struct EthLogsPageView {
     pub items: Vec<EthLogItemView>,
     pub next_cursor: Option<EthLogsCursorView>,
+    /// 要求範囲のうち prune 済みで archive canister にある部分
+    pub archived: Vec<ArchivedRangeView>,
 }
 
 #[derive(Clone, Debug, CandidType, Deserialize)]
@@ -372,6 +540,18 @@ pub struct RpcCallResultView {
     pub gas_used: u64,
     pub return_data: Vec<u8>,
     pub revert_data: Option<Vec<u8>>,
+    pub revert_reason: Option<RevertReasonView>,
+}
+
+/// revert dataの解釈結果。`message` は geth の `execution reverted: <reason>` と同じ形。
+#[derive(Clone, Debug, CandidType, Deserialize, Eq, PartialEq)]
+pub struct RevertReasonView {
+    pub message: String,
+    /// `Error(string)` の文字列、または `Panic(uint256)` の説明
+    pub reason: Option<String>,
+    pub panic_code: Option<u64>,
+    /// `Error` / `Panic` 以外のcustom errorのselector
+    pub custom_error_selector: Option<Vec<u8>>,
 }
 
 #[derive(Clone, Debug, CandidType, Deserialize, Eq, PartialEq)]
@@ -423,11 +603,28 @@ pub enum RpcBlockLookupView {
 #[derive(Clone, Debug, CandidType, Deserialize)]
 pub enum RpcReceiptLookupView {
     Found(Box<EthReceiptView>),
+    Dropped(Box<DroppedTxView>),
     Pruned { pruned_before_block: u64 },
     PossiblyPruned { pruned_before_block: u64 },
     NotFound,
 }
 
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct DroppedTxView {
+    pub tx_hash: Vec<u8>,
+    pub eth_tx_hash: Option<Vec<u8>>,
+    pub tx_type: u8,
+    pub from: Option<Vec<u8>>,
+    pub nonce: Option<u64>,
+    pub drop_code: u16,
+    pub reason: String,
+    pub block_attempted: Option<u64>,
+    pub dropped_at: u64,
+    pub max_fee_per_gas: u128,
+    pub max_priority_fee_per_gas: u128,
+    pub base_fee_per_gas: u64,
+}
+
 #[derive(Clone, Debug, CandidType, Deserialize)]
 pub struct ExportCursorView {
     pub block_number: u64,
@@ -457,6 +654,62 @@ pub enum ExportErrorView {
     Limit,
 }
 
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct StateSnapshotManifestView {
+    pub head_number: u64,
+    pub block_hash: Vec<u8>,
+    pub timestamp: u64,
+    pub state_root: Vec<u8>,
+    pub evm_state_epoch: u64,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct StateSnapshotCursorView {
+    pub head_number: u64,
+    pub evm_state_epoch: u64,
+    pub segment: u8,
+    pub last_key: Option<Vec<u8>>,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct StateSnapshotPageView {
+    pub manifest: StateSnapshotManifestView,
+    pub segment: u8,
+    pub entry_count: u32,
+    pub entries: Vec<u8>,
+    pub next_cursor: Option<StateSnapshotCursorView>,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub enum StateSnapshotImportOpView {
+    Begin(StateSnapshotManifestView),
+    /// 取り込み先の既存状態を `max_steps` 件ずつ消す。Loading になるまで繰り返す。
+    Clear {
+        max_steps: u32,
+    },
+    Chunk(StateSnapshotPageView),
+    Finish {
+        max_steps: u32,
+    },
+}
+
+#[derive(Clone, Copy, Debug, CandidType, Deserialize, Eq, PartialEq)]
+pub enum StateSnapshotImportPhaseView {
+    Idle,
+    Clearing,
+    Loading,
+    Verifying,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct StateSnapshotImportStatusView {
+    pub phase: StateSnapshotImportPhaseView,
+    pub manifest: StateSnapshotManifestView,
+    pub accounts: u64,
+    pub storage: u64,
+    pub codes: u64,
+}
+
 #[derive(Clone, Debug, CandidType, Deserialize)]
 pub enum PendingStatusView {
     Queued { seq: u64 },
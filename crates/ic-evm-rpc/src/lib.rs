@@ -1,19 +1,26 @@
 //! どこで: wrapperのRPC補助層 / 何を: eth系参照ロジックを分離 / なぜ: canister entrypointの責務を薄くするため
 
+pub mod revert;
+
 use evm_core::revm_exec::ExecError;
 use evm_core::{chain, hash};
-use evm_db::chain_data::constants::CHAIN_ID;
+use evm_db::chain_data::constants::{
+    CHAIN_ID, DROP_CODE_BLOCK_GAS_EXCEEDED, DROP_CODE_CALLER_MISSING, DROP_CODE_DECODE,
+    DROP_CODE_EXEC, DROP_CODE_EXEC_PRECHECK, DROP_CODE_INSTRUCTION_BUDGET, DROP_CODE_INVALID_FEE,
+    DROP_CODE_MISSING, DROP_CODE_REPLACED, DROP_CODE_RESULT_TOO_LARGE,
+};
 use evm_db::chain_data::{
-    BlockData, ReceiptLike, StoredTx, StoredTxBytes, TxId, TxKind, TxLoc, TxLocKind,
+    BlockData, DropRecordV1, ReceiptLike, StoredTx, StoredTxBytes, TxId, TxKind, TxLoc, TxLocKind,
 };
 use evm_db::stable_state::with_state;
 use evm_db::types::keys::{make_account_key, make_code_key, make_storage_key};
 use ic_evm_rpc_types::{
-    DecodedTxView, EthBlockView, EthLogFilterView, EthLogItemView, EthLogsCursorView,
-    EthLogsPageView, EthReceiptLogView, EthReceiptView, EthTxListView, EthTxView, GetLogsErrorView,
+    AccountProofView, ArchivedRangeView, DecodedTxView, DroppedTxView, EthBlockView,
+    EthLogFilterView, EthLogItemView, EthLogsCursorView, EthLogsPageView, EthReceiptLogView,
+    EthReceiptView, EthTxListView, EthTxView, GetLogsErrorView, RevertReasonView,
     RpcAccessListItemView, RpcBlockLookupView, RpcBlockTagView, RpcCallObjectView,
     RpcCallResultView, RpcErrorView, RpcFeeHistoryView, RpcHistoryWindowView, RpcReceiptLookupView,
-    SubmitTxError, TxKindView,
+    StorageProofView, SubmitTxError, TxKindView,
 };
 use tracing::{error, warn};
 
@@ -70,10 +77,14 @@ pub fn rpc_eth_get_transaction_receipt_by_eth_hash(eth_tx_hash: Vec<u8>) -> Opti
 pub fn rpc_eth_get_transaction_receipt_with_status_by_eth_hash(
     eth_tx_hash: Vec<u8>,
 ) -> RpcReceiptLookupView {
-    let Some(tx_id) = find_eth_tx_id_by_eth_hash_bytes(&eth_tx_hash) else {
-        return RpcReceiptLookupView::NotFound;
-    };
-    receipt_lookup_status(tx_id)
+    if let Some(tx_id) = find_eth_tx_id_by_eth_hash_bytes(&eth_tx_hash) {
+        return receipt_lookup_status(tx_id);
+    }
+    // drop済みTxはeth hash索引から外れるため、drop記録側の索引で引く。
+    match parse_hash_32(eth_tx_hash).and_then(|hash| chain::get_drop_record_by_eth_hash(&hash)) {
+        Some(record) => RpcReceiptLookupView::Dropped(Box::new(drop_record_to_view(record))),
+        None => RpcReceiptLookupView::NotFound,
+    }
 }
 
 pub fn rpc_eth_get_transaction_receipt_with_status_by_tx_id(
@@ -181,6 +192,76 @@ pub fn rpc_eth_get_storage_at(
     Ok(value)
 }
 
+/// 現在の accounts ではなく、指定 state root の trie から値と proof を読む。
+pub fn rpc_eth_account_proof(
+    address: Vec<u8>,
+    state_root: [u8; 32],
+) -> Result<AccountProofView, RpcErrorView> {
+    let addr = parse_address_20_with_label(address, "address")
+        .map_err(|message| invalid_error("invalid.address", message))?;
+    let proof =
+        evm_core::state_proof::prove_account(state_root, addr).map_err(state_proof_error_to_rpc)?;
+    Ok(AccountProofView {
+        address: addr.to_vec(),
+        nonce: proof.nonce,
+        balance: proof.balance.to_vec(),
+        storage_root: proof.storage_root.to_vec(),
+        code_hash: proof.code_hash.to_vec(),
+        account_proof: proof.proof,
+    })
+}
+
+pub fn rpc_eth_storage_proof(
+    account: &AccountProofView,
+    slot: Vec<u8>,
+) -> Result<StorageProofView, RpcErrorView> {
+    let slot32 = parse_hash_32(slot)
+        .ok_or_else(|| invalid_error("invalid.slot", "slot must be 32 bytes"))?;
+    let storage_root = parse_hash_32(account.storage_root.clone())
+        .ok_or_else(|| execution_error("state_proof.corrupt", "storage root must be 32 bytes"))?;
+    let proof = evm_core::state_proof::prove_storage(storage_root, slot32)
+        .map_err(state_proof_error_to_rpc)?;
+    Ok(StorageProofView {
+        slot: slot32.to_vec(),
+        value: proof.value.to_vec(),
+        proof: proof.proof,
+    })
+}
+
+/// code は proof 済みの code_hash で引く。空 account の KECCAK_EMPTY では空を返す。
+pub fn rpc_eth_code_for_proof(account: &AccountProofView) -> Result<Vec<u8>, RpcErrorView> {
+    let code_hash = parse_hash_32(account.code_hash.clone())
+        .ok_or_else(|| execution_error("state_proof.corrupt", "code hash must be 32 bytes"))?;
+    if code_hash == evm_core::hash::keccak256(&[]) {
+        return Ok(Vec::new());
+    }
+    with_state(|state| {
+        state
+            .codes
+            .get(&make_code_key(code_hash))
+            .map(|value| value.0)
+    })
+    .ok_or_else(|| {
+        execution_error(
+            "state_proof.code_missing",
+            "code for proven hash is missing",
+        )
+    })
+}
+
+fn state_proof_error_to_rpc(err: evm_core::state_proof::StateProofError) -> RpcErrorView {
+    match err {
+        evm_core::state_proof::StateProofError::MissingNode(_) => execution_error(
+            "state_proof.node_missing",
+            format!("state proof failed: {err:?}"),
+        ),
+        evm_core::state_proof::StateProofError::CorruptNode => execution_error(
+            "state_proof.corrupt",
+            format!("state proof failed: {err:?}"),
+        ),
+    }
+}
+
 const RPC_ERR_INVALID_PARAMS: u32 = 1001;
 const RPC_ERR_EXECUTION_FAILED: u32 = 2001;
 const MAX_FEE_HISTORY_BLOCKS: u64 = 256;
@@ -212,6 +293,9 @@ fn execution_error(prefix: &str, message: impl Into<String>) -> RpcErrorView {
 fn execution_error_for_chain_error(default_prefix: &str, err: chain::ChainError) -> RpcErrorView {
     let prefix = match &err {
         chain::ChainError::ExecFailed(Some(ExecError::SnapshotChanged)) => "exec.snapshot.changed",
+        chain::ChainError::ExecFailed(Some(ExecError::Revert)) => {
+            return execution_error("exec.reverted", revert::decode_revert(&[]).message);
+        }
         _ => default_prefix,
     };
     execution_error(prefix, format!("eth_call_object failed: {err:?}"))
@@ -226,6 +310,11 @@ pub fn rpc_eth_call_object(call: RpcCallObjectView) -> Result<RpcCallResultView,
         status: out.status,
         gas_used: out.gas_used,
         return_data: out.return_data,
+        revert_reason: revert::call_revert_reason(
+            out.status,
+            out.halt_reason,
+            out.revert_data.as_deref().unwrap_or_default(),
+        ),
         revert_data: out.revert_data,
     })
 }
@@ -247,6 +336,11 @@ where
         status: out.status,
         gas_used: out.gas_used,
         return_data: out.return_data,
+        revert_reason: revert::call_revert_reason(
+            out.status,
+            out.halt_reason,
+            out.revert_data.as_deref().unwrap_or_default(),
+        ),
         revert_data: out.revert_data,
     })
 }
@@ -571,8 +665,13 @@ pub fn rpc_eth_get_logs_paged(
     };
 
     let pruned_before = with_state(|state| state.prune_state.get().pruned_before());
+    let mut archived = Vec::new();
     if let Some(pruned) = pruned_before {
         if from <= pruned {
+            archived = evm_core::archive::archived_ranges_overlapping(from, pruned.min(to))
+                .into_iter()
+                .map(archive_range_to_view)
+                .collect();
             from = pruned.saturating_add(1);
         }
     }
@@ -618,6 +717,7 @@ pub fn rpc_eth_get_logs_paged(
                         tx_index: u32::try_from(tx_pos).unwrap_or(u32::MAX),
                         log_index: 0,
                     }),
+                    archived,
                 });
             }
             let Some(receipt) = chain::get_receipt(tx_id) else {
@@ -679,6 +779,7 @@ pub fn rpc_eth_get_logs_paged(
                             log_index: u32::try_from(log_index.saturating_add(1))
                                 .unwrap_or(u32::MAX),
                         }),
+                        archived,
                     });
                 }
             }
@@ -689,6 +790,7 @@ pub fn rpc_eth_get_logs_paged(
     Ok(EthLogsPageView {
         items: out,
         next_cursor: None,
+        archived,
     })
 }
 
@@ -1007,6 +1109,7 @@ fn receipt_to_eth_view(receipt: ReceiptLike) -> EthReceiptView {
     let block_hash = chain::get_block(receipt.block_number).map(|block| block.block_hash.to_vec());
     let base_log_index = base_log_index_for_receipt(&receipt);
     let cumulative_gas_used = cumulative_gas_used_for_receipt(&receipt);
+    let revert_reason = receipt_revert_reason(&receipt);
     EthReceiptView {
         tx_hash: receipt.tx_id.0.to_vec(),
         eth_tx_hash,
@@ -1040,9 +1143,14 @@ fn receipt_to_eth_view(receipt: ReceiptLike) -> EthReceiptView {
                 log_index: base_log_index.saturating_add(u32::try_from(idx).unwrap_or(u32::MAX)),
             })
             .collect(),
+        revert_reason,
     }
 }
 
+fn receipt_revert_reason(receipt: &ReceiptLike) -> Option<RevertReasonView> {
+    revert::receipt_revert_reason(receipt.status, &receipt.return_data)
+}
+
 fn cumulative_gas_used_for_receipt(receipt: &ReceiptLike) -> u64 {
     let Some(block) = chain::get_block(receipt.block_number) else {
         warn!(
@@ -1468,6 +1576,9 @@ fn receipt_lookup_status(tx_id: TxId) -> RpcReceiptLookupView {
     if let Some(receipt) = chain::get_receipt(&tx_id) {
         return RpcReceiptLookupView::Found(Box::new(receipt_to_eth_view(receipt)));
     }
+    if let Some(record) = chain::get_drop_record(&tx_id) {
+        return RpcReceiptLookupView::Dropped(Box::new(drop_record_to_view(record)));
+    }
     let pruned_before = with_state(|state| state.prune_state.get().pruned_before());
     let loc = chain::get_tx_loc(&tx_id);
     if let Some(loc) = loc {
@@ -1490,6 +1601,47 @@ fn receipt_lookup_status(tx_id: TxId) -> RpcReceiptLookupView {
     RpcReceiptLookupView::NotFound
 }
 
+pub fn archive_range_to_view(range: evm_core::archive::ArchiveRange) -> ArchivedRangeView {
+    ArchivedRangeView {
+        canister_id: candid::Principal::from_slice(&range.canister),
+        start_block: range.start_block,
+        end_block: range.end_block,
+    }
+}
+
+fn drop_record_to_view(record: DropRecordV1) -> DroppedTxView {
+    DroppedTxView {
+        tx_hash: record.tx_id.0.to_vec(),
+        eth_tx_hash: record.eth_tx_hash.map(|hash| hash.to_vec()),
+        tx_type: record.kind,
+        from: record.sender.map(|sender| sender.to_vec()),
+        nonce: record.nonce,
+        drop_code: record.drop_code,
+        reason: drop_reason(record.drop_code).to_string(),
+        block_attempted: record.block_attempted,
+        dropped_at: record.dropped_at,
+        max_fee_per_gas: record.max_fee_per_gas,
+        max_priority_fee_per_gas: record.max_priority_fee_per_gas,
+        base_fee_per_gas: record.base_fee,
+    }
+}
+
+pub fn drop_reason(drop_code: u16) -> &'static str {
+    match drop_code {
+        DROP_CODE_DECODE => "transaction could not be decoded",
+        DROP_CODE_EXEC => "execution failed before a receipt could be produced",
+        DROP_CODE_MISSING => "transaction payload was missing",
+        DROP_CODE_CALLER_MISSING => "caller could not be resolved",
+        DROP_CODE_INVALID_FEE => "fee no longer satisfies base fee or minimum priority fee",
+        DROP_CODE_REPLACED => "replaced by another transaction or evicted from a full queue",
+        DROP_CODE_RESULT_TOO_LARGE => "execution result exceeded the size limit",
+        DROP_CODE_BLOCK_GAS_EXCEEDED => "gas limit exceeds the block gas limit",
+        DROP_CODE_INSTRUCTION_BUDGET => "exceeded the per-block instruction budget",
+        DROP_CODE_EXEC_PRECHECK => "rejected by execution precheck",
+        _ => "unknown drop reason",
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::{
@@ -0,0 +1,253 @@
+//! どこで: receipt/eth_call のrevert表示 / 何を: Error(string)・Panic(uint256)・custom errorの解釈 / なぜ: gethと同じ `execution reverted: <reason>` を返すため
+
+use evm_core::revm_exec::OpHaltReason;
+use ic_evm_rpc_types::RevertReasonView;
+
+const ERROR_STRING_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+const PANIC_SELECTOR: [u8; 4] = [0x4e, 0x48, 0x7b, 0x71];
+const EXECUTION_REVERTED: &str = "execution reverted";
+// 壊れたrevert dataをmessageへ載せる上限。上限を超えた分は省略する。
+const MALFORMED_HEX_MAX_BYTES: usize = 64;
+
+/// eth_call の失敗表示。halt（out of gas / invalid opcode 等）は revert ではないので理由を付けない。
+pub fn call_revert_reason(
+    status: u8,
+    halt_reason: Option<OpHaltReason>,
+    data: &[u8],
+) -> Option<RevertReasonView> {
+    (status == 0 && halt_reason.is_none()).then(|| decode_revert(data))
+}
+
+/// 保存済みreceiptの失敗表示。receipt は halt 理由を持たず、halt の戻り値は常に空なので、
+/// 戻り値が空の失敗は revert と断定しない。
+pub fn receipt_revert_reason(status: u8, return_data: &[u8]) -> Option<RevertReasonView> {
+    (status == 0 && !return_data.is_empty()).then(|| decode_revert(return_data))
+}
+
+/// revert dataを解釈する。空のrevertも geth と同じく理由なしの `execution reverted` とする。
+/// `Error(string)` / `Panic(uint256)` の形が壊れている場合は custom error とせず、生データを示す。
+pub fn decode_revert(data: &[u8]) -> RevertReasonView {
+    if data.is_empty() {
+        return reverted(None, None, None);
+    }
+    let Some((selector, body)) = data.split_first_chunk::<4>() else {
+        return malformed(data);
+    };
+    if *selector == ERROR_STRING_SELECTOR {
+        return match decode_abi_string(body) {
+            Some(reason) => reverted(Some(reason), None, None),
+            None => malformed(data),
+        };
+    }
+    if *selector == PANIC_SELECTOR {
+        return match decode_panic_code(body) {
+            Some(code) => reverted(Some(panic_reason(code)), Some(code), None),
+            None => malformed(data),
+        };
+    }
+    reverted(None, None, Some(selector.to_vec()))
+}
+
+fn malformed(data: &[u8]) -> RevertReasonView {
+    let shown = &data[..data.len().min(MALFORMED_HEX_MAX_BYTES)];
+    let hex: String = shown.iter().map(|byte| format!("{byte:02x}")).collect();
+    let ellipsis = if shown.len() < data.len() { "..." } else { "" };
+    RevertReasonView {
+        message: format!("{EXECUTION_REVERTED}: malformed revert data 0x{hex}{ellipsis}"),
+        reason: None,
+        panic_code: None,
+        custom_error_selector: None,
+    }
+}
+
+fn reverted(
+    reason: Option<String>,
+    panic_code: Option<u64>,
+    custom_error_selector: Option<Vec<u8>>,
+) -> RevertReasonView {
+    let message = match reason.as_deref() {
+        Some(reason) => format!("{EXECUTION_REVERTED}: {reason}"),
+        None => EXECUTION_REVERTED.to_string(),
+    };
+    RevertReasonView {
+        message,
+        reason,
+        panic_code,
+        custom_error_selector,
+    }
+}
+
+// ABIの動的string: offset(32) / length(32) / data。offsetはselector後のbody先頭からの位置。
+fn decode_abi_string(body: &[u8]) -> Option<String> {
+    let offset = read_word_usize(body, 0)?;
+    let len = read_word_usize(body, offset)?;
+    let start = offset.checked_add(32)?;
+    let end = start.checked_add(len)?;
+    let bytes = body.get(start..end)?;
+    String::from_utf8(bytes.to_vec()).ok()
+}
+
+fn decode_panic_code(body: &[u8]) -> Option<u64> {
+    let word = body.get(0..32)?;
+    if word[..24].iter().any(|byte| *byte != 0) {
+        return None;
+    }
+    let mut out = [0u8; 8];
+    out.copy_from_slice(&word[24..32]);
+    Some(u64::from_be_bytes(out))
+}
+
+fn read_word_usize(body: &[u8], at: usize) -> Option<usize> {
+    let end = at.checked_add(32)?;
+    let word = body.get(at..end)?;
+    if word[..24].iter().any(|byte| *byte != 0) {
+        return None;
+    }
+    let mut out = [0u8; 8];
+    out.copy_from_slice(&word[24..32]);
+    usize::try_from(u64::from_be_bytes(out)).ok()
+}
+
+// solc の Panic コード表（geth の abi.panicReasons と同じ文言）。
+fn panic_reason(code: u64) -> String {
+    let known = match code {
+        0x00 => "generic panic",
+        0x01 => "assert(false)",
+        0x11 => "arithmetic underflow or overflow",
+        0x12 => "division or modulo by zero",
+        0x21 => "enum overflow",
+        0x22 => "invalid encoded storage byte array accessed",
+        0x31 => "out-of-bounds array access; popping on an empty array",
+        0x32 => "out-of-bounds access of an array or bytesN",
+        0x41 => "out of memory",
+        0x51 => "uninitialized function",
+        _ => return format!("unknown panic code: {code:#x}"),
+    };
+    known.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        call_revert_reason, decode_revert, receipt_revert_reason, ERROR_STRING_SELECTOR,
+        MALFORMED_HEX_MAX_BYTES, PANIC_SELECTOR,
+    };
+    use evm_core::revm_exec::OpHaltReason;
+
+    fn word(value: u64) -> [u8; 32] {
+        let mut out = [0u8; 32];
+        out[24..].copy_from_slice(&value.to_be_bytes());
+        out
+    }
+
+    #[test]
+    fn decodes_error_string() {
+        let mut data = ERROR_STRING_SELECTOR.to_vec();
+        data.extend_from_slice(&word(32));
+        data.extend_from_slice(&word(13));
+        let mut text = b"not the owner".to_vec();
+        text.resize(32, 0);
+        data.extend_from_slice(&text);
+        let view = decode_revert(&data);
+        assert_eq!(view.message, "execution reverted: not the owner");
+        assert_eq!(view.reason.as_deref(), Some("not the owner"));
+        assert_eq!(view.panic_code, None);
+        assert_eq!(view.custom_error_selector, None);
+    }
+
+    #[test]
+    fn decodes_panic_code() {
+        let mut data = PANIC_SELECTOR.to_vec();
+        data.extend_from_slice(&word(0x11));
+        let view = decode_revert(&data);
+        assert_eq!(
+            view.message,
+            "execution reverted: arithmetic underflow or overflow"
+        );
+        assert_eq!(view.panic_code, Some(0x11));
+
+        let mut unknown = PANIC_SELECTOR.to_vec();
+        unknown.extend_from_slice(&word(0x99));
+        assert_eq!(
+            decode_revert(&unknown).reason.as_deref(),
+            Some("unknown panic code: 0x99")
+        );
+    }
+
+    #[test]
+    fn keeps_custom_errors_as_plain_revert() {
+        let custom = decode_revert(&[0xde, 0xad, 0xbe, 0xef, 0x01]);
+        assert_eq!(custom.message, "execution reverted");
+        assert_eq!(
+            custom.custom_error_selector,
+            Some(vec![0xde, 0xad, 0xbe, 0xef])
+        );
+
+        let empty = decode_revert(&[]);
+        assert_eq!(empty.message, "execution reverted");
+        assert_eq!(empty.custom_error_selector, None);
+    }
+
+    #[test]
+    fn reports_malformed_error_and_panic_payloads_as_raw_data() {
+        // offsetがdata外を指すError(string)は custom error ではなく生データとして示す。
+        let mut truncated = ERROR_STRING_SELECTOR.to_vec();
+        truncated.extend_from_slice(&word(64));
+        let view = decode_revert(&truncated);
+        assert_eq!(view.reason, None);
+        assert_eq!(view.custom_error_selector, None);
+        assert!(view
+            .message
+            .starts_with("execution reverted: malformed revert data 0x08c379a0"));
+
+        let short_panic = decode_revert(&PANIC_SELECTOR);
+        assert_eq!(short_panic.panic_code, None);
+        assert_eq!(short_panic.custom_error_selector, None);
+        assert_eq!(
+            short_panic.message,
+            "execution reverted: malformed revert data 0x4e487b71"
+        );
+
+        assert_eq!(
+            decode_revert(&[0xab]).message,
+            "execution reverted: malformed revert data 0xab"
+        );
+    }
+
+    #[test]
+    fn caps_malformed_revert_data_in_message() {
+        let mut long = ERROR_STRING_SELECTOR.to_vec();
+        long.resize(4096, 0xff);
+        let view = decode_revert(&long);
+        let expected_hex_len = MALFORMED_HEX_MAX_BYTES * 2;
+        assert!(view.message.ends_with("..."));
+        assert_eq!(
+            view.message.len(),
+            "execution reverted: malformed revert data 0x".len() + expected_hex_len + 3
+        );
+    }
+
+    #[test]
+    fn call_reason_is_only_for_reverts() {
+        assert_eq!(call_revert_reason(1, None, &[]), None);
+        let view = call_revert_reason(0, None, &[]).expect("empty revert must be explained");
+        assert_eq!(view.message, "execution reverted");
+        assert_eq!(
+            call_revert_reason(0, Some(OpHaltReason::OutOfGas), &[]),
+            None
+        );
+        assert_eq!(
+            call_revert_reason(0, Some(OpHaltReason::InvalidOpcode), &[]),
+            None
+        );
+    }
+
+    #[test]
+    fn receipt_reason_needs_return_data() {
+        assert_eq!(receipt_revert_reason(0, &[]), None);
+        assert_eq!(receipt_revert_reason(1, &[0xde, 0xad, 0xbe, 0xef]), None);
+        let custom = receipt_revert_reason(0, &[0xde, 0xad, 0xbe, 0xef])
+            .expect("revert data must be explained");
+        assert_eq!(custom.message, "execution reverted");
+    }
+}
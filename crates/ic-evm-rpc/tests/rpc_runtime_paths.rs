@@ -346,6 +346,102 @@ fn rpc_eth_estimate_gas_object_returns_minimum_successful_gas_limit() {
     assert!(success.gas_used < estimate);
 }
 
+#[test]
+fn rpc_eth_call_object_decodes_panic_revert() {
+    let _guard = test_lock().lock().expect("lock");
+    init_stable_state();
+
+    let from = [0x57u8; 20];
+    let to = [0x67u8; 20];
+    // mstore(0, 0x4e487b71 << 224); mstore(4, 0x11); revert(0, 0x24)
+    let code = vec![
+        0x63, 0x4e, 0x48, 0x7b, 0x71, 0x60, 0xe0, 0x1b, 0x60, 0x00, 0x52, 0x60, 0x11, 0x60, 0x04,
+        0x52, 0x60, 0x24, 0x60, 0x00, 0xfd,
+    ];
+    let code_hash = hash::keccak256(&code);
+    with_state_mut(|state| {
+        state.accounts.insert(
+            make_account_key(from),
+            AccountVal::from_parts(0, [0xffu8; 32], [0u8; 32]),
+        );
+        state.accounts.insert(
+            make_account_key(to),
+            AccountVal::from_parts(0, [0u8; 32], code_hash),
+        );
+        state
+            .codes
+            .insert(make_code_key(code_hash), CodeVal(code.clone()));
+    });
+
+    let out = rpc_eth_call_object(RpcCallObjectView {
+        to: Some(to.to_vec()),
+        from: Some(from.to_vec()),
+        gas: Some(100_000),
+        gas_price: None,
+        nonce: None,
+        max_fee_per_gas: None,
+        max_priority_fee_per_gas: None,
+        chain_id: None,
+        tx_type: None,
+        access_list: None,
+        value: Some(vec![0u8; 32]),
+        data: Some(Vec::new()),
+    })
+    .expect("reverting call should execute");
+    assert_eq!(out.status, 0);
+    assert_eq!(out.revert_data.as_ref().map(Vec::len), Some(36));
+    let reason = out.revert_reason.expect("panic revert must be decoded");
+    assert_eq!(
+        reason.message,
+        "execution reverted: arithmetic underflow or overflow"
+    );
+    assert_eq!(reason.panic_code, Some(0x11));
+}
+
+#[test]
+fn rpc_eth_call_object_halt_has_no_revert_reason() {
+    let _guard = test_lock().lock().expect("lock");
+    init_stable_state();
+
+    let from = [0x58u8; 20];
+    let to = [0x68u8; 20];
+    // INVALID
+    let code = vec![0xfe];
+    let code_hash = hash::keccak256(&code);
+    with_state_mut(|state| {
+        state.accounts.insert(
+            make_account_key(from),
+            AccountVal::from_parts(0, [0xffu8; 32], [0u8; 32]),
+        );
+        state.accounts.insert(
+            make_account_key(to),
+            AccountVal::from_parts(0, [0u8; 32], code_hash),
+        );
+        state
+            .codes
+            .insert(make_code_key(code_hash), CodeVal(code.clone()));
+    });
+
+    let out = rpc_eth_call_object(RpcCallObjectView {
+        to: Some(to.to_vec()),
+        from: Some(from.to_vec()),
+        gas: Some(100_000),
+        gas_price: None,
+        nonce: None,
+        max_fee_per_gas: None,
+        max_priority_fee_per_gas: None,
+        chain_id: None,
+        tx_type: None,
+        access_list: None,
+        value: Some(vec![0u8; 32]),
+        data: Some(Vec::new()),
+    })
+    .expect("halting call should execute");
+    assert_eq!(out.status, 0);
+    assert_eq!(out.revert_data, None);
+    assert_eq!(out.revert_reason, None);
+}
+
 #[test]
 fn rpc_eth_txcount_at_respects_latest_and_pending_semantics() {
     let _guard = test_lock().lock().expect("lock");
@@ -1904,6 +2000,115 @@ fn get_transaction_receipt_with_status_by_tx_id_accepts_tx_id() {
     }
 }
 
+#[test]
+fn get_transaction_receipt_with_status_reports_dropped_tx() {
+    let _guard = test_lock().lock().expect("lock");
+    init_stable_state();
+    with_state_mut(|state| {
+        let mut chain_state = *state.chain_state.get();
+        chain_state.base_fee = 1_000_000_000;
+        chain_state.min_priority_fee = 0;
+        chain_state.min_gas_price = 0;
+        state.chain_state.set(chain_state);
+    });
+    fund_eth_signer();
+    let raw = build_eth_signed_1559(0, 4_000_000_000, 1_000_000_000);
+    let eth_hash = hash::keccak256(&raw);
+    let dropped = chain::submit_tx(TxKind::EthSigned, raw, vec![0x89]).expect("submit eth tx");
+    let replacement = build_eth_signed_1559(0, 8_000_000_000, 2_000_000_000);
+    chain::submit_tx(TxKind::EthSigned, replacement, vec![0x89]).expect("submit replacement");
+
+    let by_tx_id = rpc_eth_get_transaction_receipt_with_status_by_tx_id(dropped.0.to_vec());
+    let RpcReceiptLookupView::Dropped(view) = by_tx_id else {
+        panic!("expected Dropped for replaced tx_id");
+    };
+    assert_eq!(view.tx_hash, dropped.0.to_vec());
+    assert_eq!(view.eth_tx_hash, Some(eth_hash.to_vec()));
+    assert_eq!(view.from, Some(test_signer().address().to_vec()));
+    assert_eq!(view.nonce, Some(0));
+    assert_eq!(view.drop_code, 6);
+    assert!(view.reason.contains("replaced"));
+    assert_eq!(view.max_fee_per_gas, 4_000_000_000);
+    assert_eq!(view.max_priority_fee_per_gas, 1_000_000_000);
+    assert_eq!(view.base_fee_per_gas, 1_000_000_000);
+
+    // drop済みTxはeth hash索引から外れても、drop記録から引ける。
+    let by_eth_hash = rpc_eth_get_transaction_receipt_with_status_by_eth_hash(eth_hash.to_vec());
+    let RpcReceiptLookupView::Dropped(view) = by_eth_hash else {
+        panic!("expected Dropped for replaced eth hash");
+    };
+    assert_eq!(view.tx_hash, dropped.0.to_vec());
+}
+
+#[test]
+fn get_transaction_receipt_with_status_decodes_revert_reason() {
+    let _guard = test_lock().lock().expect("lock");
+    init_stable_state();
+
+    let raw = vec![0x02, 0x55];
+    let tx_id = TxId(hash::stored_tx_id(
+        TxKind::EthSigned,
+        &raw,
+        None,
+        None,
+        None,
+    ));
+    let stored = StoredTxBytes::new_with_fees(
+        tx_id,
+        TxKind::EthSigned,
+        raw,
+        None,
+        Vec::new(),
+        Vec::new(),
+        0,
+        0,
+        false,
+    );
+    // Error("paused")
+    let mut return_data = vec![0x08, 0xc3, 0x79, 0xa0];
+    let mut word = [0u8; 32];
+    word[31] = 0x20;
+    return_data.extend_from_slice(&word);
+    word[31] = 6;
+    return_data.extend_from_slice(&word);
+    let mut text = [0u8; 32];
+    text[..6].copy_from_slice(b"paused");
+    return_data.extend_from_slice(&text);
+    let receipt = ReceiptLike {
+        tx_id,
+        block_number: 12,
+        tx_index: 0,
+        status: 0,
+        gas_used: 23_000,
+        effective_gas_price: 1,
+        l1_data_fee: 0,
+        operator_fee: 0,
+        total_fee: 0,
+        return_data_hash: hash::keccak256(&return_data),
+        return_data,
+        contract_address: None,
+        logs: vec![],
+    };
+    with_state_mut(|state| {
+        state.tx_store.insert(tx_id, stored);
+        let receipt_ptr = state
+            .blob_store
+            .store_bytes(&receipt.clone().into_bytes())
+            .expect("store receipt");
+        state.receipts.insert(tx_id, receipt_ptr);
+    });
+
+    let RpcReceiptLookupView::Found(found) =
+        rpc_eth_get_transaction_receipt_with_status_by_tx_id(tx_id.0.to_vec())
+    else {
+        panic!("expected Found for reverted receipt");
+    };
+    let reason = found.revert_reason.expect("revert reason must be decoded");
+    assert_eq!(reason.message, "execution reverted: paused");
+    assert_eq!(reason.reason.as_deref(), Some("paused"));
+    assert_eq!(reason.panic_code, None);
+}
+
 #[test]
 fn get_transaction_receipt_with_status_by_tx_id_rejects_invalid_len() {
     let _guard = test_lock().lock().expect("lock");
@@ -0,0 +1,74 @@
+//! どこで: archive canister との境界 / 何を: prune上限の制限とack範囲の妥当性 / なぜ: archiveが受け取っていない履歴を消さないため
+
+#[cfg(verus_keep_ghost)]
+use vstd::prelude::*;
+
+/// archive 未設定なら従来どおり prune_before まで、設定済みなら ack 済みの範囲までしか消さない。
+#[cfg_attr(verus_keep_ghost, verus_spec(out => ensures
+    !archive_configured ==> out == Some(prune_before),
+    archive_configured && matches!(acked_through, None) ==> matches!(out, None),
+    archive_configured && matches!(acked_through, Some(_)) ==> out == Some(
+        if acked_through.unwrap() < prune_before { acked_through.unwrap() } else { prune_before }
+    ),
+))]
+pub fn archive_prune_ceiling(
+    prune_before: u64,
+    archive_configured: bool,
+    acked_through: Option<u64>,
+) -> Option<u64> {
+    if !archive_configured {
+        return Some(prune_before);
+    }
+    let acked = acked_through?;
+    Some(if acked < prune_before {
+        acked
+    } else {
+        prune_before
+    })
+}
+
+/// ack は head を超えず、前回より前へ戻らず、空でない範囲でなければならない。
+#[cfg_attr(verus_keep_ghost, verus_spec(valid => ensures
+    valid == (start_block <= end_block && end_block <= head_block),
+))]
+pub fn archive_ack_valid(start_block: u64, end_block: u64, head_block: u64) -> bool {
+    start_block <= end_block && end_block <= head_block
+}
+
+/// prune時に残す tx→block 記録の件数を上限に収めるため、古い方から捨てる件数。
+#[cfg_attr(verus_keep_ghost, verus_spec(trim => ensures
+    len <= cap ==> trim == 0,
+    len > cap ==> trim == len - cap,
+))]
+pub fn archived_tx_trim_count(len: u64, cap: u64) -> u64 {
+    len.saturating_sub(cap)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{archive_ack_valid, archive_prune_ceiling, archived_tx_trim_count};
+
+    #[test]
+    fn prune_ceiling_follows_archive_ack() {
+        assert_eq!(archive_prune_ceiling(100, false, None), Some(100));
+        assert_eq!(archive_prune_ceiling(100, true, None), None);
+        assert_eq!(archive_prune_ceiling(100, true, Some(40)), Some(40));
+        assert_eq!(archive_prune_ceiling(100, true, Some(400)), Some(100));
+    }
+
+    #[test]
+    fn ack_must_be_non_empty_and_below_head() {
+        assert!(archive_ack_valid(0, 10, 10));
+        assert!(archive_ack_valid(11, 11, 20));
+        assert!(!archive_ack_valid(11, 10, 20));
+        assert!(!archive_ack_valid(0, 21, 20));
+    }
+
+    #[test]
+    fn archived_tx_trim_keeps_at_most_cap() {
+        assert_eq!(archived_tx_trim_count(10, 100), 0);
+        assert_eq!(archived_tx_trim_count(100, 100), 0);
+        assert_eq!(archived_tx_trim_count(105, 100), 5);
+        assert_eq!(archived_tx_trim_count(5, 0), 5);
+    }
+}
@@ -0,0 +1,40 @@
+//! どこで: Blobの圧縮形式 / 何を: 圧縮を採用する条件と展開長の検査 / なぜ: サイズクラスが下がらない圧縮で読み出しコストだけ増やさないため
+
+#[cfg(verus_keep_ghost)]
+use vstd::prelude::*;
+
+/// BlobStore はサイズクラス単位で確保するため、クラスが下がるときだけ圧縮形式で持つ。
+#[cfg_attr(verus_keep_ghost, verus_spec(keep => ensures
+    keep == (compressed_class < raw_class),
+))]
+pub fn keep_compressed(raw_class: u32, compressed_class: u32) -> bool {
+    compressed_class < raw_class
+}
+
+/// 展開後の長さは header の申告値と一致し、最大クラスを超えてはならない。
+#[cfg_attr(verus_keep_ghost, verus_spec(valid => ensures
+    valid == (declared_len > 0 && declared_len <= max_len && decoded_len == declared_len as usize),
+))]
+pub fn decoded_len_valid(declared_len: u32, decoded_len: usize, max_len: u32) -> bool {
+    declared_len > 0 && declared_len <= max_len && decoded_len == declared_len as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decoded_len_valid, keep_compressed};
+
+    #[test]
+    fn compression_is_kept_only_when_class_shrinks() {
+        assert!(keep_compressed(16_384, 8_192));
+        assert!(!keep_compressed(8_192, 8_192));
+        assert!(!keep_compressed(8_192, 16_384));
+    }
+
+    #[test]
+    fn decoded_len_must_match_declared_and_limit() {
+        assert!(decoded_len_valid(100, 100, 4_096));
+        assert!(!decoded_len_valid(100, 99, 4_096));
+        assert!(!decoded_len_valid(0, 0, 4_096));
+        assert!(!decoded_len_valid(5_000, 5_000, 4_096));
+    }
+}
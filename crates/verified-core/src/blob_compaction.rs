@@ -0,0 +1,75 @@
+//! どこで: BlobStoreのcompaction / 何を: 実行条件・移動先の妥当性・回収可能量 / なぜ: 参照張り替え中に他の書き込み経路と競合させないため
+
+#[cfg(verus_keep_ghost)]
+use vstd::prelude::*;
+
+/// staged block と prune journal は BlobPtr を参照表の外に持つため、張り替えの対象外になる。
+/// どちらかが残っている間、または状態snapshotの取り込み中は compaction を進めない。
+#[cfg_attr(verus_keep_ghost, verus_spec(allowed => ensures
+    allowed == (!staged_block_active && !prune_journal_pending && !snapshot_import_active),
+))]
+pub fn compaction_tick_allowed(
+    staged_block_active: bool,
+    prune_journal_pending: bool,
+    snapshot_import_active: bool,
+) -> bool {
+    !staged_block_active && !prune_journal_pending && !snapshot_import_active
+}
+
+/// 移動は arena の先頭側へ向かう場合だけ意味がある。同じクラス内で offset が下がることを要求する。
+#[cfg_attr(verus_keep_ghost, verus_spec(moves => ensures
+    moves == (src_class == dst_class && dst_offset < src_offset),
+))]
+pub fn relocation_moves_lower(
+    src_class: u32,
+    src_offset: u64,
+    dst_class: u32,
+    dst_offset: u64,
+) -> bool {
+    src_class == dst_class && dst_offset < src_offset
+}
+
+/// arena 末尾までのうち、Used/Quarantine 以外の領域。詰め直しと末尾切り詰めで取り戻せる上限。
+#[cfg_attr(verus_keep_ghost, verus_spec(bytes => ensures
+    bytes as int == if (used_bytes as int) + (quarantine_bytes as int) >= arena_end_bytes as int {
+        0int
+    } else {
+        (arena_end_bytes as int) - (used_bytes as int) - (quarantine_bytes as int)
+    },
+))]
+pub fn reclaimable_bytes(arena_end_bytes: u64, used_bytes: u64, quarantine_bytes: u64) -> u64 {
+    let live = u128::from(used_bytes) + u128::from(quarantine_bytes);
+    let end = u128::from(arena_end_bytes);
+    if live >= end {
+        0
+    } else {
+        (arena_end_bytes - used_bytes) - quarantine_bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compaction_tick_allowed, reclaimable_bytes, relocation_moves_lower};
+
+    #[test]
+    fn compaction_waits_for_out_of_table_pointers() {
+        assert!(compaction_tick_allowed(false, false, false));
+        assert!(!compaction_tick_allowed(true, false, false));
+        assert!(!compaction_tick_allowed(false, true, false));
+        assert!(!compaction_tick_allowed(false, false, true));
+    }
+
+    #[test]
+    fn relocation_must_stay_in_class_and_move_down() {
+        assert!(relocation_moves_lower(8192, 16384, 8192, 0));
+        assert!(!relocation_moves_lower(8192, 16384, 8192, 16384));
+        assert!(!relocation_moves_lower(8192, 16384, 16384, 0));
+    }
+
+    #[test]
+    fn reclaimable_bytes_saturates_at_zero() {
+        assert_eq!(reclaimable_bytes(65536, 16384, 8192), 40960);
+        assert_eq!(reclaimable_bytes(8192, 8192, 8192), 0);
+        assert_eq!(reclaimable_bytes(u64::MAX, u64::MAX, u64::MAX), 0);
+    }
+}
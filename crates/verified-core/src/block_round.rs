@@ -0,0 +1,121 @@
+//! どこで: 複数ラウンドのブロック組成 / 何を: staged block の再開条件とラウンド終了時の封印判定 / なぜ: 命令上限をまたいでも同じ親の上に同じ順序でブロックを組むため
+
+#[cfg(verus_keep_ghost)]
+use vstd::prelude::*;
+
+#[cfg_attr(verus_keep_ghost, verus_verify)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BlockRoundDecision {
+    /// 実行済みTxとdiffを保存し、次のメッセージで続きを組む。
+    Stage,
+    /// 現ラウンドでブロックを確定する。
+    Seal,
+}
+
+/// staged block は head の直後で、組成開始時と同じ親・同じ状態の上でのみ再開できる。
+/// 各staged Txが current pending のままであることは `staging::staged_tx_is_current_pending_raw` で別途確認する。
+#[cfg_attr(verus_keep_ghost, verus_spec(resumable => ensures
+    resumable == (
+        head_number < u64::MAX
+        && staged_number == head_number + 1
+        && parent_hash_matches
+        && state_epoch_matches
+    ),
+))]
+pub fn staged_block_resumable(
+    head_number: u64,
+    staged_number: u64,
+    parent_hash_matches: bool,
+    state_epoch_matches: bool,
+) -> bool {
+    head_number.checked_add(1) == Some(staged_number) && parent_hash_matches && state_epoch_matches
+}
+
+/// 命令上限で止まり、未処理の候補が残り、ラウンド上限に達していない場合だけ持ち越す。
+/// block gas が埋まった場合は候補が残っていても封印する。
+#[cfg_attr(verus_keep_ghost, verus_spec(decision => ensures
+    decision == BlockRoundDecision::Stage
+        <==> (
+            !block_gas_full
+            && instruction_exhausted
+            && unprocessed_candidates > 0
+            && (rounds_done as int) + 1 < max_rounds as int
+        ),
+))]
+pub fn round_end_decision(
+    block_gas_full: bool,
+    instruction_exhausted: bool,
+    unprocessed_candidates: usize,
+    rounds_done: u32,
+    max_rounds: u32,
+) -> BlockRoundDecision {
+    if !block_gas_full
+        && instruction_exhausted
+        && unprocessed_candidates > 0
+        && u64::from(rounds_done) + 1 < u64::from(max_rounds)
+    {
+        BlockRoundDecision::Stage
+    } else {
+        BlockRoundDecision::Seal
+    }
+}
+
+/// ラウンド途中で命令予算を使い切ったTxは、次ラウンドの予算で再実行する。
+/// ラウンド先頭のTxは次ラウンドでも同じ予算しか得られないため従来どおりdropする。
+#[cfg_attr(verus_keep_ghost, verus_spec(retry => ensures
+    retry == (staging_allowed && executed_in_round > 0),
+))]
+pub fn budget_exceeded_tx_retries(staging_allowed: bool, executed_in_round: usize) -> bool {
+    staging_allowed && executed_in_round > 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        budget_exceeded_tx_retries, round_end_decision, staged_block_resumable, BlockRoundDecision,
+    };
+
+    #[test]
+    fn staged_block_resumes_only_on_unchanged_parent() {
+        assert!(staged_block_resumable(7, 8, true, true));
+        assert!(!staged_block_resumable(8, 8, true, true));
+        assert!(!staged_block_resumable(7, 8, false, true));
+        assert!(!staged_block_resumable(7, 8, true, false));
+        assert!(!staged_block_resumable(u64::MAX, u64::MAX, true, true));
+    }
+
+    #[test]
+    fn round_end_stages_only_when_instruction_bound() {
+        assert_eq!(
+            round_end_decision(false, true, 3, 0, 16),
+            BlockRoundDecision::Stage
+        );
+        assert_eq!(
+            round_end_decision(true, true, 3, 0, 16),
+            BlockRoundDecision::Seal
+        );
+        assert_eq!(
+            round_end_decision(false, false, 3, 0, 16),
+            BlockRoundDecision::Seal
+        );
+        assert_eq!(
+            round_end_decision(false, true, 0, 0, 16),
+            BlockRoundDecision::Seal
+        );
+        assert_eq!(
+            round_end_decision(false, true, 3, 15, 16),
+            BlockRoundDecision::Seal
+        );
+        assert_eq!(
+            round_end_decision(false, true, 3, 0, 0),
+            BlockRoundDecision::Seal
+        );
+    }
+
+    #[test]
+    fn budget_exceeded_retry_requires_prior_progress() {
+        assert!(budget_exceeded_tx_retries(true, 1));
+        assert!(!budget_exceeded_tx_retries(true, 0));
+        assert!(!budget_exceeded_tx_retries(false, 3));
+    }
+}
@@ -0,0 +1,86 @@
+//! どこで: asset ごとの bridge 上限 / 何を: 1 回の上限、24 時間窓の累計上限、窓の時間 bucket の送り / なぜ: wrap と unwrap の両側で同じ判定を使い、上限を越える量を台帳へ流さないため
+
+#[cfg(verus_keep_ghost)]
+use vstd::prelude::*;
+
+/// 24 時間窓を 1 時間ずつの bucket で持つ。
+pub const BRIDGE_WINDOW_BUCKETS: u64 = 24;
+
+/// 上限が無ければ通す。加算が溢れるときは上限を越えたものとして扱う。
+#[cfg_attr(verus_keep_ghost, verus_spec(allowed => ensures
+    allowed == match cap {
+        Some(limit) => used + amount <= limit,
+        None => true,
+    },
+))]
+pub fn bridge_limit_allows(used: u128, amount: u128, cap: Option<u128>) -> bool {
+    match cap {
+        Some(limit) => match used.checked_add(amount) {
+            Some(total) => total <= limit,
+            None => false,
+        },
+        None => true,
+    }
+}
+
+/// 最後に記録した時刻から進んだ bucket 数。窓より長く空いたら全 bucket を消す。
+/// 時計が戻ったときは 0 を返し、記録済みの量を消さない。
+#[cfg_attr(verus_keep_ghost, verus_spec(cleared => ensures
+    now_hour <= last_hour ==> cleared == 0,
+    now_hour > last_hour && now_hour - last_hour >= BRIDGE_WINDOW_BUCKETS ==> cleared == BRIDGE_WINDOW_BUCKETS,
+    now_hour > last_hour && now_hour - last_hour < BRIDGE_WINDOW_BUCKETS ==> cleared == now_hour - last_hour,
+))]
+pub fn bridge_window_buckets_to_clear(last_hour: u64, now_hour: u64) -> u64 {
+    if now_hour <= last_hour {
+        return 0;
+    }
+    let elapsed = now_hour - last_hour;
+    if elapsed >= BRIDGE_WINDOW_BUCKETS {
+        BRIDGE_WINDOW_BUCKETS
+    } else {
+        elapsed
+    }
+}
+
+/// 記録した時刻の bucket がまだ窓の中にあるか。取り消しは窓の中の bucket にだけ効かせる。
+#[cfg_attr(verus_keep_ghost, verus_spec(inside => ensures
+    inside == (recorded_hour <= now_hour && now_hour - recorded_hour < BRIDGE_WINDOW_BUCKETS),
+))]
+pub fn bridge_window_contains(recorded_hour: u64, now_hour: u64) -> bool {
+    recorded_hour <= now_hour && now_hour - recorded_hour < BRIDGE_WINDOW_BUCKETS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        bridge_limit_allows, bridge_window_buckets_to_clear, bridge_window_contains,
+        BRIDGE_WINDOW_BUCKETS,
+    };
+
+    #[test]
+    fn limit_allows_up_to_cap_and_rejects_overflow() {
+        assert!(bridge_limit_allows(u128::MAX, 1, None));
+        assert!(bridge_limit_allows(60, 40, Some(100)));
+        assert!(!bridge_limit_allows(61, 40, Some(100)));
+        assert!(!bridge_limit_allows(0, 1, Some(0)));
+        assert!(!bridge_limit_allows(u128::MAX, 1, Some(u128::MAX)));
+    }
+
+    #[test]
+    fn window_clears_elapsed_buckets_and_keeps_them_on_clock_skew() {
+        assert_eq!(bridge_window_buckets_to_clear(10, 10), 0);
+        assert_eq!(bridge_window_buckets_to_clear(10, 9), 0);
+        assert_eq!(bridge_window_buckets_to_clear(10, 13), 3);
+        assert_eq!(
+            bridge_window_buckets_to_clear(10, 10 + BRIDGE_WINDOW_BUCKETS),
+            BRIDGE_WINDOW_BUCKETS
+        );
+        assert_eq!(
+            bridge_window_buckets_to_clear(0, u64::MAX),
+            BRIDGE_WINDOW_BUCKETS
+        );
+        assert!(bridge_window_contains(10, 33));
+        assert!(!bridge_window_contains(10, 34));
+        assert!(!bridge_window_contains(11, 10));
+    }
+}
@@ -0,0 +1,63 @@
+//! どこで: certified data の block log / 何を: 証明対象に残す block 窓と再構築判定 / なぜ: heap 上の木を有界に保ちつつ tip を常に certify するため
+
+#[cfg(verus_keep_ghost)]
+use vstd::prelude::*;
+
+/// 窓は head を含む直近 window 個の block。window 0 は head だけとして扱う。
+#[cfg_attr(verus_keep_ghost, verus_spec(start => ensures
+    start <= head_block,
+    window > 0 && head_block >= window ==> start == head_block - (window - 1),
+    window > 0 && head_block < window ==> start == 0,
+    window == 0 ==> start == head_block,
+))]
+pub fn certified_window_start(head_block: u64, window: u64) -> u64 {
+    if window == 0 {
+        return head_block;
+    }
+    if head_block < window {
+        0
+    } else {
+        head_block - (window - 1)
+    }
+}
+
+/// 差分追加できるのは、前回の tip から前進していて、その差が窓に収まるときだけ。
+#[cfg_attr(verus_keep_ghost, verus_spec(rebuild => ensures
+    matches!(certified_tip, None) ==> rebuild,
+    matches!(certified_tip, Some(_)) ==> rebuild == (
+        certified_tip.unwrap() > head_block || head_block - certified_tip.unwrap() >= window
+    ),
+))]
+pub fn certified_log_needs_rebuild(
+    certified_tip: Option<u64>,
+    head_block: u64,
+    window: u64,
+) -> bool {
+    match certified_tip {
+        None => true,
+        Some(tip) => tip > head_block || head_block - tip >= window,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{certified_log_needs_rebuild, certified_window_start};
+
+    #[test]
+    fn window_start_keeps_head_inside_window() {
+        assert_eq!(certified_window_start(0, 32), 0);
+        assert_eq!(certified_window_start(31, 32), 0);
+        assert_eq!(certified_window_start(32, 32), 1);
+        assert_eq!(certified_window_start(100, 32), 69);
+        assert_eq!(certified_window_start(7, 0), 7);
+    }
+
+    #[test]
+    fn rebuild_only_when_tip_cannot_be_extended() {
+        assert!(certified_log_needs_rebuild(None, 5, 32));
+        assert!(!certified_log_needs_rebuild(Some(5), 5, 32));
+        assert!(!certified_log_needs_rebuild(Some(5), 36, 32));
+        assert!(certified_log_needs_rebuild(Some(5), 37, 32));
+        assert!(certified_log_needs_rebuild(Some(9), 5, 32));
+    }
+}
@@ -0,0 +1,46 @@
+//! どこで: 入金用subaccountの sweep / 何を: sweep 額と未確定 sweep の保持条件 / なぜ: 台帳 fee を割る入金を弾き、結果の分からない sweep を別 request で二重に mint しないため
+
+#[cfg(verus_keep_ghost)]
+use vstd::prelude::*;
+
+/// sweep は台帳 fee を残高から払うので、fee を超えた分だけを wrap する。
+#[cfg_attr(verus_keep_ghost, verus_spec(amount => ensures
+    balance <= ledger_fee ==> amount == Option::<u128>::None,
+    balance > ledger_fee ==> matches!(amount, Some(_)),
+    matches!(amount, Some(_)) ==> amount.unwrap() == balance - ledger_fee,
+))]
+pub fn deposit_sweep_amount(balance: u128, ledger_fee: u128) -> Option<u128> {
+    if balance > ledger_fee {
+        Some(balance - ledger_fee)
+    } else {
+        None
+    }
+}
+
+/// 台帳が確定的に拒否したときだけ sweep を手放す。呼び出し失敗は送金済みの可能性があるため同じ request で再試行する。
+#[cfg_attr(verus_keep_ghost, verus_spec(keep => ensures
+    keep == (!succeeded && !definite_failure),
+))]
+pub fn deposit_sweep_stays_in_flight(succeeded: bool, definite_failure: bool) -> bool {
+    !succeeded && !definite_failure
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{deposit_sweep_amount, deposit_sweep_stays_in_flight};
+
+    #[test]
+    fn sweep_amount_leaves_ledger_fee() {
+        assert_eq!(deposit_sweep_amount(10_000, 10_000), None);
+        assert_eq!(deposit_sweep_amount(0, 0), None);
+        assert_eq!(deposit_sweep_amount(10_001, 10_000), Some(1));
+        assert_eq!(deposit_sweep_amount(u128::MAX, 0), Some(u128::MAX));
+    }
+
+    #[test]
+    fn only_uncertain_sweeps_stay_in_flight() {
+        assert!(!deposit_sweep_stays_in_flight(true, false));
+        assert!(!deposit_sweep_stays_in_flight(false, true));
+        assert!(deposit_sweep_stays_in_flight(false, false));
+    }
+}
@@ -38,9 +38,30 @@ pub fn push(next_seq: u64, len: u32, capacity: u64) -> DroppedRingTransition {
     }
 }
 
+/// drop記録の最古1件を消すべきか。件数上限超過か、保持期間切れのどちらかで消す。
+#[cfg_attr(verus_keep_ghost, verus_spec(result => ensures
+    live_records == 0 ==> !result,
+    (live_records > 0 && live_records > max_records) ==> result,
+))]
+pub fn drop_record_should_evict(
+    live_records: u64,
+    max_records: u64,
+    oldest_dropped_at: u64,
+    now_sec: u64,
+    retain_secs: u64,
+) -> bool {
+    if live_records == 0 {
+        return false;
+    }
+    if live_records > max_records {
+        return true;
+    }
+    now_sec.saturating_sub(oldest_dropped_at) > retain_secs
+}
+
 #[cfg(test)]
 mod tests {
-    use super::push;
+    use super::{drop_record_should_evict, push};
 
     #[test]
     fn push_grows_until_capacity_then_evicts() {
@@ -55,4 +76,12 @@ mod tests {
         assert_eq!(full.len, 2);
         assert_eq!(full.evict_seq, Some(0));
     }
+
+    #[test]
+    fn drop_record_evicts_on_count_or_age() {
+        assert!(!drop_record_should_evict(0, 0, 0, 100, 1));
+        assert!(drop_record_should_evict(3, 2, 100, 100, 60));
+        assert!(!drop_record_should_evict(2, 2, 100, 160, 60));
+        assert!(drop_record_should_evict(2, 2, 100, 161, 60));
+    }
 }
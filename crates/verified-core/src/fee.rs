@@ -21,6 +21,23 @@ pub fn effective_gas_price(max_fee: u128, max_priority: u128, base_fee: u64) ->
     u64::try_from(capped).ok()
 }
 
+// pending index は base fee に依存しない2つのrankで持つ。
+// min_i min(max_fee_i, base + priority_i) = min(min_i max_fee_i, base + min_i priority_i) なので、
+// 各indexの先頭だけ見れば現在のbase feeでの最安txが決まる。priority > max_fee の不正txは (0, 0)。
+#[cfg_attr(verus_keep_ghost, verus_spec(ranks => ensures
+    max_priority > max_fee ==> ranks.0 == 0 && ranks.1 == 0,
+    max_priority <= max_fee ==> ranks.1 <= ranks.0,
+))]
+pub fn pending_fee_ranks(max_fee: u128, max_priority: u128) -> (u64, u64) {
+    if max_priority > max_fee {
+        return (0, 0);
+    }
+    (
+        u64::try_from(max_fee).unwrap_or(u64::MAX),
+        u64::try_from(max_priority).unwrap_or(u64::MAX),
+    )
+}
+
 #[cfg_attr(verus_keep_ghost, verus_spec(satisfied => ensures
     matches!(gas_priority_fee, Some(_)) ==> satisfied == (
         gas_priority_fee.unwrap() >= min_priority_fee as u128
@@ -87,7 +104,10 @@ pub fn base_fee_reward(gas_used: u64, base_fee: u64) -> u128 {
 
 #[cfg(test)]
 mod tests {
-    use super::{base_fee_reward, effective_gas_price, l2_fee, min_fee_satisfied, total_fee};
+    use super::{
+        base_fee_reward, effective_gas_price, l2_fee, min_fee_satisfied, pending_fee_ranks,
+        total_fee,
+    };
 
     #[test]
     fn effective_gas_price_caps_priority() {
@@ -102,6 +122,16 @@ mod tests {
         assert_eq!(effective_gas_price(u128::MAX, u128::MAX, u64::MAX), None);
     }
 
+    #[test]
+    fn pending_fee_ranks_saturate_and_zero_invalid() {
+        assert_eq!(pending_fee_ranks(10, 3), (10, 3));
+        assert_eq!(pending_fee_ranks(10, 11), (0, 0));
+        assert_eq!(
+            pending_fee_ranks(u128::MAX, u128::MAX),
+            (u64::MAX, u64::MAX)
+        );
+    }
+
     #[test]
     fn min_fee_satisfied_checks_dynamic_and_legacy() {
         assert!(min_fee_satisfied(30, Some(10), 20, 10, 1));
@@ -13,6 +13,12 @@ pub const ICP_QUERY_PRECOMPILE_ADDRESS_CODE: u64 = 3;
 #[cfg_attr(verus_keep_ghost, verus_verify)]
 pub const COMPACT_FORMAT_VERSION: u64 = 1;
 #[cfg_attr(verus_keep_ghost, verus_verify)]
+pub const COMPACT_SUBACCOUNT_FORMAT_VERSION: u64 = 2;
+#[cfg_attr(verus_keep_ghost, verus_verify)]
+pub const COMPACT_SUBACCOUNT_LEN: u64 = 32;
+#[cfg_attr(verus_keep_ghost, verus_verify)]
+pub const COMPACT_NFT_UNWRAP_FORMAT_VERSION: u64 = 3;
+#[cfg_attr(verus_keep_ghost, verus_verify)]
 pub const MAX_PRINCIPAL_LEN: u64 = 29;
 #[cfg_attr(verus_keep_ghost, verus_verify)]
 pub const MAX_QUERY_METHOD_LEN: u64 = 64;
@@ -21,6 +27,8 @@ pub const COMPACT_UNWRAP_INPUT_LEN: u64 = 93;
 #[cfg_attr(verus_keep_ghost, verus_verify)]
 pub const COMPACT_NATIVE_WITHDRAW_INPUT_LEN: u64 = 31;
 #[cfg_attr(verus_keep_ghost, verus_verify)]
+pub const COMPACT_NFT_UNWRAP_INPUT_LEN: u64 = 125;
+#[cfg_attr(verus_keep_ghost, verus_verify)]
 pub const UNWRAP_BURN_GAS_SURCHARGE: u64 = 45_000;
 #[cfg_attr(verus_keep_ghost, verus_verify)]
 pub const ICP_QUERY_KIND_QUERY: u64 = 0;
@@ -51,10 +59,27 @@ pub fn compact_principal_slot_safe_raw(len: u64, slot_present: u64, padding_zero
     len >= 1 && len <= MAX_PRINCIPAL_LEN && slot_present == 1 && padding_zero == 1
 }
 
+/// 版 1 は従来の固定長、版 2 は末尾に 32byte の subaccount を足した固定長だけを受け入れる。
+#[cfg_attr(verus_keep_ghost, verus_spec(valid => requires
+    v1_len <= u64::MAX - COMPACT_SUBACCOUNT_LEN,
+ensures
+    valid == (
+        (input_len == v1_len && version == COMPACT_FORMAT_VERSION)
+        || (input_len == v1_len + COMPACT_SUBACCOUNT_LEN
+            && version == COMPACT_SUBACCOUNT_FORMAT_VERSION)
+    ),
+))]
+pub fn compact_versioned_input_len_safe_raw(input_len: u64, version: u64, v1_len: u64) -> bool {
+    (input_len == v1_len && version == COMPACT_FORMAT_VERSION)
+        || (input_len == v1_len + COMPACT_SUBACCOUNT_LEN
+            && version == COMPACT_SUBACCOUNT_FORMAT_VERSION)
+}
+
 #[cfg_attr(verus_keep_ghost, verus_spec(valid => ensures
     valid == (
-        input_len == COMPACT_UNWRAP_INPUT_LEN
-        && version == COMPACT_FORMAT_VERSION
+        ((input_len == COMPACT_UNWRAP_INPUT_LEN && version == COMPACT_FORMAT_VERSION)
+            || (input_len == COMPACT_UNWRAP_INPUT_LEN + COMPACT_SUBACCOUNT_LEN
+                && version == COMPACT_SUBACCOUNT_FORMAT_VERSION))
         && asset_len >= 1
         && asset_len <= MAX_PRINCIPAL_LEN
         && asset_slot_present == 1
@@ -77,8 +102,7 @@ pub fn compact_unwrap_input_safe_raw(
     recipient_slot_present: u64,
     recipient_padding_zero: u64,
 ) -> bool {
-    input_len == COMPACT_UNWRAP_INPUT_LEN
-        && version == COMPACT_FORMAT_VERSION
+    compact_versioned_input_len_safe_raw(input_len, version, COMPACT_UNWRAP_INPUT_LEN)
         && asset_len >= 1
         && asset_len <= MAX_PRINCIPAL_LEN
         && asset_slot_present == 1
@@ -90,10 +114,51 @@ pub fn compact_unwrap_input_safe_raw(
         && recipient_padding_zero == 1
 }
 
+/// NFT 版は version 3 の固定長だけを受け入れ、subaccount 欄は常に同梱する（全0は未指定）。
+#[cfg_attr(verus_keep_ghost, verus_spec(valid => ensures
+    valid == (
+        input_len == COMPACT_NFT_UNWRAP_INPUT_LEN
+        && version == COMPACT_NFT_UNWRAP_FORMAT_VERSION
+        && collection_len >= 1
+        && collection_len <= MAX_PRINCIPAL_LEN
+        && collection_slot_present == 1
+        && collection_padding_zero == 1
+        && token_id_present == 1
+        && recipient_len >= 1
+        && recipient_len <= MAX_PRINCIPAL_LEN
+        && recipient_slot_present == 1
+        && recipient_padding_zero == 1
+    ),
+))]
+pub fn compact_nft_unwrap_input_safe_raw(
+    input_len: u64,
+    version: u64,
+    collection_len: u64,
+    collection_slot_present: u64,
+    collection_padding_zero: u64,
+    token_id_present: u64,
+    recipient_len: u64,
+    recipient_slot_present: u64,
+    recipient_padding_zero: u64,
+) -> bool {
+    input_len == COMPACT_NFT_UNWRAP_INPUT_LEN
+        && version == COMPACT_NFT_UNWRAP_FORMAT_VERSION
+        && collection_len >= 1
+        && collection_len <= MAX_PRINCIPAL_LEN
+        && collection_slot_present == 1
+        && collection_padding_zero == 1
+        && token_id_present == 1
+        && recipient_len >= 1
+        && recipient_len <= MAX_PRINCIPAL_LEN
+        && recipient_slot_present == 1
+        && recipient_padding_zero == 1
+}
+
 #[cfg_attr(verus_keep_ghost, verus_spec(valid => ensures
     valid == (
-        input_len == COMPACT_NATIVE_WITHDRAW_INPUT_LEN
-        && version == COMPACT_FORMAT_VERSION
+        ((input_len == COMPACT_NATIVE_WITHDRAW_INPUT_LEN && version == COMPACT_FORMAT_VERSION)
+            || (input_len == COMPACT_NATIVE_WITHDRAW_INPUT_LEN + COMPACT_SUBACCOUNT_LEN
+                && version == COMPACT_SUBACCOUNT_FORMAT_VERSION))
         && recipient_len >= 1
         && recipient_len <= MAX_PRINCIPAL_LEN
         && recipient_slot_present == 1
@@ -109,8 +174,7 @@ pub fn compact_native_withdraw_input_safe_raw(
     recipient_padding_zero: u64,
     recipient_is_anonymous: u64,
 ) -> bool {
-    input_len == COMPACT_NATIVE_WITHDRAW_INPUT_LEN
-        && version == COMPACT_FORMAT_VERSION
+    compact_versioned_input_len_safe_raw(input_len, version, COMPACT_NATIVE_WITHDRAW_INPUT_LEN)
         && recipient_len >= 1
         && recipient_len <= MAX_PRINCIPAL_LEN
         && recipient_slot_present == 1
@@ -1,26 +1,39 @@
 //! どこで: 検証対象の純粋モデル / 何を: 状態遷移ルール / なぜ: canister境界から業務ロジックを分離するため
 
+pub mod archive;
 pub mod batch;
+pub mod blob_codec;
+pub mod blob_compaction;
 pub mod block;
 pub mod block_persist;
+pub mod block_round;
+pub mod bridge_limits;
+pub mod certified_log;
 pub mod core_safety;
 pub mod core_safety_block;
 pub mod core_safety_included;
+pub mod deposit;
 pub mod dropped_ring;
 pub mod fee;
 pub mod kasane_precompiles;
 pub mod native_amount;
 pub mod no_reorg;
 pub mod nonce;
+pub mod optimistic_exec;
 pub mod pending;
 pub mod prune;
 pub mod prune_safety;
 pub mod queue;
+pub mod ready_bucket;
 pub mod receipt_index;
+pub mod reserves;
+pub mod schema_migration;
+pub mod scrub;
 pub mod stable_codec;
 pub mod stable_namespace;
 pub mod staging;
 pub mod state_diff;
+pub mod state_snapshot;
 pub mod tx_index;
 pub mod unwrap_dispatch;
 pub mod upgrade_safety;
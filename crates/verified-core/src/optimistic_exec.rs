@@ -0,0 +1,108 @@
+//! どこで: produce_block の楽観実行 / 何を: snapshot上の事前実行結果の採否判定 / なぜ: 逐次実行と同じ結果になる事前実行だけをcommitするため
+
+#[cfg(verus_keep_ghost)]
+use vstd::prelude::*;
+
+use std::collections::BTreeSet;
+
+#[cfg_attr(verus_keep_ghost, verus_verify)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum OptimisticCommitDecision {
+    Accept,
+    Reexecute,
+}
+
+/// 事前実行の read set（state diffに現れた全address）が、同一ブロックで先にcommitされた
+/// write set と交差するかを判定する。`exempt` は差分加算で再適用できるaddress（fee recipient）。
+pub fn read_set_conflicts(
+    read_set: &[[u8; 20]],
+    written: &BTreeSet<[u8; 20]>,
+    exempt: [u8; 20],
+) -> bool {
+    read_set
+        .iter()
+        .any(|address| *address != exempt && written.contains(address))
+}
+
+#[cfg_attr(verus_keep_ghost, verus_spec(decision => ensures
+    decision == OptimisticCommitDecision::Accept
+        <==> (pre_exec_succeeded && !read_set_conflicts && !fee_recipient_conflicts),
+))]
+pub fn optimistic_commit_decision(
+    pre_exec_succeeded: bool,
+    read_set_conflicts: bool,
+    fee_recipient_conflicts: bool,
+) -> OptimisticCommitDecision {
+    if pre_exec_succeeded && !read_set_conflicts && !fee_recipient_conflicts {
+        OptimisticCommitDecision::Accept
+    } else {
+        OptimisticCommitDecision::Reexecute
+    }
+}
+
+/// fee recipient への書き込みを「snapshotからの増分」として現在値へ載せ替えられるか。
+/// Tx が fee recipient の状態を観測した場合や、snapshot時点で未作成だった場合は載せ替えない。
+#[cfg_attr(verus_keep_ghost, verus_spec(conflicts => ensures
+    conflicts == (recipient_written_in_block
+        && (recipient_observed || !recipient_existed_at_snapshot || recipient_balance_decreased)),
+))]
+pub fn fee_recipient_conflicts(
+    recipient_written_in_block: bool,
+    recipient_observed: bool,
+    recipient_existed_at_snapshot: bool,
+    recipient_balance_decreased: bool,
+) -> bool {
+    recipient_written_in_block
+        && (recipient_observed || !recipient_existed_at_snapshot || recipient_balance_decreased)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        fee_recipient_conflicts, optimistic_commit_decision, read_set_conflicts,
+        OptimisticCommitDecision,
+    };
+    use std::collections::BTreeSet;
+
+    #[test]
+    fn read_set_conflicts_ignores_exempt_address() {
+        let exempt = [0xfe; 20];
+        let written = BTreeSet::from([[1u8; 20], exempt]);
+        assert!(!read_set_conflicts(&[[2u8; 20], exempt], &written, exempt));
+        assert!(read_set_conflicts(
+            &[[2u8; 20], [1u8; 20]],
+            &written,
+            exempt
+        ));
+        assert!(!read_set_conflicts(&[], &written, exempt));
+    }
+
+    #[test]
+    fn commit_decision_requires_clean_pre_execution() {
+        assert_eq!(
+            optimistic_commit_decision(true, false, false),
+            OptimisticCommitDecision::Accept
+        );
+        assert_eq!(
+            optimistic_commit_decision(false, false, false),
+            OptimisticCommitDecision::Reexecute
+        );
+        assert_eq!(
+            optimistic_commit_decision(true, true, false),
+            OptimisticCommitDecision::Reexecute
+        );
+        assert_eq!(
+            optimistic_commit_decision(true, false, true),
+            OptimisticCommitDecision::Reexecute
+        );
+    }
+
+    #[test]
+    fn fee_recipient_only_conflicts_after_block_write() {
+        assert!(!fee_recipient_conflicts(false, true, false, true));
+        assert!(!fee_recipient_conflicts(true, false, true, false));
+        assert!(fee_recipient_conflicts(true, true, true, false));
+        assert!(fee_recipient_conflicts(true, false, false, false));
+        assert!(fee_recipient_conflicts(true, false, true, true));
+    }
+}
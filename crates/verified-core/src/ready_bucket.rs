@@ -0,0 +1,107 @@
+//! どこで: ready候補の順序付け / 何を: base fee依存のbucket分類と境界 / なぜ: base fee変動時に影響Txだけ再分類するため
+
+#[cfg(verus_keep_ghost)]
+use vstd::prelude::*;
+
+#[cfg_attr(verus_keep_ghost, verus_verify)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ReadyFeeBucket {
+    /// effective = base_fee + max_priority（priorityで順序が決まる）
+    Tip(u128),
+    /// effective = max_fee（max_feeで順序が決まる）
+    Cap(u128),
+    /// effective = 0（無効feeまたはbase fee未満）
+    Zero,
+}
+
+pub const READY_FEE_BOUNDARY_SLOTS: usize = 3;
+
+#[cfg_attr(verus_keep_ghost, verus_spec(bucket => ensures
+    max_priority > max_fee ==> bucket == ReadyFeeBucket::Zero,
+    max_fee < base_fee as u128 ==> bucket == ReadyFeeBucket::Zero,
+))]
+pub fn classify(max_fee: u128, max_priority: u128, base_fee: u64) -> ReadyFeeBucket {
+    if max_priority > max_fee {
+        return ReadyFeeBucket::Zero;
+    }
+    let base_fee = u128::from(base_fee);
+    if max_fee < base_fee {
+        return ReadyFeeBucket::Zero;
+    }
+    let tip_bound = base_fee.saturating_add(max_priority);
+    if max_fee.min(tip_bound) > u128::from(u64::MAX) {
+        return ReadyFeeBucket::Zero;
+    }
+    if tip_bound <= max_fee {
+        ReadyFeeBucket::Tip(max_priority)
+    } else {
+        ReadyFeeBucket::Cap(max_fee)
+    }
+}
+
+impl ReadyFeeBucket {
+    /// verified_core::fee::effective_gas_price(..).unwrap_or(0) と一致する。
+    pub fn effective_gas_price(self, base_fee: u64) -> u64 {
+        match self {
+            ReadyFeeBucket::Tip(priority) => {
+                u64::try_from(u128::from(base_fee).saturating_add(priority)).unwrap_or(0)
+            }
+            ReadyFeeBucket::Cap(max_fee) => u64::try_from(max_fee).unwrap_or(0),
+            ReadyFeeBucket::Zero => 0,
+        }
+    }
+}
+
+/// classify の各判定は `base_fee <= x` の形で、xがこの境界値になる。
+/// 有効feeでないTx（priority > max_fee）は base fee に依存しないため境界を持たない。
+pub fn boundaries(max_fee: u128, max_priority: u128) -> [Option<u128>; READY_FEE_BOUNDARY_SLOTS] {
+    if max_priority > max_fee {
+        return [None; READY_FEE_BOUNDARY_SLOTS];
+    }
+    [
+        Some(max_fee),
+        Some(max_fee - max_priority),
+        u128::from(u64::MAX).checked_sub(max_priority),
+    ]
+}
+
+#[cfg_attr(verus_keep_ghost, verus_spec(crossed => ensures
+    crossed == (
+        (old_base_fee as u128) <= boundary && boundary < (new_base_fee as u128)
+        || (new_base_fee as u128) <= boundary && boundary < (old_base_fee as u128)
+    ),
+))]
+pub fn boundary_crossed(boundary: u128, old_base_fee: u64, new_base_fee: u64) -> bool {
+    let lo = u128::from(old_base_fee.min(new_base_fee));
+    let hi = u128::from(old_base_fee.max(new_base_fee));
+    lo <= boundary && boundary < hi
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{boundaries, boundary_crossed, classify, ReadyFeeBucket};
+    use crate::fee::effective_gas_price;
+
+    #[test]
+    fn classify_matches_effective_gas_price_regimes() {
+        assert_eq!(classify(100, 10, 50), ReadyFeeBucket::Tip(10));
+        assert_eq!(classify(100, 10, 95), ReadyFeeBucket::Cap(100));
+        assert_eq!(classify(100, 10, 101), ReadyFeeBucket::Zero);
+        assert_eq!(classify(10, 11, 0), ReadyFeeBucket::Zero);
+        assert_eq!(
+            classify(100, 10, 95).effective_gas_price(95),
+            effective_gas_price(100, 10, 95).unwrap_or(0)
+        );
+    }
+
+    #[test]
+    fn boundaries_cover_bucket_changes() {
+        let bounds = boundaries(100, 10);
+        assert_eq!(bounds[0], Some(100));
+        assert_eq!(bounds[1], Some(90));
+        assert!(boundaries(10, 11).iter().all(Option::is_none));
+        assert!(boundary_crossed(90, 80, 95));
+        assert!(boundary_crossed(90, 95, 80));
+        assert!(!boundary_crossed(90, 91, 95));
+    }
+}
@@ -0,0 +1,55 @@
+//! どこで: wrap 準備金の照合 / 何を: 負債合計と不足の判定 / なぜ: 台帳の預かり残高が wrapped token と処理中 request を裏付けているかを一つの式で決めるため
+
+#[cfg(verus_keep_ghost)]
+use vstd::prelude::*;
+
+/// 発行済み wrapped token に、台帳へ入ったがまだ mint していない wrap と、burn 済みでまだ払い出していない unwrap を足す。
+#[cfg_attr(verus_keep_ghost, verus_spec(total => ensures
+    wrapped_supply + in_flight_wrap + in_flight_unwrap <= u128::MAX ==>
+        total == Some((wrapped_supply + in_flight_wrap + in_flight_unwrap) as u128),
+    wrapped_supply + in_flight_wrap + in_flight_unwrap > u128::MAX ==> total == Option::<u128>::None,
+))]
+pub fn reserve_liabilities(
+    wrapped_supply: u128,
+    in_flight_wrap: u128,
+    in_flight_unwrap: u128,
+) -> Option<u128> {
+    wrapped_supply
+        .checked_add(in_flight_wrap)?
+        .checked_add(in_flight_unwrap)
+}
+
+/// 負債を数えきれないときも不足として扱い、wrap を止める側に倒す。
+#[cfg_attr(verus_keep_ghost, verus_spec(shortfall => ensures
+    shortfall == match liabilities {
+        Some(total) => ledger_balance < total,
+        None => true,
+    },
+))]
+pub fn reserve_shortfall(ledger_balance: u128, liabilities: Option<u128>) -> bool {
+    match liabilities {
+        Some(total) => ledger_balance < total,
+        None => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{reserve_liabilities, reserve_shortfall};
+
+    #[test]
+    fn liabilities_sum_supply_and_in_flight_requests() {
+        assert_eq!(reserve_liabilities(100, 20, 3), Some(123));
+        assert_eq!(reserve_liabilities(u128::MAX, 0, 0), Some(u128::MAX));
+        assert_eq!(reserve_liabilities(u128::MAX, 1, 0), None);
+        assert_eq!(reserve_liabilities(1, 0, u128::MAX), None);
+    }
+
+    #[test]
+    fn shortfall_only_when_balance_below_liabilities() {
+        assert!(!reserve_shortfall(123, Some(123)));
+        assert!(!reserve_shortfall(124, Some(123)));
+        assert!(reserve_shortfall(122, Some(123)));
+        assert!(reserve_shortfall(u128::MAX, None));
+    }
+}
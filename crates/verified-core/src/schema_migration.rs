@@ -0,0 +1,51 @@
+//! どこで: schema migration registry / 何を: 版範囲に対する migration の適用判定と upgrade checkpoint の受理判定 / なぜ: upgrade 元の版ごとに必要な migration だけを順に走らせ、互換の無い downgrade を止めるため
+
+#[cfg(verus_keep_ghost)]
+use vstd::prelude::*;
+
+/// `from_version` から `to_version` への upgrade で、`migration_to` に到達する migration が必要か。
+/// 途中の版から来た場合も、その版以降に導入された migration は全て走らせる。
+#[cfg_attr(verus_keep_ghost, verus_spec(applies => ensures
+    applies == (from_version < migration_to && migration_to <= to_version),
+))]
+pub fn schema_migration_applies(from_version: u32, to_version: u32, migration_to: u32) -> bool {
+    from_version < migration_to && migration_to <= to_version
+}
+
+/// 前の wasm が残した upgrade checkpoint を読み込んでよいか。
+/// 未知の checkpoint 版と、この wasm が扱える版より新しい stable layout は拒否する。
+#[cfg_attr(verus_keep_ghost, verus_spec(accepted => ensures
+    accepted == (stored_version != 0 && stored_version <= supported_version
+        && stored_schema <= supported_schema),
+))]
+pub fn upgrade_checkpoint_accepted(
+    stored_version: u32,
+    stored_schema: u32,
+    supported_version: u32,
+    supported_schema: u32,
+) -> bool {
+    stored_version != 0 && stored_version <= supported_version && stored_schema <= supported_schema
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{schema_migration_applies, upgrade_checkpoint_accepted};
+
+    #[test]
+    fn applies_only_inside_upgrade_range() {
+        assert!(schema_migration_applies(4, 6, 5));
+        assert!(schema_migration_applies(2, 6, 5));
+        assert!(!schema_migration_applies(5, 6, 5));
+        assert!(!schema_migration_applies(2, 4, 5));
+        assert!(!schema_migration_applies(6, 6, 6));
+    }
+
+    #[test]
+    fn checkpoint_rejects_unknown_version_and_schema_downgrade() {
+        assert!(upgrade_checkpoint_accepted(2, 5, 2, 5));
+        assert!(upgrade_checkpoint_accepted(1, 3, 2, 5));
+        assert!(!upgrade_checkpoint_accepted(0, 3, 2, 5));
+        assert!(!upgrade_checkpoint_accepted(3, 3, 2, 5));
+        assert!(!upgrade_checkpoint_accepted(2, 6, 2, 5));
+    }
+}
@@ -0,0 +1,75 @@
+//! どこで: 整合性scrub / 何を: block上のtxに対する索引観測の分類 / なぜ: receipt_index の双方向整合が崩れたときに、どの欠けを安全に補えるかを決めるため
+
+#[cfg(verus_keep_ghost)]
+use vstd::prelude::*;
+
+use crate::receipt_index::ReceiptIndexObservation;
+
+#[cfg_attr(verus_keep_ghost, verus_verify)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct BlockTxScrub {
+    pub missing_tx_index: bool,
+    pub missing_included_loc: bool,
+    pub missing_receipt: bool,
+    /// 3つとも揃っているのに位置が一致しない
+    pub location_mismatch: bool,
+}
+
+/// block を正として観測を分類する。欠けは block 上の位置から補えるが、食い違いは報告だけにする。
+#[cfg_attr(verus_keep_ghost, verus_spec(result => ensures
+    result.missing_tx_index == !input.tx_index_present,
+    result.missing_included_loc == !input.included_loc_present,
+    result.missing_receipt == !input.receipt_present,
+    result.location_mismatch == (
+        input.tx_index_present && input.included_loc_present && input.receipt_present
+        && !(input.index_matches_loc && input.receipt_matches_loc && input.loc_points_to_block_tx)
+    ),
+))]
+pub fn classify_block_tx(input: ReceiptIndexObservation) -> BlockTxScrub {
+    let all_present = input.tx_index_present && input.included_loc_present && input.receipt_present;
+    BlockTxScrub {
+        missing_tx_index: !input.tx_index_present,
+        missing_included_loc: !input.included_loc_present,
+        missing_receipt: !input.receipt_present,
+        location_mismatch: all_present
+            && !(input.index_matches_loc
+                && input.receipt_matches_loc
+                && input.loc_points_to_block_tx),
+    }
+}
+
+#[cfg_attr(verus_keep_ghost, verus_spec(clean => ensures
+    clean == (!scrub.missing_tx_index && !scrub.missing_included_loc
+        && !scrub.missing_receipt && !scrub.location_mismatch),
+))]
+pub fn block_tx_clean(scrub: BlockTxScrub) -> bool {
+    !scrub.missing_tx_index
+        && !scrub.missing_included_loc
+        && !scrub.missing_receipt
+        && !scrub.location_mismatch
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{block_tx_clean, classify_block_tx};
+    use crate::receipt_index::{receipt_index_target_observation_safe, ReceiptIndexObservation};
+
+    #[test]
+    fn clean_block_tx_matches_included_target_observation() {
+        for bits in 0u8..64 {
+            let input = ReceiptIndexObservation {
+                tx_index_present: bits & 1 != 0,
+                receipt_present: bits & 2 != 0,
+                included_loc_present: bits & 4 != 0,
+                index_matches_loc: bits & 8 != 0,
+                receipt_matches_loc: bits & 16 != 0,
+                loc_points_to_block_tx: bits & 32 != 0,
+            };
+            assert_eq!(
+                block_tx_clean(classify_block_tx(input)),
+                receipt_index_target_observation_safe(true, input),
+                "bits={bits:06b}"
+            );
+        }
+    }
+}
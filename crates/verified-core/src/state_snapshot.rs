@@ -0,0 +1,51 @@
+//! どこで: 状態スナップショットのexport/import / 何を: cursor継続条件とimport開始条件 / なぜ: 途中で状態が動いたページや稼働中canisterへの上書きを防ぐため
+
+#[cfg(verus_keep_ghost)]
+use vstd::prelude::*;
+
+/// cursor は発行時と同じ head・同じ evm_state_epoch の上でのみ続きを読める。
+/// どちらかが動いていれば、前のページと混ぜると一貫しない状態になる。
+#[cfg_attr(verus_keep_ghost, verus_spec(current => ensures
+    current == (cursor_head == head_number && cursor_epoch == state_epoch),
+))]
+pub fn snapshot_cursor_current(
+    cursor_head: u64,
+    cursor_epoch: u64,
+    head_number: u64,
+    state_epoch: u64,
+) -> bool {
+    cursor_head == head_number && cursor_epoch == state_epoch
+}
+
+/// import は genesis のまま Tx を一件も抱えていない canister にだけ始められる。
+/// 取り込み途中の再開始は、書き込みが止まっているため同じ条件で判定できる。
+#[cfg_attr(verus_keep_ghost, verus_spec(allowed => ensures
+    allowed == (head_number == 0 && queued_txs == 0 && !staged_block_active),
+))]
+pub fn snapshot_import_begin_allowed(
+    head_number: u64,
+    queued_txs: u64,
+    staged_block_active: bool,
+) -> bool {
+    head_number == 0 && queued_txs == 0 && !staged_block_active
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{snapshot_cursor_current, snapshot_import_begin_allowed};
+
+    #[test]
+    fn cursor_is_stale_after_head_or_epoch_moves() {
+        assert!(snapshot_cursor_current(5, 9, 5, 9));
+        assert!(!snapshot_cursor_current(5, 9, 6, 9));
+        assert!(!snapshot_cursor_current(5, 9, 5, 10));
+    }
+
+    #[test]
+    fn import_requires_empty_genesis_canister() {
+        assert!(snapshot_import_begin_allowed(0, 0, false));
+        assert!(!snapshot_import_begin_allowed(1, 0, false));
+        assert!(!snapshot_import_begin_allowed(0, 1, false));
+        assert!(!snapshot_import_begin_allowed(0, 0, true));
+    }
+}
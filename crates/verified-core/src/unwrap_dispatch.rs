@@ -11,6 +11,8 @@ pub const UNWRAP_STATUS_DISPATCHING: u64 = 1;
 pub const UNWRAP_STATUS_DISPATCHED: u64 = 2;
 #[cfg_attr(verus_keep_ghost, verus_verify)]
 pub const UNWRAP_STATUS_DISPATCH_FAILED: u64 = 3;
+#[cfg_attr(verus_keep_ghost, verus_verify)]
+pub const UNWRAP_STATUS_DISPATCH_UNCERTAIN: u64 = 4;
 
 #[cfg_attr(verus_keep_ghost, verus_spec(result => ensures
     result == (
@@ -24,7 +26,8 @@ pub fn unwrap_dispatch_terminal_raw(status: u64) -> bool {
 
 #[cfg_attr(verus_keep_ghost, verus_spec(result => ensures
     result == (
-        previous_status == UNWRAP_STATUS_DISPATCH_FAILED
+        (previous_status == UNWRAP_STATUS_DISPATCH_FAILED
+            || previous_status == UNWRAP_STATUS_DISPATCH_UNCERTAIN)
         && next_status == UNWRAP_STATUS_QUEUED
         && queue_inserted == 1
         && error_cleared == 1
@@ -36,7 +39,8 @@ pub fn unwrap_retry_transition_safe_raw(
     queue_inserted: u64,
     error_cleared: u64,
 ) -> bool {
-    previous_status == UNWRAP_STATUS_DISPATCH_FAILED
+    (previous_status == UNWRAP_STATUS_DISPATCH_FAILED
+        || previous_status == UNWRAP_STATUS_DISPATCH_UNCERTAIN)
         && next_status == UNWRAP_STATUS_QUEUED
         && queue_inserted == 1
         && error_cleared == 1
@@ -55,11 +59,13 @@ pub fn unwrap_retry_transition_safe_raw(
             && error_present == 0
             && queue_inserted == 0)
         || (previous_status == UNWRAP_STATUS_DISPATCHING
-            && next_status == UNWRAP_STATUS_DISPATCH_FAILED
+            && (next_status == UNWRAP_STATUS_DISPATCH_FAILED
+                || next_status == UNWRAP_STATUS_DISPATCH_UNCERTAIN)
             && ledger_tx_id_present == 0
             && error_present == 1
             && queue_inserted == 0)
-        || (previous_status == UNWRAP_STATUS_DISPATCH_FAILED
+        || ((previous_status == UNWRAP_STATUS_DISPATCH_FAILED
+            || previous_status == UNWRAP_STATUS_DISPATCH_UNCERTAIN)
             && next_status == UNWRAP_STATUS_QUEUED
             && ledger_tx_id_present == 0
             && error_present == 0
@@ -84,11 +90,13 @@ pub fn unwrap_dispatch_transition_safe_raw(
             && error_present == 0
             && queue_inserted == 0)
         || (previous_status == UNWRAP_STATUS_DISPATCHING
-            && next_status == UNWRAP_STATUS_DISPATCH_FAILED
+            && (next_status == UNWRAP_STATUS_DISPATCH_FAILED
+                || next_status == UNWRAP_STATUS_DISPATCH_UNCERTAIN)
             && ledger_tx_id_present == 0
             && error_present == 1
             && queue_inserted == 0)
-        || (previous_status == UNWRAP_STATUS_DISPATCH_FAILED
+        || ((previous_status == UNWRAP_STATUS_DISPATCH_FAILED
+            || previous_status == UNWRAP_STATUS_DISPATCH_UNCERTAIN)
             && next_status == UNWRAP_STATUS_QUEUED
             && ledger_tx_id_present == 0
             && error_present == 0
@@ -108,7 +116,8 @@ pub fn unwrap_dispatch_transition_safe_raw(
             && queue_inserted == 1
             && timestamp_updated == 1)
         || ((previous_status == UNWRAP_STATUS_DISPATCHED
-            || previous_status == UNWRAP_STATUS_DISPATCH_FAILED)
+            || previous_status == UNWRAP_STATUS_DISPATCH_FAILED
+            || previous_status == UNWRAP_STATUS_DISPATCH_UNCERTAIN)
             && next_status == previous_status
             && queue_inserted == 0
             && timestamp_updated == 0)
@@ -132,8 +141,86 @@ pub fn unwrap_upgrade_recovery_safe_raw(
             && queue_inserted == 1
             && timestamp_updated == 1)
         || ((previous_status == UNWRAP_STATUS_DISPATCHED
-            || previous_status == UNWRAP_STATUS_DISPATCH_FAILED)
+            || previous_status == UNWRAP_STATUS_DISPATCH_FAILED
+            || previous_status == UNWRAP_STATUS_DISPATCH_UNCERTAIN)
             && next_status == previous_status
             && queue_inserted == 0
             && timestamp_updated == 0)
 }
+
+/// 結果の分からない dispatch は、ledger の重複排除が効く間だけ同じ created_at_time で再送してよい。
+#[cfg_attr(verus_keep_ghost, verus_spec(result => ensures
+    result == (
+        transfer_created_at_time != 0
+        && (now <= transfer_created_at_time || now - transfer_created_at_time < tx_window)
+    ),
+))]
+pub fn unwrap_uncertain_retry_safe_raw(
+    now: u64,
+    transfer_created_at_time: u64,
+    tx_window: u64,
+) -> bool {
+    transfer_created_at_time != 0
+        && (now <= transfer_created_at_time || now - transfer_created_at_time < tx_window)
+}
+
+/// ledger 照合の結論は二つだけ。載っていれば Dispatched、無ければ新しい created_at_time で再送できる失敗にする。
+#[cfg_attr(verus_keep_ghost, verus_spec(result => ensures
+    result == (
+        previous_status == UNWRAP_STATUS_DISPATCH_UNCERTAIN
+        && ((next_status == UNWRAP_STATUS_DISPATCHED
+            && ledger_tx_id_present == 1
+            && error_present == 0
+            && created_at_cleared == 0)
+            || (next_status == UNWRAP_STATUS_DISPATCH_FAILED
+                && ledger_tx_id_present == 0
+                && error_present == 1
+                && created_at_cleared == 1))
+    ),
+))]
+pub fn unwrap_reconcile_transition_safe_raw(
+    previous_status: u64,
+    next_status: u64,
+    ledger_tx_id_present: u64,
+    error_present: u64,
+    created_at_cleared: u64,
+) -> bool {
+    previous_status == UNWRAP_STATUS_DISPATCH_UNCERTAIN
+        && ((next_status == UNWRAP_STATUS_DISPATCHED
+            && ledger_tx_id_present == 1
+            && error_present == 0
+            && created_at_cleared == 0)
+            || (next_status == UNWRAP_STATUS_DISPATCH_FAILED
+                && ledger_tx_id_present == 0
+                && error_present == 1
+                && created_at_cleared == 1))
+}
+
+/// ledger は created_at_time が時刻より permitted_drift 以上未来の送金を拒むので、
+/// それより古い block の手前に当該送金は無い。
+#[cfg_attr(verus_keep_ghost, verus_spec(result => ensures
+    result == (
+        block_timestamp < created_at_time
+        && created_at_time - block_timestamp > permitted_drift
+    ),
+))]
+pub fn ledger_block_predates_transfer_raw(
+    block_timestamp: u64,
+    created_at_time: u64,
+    permitted_drift: u64,
+) -> bool {
+    block_timestamp < created_at_time && created_at_time - block_timestamp > permitted_drift
+}
+
+/// まとめて送る dispatch は、tick 全体の件数と同じ ledger への同時呼び出し数の両方を上限で抑える。
+#[cfg_attr(verus_keep_ghost, verus_spec(result => ensures
+    result == (batch_len < batch_max && ledger_in_flight < ledger_cap),
+))]
+pub fn unwrap_batch_admit_raw(
+    batch_len: u64,
+    batch_max: u64,
+    ledger_in_flight: u64,
+    ledger_cap: u64,
+) -> bool {
+    batch_len < batch_max && ledger_in_flight < ledger_cap
+}
@@ -1,4 +1,4 @@
-//! どこで: wrap quote 境界 / 何を: 承認上限とfee構成 / なぜ: ユーザー承認を超えるfee徴収を防ぐため
+//! どこで: wrap quote 境界 / 何を: 承認上限、fee構成、asset払いのmint量 / なぜ: ユーザー承認を超えるfee徴収と、引き取った量を超えるmintを防ぐため
 
 #[cfg(verus_keep_ghost)]
 use vstd::prelude::*;
@@ -43,3 +43,115 @@ pub fn wrap_quote_components_safe_raw(
         && gas_fee_component_matches == 1
         && charged_fee_component_matches == 1
 }
+
+/// 段階 fee の bps は amount 全体を上限にする。
+#[cfg_attr(verus_keep_ghost, verus_verify)]
+pub const FEE_TIER_MAX_BPS: u32 = 10_000;
+
+/// amount に比例する段階 fee。端数は切り上げ、溢れるときは None。
+#[cfg_attr(verus_keep_ghost, verus_spec(result => ensures
+    amount * (fee_bps as u128) + GAS_PRICE_DENOMINATOR_BPS - 1 <= u128::MAX ==>
+        result == Some(((amount * (fee_bps as u128) + GAS_PRICE_DENOMINATOR_BPS - 1) / GAS_PRICE_DENOMINATOR_BPS) as u128),
+))]
+pub fn wrap_amount_fee_e8s(amount: u128, fee_bps: u32) -> Option<u128> {
+    let scaled = amount
+        .checked_mul(u128::from(fee_bps))?
+        .checked_add(GAS_PRICE_DENOMINATOR_BPS - 1)?;
+    Some(scaled / GAS_PRICE_DENOMINATOR_BPS)
+}
+
+/// 別 ledger で払うときは gas 分を含める。wrap 対象の asset で払うときは gas を asset 単位へ換算できないので、
+/// 固定 fee と段階 fee だけを取る。
+#[cfg_attr(verus_keep_ghost, verus_spec(result => ensures
+    fee_in_asset == 1 && flat_fee_e8s + amount_fee_e8s <= u128::MAX ==>
+        result == Some((flat_fee_e8s + amount_fee_e8s) as u128),
+    fee_in_asset != 1 && gas_fee_e8s + flat_fee_e8s + amount_fee_e8s <= u128::MAX ==>
+        result == Some((gas_fee_e8s + flat_fee_e8s + amount_fee_e8s) as u128),
+))]
+pub fn wrap_charged_fee_e8s_raw(
+    fee_in_asset: u64,
+    gas_fee_e8s: u128,
+    flat_fee_e8s: u128,
+    amount_fee_e8s: u128,
+) -> Option<u128> {
+    let fee = flat_fee_e8s.checked_add(amount_fee_e8s)?;
+    if fee_in_asset == 1 {
+        Some(fee)
+    } else {
+        fee.checked_add(gas_fee_e8s)
+    }
+}
+
+/// mint する量。asset で払う fee は mint 量から引き、何も mint できない wrap は受け付けない。
+#[cfg_attr(verus_keep_ghost, verus_spec(result => ensures
+    fee_in_asset == 1 && charged_fee_e8s < amount ==>
+        result == Some((amount - charged_fee_e8s) as u128),
+    fee_in_asset == 1 && charged_fee_e8s >= amount ==> result == Option::<u128>::None,
+    fee_in_asset != 1 ==> result == Some(amount),
+))]
+pub fn wrap_mint_amount_raw(
+    fee_in_asset: u64,
+    amount: u128,
+    charged_fee_e8s: u128,
+) -> Option<u128> {
+    if fee_in_asset != 1 {
+        return Some(amount);
+    }
+    if charged_fee_e8s >= amount {
+        return None;
+    }
+    Some(amount - charged_fee_e8s)
+}
+
+/// asset で払う wrap は mint 量と fee の和が引き取った量に一致し、別 ledger で払う wrap は全量を mint する。
+#[cfg_attr(verus_keep_ghost, verus_spec(result => ensures
+    result == (
+        if fee_in_asset == 1 {
+            mint_amount > 0 && mint_amount + charged_fee_e8s == pulled_amount
+        } else {
+            mint_amount == pulled_amount
+        }
+    ),
+))]
+pub fn wrap_mint_conserves_pull_raw(
+    fee_in_asset: u64,
+    pulled_amount: u128,
+    charged_fee_e8s: u128,
+    mint_amount: u128,
+) -> bool {
+    if fee_in_asset == 1 {
+        mint_amount > 0 && mint_amount.checked_add(charged_fee_e8s) == Some(pulled_amount)
+    } else {
+        mint_amount == pulled_amount
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        wrap_amount_fee_e8s, wrap_charged_fee_e8s_raw, wrap_mint_amount_raw,
+        wrap_mint_conserves_pull_raw,
+    };
+
+    #[test]
+    fn tiered_fee_rounds_up_and_gas_only_counts_for_ledger_fees() {
+        assert_eq!(wrap_amount_fee_e8s(10_000, 25), Some(25));
+        assert_eq!(wrap_amount_fee_e8s(1, 25), Some(1));
+        assert_eq!(wrap_amount_fee_e8s(0, 25), Some(0));
+        assert_eq!(wrap_amount_fee_e8s(u128::MAX, 2), None);
+        assert_eq!(wrap_charged_fee_e8s_raw(0, 7, 10, 3), Some(20));
+        assert_eq!(wrap_charged_fee_e8s_raw(1, 7, 10, 3), Some(13));
+        assert_eq!(wrap_charged_fee_e8s_raw(0, u128::MAX, 1, 0), None);
+    }
+
+    #[test]
+    fn mint_amount_deducts_in_asset_fee_and_conserves_pull() {
+        assert_eq!(wrap_mint_amount_raw(0, 100, 30), Some(100));
+        assert_eq!(wrap_mint_amount_raw(1, 100, 30), Some(70));
+        assert_eq!(wrap_mint_amount_raw(1, 100, 100), None);
+        assert!(wrap_mint_conserves_pull_raw(1, 100, 30, 70));
+        assert!(!wrap_mint_conserves_pull_raw(1, 100, 30, 100));
+        assert!(!wrap_mint_conserves_pull_raw(1, 30, 30, 0));
+        assert!(wrap_mint_conserves_pull_raw(0, 100, 30, 100));
+    }
+}
@@ -1,4 +1,4 @@
-//! どこで: wrap request 境界 / 何を: pending・idempotency・stage・recover条件 / なぜ: 二重請求と不正復旧を防ぐため
+//! どこで: wrap request 境界 / 何を: pending・idempotency・stage・recover条件・期限切れ返金 / なぜ: 二重請求と不正復旧を防ぐため
 #![allow(clippy::too_many_arguments)]
 
 #[cfg(verus_keep_ghost)]
@@ -149,6 +149,7 @@ pub fn wrap_idempotent_response_safe_raw(
             && incomplete_error == 1)
 }
 
+/// Refunding へ入れるのは recovering (手動 recover か期限切れ返金の判定済み) のときだけ。
 #[cfg_attr(verus_keep_ghost, verus_spec(result => ensures
     result == (
         (previous_stage == WRAP_STAGE_FEE_PENDING
@@ -168,7 +169,8 @@ pub fn wrap_idempotent_response_safe_raw(
         || (previous_stage == WRAP_STAGE_PULLED
             && (next_stage == WRAP_STAGE_PULLED
                 || next_stage == WRAP_STAGE_MINT_SUBMITTING
-                || next_stage == WRAP_STAGE_FAILED))
+                || next_stage == WRAP_STAGE_FAILED
+                || (recovering == 1 && next_stage == WRAP_STAGE_REFUNDING)))
         || (previous_stage == WRAP_STAGE_MINT_SUBMITTING
             && (next_stage == WRAP_STAGE_MINT_SUBMITTING
                 || next_stage == WRAP_STAGE_MINT_SUBMITTED
@@ -177,7 +179,8 @@ pub fn wrap_idempotent_response_safe_raw(
         || (previous_stage == WRAP_STAGE_MINT_SUBMITTED
             && (next_stage == WRAP_STAGE_MINT_SUBMITTED
                 || next_stage == WRAP_STAGE_SUCCEEDED
-                || next_stage == WRAP_STAGE_FAILED))
+                || next_stage == WRAP_STAGE_FAILED
+                || (recovering == 1 && next_stage == WRAP_STAGE_REFUNDING)))
         || (previous_stage == WRAP_STAGE_FAILED
             && ((next_stage == WRAP_STAGE_FAILED)
                 || (recovering == 1 && next_stage == WRAP_STAGE_REFUNDING)))
@@ -209,7 +212,8 @@ pub fn wrap_stage_transition_safe_raw(
         || (previous_stage == WRAP_STAGE_PULLED
             && (next_stage == WRAP_STAGE_PULLED
                 || next_stage == WRAP_STAGE_MINT_SUBMITTING
-                || next_stage == WRAP_STAGE_FAILED))
+                || next_stage == WRAP_STAGE_FAILED
+                || (recovering == 1 && next_stage == WRAP_STAGE_REFUNDING)))
         || (previous_stage == WRAP_STAGE_MINT_SUBMITTING
             && (next_stage == WRAP_STAGE_MINT_SUBMITTING
                 || next_stage == WRAP_STAGE_MINT_SUBMITTED
@@ -218,7 +222,8 @@ pub fn wrap_stage_transition_safe_raw(
         || (previous_stage == WRAP_STAGE_MINT_SUBMITTED
             && (next_stage == WRAP_STAGE_MINT_SUBMITTED
                 || next_stage == WRAP_STAGE_SUCCEEDED
-                || next_stage == WRAP_STAGE_FAILED))
+                || next_stage == WRAP_STAGE_FAILED
+                || (recovering == 1 && next_stage == WRAP_STAGE_REFUNDING)))
         || (previous_stage == WRAP_STAGE_FAILED
             && ((next_stage == WRAP_STAGE_FAILED)
                 || (recovering == 1 && next_stage == WRAP_STAGE_REFUNDING)))
@@ -260,6 +265,104 @@ pub fn wrap_recover_allowed_raw(
         && withdraw_ledger_tx_id_present == 0
 }
 
+/// 期限切れの自動返金で mint が後から着地しないこと。
+/// 失敗が確定して復旧可能な request、mint を出す前に queue で待つ request、mint tx が drop された request だけを返金する。
+#[cfg_attr(verus_keep_ghost, verus_spec(result => ensures
+    result == (
+        (status == WRAP_REQUEST_STATUS_FAILED
+            && stage == WRAP_STAGE_FAILED
+            && mint_failed_recoverable == 1)
+        || (status == WRAP_REQUEST_STATUS_QUEUED
+            && stage == WRAP_STAGE_PULLED
+            && mint_tx_id_present == 0
+            && mint_submit_started == 0)
+        || (status == WRAP_REQUEST_STATUS_RUNNING
+            && stage == WRAP_STAGE_MINT_SUBMITTED
+            && mint_tx_id_present == 1
+            && mint_tx_dropped == 1)
+    ),
+))]
+pub fn wrap_mint_cannot_land_raw(
+    status: u64,
+    stage: u64,
+    mint_failed_recoverable: u64,
+    mint_tx_id_present: u64,
+    mint_submit_started: u64,
+    mint_tx_dropped: u64,
+) -> bool {
+    (status == WRAP_REQUEST_STATUS_FAILED
+        && stage == WRAP_STAGE_FAILED
+        && mint_failed_recoverable == 1)
+        || (status == WRAP_REQUEST_STATUS_QUEUED
+            && stage == WRAP_STAGE_PULLED
+            && mint_tx_id_present == 0
+            && mint_submit_started == 0)
+        || (status == WRAP_REQUEST_STATUS_RUNNING
+            && stage == WRAP_STAGE_MINT_SUBMITTED
+            && mint_tx_id_present == 1
+            && mint_tx_dropped == 1)
+}
+
+/// 期限 (0 は期限なし) を過ぎ、引き取り済みで未返金、かつ mint が着地し得ない wrap だけを自動返金する。
+#[cfg_attr(verus_keep_ghost, verus_spec(result => ensures
+    result == (
+        deadline_at != 0
+        && now >= deadline_at
+        && gas_limit_nonzero == 1
+        && pull_ledger_tx_id_present == 1
+        && withdraw_in_progress == 0
+        && withdrawn == 0
+        && withdraw_ledger_tx_id_present == 0
+        && mint_cannot_land == 1
+    ),
+))]
+pub fn wrap_expiry_refund_allowed_raw(
+    now: u64,
+    deadline_at: u64,
+    gas_limit_nonzero: u64,
+    pull_ledger_tx_id_present: u64,
+    withdraw_in_progress: u64,
+    withdrawn: u64,
+    withdraw_ledger_tx_id_present: u64,
+    mint_cannot_land: u64,
+) -> bool {
+    deadline_at != 0
+        && now >= deadline_at
+        && gas_limit_nonzero == 1
+        && pull_ledger_tx_id_present == 1
+        && withdraw_in_progress == 0
+        && withdrawn == 0
+        && withdraw_ledger_tx_id_present == 0
+        && mint_cannot_land == 1
+}
+
+/// 自動返金で戻す量。asset で受け取った fee と返金の ledger fee を引き、custody から持ち出さない。
+#[cfg_attr(verus_keep_ghost, verus_spec(result => ensures
+    fee_in_asset == 1 && charged_fee_e8s + ledger_fee_e8s < pulled_amount ==>
+        result == Some((pulled_amount - charged_fee_e8s - ledger_fee_e8s) as u128),
+    fee_in_asset == 1 && charged_fee_e8s + ledger_fee_e8s >= pulled_amount ==>
+        result == Option::<u128>::None,
+    fee_in_asset != 1 && ledger_fee_e8s < pulled_amount ==>
+        result == Some((pulled_amount - ledger_fee_e8s) as u128),
+    fee_in_asset != 1 && ledger_fee_e8s >= pulled_amount ==> result == Option::<u128>::None,
+))]
+pub fn wrap_expiry_refund_amount_raw(
+    fee_in_asset: u64,
+    pulled_amount: u128,
+    charged_fee_e8s: u128,
+    ledger_fee_e8s: u128,
+) -> Option<u128> {
+    let kept = if fee_in_asset == 1 {
+        charged_fee_e8s.checked_add(ledger_fee_e8s)?
+    } else {
+        ledger_fee_e8s
+    };
+    if kept >= pulled_amount {
+        return None;
+    }
+    Some(pulled_amount - kept)
+}
+
 #[cfg_attr(verus_keep_ghost, verus_spec(result => ensures
     result == (
         gas_limit_zero == 1
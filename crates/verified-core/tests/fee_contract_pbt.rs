@@ -2,7 +2,7 @@
 
 use proptest::prelude::*;
 use verified_core::fee::{
-    base_fee_reward, effective_gas_price, l2_fee, min_fee_satisfied, total_fee,
+    base_fee_reward, effective_gas_price, l2_fee, min_fee_satisfied, pending_fee_ranks, total_fee,
 };
 
 proptest! {
@@ -57,4 +57,27 @@ proptest! {
             expected_l2.saturating_add(l1_data_fee).saturating_add(operator_fee)
         );
     }
+
+    #[test]
+    fn pbt_pending_fee_rank_heads_find_lowest_effective_price(
+        fees in proptest::collection::vec((any::<u128>(), any::<u128>()), 1..32),
+        base_fee in any::<u64>(),
+    ) {
+        let ranks: Vec<(u64, u64)> = fees
+            .iter()
+            .map(|(max_fee, max_priority)| pending_fee_ranks(*max_fee, *max_priority))
+            .collect();
+        let effective = |(cap, tip): (u64, u64)| {
+            effective_gas_price(u128::from(cap), u128::from(tip), base_fee).unwrap_or(0)
+        };
+        let expected = ranks.iter().map(|ranks| effective(*ranks)).min();
+        let cap_head = ranks.iter().min_by_key(|ranks| ranks.0).copied();
+        let tip_head = ranks.iter().min_by_key(|ranks| ranks.1).copied();
+        let from_heads = cap_head
+            .into_iter()
+            .chain(tip_head)
+            .map(effective)
+            .min();
+        prop_assert_eq!(from_heads, expected);
+    }
 }
@@ -3,15 +3,17 @@
 use proptest::prelude::*;
 use verified_core::kasane_precompiles::{
     compact_icp_query_input_safe_raw, compact_native_withdraw_input_safe_raw,
-    compact_principal_slot_safe_raw, compact_unwrap_input_safe_raw,
+    compact_nft_unwrap_input_safe_raw, compact_principal_slot_safe_raw,
+    compact_unwrap_input_safe_raw, compact_versioned_input_len_safe_raw,
     icp_query_execution_gate_safe_raw, icp_query_gas_observation_safe_raw,
     icp_query_update_kind_rejected_raw, precompile_extra_gas_policy_safe_raw,
     precompile_log_shape_safe_raw, wrap_precompile_gas_observation_safe_raw,
-    COMPACT_FORMAT_VERSION, COMPACT_NATIVE_WITHDRAW_INPUT_LEN, COMPACT_UNWRAP_INPUT_LEN,
-    ICP_PRECOMPILE_KIND_UPDATE, ICP_QUERY_BASE_GAS, ICP_QUERY_INPUT_BYTE_GAS, ICP_QUERY_KIND_QUERY,
-    ICP_QUERY_PRECOMPILE_ADDRESS_CODE, ICP_QUERY_REPLY_BYTE_GAS,
-    MAX_ICP_QUERY_COMBINED_LEN_WITH_EXACT_GAS, MAX_PRINCIPAL_LEN, MAX_QUERY_METHOD_LEN,
-    NATIVE_WITHDRAW_PRECOMPILE_ADDRESS_CODE, UNWRAP_BURN_GAS_SURCHARGE,
+    COMPACT_FORMAT_VERSION, COMPACT_NATIVE_WITHDRAW_INPUT_LEN, COMPACT_NFT_UNWRAP_FORMAT_VERSION,
+    COMPACT_NFT_UNWRAP_INPUT_LEN, COMPACT_SUBACCOUNT_FORMAT_VERSION, COMPACT_SUBACCOUNT_LEN,
+    COMPACT_UNWRAP_INPUT_LEN, ICP_PRECOMPILE_KIND_UPDATE, ICP_QUERY_BASE_GAS,
+    ICP_QUERY_INPUT_BYTE_GAS, ICP_QUERY_KIND_QUERY, ICP_QUERY_PRECOMPILE_ADDRESS_CODE,
+    ICP_QUERY_REPLY_BYTE_GAS, MAX_ICP_QUERY_COMBINED_LEN_WITH_EXACT_GAS, MAX_PRINCIPAL_LEN,
+    MAX_QUERY_METHOD_LEN, NATIVE_WITHDRAW_PRECOMPILE_ADDRESS_CODE, UNWRAP_BURN_GAS_SURCHARGE,
     WRAP_PRECOMPILE_ADDRESS_CODE,
 };
 
@@ -19,6 +21,12 @@ fn expected_principal(len: u64, slot_present: u64, padding_zero: u64) -> bool {
     (1..=MAX_PRINCIPAL_LEN).contains(&len) && slot_present == 1 && padding_zero == 1
 }
 
+fn expected_versioned_len(input_len: u64, version: u64, v1_len: u64) -> bool {
+    (input_len == v1_len && version == COMPACT_FORMAT_VERSION)
+        || (input_len == v1_len + COMPACT_SUBACCOUNT_LEN
+            && version == COMPACT_SUBACCOUNT_FORMAT_VERSION)
+}
+
 fn expected_ratio_extra(elapsed_instruction: u64, numerator: u64, denominator: u64) -> u64 {
     if elapsed_instruction == 0 || numerator == 0 {
         return 0;
@@ -30,6 +38,40 @@ fn expected_ratio_extra(elapsed_instruction: u64, numerator: u64, denominator: u
     rounded.min(u128::from(u64::MAX)) as u64
 }
 
+#[test]
+fn compact_subaccount_version_extends_v1_length_only() {
+    for v1_len in [COMPACT_UNWRAP_INPUT_LEN, COMPACT_NATIVE_WITHDRAW_INPUT_LEN] {
+        assert!(compact_versioned_input_len_safe_raw(
+            v1_len,
+            COMPACT_FORMAT_VERSION,
+            v1_len
+        ));
+        assert!(compact_versioned_input_len_safe_raw(
+            v1_len + COMPACT_SUBACCOUNT_LEN,
+            COMPACT_SUBACCOUNT_FORMAT_VERSION,
+            v1_len
+        ));
+        assert!(!compact_versioned_input_len_safe_raw(
+            v1_len + COMPACT_SUBACCOUNT_LEN,
+            COMPACT_FORMAT_VERSION,
+            v1_len
+        ));
+        assert!(!compact_versioned_input_len_safe_raw(
+            v1_len,
+            COMPACT_SUBACCOUNT_FORMAT_VERSION,
+            v1_len
+        ));
+    }
+    assert!(compact_native_withdraw_input_safe_raw(
+        COMPACT_NATIVE_WITHDRAW_INPUT_LEN + COMPACT_SUBACCOUNT_LEN,
+        COMPACT_SUBACCOUNT_FORMAT_VERSION,
+        10,
+        1,
+        1,
+        0,
+    ));
+}
+
 proptest! {
     #[test]
     fn pbt_compact_principal_and_unwrap_input_require_fixed_shape(
@@ -59,8 +101,7 @@ proptest! {
                 recipient_slot_present,
                 recipient_padding_zero,
             ),
-            input_len == COMPACT_UNWRAP_INPUT_LEN
-                && version == COMPACT_FORMAT_VERSION
+            expected_versioned_len(input_len, version, COMPACT_UNWRAP_INPUT_LEN)
                 && expected_principal(asset_len, asset_slot_present, asset_padding_zero)
                 && amount_present == 1
                 && expected_principal(
@@ -71,6 +112,54 @@ proptest! {
         );
     }
 
+    #[test]
+    fn pbt_nft_unwrap_input_requires_v3_fixed_shape(
+        input_len in 90u64..160,
+        version in 0u64..5,
+        collection_len in 0u64..40,
+        collection_slot_present in 0u64..3,
+        collection_padding_zero in 0u64..3,
+        token_id_present in 0u64..3,
+        recipient_len in 0u64..40,
+        recipient_slot_present in 0u64..3,
+        recipient_padding_zero in 0u64..3,
+    ) {
+        prop_assert_eq!(
+            compact_nft_unwrap_input_safe_raw(
+                input_len,
+                version,
+                collection_len,
+                collection_slot_present,
+                collection_padding_zero,
+                token_id_present,
+                recipient_len,
+                recipient_slot_present,
+                recipient_padding_zero,
+            ),
+            input_len == COMPACT_NFT_UNWRAP_INPUT_LEN
+                && version == COMPACT_NFT_UNWRAP_FORMAT_VERSION
+                && expected_principal(collection_len, collection_slot_present, collection_padding_zero)
+                && token_id_present == 1
+                && expected_principal(
+                    recipient_len,
+                    recipient_slot_present,
+                    recipient_padding_zero,
+                )
+        );
+        // 同じ長さでも fungible 側の版では NFT として解釈しない。
+        prop_assert!(!compact_unwrap_input_safe_raw(
+            COMPACT_NFT_UNWRAP_INPUT_LEN,
+            COMPACT_NFT_UNWRAP_FORMAT_VERSION,
+            1,
+            1,
+            1,
+            1,
+            1,
+            1,
+            1,
+        ));
+    }
+
     #[test]
     fn pbt_native_withdraw_input_rejects_anonymous_principal(
         input_len in 0u64..80,
@@ -89,8 +178,7 @@ proptest! {
                 recipient_padding_zero,
                 recipient_is_anonymous,
             ),
-            input_len == COMPACT_NATIVE_WITHDRAW_INPUT_LEN
-                && version == COMPACT_FORMAT_VERSION
+            expected_versioned_len(input_len, version, COMPACT_NATIVE_WITHDRAW_INPUT_LEN)
                 && expected_principal(
                     recipient_len,
                     recipient_slot_present,
@@ -0,0 +1,38 @@
+//! どこで: ready bucket PBT / 何を: bucket順序とeffective gas priceの一致 / なぜ: 遅延再分類の取りこぼしを乱択で検出するため
+
+use proptest::prelude::*;
+use verified_core::fee::effective_gas_price;
+use verified_core::ready_bucket::{boundaries, boundary_crossed, classify};
+
+proptest! {
+    #[test]
+    fn pbt_bucket_effective_matches_fee_model(
+        max_fee in any::<u128>(),
+        max_priority in any::<u128>(),
+        base_fee in any::<u64>(),
+    ) {
+        let bucket = classify(max_fee, max_priority, base_fee);
+        prop_assert_eq!(
+            bucket.effective_gas_price(base_fee),
+            effective_gas_price(max_fee, max_priority, base_fee).unwrap_or(0)
+        );
+    }
+
+    #[test]
+    fn pbt_bucket_change_implies_boundary_crossed(
+        max_fee in 0u128..(1u128 << 70),
+        max_priority in 0u128..(1u128 << 66),
+        old_base_fee in any::<u64>(),
+        new_base_fee in any::<u64>(),
+    ) {
+        let before = classify(max_fee, max_priority, old_base_fee);
+        let after = classify(max_fee, max_priority, new_base_fee);
+        if before != after {
+            let crossed = boundaries(max_fee, max_priority)
+                .iter()
+                .flatten()
+                .any(|boundary| boundary_crossed(*boundary, old_base_fee, new_base_fee));
+            prop_assert!(crossed);
+        }
+    }
+}
@@ -2,9 +2,11 @@
 
 use proptest::prelude::*;
 use verified_core::unwrap_dispatch::{
-    unwrap_dispatch_terminal_raw, unwrap_dispatch_transition_safe_raw,
-    unwrap_retry_transition_safe_raw, unwrap_upgrade_recovery_safe_raw, UNWRAP_STATUS_DISPATCHED,
-    UNWRAP_STATUS_DISPATCHING, UNWRAP_STATUS_DISPATCH_FAILED, UNWRAP_STATUS_QUEUED,
+    ledger_block_predates_transfer_raw, unwrap_batch_admit_raw, unwrap_dispatch_terminal_raw,
+    unwrap_dispatch_transition_safe_raw, unwrap_reconcile_transition_safe_raw,
+    unwrap_retry_transition_safe_raw, unwrap_uncertain_retry_safe_raw,
+    unwrap_upgrade_recovery_safe_raw, UNWRAP_STATUS_DISPATCHED, UNWRAP_STATUS_DISPATCHING,
+    UNWRAP_STATUS_DISPATCH_FAILED, UNWRAP_STATUS_DISPATCH_UNCERTAIN, UNWRAP_STATUS_QUEUED,
 };
 
 fn expected_dispatch_transition(
@@ -25,11 +27,13 @@ fn expected_dispatch_transition(
             && error_present == 0
             && queue_inserted == 0)
         || (previous_status == UNWRAP_STATUS_DISPATCHING
-            && next_status == UNWRAP_STATUS_DISPATCH_FAILED
+            && (next_status == UNWRAP_STATUS_DISPATCH_FAILED
+                || next_status == UNWRAP_STATUS_DISPATCH_UNCERTAIN)
             && ledger_tx_id_present == 0
             && error_present == 1
             && queue_inserted == 0)
-        || (previous_status == UNWRAP_STATUS_DISPATCH_FAILED
+        || ((previous_status == UNWRAP_STATUS_DISPATCH_FAILED
+            || previous_status == UNWRAP_STATUS_DISPATCH_UNCERTAIN)
             && next_status == UNWRAP_STATUS_QUEUED
             && ledger_tx_id_present == 0
             && error_present == 0
@@ -54,7 +58,8 @@ fn expected_upgrade_recovery(
             && queue_inserted == 1
             && timestamp_updated == 1)
         || ((previous_status == UNWRAP_STATUS_DISPATCHED
-            || previous_status == UNWRAP_STATUS_DISPATCH_FAILED)
+            || previous_status == UNWRAP_STATUS_DISPATCH_FAILED
+            || previous_status == UNWRAP_STATUS_DISPATCH_UNCERTAIN)
             && next_status == previous_status
             && queue_inserted == 0
             && timestamp_updated == 0)
@@ -74,7 +79,8 @@ proptest! {
         );
         prop_assert_eq!(
             unwrap_retry_transition_safe_raw(status, next_status, queue_inserted, error_cleared),
-            status == UNWRAP_STATUS_DISPATCH_FAILED
+            (status == UNWRAP_STATUS_DISPATCH_FAILED
+                || status == UNWRAP_STATUS_DISPATCH_UNCERTAIN)
                 && next_status == UNWRAP_STATUS_QUEUED
                 && queue_inserted == 1
                 && error_cleared == 1
@@ -132,4 +138,77 @@ proptest! {
             )
         );
     }
+
+    #[test]
+    fn pbt_unwrap_reconcile_settles_only_uncertain_requests(
+        previous_status in 0u64..6,
+        next_status in 0u64..6,
+        ledger_tx_id_present in 0u64..3,
+        error_present in 0u64..3,
+        created_at_cleared in 0u64..3,
+    ) {
+        let found = next_status == UNWRAP_STATUS_DISPATCHED
+            && ledger_tx_id_present == 1
+            && error_present == 0
+            && created_at_cleared == 0;
+        let absent = next_status == UNWRAP_STATUS_DISPATCH_FAILED
+            && ledger_tx_id_present == 0
+            && error_present == 1
+            && created_at_cleared == 1;
+        prop_assert_eq!(
+            unwrap_reconcile_transition_safe_raw(
+                previous_status,
+                next_status,
+                ledger_tx_id_present,
+                error_present,
+                created_at_cleared,
+            ),
+            previous_status == UNWRAP_STATUS_DISPATCH_UNCERTAIN && (found || absent)
+        );
+    }
+
+    #[test]
+    fn pbt_uncertain_retry_stays_inside_dedup_window(
+        now in any::<u64>(),
+        created_at in any::<u64>(),
+        window in any::<u64>(),
+    ) {
+        let inside = u128::from(now) < u128::from(created_at) + u128::from(window)
+            || now <= created_at;
+        prop_assert_eq!(
+            unwrap_uncertain_retry_safe_raw(now, created_at, window),
+            created_at != 0 && inside
+        );
+    }
+
+    #[test]
+    fn pbt_ledger_block_predates_transfer_beyond_drift(
+        block_ts in any::<u64>(),
+        created_at in any::<u64>(),
+        drift in any::<u64>(),
+    ) {
+        prop_assert_eq!(
+            ledger_block_predates_transfer_raw(block_ts, created_at, drift),
+            u128::from(block_ts) + u128::from(drift) < u128::from(created_at)
+        );
+    }
+
+    #[test]
+    fn pbt_unwrap_batch_admit_never_exceeds_caps(
+        batch_max in 0u64..20,
+        ledger_cap in 0u64..6,
+        ledgers in proptest::collection::vec(0u8..4, 0..40),
+    ) {
+        let mut batch_len = 0u64;
+        let mut in_flight = [0u64; 4];
+        for ledger in ledgers {
+            let slot = usize::from(ledger);
+            if unwrap_batch_admit_raw(batch_len, batch_max, in_flight[slot], ledger_cap) {
+                batch_len += 1;
+                in_flight[slot] += 1;
+            }
+        }
+        prop_assert!(batch_len <= batch_max);
+        prop_assert!(in_flight.iter().all(|count| *count <= ledger_cap));
+    }
 }
@@ -1,13 +1,15 @@
-//! どこで: wrap request PBT / 何を: idempotency・stage・recover / なぜ: 二重請求と不正復旧仕様の誤りを検出するため
+//! どこで: wrap request PBT / 何を: idempotency・stage・recover・期限切れ返金 / なぜ: 二重請求と不正復旧仕様の誤りを検出するため
 
 use proptest::prelude::*;
 use verified_core::wrap_request::{
-    wrap_idempotent_response_safe_raw, wrap_pending_reservation_safe_raw, wrap_recover_allowed_raw,
-    wrap_stage_transition_safe_raw, WRAP_REQUEST_STATUS_FAILED, WRAP_RESERVE_IDEMPOTENCY_MISMATCH,
-    WRAP_RESERVE_IN_PROGRESS, WRAP_RESERVE_OK, WRAP_STAGE_FAILED, WRAP_STAGE_FEE_COLLECTED,
-    WRAP_STAGE_FEE_PENDING, WRAP_STAGE_MINT_SUBMITTED, WRAP_STAGE_MINT_SUBMITTING,
-    WRAP_STAGE_PULLED, WRAP_STAGE_PULL_PENDING, WRAP_STAGE_REFUNDED, WRAP_STAGE_REFUNDING,
-    WRAP_STAGE_SUCCEEDED,
+    wrap_expiry_refund_allowed_raw, wrap_expiry_refund_amount_raw,
+    wrap_idempotent_response_safe_raw, wrap_mint_cannot_land_raw,
+    wrap_pending_reservation_safe_raw, wrap_recover_allowed_raw, wrap_stage_transition_safe_raw,
+    WRAP_REQUEST_STATUS_FAILED, WRAP_REQUEST_STATUS_QUEUED, WRAP_REQUEST_STATUS_RUNNING,
+    WRAP_RESERVE_IDEMPOTENCY_MISMATCH, WRAP_RESERVE_IN_PROGRESS, WRAP_RESERVE_OK,
+    WRAP_STAGE_FAILED, WRAP_STAGE_FEE_COLLECTED, WRAP_STAGE_FEE_PENDING, WRAP_STAGE_MINT_SUBMITTED,
+    WRAP_STAGE_MINT_SUBMITTING, WRAP_STAGE_PULLED, WRAP_STAGE_PULL_PENDING, WRAP_STAGE_REFUNDED,
+    WRAP_STAGE_REFUNDING, WRAP_STAGE_SUCCEEDED,
 };
 
 fn expected_reservation(
@@ -90,7 +92,7 @@ fn expected_stage(previous_stage: u64, next_stage: u64, recovering: u64) -> bool
             matches!(
                 next_stage,
                 WRAP_STAGE_PULLED | WRAP_STAGE_MINT_SUBMITTING | WRAP_STAGE_FAILED
-            )
+            ) || (recovering == 1 && next_stage == WRAP_STAGE_REFUNDING)
         }
         WRAP_STAGE_MINT_SUBMITTING => matches!(
             next_stage,
@@ -99,10 +101,12 @@ fn expected_stage(previous_stage: u64, next_stage: u64, recovering: u64) -> bool
                 | WRAP_STAGE_SUCCEEDED
                 | WRAP_STAGE_FAILED
         ),
-        WRAP_STAGE_MINT_SUBMITTED => matches!(
-            next_stage,
-            WRAP_STAGE_MINT_SUBMITTED | WRAP_STAGE_SUCCEEDED | WRAP_STAGE_FAILED
-        ),
+        WRAP_STAGE_MINT_SUBMITTED => {
+            matches!(
+                next_stage,
+                WRAP_STAGE_MINT_SUBMITTED | WRAP_STAGE_SUCCEEDED | WRAP_STAGE_FAILED
+            ) || (recovering == 1 && next_stage == WRAP_STAGE_REFUNDING)
+        }
         WRAP_STAGE_FAILED => {
             next_stage == WRAP_STAGE_FAILED
                 || (recovering == 1 && next_stage == WRAP_STAGE_REFUNDING)
@@ -116,6 +120,18 @@ fn expected_stage(previous_stage: u64, next_stage: u64, recovering: u64) -> bool
     }
 }
 
+#[test]
+fn wrap_expiry_refund_keeps_fees_in_custody() {
+    assert_eq!(wrap_expiry_refund_amount_raw(0, 1_000, 30, 10), Some(990));
+    assert_eq!(wrap_expiry_refund_amount_raw(1, 1_000, 30, 10), Some(960));
+    assert_eq!(wrap_expiry_refund_amount_raw(1, 40, 30, 10), None);
+    assert_eq!(wrap_expiry_refund_amount_raw(0, 10, 30, 10), None);
+    assert_eq!(
+        wrap_expiry_refund_amount_raw(1, u128::MAX, u128::MAX, 1),
+        None
+    );
+}
+
 #[test]
 fn wrap_stage_transition_rejects_terminal_and_skip_edges() {
     for (from, to, recovering) in [
@@ -124,6 +140,8 @@ fn wrap_stage_transition_rejects_terminal_and_skip_edges() {
         (WRAP_STAGE_SUCCEEDED, WRAP_STAGE_MINT_SUBMITTING, 0),
         (WRAP_STAGE_REFUNDED, WRAP_STAGE_FEE_COLLECTED, 0),
         (WRAP_STAGE_FAILED, WRAP_STAGE_REFUNDING, 0),
+        (WRAP_STAGE_PULLED, WRAP_STAGE_REFUNDING, 0),
+        (WRAP_STAGE_MINT_SUBMITTING, WRAP_STAGE_REFUNDING, 1),
     ] {
         assert!(!wrap_stage_transition_safe_raw(from, to, recovering));
     }
@@ -137,6 +155,8 @@ fn wrap_stage_transition_accepts_observed_edges() {
         (WRAP_STAGE_PULLED, WRAP_STAGE_MINT_SUBMITTING, 0),
         (WRAP_STAGE_MINT_SUBMITTED, WRAP_STAGE_SUCCEEDED, 0),
         (WRAP_STAGE_FAILED, WRAP_STAGE_REFUNDING, 1),
+        (WRAP_STAGE_PULLED, WRAP_STAGE_REFUNDING, 1),
+        (WRAP_STAGE_MINT_SUBMITTED, WRAP_STAGE_REFUNDING, 1),
         (WRAP_STAGE_REFUNDING, WRAP_STAGE_REFUNDED, 0),
     ] {
         assert!(wrap_stage_transition_safe_raw(from, to, recovering));
@@ -245,4 +265,65 @@ proptest! {
         );
     }
 
+    #[test]
+    fn pbt_wrap_expiry_refund_only_when_mint_cannot_land(
+        now in 0u64..6,
+        deadline_at in 0u64..6,
+        status in 0u64..5,
+        stage in 0u64..12,
+        mint_failed_recoverable in 0u64..2,
+        mint_tx_id_present in 0u64..2,
+        mint_submit_started in 0u64..2,
+        mint_tx_dropped in 0u64..2,
+        pull_ledger_tx_id_present in 0u64..2,
+        withdraw_in_progress in 0u64..2,
+        withdrawn in 0u64..2,
+    ) {
+        let cannot_land = wrap_mint_cannot_land_raw(
+            status,
+            stage,
+            mint_failed_recoverable,
+            mint_tx_id_present,
+            mint_submit_started,
+            mint_tx_dropped,
+        );
+        // 送信済みで drop も失敗も確定していない mint は、後から着地し得るので返金しない。
+        if mint_tx_id_present == 1 && mint_tx_dropped == 0 && mint_failed_recoverable == 0 {
+            prop_assert!(!cannot_land);
+        }
+        if status == WRAP_REQUEST_STATUS_QUEUED {
+            prop_assert_eq!(
+                cannot_land,
+                stage == WRAP_STAGE_PULLED && mint_tx_id_present == 0 && mint_submit_started == 0
+            );
+        }
+        if status == WRAP_REQUEST_STATUS_RUNNING {
+            prop_assert_eq!(
+                cannot_land,
+                stage == WRAP_STAGE_MINT_SUBMITTED && mint_tx_id_present == 1 && mint_tx_dropped == 1
+            );
+        }
+        let allowed = wrap_expiry_refund_allowed_raw(
+            now,
+            deadline_at,
+            1,
+            pull_ledger_tx_id_present,
+            withdraw_in_progress,
+            withdrawn,
+            0,
+            u64::from(cannot_land),
+        );
+        prop_assert_eq!(
+            allowed,
+            deadline_at != 0
+                && now >= deadline_at
+                && pull_ledger_tx_id_present == 1
+                && withdraw_in_progress == 0
+                && withdrawn == 0
+                && cannot_land
+        );
+        if allowed {
+            prop_assert!(wrap_stage_transition_safe_raw(stage, WRAP_STAGE_REFUNDING, 1));
+        }
+    }
 }
@@ -9,11 +9,13 @@ SCRIPT_DIR="$(cd "$(dirname "${BASH_SOURCE[0]}")" && pwd)"
 REPO_ROOT="$(cd "${SCRIPT_DIR}/.." && pwd)"
 DEFAULT_DID="${REPO_ROOT}/crates/ic-evm-gateway/evm_canister.did"
 ADMIN_DID="${REPO_ROOT}/crates/ic-evm-gateway/evm_canister_precompile_profile_admin.did"
+ARCHIVE_DID="${REPO_ROOT}/crates/ic-evm-archive/archive_canister.did"
 
 check_did_sync() {
   local label="$1"
   local expected_did="$2"
   local features="$3"
+  local package="${4:-ic-evm-gateway}"
   local generated_did
   local normalized_expected
   local normalized_generated
@@ -22,7 +24,7 @@ check_did_sync() {
   normalized_expected="$(mktemp -t evm_canister.expected.XXXXXX.did)"
   normalized_generated="$(mktemp -t evm_canister.generated.normalized.XXXXXX.did)"
 
-  cargo run -q -p ic-evm-gateway --features "${features}" --bin export_did > "${generated_did}"
+  cargo run -q -p "${package}" --features "${features}" --bin export_did > "${generated_did}"
 
   grep -Ev '^[[:space:]]*//' "${expected_did}" > "${normalized_expected}"
   grep -Ev '^[[:space:]]*//' "${generated_did}" > "${normalized_generated}"
@@ -39,3 +41,4 @@ check_did_sync() {
 
 check_did_sync "default" "${DEFAULT_DID}" "did-gen"
 check_did_sync "precompile-profile-admin" "${ADMIN_DID}" "did-gen precompile-profile-admin"
+check_did_sync "archive" "${ARCHIVE_DID}" "did-gen" "ic-evm-archive"
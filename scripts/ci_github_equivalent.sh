@@ -126,9 +126,12 @@ find vendor/ark-relations -type f -print0 | sort -z | xargs -0 sha256sum > "${sn
 (cd tools/wrapper-vite/contracts && forge build)
 
 cargo test -p verified-core --locked --lib --tests
-cargo test -p evm-db -p ic-evm-core -p ic-evm-gateway --locked --lib --tests
+cargo test -p evm-db -p ic-evm-core -p ic-evm-gateway -p ic-evm-archive --locked --lib --tests
 cargo test --manifest-path crates/evm-rpc-e2e/Cargo.toml --no-run --locked
 cargo build --release --target wasm32-unknown-unknown -p ic-evm-gateway --locked
+cargo build --release --target wasm32-unknown-unknown -p ic-evm-archive --locked
+# wrap_unwrap_flow_e2e の照合テストは結果の分からない送金を偽 ledger で再現する。
+cargo build --release --target wasm32-unknown-unknown --manifest-path crates/evm-rpc-e2e/fake-ledger/Cargo.toml
 
 . scripts/prepare_ci_icrc1_ledger_wasm.sh
 if [[ -n "${POCKET_IC_BIN:-}" ]] && ! "${POCKET_IC_BIN}" --version 2>/dev/null | grep -Eq '^pocket-ic-server 12\.'; then
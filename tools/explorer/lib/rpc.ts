@@ -2,10 +2,16 @@
 
 import { Actor, HttpAgent } from "@icp-sdk/core/agent";
 import type { IDL } from "@icp-sdk/core/candid";
+import type { Principal } from "@dfinity/principal";
 import { loadConfig } from "./config";
 import { bytesToBigInt, normalizeHex, parseHex } from "./hex";
 
-export type LookupError = { NotFound: null } | { Pending: null } | { Pruned: { pruned_before_block: bigint } };
+export type ArchivedRangeView = { canister_id: Principal; start_block: bigint; end_block: bigint };
+export type LookupError =
+  | { NotFound: null }
+  | { Pending: null }
+  | { Pruned: { pruned_before_block: bigint } }
+  | { Archived: { ranges: ArchivedRangeView[] } };
 
 type Result<T, E> = { Ok: T } | { Err: E };
 type ResultBytes = Result<Uint8Array, string>;
@@ -138,9 +144,25 @@ export type GetLogsErrorView =
 export type RpcReceiptLookupView =
   | { NotFound: null }
   | { Found: EthReceiptView }
+  | { Dropped: DroppedTxView }
   | { PossiblyPruned: { pruned_before_block: bigint } }
   | { Pruned: { pruned_before_block: bigint } };
 
+export type DroppedTxView = {
+  tx_hash: Uint8Array;
+  eth_tx_hash: [] | [Uint8Array];
+  tx_type: number;
+  from: [] | [Uint8Array];
+  nonce: [] | [bigint];
+  drop_code: number;
+  reason: string;
+  block_attempted: [] | [bigint];
+  dropped_at: bigint;
+  max_fee_per_gas: bigint;
+  max_priority_fee_per_gas: bigint;
+  base_fee_per_gas: bigint;
+};
+
 export type EthReceiptView = {
   tx_hash: Uint8Array;
   eth_tx_hash: [] | [Uint8Array];
@@ -437,7 +459,17 @@ const idlFactory: IDL.InterfaceFactory = ({ IDL }) => {
     gas_used: IDL.Nat64,
     contract_address: IDL.Opt(IDL.Vec(IDL.Nat8)),
   });
-  const lookupError = IDL.Variant({ NotFound: IDL.Null, Pruned: IDL.Record({ pruned_before_block: IDL.Nat64 }), Pending: IDL.Null });
+  const archivedRangeView = IDL.Record({
+    canister_id: IDL.Principal,
+    start_block: IDL.Nat64,
+    end_block: IDL.Nat64,
+  });
+  const lookupError = IDL.Variant({
+    NotFound: IDL.Null,
+    Pruned: IDL.Record({ pruned_before_block: IDL.Nat64 }),
+    Pending: IDL.Null,
+    Archived: IDL.Record({ ranges: IDL.Vec(archivedRangeView) }),
+  });
   const txKindView = IDL.Variant({ EthSigned: IDL.Null, IcSynthetic: IDL.Null });
   const rpcTxView = IDL.Record({
     kind: txKindView,
@@ -535,6 +567,20 @@ const idlFactory: IDL.InterfaceFactory = ({ IDL }) => {
   const rpcReceiptLookupView = IDL.Variant({
     NotFound: IDL.Null,
     Found: ethReceiptView,
+    Dropped: IDL.Record({
+      tx_hash: IDL.Vec(IDL.Nat8),
+      eth_tx_hash: IDL.Opt(IDL.Vec(IDL.Nat8)),
+      tx_type: IDL.Nat8,
+      from: IDL.Opt(IDL.Vec(IDL.Nat8)),
+      nonce: IDL.Opt(IDL.Nat64),
+      drop_code: IDL.Nat16,
+      reason: IDL.Text,
+      block_attempted: IDL.Opt(IDL.Nat64),
+      dropped_at: IDL.Nat64,
+      max_fee_per_gas: IDL.Nat,
+      max_priority_fee_per_gas: IDL.Nat,
+      base_fee_per_gas: IDL.Nat64,
+    }),
     PossiblyPruned: IDL.Record({ pruned_before_block: IDL.Nat64 }),
     Pruned: IDL.Record({ pruned_before_block: IDL.Nat64 }),
   });
@@ -79,6 +79,18 @@ export async function getTxMonitorView(inputHashHex: string): Promise<TxMonitorV
     };
   }
 
+  if ("Dropped" in receipt) {
+    return {
+      inputHashHex: normalized,
+      resolvedEthTxHashHex: firstEthHashHex(tx),
+      txIdHex: toHexLower(receipt.Dropped.tx_hash),
+      state: "dropped",
+      summary: `dropped (code=${receipt.Dropped.drop_code}): ${receipt.Dropped.reason}`,
+      tx,
+      receipt,
+      pending,
+    };
+  }
   if ("Pruned" in receipt) {
     return {
       inputHashHex: normalized,
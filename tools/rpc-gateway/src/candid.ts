@@ -21,11 +21,18 @@ export const idlFactory: IDL.InterfaceFactory = ({ IDL }) => {
     value: IDL.Opt(IDL.Vec(IDL.Nat8)),
     data: IDL.Opt(IDL.Vec(IDL.Nat8)),
   });
+  const RevertReasonView = IDL.Record({
+    message: IDL.Text,
+    reason: IDL.Opt(IDL.Text),
+    panic_code: IDL.Opt(IDL.Nat64),
+    custom_error_selector: IDL.Opt(IDL.Vec(IDL.Nat8)),
+  });
   const RpcCallResultView = IDL.Record({
     status: IDL.Nat8,
     gas_used: IDL.Nat64,
     return_data: IDL.Vec(IDL.Nat8),
     revert_data: IDL.Opt(IDL.Vec(IDL.Nat8)),
+    revert_reason: IDL.Opt(RevertReasonView),
   });
   const RpcBlockTagView = IDL.Variant({
     Latest: IDL.Null,
@@ -102,6 +109,7 @@ export const idlFactory: IDL.InterfaceFactory = ({ IDL }) => {
     contract_address: IDL.Opt(IDL.Vec(IDL.Nat8)),
     tx_type: IDL.Opt(IDL.Nat8),
     tx_hash: IDL.Vec(IDL.Nat8),
+    revert_reason: IDL.Opt(RevertReasonView),
   });
   const EthBlockView = IDL.Record({
     txs: IDL.Variant({ Full: IDL.Vec(EthTxView), Hashes: IDL.Vec(IDL.Vec(IDL.Nat8)) }),
@@ -154,9 +162,24 @@ export const idlFactory: IDL.InterfaceFactory = ({ IDL }) => {
     InvalidArgument: IDL.Text,
     UnsupportedFilter: IDL.Text,
   });
+  const DroppedTxView = IDL.Record({
+    tx_hash: IDL.Vec(IDL.Nat8),
+    eth_tx_hash: IDL.Opt(IDL.Vec(IDL.Nat8)),
+    tx_type: IDL.Nat8,
+    from: IDL.Opt(IDL.Vec(IDL.Nat8)),
+    nonce: IDL.Opt(IDL.Nat64),
+    drop_code: IDL.Nat16,
+    reason: IDL.Text,
+    block_attempted: IDL.Opt(IDL.Nat64),
+    dropped_at: IDL.Nat64,
+    max_fee_per_gas: IDL.Nat,
+    max_priority_fee_per_gas: IDL.Nat,
+    base_fee_per_gas: IDL.Nat64,
+  });
   const RpcReceiptLookupView = IDL.Variant({
     NotFound: IDL.Null,
     Found: EthReceiptView,
+    Dropped: DroppedTxView,
     PossiblyPruned: IDL.Record({ pruned_before_block: IDL.Nat64 }),
     Pruned: IDL.Record({ pruned_before_block: IDL.Nat64 }),
   });
@@ -34,6 +34,13 @@ export type EthTxView = {
   decoded: [] | [DecodedTxView];
 };
 
+export type RevertReasonView = {
+  message: string;
+  reason: [] | [string];
+  panic_code: [] | [bigint];
+  custom_error_selector: [] | [Uint8Array];
+};
+
 export type EthReceiptView = {
   effective_gas_price: bigint;
   status: number;
@@ -52,6 +59,7 @@ export type EthReceiptView = {
   contract_address: [] | [Uint8Array];
   tx_type: [] | [number];
   tx_hash: Uint8Array;
+  revert_reason?: [] | [RevertReasonView];
 };
 
 export type EthBlockView = {
@@ -99,9 +107,25 @@ type GetLogsErrorView =
   | { RangeTooLarge: null }
   | { InvalidArgument: string }
   | { UnsupportedFilter: string };
+export type DroppedTxView = {
+  tx_hash: Uint8Array;
+  eth_tx_hash: [] | [Uint8Array];
+  tx_type: number;
+  from: [] | [Uint8Array];
+  nonce: [] | [bigint];
+  drop_code: number;
+  reason: string;
+  block_attempted: [] | [bigint];
+  dropped_at: bigint;
+  max_fee_per_gas: bigint;
+  max_priority_fee_per_gas: bigint;
+  base_fee_per_gas: bigint;
+};
+
 export type RpcReceiptLookupView =
   | { NotFound: null }
   | { Found: EthReceiptView }
+  | { Dropped: DroppedTxView }
   | { PossiblyPruned: { pruned_before_block: bigint } }
   | { Pruned: { pruned_before_block: bigint } };
 
@@ -134,6 +158,7 @@ export type CallObjectResult = {
   gas_used: bigint;
   return_data: Uint8Array;
   revert_data: [] | [Uint8Array];
+  revert_reason?: [] | [RevertReasonView];
 };
 export type OpsStatusView = {
   needs_migration: boolean;
@@ -11,6 +11,7 @@ import {
   type EthReceiptView,
   type EthTxView,
   type OpsStatusView,
+  type RevertReasonView,
 } from "./client.js";
 import { bytesToQuantity, ensureLen, parseDataHex, parseQuantityHex, toDataHex, toQuantityHex } from "./hex.js";
 import { ERR_INTERNAL, ERR_INVALID_PARAMS, ERR_METHOD_NOT_FOUND, JsonRpcRequest, JsonRpcResponse, makeError, makeSuccess } from "./jsonrpc.js";
@@ -272,6 +273,20 @@ async function onGetTransactionReceipt(id: string | number | null, params: unkno
       pruned_before_block: toQuantityHex(receiptLookup.Pruned.pruned_before_block),
     });
   }
+  if ("Dropped" in receiptLookup) {
+    const dropped = receiptLookup.Dropped;
+    return makeError(id, -32001, "resource not found", {
+      reason: "receipt.dropped",
+      drop_code: dropped.drop_code,
+      drop_reason: dropped.reason,
+      from: dropped.from.length === 0 ? null : toDataHex(dropped.from[0]),
+      nonce: dropped.nonce.length === 0 ? null : toQuantityHex(dropped.nonce[0]),
+      block_attempted: dropped.block_attempted.length === 0 ? null : toQuantityHex(dropped.block_attempted[0]),
+      max_fee_per_gas: toQuantityHex(dropped.max_fee_per_gas),
+      max_priority_fee_per_gas: toQuantityHex(dropped.max_priority_fee_per_gas),
+      base_fee_per_gas: toQuantityHex(dropped.base_fee_per_gas),
+    });
+  }
   const mapped = mapReceipt(receiptLookup.Found, txHash);
   if (!receiptMatchesRequestedHash(mapped, txHash)) {
     return makeSuccess(id, null);
@@ -453,7 +468,7 @@ async function onEthCall(id: string | number | null, params: unknown): Promise<J
     return mapRpcError(id, out.Err, "execution failed");
   }
   if (out.Ok.status === 0) {
-    return makeError(id, -32000, "execution reverted", revertDataToHex(out.Ok.revert_data));
+    return makeError(id, -32000, revertMessage(out.Ok.revert_reason), revertDataToHex(out.Ok.revert_data));
   }
   return makeSuccess(id, toDataHex(out.Ok.return_data));
 }
@@ -1483,6 +1498,12 @@ function normalizeStorageSlot32(slot: string): Uint8Array {
   return Uint8Array.from(Buffer.from(hex.padStart(64, "0"), "hex"));
 }
 
+function revertMessage(revertReason: [] | [RevertReasonView] | undefined): string {
+  const decoded = revertReason ?? [];
+  // The canister only attaches a reason to reverts; a failure without one is a halt (out of gas etc.).
+  return decoded.length === 0 ? "execution failed" : decoded[0].message;
+}
+
 function revertDataToHex(revertData: [] | [Uint8Array]): string {
   if (revertData.length === 0) {
     return "0x";
@@ -1682,6 +1703,18 @@ function mapReceipt(receipt: EthReceiptView, fallbackTxHash: Uint8Array): Record
     status: toQuantityHex(BigInt(receipt.status)),
     type: receipt.tx_type.length === 0 ? "0x0" : toQuantityHex(BigInt(receipt.tx_type[0])),
     effectiveGasPrice: toQuantityHex(receipt.effective_gas_price),
+    ...revertFields(receipt.revert_reason ?? []),
+  };
+}
+
+function revertFields(revertReason: [] | [RevertReasonView]): Record<string, unknown> {
+  if (revertReason.length === 0) {
+    return {};
+  }
+  const decoded = revertReason[0];
+  return {
+    revertReason: decoded.reason.length === 0 ? null : decoded.reason[0],
+    panicCode: decoded.panic_code.length === 0 ? null : toQuantityHex(decoded.panic_code[0]),
   };
 }
 
@@ -2,19 +2,35 @@
 pragma solidity ^0.8.20;
 
 import "./WrappedAssetToken.sol";
+import "./WrappedNftToken.sol";
 
 /// @notice Deploys per-asset wrapped tokens by CREATE2 and mints via a single minter.
 /// salt = keccak256("kasane.wrap.v1", chain_id, icrc2_canister_id_bytes)
+/// ICRC-7 collections use salt = keccak256("kasane.wrap.nft.v1", chain_id, icrc7_canister_id_bytes)
 contract WrapTokenFactory {
     string internal constant DOMAIN = "kasane.wrap.v1";
+    string internal constant NFT_DOMAIN = "kasane.wrap.nft.v1";
     address public immutable minter;
 
     mapping(bytes32 => address) public tokenByAssetKey;
     mapping(bytes32 => uint8) public decimalsByAssetKey;
+    mapping(bytes32 => address) public nftByCollectionKey;
+
+    /// @notice Values read from the source ledger's icrc1_metadata. Empty strings keep the token's current value.
+    struct TokenMetadata {
+        string name;
+        string symbol;
+        string logo;
+        uint256 ledgerFee;
+    }
 
     event TokenDeployed(bytes canisterId, bytes32 indexed assetKey, address indexed token, bytes32 salt);
     event Minted(bytes canisterId, bytes32 indexed assetKey, address indexed token, address to, uint256 amount);
     event Burned(bytes canisterId, bytes32 indexed assetKey, address indexed token, address from, uint256 amount);
+    event MetadataSynced(bytes canisterId, bytes32 indexed assetKey, address indexed token);
+    event NftDeployed(bytes canisterId, bytes32 indexed collectionKey, address indexed token, bytes32 salt);
+    event NftMinted(bytes canisterId, bytes32 indexed collectionKey, address indexed token, address to, uint256 tokenId);
+    event NftBurned(bytes canisterId, bytes32 indexed collectionKey, address indexed token, address from, uint256 tokenId);
 
     modifier onlyMinter() {
         require(msg.sender == minter, "auth.minter_required");
@@ -52,6 +68,39 @@ contract WrapTokenFactory {
         external
         onlyMinter
         returns (address token)
+    {
+        token = _mintForAsset(canisterId, decimals, to, amount);
+    }
+
+    /// @notice Same as mintForAsset, but applies metadata when this call deploys the token.
+    /// An already deployed token ignores it; use setAssetMetadata to update.
+    function mintForAssetWithMetadata(
+        bytes calldata canisterId,
+        uint8 decimals,
+        address to,
+        uint256 amount,
+        TokenMetadata calldata metadata
+    ) external onlyMinter returns (address token) {
+        bool deployed = tokenByAssetKey[computeAssetKey(canisterId)] != address(0);
+        token = _mintForAsset(canisterId, decimals, to, amount);
+        if (!deployed) {
+            _applyMetadata(canisterId, token, metadata);
+        }
+    }
+
+    function setAssetMetadata(bytes calldata canisterId, TokenMetadata calldata metadata)
+        external
+        onlyMinter
+        returns (address token)
+    {
+        token = tokenByAssetKey[computeAssetKey(canisterId)];
+        require(token != address(0), "metadata.token_not_deployed");
+        _applyMetadata(canisterId, token, metadata);
+    }
+
+    function _mintForAsset(bytes calldata canisterId, uint8 decimals, address to, uint256 amount)
+        private
+        returns (address token)
     {
         require(to != address(0), "arg.to_zero");
         bytes32 assetKey = computeAssetKey(canisterId);
@@ -85,6 +134,64 @@ contract WrapTokenFactory {
         emit Burned(canisterId, assetKey, token, from, amount);
     }
 
+    function computeCollectionKey(bytes calldata canisterId) public view returns (bytes32) {
+        _validateCanisterId(canisterId);
+        return keccak256(abi.encodePacked(NFT_DOMAIN, block.chainid, canisterId));
+    }
+
+    function getNftAddress(bytes calldata canisterId) external view returns (address) {
+        return nftByCollectionKey[computeCollectionKey(canisterId)];
+    }
+
+    function predictNftAddress(bytes calldata canisterId) external view returns (address predicted) {
+        bytes32 salt = computeCollectionKey(canisterId);
+        bytes memory initCode = abi.encodePacked(
+            type(WrappedNftToken).creationCode,
+            abi.encode(_nftNameFor(canisterId), _nftSymbolFor(canisterId))
+        );
+        bytes32 hash = keccak256(
+            abi.encodePacked(bytes1(0xff), address(this), salt, keccak256(initCode))
+        );
+        return address(uint160(uint256(hash)));
+    }
+
+    function mintNftForCollection(bytes calldata canisterId, address to, uint256 tokenId)
+        external
+        onlyMinter
+        returns (address token)
+    {
+        require(to != address(0), "arg.to_zero");
+        bytes32 collectionKey = computeCollectionKey(canisterId);
+        token = nftByCollectionKey[collectionKey];
+        if (token == address(0)) {
+            token = address(new WrappedNftToken{salt: collectionKey}(
+                _nftNameFor(canisterId),
+                _nftSymbolFor(canisterId)
+            ));
+            nftByCollectionKey[collectionKey] = token;
+            emit NftDeployed(canisterId, collectionKey, token, collectionKey);
+        }
+        WrappedNftToken(token).mint(to, tokenId);
+        emit NftMinted(canisterId, collectionKey, token, to, tokenId);
+    }
+
+    function burnNftFromCollection(bytes calldata canisterId, address from, uint256 tokenId)
+        external
+        onlyMinter
+        returns (address token)
+    {
+        bytes32 collectionKey = computeCollectionKey(canisterId);
+        token = nftByCollectionKey[collectionKey];
+        require(token != address(0), "unwrap.token_not_deployed");
+        WrappedNftToken(token).burnFromByFactory(from, tokenId);
+        emit NftBurned(canisterId, collectionKey, token, from, tokenId);
+    }
+
+    function _applyMetadata(bytes calldata canisterId, address token, TokenMetadata calldata metadata) private {
+        WrappedAssetToken(token).setMetadata(metadata.name, metadata.symbol, metadata.logo, metadata.ledgerFee);
+        emit MetadataSynced(canisterId, computeAssetKey(canisterId), token);
+    }
+
     function _tokenInitCode(bytes calldata canisterId, uint8 decimals) private view returns (bytes memory) {
         return abi.encodePacked(
             type(WrappedAssetToken).creationCode,
@@ -100,6 +207,14 @@ contract WrapTokenFactory {
         return string(abi.encodePacked("KW", _shortHex(canisterId)));
     }
 
+    function _nftNameFor(bytes calldata canisterId) private pure returns (string memory) {
+        return string(abi.encodePacked("Kasane Wrapped NFT ", _shortHex(canisterId)));
+    }
+
+    function _nftSymbolFor(bytes calldata canisterId) private pure returns (string memory) {
+        return string(abi.encodePacked("KWN", _shortHex(canisterId)));
+    }
+
     function _shortHex(bytes calldata data) private pure returns (string memory) {
         bytes32 h = keccak256(data);
         bytes memory out = new bytes(16);
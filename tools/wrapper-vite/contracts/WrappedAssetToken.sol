@@ -2,6 +2,7 @@
 pragma solidity ^0.8.20;
 
 /// @notice Minimal ERC20 used by WrapTokenFactory. Mint authority is fixed to the factory.
+/// name/symbol/logo/ledgerFee mirror the source ledger's icrc1_metadata when the gateway provides it.
 contract WrappedAssetToken {
     string public name;
     string public symbol;
@@ -11,9 +12,12 @@ contract WrappedAssetToken {
 
     mapping(address => uint256) public balanceOf;
     mapping(address => mapping(address => uint256)) public allowance;
+    string public logo;
+    uint256 public ledgerFee;
 
     event Transfer(address indexed from, address indexed to, uint256 value);
     event Approval(address indexed owner, address indexed spender, uint256 value);
+    event MetadataUpdated(string name, string symbol, uint256 ledgerFee);
 
     modifier onlyFactory() {
         require(msg.sender == factory, "auth.factory_required");
@@ -56,6 +60,24 @@ contract WrappedAssetToken {
         emit Transfer(address(0), to, amount);
     }
 
+    /// @dev Empty strings keep the current value so a ledger without a field does not erase it.
+    function setMetadata(string calldata name_, string calldata symbol_, string calldata logo_, uint256 ledgerFee_)
+        external
+        onlyFactory
+    {
+        if (bytes(name_).length != 0) {
+            name = name_;
+        }
+        if (bytes(symbol_).length != 0) {
+            symbol = symbol_;
+        }
+        if (bytes(logo_).length != 0) {
+            logo = logo_;
+        }
+        ledgerFee = ledgerFee_;
+        emit MetadataUpdated(name, symbol, ledgerFee);
+    }
+
     function burn(uint256) external pure returns (bool) {
         revert("disabled.use_factory");
     }
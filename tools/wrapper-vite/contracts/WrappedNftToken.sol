@@ -0,0 +1,132 @@
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.20;
+
+interface IERC721Receiver {
+    function onERC721Received(address operator, address from, uint256 tokenId, bytes calldata data)
+        external
+        returns (bytes4);
+}
+
+/// @notice Minimal ERC721 used by WrapTokenFactory for ICRC-7 collections. Mint authority is fixed to the factory.
+/// Token ids mirror the ICRC-7 token ids of the source collection.
+contract WrappedNftToken {
+    string public name;
+    string public symbol;
+    address public immutable factory;
+    uint256 public totalSupply;
+
+    mapping(uint256 => address) private _owners;
+    mapping(address => uint256) private _balances;
+    mapping(uint256 => address) public getApproved;
+    mapping(address => mapping(address => bool)) public isApprovedForAll;
+
+    event Transfer(address indexed from, address indexed to, uint256 indexed tokenId);
+    event Approval(address indexed owner, address indexed approved, uint256 indexed tokenId);
+    event ApprovalForAll(address indexed owner, address indexed operator, bool approved);
+
+    modifier onlyFactory() {
+        require(msg.sender == factory, "auth.factory_required");
+        _;
+    }
+
+    constructor(string memory name_, string memory symbol_) {
+        name = name_;
+        symbol = symbol_;
+        factory = msg.sender;
+    }
+
+    function supportsInterface(bytes4 interfaceId) external pure returns (bool) {
+        return interfaceId == 0x01ffc9a7 // ERC165
+            || interfaceId == 0x80ac58cd // ERC721
+            || interfaceId == 0x5b5e139f; // ERC721Metadata
+    }
+
+    function balanceOf(address owner) external view returns (uint256) {
+        require(owner != address(0), "arg.owner_zero");
+        return _balances[owner];
+    }
+
+    function ownerOf(uint256 tokenId) public view returns (address owner) {
+        owner = _owners[tokenId];
+        require(owner != address(0), "erc721.nonexistent_token");
+    }
+
+    function tokenURI(uint256 tokenId) external view returns (string memory) {
+        ownerOf(tokenId);
+        return "";
+    }
+
+    function approve(address to, uint256 tokenId) external {
+        address owner = ownerOf(tokenId);
+        require(msg.sender == owner || isApprovedForAll[owner][msg.sender], "erc721.not_authorized");
+        getApproved[tokenId] = to;
+        emit Approval(owner, to, tokenId);
+    }
+
+    function setApprovalForAll(address operator, bool approved) external {
+        isApprovedForAll[msg.sender][operator] = approved;
+        emit ApprovalForAll(msg.sender, operator, approved);
+    }
+
+    function transferFrom(address from, address to, uint256 tokenId) public {
+        _transfer(from, to, tokenId);
+    }
+
+    function safeTransferFrom(address from, address to, uint256 tokenId) external {
+        safeTransferFrom(from, to, tokenId, "");
+    }
+
+    function safeTransferFrom(address from, address to, uint256 tokenId, bytes memory data) public {
+        _transfer(from, to, tokenId);
+        if (to.code.length != 0) {
+            require(
+                IERC721Receiver(to).onERC721Received(msg.sender, from, tokenId, data)
+                    == IERC721Receiver.onERC721Received.selector,
+                "erc721.receiver_rejected"
+            );
+        }
+    }
+
+    function mint(address to, uint256 tokenId) external onlyFactory {
+        require(to != address(0), "arg.to_zero");
+        require(_owners[tokenId] == address(0), "erc721.token_exists");
+        _owners[tokenId] = to;
+        _balances[to] += 1;
+        totalSupply += 1;
+        emit Transfer(address(0), to, tokenId);
+    }
+
+    function burn(uint256) external pure {
+        revert("disabled.use_factory");
+    }
+
+    function burnFromByFactory(address from, uint256 tokenId) external onlyFactory returns (bool) {
+        address owner = ownerOf(tokenId);
+        require(owner == from, "erc721.incorrect_owner");
+        require(
+            getApproved[tokenId] == msg.sender || isApprovedForAll[owner][msg.sender],
+            "erc721.insufficient_approval"
+        );
+        delete getApproved[tokenId];
+        delete _owners[tokenId];
+        _balances[owner] -= 1;
+        totalSupply -= 1;
+        emit Transfer(owner, address(0), tokenId);
+        return true;
+    }
+
+    function _transfer(address from, address to, uint256 tokenId) private {
+        require(to != address(0), "arg.to_zero");
+        address owner = ownerOf(tokenId);
+        require(owner == from, "erc721.incorrect_owner");
+        require(
+            msg.sender == owner || getApproved[tokenId] == msg.sender || isApprovedForAll[owner][msg.sender],
+            "erc721.not_authorized"
+        );
+        delete getApproved[tokenId];
+        _balances[from] -= 1;
+        _balances[to] += 1;
+        _owners[tokenId] = to;
+        emit Transfer(from, to, tokenId);
+    }
+}
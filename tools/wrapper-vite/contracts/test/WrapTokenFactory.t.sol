@@ -3,6 +3,7 @@ pragma solidity ^0.8.20;
 
 import "../WrapTokenFactory.sol";
 import "../WrappedAssetToken.sol";
+import "../WrappedNftToken.sol";
 
 contract ExternalMinter {
     function callMint(
@@ -31,6 +32,16 @@ contract TokenHolder {
     }
 }
 
+contract NftHolder {
+    function approveNft(WrappedNftToken token, address to, uint256 tokenId) external {
+        token.approve(to, tokenId);
+    }
+
+    function approveAll(WrappedNftToken token, address operator) external {
+        token.setApprovalForAll(operator, true);
+    }
+}
+
 contract WrapTokenFactoryTest {
     function testMintDeploysAtPredictedAddressAndReusesToken() public {
         WrapTokenFactory factory = new WrapTokenFactory(address(this));
@@ -210,6 +221,128 @@ contract WrapTokenFactoryTest {
         require(bytes(wrapped.name()).length == 31, "name_suffix_length");
     }
 
+    function testMintWithMetadataAppliesOnlyOnDeployAndKeepsPredictedAddress() public {
+        WrapTokenFactory factory = new WrapTokenFactory(address(this));
+        bytes memory canisterId = hex"010203040506";
+        address predicted = factory.predictTokenAddress(canisterId, 8);
+
+        address token = factory.mintForAssetWithMetadata(
+            canisterId, 8, address(0xBEEF), 7, WrapTokenFactory.TokenMetadata("Ledger Token", "LTK", "data:,logo", 10_000)
+        );
+        require(token == predicted, "predict_mismatch");
+        WrappedAssetToken wrapped = WrappedAssetToken(token);
+        require(keccak256(bytes(wrapped.name())) == keccak256("Ledger Token"), "name_not_applied");
+        require(keccak256(bytes(wrapped.symbol())) == keccak256("LTK"), "symbol_not_applied");
+        require(keccak256(bytes(wrapped.logo())) == keccak256("data:,logo"), "logo_not_applied");
+        require(wrapped.ledgerFee() == 10_000, "fee_not_applied");
+
+        factory.mintForAssetWithMetadata(
+            canisterId, 8, address(0xBEEF), 3, WrapTokenFactory.TokenMetadata("Other", "OTH", "", 1)
+        );
+        require(keccak256(bytes(wrapped.symbol())) == keccak256("LTK"), "metadata_overwritten_on_mint");
+        require(wrapped.balanceOf(address(0xBEEF)) == 10, "second_mint_balance");
+    }
+
+    function testSetAssetMetadataKeepsFieldsLeftEmpty() public {
+        WrapTokenFactory factory = new WrapTokenFactory(address(this));
+        bytes memory canisterId = hex"0102";
+        (bool missingOk,) = address(factory).call(
+            abi.encodeWithSelector(
+                WrapTokenFactory.setAssetMetadata.selector,
+                canisterId,
+                WrapTokenFactory.TokenMetadata("Name", "SYM", "", 0)
+            )
+        );
+        require(!missingOk, "expected_not_deployed_revert");
+
+        address token = factory.mintForAsset(canisterId, 8, address(0xBEEF), 1);
+        WrappedAssetToken wrapped = WrappedAssetToken(token);
+        string memory defaultName = wrapped.name();
+        factory.setAssetMetadata(canisterId, WrapTokenFactory.TokenMetadata("", "SYM", "data:,logo", 5));
+        require(keccak256(bytes(wrapped.name())) == keccak256(bytes(defaultName)), "empty_name_overwrote");
+        require(keccak256(bytes(wrapped.symbol())) == keccak256("SYM"), "symbol_not_synced");
+        require(wrapped.ledgerFee() == 5, "fee_not_synced");
+    }
+
+    function testMintNftDeploysAtPredictedAddressAndReusesCollection() public {
+        WrapTokenFactory factory = new WrapTokenFactory(address(this));
+        bytes memory canisterId = hex"0a0b0c0d";
+        address recipient = address(0xBEEF);
+
+        address predicted = factory.predictNftAddress(canisterId);
+        address token = factory.mintNftForCollection(canisterId, recipient, 42);
+        require(token == predicted, "predict_mismatch");
+        require(factory.getNftAddress(canisterId) == token, "lookup_mismatch");
+        require(factory.getTokenAddress(canisterId) == address(0), "fungible_key_shared");
+
+        WrappedNftToken nft = WrappedNftToken(token);
+        require(nft.ownerOf(42) == recipient, "first_mint_owner");
+        require(nft.balanceOf(recipient) == 1, "first_mint_balance");
+
+        address tokenAgain = factory.mintNftForCollection(canisterId, recipient, 7);
+        require(tokenAgain == token, "token_redeployed");
+        require(nft.balanceOf(recipient) == 2, "second_mint_balance");
+        require(nft.totalSupply() == 2, "second_mint_supply");
+    }
+
+    function testMintNftRejectsDuplicateTokenId() public {
+        WrapTokenFactory factory = new WrapTokenFactory(address(this));
+        bytes memory canisterId = hex"0a0b0c0d";
+        factory.mintNftForCollection(canisterId, address(0xBEEF), 42);
+
+        (bool ok, bytes memory data) = address(factory).call(
+            abi.encodeWithSelector(
+                WrapTokenFactory.mintNftForCollection.selector,
+                canisterId,
+                address(0xCAFE),
+                uint256(42)
+            )
+        );
+
+        require(!ok, "expected_revert");
+        require(_revertReasonEquals(data, "erc721.token_exists"), "unexpected_revert_reason");
+    }
+
+    function testBurnNftRequiresFactoryApproval() public {
+        WrapTokenFactory factory = new WrapTokenFactory(address(this));
+        bytes memory canisterId = hex"0a0b0c0d";
+        NftHolder holder = new NftHolder();
+        address token = factory.mintNftForCollection(canisterId, address(holder), 42);
+        WrappedNftToken nft = WrappedNftToken(token);
+
+        (bool ok, bytes memory data) = address(factory).call(
+            abi.encodeWithSelector(
+                WrapTokenFactory.burnNftFromCollection.selector,
+                canisterId,
+                address(holder),
+                uint256(42)
+            )
+        );
+        require(!ok, "expected_revert");
+        require(_revertReasonEquals(data, "erc721.insufficient_approval"), "unexpected_revert_reason");
+
+        holder.approveNft(nft, address(factory), 42);
+        factory.burnNftFromCollection(canisterId, address(holder), 42);
+        require(nft.balanceOf(address(holder)) == 0, "burn_balance");
+        require(nft.totalSupply() == 0, "burn_supply");
+        require(nft.getApproved(42) == address(0), "burn_approval_cleared");
+    }
+
+    function testBurnNftAcceptsOperatorApproval() public {
+        WrapTokenFactory factory = new WrapTokenFactory(address(this));
+        bytes memory canisterId = hex"0a0b0c0d";
+        NftHolder holder = new NftHolder();
+        address token = factory.mintNftForCollection(canisterId, address(holder), 42);
+        holder.approveAll(WrappedNftToken(token), address(factory));
+
+        factory.burnNftFromCollection(canisterId, address(holder), 42);
+        (bool ok, bytes memory data) = token.call(
+            abi.encodeWithSelector(WrappedNftToken.ownerOf.selector, uint256(42))
+        );
+        require(!ok, "expected_revert");
+        require(_revertReasonEquals(data, "erc721.nonexistent_token"), "unexpected_revert_reason");
+    }
+
     function _revertReasonEquals(bytes memory revertData, string memory expected)
         private
         pure